@@ -0,0 +1,257 @@
+//! This module contains a guard against allocating on the audio thread, with the `alloc-guard`
+//! feature. Allocating can take a lock or a system call, which makes the audio thread miss its
+//! deadline now and then, so it's the kind of bug that is easy to miss in testing. The
+//! `GuardedAllocator` wraps the global allocator and counts the allocations in scopes that
+//! forbid them. `assert_no_alloc` panics when its scope allocated, and a `NoAllocProcessor`
+//! does that for every block a processor processes, while preparing and resetting may still
+//! allocate. Where a function returns a new buffer, there is an `_into` variant that writes
+//! into a buffer that is already allocated, like `Buffer::clone_resized_into`,
+//! `resample::resample_into` and `WavReader::read_into`.
+//! ```rust
+//! use std::alloc::System;
+//!
+//! use rabu::alloc_guard::{count_allocations, GuardedAllocator};
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: GuardedAllocator<System> = GuardedAllocator::new(System);
+//!
+//! let mut buffer = Vec::with_capacity(4);
+//! let (_, allocations) = count_allocations(|| buffer.extend([1, 2, 3, 4]));
+//! assert_eq!(allocations, 0);
+//!
+//! let (_, allocations) = count_allocations(|| buffer.push(5));
+//! assert_eq!(allocations, 1);
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::cell::Cell;
+
+use crate::buffer::Buffer;
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::units::{BufferSize, Channels, Latency, SampleRate};
+
+thread_local! {
+    /// The number of scopes on this thread that forbid allocating.
+    static FORBIDDEN: Cell<usize> = const { Cell::new(0) };
+    /// The number of allocations in those scopes.
+    static VIOLATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Wraps an allocator, and counts the allocations, reallocations and deallocations in scopes
+/// that forbid them. Only allocators that are installed as the `#[global_allocator]` see the
+/// allocations.
+#[derive(Debug, Default)]
+pub struct GuardedAllocator<A> {
+    allocator: A,
+}
+
+impl<A> GuardedAllocator<A> {
+    /// Wraps the allocator, e.g. `std::alloc::System`.
+    pub const fn new(allocator: A) -> Self {
+        Self { allocator }
+    }
+}
+
+/// Counts the call when the thread is in a scope that forbids allocating.
+fn check() {
+    // The thread locals may be gone already while a thread shuts down.
+    let forbidden = FORBIDDEN.try_with(Cell::get).unwrap_or(0);
+    if forbidden > 0 {
+        let _ = VIOLATIONS.try_with(|violations| violations.set(violations.get() + 1));
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for GuardedAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        check();
+        self.allocator.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        check();
+        self.allocator.dealloc(ptr, layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        check();
+        self.allocator.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        check();
+        self.allocator.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Runs the function in a scope that forbids allocating, and gives back its result with the
+/// number of allocations, reallocations and deallocations in it. This is always 0 without a
+/// `GuardedAllocator` as the global allocator.
+pub fn count_allocations<R>(function: impl FnOnce() -> R) -> (R, usize) {
+    let before = VIOLATIONS.with(Cell::get);
+    FORBIDDEN.with(|forbidden| forbidden.set(forbidden.get() + 1));
+    let result = function();
+    FORBIDDEN.with(|forbidden| forbidden.set(forbidden.get() - 1));
+    let after = VIOLATIONS.with(Cell::get);
+    (result, after - before)
+}
+
+/// Runs the function in a scope that forbids allocating, and gives back its result.
+/// This will panic if the function allocated, reallocated or deallocated.
+pub fn assert_no_alloc<R>(function: impl FnOnce() -> R) -> R {
+    let (result, allocations) = count_allocations(function);
+    assert_eq!(
+        allocations, 0,
+        "allocated {allocations} times in a scope that forbids allocating"
+    );
+    result
+}
+
+/// Wraps a processor, and panics when it allocates while it processes a block.
+#[derive(Clone, Debug)]
+pub struct NoAllocProcessor<P> {
+    processor: P,
+}
+
+impl<P: AudioProcessor> NoAllocProcessor<P> {
+    /// Wraps the processor.
+    pub fn new(processor: P) -> Self {
+        Self { processor }
+    }
+
+    /// Returns the processor.
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+
+    /// Returns the processor.
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    /// Gives back the processor.
+    pub fn into_inner(self) -> P {
+        self.processor
+    }
+}
+
+impl<P: AudioProcessor> AudioProcessor for NoAllocProcessor<P> {
+    fn prepare(
+        &mut self,
+        sample_rate: SampleRate,
+        buffer_size: BufferSize,
+        num_channels: Channels,
+    ) {
+        self.processor
+            .prepare(sample_rate, buffer_size, num_channels);
+    }
+
+    /// Processes the block with the wrapped processor.
+    /// This will panic if the wrapped processor allocates.
+    fn process(&mut self, buffer: &mut Buffer<f32>, context: &ProcessContext) {
+        assert_no_alloc(|| self.processor.process(buffer, context));
+    }
+
+    fn latency(&self) -> Latency {
+        self.processor.latency()
+    }
+
+    fn reset(&mut self) {
+        self.processor.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::System;
+
+    use super::*;
+    use crate::biquad::FilterType;
+    use crate::eq::{EqBand, Equalizer};
+    use crate::gate::Gate;
+    use crate::graph::Graph;
+    use crate::resample::{resample_into, ResampleQuality};
+    use crate::units::{Decibels, Frequency, Samples};
+
+    #[global_allocator]
+    static ALLOCATOR: GuardedAllocator<System> = GuardedAllocator::new(System);
+
+    /// Allocates a little every block.
+    struct Allocating;
+
+    impl AudioProcessor for Allocating {
+        fn prepare(&mut self, _: SampleRate, _: BufferSize, _: Channels) {
+            let _ = vec![0.0_f32; 64];
+        }
+
+        fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+            let copy: Vec<f32> = buffer.chan(0).to_vec();
+            buffer.chan_mut(0)[0] = copy[0];
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    fn process(processor: &mut dyn AudioProcessor) {
+        let sample_rate = SampleRate::from(48000);
+        processor.prepare(sample_rate, BufferSize::from(64), Channels::from(2));
+        let mut buffer = Buffer::allocate(Channels::from(2), Samples::from(64));
+        processor.process(&mut buffer, &ProcessContext::new(sample_rate));
+    }
+
+    #[test]
+    #[should_panic(expected = "forbids allocating")]
+    fn allocating_processor_panics() {
+        process(&mut NoAllocProcessor::new(Allocating));
+    }
+
+    #[test]
+    fn nested_scopes_count_once() {
+        let ((_, inner), outer) = count_allocations(|| count_allocations(|| vec![1]));
+
+        assert_eq!(inner, 1);
+        assert_eq!(outer, 1);
+    }
+
+    #[test]
+    fn into_variants_do_not_allocate() {
+        let input = Buffer::<f32>::allocate(Channels::from(2), Samples::from(64));
+        let mut output = Buffer::allocate(Channels::from(2), Samples::from(128));
+
+        assert_no_alloc(|| {
+            input.clone_resized_into(&mut output);
+            let length = resample_into(
+                &input,
+                SampleRate::from(48000),
+                SampleRate::from(96000),
+                ResampleQuality::Linear,
+                &mut output,
+            );
+            assert_eq!(length, Samples::from(128));
+        });
+    }
+
+    #[test]
+    fn graph_processes_without_allocating() {
+        let sample_rate = SampleRate::from(48000);
+        let mut graph = Graph::new();
+        let gate = graph.add_node(
+            Gate::new(Decibels::from(-60.0), sample_rate),
+            Channels::from(2),
+        );
+        let band = EqBand::new(
+            FilterType::Peak,
+            Frequency::from(1000.0),
+            1.0,
+            Decibels::from(3.0),
+        );
+        let eq = graph.add_node(
+            Equalizer::new(sample_rate, Channels::from(2), vec![band]),
+            Channels::from(2),
+        );
+        graph.connect_all(Graph::INPUT, gate);
+        graph.connect_all(gate, eq);
+        graph.connect_all(eq, Graph::OUTPUT);
+
+        process(&mut NoAllocProcessor::new(graph));
+    }
+}
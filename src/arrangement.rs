@@ -0,0 +1,262 @@
+//! This module contains an arrangement: the clips on a timeline, each with the time section it
+//! spans. The clips are indexed by their start, together with the latest end up to every clip,
+//! so finding the clips a block of audio overlaps only goes over the clips from the first one
+//! that may still be playing, up to the block's end. Both are found with a binary search, but
+//! one long clip early on, like a pad under the whole song, still makes it go over every clip
+//! from that one on.
+//! ```rust
+//! use rabu::arrangement::Arrangement;
+//! use rabu::units::{Duration, TimePoint, TimeSection};
+//!
+//! let section = |start: f64, duration: f64| TimeSection {
+//!     start: TimePoint::from_secs_f64(start),
+//!     duration: Duration::from_secs_f64(duration),
+//! };
+//!
+//! let mut arrangement = Arrangement::new();
+//! arrangement.insert("intro", section(0.0, 8.0));
+//! arrangement.insert("drums", section(4.0, 16.0));
+//! arrangement.insert("outro", section(20.0, 4.0));
+//!
+//! let block = section(7.9, 0.2);
+//! let clips: Vec<_> = arrangement.overlapping(block).map(|(id, _)| id).collect();
+//! assert_eq!(clips, ["intro", "drums"]);
+//!
+//! arrangement.move_to(&"outro", TimePoint::from_secs_f64(6.0));
+//! assert_eq!(arrangement.overlapping(block).count(), 3);
+//! ```
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use partial_min_max::max;
+
+use crate::units::{TimePoint, TimeSection};
+
+/// The clips on a timeline, which can be looked up by id and by the time they overlap.
+#[derive(Clone, Debug)]
+pub struct Arrangement<Id> {
+    sections: HashMap<Id, TimeSection>,
+    /// The clips, ordered by their start.
+    clips: Vec<(TimeSection, Id)>,
+    /// The latest end of the clips up to and including the clip at the same index.
+    latest_ends: Vec<TimePoint>,
+}
+
+impl<Id> Default for Arrangement<Id> {
+    fn default() -> Self {
+        Self {
+            sections: HashMap::new(),
+            clips: Vec::new(),
+            latest_ends: Vec::new(),
+        }
+    }
+}
+
+impl<Id> Arrangement<Id>
+where
+    Id: Copy + Eq + Hash,
+{
+    /// Creates an empty arrangement.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of clips.
+    pub fn len(&self) -> usize {
+        self.clips.len()
+    }
+
+    /// Tells whether there are no clips.
+    pub fn is_empty(&self) -> bool {
+        self.clips.is_empty()
+    }
+
+    /// Returns the section of the clip, if it's in the arrangement.
+    pub fn section(&self, id: &Id) -> Option<TimeSection> {
+        self.sections.get(id).copied()
+    }
+
+    /// Returns the end of the last clip, or `None` when there are no clips.
+    pub fn end(&self) -> Option<TimePoint> {
+        self.latest_ends.last().copied()
+    }
+
+    /// Adds the clip, or sets its section when it's already in the arrangement. Gives back the
+    /// section it had before.
+    pub fn insert(&mut self, id: Id, section: TimeSection) -> Option<TimeSection> {
+        let previous = self.remove(&id);
+        let index = self
+            .clips
+            .partition_point(|(existing, _)| existing.start <= section.start);
+        self.clips.insert(index, (section, id));
+        self.sections.insert(id, section);
+        self.update_latest_ends(index);
+        previous
+    }
+
+    /// Removes the clip, and gives back its section if it was in the arrangement.
+    pub fn remove(&mut self, id: &Id) -> Option<TimeSection> {
+        let section = self.sections.remove(id)?;
+        let first = self
+            .clips
+            .partition_point(|(existing, _)| existing.start < section.start);
+        let index = first
+            + self.clips[first..]
+                .iter()
+                .position(|(_, existing)| existing == id)?;
+        self.clips.remove(index);
+        self.update_latest_ends(index);
+        Some(section)
+    }
+
+    /// Moves the clip to start at the time point, keeping its duration.
+    /// Gives back `false` when the clip isn't in the arrangement.
+    pub fn move_to(&mut self, id: &Id, start: TimePoint) -> bool {
+        match self.section(id) {
+            Some(section) => {
+                self.insert(*id, TimeSection { start, ..section });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Iterates over the clips in the order of their start.
+    pub fn iter(&self) -> impl Iterator<Item = (Id, TimeSection)> + '_ {
+        self.clips.iter().map(|(section, id)| (*id, *section))
+    }
+
+    /// Iterates over the clips that overlap the range, in the order of their start.
+    /// Clips that only touch the range don't overlap it. This goes over the clips from the first
+    /// one that ends after the range starts, so in the worst case, with a long clip at the
+    /// start, over all clips that start before the range ends.
+    pub fn overlapping(&self, range: TimeSection) -> impl Iterator<Item = (Id, TimeSection)> + '_ {
+        // The latest ends only go up, so the clips before this one all end before the range.
+        let first = self.latest_ends.partition_point(|end| *end <= range.start);
+        let last = self
+            .clips
+            .partition_point(|(section, _)| section.start < range.end());
+        self.clips[first..last.max(first)]
+            .iter()
+            .filter(move |(section, _)| section.get_overlap(range).is_some())
+            .map(|(section, id)| (*id, *section))
+    }
+
+    /// Recomputes the latest ends from the index on.
+    fn update_latest_ends(&mut self, index: usize) {
+        self.latest_ends.truncate(index);
+        for (section, _) in &self.clips[index..] {
+            let end = match self.latest_ends.last() {
+                Some(latest) => max(*latest, section.end()),
+                None => section.end(),
+            };
+            self.latest_ends.push(end);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Duration;
+
+    fn section(start: f64, duration: f64) -> TimeSection {
+        TimeSection {
+            start: TimePoint::from_secs_f64(start),
+            duration: Duration::from_secs_f64(duration),
+        }
+    }
+
+    fn overlapping(arrangement: &Arrangement<u32>, start: f64, duration: f64) -> Vec<u32> {
+        arrangement
+            .overlapping(section(start, duration))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_clips_in_a_block() {
+        let mut arrangement = Arrangement::new();
+        arrangement.insert(1, section(0.0, 100.0));
+        arrangement.insert(2, section(10.0, 1.0));
+        arrangement.insert(3, section(12.0, 1.0));
+        arrangement.insert(4, section(11.5, 0.1));
+
+        assert_eq!(overlapping(&arrangement, 10.5, 1.25), [1, 2, 4]);
+        assert_eq!(overlapping(&arrangement, 11.0, 0.5), [1]);
+        assert!(overlapping(&arrangement, 150.0, 1.0).is_empty());
+        assert_eq!(arrangement.end(), Some(TimePoint::from_secs_f64(100.0)));
+    }
+
+    #[test]
+    fn clips_that_touch_the_block_are_left_out() {
+        let mut arrangement = Arrangement::new();
+        arrangement.insert(1, section(0.0, 1.0));
+        arrangement.insert(2, section(2.0, 1.0));
+
+        assert!(overlapping(&arrangement, 1.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn inserting_an_existing_clip_replaces_it() {
+        let mut arrangement = Arrangement::new();
+        arrangement.insert(1, section(0.0, 1.0));
+
+        let previous = arrangement.insert(1, section(5.0, 1.0));
+
+        assert_eq!(previous, Some(section(0.0, 1.0)));
+        assert_eq!(arrangement.len(), 1);
+        assert!(overlapping(&arrangement, 0.0, 1.0).is_empty());
+        assert_eq!(overlapping(&arrangement, 5.0, 1.0), [1]);
+    }
+
+    #[test]
+    fn removes_and_moves_clips() {
+        let mut arrangement = Arrangement::new();
+        arrangement.insert(1, section(0.0, 10.0));
+        arrangement.insert(2, section(0.0, 1.0));
+        arrangement.insert(3, section(3.0, 1.0));
+
+        assert_eq!(arrangement.remove(&1), Some(section(0.0, 10.0)));
+        assert_eq!(arrangement.remove(&1), None);
+        assert!(arrangement.move_to(&2, TimePoint::from_secs_f64(5.0)));
+        assert!(!arrangement.move_to(&1, TimePoint::from_secs_f64(5.0)));
+
+        assert!(overlapping(&arrangement, 0.0, 3.0).is_empty());
+        assert_eq!(overlapping(&arrangement, 0.0, 10.0), [3, 2]);
+        assert_eq!(arrangement.section(&2), Some(section(5.0, 1.0)));
+        assert_eq!(arrangement.end(), Some(TimePoint::from_secs_f64(6.0)));
+
+        arrangement.remove(&2);
+        arrangement.remove(&3);
+        assert!(arrangement.is_empty());
+        assert_eq!(arrangement.end(), None);
+    }
+
+    #[test]
+    fn matches_a_linear_search() {
+        let mut arrangement = Arrangement::new();
+        for id in 0..200_u32 {
+            let start = (id * 37 % 101) as f64;
+            let duration = (id * 13 % 17) as f64 + 0.5;
+            arrangement.insert(id, section(start, duration));
+        }
+        for id in (0..200).step_by(3) {
+            arrangement.remove(&id);
+        }
+
+        for start in 0..120 {
+            let block = section(start as f64 + 0.25, 1.0);
+            let mut found = overlapping(&arrangement, block.start.as_secs_f64(), 1.0);
+            let mut expected: Vec<_> = arrangement
+                .iter()
+                .filter(|(_, section)| section.get_overlap(block).is_some())
+                .map(|(id, _)| id)
+                .collect();
+            found.sort();
+            expected.sort();
+            assert_eq!(found, expected);
+        }
+    }
+}
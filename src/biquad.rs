@@ -16,9 +16,19 @@
 //! let output_sample = filter.process(input_sample);
 //! ```
 
-use crate::units::{Frequency, SampleRate};
+use std::ops::Range;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::buffer::Buffer;
+use crate::bypass::Bypass;
+use crate::processor::SampleProcessor;
+use crate::sample::{Float, Sample};
+use crate::units::{Channels, Decibels, Frequency, SampleRate, Samples};
 
 /// The coefficients for a `BiquadFilter`.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct BiquadCoefficients {
     pub a1: f64,
     pub a2: f64,
@@ -27,39 +37,316 @@ pub struct BiquadCoefficients {
     pub b2: f64,
 }
 
+impl BiquadCoefficients {
+    /// Evaluates the magnitude response of the filter at the given frequency:
+    /// ```
+    /// use rabu::biquad::peak_coefficients;
+    /// use rabu::units::{Decibels, Frequency, SampleRate};
+    ///
+    /// let sample_rate = SampleRate::from(48000);
+    /// let frequency = Frequency::from(1000.0);
+    /// let coefficients = peak_coefficients(sample_rate, frequency, 1.0, Decibels::from(6.0));
+    ///
+    /// let gain = coefficients.magnitude_at(frequency, sample_rate);
+    ///
+    /// assert!((gain.as_f64() - 6.0).abs() < 1e-9);
+    /// ```
+    pub fn magnitude_at(&self, frequency: Frequency, sample_rate: SampleRate) -> Decibels {
+        let w = 2.0 * std::f64::consts::PI * frequency.as_f64() / sample_rate.as_f64();
+        let (cos_w, sin_w) = (w.cos(), w.sin());
+        let (cos_2w, sin_2w) = ((2.0 * w).cos(), (2.0 * w).sin());
+
+        let num_re = self.b0 + self.b1 * cos_w + self.b2 * cos_2w;
+        let num_im = -self.b1 * sin_w - self.b2 * sin_2w;
+        let den_re = 1.0 + self.a1 * cos_w + self.a2 * cos_2w;
+        let den_im = -self.a1 * sin_w - self.a2 * sin_2w;
+
+        Decibels::from_gain(num_re.hypot(num_im) / den_re.hypot(den_im))
+    }
+}
+
+/// The coefficients of a biquad in the float type its state is kept in.
+#[derive(Copy, Clone, Debug)]
+struct Coefficients<F> {
+    a1: F,
+    a2: F,
+    b0: F,
+    b1: F,
+    b2: F,
+}
+
+impl<F: Float> From<BiquadCoefficients> for Coefficients<F> {
+    fn from(coefficients: BiquadCoefficients) -> Self {
+        Self {
+            a1: F::from_f64(coefficients.a1),
+            a2: F::from_f64(coefficients.a2),
+            b0: F::from_f64(coefficients.b0),
+            b1: F::from_f64(coefficients.b1),
+            b2: F::from_f64(coefficients.b2),
+        }
+    }
+}
+
 /// A biquad filter used to filter audio signals.
-pub struct BiquadFilter {
-    coefficients: BiquadCoefficients,
-    x1: f64,
-    x2: f64,
-    y1: f64,
-    y2: f64,
+/// Its state is kept in `F`, which is `f64` unless created with `with_precision`.
+#[derive(Clone, Debug)]
+pub struct BiquadFilter<F = f64> {
+    coefficients: Coefficients<F>,
+    x1: F,
+    x2: F,
+    y1: F,
+    y2: F,
 }
 
 impl BiquadFilter {
     /// Creates a new biquad filter using the provided coefficients.
     pub fn new(coefficients: BiquadCoefficients) -> Self {
+        Self::with_precision(coefficients)
+    }
+}
+
+impl<F: Float> BiquadFilter<F> {
+    /// Creates a new biquad filter using the provided coefficients, which keeps its state in `F`.
+    pub fn with_precision(coefficients: BiquadCoefficients) -> Self {
         Self {
-            coefficients,
-            x1: 0.0,
-            x2: 0.0,
-            y1: 0.0,
-            y2: 0.0,
+            coefficients: Coefficients::from(coefficients),
+            x1: F::default(),
+            x2: F::default(),
+            y1: F::default(),
+            y2: F::default(),
         }
     }
 
     /// Sets the coefficients to the provided ones.
     pub fn set_coefficients(&mut self, coefficients: BiquadCoefficients) {
-        self.coefficients = coefficients;
+        self.coefficients = Coefficients::from(coefficients);
+    }
+
+    /// Clears the internal state of the filter, as if it never processed any audio.
+    pub fn reset(&mut self) {
+        self.x1 = F::default();
+        self.x2 = F::default();
+        self.y1 = F::default();
+        self.y2 = F::default();
     }
 
     /// Processes one sample of input audio and produces the filter output sample.
     pub fn process(&mut self, input: f64) -> f64 {
-        let output = self.coefficients.b0 * input
-            + self.coefficients.b1 * self.x1
-            + self.coefficients.b2 * self.x2
-            - self.coefficients.a1 * self.y1
-            - self.coefficients.a2 * self.y2;
+        self.process_float(F::from_f64(input)).to_f64()
+    }
+
+    /// Processes one sample of input audio in the float type of the state, without converting it.
+    pub fn process_float(&mut self, input: F) -> F {
+        let c = &self.coefficients;
+        let output =
+            c.b0 * input + c.b1 * self.x1 + c.b2 * self.x2 - c.a1 * self.y1 - c.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = input;
+        self.y2 = self.y1;
+        self.y1 = output;
+        output
+    }
+}
+
+impl<F: Float> SampleProcessor for BiquadFilter<F> {
+    fn process(&mut self, input: f64) -> f64 {
+        BiquadFilter::process(self, input)
+    }
+}
+
+/// Applies the same biquad filter to every channel of a `Buffer`, keeping separate state
+/// for each channel. The channels are processed in parallel lanes of 8 or 4 channels at a time,
+/// which lets the compiler use SIMD instructions for multichannel audio.
+/// Like `BiquadFilter`, its state is kept in `F`, which is `f64` by default.
+/// It can be bypassed without clicks, crossfading over 128 samples by default:
+/// ```
+/// use rabu::biquad::{low_pass_coefficients, MultiBiquad};
+/// use rabu::buffer::Buffer;
+/// use rabu::units::{Channels, Frequency, SampleRate, Samples};
+///
+/// let coefficients = low_pass_coefficients(SampleRate::from(44100), Frequency::from(1000.0));
+/// let mut filter = MultiBiquad::new(coefficients, Channels::from(2));
+/// let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+///
+/// filter.process(&mut buffer);
+/// filter.set_bypassed(true);
+/// filter.process(&mut buffer);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MultiBiquad<F = f64> {
+    coefficients: Coefficients<F>,
+    lanes: Vec<LaneGroup<F>>,
+    num_channels: Channels,
+    bypass: Bypass,
+}
+
+impl MultiBiquad {
+    /// Creates a new filter for the given number of channels using the provided coefficients.
+    pub fn new(coefficients: BiquadCoefficients, num_channels: Channels) -> Self {
+        Self::with_precision(coefficients, num_channels)
+    }
+}
+
+impl<F: Float> MultiBiquad<F> {
+    /// Creates a new filter for the given number of channels using the provided coefficients,
+    /// which keeps its state in `F`.
+    pub fn with_precision(coefficients: BiquadCoefficients, num_channels: Channels) -> Self {
+        let mut lanes = Vec::new();
+        let mut remaining = num_channels.as_usize();
+        while remaining > 0 {
+            if remaining >= 8 {
+                lanes.push(LaneGroup::Eight(LaneState::default()));
+                remaining -= 8;
+            } else {
+                lanes.push(LaneGroup::Four(LaneState::default()));
+                remaining = remaining.saturating_sub(4);
+            }
+        }
+
+        Self {
+            coefficients: Coefficients::from(coefficients),
+            lanes,
+            num_channels,
+            bypass: Bypass::new(Samples::from(128)),
+        }
+    }
+
+    /// Returns the number of channels this filter processes.
+    pub fn num_channels(&self) -> Channels {
+        self.num_channels
+    }
+
+    /// Sets the coefficients of all channels to the provided ones.
+    pub fn set_coefficients(&mut self, coefficients: BiquadCoefficients) {
+        self.coefficients = Coefficients::from(coefficients);
+    }
+
+    /// Clears the internal state of all channels and finishes any ongoing bypass crossfade.
+    pub fn reset(&mut self) {
+        for group in &mut self.lanes {
+            match group {
+                LaneGroup::Four(state) => *state = LaneState::default(),
+                LaneGroup::Eight(state) => *state = LaneState::default(),
+            }
+        }
+        self.bypass.skip_ramp();
+    }
+
+    /// Sets whether the filter should be bypassed. The change is crossfaded over the bypass ramp.
+    pub fn set_bypassed(&mut self, bypassed: bool) {
+        self.bypass.set_bypassed(bypassed);
+    }
+
+    /// Returns whether bypass is turned on.
+    pub fn is_bypassed(&self) -> bool {
+        self.bypass.is_bypassed()
+    }
+
+    /// Sets the length of the crossfade that is used when toggling bypass.
+    pub fn set_bypass_ramp(&mut self, ramp_length: Samples) {
+        self.bypass.set_ramp_length(ramp_length);
+    }
+
+    /// Filters the given buffer in place.
+    /// This will panic if the buffer doesn't have the same number of channels as the filter.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+
+        if self.bypass.is_fully_bypassed() {
+            return;
+        }
+
+        let num_channels = self.num_channels.as_usize();
+        let num_samples = buffer.num_samples().as_usize();
+        let data = buffer.data_mut();
+        let start_bypass = self.bypass;
+        let mut first_channel = 0;
+
+        for group in &mut self.lanes {
+            let mut bypass = start_bypass;
+            let channels = first_channel..num_channels;
+            match group {
+                LaneGroup::Four(state) => {
+                    state.process(&self.coefficients, data, num_samples, channels, &mut bypass);
+                    first_channel += 4;
+                }
+                LaneGroup::Eight(state) => {
+                    state.process(&self.coefficients, data, num_samples, channels, &mut bypass);
+                    first_channel += 8;
+                }
+            }
+            self.bypass = bypass;
+        }
+    }
+}
+
+/// A group of channels of a `MultiBiquad` that are processed together.
+#[derive(Clone, Debug)]
+enum LaneGroup<F> {
+    Four(LaneState<F, 4>),
+    Eight(LaneState<F, 8>),
+}
+
+/// The state of `N` biquads sharing the same coefficients, stored per lane so that the
+/// processing of all lanes can be vectorized.
+#[derive(Copy, Clone, Debug)]
+struct LaneState<F, const N: usize> {
+    x1: [F; N],
+    x2: [F; N],
+    y1: [F; N],
+    y2: [F; N],
+}
+
+impl<F: Float, const N: usize> Default for LaneState<F, N> {
+    fn default() -> Self {
+        Self {
+            x1: [F::default(); N],
+            x2: [F::default(); N],
+            y1: [F::default(); N],
+            y2: [F::default(); N],
+        }
+    }
+}
+
+impl<F: Float, const N: usize> LaneState<F, N> {
+    /// Processes (up to) `N` channels of the given non-interleaved data, starting at the first
+    /// channel of the given range. Lanes without a channel are processed on silence.
+    fn process<T: Sample>(
+        &mut self,
+        coefficients: &Coefficients<F>,
+        data: &mut [T],
+        num_samples: usize,
+        channels: Range<usize>,
+        bypass: &mut Bypass,
+    ) {
+        let num_lanes = channels.len().min(N);
+        let offset = channels.start * num_samples;
+
+        for index in 0..num_samples {
+            let mut input = [F::default(); N];
+            for (lane, value) in input.iter_mut().enumerate().take(num_lanes) {
+                *value = F::from_f64(data[offset + lane * num_samples + index].to_f64());
+            }
+
+            let output = self.tick(coefficients, input);
+
+            let (dry_gain, wet_gain) = bypass.next_gains();
+            let (dry_gain, wet_gain) = (F::from_f64(dry_gain), F::from_f64(wet_gain));
+            for lane in 0..num_lanes {
+                let mixed = input[lane] * dry_gain + output[lane] * wet_gain;
+                data[offset + lane * num_samples + index] = T::from_f64(mixed.to_f64());
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn tick(&mut self, c: &Coefficients<F>, input: [F; N]) -> [F; N] {
+        let mut output = [F::default(); N];
+        for lane in 0..N {
+            output[lane] = c.b0 * input[lane] + c.b1 * self.x1[lane] + c.b2 * self.x2[lane]
+                - c.a1 * self.y1[lane]
+                - c.a2 * self.y2[lane];
+        }
         self.x2 = self.x1;
         self.x1 = input;
         self.y2 = self.y1;
@@ -149,3 +436,412 @@ pub fn high_pass_coefficients(
         a2: a2 / a0,
     }
 }
+
+/// Creates the biquad coefficients for a low pass filter with a resonance (Q),
+/// given a sample rate and a cutoff frequency.
+pub fn resonant_low_pass_coefficients(
+    sample_rate: SampleRate,
+    cutoff_frequency: Frequency,
+    q: f64,
+) -> BiquadCoefficients {
+    let (cos_w0, alpha) = cos_w0_and_alpha(sample_rate, cutoff_frequency, q);
+
+    normalize(
+        (1.0 - cos_w0) / 2.0,
+        1.0 - cos_w0,
+        (1.0 - cos_w0) / 2.0,
+        1.0 + alpha,
+        -2.0 * cos_w0,
+        1.0 - alpha,
+    )
+}
+
+/// Creates the biquad coefficients for a high pass filter with a resonance (Q),
+/// given a sample rate and a cutoff frequency.
+pub fn resonant_high_pass_coefficients(
+    sample_rate: SampleRate,
+    cutoff_frequency: Frequency,
+    q: f64,
+) -> BiquadCoefficients {
+    let (cos_w0, alpha) = cos_w0_and_alpha(sample_rate, cutoff_frequency, q);
+
+    normalize(
+        (1.0 + cos_w0) / 2.0,
+        -(1.0 + cos_w0),
+        (1.0 + cos_w0) / 2.0,
+        1.0 + alpha,
+        -2.0 * cos_w0,
+        1.0 - alpha,
+    )
+}
+
+/// Creates the biquad coefficients for a band pass filter with a peak gain of 0 dB at the
+/// center frequency. A higher Q gives a narrower band.
+pub fn resonant_band_pass_coefficients(
+    sample_rate: SampleRate,
+    center_frequency: Frequency,
+    q: f64,
+) -> BiquadCoefficients {
+    let (cos_w0, alpha) = cos_w0_and_alpha(sample_rate, center_frequency, q);
+
+    normalize(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha)
+}
+
+/// Creates the biquad coefficients for a notch filter that removes the center frequency.
+/// A higher Q gives a narrower notch.
+pub fn notch_coefficients(
+    sample_rate: SampleRate,
+    center_frequency: Frequency,
+    q: f64,
+) -> BiquadCoefficients {
+    let (cos_w0, alpha) = cos_w0_and_alpha(sample_rate, center_frequency, q);
+
+    normalize(
+        1.0,
+        -2.0 * cos_w0,
+        1.0,
+        1.0 + alpha,
+        -2.0 * cos_w0,
+        1.0 - alpha,
+    )
+}
+
+/// Creates the biquad coefficients for a peaking (bell) filter that boosts or cuts
+/// the center frequency by the given gain.
+pub fn peak_coefficients(
+    sample_rate: SampleRate,
+    center_frequency: Frequency,
+    q: f64,
+    gain: Decibels,
+) -> BiquadCoefficients {
+    let (cos_w0, alpha) = cos_w0_and_alpha(sample_rate, center_frequency, q);
+    let a = shelf_amplitude(gain);
+
+    normalize(
+        1.0 + alpha * a,
+        -2.0 * cos_w0,
+        1.0 - alpha * a,
+        1.0 + alpha / a,
+        -2.0 * cos_w0,
+        1.0 - alpha / a,
+    )
+}
+
+/// Creates the biquad coefficients for a low shelf filter that boosts or cuts everything
+/// below the corner frequency by the given gain.
+pub fn low_shelf_coefficients(
+    sample_rate: SampleRate,
+    corner_frequency: Frequency,
+    q: f64,
+    gain: Decibels,
+) -> BiquadCoefficients {
+    let (cos_w0, alpha) = cos_w0_and_alpha(sample_rate, corner_frequency, q);
+    let a = shelf_amplitude(gain);
+    let two_sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+
+    normalize(
+        a * ((a + 1.0) - (a - 1.0) * cos_w0 + two_sqrt_a_alpha),
+        2.0 * a * ((a - 1.0) - (a + 1.0) * cos_w0),
+        a * ((a + 1.0) - (a - 1.0) * cos_w0 - two_sqrt_a_alpha),
+        (a + 1.0) + (a - 1.0) * cos_w0 + two_sqrt_a_alpha,
+        -2.0 * ((a - 1.0) + (a + 1.0) * cos_w0),
+        (a + 1.0) + (a - 1.0) * cos_w0 - two_sqrt_a_alpha,
+    )
+}
+
+/// Creates the biquad coefficients for a high shelf filter that boosts or cuts everything
+/// above the corner frequency by the given gain.
+pub fn high_shelf_coefficients(
+    sample_rate: SampleRate,
+    corner_frequency: Frequency,
+    q: f64,
+    gain: Decibels,
+) -> BiquadCoefficients {
+    let (cos_w0, alpha) = cos_w0_and_alpha(sample_rate, corner_frequency, q);
+    let a = shelf_amplitude(gain);
+    let two_sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+
+    normalize(
+        a * ((a + 1.0) + (a - 1.0) * cos_w0 + two_sqrt_a_alpha),
+        -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w0),
+        a * ((a + 1.0) + (a - 1.0) * cos_w0 - two_sqrt_a_alpha),
+        (a + 1.0) - (a - 1.0) * cos_w0 + two_sqrt_a_alpha,
+        2.0 * ((a - 1.0) - (a + 1.0) * cos_w0),
+        (a + 1.0) - (a - 1.0) * cos_w0 - two_sqrt_a_alpha,
+    )
+}
+
+/// Creates the biquad coefficients for an all pass filter, which leaves the magnitude untouched
+/// but shifts the phase around the center frequency. A higher Q gives a steeper phase transition.
+pub fn all_pass_coefficients(
+    sample_rate: SampleRate,
+    center_frequency: Frequency,
+    q: f64,
+) -> BiquadCoefficients {
+    let (cos_w0, alpha) = cos_w0_and_alpha(sample_rate, center_frequency, q);
+
+    normalize(
+        1.0 - alpha,
+        -2.0 * cos_w0,
+        1.0 + alpha,
+        1.0 + alpha,
+        -2.0 * cos_w0,
+        1.0 - alpha,
+    )
+}
+
+/// The basic filter types that can be designed with `design`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FilterType {
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+    Peak,
+    LowShelf,
+    HighShelf,
+    AllPass,
+}
+
+/// Creates the biquad coefficients for any of the basic filter types. This is useful when the
+/// filter type is a parameter itself. The gain is only used by the peak and shelf filters:
+/// ```
+/// use rabu::biquad::{design, FilterType};
+/// use rabu::units::{Decibels, Frequency, SampleRate};
+///
+/// let sample_rate = SampleRate::from(48000);
+/// let frequency = Frequency::from(500.0);
+///
+/// let coefficients = design(FilterType::LowShelf, sample_rate, frequency, 0.7, Decibels::from(4.0));
+///
+/// let gain = coefficients.magnitude_at(Frequency::from(10.0), sample_rate);
+/// assert!((gain.as_f64() - 4.0).abs() < 0.01);
+/// ```
+pub fn design(
+    filter_type: FilterType,
+    sample_rate: SampleRate,
+    frequency: Frequency,
+    q: f64,
+    gain: Decibels,
+) -> BiquadCoefficients {
+    match filter_type {
+        FilterType::LowPass => resonant_low_pass_coefficients(sample_rate, frequency, q),
+        FilterType::HighPass => resonant_high_pass_coefficients(sample_rate, frequency, q),
+        FilterType::BandPass => resonant_band_pass_coefficients(sample_rate, frequency, q),
+        FilterType::Notch => notch_coefficients(sample_rate, frequency, q),
+        FilterType::Peak => peak_coefficients(sample_rate, frequency, q, gain),
+        FilterType::LowShelf => low_shelf_coefficients(sample_rate, frequency, q, gain),
+        FilterType::HighShelf => high_shelf_coefficients(sample_rate, frequency, q, gain),
+        FilterType::AllPass => all_pass_coefficients(sample_rate, frequency, q),
+    }
+}
+
+/// A second-order section of an analog (s-domain) filter, with the polynomial coefficients
+/// ordered from the highest power of `s` to the lowest:
+/// `H(s) = (n[0]·s² + n[1]·s + n[2]) / (d[0]·s² + d[1]·s + d[2])`.
+/// The coefficients are in terms of angular frequency (rad/s), so a pole at 1 kHz is at `2π·1000`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AnalogSection {
+    pub numerator: [f64; 3],
+    pub denominator: [f64; 3],
+}
+
+/// Converts an analog second-order section into biquad coefficients using the bilinear
+/// transform. When a pre-warp frequency is given, the digital filter matches the analog one
+/// exactly at that frequency, which compensates for the frequency warping of the transform:
+/// ```
+/// use rabu::biquad::{bilinear_transform, AnalogSection};
+/// use rabu::units::{Frequency, SampleRate};
+///
+/// // Analog first-order low pass at 1 kHz: H(s) = wc / (s + wc)
+/// let cutoff = Frequency::from(1000.0);
+/// let wc = 2.0 * std::f64::consts::PI * cutoff.as_f64();
+/// let section = AnalogSection {
+///     numerator: [0.0, 0.0, wc],
+///     denominator: [0.0, 1.0, wc],
+/// };
+///
+/// let sample_rate = SampleRate::from(48000);
+/// let coefficients = bilinear_transform(section, sample_rate, Some(cutoff));
+///
+/// let gain = coefficients.magnitude_at(cutoff, sample_rate);
+/// assert!((gain.as_f64() + 3.0103).abs() < 1e-3);
+/// ```
+pub fn bilinear_transform(
+    section: AnalogSection,
+    sample_rate: SampleRate,
+    prewarp_frequency: Option<Frequency>,
+) -> BiquadCoefficients {
+    let k = match prewarp_frequency {
+        Some(frequency) => {
+            let w = 2.0 * std::f64::consts::PI * frequency.as_f64();
+            w / (w / (2.0 * sample_rate.as_f64())).tan()
+        }
+        None => 2.0 * sample_rate.as_f64(),
+    };
+
+    let transform = |[s2, s1, s0]: [f64; 3]| {
+        (
+            s2 * k * k + s1 * k + s0,
+            2.0 * (s0 - s2 * k * k),
+            s2 * k * k - s1 * k + s0,
+        )
+    };
+    let (b0, b1, b2) = transform(section.numerator);
+    let (a0, a1, a2) = transform(section.denominator);
+
+    normalize(b0, b1, b2, a0, a1, a2)
+}
+
+fn cos_w0_and_alpha(sample_rate: SampleRate, frequency: Frequency, q: f64) -> (f64, f64) {
+    let w0 = 2.0 * std::f64::consts::PI * frequency.as_f64() / sample_rate.as_f64();
+    (w0.cos(), w0.sin() / (2.0 * q))
+}
+
+fn shelf_amplitude(gain: Decibels) -> f64 {
+    10.0_f64.powf(gain.as_f64() / 40.0)
+}
+
+fn normalize(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> BiquadCoefficients {
+    BiquadCoefficients {
+        b0: b0 / a0,
+        b1: b1 / a0,
+        b2: b2 / a0,
+        a1: a1 / a0,
+        a2: a2 / a0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn multi_biquad_filters_channels_independently() {
+        let coefficients = low_pass_coefficients(SampleRate::from(44100), Frequency::from(100.0));
+        let mut filter = MultiBiquad::new(coefficients, Channels::from(2));
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(2), Samples::from(4));
+        buffer.chan_mut(0)[0] = 1.0;
+
+        filter.process(&mut buffer);
+
+        assert!(buffer.chan(0)[0] > 0.0);
+        assert!(buffer.chan(1).iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn multi_biquad_bypass_ramps_to_dry() {
+        let coefficients = high_pass_coefficients(SampleRate::from(44100), Frequency::from(1000.0));
+        let mut filter = MultiBiquad::new(coefficients, Channels::from(2));
+        filter.set_bypass_ramp(Samples::from(8));
+        filter.set_bypassed(true);
+
+        let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(16));
+        buffer.map_samples(|_| 1.0);
+        filter.process(&mut buffer);
+
+        for channel in buffer.iter_chans() {
+            assert!(channel[0] < 1.0);
+            assert!(channel[8..].iter().all(|s| *s == 1.0));
+        }
+    }
+
+    #[test]
+    fn bilinear_transform_of_butterworth_matches_cookbook() {
+        let sample_rate = SampleRate::from(44100);
+        let cutoff = Frequency::from(2000.0);
+        let wc = 2.0 * std::f64::consts::PI * cutoff.as_f64();
+        let section = AnalogSection {
+            numerator: [0.0, 0.0, wc * wc],
+            denominator: [1.0, std::f64::consts::SQRT_2 * wc, wc * wc],
+        };
+
+        let transformed = bilinear_transform(section, sample_rate, Some(cutoff));
+        let cookbook =
+            resonant_low_pass_coefficients(sample_rate, cutoff, std::f64::consts::FRAC_1_SQRT_2);
+
+        for (a, b) in [
+            (transformed.b0, cookbook.b0),
+            (transformed.b1, cookbook.b1),
+            (transformed.b2, cookbook.b2),
+            (transformed.a1, cookbook.a1),
+            (transformed.a2, cookbook.a2),
+        ] {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn multi_biquad_lanes_match_scalar_filter() {
+        let coefficients = peak_coefficients(
+            SampleRate::from(48000),
+            Frequency::from(3000.0),
+            2.0,
+            Decibels::from(9.0),
+        );
+        let num_channels = Channels::from(13);
+        let mut filter = MultiBiquad::new(coefficients, num_channels);
+        let mut buffer = Buffer::<f64>::allocate(num_channels, Samples::from(64));
+        for channel in buffer.channel_indices() {
+            for (index, sample) in buffer.chan_mut(channel).iter_mut().enumerate() {
+                *sample = ((channel * 64 + index) as f64 * 0.37).sin();
+            }
+        }
+        let original = buffer.clone();
+
+        filter.process(&mut buffer);
+
+        for channel in buffer.channel_indices() {
+            let mut scalar = BiquadFilter::new(coefficients);
+            for (input, output) in original.chan(channel).iter().zip(buffer.chan(channel)) {
+                assert_eq!(scalar.process(*input), *output);
+            }
+        }
+    }
+
+    #[test]
+    fn f32_state_stays_close_to_f64_state() {
+        let coefficients = low_pass_coefficients(SampleRate::from(48000), Frequency::from(500.0));
+        let mut single = MultiBiquad::<f32>::with_precision(coefficients, Channels::from(2));
+        let mut double = MultiBiquad::<f64>::with_precision(coefficients, Channels::from(2));
+        let mut single_buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(4800));
+        for channel in single_buffer.channel_indices() {
+            for (index, sample) in single_buffer.chan_mut(channel).iter_mut().enumerate() {
+                *sample = ((index * (channel + 1)) as f32 * 0.05).sin();
+            }
+        }
+        let mut double_buffer = single_buffer.clone();
+
+        single.process(&mut single_buffer);
+        double.process(&mut double_buffer);
+
+        for (a, b) in single_buffer.data().iter().zip(double_buffer.data()) {
+            assert!((a - b).abs() < 1e-4);
+        }
+        let mut scalar = BiquadFilter::<f32>::with_precision(coefficients);
+        assert_eq!(scalar.process_float(1.0), coefficients.b0 as f32);
+    }
+
+    #[test_case(FilterType::LowPass, 10.0 => 0.0; "low pass below cutoff")]
+    #[test_case(FilterType::HighPass, 20000.0 => 0.0; "high pass above cutoff")]
+    #[test_case(FilterType::BandPass, 1000.0 => 0.0; "band pass at center")]
+    #[test_case(FilterType::Peak, 1000.0 => -5.0; "peak at center")]
+    #[test_case(FilterType::HighShelf, 20000.0 => -5.0; "high shelf above corner")]
+    #[test_case(FilterType::AllPass, 1000.0 => 0.0; "all pass at center")]
+    #[test_case(FilterType::AllPass, 5000.0 => 0.0; "all pass elsewhere")]
+    fn designed_filter_response(filter_type: FilterType, frequency: f64) -> f64 {
+        let sample_rate = SampleRate::from(96000);
+        let coefficients = design(
+            filter_type,
+            sample_rate,
+            Frequency::from(1000.0),
+            0.7,
+            Decibels::from(-5.0),
+        );
+        let gain = coefficients.magnitude_at(Frequency::from(frequency), sample_rate);
+        (gain.as_f64() * 100.0).round() / 100.0
+    }
+}
@@ -0,0 +1,185 @@
+//! This module contains a bitcrusher, the classic lo-fi effect. It quantizes audio to fewer
+//! bits than it has, and lowers the effective sample rate by holding every sample for a while,
+//! without any filtering, so the aliasing is part of the sound.
+//! ```rust
+//! use rabu::bitcrusher::Bitcrusher;
+//! use rabu::buffer::Buffer;
+//! use rabu::units::{BitDepth, Channels, SampleRate, Samples};
+//!
+//! let mut crusher = Bitcrusher::new(Channels::from(2), SampleRate::from(48000));
+//! crusher.set_bit_depth(BitDepth::Bits8);
+//! crusher.set_effective_sample_rate(SampleRate::from(11025));
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! crusher.process(&mut buffer);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::units::{BitDepth, Channels, SampleRate};
+
+/// Reduces the resolution and sample rate of audio.
+#[derive(Clone, Debug)]
+pub struct Bitcrusher {
+    bits: f64,
+    sample_rate: SampleRate,
+    effective_sample_rate: SampleRate,
+    /// How far the hold is to taking the next sample, where 1 means now.
+    hold_phase: f64,
+    held: Vec<f64>,
+}
+
+impl Bitcrusher {
+    /// Creates a new bitcrusher at 16 bits that doesn't reduce the sample rate.
+    pub fn new(num_channels: Channels, sample_rate: SampleRate) -> Self {
+        Self {
+            bits: 16.0,
+            sample_rate,
+            effective_sample_rate: sample_rate,
+            hold_phase: 1.0,
+            held: vec![0.0; num_channels.as_usize()],
+        }
+    }
+
+    /// Returns the number of bits the audio is quantized to.
+    pub fn bits(&self) -> f64 {
+        self.bits
+    }
+
+    /// Changes the number of bits the audio is quantized to. Fractional bits are allowed, so
+    /// the bits can be swept smoothly. The bits are kept at 1 or more.
+    pub fn set_bits(&mut self, bits: f64) {
+        self.bits = bits.max(1.0);
+    }
+
+    /// Quantizes to the given bit depth.
+    pub fn set_bit_depth(&mut self, bit_depth: BitDepth) {
+        self.set_bits(bit_depth.to_u16() as f64);
+    }
+
+    /// Returns the rate at which new samples are taken.
+    pub fn effective_sample_rate(&self) -> SampleRate {
+        self.effective_sample_rate
+    }
+
+    /// Changes the rate at which new samples are taken. Rates above the sample rate don't
+    /// change anything.
+    pub fn set_effective_sample_rate(&mut self, effective_sample_rate: SampleRate) {
+        self.effective_sample_rate = effective_sample_rate;
+    }
+
+    /// Changes the sample rate of the audio that is processed.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Clears the held samples.
+    pub fn reset(&mut self) {
+        self.held.fill(0.0);
+        self.hold_phase = 1.0;
+    }
+
+    /// Returns the sample quantized to the current number of bits.
+    pub fn quantize(&self, sample: f64) -> f64 {
+        quantize(sample, self.levels())
+    }
+
+    /// Crushes the buffer.
+    /// This will panic if the buffer doesn't have the number of channels the bitcrusher was
+    /// made for.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        assert_eq!(buffer.num_channels().as_usize(), self.held.len());
+        let levels = self.levels();
+        let step = (self.effective_sample_rate.as_f64() / self.sample_rate.as_f64()).min(1.0);
+
+        for index in buffer.sample_indices() {
+            let take = self.hold_phase >= 1.0;
+            if take {
+                self.hold_phase -= 1.0;
+            }
+            self.hold_phase += step;
+
+            for (channel, held) in buffer.iter_chans_mut().zip(self.held.iter_mut()) {
+                if take {
+                    *held = channel[index].to_f64();
+                }
+                channel[index] = T::from_f64(quantize(*held, levels));
+            }
+        }
+    }
+
+    /// The number of quantization levels on either side of 0.
+    fn levels(&self) -> f64 {
+        2.0_f64.powf(self.bits - 1.0)
+    }
+}
+
+fn quantize(sample: f64, levels: f64) -> f64 {
+    ((sample * levels).round() / levels).clamp(-1.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Samples;
+
+    fn crusher() -> Bitcrusher {
+        Bitcrusher::new(Channels::from(1), SampleRate::from(48000))
+    }
+
+    #[test_case(2.0, 0.3 => 0.5; "2 bits")]
+    #[test_case(3.0, 0.3 => 0.25; "3 bits")]
+    #[test_case(2.0, -0.8 => -1.0; "negative")]
+    #[test_case(1.0, 0.4 => 0.0; "one bit rounds to silence")]
+    fn quantizes_to_the_bits(bits: f64, sample: f64) -> f64 {
+        let mut crusher = crusher();
+        crusher.set_bits(bits);
+        crusher.quantize(sample)
+    }
+
+    #[test]
+    fn fractional_bits_lie_between_the_whole_ones() {
+        let mut crusher = crusher();
+        let error = |crusher: &Bitcrusher| {
+            (0..1000)
+                .map(|n| {
+                    let sample = n as f64 / 1000.0;
+                    (crusher.quantize(sample) - sample).abs()
+                })
+                .sum::<f64>()
+        };
+
+        crusher.set_bits(4.0);
+        let four = error(&crusher);
+        crusher.set_bits(4.5);
+        let four_and_a_half = error(&crusher);
+        crusher.set_bits(5.0);
+        let five = error(&crusher);
+
+        assert!(four > four_and_a_half && four_and_a_half > five);
+    }
+
+    #[test]
+    fn sample_and_hold_lowers_the_rate() {
+        let mut crusher = crusher();
+        crusher.set_effective_sample_rate(SampleRate::from(16000));
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(1), Samples::from(7));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = n as f64 / 8.0;
+        }
+
+        crusher.process(&mut buffer);
+
+        let expected = [0.0, 0.0, 0.0, 3.0, 3.0, 3.0, 6.0].map(|n| n / 8.0);
+        assert_eq!(buffer.chan(0), &expected);
+    }
+
+    #[test]
+    fn bit_depth_sets_the_bits() {
+        let mut crusher = crusher();
+        crusher.set_bit_depth(BitDepth::Bits8);
+        assert_eq!(crusher.bits(), 8.0);
+    }
+}
@@ -47,14 +47,30 @@ where
     /// Creates a new buffer with the given size, copying all data from self.
     pub fn clone_resized(&self, num_channels: Channels, num_samples: Samples) -> Self {
         let mut target = Self::allocate(num_channels, num_samples);
+        self.clone_resized_into(&mut target);
+        target
+    }
 
-        for channel in 0..min(self.num_channels(), num_channels).as_usize() {
-            for sample in 0..min(self.num_samples(), num_samples).as_usize() {
-                target.chan_mut(channel)[sample] = self.chan(channel)[sample];
-            }
+    /// Copies all data from self into a target of another size, without allocating. Channels and
+    /// samples that don't fit are left out, and the rest of the target is filled with the
+    /// default value.
+    /// ```
+    /// use rabu::buffer::Buffer;
+    /// use rabu::units::{Channels, Samples};
+    ///
+    /// let buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+    /// let mut target = Buffer::allocate(Channels::from(1), Samples::from(100));
+    ///
+    /// buffer.clone_resized_into(&mut target);
+    ///
+    /// assert_eq!(target.chan(0).len(), 100);
+    /// ```
+    pub fn clone_resized_into(&self, target: &mut Self) {
+        let num_samples = min(self.num_samples(), target.num_samples()).as_usize();
+        target.fill_default();
+        for (to, from) in target.iter_chans_mut().zip(self.iter_chans()) {
+            to[..num_samples].copy_from_slice(&from[..num_samples]);
         }
-
-        target
     }
 
     /// Returns a reference to the internal buffer. Channels are stored one after the other,
@@ -75,6 +91,28 @@ where
         self.data.fill(T::default());
     }
 
+    /// Changes the number of samples of every channel, and fills the buffer with the default
+    /// value. This doesn't allocate when the buffer held at least as many samples before, so
+    /// a buffer allocated for the largest block can be used for shorter blocks as well.
+    /// ```
+    /// use rabu::buffer::Buffer;
+    /// use rabu::units::{Channels, Samples};
+    ///
+    /// let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+    ///
+    /// buffer.set_num_samples(Samples::from(100));
+    ///
+    /// assert_eq!(buffer.chan(1).len(), 100);
+    /// ```
+    pub fn set_num_samples(&mut self, num_samples: Samples) {
+        self.data.clear();
+        self.data.resize(
+            num_samples.as_usize() * self.num_channels.as_usize(),
+            T::default(),
+        );
+        self.num_samples = num_samples;
+    }
+
     /// Gives you the channel numbers as a range. This can be useful when you want to iterate over
     /// the channel indices.
     pub fn channel_indices(&self) -> Range<usize> {
@@ -149,7 +187,7 @@ where
     ///     }   
     /// }
     /// ```
-    pub fn iter_chans(&self) -> ChannelIterator<T> {
+    pub fn iter_chans(&self) -> ChannelIterator<'_, T> {
         ChannelIterator {
             buffer: self,
             current_channel: 0,
@@ -169,7 +207,7 @@ where
     ///     }
     /// }
     /// ```
-    pub fn iter_chans_mut(&mut self) -> MutChannelIterator<T> {
+    pub fn iter_chans_mut(&mut self) -> MutChannelIterator<'_, T> {
         MutChannelIterator {
             buffer: self,
             current_channel: 0,
@@ -222,7 +260,7 @@ where
     ///
     /// assert_eq!(result, vec![1.0, 0.0, 2.0, 0.0, 3.0, 0.0]);
     ///```
-    pub fn iter_interleaved(&self) -> InterleavedIterator<T> {
+    pub fn iter_interleaved(&self) -> InterleavedIterator<'_, T> {
         InterleavedIterator {
             buffer: self,
             index: 0,
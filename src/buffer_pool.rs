@@ -0,0 +1,127 @@
+//! This module contains a pool of buffers, which are allocated up front and then handed out and
+//! taken back, so code on the audio thread can get buffers without allocating. All buffers of
+//! a pool have room for the same number of samples, but they can have any number of channels.
+//! ```rust
+//! use rabu::buffer_pool::BufferPool;
+//! use rabu::units::{Channels, Samples};
+//!
+//! let mut pool = BufferPool::<f32>::new(Samples::from(512));
+//! pool.reserve(Channels::from(2), 4);
+//!
+//! let buffer = pool.take(Channels::from(2));
+//! assert_eq!(pool.num_free(), 3);
+//!
+//! pool.give_back(buffer);
+//! assert_eq!(pool.num_free(), 4);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::units::{Channels, Samples};
+
+/// Buffers of the same length that can be taken and given back.
+#[derive(Clone, Debug)]
+pub struct BufferPool<T> {
+    num_samples: Samples,
+    free: Vec<Buffer<T>>,
+}
+
+impl<T> BufferPool<T>
+where
+    T: Copy + Default,
+{
+    /// Creates a new, empty pool for buffers of the given number of samples.
+    pub fn new(num_samples: Samples) -> Self {
+        Self {
+            num_samples,
+            free: Vec::new(),
+        }
+    }
+
+    /// Returns the number of samples of the buffers.
+    pub fn num_samples(&self) -> Samples {
+        self.num_samples
+    }
+
+    /// Returns the number of buffers in the pool.
+    pub fn num_free(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Allocates buffers with the number of channels, until the pool has at least the given
+    /// number of them.
+    pub fn reserve(&mut self, num_channels: Channels, count: usize) {
+        let available = self
+            .free
+            .iter()
+            .filter(|buffer| buffer.num_channels() == num_channels)
+            .count();
+        self.free.reserve(count.saturating_sub(available));
+        for _ in available..count {
+            self.free
+                .push(Buffer::allocate(num_channels, self.num_samples));
+        }
+    }
+
+    /// Hands out a silent buffer with the number of channels. This only allocates when the pool
+    /// has no such buffer left.
+    pub fn take(&mut self, num_channels: Channels) -> Buffer<T> {
+        match self
+            .free
+            .iter()
+            .position(|buffer| buffer.num_channels() == num_channels)
+        {
+            Some(index) => {
+                let mut buffer = self.free.swap_remove(index);
+                buffer.set_num_samples(self.num_samples);
+                buffer
+            }
+            None => Buffer::allocate(num_channels, self.num_samples),
+        }
+    }
+
+    /// Takes the buffer back, so it can be handed out again. The buffer should come from the
+    /// pool: a buffer that was allocated with fewer samples has to grow when it's handed out.
+    pub fn give_back(&mut self, buffer: Buffer<T>) {
+        self.free.push(buffer);
+    }
+
+    /// Drops all buffers in the pool, and changes the number of samples of the buffers it will
+    /// hand out.
+    pub fn clear(&mut self, num_samples: Samples) {
+        self.free.clear();
+        self.num_samples = num_samples;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hands_out_silent_buffers_with_the_number_of_channels() {
+        let mut pool = BufferPool::<f32>::new(Samples::from(8));
+        pool.reserve(Channels::from(1), 1);
+        pool.reserve(Channels::from(2), 1);
+
+        let mut buffer = pool.take(Channels::from(2));
+        buffer.set_num_samples(Samples::from(4));
+        buffer.chan_mut(0)[0] = 1.0;
+        pool.give_back(buffer);
+        let buffer = pool.take(Channels::from(2));
+
+        assert_eq!(buffer.num_channels(), Channels::from(2));
+        assert_eq!(buffer.num_samples(), Samples::from(8));
+        assert!(buffer.is_default_filled());
+        assert_eq!(pool.num_free(), 1);
+    }
+
+    #[test]
+    fn reserve_counts_the_buffers_that_are_there() {
+        let mut pool = BufferPool::<f32>::new(Samples::from(8));
+
+        pool.reserve(Channels::from(2), 2);
+        pool.reserve(Channels::from(2), 3);
+
+        assert_eq!(pool.num_free(), 3);
+    }
+}
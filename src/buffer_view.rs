@@ -0,0 +1,335 @@
+//! This module contains views of audio that is owned by someone else, e.g. the channel slices a
+//! plugin framework hands to its process callback, like `nih_plug::buffer::Buffer::as_slice`.
+//! A view can be processed in place without copying it into a `Buffer` first, and can be split
+//! into parts at the events in the block, so events are applied sample accurately.
+//! ```rust
+//! use rabu::buffer_view::{split_view, BufferViewMut};
+//! use rabu::events::TimedEvent;
+//! use rabu::units::Samples;
+//!
+//! // The channels as the host gives them.
+//! let mut left = [1.0_f32; 8];
+//! let mut right = [1.0_f32; 8];
+//! let mut channels = [&mut left[..], &mut right[..]];
+//! let mut view = BufferViewMut::new(&mut channels);
+//!
+//! // Gain changes on the timeline, and the block starts at sample 100.
+//! let events = [TimedEvent { position: Samples::from(104), event: 0.5_f32 }];
+//! let mut gain = 1.0;
+//! split_view(&mut view, Samples::from(100), &events, |mut part, _, events| {
+//!     for event in events {
+//!         gain = event.event;
+//!     }
+//!     for channel in part.iter_chans_mut() {
+//!         channel.iter_mut().for_each(|sample| *sample *= gain);
+//!     }
+//! });
+//!
+//! assert_eq!(left, [1.0, 1.0, 1.0, 1.0, 0.5, 0.5, 0.5, 0.5]);
+//! ```
+
+use std::ops::Range;
+
+use crate::buffer::Buffer;
+use crate::events::{split_block, SubBlock, TimedEvent};
+use crate::units::{Channels, SampleSection, Samples};
+
+/// A view of channels of audio owned by someone else.
+#[derive(Clone, Debug)]
+pub struct BufferView<'a, T> {
+    channels: &'a [&'a [T]],
+    range: Range<usize>,
+}
+
+/// A mutable view of channels of audio owned by someone else.
+#[derive(Debug)]
+pub struct BufferViewMut<'a, 'b, T> {
+    channels: &'a mut [&'b mut [T]],
+    range: Range<usize>,
+}
+
+impl<'a, T> BufferView<'a, T>
+where
+    T: Copy + Default,
+{
+    /// Creates a view of the channels.
+    /// This will panic if the channels don't all have the same length.
+    pub fn new(channels: &'a [&'a [T]]) -> Self {
+        let length = channels.first().map_or(0, |channel| channel.len());
+        assert!(channels.iter().all(|channel| channel.len() == length));
+        Self {
+            channels,
+            range: 0..length,
+        }
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.channels.len())
+    }
+
+    /// Returns the number of samples per channel.
+    pub fn num_samples(&self) -> Samples {
+        Samples::from(self.range.len())
+    }
+
+    /// Returns the samples of the channel.
+    /// This will panic if the channel doesn't exist.
+    pub fn chan(&self, index: usize) -> &'a [T] {
+        &self.channels[index][self.range.clone()]
+    }
+
+    /// Iterates over the channels.
+    pub fn iter_chans(&self) -> impl Iterator<Item = &'a [T]> + '_ {
+        self.channels
+            .iter()
+            .map(|channel| &channel[self.range.clone()])
+    }
+
+    /// Returns a view of the samples in the range.
+    /// This will panic if the range is out of bounds.
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        Self {
+            channels: self.channels,
+            range: sub_range(&self.range, range),
+        }
+    }
+
+    /// Copies the samples into the buffer.
+    /// This will panic if the buffer doesn't have the same number of channels and samples.
+    pub fn copy_into(&self, buffer: &mut Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+        assert_eq!(buffer.num_samples(), self.num_samples());
+        for (to, from) in buffer.iter_chans_mut().zip(self.iter_chans()) {
+            to.copy_from_slice(from);
+        }
+    }
+}
+
+impl<'a, 'b, T> BufferViewMut<'a, 'b, T>
+where
+    T: Copy + Default,
+{
+    /// Creates a mutable view of the channels.
+    /// This will panic if the channels don't all have the same length.
+    pub fn new(channels: &'a mut [&'b mut [T]]) -> Self {
+        let length = channels.first().map_or(0, |channel| channel.len());
+        assert!(channels.iter().all(|channel| channel.len() == length));
+        Self {
+            channels,
+            range: 0..length,
+        }
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.channels.len())
+    }
+
+    /// Returns the number of samples per channel.
+    pub fn num_samples(&self) -> Samples {
+        Samples::from(self.range.len())
+    }
+
+    /// Returns the samples of the channel.
+    /// This will panic if the channel doesn't exist.
+    pub fn chan(&self, index: usize) -> &[T] {
+        &self.channels[index][self.range.clone()]
+    }
+
+    /// Returns the samples of the channel to change.
+    /// This will panic if the channel doesn't exist.
+    pub fn chan_mut(&mut self, index: usize) -> &mut [T] {
+        &mut self.channels[index][self.range.clone()]
+    }
+
+    /// Iterates over the channels.
+    pub fn iter_chans(&self) -> ViewChannelIterator<'_, 'b, T> {
+        ViewChannelIterator {
+            channels: self.channels.iter(),
+            range: self.range.clone(),
+        }
+    }
+
+    /// Iterates over the channels to change them.
+    pub fn iter_chans_mut(&mut self) -> MutViewChannelIterator<'_, 'b, T> {
+        MutViewChannelIterator {
+            channels: self.channels.iter_mut(),
+            range: self.range.clone(),
+        }
+    }
+
+    /// Returns a mutable view of the samples in the range.
+    /// This will panic if the range is out of bounds.
+    pub fn slice_mut(&mut self, range: Range<usize>) -> BufferViewMut<'_, 'b, T> {
+        BufferViewMut {
+            range: sub_range(&self.range, range),
+            channels: self.channels,
+        }
+    }
+
+    /// Fills all channels with the default value, which is silence for samples.
+    pub fn fill_default(&mut self) {
+        for channel in self.iter_chans_mut() {
+            channel.fill(T::default());
+        }
+    }
+
+    /// Copies the samples of the buffer into the view.
+    /// This will panic if the buffer doesn't have the same number of channels and samples.
+    pub fn copy_from(&mut self, buffer: &Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+        assert_eq!(buffer.num_samples(), self.num_samples());
+        for (to, from) in self.iter_chans_mut().zip(buffer.iter_chans()) {
+            to.copy_from_slice(from);
+        }
+    }
+
+    /// Copies the samples into the buffer.
+    /// This will panic if the buffer doesn't have the same number of channels and samples.
+    pub fn copy_into(&self, buffer: &mut Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+        assert_eq!(buffer.num_samples(), self.num_samples());
+        for (to, from) in buffer.iter_chans_mut().zip(self.iter_chans()) {
+            to.copy_from_slice(from);
+        }
+    }
+}
+
+/// Splits the view at the positions of the events in it, like `events::split_block`, and calls
+/// `process` for every part in order, with a view of the part and the events at its start.
+/// The view starts at the position on the timeline, and the events must be sorted by position.
+pub fn split_view<T, E>(
+    view: &mut BufferViewMut<'_, '_, T>,
+    position: Samples,
+    events: &[TimedEvent<E>],
+    mut process: impl FnMut(BufferViewMut<'_, '_, T>, SubBlock, &[TimedEvent<E>]),
+) where
+    T: Copy + Default,
+{
+    let block = SampleSection {
+        start: position,
+        length: view.num_samples(),
+    };
+    split_block(block, events, |part, events| {
+        let start = part.offset.as_usize();
+        let range = start..start + part.section.length.as_usize();
+        process(view.slice_mut(range), part, events);
+    });
+}
+
+/// Returns the range relative to the start of the outer range.
+fn sub_range(outer: &Range<usize>, range: Range<usize>) -> Range<usize> {
+    assert!(range.start <= range.end && range.end <= outer.len());
+    outer.start + range.start..outer.start + range.end
+}
+
+/// Iterates over the channels of a `BufferViewMut`.
+pub struct ViewChannelIterator<'a, 'b, T> {
+    channels: std::slice::Iter<'a, &'b mut [T]>,
+    range: Range<usize>,
+}
+
+impl<'a, T> Iterator for ViewChannelIterator<'a, '_, T> {
+    type Item = &'a [T];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let channel = self.channels.next()?;
+        Some(&channel[self.range.clone()])
+    }
+}
+
+/// Iterates over the channels of a `BufferViewMut` to change them.
+pub struct MutViewChannelIterator<'a, 'b, T> {
+    channels: std::slice::IterMut<'a, &'b mut [T]>,
+    range: Range<usize>,
+}
+
+impl<'a, T> Iterator for MutViewChannelIterator<'a, '_, T> {
+    type Item = &'a mut [T];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let channel = self.channels.next()?;
+        Some(&mut channel[self.range.clone()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn views_the_channels_without_copying() {
+        let left = [1.0, 2.0, 3.0];
+        let right = [4.0, 5.0, 6.0];
+        let channels = [&left[..], &right[..]];
+
+        let view = BufferView::new(&channels);
+        let part = view.slice(1..3);
+        let mut buffer = Buffer::allocate(Channels::from(2), Samples::from(2));
+        part.copy_into(&mut buffer);
+
+        assert_eq!(view.num_channels(), Channels::from(2));
+        assert_eq!(view.num_samples(), Samples::from(3));
+        assert_eq!(part.chan(1), &[5.0, 6.0]);
+        assert_eq!(buffer.chan(0), &[2.0, 3.0]);
+        assert_eq!(part.iter_chans().count(), 2);
+    }
+
+    #[test]
+    fn changes_the_samples_of_the_owner() {
+        let mut left = [0.0; 4];
+        let mut right = [0.0; 4];
+        let mut channels = [&mut left[..], &mut right[..]];
+        let mut view = BufferViewMut::new(&mut channels);
+
+        let mut buffer = Buffer::allocate(Channels::from(2), Samples::from(2));
+        buffer.chan_mut(0).fill(1.0);
+        buffer.chan_mut(1).fill(2.0);
+        view.slice_mut(2..4).copy_from(&buffer);
+        view.chan_mut(0)[0] = -1.0;
+
+        assert_eq!(left, [-1.0, 0.0, 1.0, 1.0]);
+        assert_eq!(right, [0.0, 0.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn splits_at_the_events() {
+        let mut samples = [0.0; 10];
+        let mut channels = [&mut samples[..]];
+        let mut view = BufferViewMut::new(&mut channels);
+        let events = [
+            TimedEvent {
+                position: Samples::from(53),
+                event: 1.0,
+            },
+            TimedEvent {
+                position: Samples::from(57),
+                event: 2.0,
+            },
+        ];
+
+        let mut parts = Vec::new();
+        split_view(
+            &mut view,
+            Samples::from(50),
+            &events,
+            |mut part, sub_block, events| {
+                let value = events.first().map_or(0.0, |event| event.event);
+                part.chan_mut(0).fill(value);
+                parts.push((sub_block.offset.as_usize(), part.num_samples().as_usize()));
+            },
+        );
+
+        assert_eq!(parts, [(0, 3), (3, 4), (7, 3)]);
+        assert_eq!(samples, [0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 2.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn channels_of_different_lengths_panic() {
+        let mut left = [0.0; 4];
+        let mut right = [0.0; 3];
+        BufferViewMut::new(&mut [&mut left[..], &mut right[..]]);
+    }
+}
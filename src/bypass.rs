@@ -0,0 +1,264 @@
+//! This module contains a click-free bypass mechanism. Instead of switching between the dry and
+//! the processed signal instantly, it crossfades between the two over a configurable ramp.
+//! Any `SampleProcessor` can be made bypassable by wrapping it in a `Bypassable`:
+//! ```rust
+//! use rabu::biquad::{low_pass_coefficients, BiquadFilter};
+//! use rabu::bypass::Bypassable;
+//! use rabu::processor::SampleProcessor;
+//! use rabu::units::{Frequency, SampleRate, Samples};
+//!
+//! let coefficients = low_pass_coefficients(SampleRate::from(44100), Frequency::from(1000.0));
+//! let mut filter = Bypassable::new(BiquadFilter::new(coefficients), Samples::from(64));
+//!
+//! filter.set_bypassed(true);
+//!
+//! for _ in 0..64 {
+//!     filter.process(0.5);
+//! }
+//!
+//! assert_eq!(filter.process(0.5), 0.5);
+//! ```
+
+use crate::fades::FadeCurve;
+use crate::processor::SampleProcessor;
+use crate::units::{NormalizedValue, Samples};
+
+/// Keeps track of the crossfade between the dry and the processed (wet) signal.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bypass {
+    ramp_length: Samples,
+    ramp_position: Samples,
+    bypassed: bool,
+    curve: FadeCurve,
+}
+
+impl Bypass {
+    /// Creates a new (active, so not bypassed) bypass with the given crossfade length.
+    pub fn new(ramp_length: Samples) -> Self {
+        Self {
+            ramp_length,
+            ramp_position: ramp_length,
+            bypassed: false,
+            curve: FadeCurve::Linear,
+        }
+    }
+
+    /// Sets whether the processing should be bypassed. The change is applied gradually over
+    /// the length of the ramp.
+    pub fn set_bypassed(&mut self, bypassed: bool) {
+        self.bypassed = bypassed;
+    }
+
+    /// Returns whether bypass is turned on. The signal might still be ramping towards dry.
+    pub fn is_bypassed(&self) -> bool {
+        self.bypassed
+    }
+
+    /// Returns `true` when the bypass is on and the crossfade has finished, in which case
+    /// the processing can be skipped entirely.
+    pub fn is_fully_bypassed(&self) -> bool {
+        self.bypassed && self.ramp_position == Samples::from(0)
+    }
+
+    /// Returns `true` when the bypass is off and the crossfade has finished.
+    pub fn is_fully_active(&self) -> bool {
+        !self.bypassed && self.ramp_position == self.ramp_length
+    }
+
+    /// Returns the length of the crossfade.
+    pub fn ramp_length(&self) -> Samples {
+        self.ramp_length
+    }
+
+    /// Changes the length of the crossfade, keeping the current mix as close as possible.
+    pub fn set_ramp_length(&mut self, ramp_length: Samples) {
+        let position = self.position();
+        self.ramp_length = ramp_length;
+        self.ramp_position = Samples::from((position * ramp_length.as_f64()).round() as u64);
+    }
+
+    /// Returns the curve of the crossfade.
+    pub fn curve(&self) -> FadeCurve {
+        self.curve
+    }
+
+    /// Changes the curve of the crossfade, which is linear by default. The processed signal is
+    /// usually much like the dry one, which a linear crossfade suits best.
+    pub fn set_curve(&mut self, curve: FadeCurve) {
+        self.curve = curve;
+    }
+
+    /// Jumps to the end of the current crossfade, so the bypass state is applied immediately.
+    pub fn skip_ramp(&mut self) {
+        self.ramp_position = if self.bypassed {
+            Samples::from(0)
+        } else {
+            self.ramp_length
+        };
+    }
+
+    /// Mixes the given dry and wet samples according to the current crossfade position
+    /// and advances the crossfade by one sample.
+    pub fn mix(&mut self, dry: f64, wet: f64) -> f64 {
+        let (dry_gain, wet_gain) = self.next_gains();
+        dry * dry_gain + wet * wet_gain
+    }
+
+    /// Returns the gains of the dry and the wet signal for the current sample (both between
+    /// 0 and 1) and advances the crossfade by one sample. This is useful when the same
+    /// crossfade has to be applied to multiple channels at once.
+    pub fn next_gains(&mut self) -> (f64, f64) {
+        let gains = self
+            .curve
+            .crossfade_gains(NormalizedValue::from(self.position()));
+        self.advance();
+        gains
+    }
+
+    /// Returns the gain of the wet signal for the current sample (between 0 and 1)
+    /// and advances the crossfade by one sample.
+    pub fn next_wet_gain(&mut self) -> f64 {
+        self.next_gains().1
+    }
+
+    /// How far the crossfade is from dry (0) to wet (1).
+    fn position(&self) -> f64 {
+        if self.ramp_length == Samples::from(0) {
+            return if self.bypassed { 0.0 } else { 1.0 };
+        }
+        self.ramp_position.as_f64() / self.ramp_length.as_f64()
+    }
+
+    fn advance(&mut self) {
+        if self.bypassed && self.ramp_position > Samples::from(0) {
+            self.ramp_position -= Samples::from(1);
+        } else if !self.bypassed && self.ramp_position < self.ramp_length {
+            self.ramp_position += Samples::from(1);
+        }
+    }
+}
+
+/// Wraps a `SampleProcessor` to make it bypassable without clicks.
+/// Once fully bypassed, the wrapped processor is not called anymore.
+#[derive(Clone, Debug)]
+pub struct Bypassable<P> {
+    processor: P,
+    bypass: Bypass,
+}
+
+impl<P> Bypassable<P>
+where
+    P: SampleProcessor,
+{
+    /// Wraps the given processor, crossfading over `ramp_length` when bypass is toggled.
+    pub fn new(processor: P, ramp_length: Samples) -> Self {
+        Self {
+            processor,
+            bypass: Bypass::new(ramp_length),
+        }
+    }
+
+    /// Sets whether the wrapped processor should be bypassed.
+    pub fn set_bypassed(&mut self, bypassed: bool) {
+        self.bypass.set_bypassed(bypassed);
+    }
+
+    /// Returns whether bypass is turned on.
+    pub fn is_bypassed(&self) -> bool {
+        self.bypass.is_bypassed()
+    }
+
+    /// Returns a reference to the wrapped processor.
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+
+    /// Returns a mutable reference to the wrapped processor.
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    /// Unwraps the processor.
+    pub fn into_inner(self) -> P {
+        self.processor
+    }
+}
+
+impl<P> SampleProcessor for Bypassable<P>
+where
+    P: SampleProcessor,
+{
+    fn process(&mut self, input: f64) -> f64 {
+        if self.bypass.is_fully_bypassed() {
+            return input;
+        }
+
+        let wet = self.processor.process(input);
+        self.bypass.mix(input, wet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crossfades_linearly_to_dry() {
+        let mut processor = Bypassable::new(|_| 1.0, Samples::from(4));
+        processor.set_bypassed(true);
+
+        let output: Vec<_> = (0..6).map(|_| processor.process(0.0)).collect();
+
+        assert_eq!(output, vec![1.0, 0.75, 0.5, 0.25, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn crossfades_back_to_wet() {
+        let mut processor = Bypassable::new(|_| 1.0, Samples::from(2));
+        processor.set_bypassed(true);
+        (0..2).for_each(|_| _ = processor.process(0.0));
+
+        processor.set_bypassed(false);
+        let output: Vec<_> = (0..3).map(|_| processor.process(0.0)).collect();
+
+        assert_eq!(output, vec![0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn zero_length_ramp_switches_instantly() {
+        let mut processor = Bypassable::new(|_| 1.0, Samples::from(0));
+        assert_eq!(processor.process(0.0), 1.0);
+
+        processor.set_bypassed(true);
+        assert_eq!(processor.process(0.0), 0.0);
+    }
+
+    #[test]
+    fn fully_bypassed_processor_is_not_called() {
+        let calls = std::cell::Cell::new(0);
+        let mut processor = Bypassable::new(
+            |x| {
+                calls.set(calls.get() + 1);
+                x
+            },
+            Samples::from(1),
+        );
+        processor.set_bypassed(true);
+        (0..10).for_each(|_| _ = processor.process(0.0));
+
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn crossfades_with_the_curve() {
+        let mut bypass = Bypass::new(Samples::from(2));
+        bypass.set_curve(FadeCurve::EqualPower);
+        bypass.set_bypassed(true);
+
+        let (dry, wet) = bypass.next_gains();
+        assert!(dry.abs() < 1e-12 && (wet - 1.0).abs() < 1e-12);
+        let (dry, wet) = bypass.next_gains();
+        assert!((dry - wet).abs() < 1e-12);
+        assert!((dry * dry + wet * wet - 1.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,71 @@
+//! This module contains the `AudioClip` struct, which is a buffer of audio together with the
+//! sample rate it was recorded at. Keeping the two together makes sure audio from files with
+//! different sample rates is played back at the right speed.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::clip::AudioClip;
+//! use rabu::units::{Channels, SampleRate, Samples, Seconds};
+//!
+//! let buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(22050));
+//! let clip = AudioClip::new(buffer, SampleRate::from(44100));
+//!
+//! assert_eq!(clip.duration(), Seconds::from(0.5));
+//! ```
+
+use crate::buffer::Buffer;
+use crate::units::{Channels, SampleRate, Samples, Seconds};
+
+/// A buffer of audio together with its sample rate.
+#[derive(Clone, Debug)]
+pub struct AudioClip<T> {
+    buffer: Buffer<T>,
+    sample_rate: SampleRate,
+}
+
+impl<T> AudioClip<T>
+where
+    T: Copy + Default,
+{
+    /// Creates a new clip from the given audio, which is recorded at the given sample rate.
+    pub fn new(buffer: Buffer<T>, sample_rate: SampleRate) -> Self {
+        Self {
+            buffer,
+            sample_rate,
+        }
+    }
+
+    /// Returns the audio of the clip.
+    pub fn buffer(&self) -> &Buffer<T> {
+        &self.buffer
+    }
+
+    /// Returns the audio of the clip mutably.
+    pub fn buffer_mut(&mut self) -> &mut Buffer<T> {
+        &mut self.buffer
+    }
+
+    /// Returns the sample rate the clip was recorded at.
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// Returns the number of channels of the clip.
+    pub fn num_channels(&self) -> Channels {
+        self.buffer.num_channels()
+    }
+
+    /// Returns the length of the clip in samples.
+    pub fn num_samples(&self) -> Samples {
+        self.buffer.num_samples()
+    }
+
+    /// Returns the length of the clip in seconds.
+    pub fn duration(&self) -> Seconds {
+        self.num_samples().to_seconds(self.sample_rate)
+    }
+
+    /// Consumes the clip and gives back its audio.
+    pub fn into_buffer(self) -> Buffer<T> {
+        self.buffer
+    }
+}
@@ -0,0 +1,241 @@
+//! This module contains clipping detection, which tells where a buffer clips before it is
+//! exported, so the regions can be shown to the user. A sample clips when it reaches the
+//! ceiling. With true peaks, the waveform in between the samples counts as well, which catches
+//! the overs that only show up after conversion to analog or lossy encoding.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::clipping::detect_clipping;
+//! use rabu::units::{Channels, Decibels, SampleRate, Samples};
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(1000));
+//! buffer.chan_mut(1)[500..510].fill(1.2);
+//!
+//! let report = detect_clipping(&buffer, SampleRate::from(1000), Decibels::from(0.0), false);
+//!
+//! assert_eq!(report.regions.len(), 1);
+//! assert!((report.regions.as_slice()[0].start.as_secs_f64() - 0.5).abs() < 1e-9);
+//! assert_eq!(report.clipped_samples, 10);
+//! assert!((report.max_overshoot.as_f64() - 1.58).abs() < 0.01);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::dynamics::level_to_db;
+use crate::meter::true_peak_oversampling;
+use crate::oversampling::Oversampler;
+use crate::sample::Sample;
+use crate::sections::SectionList;
+use crate::units::{Decibels, SampleRate, Samples, TimePoint, TimeSection};
+
+/// Where and how much a buffer clips.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClippingReport {
+    /// The regions where at least one channel clips.
+    pub regions: SectionList,
+    /// The number of clipped samples, added up over the channels.
+    pub clipped_samples: usize,
+    /// How far the highest peak goes over the ceiling, or 0 dB without clipping.
+    pub max_overshoot: Decibels,
+}
+
+impl ClippingReport {
+    /// Tells whether anything clips.
+    pub fn is_clipping(&self) -> bool {
+        !self.regions.is_empty()
+    }
+}
+
+/// Finds where the buffer reaches the ceiling, e.g. 0 dBFS, or a little lower to leave room
+/// for the encoder. With `true_peak`, the buffer is oversampled like a true peak meter does,
+/// and a sample also counts as clipped when the waveform right after it reaches the ceiling.
+pub fn detect_clipping<T: Sample>(
+    buffer: &Buffer<T>,
+    sample_rate: SampleRate,
+    ceiling: Decibels,
+    true_peak: bool,
+) -> ClippingReport {
+    let ceiling_gain = ceiling.to_gain();
+    let mut clipped = vec![false; buffer.num_samples().as_usize()];
+    let mut clipped_samples = 0;
+    let mut peak = 0.0_f64;
+
+    for channel in buffer.iter_chans() {
+        let mut levels: Vec<f64> = channel.iter().map(|sample| sample.to_f64().abs()).collect();
+        if true_peak {
+            add_true_peaks(channel, sample_rate, &mut levels);
+        }
+        for (is_clipped, level) in clipped.iter_mut().zip(&levels) {
+            if *level >= ceiling_gain {
+                *is_clipped = true;
+                clipped_samples += 1;
+            }
+            peak = peak.max(*level);
+        }
+    }
+
+    let max_overshoot = if clipped_samples > 0 {
+        level_to_db(peak) - ceiling.as_f64()
+    } else {
+        0.0
+    };
+    ClippingReport {
+        regions: regions(&clipped, sample_rate),
+        clipped_samples,
+        max_overshoot: Decibels::from(max_overshoot.max(0.0)),
+    }
+}
+
+/// Raises the level of every sample to the peak of the oversampled waveform from that sample
+/// up to the next one.
+fn add_true_peaks<T: Sample>(channel: &[T], sample_rate: SampleRate, levels: &mut [f64]) {
+    let mut oversampler = Oversampler::new(true_peak_oversampling(sample_rate));
+    // The upsampling filter delays by half of the latency, so the tail is flushed out with
+    // silence.
+    let delay = oversampler.latency_samples().as_usize() / 2;
+    let input = channel
+        .iter()
+        .map(|sample| sample.to_f64())
+        .chain(std::iter::repeat_n(0.0, delay));
+    for (index, sample) in input.enumerate() {
+        let mut peak = 0.0_f64;
+        oversampler.upsample(sample, |oversampled| peak = peak.max(oversampled.abs()));
+        if let Some(level) = index.checked_sub(delay).and_then(|n| levels.get_mut(n)) {
+            *level = level.max(peak);
+        }
+    }
+}
+
+/// Turns the runs of clipped samples into sections.
+fn regions(clipped: &[bool], sample_rate: SampleRate) -> SectionList {
+    let to_time =
+        |position: usize| TimePoint::from(Samples::from(position).to_seconds(sample_rate));
+    let mut regions = SectionList::new();
+    let mut start = None;
+    for (position, is_clipped) in clipped.iter().chain([&false]).enumerate() {
+        match (start, is_clipped) {
+            (None, true) => start = Some(position),
+            (Some(first), false) => {
+                regions.insert(TimeSection {
+                    start: to_time(first),
+                    duration: to_time(position) - to_time(first),
+                });
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Channels;
+
+    const SAMPLE_RATE: u32 = 1000;
+
+    fn buffer(channels: &[&[f64]]) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(
+            Channels::from(channels.len()),
+            Samples::from(channels[0].len()),
+        );
+        for (index, samples) in channels.iter().enumerate() {
+            buffer.chan_mut(index).copy_from_slice(samples);
+        }
+        buffer
+    }
+
+    fn seconds(sections: &SectionList) -> Vec<(f64, f64)> {
+        sections
+            .iter()
+            .map(|section| (section.start.as_secs_f64(), section.end().as_secs_f64()))
+            .collect()
+    }
+
+    #[test]
+    fn clean_audio_has_no_regions() {
+        let buffer = buffer(&[&[0.5, -0.9, 0.99, 0.0]]);
+
+        let report = detect_clipping(
+            &buffer,
+            SampleRate::from(SAMPLE_RATE),
+            Decibels::from(0.0),
+            false,
+        );
+
+        assert!(!report.is_clipping());
+        assert_eq!(report.clipped_samples, 0);
+        assert_eq!(report.max_overshoot, Decibels::from(0.0));
+    }
+
+    #[test]
+    fn runs_of_clipped_samples_become_regions() {
+        let buffer = buffer(&[&[0.0, 1.0, -1.0, 0.5, 0.0, 0.0, 2.0, 0.0]]);
+
+        let report = detect_clipping(
+            &buffer,
+            SampleRate::from(SAMPLE_RATE),
+            Decibels::from(0.0),
+            false,
+        );
+
+        let regions = seconds(&report.regions);
+        assert_eq!(regions.len(), 2);
+        assert!((regions[0].0 - 0.001).abs() < 1e-9 && (regions[0].1 - 0.003).abs() < 1e-9);
+        assert!((regions[1].0 - 0.006).abs() < 1e-9 && (regions[1].1 - 0.007).abs() < 1e-9);
+        assert_eq!(report.clipped_samples, 3);
+        assert!((report.max_overshoot.as_f64() - 6.02).abs() < 0.01);
+    }
+
+    #[test]
+    fn channels_clipping_together_share_a_region() {
+        let buffer = buffer(&[&[1.0, 1.0, 0.0, 0.0], &[0.0, 1.0, 1.0, 0.0]]);
+
+        let report = detect_clipping(
+            &buffer,
+            SampleRate::from(SAMPLE_RATE),
+            Decibels::from(0.0),
+            false,
+        );
+
+        assert_eq!(report.regions.len(), 1);
+        assert_eq!(report.clipped_samples, 4);
+    }
+
+    #[test]
+    fn ceiling_below_full_scale() {
+        let buffer = buffer(&[&[0.0, 0.95, 0.0]]);
+
+        let report = detect_clipping(
+            &buffer,
+            SampleRate::from(SAMPLE_RATE),
+            Decibels::from(-1.0),
+            false,
+        );
+
+        assert_eq!(report.clipped_samples, 1);
+        assert!((report.max_overshoot.as_f64() - 0.55).abs() < 0.01);
+    }
+
+    #[test]
+    fn true_peaks_find_the_overs_in_between_the_samples() {
+        // A sine at a quarter of the sample rate, sampled 45 degrees off its peaks, has its
+        // samples 3 dB below the peaks of the waveform, which go 1.58 dB over.
+        let samples: Vec<f64> = (0..400)
+            .map(|n| (std::f64::consts::FRAC_PI_2 * n as f64 + std::f64::consts::FRAC_PI_4).sin())
+            .map(|sample| sample * 1.2)
+            .collect();
+        let buffer = buffer(&[&samples]);
+        let sample_rate = SampleRate::from(48000);
+
+        let sample_peaks = detect_clipping(&buffer, sample_rate, Decibels::from(0.0), false);
+        let true_peaks = detect_clipping(&buffer, sample_rate, Decibels::from(0.0), true);
+
+        assert!(!sample_peaks.is_clipping());
+        assert!(true_peaks.is_clipping());
+        // The filters ring a little where the sine starts and stops.
+        assert!((true_peaks.max_overshoot.as_f64() - 1.58).abs() < 0.2);
+        let region = true_peaks.regions.bounds().unwrap();
+        assert!(region.start.as_secs_f64() < 0.001);
+        assert!(region.end().as_secs_f64() > 0.007);
+    }
+}
@@ -0,0 +1,254 @@
+//! This module contains a uniformly partitioned FFT convolution engine. The impulse response
+//! is split into partitions of one block each, so the cost per block is bounded no matter how
+//! long the impulse response is. All memory is allocated up front, so processing is real-time safe.
+//! The engine has a latency of exactly one block.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::convolution::Convolver;
+//! use rabu::units::{Channels, Latency, SampleRate, Samples};
+//!
+//! let mut impulse_response = Buffer::<f32>::allocate(Channels::from(1), Samples::from(48000));
+//! impulse_response.chan_mut(0)[0] = 1.0;
+//!
+//! let mut convolver = Convolver::new(&impulse_response, Channels::from(2), Samples::from(256));
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//!
+//! convolver.process(&mut buffer);
+//!
+//! assert_eq!(convolver.latency(SampleRate::from(256)), Latency::from_secs_f64(1.0));
+//! ```
+
+use crate::buffer::Buffer;
+use crate::fft::{Complex, FftPlan};
+use crate::sample::Sample;
+use crate::units::{Channels, Latency, SampleRate, Samples};
+
+/// Convolves every channel of a buffer with an impulse response.
+/// If the impulse response has one channel, it is used for all channels. Otherwise, every
+/// channel is convolved with the impulse response channel with the same index.
+#[derive(Clone, Debug)]
+pub struct Convolver {
+    channels: Vec<PartitionedConvolver>,
+    block_size: Samples,
+}
+
+impl Convolver {
+    /// Creates a new convolver for the given impulse response, processing partitions of
+    /// `block_size` samples. Larger blocks are more efficient, but add more latency.
+    /// This will panic if the block size is not a power of two, or if the impulse response
+    /// is not mono and doesn't have `num_channels` channels.
+    pub fn new<T: Sample>(
+        impulse_response: &Buffer<T>,
+        num_channels: Channels,
+        block_size: Samples,
+    ) -> Self {
+        let ir_channels = impulse_response.num_channels();
+        assert!(ir_channels == Channels::from(1) || ir_channels == num_channels);
+
+        let plan = FftPlan::new(2 * block_size.as_usize());
+        let channels = (0..num_channels.as_usize())
+            .map(|channel| {
+                let ir_channel = channel.min(ir_channels.as_usize() - 1);
+                PartitionedConvolver::new(impulse_response.chan(ir_channel), plan.clone())
+            })
+            .collect();
+
+        Self {
+            channels,
+            block_size,
+        }
+    }
+
+    /// Returns the number of channels this convolver processes.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.channels.len())
+    }
+
+    /// Returns the size of the partitions.
+    pub fn block_size(&self) -> Samples {
+        self.block_size
+    }
+
+    /// Returns the latency that is added by the convolver, which is one block.
+    pub fn latency(&self, sample_rate: SampleRate) -> Latency {
+        Latency::from(self.block_size.to_seconds(sample_rate))
+    }
+
+    /// Clears the internal state, as if no audio was processed yet.
+    pub fn reset(&mut self) {
+        self.channels
+            .iter_mut()
+            .for_each(PartitionedConvolver::reset);
+    }
+
+    /// Convolves the given buffer in place. The buffer can have any length.
+    /// This will panic if the buffer doesn't have the same number of channels as the convolver.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+
+        for (convolver, channel) in self.channels.iter_mut().zip(buffer.iter_chans_mut()) {
+            for sample in channel.iter_mut() {
+                *sample = T::from_f64(convolver.process(sample.to_f64()));
+            }
+        }
+    }
+
+    /// Convolves one sample of the given channel.
+    pub(crate) fn process_sample(&mut self, channel: usize, input: f64) -> f64 {
+        self.channels[channel].process(input)
+    }
+}
+
+/// Uniformly partitioned overlap-save convolution of a single channel.
+#[derive(Clone, Debug)]
+struct PartitionedConvolver {
+    plan: FftPlan,
+    partitions: Vec<Vec<Complex>>,
+    delay_line: Vec<Vec<Complex>>,
+    delay_line_index: usize,
+    input: Vec<f64>,
+    output: Vec<f64>,
+    position: usize,
+    accumulator: Vec<Complex>,
+}
+
+impl PartitionedConvolver {
+    fn new<T: Sample>(impulse_response: &[T], plan: FftPlan) -> Self {
+        let fft_size = plan.size();
+        let block_size = fft_size / 2;
+        let num_partitions = impulse_response.len().div_ceil(block_size).max(1);
+
+        let partitions = (0..num_partitions)
+            .map(|partition| {
+                let mut spectrum = vec![Complex::default(); fft_size];
+                let start = (partition * block_size).min(impulse_response.len());
+                let end = (start + block_size).min(impulse_response.len());
+                for (bin, sample) in spectrum.iter_mut().zip(&impulse_response[start..end]) {
+                    bin.re = sample.to_f64();
+                }
+                plan.forward(&mut spectrum);
+                spectrum
+            })
+            .collect();
+
+        Self {
+            plan,
+            partitions,
+            delay_line: vec![vec![Complex::default(); fft_size]; num_partitions],
+            delay_line_index: 0,
+            input: vec![0.0; fft_size],
+            output: vec![0.0; block_size],
+            position: 0,
+            accumulator: vec![Complex::default(); fft_size],
+        }
+    }
+
+    fn reset(&mut self) {
+        self.delay_line
+            .iter_mut()
+            .for_each(|s| s.fill(Complex::default()));
+        self.input.fill(0.0);
+        self.output.fill(0.0);
+        self.position = 0;
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let block_size = self.output.len();
+        let output = self.output[self.position];
+        self.input[block_size + self.position] = input;
+        self.position += 1;
+
+        if self.position == block_size {
+            self.process_partition();
+            self.position = 0;
+        }
+
+        output
+    }
+
+    fn process_partition(&mut self) {
+        let block_size = self.output.len();
+        let num_partitions = self.partitions.len();
+
+        let spectrum = &mut self.delay_line[self.delay_line_index];
+        for (bin, sample) in spectrum.iter_mut().zip(self.input.iter()) {
+            *bin = Complex::new(*sample, 0.0);
+        }
+        self.plan.forward(spectrum);
+
+        self.accumulator.fill(Complex::default());
+        for (age, partition) in self.partitions.iter().enumerate() {
+            let index = (self.delay_line_index + num_partitions - age) % num_partitions;
+            let spectrum = &self.delay_line[index];
+            for ((sum, x), h) in self.accumulator.iter_mut().zip(spectrum).zip(partition) {
+                *sum += *x * *h;
+            }
+        }
+        self.delay_line_index = (self.delay_line_index + 1) % num_partitions;
+
+        self.plan.inverse(&mut self.accumulator);
+        for (out, value) in self.output.iter_mut().zip(&self.accumulator[block_size..]) {
+            *out = value.re;
+        }
+
+        self.input.copy_within(block_size.., 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mono(samples: &[f64]) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(samples.len()));
+        buffer.chan_mut(0).copy_from_slice(samples);
+        buffer
+    }
+
+    #[test]
+    fn matches_direct_convolution_after_one_block() {
+        let impulse_response: Vec<f64> = (0..37).map(|n| (n as f64 * 0.37).cos() / 4.0).collect();
+        let input: Vec<f64> = (0..200).map(|n| (n as f64 * 0.11).sin()).collect();
+        let block_size = 8;
+
+        let mut convolver = Convolver::new(
+            &mono(&impulse_response),
+            Channels::from(1),
+            Samples::from(block_size),
+        );
+        let mut buffer = mono(&input);
+        convolver.process(&mut buffer);
+
+        for n in 0..input.len() - block_size {
+            let expected: f64 = (0..=n)
+                .filter(|k| n - k < impulse_response.len())
+                .map(|k| input[k] * impulse_response[n - k])
+                .sum();
+            assert!((buffer.chan(0)[n + block_size] - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn uses_matching_impulse_response_channel() {
+        let mut impulse_response = Buffer::<f32>::allocate(Channels::from(2), Samples::from(4));
+        impulse_response.chan_mut(0)[0] = 1.0;
+        impulse_response.chan_mut(1)[0] = 0.5;
+        let mut convolver = Convolver::new(&impulse_response, Channels::from(2), Samples::from(4));
+
+        let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(8));
+        buffer.map_samples(|_| 1.0);
+        convolver.process(&mut buffer);
+
+        assert!((buffer.chan(0)[4] - 1.0).abs() < 1e-6);
+        assert!((buffer.chan(1)[4] - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn reports_one_block_of_latency() {
+        let convolver = Convolver::new(&mono(&[1.0]), Channels::from(1), Samples::from(128));
+        assert_eq!(
+            convolver.latency(SampleRate::from(128)),
+            Latency::from_secs_f64(1.0)
+        );
+    }
+}
@@ -0,0 +1,405 @@
+//! This module contains a convolution reverb, which places audio in the space an impulse
+//! response was recorded in. The impulse response can come from a WAV file or a buffer, and is
+//! resampled when it was recorded at another sample rate than the session. The reverb runs on
+//! the partitioned convolution engine, and has a pre-delay and a dry/wet mix. The dry signal is
+//! delayed along with the engine, so both stay aligned and the latency is one block.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::convolution_reverb::ConvolutionReverb;
+//! use rabu::units::{Channels, NormalizedValue, SampleRate, Samples, Seconds};
+//!
+//! // A decaying noise burst as an impulse response, recorded at 44.1 kHz.
+//! let mut impulse_response = Buffer::<f32>::allocate(Channels::from(1), Samples::from(22050));
+//! for (n, sample) in impulse_response.chan_mut(0).iter_mut().enumerate() {
+//!     *sample = (n as f32 * 0.37).sin() * (-(n as f32) / 4000.0).exp();
+//! }
+//!
+//! let mut reverb = ConvolutionReverb::new(
+//!     &impulse_response,
+//!     SampleRate::from(44100),
+//!     SampleRate::from(48000),
+//!     Channels::from(2),
+//!     Samples::from(256),
+//! );
+//! reverb.set_mix(NormalizedValue::from(0.3));
+//! reverb.set_pre_delay(Seconds::from(0.02));
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! reverb.process(&mut buffer);
+//! ```
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::buffer::Buffer;
+use crate::convolution::Convolver;
+use crate::delay::DelayLine;
+use crate::preset::Preset;
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::resample::{resample, ResampleQuality};
+use crate::sample::Sample;
+use crate::units::{
+    BufferSize, Channels, Latency, NormalizedValue, SampleRate, Samples, SamplesF64, Seconds,
+};
+use crate::varispeed::Interpolation;
+use crate::wav::WavFile;
+
+/// The longest pre-delay in seconds.
+pub const MAX_PRE_DELAY: f64 = 0.5;
+
+/// A reverb that convolves audio with an impulse response.
+#[derive(Clone, Debug)]
+pub struct ConvolutionReverb {
+    /// The impulse response at its own sample rate, to convert it again when the sample rate
+    /// changes.
+    impulse_response: Buffer<f32>,
+    impulse_response_sample_rate: SampleRate,
+    sample_rate: SampleRate,
+    block_size: Samples,
+    convolver: Convolver,
+    dry: Vec<DelayLine>,
+    pre_delays: Vec<DelayLine>,
+    pre_delay: Seconds,
+    mix: NormalizedValue,
+}
+
+impl ConvolutionReverb {
+    /// Creates a new fully wet reverb without pre-delay, for the impulse response that was
+    /// recorded at `impulse_response_sample_rate`, processing partitions of `block_size`
+    /// samples.
+    /// This will panic if the block size is not a power of two, or if the impulse response is
+    /// not mono and doesn't have `num_channels` channels.
+    pub fn new<T: Sample>(
+        impulse_response: &Buffer<T>,
+        impulse_response_sample_rate: SampleRate,
+        sample_rate: SampleRate,
+        num_channels: Channels,
+        block_size: Samples,
+    ) -> Self {
+        let mut stored = Buffer::allocate(
+            impulse_response.num_channels(),
+            impulse_response.num_samples(),
+        );
+        for (to, from) in stored.iter_chans_mut().zip(impulse_response.iter_chans()) {
+            for (to, from) in to.iter_mut().zip(from) {
+                *to = from.to_f64() as f32;
+            }
+        }
+        Self::from_parts(
+            stored,
+            impulse_response_sample_rate,
+            sample_rate,
+            num_channels,
+            block_size,
+        )
+    }
+
+    /// Creates a new fully wet reverb without pre-delay for the impulse response in the file.
+    /// This will panic if the block size is not a power of two, or if the file is not mono and
+    /// doesn't have `num_channels` channels.
+    pub fn from_wav(
+        file: &WavFile,
+        sample_rate: SampleRate,
+        num_channels: Channels,
+        block_size: Samples,
+    ) -> Self {
+        Self::from_parts(
+            file.audio().clone(),
+            file.sample_rate(),
+            sample_rate,
+            num_channels,
+            block_size,
+        )
+    }
+
+    fn from_parts(
+        impulse_response: Buffer<f32>,
+        impulse_response_sample_rate: SampleRate,
+        sample_rate: SampleRate,
+        num_channels: Channels,
+        block_size: Samples,
+    ) -> Self {
+        let convolver = if impulse_response_sample_rate == sample_rate {
+            Convolver::new(&impulse_response, num_channels, block_size)
+        } else {
+            let resampled = resample(
+                &impulse_response,
+                impulse_response_sample_rate,
+                sample_rate,
+                ResampleQuality::default(),
+            );
+            Convolver::new(&resampled, num_channels, block_size)
+        };
+        let max_pre_delay = Seconds::from(MAX_PRE_DELAY).to_samples(sample_rate);
+
+        Self {
+            impulse_response,
+            impulse_response_sample_rate,
+            sample_rate,
+            block_size,
+            convolver,
+            dry: (0..num_channels.as_usize())
+                .map(|_| DelayLine::new(block_size, Interpolation::None))
+                .collect(),
+            pre_delays: (0..num_channels.as_usize())
+                .map(|_| DelayLine::new(max_pre_delay, Interpolation::Linear))
+                .collect(),
+            pre_delay: Seconds::from(0.0),
+            mix: NormalizedValue::from(1.0),
+        }
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        self.convolver.num_channels()
+    }
+
+    /// Returns the sample rate.
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// Returns the mix, from 0 (only the dry signal) to 1 (only the reverb).
+    pub fn mix(&self) -> NormalizedValue {
+        self.mix
+    }
+
+    /// Sets the mix, from 0 (only the dry signal) to 1 (only the reverb).
+    pub fn set_mix(&mut self, mix: NormalizedValue) {
+        self.mix = mix;
+    }
+
+    /// Returns the time between the dry signal and the start of the reverb.
+    pub fn pre_delay(&self) -> Seconds {
+        self.pre_delay
+    }
+
+    /// Sets the time between the dry signal and the start of the reverb.
+    /// This will panic if the pre-delay is negative or longer than `MAX_PRE_DELAY`.
+    pub fn set_pre_delay(&mut self, pre_delay: Seconds) {
+        assert!(
+            (0.0..=MAX_PRE_DELAY).contains(&pre_delay.as_f64()),
+            "the pre-delay must be between 0 and {MAX_PRE_DELAY} seconds"
+        );
+        self.pre_delay = pre_delay;
+    }
+
+    /// Returns the latency of the convolution engine, which is one block.
+    pub fn latency(&self) -> Latency {
+        self.convolver.latency(self.sample_rate)
+    }
+
+    /// Clears the tail of the reverb.
+    pub fn reset(&mut self) {
+        self.convolver.reset();
+        for line in self.dry.iter_mut().chain(&mut self.pre_delays) {
+            line.reset();
+        }
+    }
+
+    /// Adds the reverb to the buffer in place. The buffer can have any length.
+    /// This will panic if the buffer doesn't have the same number of channels as the reverb.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+        let pre_delay = SamplesF64::from(self.pre_delay.as_f64() * self.sample_rate.as_f64());
+        let wet_gain = self.mix.as_f64();
+        let dry_gain = 1.0 - wet_gain;
+
+        for (channel, samples) in buffer.iter_chans_mut().enumerate() {
+            let dry = &mut self.dry[channel];
+            let pre_delay_line = &mut self.pre_delays[channel];
+            for sample in samples.iter_mut() {
+                let input = sample.to_f64();
+                dry.push(input);
+                pre_delay_line.push(input);
+                let wet = self
+                    .convolver
+                    .process_sample(channel, pre_delay_line.read_fractional(pre_delay));
+                let output = dry_gain * dry.read(self.block_size) + wet_gain * wet;
+                *sample = T::from_f64(output);
+            }
+        }
+    }
+}
+
+impl AudioProcessor for ConvolutionReverb {
+    /// Converts the impulse response to the sample rate, and clears the tail of the reverb.
+    fn prepare(&mut self, sample_rate: SampleRate, _: BufferSize, num_channels: Channels) {
+        let mut reverb = Self::from_parts(
+            self.impulse_response.clone(),
+            self.impulse_response_sample_rate,
+            sample_rate,
+            num_channels,
+            self.block_size,
+        );
+        reverb.mix = self.mix;
+        reverb.pre_delay = self.pre_delay;
+        *self = reverb;
+    }
+
+    fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+        ConvolutionReverb::process(self, buffer);
+    }
+
+    fn latency(&self) -> Latency {
+        ConvolutionReverb::latency(self)
+    }
+
+    fn reset(&mut self) {
+        ConvolutionReverb::reset(self);
+    }
+}
+
+/// The settings of a `ConvolutionReverb`, for presets. The impulse response isn't part of
+/// them, as it is audio rather than a setting.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct ConvolutionReverbSettings {
+    pub mix: NormalizedValue,
+    pub pre_delay: Seconds,
+}
+
+impl Default for ConvolutionReverbSettings {
+    fn default() -> Self {
+        Self {
+            mix: NormalizedValue::from(1.0),
+            pre_delay: Seconds::from(0.0),
+        }
+    }
+}
+
+impl Preset for ConvolutionReverb {
+    type Settings = ConvolutionReverbSettings;
+    const PROCESSOR: &'static str = "convolution reverb";
+    const VERSION: u32 = 1;
+
+    fn settings(&self) -> ConvolutionReverbSettings {
+        ConvolutionReverbSettings {
+            mix: self.mix,
+            pre_delay: self.pre_delay,
+        }
+    }
+
+    /// This will panic if the pre-delay is negative or longer than `MAX_PRE_DELAY`.
+    fn apply_settings(&mut self, settings: &ConvolutionReverbSettings) {
+        self.set_mix(settings.mix);
+        self.set_pre_delay(settings.pre_delay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_SIZE: usize = 64;
+
+    /// A mono impulse response with an impulse at the given position.
+    fn impulse_at(position: usize) -> Buffer<f32> {
+        let mut impulse_response = Buffer::allocate(Channels::from(1), Samples::from(256));
+        impulse_response.chan_mut(0)[position] = 1.0;
+        impulse_response
+    }
+
+    fn reverb(
+        impulse_response: &Buffer<f32>,
+        impulse_response_sample_rate: u32,
+    ) -> ConvolutionReverb {
+        ConvolutionReverb::new(
+            impulse_response,
+            SampleRate::from(impulse_response_sample_rate),
+            SampleRate::from(48000),
+            Channels::from(2),
+            Samples::from(BLOCK_SIZE),
+        )
+    }
+
+    /// Processes an impulse, and returns where the output of the first channel peaks.
+    fn peak(reverb: &mut ConvolutionReverb) -> (usize, f32) {
+        let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(1024));
+        buffer.chan_mut(0)[0] = 1.0;
+        reverb.process(&mut buffer);
+        buffer
+            .chan(0)
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+            .unwrap()
+    }
+
+    #[test]
+    fn wet_signal_is_delayed_by_the_latency() {
+        let mut reverb = reverb(&impulse_at(10), 48000);
+
+        assert_eq!(peak(&mut reverb), (BLOCK_SIZE + 10, 1.0));
+    }
+
+    #[test]
+    fn dry_signal_is_aligned_with_the_wet_signal() {
+        let mut reverb = reverb(&impulse_at(0), 48000);
+        reverb.set_mix(NormalizedValue::from(0.0));
+
+        assert_eq!(peak(&mut reverb), (BLOCK_SIZE, 1.0));
+        assert_eq!(
+            reverb.latency(),
+            Latency::from(Samples::from(BLOCK_SIZE).to_seconds(SampleRate::from(48000)))
+        );
+    }
+
+    #[test]
+    fn pre_delay_delays_the_wet_signal() {
+        let mut reverb = reverb(&impulse_at(0), 48000);
+        reverb.set_pre_delay(Seconds::from(0.005));
+
+        assert_eq!(peak(&mut reverb).0, BLOCK_SIZE + 240);
+    }
+
+    #[test]
+    fn impulse_response_is_resampled_to_the_sample_rate() {
+        let mut reverb = reverb(&impulse_at(50), 24000);
+
+        assert_eq!(peak(&mut reverb).0, BLOCK_SIZE + 100);
+    }
+
+    #[test]
+    fn reads_the_impulse_response_from_a_wav_file() {
+        let file = WavFile::new(
+            impulse_at(20),
+            SampleRate::from(48000),
+            crate::quantize::SampleFormat::Float32,
+        );
+        let mut reverb = ConvolutionReverb::from_wav(
+            &file,
+            SampleRate::from(48000),
+            Channels::from(2),
+            Samples::from(BLOCK_SIZE),
+        );
+
+        assert_eq!(peak(&mut reverb), (BLOCK_SIZE + 20, 1.0));
+    }
+
+    #[test]
+    fn prepare_keeps_the_settings() {
+        let mut reverb = reverb(&impulse_at(0), 48000);
+        reverb.set_mix(NormalizedValue::from(0.25));
+        reverb.set_pre_delay(Seconds::from(0.1));
+
+        AudioProcessor::prepare(
+            &mut reverb,
+            SampleRate::from(96000),
+            BufferSize::from(128),
+            Channels::from(1),
+        );
+
+        assert_eq!(reverb.mix(), NormalizedValue::from(0.25));
+        assert_eq!(reverb.pre_delay(), Seconds::from(0.1));
+        assert_eq!(reverb.num_channels(), Channels::from(1));
+        assert_eq!(reverb.sample_rate(), SampleRate::from(96000));
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_long_pre_delay_panics() {
+        reverb(&impulse_at(0), 48000).set_pre_delay(Seconds::from(1.0));
+    }
+}
@@ -0,0 +1,311 @@
+//! This module contains Linkwitz-Riley crossovers, which split audio into frequency bands that
+//! add back up to a flat magnitude response, e.g. for multiband dynamics or EQ. A `Crossover`
+//! splits into a low and a high band with 24 dB/octave slopes, and a `MultibandSplitter`
+//! chains them into 2 to 5 bands. The lower bands are passed through the all pass filters that
+//! the crossovers above them add to the higher bands, so all bands stay in phase and their sum
+//! is an all pass version of the input.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::crossover::{combine, MultibandSplitter};
+//! use rabu::units::{Channels, Frequency, SampleRate, Samples};
+//!
+//! let channels = Channels::from(2);
+//! let mut splitter = MultibandSplitter::new(
+//!     SampleRate::from(48000),
+//!     &[Frequency::from(200.0), Frequency::from(2000.0)],
+//!     channels,
+//! );
+//!
+//! let input = Buffer::<f32>::allocate(channels, Samples::from(512));
+//! let mut bands = vec![Buffer::allocate(channels, Samples::from(512)); splitter.num_bands()];
+//! splitter.split(&input, &mut bands);
+//!
+//! // Compress, saturate or EQ the bands here.
+//! bands[0].map_samples(|sample| sample * 0.5);
+//!
+//! let mut output = Buffer::allocate(channels, Samples::from(512));
+//! combine(&bands, &mut output);
+//! ```
+
+use std::f64::consts::FRAC_1_SQRT_2;
+
+use crate::biquad::{
+    all_pass_coefficients, resonant_high_pass_coefficients, resonant_low_pass_coefficients,
+    MultiBiquad,
+};
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::units::{Channels, Frequency, SampleRate};
+
+/// The largest number of bands a `MultibandSplitter` can split into.
+pub const MAX_BANDS: usize = 5;
+
+/// A 4th order Linkwitz-Riley crossover, which is two Butterworth filters in series on either
+/// side. The low and high band are both 6 dB down at the crossover frequency, and add up to an
+/// all pass response.
+#[derive(Clone, Debug)]
+pub struct Crossover {
+    frequency: Frequency,
+    low: [MultiBiquad; 2],
+    high: [MultiBiquad; 2],
+}
+
+impl Crossover {
+    /// Creates a new crossover at the given frequency for the given number of channels.
+    /// This will panic if the frequency is not between 0 and the Nyquist frequency.
+    pub fn new(sample_rate: SampleRate, frequency: Frequency, num_channels: Channels) -> Self {
+        assert!(
+            frequency.as_f64() > 0.0 && frequency.as_f64() < sample_rate.as_f64() / 2.0,
+            "the crossover frequency must be between 0 and the Nyquist frequency"
+        );
+        let low = resonant_low_pass_coefficients(sample_rate, frequency, FRAC_1_SQRT_2);
+        let high = resonant_high_pass_coefficients(sample_rate, frequency, FRAC_1_SQRT_2);
+        Self {
+            frequency,
+            low: [
+                MultiBiquad::new(low, num_channels),
+                MultiBiquad::new(low, num_channels),
+            ],
+            high: [
+                MultiBiquad::new(high, num_channels),
+                MultiBiquad::new(high, num_channels),
+            ],
+        }
+    }
+
+    /// Returns the crossover frequency.
+    pub fn frequency(&self) -> Frequency {
+        self.frequency
+    }
+
+    /// Clears the state of the filters.
+    pub fn reset(&mut self) {
+        for filter in self.low.iter_mut().chain(&mut self.high) {
+            filter.reset();
+        }
+    }
+
+    /// Splits the input into a low and a high band.
+    /// This will panic if the buffers don't have the same size, or another number of channels
+    /// than the crossover.
+    pub fn split<T: Sample>(
+        &mut self,
+        input: &Buffer<T>,
+        low: &mut Buffer<T>,
+        high: &mut Buffer<T>,
+    ) {
+        input.copy_into(low);
+        self.split_in_place(low, high);
+    }
+
+    /// Splits the input in the low buffer into both buffers.
+    fn split_in_place<T: Sample>(&mut self, low: &mut Buffer<T>, high: &mut Buffer<T>) {
+        low.copy_into(high);
+        for filter in &mut self.low {
+            filter.process(low);
+        }
+        for filter in &mut self.high {
+            filter.process(high);
+        }
+    }
+}
+
+/// Splits audio into 2 to 5 bands with a chain of crossovers.
+#[derive(Clone, Debug)]
+pub struct MultibandSplitter {
+    crossovers: Vec<Crossover>,
+    /// The all pass filters of the crossovers above every band.
+    compensation: Vec<Vec<MultiBiquad>>,
+}
+
+impl MultibandSplitter {
+    /// Creates a new splitter with a band between every two crossover frequencies, and one
+    /// below the first and above the last.
+    /// This will panic if there are not 1 to 4 frequencies, if they are not rising, or if one
+    /// is not between 0 and the Nyquist frequency.
+    pub fn new(sample_rate: SampleRate, frequencies: &[Frequency], num_channels: Channels) -> Self {
+        assert!(
+            (1..MAX_BANDS).contains(&frequencies.len()),
+            "a splitter needs 1 to 4 crossover frequencies"
+        );
+        assert!(
+            frequencies.windows(2).all(|pair| pair[0] < pair[1]),
+            "the crossover frequencies must be rising"
+        );
+
+        let crossovers = frequencies
+            .iter()
+            .map(|&frequency| Crossover::new(sample_rate, frequency, num_channels))
+            .collect();
+        let compensation = (0..=frequencies.len())
+            .map(|band| {
+                frequencies
+                    .iter()
+                    .skip(band + 1)
+                    .map(|&frequency| {
+                        let coefficients =
+                            all_pass_coefficients(sample_rate, frequency, FRAC_1_SQRT_2);
+                        MultiBiquad::new(coefficients, num_channels)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            crossovers,
+            compensation,
+        }
+    }
+
+    /// Returns the number of bands.
+    pub fn num_bands(&self) -> usize {
+        self.crossovers.len() + 1
+    }
+
+    /// Returns the crossover frequencies.
+    pub fn frequencies(&self) -> Vec<Frequency> {
+        self.crossovers.iter().map(Crossover::frequency).collect()
+    }
+
+    /// Clears the state of the filters.
+    pub fn reset(&mut self) {
+        for crossover in &mut self.crossovers {
+            crossover.reset();
+        }
+        for filter in self.compensation.iter_mut().flatten() {
+            filter.reset();
+        }
+    }
+
+    /// Splits the input into the bands, from low to high.
+    /// This will panic if the number of bands is wrong, or if the buffers don't have the same
+    /// size, or another number of channels than the splitter.
+    pub fn split<T: Sample>(&mut self, input: &Buffer<T>, bands: &mut [Buffer<T>]) {
+        assert_eq!(bands.len(), self.num_bands(), "wrong number of bands");
+
+        input.copy_into(&mut bands[0]);
+        for (index, crossover) in self.crossovers.iter_mut().enumerate() {
+            let (lower, higher) = bands.split_at_mut(index + 1);
+            crossover.split_in_place(&mut lower[index], &mut higher[0]);
+        }
+
+        for (band, filters) in bands.iter_mut().zip(&mut self.compensation) {
+            for filter in filters {
+                filter.process(band);
+            }
+        }
+    }
+}
+
+/// Sums the bands into the output.
+/// This will panic if the buffers don't have the same size.
+pub fn combine<T: Sample>(bands: &[Buffer<T>], output: &mut Buffer<T>) {
+    output.fill_default();
+    for band in bands {
+        assert_eq!(band.num_channels(), output.num_channels());
+        assert_eq!(band.num_samples(), output.num_samples());
+        for (output, band) in output.iter_chans_mut().zip(band.iter_chans()) {
+            for (output, sample) in output.iter_mut().zip(band) {
+                *output = T::from_f64(output.to_f64() + sample.to_f64());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Samples;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn sine(frequency: f64) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(2), Samples::from(SAMPLE_RATE));
+        for channel in buffer.iter_chans_mut() {
+            for (n, sample) in channel.iter_mut().enumerate() {
+                *sample = (2.0 * PI * frequency * n as f64 / SAMPLE_RATE as f64).sin();
+            }
+        }
+        buffer
+    }
+
+    /// The RMS of the second half of the first channel, after the filters have settled.
+    fn rms(buffer: &Buffer<f64>) -> f64 {
+        let samples = &buffer.chan(0)[SAMPLE_RATE as usize / 2..];
+        (samples.iter().map(|sample| sample * sample).sum::<f64>() / samples.len() as f64).sqrt()
+    }
+
+    fn split(frequencies: &[f64], input: &Buffer<f64>) -> Vec<Buffer<f64>> {
+        let frequencies: Vec<Frequency> = frequencies.iter().map(|&f| Frequency::from(f)).collect();
+        let mut splitter = MultibandSplitter::new(
+            SampleRate::from(SAMPLE_RATE),
+            &frequencies,
+            Channels::from(2),
+        );
+        let mut bands = vec![input.clone(); splitter.num_bands()];
+        splitter.split(input, &mut bands);
+        bands
+    }
+
+    #[test_case(&[1000.0], 50.0; "two bands low")]
+    #[test_case(&[1000.0], 1000.0; "two bands at the crossover")]
+    #[test_case(&[200.0, 2000.0], 632.0; "three bands between crossovers")]
+    #[test_case(&[100.0, 500.0, 2000.0, 8000.0], 2000.0; "five bands at a crossover")]
+    #[test_case(&[100.0, 500.0, 2000.0, 8000.0], 15000.0; "five bands high")]
+    fn bands_sum_to_unity(frequencies: &[f64], frequency: f64) {
+        let input = sine(frequency);
+        let bands = split(frequencies, &input);
+        let mut output = input.clone();
+
+        combine(&bands, &mut output);
+
+        assert!((rms(&output) / rms(&input) - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn bands_separate_frequencies() {
+        let frequencies = [200.0, 2000.0];
+
+        for (frequency, loudest) in [(50.0, 0), (632.0, 1), (8000.0, 2)] {
+            let bands = split(&frequencies, &sine(frequency));
+            let levels: Vec<f64> = bands.iter().map(rms).collect();
+            for (band, level) in levels.iter().enumerate() {
+                if band != loudest {
+                    assert!(*level < levels[loudest] * 0.1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn crossover_bands_are_6_db_down_at_the_crossover() {
+        let input = sine(1000.0);
+        let mut crossover = Crossover::new(
+            SampleRate::from(SAMPLE_RATE),
+            Frequency::from(1000.0),
+            Channels::from(2),
+        );
+        let (mut low, mut high) = (input.clone(), input.clone());
+
+        crossover.split(&input, &mut low, &mut high);
+
+        assert!((rms(&low) / rms(&input) - 0.5).abs() < 0.001);
+        assert!((rms(&high) / rms(&input) - 0.5).abs() < 0.001);
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_bands_panics() {
+        split(&[100.0, 200.0, 400.0, 800.0, 1600.0], &sine(100.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn falling_frequencies_panic() {
+        split(&[2000.0, 200.0], &sine(100.0));
+    }
+}
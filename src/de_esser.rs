@@ -0,0 +1,319 @@
+//! This module contains a de-esser, which tames the sharp "s" and "sh" sounds of vocals. It is a
+//! compressor with a filtered sidechain: the detector only hears the band where the sibilance
+//! is, so the compressor only turns the audio down while an "s" sounds, and at most by the
+//! range.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::de_esser::{DeEsser, Detector};
+//! use rabu::units::{Channels, Decibels, Frequency, SampleRate, Samples};
+//!
+//! let mut de_esser = DeEsser::new(SampleRate::from(48000), Channels::from(2));
+//! de_esser.set_frequency(Frequency::from(7000.0));
+//! de_esser.set_threshold(Decibels::from(-30.0));
+//! de_esser.set_range(Decibels::from(-8.0));
+//! de_esser.set_detector(Detector::HighShelf);
+//!
+//! let mut vocals = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! de_esser.process(&mut vocals);
+//!
+//! assert_eq!(de_esser.gain_reduction(), Decibels::from(0.0));
+//! ```
+
+use crate::biquad::{
+    high_shelf_coefficients, resonant_band_pass_coefficients, BiquadCoefficients, MultiBiquad,
+};
+use crate::buffer::Buffer;
+use crate::dynamics::Compressor;
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::sample::Sample;
+use crate::units::{
+    BufferSize, Channels, Decibels, Frequency, Ratio, SampleRate, Samples, Seconds,
+};
+
+/// The filter that picks the sibilance out of the sidechain.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Detector {
+    /// Only listens to the band around the frequency.
+    BandPass,
+    /// Listens to everything, but gives the frequencies above the frequency 12 dB more weight.
+    HighShelf,
+}
+
+/// A compressor that reacts to the sibilance in the audio.
+#[derive(Clone, Debug)]
+pub struct DeEsser {
+    sample_rate: SampleRate,
+    frequency: Frequency,
+    detector: Detector,
+    compressor: Compressor,
+    filter: MultiBiquad,
+    sidechain: Buffer<f64>,
+}
+
+impl DeEsser {
+    /// The ratio of the compressor, which is steep so the sibilance hardly goes over the
+    /// threshold.
+    pub const RATIO: f64 = 10.0;
+
+    /// The Q of the band pass detector.
+    pub const BAND_PASS_Q: f64 = 2.0;
+
+    /// The boost of the high shelf detector.
+    pub const SHELF_GAIN: f64 = 12.0;
+
+    /// Creates a new de-esser with a band pass detector at 6 kHz, a threshold of -24 dB and a
+    /// range of -12 dB, which reacts within 1 ms and recovers in 50 ms.
+    pub fn new(sample_rate: SampleRate, num_channels: Channels) -> Self {
+        let frequency = Frequency::from(6000.0);
+        let detector = Detector::BandPass;
+        let mut compressor =
+            Compressor::new(Decibels::from(-24.0), Ratio::from(Self::RATIO), sample_rate);
+        compressor.set_range(Decibels::from(-12.0));
+        compressor.set_attack(Seconds::from(0.001));
+        compressor.set_release(Seconds::from(0.05));
+        Self {
+            sample_rate,
+            frequency,
+            detector,
+            compressor,
+            filter: MultiBiquad::new(
+                detector_coefficients(detector, sample_rate, frequency),
+                num_channels,
+            ),
+            sidechain: Buffer::allocate(num_channels, Samples::from(0)),
+        }
+    }
+
+    /// Returns the frequency of the sibilance.
+    pub fn frequency(&self) -> Frequency {
+        self.frequency
+    }
+
+    /// Changes the frequency the detector listens to, typically between 4 and 10 kHz.
+    pub fn set_frequency(&mut self, frequency: Frequency) {
+        self.frequency = frequency;
+        self.update_filter();
+    }
+
+    /// Returns the filter of the detector.
+    pub fn detector(&self) -> Detector {
+        self.detector
+    }
+
+    /// Changes the filter of the detector.
+    pub fn set_detector(&mut self, detector: Detector) {
+        self.detector = detector;
+        self.update_filter();
+    }
+
+    /// Returns the threshold.
+    pub fn threshold(&self) -> Decibels {
+        self.compressor.threshold()
+    }
+
+    /// Changes the level of the filtered sidechain above which the audio is turned down.
+    pub fn set_threshold(&mut self, threshold: Decibels) {
+        self.compressor.set_threshold(threshold);
+    }
+
+    /// Returns the range.
+    pub fn range(&self) -> Decibels {
+        self.compressor.range()
+    }
+
+    /// Changes how far the audio may be turned down, e.g. -12 dB.
+    pub fn set_range(&mut self, range: Decibels) {
+        self.compressor.set_range(range);
+    }
+
+    /// Returns the largest gain reduction of the last processed buffer, as a positive number,
+    /// for metering.
+    pub fn gain_reduction(&self) -> Decibels {
+        self.compressor.gain_reduction()
+    }
+
+    /// Changes the sample rate.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = sample_rate;
+        self.compressor.set_sample_rate(sample_rate);
+        self.update_filter();
+    }
+
+    /// Clears the internal state, as if no audio was processed yet.
+    pub fn reset(&mut self) {
+        self.compressor.reset();
+        self.filter.reset();
+    }
+
+    /// De-esses the buffer, based on its own sibilance.
+    /// This will panic if the buffer doesn't have the same number of channels as the de-esser.
+    /// It only allocates when the buffer is longer than the ones before, so prepare it with
+    /// `AudioProcessor::prepare` to keep it real-time safe.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.filter.num_channels());
+        self.sidechain.set_num_samples(buffer.num_samples());
+        for (sidechain, channel) in self.sidechain.iter_chans_mut().zip(buffer.iter_chans()) {
+            for (sidechain, sample) in sidechain.iter_mut().zip(channel) {
+                *sidechain = sample.to_f64();
+            }
+        }
+        self.filter.process(&mut self.sidechain);
+        self.compressor
+            .process_with_sidechain(buffer, &self.sidechain);
+    }
+
+    /// De-esses the buffer, based on the sibilance in a separate sidechain, e.g. the dry vocal
+    /// when the buffer is its reverb. The sidechain is filtered by the detector as well.
+    /// This will panic if the sidechain doesn't have the same number of channels as the
+    /// de-esser, or the same number of samples as the buffer.
+    pub fn process_with_sidechain<T: Sample>(
+        &mut self,
+        buffer: &mut Buffer<T>,
+        sidechain: &Buffer<T>,
+    ) {
+        assert_eq!(sidechain.num_channels(), self.filter.num_channels());
+        assert_eq!(sidechain.num_samples(), buffer.num_samples());
+        self.sidechain.set_num_samples(sidechain.num_samples());
+        for (filtered, channel) in self.sidechain.iter_chans_mut().zip(sidechain.iter_chans()) {
+            for (filtered, sample) in filtered.iter_mut().zip(channel) {
+                *filtered = sample.to_f64();
+            }
+        }
+        self.filter.process(&mut self.sidechain);
+        self.compressor
+            .process_with_sidechain(buffer, &self.sidechain);
+    }
+
+    fn update_filter(&mut self) {
+        self.filter.set_coefficients(detector_coefficients(
+            self.detector,
+            self.sample_rate,
+            self.frequency,
+        ));
+    }
+}
+
+impl AudioProcessor for DeEsser {
+    fn prepare(
+        &mut self,
+        sample_rate: SampleRate,
+        buffer_size: BufferSize,
+        num_channels: Channels,
+    ) {
+        self.set_sample_rate(sample_rate);
+        if num_channels != self.filter.num_channels() {
+            self.filter = MultiBiquad::new(
+                detector_coefficients(self.detector, sample_rate, self.frequency),
+                num_channels,
+            );
+        }
+        self.sidechain = Buffer::allocate(num_channels, Samples::from(buffer_size.as_usize()));
+    }
+
+    fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+        DeEsser::process(self, buffer);
+    }
+
+    fn reset(&mut self) {
+        DeEsser::reset(self);
+    }
+}
+
+fn detector_coefficients(
+    detector: Detector,
+    sample_rate: SampleRate,
+    frequency: Frequency,
+) -> BiquadCoefficients {
+    match detector {
+        Detector::BandPass => {
+            resonant_band_pass_coefficients(sample_rate, frequency, DeEsser::BAND_PASS_Q)
+        }
+        Detector::HighShelf => high_shelf_coefficients(
+            sample_rate,
+            frequency,
+            std::f64::consts::FRAC_1_SQRT_2,
+            Decibels::from(DeEsser::SHELF_GAIN),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::signals::sine;
+
+    const SAMPLE_RATE: usize = 48000;
+
+    fn tone(frequency: f64, amplitude: f64) -> Buffer<f32> {
+        let mut buffer = sine(
+            Channels::from(1),
+            Samples::from(SAMPLE_RATE / 10),
+            Frequency::from(frequency),
+            SampleRate::from(SAMPLE_RATE),
+        );
+        buffer.map_samples(|sample: f32| sample * amplitude as f32);
+        buffer
+    }
+
+    fn peak(samples: &[f32]) -> f64 {
+        samples.iter().fold(0.0, |peak, s| peak.max(s.abs() as f64))
+    }
+
+    #[test_case(Detector::BandPass)]
+    #[test_case(Detector::HighShelf)]
+    fn turns_down_sibilance(detector: Detector) {
+        let mut de_esser = DeEsser::new(SampleRate::from(SAMPLE_RATE), Channels::from(1));
+        de_esser.set_detector(detector);
+        let mut sibilance = tone(6000.0, 0.5);
+
+        de_esser.process(&mut sibilance);
+
+        let level = Decibels::from_gain(peak(&sibilance.chan(0)[2400..])).as_f64();
+        assert!((level - (-6.02 - 12.0)).abs() < 0.5);
+        assert!((de_esser.gain_reduction().as_f64() - 12.0).abs() < 0.2);
+    }
+
+    #[test]
+    fn leaves_the_voice_alone() {
+        let mut de_esser = DeEsser::new(SampleRate::from(SAMPLE_RATE), Channels::from(1));
+        let mut voice = tone(200.0, 0.5);
+
+        de_esser.process(&mut voice);
+
+        assert!(de_esser.gain_reduction().as_f64() < 0.5);
+        assert!((peak(&voice.chan(0)[2400..]) - 0.5).abs() < 0.03);
+    }
+
+    #[test]
+    fn sibilance_in_the_sidechain_turns_down_the_buffer() {
+        let mut de_esser = DeEsser::new(SampleRate::from(SAMPLE_RATE), Channels::from(1));
+        de_esser.set_range(Decibels::from(-6.0));
+        let mut reverb = tone(200.0, 0.5);
+
+        de_esser.process_with_sidechain(&mut reverb, &tone(6000.0, 0.5));
+
+        let level = Decibels::from_gain(peak(&reverb.chan(0)[2400..])).as_f64();
+        assert!((level - (-6.02 - 6.0)).abs() < 0.5);
+    }
+
+    #[test]
+    fn prepare_allocates_the_sidechain() {
+        let mut de_esser = DeEsser::new(SampleRate::from(SAMPLE_RATE), Channels::from(1));
+        de_esser.prepare(
+            SampleRate::from(44100),
+            BufferSize::from(256),
+            Channels::from(2),
+        );
+
+        let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(256));
+        AudioProcessor::process(
+            &mut de_esser,
+            &mut buffer,
+            &ProcessContext::new(SampleRate::from(44100)),
+        );
+
+        assert!(buffer.is_default_filled());
+    }
+}
@@ -0,0 +1,303 @@
+//! This module contains decay analysis of impulse responses, which gives the reverberation time
+//! (RT60) and early decay time (EDT) of a room, as defined by ISO 3382. The squared impulse
+//! response is integrated backwards (Schroeder integration) into a smooth energy decay curve,
+//! and a line is fitted to a part of that curve: EDT from 0 to -10 dB, T20 from -5 to -25 dB
+//! and T30 from -5 to -35 dB, each extrapolated to the time a decay of 60 dB would take. The
+//! analysis can be done per octave or third-octave band with a `FilterBank`.
+//! ```rust
+//! use rabu::decay::{band_decay_times, decay_times};
+//! use rabu::filterbank::BandResolution;
+//! use rabu::signals::white_noise;
+//! use rabu::units::{Channels, Frequency, SampleRate, Samples};
+//!
+//! // Noise that decays 60 dB in half a second, like the tail of a room.
+//! let sample_rate = SampleRate::from(48000);
+//! let mut impulse_response = white_noise::<f32>(Channels::from(1), Samples::from(48000), 1);
+//! for (n, sample) in impulse_response.chan_mut(0).iter_mut().enumerate() {
+//!     *sample *= 10.0_f32.powf(-3.0 * n as f32 / 48000.0 / 0.5);
+//! }
+//!
+//! let times = decay_times(&impulse_response, 0, sample_rate);
+//! let rt60 = times.rt60().unwrap();
+//! assert!((rt60.as_f64() - 0.5).abs() < 0.01);
+//!
+//! let bands = band_decay_times(
+//!     &impulse_response,
+//!     0,
+//!     sample_rate,
+//!     BandResolution::Octave,
+//!     Frequency::from(125.0),
+//!     Frequency::from(8000.0),
+//! );
+//! assert_eq!(bands.len(), 7);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::dynamics::SILENCE_DB;
+use crate::filterbank::{BandResolution, FilterBank};
+use crate::sample::Sample;
+use crate::units::{Channels, Decibels, Frequency, SampleRate, Samples, Seconds};
+
+/// How far below its peak the impulse response starts, as defined by ISO 3382.
+const ONSET_DB: f64 = -20.0;
+
+/// The decay times of an impulse response. A time is `None` when the decay doesn't get low
+/// enough to measure it, e.g. because the noise floor of the measurement is too high.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DecayTimes {
+    /// The early decay time, from the first 10 dB of the decay.
+    pub edt: Option<Seconds>,
+    /// The reverberation time from the decay between -5 and -25 dB.
+    pub t20: Option<Seconds>,
+    /// The reverberation time from the decay between -5 and -35 dB.
+    pub t30: Option<Seconds>,
+}
+
+impl DecayTimes {
+    /// Returns the reverberation time: T30, or T20 when the decay isn't deep enough for T30.
+    pub fn rt60(&self) -> Option<Seconds> {
+        self.t30.or(self.t20)
+    }
+}
+
+/// The decay times of one band of an impulse response.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BandDecayTimes {
+    pub center: Frequency,
+    pub times: DecayTimes,
+}
+
+/// Returns the energy decay curve of the impulse response: the energy that is left from every
+/// sample on, relative to the total energy. Silence gives a curve at the lowest level.
+pub fn energy_decay_curve<T: Sample>(impulse_response: &[T]) -> Vec<Decibels> {
+    let mut remaining = 0.0;
+    let mut curve: Vec<f64> = impulse_response
+        .iter()
+        .rev()
+        .map(|sample| {
+            remaining += sample.to_f64().powi(2);
+            remaining
+        })
+        .collect();
+    curve.reverse();
+
+    let total = curve.first().copied().unwrap_or_default();
+    curve
+        .into_iter()
+        .map(|energy| {
+            let level = if total > 0.0 {
+                10.0 * (energy / total).log10()
+            } else {
+                SILENCE_DB
+            };
+            Decibels::from(level.max(SILENCE_DB))
+        })
+        .collect()
+}
+
+/// Measures the decay times of a channel of the impulse response. The response starts where it
+/// first gets within 20 dB of its peak, so silence before the direct sound doesn't count.
+/// Cut off the noise floor at the end of a measured response first, as it slows down the
+/// decay.
+/// This will panic if the channel doesn't exist.
+pub fn decay_times<T: Sample>(
+    impulse_response: &Buffer<T>,
+    channel: usize,
+    sample_rate: SampleRate,
+) -> DecayTimes {
+    channel_decay_times(impulse_response.chan(channel), sample_rate)
+}
+
+/// Measures the decay times of every octave or third-octave band of a channel of the impulse
+/// response, for the bands with their center frequency between `lowest` and `highest`.
+/// This will panic if the channel doesn't exist.
+pub fn band_decay_times<T: Sample>(
+    impulse_response: &Buffer<T>,
+    channel: usize,
+    sample_rate: SampleRate,
+    resolution: BandResolution,
+    lowest: Frequency,
+    highest: Frequency,
+) -> Vec<BandDecayTimes> {
+    let samples = impulse_response.chan(channel);
+    let mut bank = FilterBank::new(sample_rate, resolution, lowest, highest);
+    let mut bands = Buffer::<f64>::allocate(
+        Channels::from(bank.num_bands()),
+        Samples::from(samples.len()),
+    );
+    let input: Vec<f64> = samples.iter().map(|sample| sample.to_f64()).collect();
+    bank.split(&input, &mut bands);
+
+    bank.centers()
+        .iter()
+        .zip(bands.iter_chans())
+        .map(|(center, band)| BandDecayTimes {
+            center: *center,
+            times: channel_decay_times(band, sample_rate),
+        })
+        .collect()
+}
+
+fn channel_decay_times<T: Sample>(samples: &[T], sample_rate: SampleRate) -> DecayTimes {
+    let peak = samples
+        .iter()
+        .map(|sample| sample.to_f64().abs())
+        .fold(0.0, f64::max);
+    let onset_level = peak * Decibels::from(ONSET_DB).to_gain();
+    let onset = samples
+        .iter()
+        .position(|sample| peak > 0.0 && sample.to_f64().abs() >= onset_level)
+        .unwrap_or(samples.len());
+
+    let curve = energy_decay_curve(&samples[onset..]);
+    DecayTimes {
+        edt: fit_decay(&curve, sample_rate, 0.0, -10.0),
+        t20: fit_decay(&curve, sample_rate, -5.0, -25.0),
+        t30: fit_decay(&curve, sample_rate, -5.0, -35.0),
+    }
+}
+
+/// Fits a line to the part of the curve between the two levels with least squares, and returns
+/// the time it takes that line to fall 60 dB. Returns `None` when the curve doesn't reach the
+/// lower level, or doesn't fall.
+fn fit_decay(
+    curve: &[Decibels],
+    sample_rate: SampleRate,
+    upper: f64,
+    lower: f64,
+) -> Option<Seconds> {
+    let first = curve.iter().position(|level| level.as_f64() <= upper)?;
+    let last = first
+        + curve[first..]
+            .iter()
+            .position(|level| level.as_f64() < lower)?;
+    let points = &curve[first..last];
+    if points.len() < 2 {
+        return None;
+    }
+
+    let count = points.len() as f64;
+    let mean_time = (first + last - 1) as f64 / 2.0;
+    let mean_level = points.iter().map(Decibels::as_f64).sum::<f64>() / count;
+    let (covariance, variance) =
+        points
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(covariance, variance), (index, level)| {
+                let time = (first + index) as f64 - mean_time;
+                (
+                    covariance + time * (level.as_f64() - mean_level),
+                    variance + time * time,
+                )
+            });
+    let slope = covariance / variance * sample_rate.as_f64();
+    (slope < 0.0).then(|| Seconds::from(-60.0 / slope))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::signals::white_noise;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// Noise that decays 60 dB in the given time, after the given number of silent samples.
+    fn room(rt60: f64, silence: usize) -> Buffer<f64> {
+        let mut buffer = white_noise::<f64>(Channels::from(1), Samples::from(SAMPLE_RATE * 2), 5);
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = match n.checked_sub(silence) {
+                Some(n) => *sample * 10.0_f64.powf(-3.0 * n as f64 / SAMPLE_RATE as f64 / rt60),
+                None => 0.0,
+            };
+        }
+        buffer
+    }
+
+    fn assert_close(time: Option<Seconds>, expected: f64) {
+        let time = time.unwrap().as_f64();
+        assert!((time - expected).abs() < 0.02 * expected, "{time}");
+    }
+
+    #[test]
+    fn energy_decay_curve_of_a_single_impulse_is_flat() {
+        let curve = energy_decay_curve(&[0.0, 1.0, 0.0, 0.0]);
+
+        assert_eq!(curve[..2], [Decibels::from(0.0), Decibels::from(0.0)]);
+        assert_eq!(curve[2], Decibels::from(SILENCE_DB));
+    }
+
+    #[test_case(0.3)]
+    #[test_case(0.8)]
+    #[test_case(1.5)]
+    fn exponential_decay_gives_the_same_times(rt60: f64) {
+        let times = decay_times(&room(rt60, 0), 0, SampleRate::from(SAMPLE_RATE));
+
+        assert_close(times.edt, rt60);
+        assert_close(times.t20, rt60);
+        assert_close(times.t30, rt60);
+        assert_eq!(times.rt60(), times.t30);
+    }
+
+    #[test]
+    fn silence_before_the_direct_sound_is_skipped() {
+        let times = decay_times(&room(0.5, 4800), 0, SampleRate::from(SAMPLE_RATE));
+
+        assert_close(times.edt, 0.5);
+        assert_close(times.rt60(), 0.5);
+    }
+
+    #[test]
+    fn short_decays_only_give_the_early_decay_time() {
+        // The energy that is left after the last sample is 1/20 of the total, or -13 dB.
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(1), Samples::from(20));
+        buffer.chan_mut(0).fill(1.0);
+
+        let times = decay_times(&buffer, 0, SampleRate::from(SAMPLE_RATE));
+
+        assert!(times.edt.is_some());
+        assert_eq!(times.t20, None);
+        assert_eq!(times.rt60(), None);
+    }
+
+    #[test]
+    fn silence_has_no_decay() {
+        let buffer = Buffer::<f32>::allocate(Channels::from(1), Samples::from(100));
+
+        let times = decay_times(&buffer, 0, SampleRate::from(SAMPLE_RATE));
+
+        assert_eq!(
+            times,
+            DecayTimes {
+                edt: None,
+                t20: None,
+                t30: None
+            }
+        );
+    }
+
+    #[test_case(0.4)]
+    #[test_case(1.2)]
+    fn every_band_has_the_decay_of_the_room(rt60: f64) {
+        let bands = band_decay_times(
+            &room(rt60, 0),
+            0,
+            SampleRate::from(SAMPLE_RATE),
+            BandResolution::Octave,
+            Frequency::from(250.0),
+            Frequency::from(4000.0),
+        );
+
+        let centers: Vec<f64> = bands
+            .iter()
+            .map(|band| band.center.as_f64().round())
+            .collect();
+        assert_eq!(centers, [251.0, 501.0, 1000.0, 1995.0, 3981.0]);
+        // A band holds less of the noise, so its decay is less smooth.
+        for band in bands {
+            let t30 = band.times.t30.unwrap().as_f64();
+            assert!((t30 - rt60).abs() < 0.05 * rt60, "{t30}");
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! This module contains a delay line: a ring buffer of the most recent samples that can be read
+//! at any delay up to its maximum, including fractional delays, which are interpolated. It is
+//! the building block of echoes, choruses, flangers and reverbs.
+//! ```rust
+//! use rabu::delay::{DelayLine, DelayTap};
+//! use rabu::units::{Samples, SamplesF64};
+//! use rabu::varispeed::Interpolation;
+//!
+//! // An echo of 100 samples that repeats at half the level every time.
+//! let mut echo = DelayLine::new(Samples::from(1000), Interpolation::Linear);
+//! echo.set_feedback(0.5);
+//!
+//! let mut audio = vec![0.0_f32; 512];
+//! audio[0] = 1.0;
+//! echo.process_block(&mut audio, SamplesF64::from(100.0));
+//!
+//! assert_eq!(audio[100], 1.0);
+//! assert_eq!(audio[200], 0.5);
+//!
+//! // Taps read at several delays at once.
+//! let taps = [
+//!     DelayTap { delay: SamplesF64::from(311.0), gain: 1.0 },
+//!     DelayTap { delay: SamplesF64::from(411.0), gain: 0.5 },
+//! ];
+//! assert_eq!(echo.read_taps(&taps), 0.25 + 0.5 * 0.5);
+//! ```
+
+use crate::sample::Sample;
+use crate::units::{SampleRate, Samples, SamplesF64, Seconds};
+use crate::varispeed::Interpolation;
+
+/// A read position in a delay line together with the gain it's mixed in with.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DelayTap {
+    /// The delay relative to the last pushed sample.
+    pub delay: SamplesF64,
+    /// The gain of the tap.
+    pub gain: f64,
+}
+
+/// A mono delay line. Delays are relative to the last pushed sample: a delay of 0 reads that
+/// sample back, a delay of 1 the sample before it.
+#[derive(Clone, Debug)]
+pub struct DelayLine {
+    buffer: Vec<f64>,
+    /// The index of the last pushed sample.
+    position: usize,
+    max_delay: Samples,
+    interpolation: Interpolation,
+    feedback: f64,
+}
+
+impl DelayLine {
+    /// The extra samples kept beyond the maximum delay, so the cubic interpolation has its
+    /// neighbours at the longest delay as well.
+    const MARGIN: usize = 3;
+
+    /// Creates a new silent delay line that can delay up to the given number of samples.
+    pub fn new(max_delay: Samples, interpolation: Interpolation) -> Self {
+        Self {
+            buffer: vec![0.0; max_delay.as_usize() + Self::MARGIN],
+            position: 0,
+            max_delay,
+            interpolation,
+            feedback: 0.0,
+        }
+    }
+
+    /// Returns the longest delay that can be read.
+    pub fn max_delay(&self) -> Samples {
+        self.max_delay
+    }
+
+    /// Returns the interpolation for fractional delays.
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    /// Changes the interpolation for fractional delays.
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
+
+    /// Returns the feedback.
+    pub fn feedback(&self) -> f64 {
+        self.feedback
+    }
+
+    /// Changes how much of the delayed signal `process` feeds back into the line. Keep it
+    /// below 1 in magnitude, or the repeats will grow.
+    pub fn set_feedback(&mut self, feedback: f64) {
+        self.feedback = feedback;
+    }
+
+    /// Silences the delay line.
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.position = 0;
+    }
+
+    /// Writes the next sample into the delay line.
+    pub fn push(&mut self, input: f64) {
+        self.position = (self.position + 1) % self.buffer.len();
+        self.buffer[self.position] = input;
+    }
+
+    /// Reads the sample at a whole delay.
+    /// This will panic if the delay is longer than the maximum delay.
+    pub fn read(&self, delay: Samples) -> f64 {
+        assert!(delay <= self.max_delay, "the delay is too long");
+        self.at(delay.as_usize() as isize)
+    }
+
+    /// Reads at a fractional delay, interpolating between the neighbouring samples.
+    /// This will panic if the delay is negative or longer than the maximum delay.
+    pub fn read_fractional(&self, delay: SamplesF64) -> f64 {
+        let delay = delay.as_f64();
+        assert!(
+            (0.0..=self.max_delay.as_f64()).contains(&delay),
+            "the delay is out of range"
+        );
+        let whole = delay.floor() as isize;
+        // Older samples have longer delays, so interpolation runs towards larger delays.
+        self.interpolation.interpolate(
+            |offset| self.at((whole + offset).max(0)),
+            delay - whole as f64,
+        )
+    }
+
+    /// Reads at a delay in seconds, interpolating between the neighbouring samples.
+    /// This will panic if the delay is negative or longer than the maximum delay.
+    pub fn read_seconds(&self, delay: Seconds, sample_rate: SampleRate) -> f64 {
+        self.read_fractional(SamplesF64::from(delay.as_f64() * sample_rate.as_f64()))
+    }
+
+    /// Reads all taps and mixes them together.
+    /// This will panic if one of the delays is out of range.
+    pub fn read_taps(&self, taps: &[DelayTap]) -> f64 {
+        taps.iter()
+            .map(|tap| self.read_fractional(tap.delay) * tap.gain)
+            .sum()
+    }
+
+    /// Pushes the input, together with the fed back signal, and returns the input delayed by
+    /// the given amount. Feedback only applies to delays of at least one sample, since shorter
+    /// delays would feed back the input itself.
+    /// This will panic if the delay is negative or longer than the maximum delay.
+    pub fn process(&mut self, input: f64, delay: SamplesF64) -> f64 {
+        if delay.as_f64() >= 1.0 {
+            // Before the push, everything is one sample less delayed.
+            let output = self.read_fractional(delay - SamplesF64::from(1.0));
+            self.push(input + self.feedback * output);
+            output
+        } else {
+            self.push(input);
+            self.read_fractional(delay)
+        }
+    }
+
+    /// Replaces the samples with their delayed versions.
+    /// This will panic if the delay is negative or longer than the maximum delay.
+    pub fn process_block<T: Sample>(&mut self, samples: &mut [T], delay: SamplesF64) {
+        for sample in samples.iter_mut() {
+            *sample = T::from_f64(self.process(sample.to_f64(), delay));
+        }
+    }
+
+    fn at(&self, delay: isize) -> f64 {
+        let length = self.buffer.len() as isize;
+        self.buffer[(self.position as isize - delay).rem_euclid(length) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn ramp(delay: &mut DelayLine, length: usize) {
+        for n in 0..length {
+            delay.push(n as f64);
+        }
+    }
+
+    #[test]
+    fn reads_back_pushed_samples() {
+        let mut delay = DelayLine::new(Samples::from(8), Interpolation::None);
+        ramp(&mut delay, 20);
+
+        assert_eq!(delay.read(Samples::from(0)), 19.0);
+        assert_eq!(delay.read(Samples::from(8)), 11.0);
+    }
+
+    #[test_case(Interpolation::None => 15.0; "none")]
+    #[test_case(Interpolation::Linear => 14.75; "linear")]
+    #[test_case(Interpolation::Cubic => 14.75; "cubic")]
+    fn fractional_delays_interpolate(interpolation: Interpolation) -> f64 {
+        let mut delay = DelayLine::new(Samples::from(8), interpolation);
+        ramp(&mut delay, 20);
+
+        delay.read_fractional(SamplesF64::from(4.25))
+    }
+
+    #[test]
+    fn longest_delay_can_be_interpolated() {
+        let mut delay = DelayLine::new(Samples::from(8), Interpolation::Cubic);
+        ramp(&mut delay, 20);
+
+        assert_eq!(delay.read_fractional(SamplesF64::from(8.0)), 11.0);
+    }
+
+    #[test]
+    fn delay_in_seconds() {
+        let mut delay = DelayLine::new(Samples::from(100), Interpolation::Linear);
+        ramp(&mut delay, 200);
+
+        let value = delay.read_seconds(Seconds::from(0.0105), SampleRate::from(1000));
+
+        assert!((value - 188.5).abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_long_delay_panics() {
+        let delay = DelayLine::new(Samples::from(8), Interpolation::Linear);
+        delay.read_fractional(SamplesF64::from(8.5));
+    }
+
+    #[test]
+    fn taps_are_mixed() {
+        let mut delay = DelayLine::new(Samples::from(8), Interpolation::Linear);
+        ramp(&mut delay, 10);
+
+        let taps = [
+            DelayTap {
+                delay: SamplesF64::from(1.0),
+                gain: 1.0,
+            },
+            DelayTap {
+                delay: SamplesF64::from(2.5),
+                gain: -2.0,
+            },
+        ];
+
+        assert_eq!(delay.read_taps(&taps), 8.0 - 2.0 * 6.5);
+    }
+
+    #[test_case(0.0 => vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0]; "plain delay")]
+    #[test_case(0.5 => vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.5, 0.0, 0.0]; "echoes")]
+    fn feedback_repeats_the_delayed_signal(feedback: f64) -> Vec<f64> {
+        let mut delay = DelayLine::new(Samples::from(8), Interpolation::None);
+        delay.set_feedback(feedback);
+        let mut samples = vec![0.0_f64; 9];
+        samples[0] = 1.0;
+
+        delay.process_block(&mut samples, SamplesF64::from(3.0));
+
+        samples
+    }
+
+    #[test]
+    fn zero_delay_passes_the_input() {
+        let mut delay = DelayLine::new(Samples::from(8), Interpolation::Linear);
+
+        assert_eq!(delay.process(1.0, SamplesF64::from(0.0)), 1.0);
+        assert_eq!(delay.process(3.0, SamplesF64::from(0.5)), 2.0);
+    }
+}
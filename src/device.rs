@@ -0,0 +1,287 @@
+//! This module picks the configuration to open an audio device with, out of the ranges of
+//! configurations the device supports, the way audio backends like cpal report them. The
+//! configuration is either exactly the one that was asked for, or the nearest one: having
+//! enough channels is preferred most, then the same sample rate, so nothing has to be
+//! resampled, then the number of channels, then the sample format with the closest precision,
+//! and then the buffer size. The latency of the buffers comes with it.
+//! ```rust
+//! use rabu::device::{negotiate, DeviceConfig, Negotiation, SupportedConfigs};
+//! use rabu::quantize::SampleFormat;
+//! use rabu::units::{BitDepth, BufferSize, Channels, SampleRate};
+//!
+//! // As reported by the device.
+//! let supported = [SupportedConfigs {
+//!     num_channels: Channels::from(2),
+//!     format: SampleFormat::Int(BitDepth::Bits24),
+//!     min_sample_rate: SampleRate::from(44100),
+//!     max_sample_rate: SampleRate::from(48000),
+//!     min_buffer_size: BufferSize::from(64),
+//!     max_buffer_size: BufferSize::from(4096),
+//! }];
+//! let desired = DeviceConfig {
+//!     sample_rate: SampleRate::from(48000),
+//!     buffer_size: BufferSize::from(32),
+//!     num_channels: Channels::from(2),
+//!     format: SampleFormat::Float32,
+//! };
+//!
+//! assert!(negotiate(desired, &supported, Negotiation::Exact).is_none());
+//!
+//! let negotiated = negotiate(desired, &supported, Negotiation::Nearest).unwrap();
+//! assert!(!negotiated.is_exact);
+//! assert_eq!(negotiated.config.buffer_size, BufferSize::from(64));
+//! assert_eq!(negotiated.config.format, SampleFormat::Int(BitDepth::Bits24));
+//! assert!((negotiated.latency.as_secs_f64() - 64.0 / 48000.0).abs() < 1e-9);
+//! ```
+
+use crate::quantize::SampleFormat;
+use crate::units::{BitDepth, BufferSize, Channels, Latency, SampleRate};
+
+/// A configuration to open a device with.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DeviceConfig {
+    /// The sample rate.
+    pub sample_rate: SampleRate,
+    /// The number of samples per channel in every callback.
+    pub buffer_size: BufferSize,
+    /// The number of channels.
+    pub num_channels: Channels,
+    /// The format of the samples.
+    pub format: SampleFormat,
+}
+
+impl DeviceConfig {
+    /// Returns the latency of one buffer, which the device adds to the signal.
+    pub fn latency(&self) -> Latency {
+        Latency::from_secs_f64(self.buffer_size.as_u32() as f64 / self.sample_rate.as_f64())
+    }
+}
+
+/// A range of configurations a device supports: every sample rate and buffer size in the
+/// ranges, with the number of channels and the format.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SupportedConfigs {
+    /// The number of channels.
+    pub num_channels: Channels,
+    /// The format of the samples.
+    pub format: SampleFormat,
+    /// The lowest sample rate.
+    pub min_sample_rate: SampleRate,
+    /// The highest sample rate.
+    pub max_sample_rate: SampleRate,
+    /// The smallest buffer size.
+    pub min_buffer_size: BufferSize,
+    /// The largest buffer size.
+    pub max_buffer_size: BufferSize,
+}
+
+impl SupportedConfigs {
+    /// Tells whether the configuration is one of these.
+    pub fn contains(&self, config: &DeviceConfig) -> bool {
+        self.num_channels == config.num_channels
+            && self.format == config.format
+            && (self.min_sample_rate.as_u32()..=self.max_sample_rate.as_u32())
+                .contains(&config.sample_rate.as_u32())
+            && (self.min_buffer_size..=self.max_buffer_size).contains(&config.buffer_size)
+    }
+
+    /// Returns the configuration of these that is closest to the desired one in sample rate
+    /// and buffer size.
+    fn closest_to(&self, desired: &DeviceConfig) -> DeviceConfig {
+        let sample_rate = desired.sample_rate.as_u32().clamp(
+            self.min_sample_rate.as_u32(),
+            self.max_sample_rate
+                .as_u32()
+                .max(self.min_sample_rate.as_u32()),
+        );
+        DeviceConfig {
+            sample_rate: SampleRate::from(sample_rate),
+            buffer_size: desired.buffer_size.clamp(
+                self.min_buffer_size,
+                self.max_buffer_size.max(self.min_buffer_size),
+            ),
+            num_channels: self.num_channels,
+            format: self.format,
+        }
+    }
+}
+
+/// How far the configuration may be from the desired one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Negotiation {
+    /// Only the desired configuration.
+    Exact,
+    /// The supported configuration nearest to the desired one.
+    Nearest,
+}
+
+/// The outcome of a negotiation.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NegotiatedConfig {
+    /// The configuration to open the device with.
+    pub config: DeviceConfig,
+    /// Whether it's the desired configuration.
+    pub is_exact: bool,
+    /// The latency of one buffer.
+    pub latency: Latency,
+}
+
+/// Picks the configuration out of the supported ones, or gives `None` when none of them is
+/// close enough.
+pub fn negotiate(
+    desired: DeviceConfig,
+    supported: &[SupportedConfigs],
+    negotiation: Negotiation,
+) -> Option<NegotiatedConfig> {
+    let config = if supported.iter().any(|configs| configs.contains(&desired)) {
+        desired
+    } else {
+        match negotiation {
+            Negotiation::Exact => return None,
+            Negotiation::Nearest => supported
+                .iter()
+                .map(|configs| configs.closest_to(&desired))
+                .min_by(|a, b| distance(a, &desired).cmp(&distance(b, &desired)))?,
+        }
+    };
+    Some(NegotiatedConfig {
+        config,
+        is_exact: config == desired,
+        latency: config.latency(),
+    })
+}
+
+/// How far the configuration is from the desired one, in the order of what matters most.
+fn distance(config: &DeviceConfig, desired: &DeviceConfig) -> (bool, u64, u32, u32, u64) {
+    let is_missing_channels = config.num_channels.as_u32() < desired.num_channels.as_u32();
+    let sample_rate = ratio_distance(config.sample_rate.as_f64(), desired.sample_rate.as_f64());
+    let channels = config
+        .num_channels
+        .as_u32()
+        .abs_diff(desired.num_channels.as_u32());
+    let (precision, desired_precision) = (precision(config.format), precision(desired.format));
+    // A more precise format is better than a less precise one.
+    let format = match precision.cmp(&desired_precision) {
+        std::cmp::Ordering::Less => 10 + desired_precision - precision,
+        _ => precision - desired_precision,
+    };
+    let buffer_size = ratio_distance(
+        config.buffer_size.as_u32() as f64,
+        desired.buffer_size.as_u32() as f64,
+    );
+    (
+        is_missing_channels,
+        sample_rate,
+        channels,
+        format,
+        buffer_size,
+    )
+}
+
+/// The distance between two values as a ratio, so twice as high is as far as half as high.
+fn ratio_distance(value: f64, desired: f64) -> u64 {
+    ((value.max(1.0) / desired.max(1.0)).ln().abs() * 1e9) as u64
+}
+
+/// Ranks the formats from the least to the most precise.
+fn precision(format: SampleFormat) -> u32 {
+    match format {
+        SampleFormat::Int(BitDepth::Bits8) => 0,
+        SampleFormat::Int(BitDepth::Bits16) => 1,
+        SampleFormat::Int(BitDepth::Bits24) => 2,
+        SampleFormat::Int(BitDepth::Bits32) => 3,
+        SampleFormat::Float32 => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configs(num_channels: usize, format: SampleFormat, rates: (u32, u32)) -> SupportedConfigs {
+        SupportedConfigs {
+            num_channels: Channels::from(num_channels),
+            format,
+            min_sample_rate: SampleRate::from(rates.0),
+            max_sample_rate: SampleRate::from(rates.1),
+            min_buffer_size: BufferSize::from(32),
+            max_buffer_size: BufferSize::from(2048),
+        }
+    }
+
+    fn desired() -> DeviceConfig {
+        DeviceConfig {
+            sample_rate: SampleRate::from(48000),
+            buffer_size: BufferSize::from(256),
+            num_channels: Channels::from(2),
+            format: SampleFormat::Float32,
+        }
+    }
+
+    #[test]
+    fn picks_the_desired_configuration_when_supported() {
+        let supported = [
+            configs(2, SampleFormat::Int(BitDepth::Bits16), (8000, 192000)),
+            configs(2, SampleFormat::Float32, (8000, 192000)),
+        ];
+
+        let negotiated = negotiate(desired(), &supported, Negotiation::Exact).unwrap();
+
+        assert!(negotiated.is_exact);
+        assert_eq!(negotiated.config, desired());
+        assert_eq!(negotiated.latency, Latency::from_secs_f64(256.0 / 48000.0));
+    }
+
+    #[test]
+    fn prefers_the_sample_rate_over_the_format() {
+        let supported = [
+            configs(2, SampleFormat::Float32, (44100, 44100)),
+            configs(2, SampleFormat::Int(BitDepth::Bits16), (48000, 48000)),
+        ];
+
+        let negotiated = negotiate(desired(), &supported, Negotiation::Nearest).unwrap();
+
+        assert_eq!(negotiated.config.sample_rate, SampleRate::from(48000));
+        assert_eq!(
+            negotiated.config.format,
+            SampleFormat::Int(BitDepth::Bits16)
+        );
+    }
+
+    #[test]
+    fn prefers_more_channels_over_too_few() {
+        let supported = [
+            configs(1, SampleFormat::Float32, (48000, 48000)),
+            configs(8, SampleFormat::Float32, (48000, 48000)),
+            configs(4, SampleFormat::Float32, (48000, 48000)),
+        ];
+
+        let negotiated = negotiate(desired(), &supported, Negotiation::Nearest).unwrap();
+
+        assert_eq!(negotiated.config.num_channels, Channels::from(4));
+    }
+
+    #[test]
+    fn prefers_a_more_precise_format() {
+        let supported = [
+            configs(2, SampleFormat::Int(BitDepth::Bits16), (48000, 48000)),
+            configs(2, SampleFormat::Int(BitDepth::Bits32), (48000, 48000)),
+        ];
+        let desired = DeviceConfig {
+            format: SampleFormat::Int(BitDepth::Bits24),
+            ..desired()
+        };
+
+        let negotiated = negotiate(desired, &supported, Negotiation::Nearest).unwrap();
+
+        assert_eq!(
+            negotiated.config.format,
+            SampleFormat::Int(BitDepth::Bits32)
+        );
+    }
+
+    #[test]
+    fn fails_without_supported_configurations() {
+        assert!(negotiate(desired(), &[], Negotiation::Nearest).is_none());
+    }
+}
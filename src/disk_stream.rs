@@ -0,0 +1,394 @@
+//! This module contains a stream that plays a WAV file from disk, so files that don't fit in
+//! memory can be played. A background thread decodes ahead into a ring buffer, and the audio
+//! thread takes blocks from it without waiting or locking: when the thread hasn't caught up, the
+//! block is filled with silence instead. Seeking doesn't wait either, the stream is silent until
+//! the thread has decoded from the new position.
+//! ```rust
+//! use std::io::Cursor;
+//!
+//! use rabu::buffer::Buffer;
+//! use rabu::disk_stream::DiskStream;
+//! use rabu::quantize::SampleFormat;
+//! use rabu::units::{Channels, Duration, SampleRate, Samples};
+//! use rabu::wav::{WavReader, WavWriter};
+//!
+//! let mut bytes = Cursor::new(Vec::new());
+//! let format = SampleFormat::Float32;
+//! let mut writer = WavWriter::new(&mut bytes, SampleRate::from(48000), Channels::from(2), format)
+//!     .unwrap();
+//! writer.write(&Buffer::<f32>::allocate(Channels::from(2), Samples::from(48000)));
+//! drop(writer);
+//!
+//! // Usually with `DiskStream::open`, which reads from a path.
+//! let reader = WavReader::new(Cursor::new(bytes.into_inner())).unwrap();
+//! let mut stream = DiskStream::new(reader, Duration::from_secs_f64(0.5));
+//!
+//! let mut block = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! while stream.available() < block.num_samples() {
+//!     std::thread::sleep(std::time::Duration::from_millis(1));
+//! }
+//! // On the audio thread.
+//! assert!(stream.read(&mut block));
+//! assert_eq!(stream.position(), Samples::from(512));
+//!
+//! stream.seek(Samples::from(24000));
+//! ```
+
+use std::io::{Read, Seek};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::buffer::Buffer;
+use crate::quantize::SampleFormat;
+use crate::sample::Sample;
+use crate::units::{Channels, Duration, SampleRate, Samples};
+use crate::wav::WavReader;
+
+/// How long the thread waits before it looks for room in the ring buffer again.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(1);
+
+/// Plays a WAV file from disk, with a background thread that decodes ahead.
+#[derive(Debug)]
+pub struct DiskStream {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+    sample_rate: SampleRate,
+    num_channels: Channels,
+    format: SampleFormat,
+    num_samples: Samples,
+    position: Samples,
+    /// The index of the next frame to read from the ring buffer, which only grows.
+    read: usize,
+    /// The last seek, which the thread has decoded from when it is acknowledged.
+    generation: u64,
+    is_acknowledged: bool,
+}
+
+/// The state that is shared with the thread.
+#[derive(Debug)]
+struct Shared {
+    /// The interleaved samples, as the bits of `f32`s.
+    samples: Vec<AtomicU32>,
+    /// The number of frames the ring buffer holds.
+    capacity: usize,
+    read: AtomicUsize,
+    write: AtomicUsize,
+    /// The write index at which the file ended, or `usize::MAX` when it didn't yet.
+    end: AtomicUsize,
+    seek_position: AtomicU64,
+    seek_generation: AtomicU64,
+    /// The write index from which the samples are of the acknowledged seek.
+    seek_start: AtomicUsize,
+    acknowledged_generation: AtomicU64,
+    stop: AtomicBool,
+}
+
+impl DiskStream {
+    /// Opens the file at the path and starts decoding ahead as far as the prefetch, or gives
+    /// `None` when it can't be read or isn't a supported WAV file.
+    pub fn open(path: impl AsRef<Path>, prefetch: Duration) -> Option<Self> {
+        Some(Self::new(WavReader::open(path)?, prefetch))
+    }
+
+    /// Starts decoding ahead from the position of the reader, as far as the prefetch.
+    pub fn new<R: Read + Seek + Send + 'static>(reader: WavReader<R>, prefetch: Duration) -> Self {
+        let sample_rate = reader.sample_rate();
+        let num_channels = reader.num_channels();
+        let capacity = prefetch.to_samples(sample_rate).as_usize().max(1);
+        let shared = Arc::new(Shared {
+            samples: (0..capacity * num_channels.as_usize())
+                .map(|_| AtomicU32::new(0))
+                .collect(),
+            capacity,
+            read: AtomicUsize::new(0),
+            write: AtomicUsize::new(0),
+            end: AtomicUsize::new(usize::MAX),
+            seek_position: AtomicU64::new(0),
+            seek_generation: AtomicU64::new(0),
+            seek_start: AtomicUsize::new(0),
+            acknowledged_generation: AtomicU64::new(0),
+            stop: AtomicBool::new(false),
+        });
+
+        let format = reader.format();
+        let num_samples = reader.num_samples();
+        let position = reader.position();
+        let thread_shared = shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("rabu disk stream".into())
+            .spawn(move || decode(reader, &thread_shared))
+            .expect("the thread can be started");
+
+        Self {
+            shared,
+            thread: Some(thread),
+            sample_rate,
+            num_channels,
+            format,
+            num_samples,
+            position,
+            read: 0,
+            generation: 0,
+            is_acknowledged: true,
+        }
+    }
+
+    /// Returns the sample rate of the file.
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// Returns the number of channels of the file.
+    pub fn num_channels(&self) -> Channels {
+        self.num_channels
+    }
+
+    /// Returns the format the samples are stored in.
+    pub fn format(&self) -> SampleFormat {
+        self.format
+    }
+
+    /// Returns the number of samples per channel in the file.
+    pub fn num_samples(&self) -> Samples {
+        self.num_samples
+    }
+
+    /// Returns the position in the file of the next block.
+    pub fn position(&self) -> Samples {
+        self.position
+    }
+
+    /// Returns the number of samples that are decoded and ready to be read.
+    pub fn available(&mut self) -> Samples {
+        if !self.acknowledge() {
+            return Samples::from(0);
+        }
+        Samples::from(self.shared.write.load(Ordering::Acquire) - self.read)
+    }
+
+    /// Tells whether the thread has decoded up to the end of the file, so the rest of it is
+    /// available.
+    pub fn is_decoded(&mut self) -> bool {
+        self.acknowledge() && self.shared.end.load(Ordering::Acquire) != usize::MAX
+    }
+
+    /// Tells whether the whole file is read.
+    pub fn is_finished(&mut self) -> bool {
+        self.acknowledge() && self.read == self.shared.end.load(Ordering::Acquire)
+    }
+
+    /// Continues from the position, which is limited to the end of the file. This doesn't
+    /// wait, the stream is silent until the thread has decoded from there.
+    pub fn seek(&mut self, position: Samples) {
+        self.position = position.min(self.num_samples);
+        self.generation += 1;
+        self.is_acknowledged = false;
+        self.shared
+            .seek_position
+            .store(self.position.as_u64(), Ordering::Relaxed);
+        self.shared
+            .seek_generation
+            .store(self.generation, Ordering::Release);
+    }
+
+    /// Fills the block with the next samples of the file. When the thread hasn't decoded far
+    /// enough, the rest of the block is silent and this gives `false`. After the end of the
+    /// file the block is silent as well, but that isn't counted as running dry.
+    /// This will panic if the block doesn't have the same number of channels as the file.
+    pub fn read<T: Sample>(&mut self, block: &mut Buffer<T>) -> bool {
+        assert_eq!(block.num_channels(), self.num_channels);
+        let length = block.num_samples().as_usize();
+        let count = self.available().as_usize().min(length);
+
+        let num_channels = self.num_channels.as_usize();
+        for (channel, samples) in block.iter_chans_mut().enumerate() {
+            for (index, sample) in samples.iter_mut().enumerate().take(count) {
+                let frame = (self.read + index) % self.shared.capacity;
+                let bits =
+                    self.shared.samples[frame * num_channels + channel].load(Ordering::Relaxed);
+                *sample = T::from_f64(f32::from_bits(bits) as f64);
+            }
+            samples[count..].fill(T::default());
+        }
+
+        self.read += count;
+        self.shared.read.store(self.read, Ordering::Release);
+        self.position += Samples::from(count);
+        count == length || self.is_finished()
+    }
+
+    /// Continues after the thread acknowledged the last seek, by skipping what it decoded
+    /// before. Gives `false` while it hasn't yet.
+    fn acknowledge(&mut self) -> bool {
+        if !self.is_acknowledged {
+            let acknowledged = self.shared.acknowledged_generation.load(Ordering::Acquire);
+            if acknowledged != self.generation {
+                return false;
+            }
+            self.read = self.shared.seek_start.load(Ordering::Relaxed);
+            self.shared.read.store(self.read, Ordering::Release);
+            self.is_acknowledged = true;
+        }
+        true
+    }
+}
+
+impl Drop for DiskStream {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Decodes into the ring buffer until the stream stops, and follows the seeks.
+fn decode<R: Read + Seek>(mut reader: WavReader<R>, shared: &Shared) {
+    let num_channels = reader.num_channels().as_usize();
+    // Decodes in parts of the ring buffer, so the thread doesn't wait for it to be empty.
+    let chunk = (shared.capacity / 4).max(1);
+    let mut write = 0;
+    let mut generation = 0;
+
+    while !shared.stop.load(Ordering::Relaxed) {
+        let requested = shared.seek_generation.load(Ordering::Acquire);
+        if requested != generation {
+            generation = requested;
+            let position = shared.seek_position.load(Ordering::Relaxed);
+            let found = reader.seek(Samples::from(position));
+            let end = if found { usize::MAX } else { write };
+            shared.end.store(end, Ordering::Relaxed);
+            shared.seek_start.store(write, Ordering::Relaxed);
+            shared
+                .acknowledged_generation
+                .store(generation, Ordering::Release);
+        }
+
+        let free = shared.capacity - (write - shared.read.load(Ordering::Acquire));
+        let is_at_end = shared.end.load(Ordering::Relaxed) != usize::MAX;
+        if is_at_end || free < chunk {
+            std::thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+
+        let block = reader.read(Samples::from(chunk));
+        let block_length = block
+            .as_ref()
+            .map_or(0, |block| block.num_samples().as_usize());
+        if let Some(block) = block {
+            for index in block.sample_indices() {
+                let frame = (write + index) % shared.capacity;
+                for (channel, samples) in block.iter_chans().enumerate() {
+                    shared.samples[frame * num_channels + channel]
+                        .store(samples[index].to_bits(), Ordering::Relaxed);
+                }
+            }
+        }
+        write += block_length;
+        shared.write.store(write, Ordering::Release);
+        // A block that is short, or can't be read, ends the file.
+        if block_length < chunk {
+            shared.end.store(write, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::wav::WavWriter;
+
+    /// A stream of a mono file where every sample is its own index.
+    fn stream(length: usize, prefetch: usize) -> DiskStream {
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = WavWriter::new(
+            &mut bytes,
+            SampleRate::from(1000),
+            Channels::from(1),
+            SampleFormat::Float32,
+        )
+        .unwrap();
+        let mut audio = Buffer::<f32>::allocate(Channels::from(1), Samples::from(length));
+        for (index, sample) in audio.chan_mut(0).iter_mut().enumerate() {
+            *sample = index as f32;
+        }
+        writer.write(&audio);
+        drop(writer);
+
+        let reader = WavReader::new(Cursor::new(bytes.into_inner())).unwrap();
+        DiskStream::new(reader, Duration::from_secs_f64(prefetch as f64 / 1000.0))
+    }
+
+    /// Waits until the block can be read in full, or up to the end of the file.
+    fn read(stream: &mut DiskStream, length: usize) -> Vec<f32> {
+        let mut block = Buffer::<f32>::allocate(Channels::from(1), Samples::from(length));
+        for _ in 0..10_000 {
+            if stream.available() >= block.num_samples() || stream.is_decoded() {
+                break;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        assert!(stream.read(&mut block));
+        block.chan(0).to_vec()
+    }
+
+    #[test]
+    fn reads_the_whole_file_through_a_small_ring_buffer() {
+        let mut stream = stream(1000, 64);
+
+        let mut samples = Vec::new();
+        while samples.len() < 1000 {
+            samples.extend(read(&mut stream, 48));
+        }
+
+        for (index, sample) in samples.iter().take(1000).enumerate() {
+            assert_eq!(*sample, index as f32);
+        }
+        assert!(samples[1000..].iter().all(|sample| *sample == 0.0));
+        assert!(stream.is_finished());
+    }
+
+    #[test]
+    fn continues_from_a_seek() {
+        let mut stream = stream(1000, 100);
+        read(&mut stream, 10);
+
+        stream.seek(Samples::from(500));
+        assert_eq!(stream.position(), Samples::from(500));
+        assert_eq!(read(&mut stream, 3), &[500.0, 501.0, 502.0]);
+
+        stream.seek(Samples::from(5));
+        assert_eq!(read(&mut stream, 2), &[5.0, 6.0]);
+        assert_eq!(stream.position(), Samples::from(7));
+    }
+
+    #[test]
+    fn is_silent_after_the_end() {
+        let mut stream = stream(10, 100);
+
+        stream.seek(Samples::from(20));
+        assert_eq!(read(&mut stream, 4), &[0.0; 4]);
+        assert_eq!(stream.position(), Samples::from(10));
+        assert!(stream.is_finished());
+
+        stream.seek(Samples::from(8));
+        assert_eq!(read(&mut stream, 4), &[8.0, 9.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn runs_dry_before_the_thread_caught_up() {
+        let mut stream = stream(100, 100);
+        stream.seek(Samples::from(50));
+        let mut block = Buffer::<f32>::allocate(Channels::from(1), Samples::from(10));
+
+        // The seek can't be acknowledged before the thread sees it.
+        if stream.available() == Samples::from(0) {
+            assert!(!stream.read(&mut block));
+            assert!(block.is_default_filled());
+        }
+    }
+}
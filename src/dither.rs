@@ -0,0 +1,200 @@
+//! This module contains dither for reducing audio to a lower bit depth. Plain rounding makes
+//! the rounding error follow the signal, which sounds like distortion on quiet passages. Adding
+//! a little triangular (TPDF) noise first turns that error into a constant, benign noise floor.
+//! Noise shaping can then move that noise towards the high frequencies, where it's less
+//! audible.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::dither::{Dither, NoiseShaping};
+//! use rabu::units::{BitDepth, Channels, Samples};
+//!
+//! let mut dither = Dither::new(BitDepth::Bits16, Channels::from(2), 1);
+//! dither.set_noise_shaping(NoiseShaping::FirstOrder);
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! buffer.map_samples(|_| 0.25);
+//! dither.process(&mut buffer);
+//!
+//! // Every sample now lies exactly on a 16 bit step.
+//! assert!(buffer.data().iter().all(|s| (s * 32768.0).fract() == 0.0));
+//! ```
+
+use crate::buffer::Buffer;
+use crate::noise::Random;
+use crate::sample::Sample;
+use crate::units::{BitDepth, Channels};
+
+/// How the dither noise is spread over frequency.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NoiseShaping {
+    /// The noise is white.
+    None,
+    /// The quantization error of every sample is subtracted from the next one, which tilts
+    /// the noise up by 6 dB per octave, towards Nyquist.
+    FirstOrder,
+}
+
+/// Quantizes audio to a bit depth with TPDF dither.
+#[derive(Clone, Debug)]
+pub struct Dither {
+    bit_depth: BitDepth,
+    noise_shaping: NoiseShaping,
+    random: Random,
+    /// The last quantization error of every channel, for noise shaping.
+    errors: Vec<f64>,
+}
+
+impl Dither {
+    /// Creates a new dither for the given bit depth without noise shaping, with noise from the
+    /// given seed.
+    pub fn new(bit_depth: BitDepth, num_channels: Channels, seed: u64) -> Self {
+        Self {
+            bit_depth,
+            noise_shaping: NoiseShaping::None,
+            random: Random::new(seed),
+            errors: vec![0.0; num_channels.as_usize()],
+        }
+    }
+
+    /// Returns the bit depth.
+    pub fn bit_depth(&self) -> BitDepth {
+        self.bit_depth
+    }
+
+    /// Returns the noise shaping.
+    pub fn noise_shaping(&self) -> NoiseShaping {
+        self.noise_shaping
+    }
+
+    /// Changes the noise shaping.
+    pub fn set_noise_shaping(&mut self, noise_shaping: NoiseShaping) {
+        self.noise_shaping = noise_shaping;
+    }
+
+    /// Clears the noise shaping state.
+    pub fn reset(&mut self) {
+        self.errors.fill(0.0);
+    }
+
+    /// Returns the value of full scale in steps of the bit depth, e.g. 32768 for 16 bits.
+    pub fn full_scale(&self) -> f64 {
+        2.0_f64.powi(self.bit_depth.to_u16() as i32 - 1)
+    }
+
+    /// Dithers and quantizes one sample of the given channel to an integer of the bit depth,
+    /// clipping samples outside -1 to 1.
+    /// This will panic if the channel doesn't exist.
+    pub fn quantize(&mut self, channel: usize, sample: f64) -> i32 {
+        let full_scale = self.full_scale();
+        let wanted = sample * full_scale - self.errors[channel];
+        // The difference of two uniform numbers has a triangular distribution of +/- 1 step.
+        let noise = self.random.next_f64() - self.random.next_f64();
+        let rounded = (wanted + noise).round();
+
+        // Only the rounding error is fed back: feeding back what got clipped off as well would
+        // make the error grow without bounds while the input is too loud.
+        if self.noise_shaping == NoiseShaping::FirstOrder {
+            self.errors[channel] = rounded - wanted;
+        }
+        rounded.clamp(-full_scale, full_scale - 1.0) as i32
+    }
+
+    /// Dithers the buffer to the bit depth in place, so every sample lies on a step of the bit
+    /// depth.
+    /// This will panic if the buffer doesn't have the number of channels the dither was made
+    /// for.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        assert_eq!(buffer.num_channels().as_usize(), self.errors.len());
+        let full_scale = self.full_scale();
+        for (index, channel) in buffer.iter_chans_mut().enumerate() {
+            for sample in channel.iter_mut() {
+                let quantized = self.quantize(index, sample.to_f64());
+                *sample = T::from_f64(quantized as f64 / full_scale);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn quantized(dither: &mut Dither, level: f64, length: usize) -> Vec<f64> {
+        (0..length)
+            .map(|_| dither.quantize(0, level) as f64)
+            .collect()
+    }
+
+    #[test_case(BitDepth::Bits8 => 128.0; "8 bits")]
+    #[test_case(BitDepth::Bits16 => 32768.0; "16 bits")]
+    #[test_case(BitDepth::Bits24 => 8388608.0; "24 bits")]
+    fn full_scale(bit_depth: BitDepth) -> f64 {
+        Dither::new(bit_depth, Channels::from(1), 0).full_scale()
+    }
+
+    #[test]
+    fn dither_keeps_details_below_one_step() {
+        let mut dither = Dither::new(BitDepth::Bits16, Channels::from(1), 3);
+        // A third of a step would round to silence without dither...
+        let level = 1.0 / 3.0 / 32768.0;
+
+        let samples = quantized(&mut dither, level, 100_000);
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+
+        // ...but on average it survives.
+        assert!((mean - 1.0 / 3.0).abs() < 0.02);
+        assert!(samples.iter().all(|s| (-1.0..=2.0).contains(s)));
+    }
+
+    #[test]
+    fn clips_at_the_edges() {
+        let mut dither = Dither::new(BitDepth::Bits8, Channels::from(1), 3);
+
+        assert!(quantized(&mut dither, 2.0, 100).iter().all(|s| *s == 127.0));
+        assert!(quantized(&mut dither, -2.0, 100)
+            .iter()
+            .all(|s| *s == -128.0));
+    }
+
+    #[test]
+    fn noise_shaping_recovers_from_clipping() {
+        let mut dither = Dither::new(BitDepth::Bits16, Channels::from(1), 3);
+        dither.set_noise_shaping(NoiseShaping::FirstOrder);
+
+        assert!(quantized(&mut dither, 1.5, 1000)
+            .iter()
+            .all(|s| *s == 32767.0));
+        assert!(quantized(&mut dither, 0.0, 1000)
+            .iter()
+            .all(|s| s.abs() <= 2.0));
+    }
+
+    /// Returns the error power of a slowly changing signal after averaging it over blocks of
+    /// 64 samples, which keeps only the low frequencies.
+    fn low_frequency_error(noise_shaping: NoiseShaping) -> f64 {
+        let mut dither = Dither::new(BitDepth::Bits16, Channels::from(1), 5);
+        dither.set_noise_shaping(noise_shaping);
+        let signal: Vec<f64> = (0..64 * 500)
+            .map(|n| 0.3 * (n as f64 * 1e-3).sin())
+            .collect();
+
+        let errors: Vec<f64> = signal
+            .iter()
+            .map(|sample| dither.quantize(0, *sample) as f64 - sample * 32768.0)
+            .collect();
+        errors
+            .chunks(64)
+            .map(|block| (block.iter().sum::<f64>() / 64.0).powi(2))
+            .sum()
+    }
+
+    #[test]
+    fn noise_shaping_moves_noise_out_of_the_low_frequencies() {
+        let flat = low_frequency_error(NoiseShaping::None);
+        let shaped = low_frequency_error(NoiseShaping::FirstOrder);
+
+        assert!(shaped < flat * 0.1, "{shaped} vs {flat}");
+    }
+}
@@ -0,0 +1,529 @@
+//! This module contains the building blocks of dynamics processors, and a compressor built from
+//! them. The `EnvelopeFollower` smooths a level with separate attack and release times, and is
+//! shared by the compressor, limiter and gate.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::dynamics::Compressor;
+//! use rabu::units::{Channels, Decibels, Ratio, SampleRate, Samples, Seconds};
+//!
+//! let mut compressor = Compressor::new(
+//!     Decibels::from(-18.0),
+//!     Ratio::from(4.0),
+//!     SampleRate::from(48000),
+//! );
+//! compressor.set_knee(Decibels::from(6.0));
+//! compressor.set_attack(Seconds::from(0.005));
+//! compressor.set_release(Seconds::from(0.2));
+//! compressor.set_makeup_gain(Decibels::from(6.0));
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! compressor.process(&mut buffer);
+//!
+//! // Silence isn't compressed.
+//! assert_eq!(compressor.gain_reduction(), Decibels::from(0.0));
+//! ```
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::buffer::Buffer;
+use crate::preset::Preset;
+use crate::processor::{AudioProcessor, ProcessContext, SampleProcessor};
+use crate::sample::Sample;
+use crate::units::{BufferSize, Channels, Decibels, Ratio, SampleRate, Seconds};
+
+/// The lowest level the detectors work with, so silence doesn't turn into negative infinity.
+pub(crate) const SILENCE_DB: f64 = -200.0;
+
+/// Follows a level, rising with the attack time and falling with the release time. The times
+/// are time constants: the time it takes to cover 63% of a step.
+#[derive(Clone, Debug)]
+pub struct EnvelopeFollower {
+    attack: Seconds,
+    release: Seconds,
+    sample_rate: SampleRate,
+    attack_coefficient: f64,
+    release_coefficient: f64,
+    value: f64,
+}
+
+impl EnvelopeFollower {
+    /// Creates a new follower that starts at 0.
+    pub fn new(attack: Seconds, release: Seconds, sample_rate: SampleRate) -> Self {
+        let mut follower = Self {
+            attack,
+            release,
+            sample_rate,
+            attack_coefficient: 0.0,
+            release_coefficient: 0.0,
+            value: 0.0,
+        };
+        follower.update_coefficients();
+        follower
+    }
+
+    /// Returns the attack time.
+    pub fn attack(&self) -> Seconds {
+        self.attack
+    }
+
+    /// Changes the attack time.
+    pub fn set_attack(&mut self, attack: Seconds) {
+        self.attack = attack;
+        self.update_coefficients();
+    }
+
+    /// Returns the release time.
+    pub fn release(&self) -> Seconds {
+        self.release
+    }
+
+    /// Changes the release time.
+    pub fn set_release(&mut self, release: Seconds) {
+        self.release = release;
+        self.update_coefficients();
+    }
+
+    /// Changes the sample rate.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = sample_rate;
+        self.update_coefficients();
+    }
+
+    /// Returns the current value.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Resets the follower to the given value.
+    pub fn reset(&mut self, value: f64) {
+        self.value = value;
+    }
+
+    fn update_coefficients(&mut self) {
+        let coefficient = |time: Seconds| {
+            let samples = time.as_f64() * self.sample_rate.as_f64();
+            if samples > 0.0 {
+                (-1.0 / samples).exp()
+            } else {
+                0.0
+            }
+        };
+        self.attack_coefficient = coefficient(self.attack);
+        self.release_coefficient = coefficient(self.release);
+    }
+}
+
+impl SampleProcessor for EnvelopeFollower {
+    fn process(&mut self, input: f64) -> f64 {
+        let coefficient = if input > self.value {
+            self.attack_coefficient
+        } else {
+            self.release_coefficient
+        };
+        self.value = input + (self.value - input) * coefficient;
+        self.value
+    }
+}
+
+/// Returns the highest absolute sample of all channels at the given index, so linked channels
+/// get the same gain and the stereo image doesn't shift.
+pub(crate) fn linked_peak<T: Sample>(buffer: &Buffer<T>, index: usize) -> f64 {
+    buffer
+        .iter_chans()
+        .map(|channel| channel[index].to_f64().abs())
+        .fold(0.0, f64::max)
+}
+
+/// Converts a linear level to decibels, with silence at `SILENCE_DB`.
+pub(crate) fn level_to_db(level: f64) -> f64 {
+    Decibels::from_gain(level).as_f64().max(SILENCE_DB)
+}
+
+/// A feed-forward compressor with a soft knee. All channels are linked, and the level can be
+/// taken from a separate sidechain signal.
+#[derive(Clone, Debug)]
+pub struct Compressor {
+    threshold: Decibels,
+    ratio: Ratio,
+    knee: Decibels,
+    makeup_gain: Decibels,
+    range: Decibels,
+    /// Smooths the gain reduction in dB.
+    follower: EnvelopeFollower,
+    gain_reduction: Decibels,
+}
+
+impl Compressor {
+    /// Creates a new compressor with a hard knee, no makeup gain, an unlimited range, 10 ms
+    /// attack and 100 ms release.
+    pub fn new(threshold: Decibels, ratio: Ratio, sample_rate: SampleRate) -> Self {
+        Self {
+            threshold,
+            ratio,
+            knee: Decibels::from(0.0),
+            makeup_gain: Decibels::from(0.0),
+            range: Decibels::from(f64::NEG_INFINITY),
+            follower: EnvelopeFollower::new(Seconds::from(0.01), Seconds::from(0.1), sample_rate),
+            gain_reduction: Decibels::from(0.0),
+        }
+    }
+
+    /// Returns the threshold.
+    pub fn threshold(&self) -> Decibels {
+        self.threshold
+    }
+
+    /// Changes the threshold.
+    pub fn set_threshold(&mut self, threshold: Decibels) {
+        self.threshold = threshold;
+    }
+
+    /// Returns the ratio.
+    pub fn ratio(&self) -> Ratio {
+        self.ratio
+    }
+
+    /// Changes the ratio.
+    /// This will panic if the ratio is below 1.
+    pub fn set_ratio(&mut self, ratio: Ratio) {
+        assert!(ratio.as_f64() >= 1.0, "the ratio can't be below 1");
+        self.ratio = ratio;
+    }
+
+    /// Returns the width of the knee.
+    pub fn knee(&self) -> Decibels {
+        self.knee
+    }
+
+    /// Changes the width of the knee around the threshold, in which the ratio gradually
+    /// increases. A width of 0 gives a hard knee.
+    pub fn set_knee(&mut self, knee: Decibels) {
+        self.knee = Decibels::from(knee.as_f64().max(0.0));
+    }
+
+    /// Returns the makeup gain.
+    pub fn makeup_gain(&self) -> Decibels {
+        self.makeup_gain
+    }
+
+    /// Changes the gain that is applied after compression.
+    pub fn set_makeup_gain(&mut self, makeup_gain: Decibels) {
+        self.makeup_gain = makeup_gain;
+    }
+
+    /// Returns the range.
+    pub fn range(&self) -> Decibels {
+        self.range
+    }
+
+    /// Changes how far the compressor may turn the audio down, e.g. -12 dB. The gain reduction
+    /// stops at the range, however far the level goes over the threshold.
+    pub fn set_range(&mut self, range: Decibels) {
+        self.range = Decibels::from(range.as_f64().min(0.0));
+    }
+
+    /// Returns the attack time.
+    pub fn attack(&self) -> Seconds {
+        self.follower.attack()
+    }
+
+    /// Changes how fast the compressor reacts to a rising level.
+    pub fn set_attack(&mut self, attack: Seconds) {
+        self.follower.set_attack(attack);
+    }
+
+    /// Returns the release time.
+    pub fn release(&self) -> Seconds {
+        self.follower.release()
+    }
+
+    /// Changes how fast the compressor recovers when the level falls.
+    pub fn set_release(&mut self, release: Seconds) {
+        self.follower.set_release(release);
+    }
+
+    /// Changes the sample rate.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.follower.set_sample_rate(sample_rate);
+    }
+
+    /// Returns the largest gain reduction of the last processed buffer, as a positive number,
+    /// for metering.
+    pub fn gain_reduction(&self) -> Decibels {
+        self.gain_reduction
+    }
+
+    /// Returns the output level for a steady input level, including the makeup gain.
+    pub fn static_curve(&self, input: Decibels) -> Decibels {
+        Decibels::from(self.compressed(input.as_f64())) + self.makeup_gain
+    }
+
+    /// Clears the internal state, as if no audio was processed yet.
+    pub fn reset(&mut self) {
+        self.follower.reset(0.0);
+        self.gain_reduction = Decibels::from(0.0);
+    }
+
+    /// Compresses the buffer, based on its own level.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        self.gain_reduction = Decibels::from(0.0);
+        for index in buffer.sample_indices() {
+            let gain = self.next_gain(linked_peak(buffer, index));
+            apply_gain(buffer, index, gain);
+        }
+    }
+
+    /// Compresses the buffer, based on the level of the sidechain. The sidechain can have a
+    /// different number of channels than the buffer.
+    /// This will panic if the sidechain and buffer don't have the same number of samples.
+    pub fn process_with_sidechain<T: Sample, S: Sample>(
+        &mut self,
+        buffer: &mut Buffer<T>,
+        sidechain: &Buffer<S>,
+    ) {
+        assert_eq!(buffer.num_samples(), sidechain.num_samples());
+        self.gain_reduction = Decibels::from(0.0);
+        for index in buffer.sample_indices() {
+            let gain = self.next_gain(linked_peak(sidechain, index));
+            apply_gain(buffer, index, gain);
+        }
+    }
+
+    fn next_gain(&mut self, level: f64) -> f64 {
+        let level = level_to_db(level);
+        let reduction = self.follower.process(level - self.compressed(level));
+        if reduction > self.gain_reduction.as_f64() {
+            self.gain_reduction = Decibels::from(reduction);
+        }
+        Decibels::from(self.makeup_gain.as_f64() - reduction).to_gain()
+    }
+
+    /// The gain computer: maps an input level to an output level, without makeup gain.
+    fn compressed(&self, level: f64) -> f64 {
+        self.curve(level).max(level + self.range.as_f64())
+    }
+
+    /// The curve of the gain computer, without the range.
+    fn curve(&self, level: f64) -> f64 {
+        let threshold = self.threshold.as_f64();
+        let knee = self.knee.as_f64();
+        let slope = 1.0 / self.ratio.as_f64() - 1.0;
+        let over = level - threshold;
+
+        if 2.0 * over <= -knee {
+            level
+        } else if 2.0 * over < knee {
+            level + slope * (over + knee / 2.0).powi(2) / (2.0 * knee)
+        } else {
+            level + slope * over
+        }
+    }
+}
+
+impl AudioProcessor for Compressor {
+    fn prepare(&mut self, sample_rate: SampleRate, _: BufferSize, _: Channels) {
+        self.set_sample_rate(sample_rate);
+    }
+
+    fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+        Compressor::process(self, buffer);
+    }
+
+    fn reset(&mut self) {
+        Compressor::reset(self);
+    }
+}
+
+/// The settings of a `Compressor`, for presets.
+/// The default leaves the signal untouched.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct CompressorSettings {
+    #[cfg_attr(feature = "serde", serde(with = "crate::preset::unlimited"))]
+    pub threshold: Decibels,
+    #[cfg_attr(feature = "serde", serde(with = "crate::preset::unlimited"))]
+    pub ratio: Ratio,
+    pub knee: Decibels,
+    pub makeup_gain: Decibels,
+    #[cfg_attr(feature = "serde", serde(with = "crate::preset::unlimited"))]
+    pub range: Decibels,
+    pub attack: Seconds,
+    pub release: Seconds,
+}
+
+impl Default for CompressorSettings {
+    fn default() -> Self {
+        Self {
+            threshold: Decibels::from(0.0),
+            ratio: Ratio::from(1.0),
+            knee: Decibels::from(0.0),
+            makeup_gain: Decibels::from(0.0),
+            range: Decibels::from(f64::NEG_INFINITY),
+            attack: Seconds::from(0.01),
+            release: Seconds::from(0.1),
+        }
+    }
+}
+
+impl Preset for Compressor {
+    type Settings = CompressorSettings;
+    const PROCESSOR: &'static str = "compressor";
+    const VERSION: u32 = 1;
+
+    fn settings(&self) -> CompressorSettings {
+        CompressorSettings {
+            threshold: self.threshold,
+            ratio: self.ratio,
+            knee: self.knee,
+            makeup_gain: self.makeup_gain,
+            range: self.range,
+            attack: self.attack(),
+            release: self.release(),
+        }
+    }
+
+    fn apply_settings(&mut self, settings: &CompressorSettings) {
+        self.set_threshold(settings.threshold);
+        self.set_ratio(settings.ratio);
+        self.set_knee(settings.knee);
+        self.set_makeup_gain(settings.makeup_gain);
+        self.set_range(settings.range);
+        self.set_attack(settings.attack);
+        self.set_release(settings.release);
+    }
+}
+
+/// Multiplies all channels at the given index with the gain.
+pub(crate) fn apply_gain<T: Sample>(buffer: &mut Buffer<T>, index: usize, gain: f64) {
+    for channel in buffer.iter_chans_mut() {
+        channel[index] = T::from_f64(channel[index].to_f64() * gain);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::{Channels, Samples};
+
+    fn constant(level: f64, length: usize) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(2), Samples::from(length));
+        buffer.map_samples(|_| level);
+        buffer
+    }
+
+    #[test]
+    fn follower_covers_63_percent_in_one_time_constant() {
+        let mut follower = EnvelopeFollower::new(
+            Seconds::from(0.01),
+            Seconds::from(0.1),
+            SampleRate::from(1000),
+        );
+
+        let rising: Vec<f64> = (0..10).map(|_| follower.process(1.0)).collect();
+        assert!((rising[9] - 0.632).abs() < 0.001);
+
+        let falling: Vec<f64> = (0..100).map(|_| follower.process(0.0)).collect();
+        assert!((falling[99] - 0.632 * 0.368).abs() < 0.001);
+    }
+
+    #[test_case(-30.0 => -30.0; "below the threshold")]
+    #[test_case(-20.0 => -20.0; "at the threshold")]
+    #[test_case(0.0 => -15.0; "above the threshold")]
+    fn hard_knee_curve(input: f64) -> f64 {
+        let compressor = Compressor::new(
+            Decibels::from(-20.0),
+            Ratio::from(4.0),
+            SampleRate::from(48000),
+        );
+        compressor.static_curve(Decibels::from(input)).as_f64()
+    }
+
+    #[test]
+    fn soft_knee_bends_around_the_threshold() {
+        let mut compressor = Compressor::new(
+            Decibels::from(-20.0),
+            Ratio::from(4.0),
+            SampleRate::from(48000),
+        );
+        compressor.set_knee(Decibels::from(10.0));
+
+        let at_threshold = compressor.static_curve(Decibels::from(-20.0)).as_f64();
+        let below_knee = compressor.static_curve(Decibels::from(-25.0)).as_f64();
+        let above_knee = compressor.static_curve(Decibels::from(-15.0)).as_f64();
+
+        assert!((at_threshold - -20.9375).abs() < 1e-9);
+        assert!((below_knee - -25.0).abs() < 1e-9);
+        assert!((above_knee - -18.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn steady_level_settles_on_the_curve() {
+        let mut compressor = Compressor::new(
+            Decibels::from(-20.0),
+            Ratio::from(4.0),
+            SampleRate::from(48000),
+        );
+        compressor.set_makeup_gain(Decibels::from(3.0));
+        let mut buffer = constant(1.0, 4800);
+
+        compressor.process(&mut buffer);
+
+        let output = Decibels::from_gain(buffer.chan(1)[4799]).as_f64();
+        assert!((output - -12.0).abs() < 0.01);
+        assert!((compressor.gain_reduction().as_f64() - 15.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn sidechain_controls_the_gain() {
+        let mut compressor = Compressor::new(
+            Decibels::from(-20.0),
+            Ratio::from(2.0),
+            SampleRate::from(48000),
+        );
+        compressor.set_attack(Seconds::from(0.0));
+        let mut quiet = constant(0.01, 16);
+        let loud = constant(1.0, 16);
+
+        compressor.process_with_sidechain(&mut quiet, &loud);
+
+        // The sidechain is 20 dB over, so the quiet signal is turned down by 10 dB.
+        assert!((quiet.chan(0)[15] - 0.01 * Decibels::from(-10.0).to_gain()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn range_limits_the_gain_reduction() {
+        let mut compressor = Compressor::new(
+            Decibels::from(-40.0),
+            Ratio::from(10.0),
+            SampleRate::from(48000),
+        );
+        compressor.set_range(Decibels::from(-6.0));
+        compressor.set_attack(Seconds::from(0.0));
+        let mut buffer = constant(1.0, 16);
+
+        compressor.process(&mut buffer);
+
+        assert!((compressor.gain_reduction().as_f64() - 6.0).abs() < 1e-9);
+        assert_eq!(
+            compressor.static_curve(Decibels::from(0.0)),
+            Decibels::from(-6.0)
+        );
+    }
+
+    #[test]
+    fn ratio_of_one_does_nothing() {
+        let mut compressor = Compressor::new(
+            Decibels::from(-40.0),
+            Ratio::from(1.0),
+            SampleRate::from(48000),
+        );
+        let mut buffer = constant(0.5, 64);
+
+        compressor.process(&mut buffer);
+
+        assert!(buffer.data().iter().all(|s| (s - 0.5).abs() < 1e-12));
+    }
+}
@@ -0,0 +1,352 @@
+//! This module contains an ADSR envelope generator, the classic modulation source for the
+//! amplitude (or any other parameter) of a note. A note-on runs the attack and decay stages and
+//! then holds the sustain level until the note-off starts the release.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::envelope::{Adsr, EnvelopeCurve};
+//! use rabu::units::{Channels, NormalizedValue, SampleRate, Samples, Seconds};
+//!
+//! let mut envelope = Adsr::new(
+//!     Seconds::from(0.01),
+//!     Seconds::from(0.1),
+//!     NormalizedValue::from(0.7),
+//!     Seconds::from(0.3),
+//!     SampleRate::from(48000),
+//! );
+//! envelope.set_curve(EnvelopeCurve::Exponential);
+//!
+//! let mut note = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! note.map_samples(|_| 1.0);
+//!
+//! envelope.note_on();
+//! envelope.apply(&mut note);
+//! envelope.note_off();
+//! ```
+
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::units::{NormalizedValue, SampleRate, Seconds};
+
+/// The shape of the attack, decay and release ramps.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EnvelopeCurve {
+    /// Straight ramps.
+    Linear,
+    /// Ramps that move fast at first and then settle slowly, like the charging capacitor of an
+    /// analog envelope. Every ramp still takes exactly its set time.
+    Exponential,
+}
+
+/// The stage the envelope is in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AdsrStage {
+    /// No note is playing and the output is 0.
+    Idle,
+    /// Rising to the peak after a note-on.
+    Attack,
+    /// Falling from the peak to the sustain level.
+    Decay,
+    /// Holding the sustain level until the note-off.
+    Sustain,
+    /// Falling to 0 after a note-off.
+    Release,
+}
+
+/// An attack-decay-sustain-release envelope, with an output between 0 and 1.
+#[derive(Clone, Debug)]
+pub struct Adsr {
+    attack: Seconds,
+    decay: Seconds,
+    sustain: NormalizedValue,
+    release: Seconds,
+    sample_rate: SampleRate,
+    curve: EnvelopeCurve,
+    stage: AdsrStage,
+    value: f64,
+    /// The ramp of the current stage: the level it started at and how far along it is.
+    ramp_start: f64,
+    ramp_position: usize,
+}
+
+impl Adsr {
+    /// How strongly the exponential curve bends.
+    const EXPONENTIAL_SHAPE: f64 = 5.0;
+
+    /// Creates a new envelope with linear ramps, which is idle until the first note-on.
+    pub fn new(
+        attack: Seconds,
+        decay: Seconds,
+        sustain: NormalizedValue,
+        release: Seconds,
+        sample_rate: SampleRate,
+    ) -> Self {
+        Self {
+            attack,
+            decay,
+            sustain,
+            release,
+            sample_rate,
+            curve: EnvelopeCurve::Linear,
+            stage: AdsrStage::Idle,
+            value: 0.0,
+            ramp_start: 0.0,
+            ramp_position: 0,
+        }
+    }
+
+    /// Returns the attack time.
+    pub fn attack(&self) -> Seconds {
+        self.attack
+    }
+
+    /// Changes the attack time, which applies from the next attack on.
+    pub fn set_attack(&mut self, attack: Seconds) {
+        self.attack = attack;
+    }
+
+    /// Returns the decay time.
+    pub fn decay(&self) -> Seconds {
+        self.decay
+    }
+
+    /// Changes the decay time, which applies from the next decay on.
+    pub fn set_decay(&mut self, decay: Seconds) {
+        self.decay = decay;
+    }
+
+    /// Returns the sustain level.
+    pub fn sustain(&self) -> NormalizedValue {
+        self.sustain
+    }
+
+    /// Changes the sustain level, which also applies to a note that is being sustained.
+    pub fn set_sustain(&mut self, sustain: NormalizedValue) {
+        self.sustain = sustain;
+    }
+
+    /// Returns the release time.
+    pub fn release(&self) -> Seconds {
+        self.release
+    }
+
+    /// Changes the release time, which applies from the next release on.
+    pub fn set_release(&mut self, release: Seconds) {
+        self.release = release;
+    }
+
+    /// Changes the sample rate, which applies from the next stage on.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Returns the shape of the ramps.
+    pub fn curve(&self) -> EnvelopeCurve {
+        self.curve
+    }
+
+    /// Changes the shape of the ramps.
+    pub fn set_curve(&mut self, curve: EnvelopeCurve) {
+        self.curve = curve;
+    }
+
+    /// Returns the current stage.
+    pub fn stage(&self) -> AdsrStage {
+        self.stage
+    }
+
+    /// Returns whether the envelope is producing anything, so a voice knows when it's done.
+    pub fn is_active(&self) -> bool {
+        self.stage != AdsrStage::Idle
+    }
+
+    /// Returns the last value the envelope produced.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Starts the attack. When a note is still sounding, the attack starts from the current
+    /// level, so retriggering doesn't click.
+    pub fn note_on(&mut self) {
+        self.enter(AdsrStage::Attack);
+    }
+
+    /// Starts the release from the current level. Does nothing when the envelope is idle.
+    pub fn note_off(&mut self) {
+        if self.stage != AdsrStage::Idle {
+            self.enter(AdsrStage::Release);
+        }
+    }
+
+    /// Silences the envelope immediately.
+    pub fn reset(&mut self) {
+        self.stage = AdsrStage::Idle;
+        self.value = 0.0;
+    }
+
+    /// Produces the next value.
+    pub fn next_sample(&mut self) -> f64 {
+        let (length, target) = match self.stage {
+            AdsrStage::Idle => return 0.0,
+            AdsrStage::Sustain => {
+                self.value = self.sustain.as_f64();
+                return self.value;
+            }
+            AdsrStage::Attack => (self.samples(self.attack), 1.0),
+            AdsrStage::Decay => (self.samples(self.decay), self.sustain.as_f64()),
+            AdsrStage::Release => (self.samples(self.release), 0.0),
+        };
+
+        self.ramp_position += 1;
+        let progress = self.ramp_position as f64 / length.max(1) as f64;
+        self.value = self.ramp_start + (target - self.ramp_start) * self.shape(progress.min(1.0));
+
+        if self.ramp_position >= length {
+            self.value = target;
+            self.enter(match self.stage {
+                AdsrStage::Attack => AdsrStage::Decay,
+                AdsrStage::Decay => AdsrStage::Sustain,
+                _ => AdsrStage::Idle,
+            });
+        }
+        self.value
+    }
+
+    /// Fills the given slice with the next values.
+    pub fn render_into<T: Sample>(&mut self, output: &mut [T]) {
+        for sample in output.iter_mut() {
+            *sample = T::from_f64(self.next_sample());
+        }
+    }
+
+    /// Multiplies every channel of the buffer with the next values.
+    pub fn apply<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        for index in buffer.sample_indices() {
+            let gain = self.next_sample();
+            for channel in buffer.iter_chans_mut() {
+                channel[index] = T::from_f64(channel[index].to_f64() * gain);
+            }
+        }
+    }
+
+    fn enter(&mut self, stage: AdsrStage) {
+        self.stage = stage;
+        self.ramp_start = self.value;
+        self.ramp_position = 0;
+        if stage == AdsrStage::Idle {
+            self.value = 0.0;
+        }
+    }
+
+    fn samples(&self, time: Seconds) -> usize {
+        time.to_samples(self.sample_rate).as_usize()
+    }
+
+    fn shape(&self, progress: f64) -> f64 {
+        match self.curve {
+            EnvelopeCurve::Linear => progress,
+            EnvelopeCurve::Exponential => {
+                let k = Self::EXPONENTIAL_SHAPE;
+                (1.0 - (-k * progress).exp()) / (1.0 - (-k).exp())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::{Channels, Samples};
+
+    /// An envelope at 1 kHz, so times in milliseconds are samples.
+    fn envelope(attack: f64, decay: f64, sustain: f64, release: f64) -> Adsr {
+        Adsr::new(
+            Seconds::from(attack / 1000.0),
+            Seconds::from(decay / 1000.0),
+            NormalizedValue::from(sustain),
+            Seconds::from(release / 1000.0),
+            SampleRate::from(1000),
+        )
+    }
+
+    fn run(envelope: &mut Adsr, length: usize) -> Vec<f64> {
+        (0..length).map(|_| envelope.next_sample()).collect()
+    }
+
+    #[test]
+    fn goes_through_all_stages() {
+        let mut adsr = envelope(4.0, 2.0, 0.5, 4.0);
+        assert_eq!(adsr.next_sample(), 0.0);
+
+        adsr.note_on();
+        assert_eq!(run(&mut adsr, 4), vec![0.25, 0.5, 0.75, 1.0]);
+        assert_eq!(adsr.stage(), AdsrStage::Decay);
+        assert_eq!(run(&mut adsr, 2), vec![0.75, 0.5]);
+        assert_eq!(adsr.stage(), AdsrStage::Sustain);
+        assert_eq!(run(&mut adsr, 3), vec![0.5, 0.5, 0.5]);
+
+        adsr.note_off();
+        assert_eq!(run(&mut adsr, 4), vec![0.375, 0.25, 0.125, 0.0]);
+        assert!(!adsr.is_active());
+    }
+
+    #[test]
+    fn release_during_attack_starts_from_the_current_level() {
+        let mut adsr = envelope(4.0, 2.0, 0.5, 2.0);
+        adsr.note_on();
+        run(&mut adsr, 2);
+
+        adsr.note_off();
+
+        assert_eq!(run(&mut adsr, 2), vec![0.25, 0.0]);
+        assert_eq!(adsr.stage(), AdsrStage::Idle);
+    }
+
+    #[test]
+    fn zero_times_jump_straight_to_the_level() {
+        let mut adsr = envelope(0.0, 0.0, 0.8, 0.0);
+
+        adsr.note_on();
+        assert_eq!(adsr.next_sample(), 1.0);
+        assert_eq!(adsr.next_sample(), 0.8);
+
+        adsr.note_off();
+        assert_eq!(adsr.next_sample(), 0.0);
+        assert!(!adsr.is_active());
+    }
+
+    #[test]
+    fn note_off_while_idle_does_nothing() {
+        let mut adsr = envelope(1.0, 1.0, 1.0, 1.0);
+        adsr.note_off();
+        assert_eq!(adsr.stage(), AdsrStage::Idle);
+    }
+
+    #[test_case(EnvelopeCurve::Linear => true; "linear is halfway at half time")]
+    #[test_case(EnvelopeCurve::Exponential => false; "exponential is further along")]
+    fn curve_shapes_the_attack(curve: EnvelopeCurve) -> bool {
+        let mut adsr = envelope(10.0, 0.0, 1.0, 0.0);
+        adsr.set_curve(curve);
+        adsr.note_on();
+
+        let attack = run(&mut adsr, 10);
+
+        assert_eq!(attack[9], 1.0);
+        attack[4] == 0.5
+    }
+
+    #[test]
+    fn apply_multiplies_the_buffer() {
+        let mut adsr = envelope(2.0, 0.0, 1.0, 0.0);
+        let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(3));
+        buffer.map_samples(|_| 2.0);
+
+        adsr.note_on();
+        adsr.apply(&mut buffer);
+
+        for channel in buffer.iter_chans() {
+            assert_eq!(channel, &[1.0, 2.0, 2.0]);
+        }
+    }
+}
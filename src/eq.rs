@@ -0,0 +1,272 @@
+//! This module contains a parametric equalizer that is built from multiple biquad bands.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::biquad::FilterType;
+//! use rabu::eq::{EqBand, Equalizer};
+//! use rabu::units::{Channels, Decibels, Frequency, SampleRate, Samples};
+//!
+//! let bands = vec![
+//!     EqBand::new(FilterType::HighPass, Frequency::from(80.0), 0.7, Decibels::from(0.0)),
+//!     EqBand::new(FilterType::Peak, Frequency::from(1000.0), 1.0, Decibels::from(-3.0)),
+//!     EqBand::new(FilterType::HighShelf, Frequency::from(8000.0), 0.7, Decibels::from(2.0)),
+//! ];
+//! let mut eq = Equalizer::new(SampleRate::from(44100), Channels::from(2), bands);
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! eq.process(&mut buffer);
+//!
+//! let response = eq.frequency_response(Frequency::from(1000.0));
+//! assert!((response.as_f64() + 3.0).abs() < 0.1);
+//! ```
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::biquad::{design, BiquadCoefficients, FilterType, MultiBiquad};
+use crate::buffer::Buffer;
+use crate::preset::Preset;
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::sample::Sample;
+use crate::units::{BufferSize, Channels, Decibels, Frequency, SampleRate};
+
+/// The settings of one band of an `Equalizer`.
+/// The gain is only used by the peak and shelf filter types.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EqBand {
+    pub filter_type: FilterType,
+    pub frequency: Frequency,
+    pub q: f64,
+    pub gain: Decibels,
+    pub enabled: bool,
+}
+
+impl EqBand {
+    /// Creates a new, enabled band with the given settings.
+    pub fn new(filter_type: FilterType, frequency: Frequency, q: f64, gain: Decibels) -> Self {
+        Self {
+            filter_type,
+            frequency,
+            q,
+            gain,
+            enabled: true,
+        }
+    }
+
+    /// Calculates the biquad coefficients for this band at the given sample rate.
+    pub fn coefficients(&self, sample_rate: SampleRate) -> BiquadCoefficients {
+        design(
+            self.filter_type,
+            sample_rate,
+            self.frequency,
+            self.q,
+            self.gain,
+        )
+    }
+}
+
+/// A parametric equalizer with any number of bands, processing them in series.
+/// Enabling or disabling a band is crossfaded, so it doesn't click.
+#[derive(Clone, Debug)]
+pub struct Equalizer {
+    sample_rate: SampleRate,
+    num_channels: Channels,
+    bands: Vec<EqBand>,
+    filters: Vec<MultiBiquad>,
+}
+
+impl Equalizer {
+    /// Creates a new equalizer with the given bands for the given number of channels.
+    pub fn new(sample_rate: SampleRate, num_channels: Channels, bands: Vec<EqBand>) -> Self {
+        let filters = bands
+            .iter()
+            .map(|band| {
+                let mut filter = MultiBiquad::new(band.coefficients(sample_rate), num_channels);
+                filter.set_bypassed(!band.enabled);
+                filter.reset();
+                filter
+            })
+            .collect();
+
+        Self {
+            sample_rate,
+            num_channels,
+            bands,
+            filters,
+        }
+    }
+
+    /// Returns the settings of all bands.
+    pub fn bands(&self) -> &[EqBand] {
+        &self.bands
+    }
+
+    /// Returns the number of bands.
+    pub fn num_bands(&self) -> usize {
+        self.bands.len()
+    }
+
+    /// Changes the settings of the band at the given index.
+    /// This will panic if there is no band at that index.
+    pub fn set_band(&mut self, index: usize, band: EqBand) {
+        self.bands[index] = band;
+        self.filters[index].set_coefficients(band.coefficients(self.sample_rate));
+        self.filters[index].set_bypassed(!band.enabled);
+    }
+
+    /// Enables or disables the band at the given index.
+    /// This will panic if there is no band at that index.
+    pub fn set_band_enabled(&mut self, index: usize, enabled: bool) {
+        self.bands[index].enabled = enabled;
+        self.filters[index].set_bypassed(!enabled);
+    }
+
+    /// Changes the sample rate, recalculating the coefficients of all bands.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = sample_rate;
+        for (band, filter) in self.bands.iter().zip(self.filters.iter_mut()) {
+            filter.set_coefficients(band.coefficients(sample_rate));
+        }
+    }
+
+    /// Clears the internal state of all bands and applies pending enable/disable changes
+    /// immediately.
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().for_each(MultiBiquad::reset);
+    }
+
+    /// Processes the given buffer in place through all bands.
+    /// This will panic if the buffer doesn't have the number of channels the equalizer was
+    /// created for.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        for filter in &mut self.filters {
+            filter.process(buffer);
+        }
+    }
+
+    /// Evaluates the combined magnitude response of all enabled bands at the given frequency.
+    /// This is useful for drawing the curve of the equalizer.
+    pub fn frequency_response(&self, frequency: Frequency) -> Decibels {
+        self.bands
+            .iter()
+            .filter(|band| band.enabled)
+            .map(|band| {
+                band.coefficients(self.sample_rate)
+                    .magnitude_at(frequency, self.sample_rate)
+            })
+            .fold(Decibels::from(0.0), |total, gain| total + gain)
+    }
+}
+
+impl AudioProcessor for Equalizer {
+    fn prepare(&mut self, sample_rate: SampleRate, _: BufferSize, num_channels: Channels) {
+        let bands = std::mem::take(&mut self.bands);
+        *self = Equalizer::new(sample_rate, num_channels, bands);
+    }
+
+    fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+        Equalizer::process(self, buffer);
+    }
+
+    fn reset(&mut self) {
+        Equalizer::reset(self);
+    }
+}
+
+/// The settings of an `Equalizer`, for presets.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct EqualizerSettings {
+    pub bands: Vec<EqBand>,
+}
+
+impl Preset for Equalizer {
+    type Settings = EqualizerSettings;
+    const PROCESSOR: &'static str = "equalizer";
+    const VERSION: u32 = 1;
+
+    fn settings(&self) -> EqualizerSettings {
+        EqualizerSettings {
+            bands: self.bands.clone(),
+        }
+    }
+
+    /// Changes the bands in place when the number of bands stays the same, so the sound
+    /// continues smoothly, and starts over with new bands otherwise.
+    fn apply_settings(&mut self, settings: &EqualizerSettings) {
+        if settings.bands.len() == self.bands.len() {
+            for (index, band) in settings.bands.iter().enumerate() {
+                self.set_band(index, *band);
+            }
+        } else {
+            *self = Equalizer::new(self.sample_rate, self.num_channels, settings.bands.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Samples;
+
+    fn peak(frequency: f64, gain: f64) -> EqBand {
+        EqBand::new(
+            FilterType::Peak,
+            Frequency::from(frequency),
+            1.0,
+            Decibels::from(gain),
+        )
+    }
+
+    #[test_case(FilterType::Peak, 1000.0 => 6.0; "peak at center")]
+    #[test_case(FilterType::LowShelf, 20.0 => 6.0; "low shelf below corner")]
+    #[test_case(FilterType::HighShelf, 20.0 => 0.0; "high shelf below corner")]
+    #[test_case(FilterType::HighShelf, 20000.0 => 6.0; "high shelf above corner")]
+    fn band_response(filter_type: FilterType, frequency: f64) -> f64 {
+        let band = EqBand::new(
+            filter_type,
+            Frequency::from(1000.0),
+            0.7,
+            Decibels::from(6.0),
+        );
+        let eq = Equalizer::new(SampleRate::from(96000), Channels::from(1), vec![band]);
+        let response = eq.frequency_response(Frequency::from(frequency)).as_f64();
+        (response * 10.0).round() / 10.0
+    }
+
+    #[test]
+    fn responses_of_bands_add_up() {
+        let bands = vec![peak(1000.0, 3.0), peak(1000.0, 2.0)];
+        let eq = Equalizer::new(SampleRate::from(48000), Channels::from(1), bands);
+
+        let response = eq.frequency_response(Frequency::from(1000.0));
+
+        assert!((response.as_f64() - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn disabled_bands_are_ignored() {
+        let bands = vec![peak(1000.0, 3.0), peak(1000.0, 2.0)];
+        let mut eq = Equalizer::new(SampleRate::from(48000), Channels::from(1), bands);
+
+        eq.set_band_enabled(0, false);
+
+        let response = eq.frequency_response(Frequency::from(1000.0));
+        assert!((response.as_f64() - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn initially_disabled_band_does_not_process() {
+        let mut band = peak(1000.0, 12.0);
+        band.enabled = false;
+        let mut eq = Equalizer::new(SampleRate::from(48000), Channels::from(1), vec![band]);
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(1), Samples::from(4));
+        buffer.chan_mut(0)[0] = 1.0;
+
+        eq.process(&mut buffer);
+
+        assert_eq!(buffer.chan(0), &[1.0, 0.0, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,151 @@
+//! This module contains timed events, like notes or parameter changes, and a helper that
+//! splits a block at the events in it. Every part of the block starts with the events at its
+//! start, so they can be applied before the part is processed, which makes them sample
+//! accurate.
+//! ```rust
+//! use rabu::events::{split_block, TimedEvent};
+//! use rabu::units::{SampleSection, Samples};
+//!
+//! // Gain changes on the timeline.
+//! let events = [
+//!     TimedEvent { position: Samples::from(1000), event: 0.5 },
+//!     TimedEvent { position: Samples::from(1100), event: 0.25 },
+//! ];
+//! let block = SampleSection { start: Samples::from(1024), length: Samples::from(128) };
+//!
+//! let mut gain = 1.0;
+//! let mut parts = Vec::new();
+//! split_block(block, &events, |part, events| {
+//!     for event in events {
+//!         gain = event.event;
+//!     }
+//!     parts.push((part.offset.as_usize(), part.section.length.as_usize(), gain));
+//! });
+//!
+//! // The first change is before the block, so the block starts at the old gain.
+//! assert_eq!(parts, [(0, 76, 1.0), (76, 52, 0.25)]);
+//! ```
+
+use crate::units::{SampleSection, Samples};
+
+/// An event at a position on the timeline.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimedEvent<E> {
+    /// The position of the event.
+    pub position: Samples,
+    /// The event.
+    pub event: E,
+}
+
+/// A part of a block, between two events.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SubBlock {
+    /// The samples of the timeline.
+    pub section: SampleSection,
+    /// Where in the block the part starts.
+    pub offset: Samples,
+}
+
+/// Splits the block at the positions of the events in it, and calls `process` for every part
+/// in order, with the events at its start. Events outside of the block are skipped, and an
+/// empty block has no parts. The events must be sorted by position.
+pub fn split_block<E>(
+    block: SampleSection,
+    events: &[TimedEvent<E>],
+    mut process: impl FnMut(SubBlock, &[TimedEvent<E>]),
+) {
+    let first = events.partition_point(|event| event.position < block.start);
+    let last = events.partition_point(|event| event.position < block.end());
+    let mut events = &events[first..last];
+
+    let mut start = block.start;
+    while start < block.end() {
+        let at_start = events
+            .iter()
+            .take_while(|event| event.position == start)
+            .count();
+        let end = events
+            .get(at_start)
+            .map_or(block.end(), |event| event.position);
+        process(
+            SubBlock {
+                section: SampleSection {
+                    start,
+                    length: end - start,
+                },
+                offset: start - block.start,
+            },
+            &events[..at_start],
+        );
+        events = &events[at_start..];
+        start = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(position: u64, event: char) -> TimedEvent<char> {
+        TimedEvent {
+            position: Samples::from(position),
+            event,
+        }
+    }
+
+    fn parts(start: u64, length: u64, events: &[TimedEvent<char>]) -> Vec<(u64, u64, u64, String)> {
+        let block = SampleSection {
+            start: Samples::from(start),
+            length: Samples::from(length),
+        };
+        let mut parts = Vec::new();
+        split_block(block, events, |part, events| {
+            parts.push((
+                part.section.start.as_u64(),
+                part.section.length.as_u64(),
+                part.offset.as_u64(),
+                events.iter().map(|event| event.event).collect(),
+            ));
+        });
+        parts
+    }
+
+    #[test]
+    fn without_events_the_block_is_one_part() {
+        assert_eq!(parts(10, 32, &[]), vec![(10, 32, 0, String::new())]);
+    }
+
+    #[test]
+    fn splits_at_every_event_position() {
+        let events = [
+            event(0, 'a'),
+            event(10, 'b'),
+            event(10, 'c'),
+            event(25, 'd'),
+        ];
+
+        assert_eq!(
+            parts(0, 32, &events),
+            vec![
+                (0, 10, 0, "a".to_string()),
+                (10, 15, 10, "bc".to_string()),
+                (25, 7, 25, "d".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_the_events_outside_of_the_block() {
+        let events = [event(5, 'a'), event(110, 'b'), event(132, 'c')];
+
+        assert_eq!(
+            parts(100, 32, &events),
+            vec![(100, 10, 0, String::new()), (110, 22, 10, "b".to_string())]
+        );
+    }
+
+    #[test]
+    fn empty_block_has_no_parts() {
+        assert_eq!(parts(10, 0, &[event(10, 'a')]), vec![]);
+    }
+}
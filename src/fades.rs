@@ -0,0 +1,405 @@
+//! This module contains fade curves, and fades and crossfades that use them. A curve gives the
+//! gain of a fade in at a position from 0 to 1; a fade out is the same curve played backwards.
+//! Which curve to use for a crossfade depends on the two signals: when they are the same or
+//! very alike (e.g. a processed and an unprocessed version), their amplitudes add up and a
+//! linear or S-curve fade keeps the level. When they are unrelated, their powers add up, and
+//! an equal power fade keeps the level. Splices join audio with a crossfade, so edits don't
+//! click.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::fades::{fade_in, FadeCurve};
+//! use rabu::units::{Channels, NormalizedValue, Samples};
+//!
+//! let (fade_out, fade_in_gain) = FadeCurve::EqualPower.crossfade_gains(NormalizedValue::from(0.3));
+//! assert!((fade_out.powi(2) + fade_in_gain.powi(2) - 1.0).abs() < 1e-12);
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(8));
+//! buffer.map_samples(|_| 1.0);
+//! fade_in(&mut buffer, Samples::from(4), FadeCurve::Linear);
+//!
+//! assert_eq!(buffer.chan(0), &[0.0, 0.25, 0.5, 0.75, 1.0, 1.0, 1.0, 1.0]);
+//! ```
+
+use std::f64::consts::FRAC_PI_2;
+
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::units::{Decibels, NormalizedValue, SampleSection, Samples};
+
+/// The shape of a fade.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FadeCurve {
+    /// A straight line. A fade in and out of the same length sum to 1.
+    Linear,
+    /// A quarter of a sine. The squares of a fade in and out of the same length sum to 1.
+    EqualPower,
+    /// Starts and ends slowly, with a fast middle. A fade in and out of the same length sum
+    /// to 1.
+    SCurve,
+    /// Moves in equal steps of decibels, starting at the range below full level. Sounds even
+    /// to the ear, especially for long fades.
+    Logarithmic { range: Decibels },
+    /// A custom curve from (0, 0) to (1, 1), shaped by two control points like a cubic Bézier
+    /// easing curve. The x coordinates of the control points are kept between 0 and 1.
+    Spline { x1: f64, y1: f64, x2: f64, y2: f64 },
+}
+
+impl FadeCurve {
+    /// Returns the gain of a fade in at the position, from 0 at the start to 1 at the end.
+    pub fn gain(&self, position: NormalizedValue) -> f64 {
+        let position = position.as_f64();
+        match *self {
+            FadeCurve::Linear => position,
+            FadeCurve::EqualPower => (position * FRAC_PI_2).sin(),
+            FadeCurve::SCurve => position * position * (3.0 - 2.0 * position),
+            FadeCurve::Logarithmic { range } => {
+                if position == 0.0 {
+                    0.0
+                } else {
+                    Decibels::from(-range.as_f64().abs() * (1.0 - position)).to_gain()
+                }
+            }
+            FadeCurve::Spline { x1, y1, x2, y2 } => {
+                spline(position, x1.clamp(0.0, 1.0), y1, x2.clamp(0.0, 1.0), y2)
+            }
+        }
+    }
+
+    /// Returns the gains of the signal that fades out and of the signal that fades in, at the
+    /// position of a crossfade.
+    pub fn crossfade_gains(&self, position: NormalizedValue) -> (f64, f64) {
+        let fade_out = self.gain(NormalizedValue::from(1.0 - position.as_f64()));
+        (fade_out, self.gain(position))
+    }
+}
+
+/// Returns the y of the Bézier curve at the x, by searching the curve parameter that gives
+/// that x. The x only goes up along the curve, so bisection always finds it.
+fn spline(x: f64, x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
+    let bezier = |t: f64, p1: f64, p2: f64| {
+        let s = 1.0 - t;
+        3.0 * s * s * t * p1 + 3.0 * s * t * t * p2 + t * t * t
+    };
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..60 {
+        let middle = (low + high) / 2.0;
+        if bezier(middle, x1, x2) < x {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    bezier((low + high) / 2.0, y1, y2)
+}
+
+/// Fades in the start of every channel of the buffer over the length. The first sample is
+/// silenced, and everything after the fade is left as it is.
+pub fn fade_in<T: Sample>(buffer: &mut Buffer<T>, length: Samples, curve: FadeCurve) {
+    let length = length.as_usize().min(buffer.num_samples().as_usize());
+    let gains = fade_gains(length, curve);
+    for channel in buffer.iter_chans_mut() {
+        for (sample, gain) in channel.iter_mut().zip(gains.iter()) {
+            *sample = T::from_f64(sample.to_f64() * gain);
+        }
+    }
+}
+
+/// Fades out the end of every channel of the buffer over the length, so the last sample is
+/// silenced.
+pub fn fade_out<T: Sample>(buffer: &mut Buffer<T>, length: Samples, curve: FadeCurve) {
+    let length = length.as_usize().min(buffer.num_samples().as_usize());
+    let gains = fade_gains(length, curve);
+    for channel in buffer.iter_chans_mut() {
+        for (sample, gain) in channel.iter_mut().rev().zip(gains.iter()) {
+            *sample = T::from_f64(sample.to_f64() * gain);
+        }
+    }
+}
+
+/// Returns a buffer that crossfades from the first buffer to the second over its whole length.
+/// This will panic if the buffers don't have the same size.
+pub fn crossfade<T: Sample>(from: &Buffer<T>, to: &Buffer<T>, curve: FadeCurve) -> Buffer<T> {
+    assert_eq!(from.num_channels(), to.num_channels());
+    assert_eq!(from.num_samples(), to.num_samples());
+    let length = from.num_samples().as_usize();
+    let mut output = Buffer::allocate(from.num_channels(), from.num_samples());
+
+    for channel in from.channel_indices() {
+        let (from, to) = (from.chan(channel), to.chan(channel));
+        for (index, sample) in output.chan_mut(channel).iter_mut().enumerate() {
+            let (from_gain, to_gain) = crossfade_gains_at(index, length, curve);
+            *sample = T::from_f64(from[index].to_f64() * from_gain + to[index].to_f64() * to_gain);
+        }
+    }
+    output
+}
+
+/// Returns a buffer with the head followed by the tail, where the end of the head crossfades
+/// into the start of the tail over the crossfade length, so the splice doesn't click. The
+/// crossfade is shortened to the shortest of the two buffers.
+/// This will panic if the buffers don't have the same number of channels.
+/// ```
+/// use rabu::buffer::Buffer;
+/// use rabu::fades::{splice, FadeCurve};
+/// use rabu::units::{Channels, Samples};
+///
+/// let mut head = Buffer::<f32>::allocate(Channels::from(1), Samples::from(4));
+/// head.map_samples(|_| 1.0);
+/// let tail = Buffer::<f32>::allocate(Channels::from(1), Samples::from(4));
+///
+/// let spliced = splice(&head, &tail, Samples::from(3), FadeCurve::Linear);
+///
+/// assert_eq!(spliced.chan(0), &[1.0, 1.0, 0.5, 0.0, 0.0]);
+/// ```
+pub fn splice<T: Sample>(
+    head: &Buffer<T>,
+    tail: &Buffer<T>,
+    crossfade: Samples,
+    curve: FadeCurve,
+) -> Buffer<T> {
+    assert_eq!(head.num_channels(), tail.num_channels());
+    let crossfade = crossfade.min(head.num_samples()).min(tail.num_samples());
+    let start = (head.num_samples() - crossfade).as_usize();
+    let length = crossfade.as_usize();
+    let mut output = Buffer::allocate(
+        head.num_channels(),
+        head.num_samples() + tail.num_samples() - crossfade,
+    );
+
+    for channel in head.channel_indices() {
+        let (head, tail) = (head.chan(channel), tail.chan(channel));
+        let output = output.chan_mut(channel);
+        output[..start].copy_from_slice(&head[..start]);
+        for index in 0..length {
+            let (head_gain, tail_gain) = crossfade_gains_at(index, length, curve);
+            output[start + index] = T::from_f64(
+                head[start + index].to_f64() * head_gain + tail[index].to_f64() * tail_gain,
+            );
+        }
+        output[start + length..].copy_from_slice(&tail[length..]);
+    }
+    output
+}
+
+/// Cuts the section out of the buffer, and joins the audio before and after it. The audio
+/// from the start of the section on crossfades into the audio from the end of the section on,
+/// over the crossfade length, which is shortened to the audio there is after the section.
+/// This will panic if the section doesn't lie within the buffer.
+/// ```
+/// use rabu::buffer::Buffer;
+/// use rabu::fades::{splice_out, FadeCurve};
+/// use rabu::units::{Channels, SampleSection, Samples};
+///
+/// let mut buffer = Buffer::<f32>::allocate(Channels::from(1), Samples::from(8));
+/// buffer.chan_mut(0).copy_from_slice(&[0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+///
+/// let section = SampleSection { start: Samples::from(2), length: Samples::from(4) };
+/// splice_out(&mut buffer, section, Samples::from(0), FadeCurve::Linear);
+///
+/// assert_eq!(buffer.chan(0), &[0.0, 1.0, 6.0, 7.0]);
+/// ```
+pub fn splice_out<T: Sample>(
+    buffer: &mut Buffer<T>,
+    section: SampleSection,
+    crossfade: Samples,
+    curve: FadeCurve,
+) {
+    assert!(section.end() <= buffer.num_samples());
+    let (start, end) = (section.start.as_usize(), section.end().as_usize());
+    let length = crossfade
+        .min(buffer.num_samples() - section.end())
+        .as_usize();
+
+    for channel in buffer.iter_chans_mut() {
+        for index in 0..length {
+            let (head_gain, tail_gain) = crossfade_gains_at(index, length, curve);
+            channel[start + index] = T::from_f64(
+                channel[start + index].to_f64() * head_gain
+                    + channel[end + index].to_f64() * tail_gain,
+            );
+        }
+        channel.copy_within(end + length.., start + length);
+    }
+    *buffer = buffer.clone_resized(buffer.num_channels(), buffer.num_samples() - section.length);
+}
+
+/// The gains of the audio that fades out and the audio that fades in, at the index of a
+/// crossfade of the length, which ends with only the audio that fades in.
+fn crossfade_gains_at(index: usize, length: usize, curve: FadeCurve) -> (f64, f64) {
+    let position = if length > 1 {
+        index as f64 / (length - 1) as f64
+    } else {
+        1.0
+    };
+    curve.crossfade_gains(NormalizedValue::from(position))
+}
+
+/// The gains of a fade in of the length, starting at silence.
+fn fade_gains(length: usize, curve: FadeCurve) -> Vec<f64> {
+    (0..length)
+        .map(|index| curve.gain(NormalizedValue::from(index as f64 / length as f64)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Channels;
+
+    fn curves() -> [FadeCurve; 5] {
+        [
+            FadeCurve::Linear,
+            FadeCurve::EqualPower,
+            FadeCurve::SCurve,
+            FadeCurve::Logarithmic {
+                range: Decibels::from(60.0),
+            },
+            FadeCurve::Spline {
+                x1: 0.4,
+                y1: 0.0,
+                x2: 0.2,
+                y2: 1.0,
+            },
+        ]
+    }
+
+    fn positions() -> impl Iterator<Item = NormalizedValue> {
+        (0..=20).map(|n| NormalizedValue::from(n as f64 / 20.0))
+    }
+
+    #[test]
+    fn curves_go_from_silence_to_full_level() {
+        for curve in curves() {
+            assert!(curve.gain(NormalizedValue::from(0.0)).abs() < 1e-9);
+            assert!((curve.gain(NormalizedValue::from(1.0)) - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn curves_only_go_up() {
+        for curve in curves() {
+            let gains: Vec<f64> = positions().map(|position| curve.gain(position)).collect();
+            assert!(gains.windows(2).all(|pair| pair[0] <= pair[1]), "{curve:?}");
+        }
+    }
+
+    #[test_case(FadeCurve::Linear; "linear")]
+    #[test_case(FadeCurve::SCurve; "s-curve")]
+    fn amplitudes_sum_to_one(curve: FadeCurve) {
+        for position in positions() {
+            let (fade_out, fade_in) = curve.crossfade_gains(position);
+            assert!((fade_out + fade_in - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn equal_power_keeps_the_power() {
+        for position in positions() {
+            let (fade_out, fade_in) = FadeCurve::EqualPower.crossfade_gains(position);
+            assert!((fade_out * fade_out + fade_in * fade_in - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn logarithmic_moves_in_decibels() {
+        let curve = FadeCurve::Logarithmic {
+            range: Decibels::from(60.0),
+        };
+        let gain = curve.gain(NormalizedValue::from(0.5));
+        assert!((Decibels::from_gain(gain).as_f64() + 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn spline_with_control_points_on_the_diagonal_is_linear() {
+        let curve = FadeCurve::Spline {
+            x1: 1.0 / 3.0,
+            y1: 1.0 / 3.0,
+            x2: 2.0 / 3.0,
+            y2: 2.0 / 3.0,
+        };
+        for position in positions() {
+            assert!((curve.gain(position) - position.as_f64()).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn fade_out_ends_in_silence() {
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(1), Samples::from(6));
+        buffer.map_samples(|_| 1.0);
+
+        fade_out(&mut buffer, Samples::from(4), FadeCurve::Linear);
+
+        assert_eq!(buffer.chan(0), &[1.0, 1.0, 0.75, 0.5, 0.25, 0.0]);
+    }
+
+    #[test]
+    fn crossfade_goes_from_one_buffer_to_the_other() {
+        let mut from = Buffer::<f64>::allocate(Channels::from(1), Samples::from(5));
+        from.map_samples(|_| 1.0);
+        let to = Buffer::allocate(Channels::from(1), Samples::from(5));
+
+        let output = crossfade(&from, &to, FadeCurve::Linear);
+
+        assert_eq!(output.chan(0), &[1.0, 0.75, 0.5, 0.25, 0.0]);
+    }
+
+    #[test]
+    fn splice_keeps_the_level_of_the_same_signal() {
+        let mut head = Buffer::<f64>::allocate(Channels::from(2), Samples::from(100));
+        head.map_samples(|_| 0.5);
+        let mut tail = Buffer::<f64>::allocate(Channels::from(2), Samples::from(50));
+        tail.map_samples(|_| 0.5);
+
+        let spliced = splice(&head, &tail, Samples::from(20), FadeCurve::SCurve);
+
+        assert_eq!(spliced.num_samples(), Samples::from(130));
+        assert!(spliced
+            .data()
+            .iter()
+            .all(|sample| (sample - 0.5).abs() < 1e-12));
+    }
+
+    #[test]
+    fn splice_shortens_the_crossfade_to_the_shortest_buffer() {
+        let head = Buffer::<f64>::allocate(Channels::from(1), Samples::from(10));
+        let mut tail = Buffer::<f64>::allocate(Channels::from(1), Samples::from(3));
+        tail.map_samples(|_| 1.0);
+
+        let spliced = splice(&head, &tail, Samples::from(8), FadeCurve::Linear);
+
+        assert_eq!(spliced.num_samples(), Samples::from(10));
+        assert_eq!(&spliced.chan(0)[7..], &[0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn splice_out_crossfades_over_the_cut() {
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(2), Samples::from(10));
+        for channel in buffer.iter_chans_mut() {
+            channel[..5].fill(1.0);
+        }
+
+        let section = SampleSection {
+            start: Samples::from(2),
+            length: Samples::from(4),
+        };
+        splice_out(&mut buffer, section, Samples::from(3), FadeCurve::Linear);
+
+        assert_eq!(buffer.num_samples(), Samples::from(6));
+        for channel in buffer.iter_chans() {
+            assert_eq!(channel, &[1.0, 1.0, 1.0, 0.5, 0.0, 0.0]);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn splice_out_of_a_section_past_the_end_panics() {
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(1), Samples::from(4));
+        let section = SampleSection {
+            start: Samples::from(2),
+            length: Samples::from(4),
+        };
+        splice_out(&mut buffer, section, Samples::from(0), FadeCurve::Linear);
+    }
+}
@@ -0,0 +1,215 @@
+//! This module contains a C interface, with the `ffi` feature, so a C or C++ host can hand its
+//! audio to DSP written with this crate without copying it. A `RabuBuffer` describes the
+//! channels of the host: one pointer per channel, and the number of samples they hold. On the
+//! Rust side it becomes a `BufferViewMut` of the same memory. The unit conversions are exposed
+//! as well, so the host computes them the same way.
+//! ```rust
+//! use rabu::ffi::{rabu_buffer_new, rabu_decibels_to_gain};
+//!
+//! // As a host would pass its channels.
+//! let mut left = [1.0_f32; 4];
+//! let mut right = [1.0_f32; 4];
+//! let channels = [left.as_mut_ptr(), right.as_mut_ptr()];
+//! let buffer = rabu_buffer_new(channels.as_ptr(), 2, 4);
+//!
+//! // Safety: the channels stay valid while the view is used, and don't overlap.
+//! unsafe {
+//!     buffer.with_view_mut(|mut view| {
+//!         let gain = rabu_decibels_to_gain(-6.0) as f32;
+//!         for channel in view.iter_chans_mut() {
+//!             channel.iter_mut().for_each(|sample| *sample *= gain);
+//!         }
+//!     });
+//! }
+//!
+//! assert!((left[0] - 0.501).abs() < 0.001);
+//! ```
+
+use crate::buffer_view::BufferViewMut;
+use crate::units::{Decibels, Frequency, MidiNote, SampleRate, Samples, Seconds};
+
+/// The most channels a buffer can have, so a view of it doesn't allocate.
+pub const MAX_CHANNELS: usize = 64;
+
+/// The channels of a host, which point to `num_samples` samples each.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct RabuBuffer {
+    /// The pointers to the channels.
+    pub channels: *const *mut f32,
+    /// The number of channels.
+    pub num_channels: u32,
+    /// The number of samples per channel.
+    pub num_samples: u32,
+}
+
+impl RabuBuffer {
+    /// Calls `f` with a view of the channels, or gives `None` without calling it when there
+    /// are more than `MAX_CHANNELS` channels, or a null pointer where samples should be.
+    ///
+    /// # Safety
+    ///
+    /// When there are channels, `channels` must point to `num_channels` pointers, which each
+    /// point to `num_samples` samples. The channels must not overlap, and nothing else may use
+    /// them while `f` runs. Without samples, the pointers to the channels may be null.
+    pub unsafe fn with_view_mut<R>(
+        &self,
+        f: impl FnOnce(BufferViewMut<'_, '_, f32>) -> R,
+    ) -> Option<R> {
+        let num_channels = self.num_channels as usize;
+        let num_samples = self.num_samples as usize;
+        if num_channels > MAX_CHANNELS || (num_channels > 0 && self.channels.is_null()) {
+            return None;
+        }
+        let pointers = match num_channels {
+            0 => &[],
+            _ => std::slice::from_raw_parts(self.channels, num_channels),
+        };
+        if num_samples > 0 && pointers.iter().any(|pointer| pointer.is_null()) {
+            return None;
+        }
+        let mut channels: [&mut [f32]; MAX_CHANNELS] =
+            std::array::from_fn(|channel| match pointers.get(channel) {
+                Some(pointer) if num_samples > 0 => {
+                    std::slice::from_raw_parts_mut(*pointer, num_samples)
+                }
+                _ => &mut [],
+            });
+        Some(f(BufferViewMut::new(&mut channels[..num_channels])))
+    }
+}
+
+/// Describes the channels of a host.
+#[no_mangle]
+pub extern "C" fn rabu_buffer_new(
+    channels: *const *mut f32,
+    num_channels: u32,
+    num_samples: u32,
+) -> RabuBuffer {
+    RabuBuffer {
+        channels,
+        num_channels,
+        num_samples,
+    }
+}
+
+/// Fills all channels of the buffer with silence. Returns `false`, and leaves the channels as
+/// they are, when the buffer has more than `MAX_CHANNELS` channels or null channels.
+///
+/// # Safety
+///
+/// The buffer must be valid, see `RabuBuffer::with_view_mut`.
+#[no_mangle]
+pub unsafe extern "C" fn rabu_buffer_clear(buffer: RabuBuffer) -> bool {
+    buffer
+        .with_view_mut(|mut view| view.fill_default())
+        .is_some()
+}
+
+/// Converts decibels to a gain factor.
+#[no_mangle]
+pub extern "C" fn rabu_decibels_to_gain(decibels: f64) -> f64 {
+    Decibels::from(decibels).to_gain()
+}
+
+/// Converts a gain factor to decibels.
+#[no_mangle]
+pub extern "C" fn rabu_gain_to_decibels(gain: f64) -> f64 {
+    Decibels::from_gain(gain).as_f64()
+}
+
+/// Converts seconds to a number of samples at the sample rate.
+#[no_mangle]
+pub extern "C" fn rabu_seconds_to_samples(seconds: f64, sample_rate: u32) -> u64 {
+    Seconds::from(seconds)
+        .to_samples(SampleRate::from(sample_rate))
+        .as_u64()
+}
+
+/// Converts a number of samples at the sample rate to seconds.
+#[no_mangle]
+pub extern "C" fn rabu_samples_to_seconds(samples: u64, sample_rate: u32) -> f64 {
+    Samples::from(samples)
+        .to_seconds(SampleRate::from(sample_rate))
+        .as_f64()
+}
+
+/// Converts a MIDI note number to its frequency in Hz.
+#[no_mangle]
+pub extern "C" fn rabu_midi_note_to_frequency(note: u8) -> f64 {
+    MidiNote::from(note).to_frequency().as_f64()
+}
+
+/// Converts a frequency in Hz to the nearest MIDI note number.
+#[no_mangle]
+pub extern "C" fn rabu_frequency_to_midi_note(frequency: f64) -> u8 {
+    MidiNote::from_frequency(Frequency::from(frequency)).as_u8()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Channels;
+
+    #[test]
+    fn views_the_channels_of_the_host() {
+        let mut left = [0.5_f32; 3];
+        let mut right = [-0.5_f32; 3];
+        let channels = [left.as_mut_ptr(), right.as_mut_ptr()];
+        let buffer = rabu_buffer_new(channels.as_ptr(), 2, 3);
+
+        let num_samples = unsafe {
+            buffer.with_view_mut(|mut view| {
+                view.chan_mut(1)[2] = 1.0;
+                view.num_samples()
+            })
+        };
+
+        assert_eq!(num_samples, Some(Samples::from(3)));
+        assert_eq!(right, [-0.5, -0.5, 1.0]);
+        assert!(unsafe { rabu_buffer_clear(buffer) });
+        assert_eq!(left, [0.0; 3]);
+    }
+
+    #[test]
+    fn a_buffer_without_channels_is_empty() {
+        let buffer = rabu_buffer_new(std::ptr::null(), 0, 0);
+
+        let num_channels = unsafe { buffer.with_view_mut(|view| view.num_channels()) };
+
+        assert_eq!(num_channels, Some(Channels::from(0)));
+    }
+
+    #[test]
+    fn channels_without_samples_may_be_null() {
+        let channels = [std::ptr::null_mut(); 2];
+        let buffer = rabu_buffer_new(channels.as_ptr(), 2, 0);
+
+        let num_channels = unsafe { buffer.with_view_mut(|view| view.num_channels()) };
+
+        assert_eq!(num_channels, Some(Channels::from(2)));
+        assert!(unsafe { rabu_buffer_clear(buffer) });
+    }
+
+    #[test]
+    fn refuses_buffers_it_cannot_view() {
+        let mut samples = [1.0_f32; 4];
+        let too_many = [samples.as_mut_ptr(); MAX_CHANNELS + 1];
+        let null = [samples.as_mut_ptr(), std::ptr::null_mut()];
+
+        assert!(!unsafe { rabu_buffer_clear(rabu_buffer_new(too_many.as_ptr(), 65, 1)) });
+        assert!(!unsafe { rabu_buffer_clear(rabu_buffer_new(null.as_ptr(), 2, 4)) });
+        assert!(!unsafe { rabu_buffer_clear(rabu_buffer_new(std::ptr::null(), 1, 4)) });
+        assert_eq!(samples, [1.0; 4]);
+    }
+
+    #[test]
+    fn converts_units() {
+        assert!((rabu_decibels_to_gain(-20.0) - 0.1).abs() < 1e-12);
+        assert!((rabu_gain_to_decibels(0.1) + 20.0).abs() < 1e-12);
+        assert_eq!(rabu_seconds_to_samples(0.5, 48000), 24000);
+        assert_eq!(rabu_samples_to_seconds(24000, 48000), 0.5);
+        assert!((rabu_midi_note_to_frequency(69) - 440.0).abs() < 1e-9);
+        assert_eq!(rabu_frequency_to_midi_note(440.0), 69);
+    }
+}
@@ -0,0 +1,374 @@
+//! This module contains a dependency-free FFT, with typed APIs for transforming real audio
+//! into complex frequency bins and back. Use `FrequencyBin` to find the frequency of a bin.
+//! The FFT is a radix-2 one, so it only supports sizes that are a power of two, and panics on
+//! other sizes. Pad the audio with silence up to such a size, e.g. 1024 or 4096.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::fft::{Complex, RealFft};
+//! use rabu::units::{Channels, Frequency, FrequencyBin, SampleRate, Samples};
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let fft_size = Samples::from(1024);
+//! let mut input = Buffer::<f32>::allocate(Channels::from(1), fft_size);
+//! for (n, sample) in input.chan_mut(0).iter_mut().enumerate() {
+//!     *sample = (2.0 * std::f32::consts::PI * 3000.0 * n as f32 / 48000.0).sin();
+//! }
+//!
+//! let mut fft = RealFft::new(fft_size);
+//! let mut spectrum = Buffer::<Complex>::allocate(Channels::from(1), fft.num_bins());
+//! fft.forward_buffer(&input, Samples::from(0), &mut spectrum);
+//!
+//! let loudest = (0..fft.num_bins().as_usize())
+//!     .max_by(|a, b| spectrum.chan(0)[*a].norm().total_cmp(&spectrum.chan(0)[*b].norm()))
+//!     .map(FrequencyBin::from)
+//!     .unwrap();
+//!
+//! assert_eq!(loudest.to_frequency(sample_rate, fft_size), Frequency::from(3000.0));
+//! ```
+
+use std::ops::{Add, AddAssign, Mul, Sub};
+
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::units::Samples;
+
+/// A complex number, as used by the FFT.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    /// Creates a new complex number from its real and imaginary part.
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// Creates a complex number from a magnitude and a phase (in radians).
+    pub fn from_polar(norm: f64, arg: f64) -> Self {
+        Self::new(norm * arg.cos(), norm * arg.sin())
+    }
+
+    /// Returns the magnitude (absolute value) of the complex number.
+    pub fn norm(&self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// Returns the squared magnitude, which is cheaper to compute than the magnitude.
+    pub fn norm_sqr(&self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// Returns the phase (argument) of the complex number in radians.
+    pub fn arg(&self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    /// Returns the complex conjugate.
+    pub fn conj(&self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl AddAssign for Complex {
+    fn add_assign(&mut self, rhs: Self) {
+        self.re += rhs.re;
+        self.im += rhs.im;
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Mul<f64> for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        Self::new(self.re * rhs, self.im * rhs)
+    }
+}
+
+/// A precomputed radix-2 FFT of a fixed (power of two) size.
+/// Transforming doesn't allocate, so it can be used on the audio thread.
+#[derive(Clone, Debug)]
+pub struct FftPlan {
+    twiddles: Vec<Complex>,
+    bit_reversed: Vec<usize>,
+}
+
+impl FftPlan {
+    /// Creates a plan for the given size.
+    /// This will panic if the size is not a power of two.
+    pub fn new(size: usize) -> Self {
+        assert!(size.is_power_of_two(), "FFT size must be a power of two");
+
+        let twiddles = (0..size / 2)
+            .map(|k| {
+                let phase = -2.0 * std::f64::consts::PI * k as f64 / size as f64;
+                Complex::new(phase.cos(), phase.sin())
+            })
+            .collect();
+
+        let bits = size.trailing_zeros();
+        let bit_reversed = (0..size)
+            .map(|index| match bits {
+                0 => 0,
+                _ => index.reverse_bits() >> (usize::BITS - bits),
+            })
+            .collect();
+
+        Self {
+            twiddles,
+            bit_reversed,
+        }
+    }
+
+    /// Returns the size of the transform.
+    pub fn size(&self) -> usize {
+        self.bit_reversed.len()
+    }
+
+    /// Performs the forward transform in place.
+    /// This will panic if the data doesn't have the size of the plan.
+    pub fn forward(&self, data: &mut [Complex]) {
+        self.transform(data, false);
+    }
+
+    /// Performs the inverse transform in place, including the `1 / size` scaling.
+    /// This will panic if the data doesn't have the size of the plan.
+    pub fn inverse(&self, data: &mut [Complex]) {
+        self.transform(data, true);
+        let scale = 1.0 / self.size() as f64;
+        data.iter_mut().for_each(|value| *value = *value * scale);
+    }
+
+    fn transform(&self, data: &mut [Complex], inverse: bool) {
+        let size = self.size();
+        assert_eq!(data.len(), size);
+
+        for (index, reversed) in self.bit_reversed.iter().enumerate() {
+            if index < *reversed {
+                data.swap(index, *reversed);
+            }
+        }
+
+        let mut length = 2;
+        while length <= size {
+            let stride = size / length;
+            for start in (0..size).step_by(length) {
+                for k in 0..length / 2 {
+                    let twiddle = self.twiddles[k * stride];
+                    let twiddle = if inverse { twiddle.conj() } else { twiddle };
+                    let even = data[start + k];
+                    let odd = data[start + k + length / 2] * twiddle;
+                    data[start + k] = even + odd;
+                    data[start + k + length / 2] = even - odd;
+                }
+            }
+            length *= 2;
+        }
+    }
+}
+
+/// Transforms real signals of a fixed size into their spectrum and back. Only the bins from DC
+/// up to and including Nyquist are produced, since the rest mirrors them for real input.
+/// Transforming doesn't allocate, so it can be used on the audio thread.
+#[derive(Clone, Debug)]
+pub struct RealFft {
+    plan: FftPlan,
+    scratch: Vec<Complex>,
+}
+
+impl RealFft {
+    /// Creates a new real FFT of the given size.
+    /// This will panic if the size is not a power of two.
+    pub fn new(size: Samples) -> Self {
+        Self {
+            plan: FftPlan::new(size.as_usize()),
+            scratch: vec![Complex::default(); size.as_usize()],
+        }
+    }
+
+    /// Returns the size of the transform.
+    pub fn size(&self) -> Samples {
+        Samples::from(self.plan.size())
+    }
+
+    /// Returns the number of bins in the spectrum, which is `size / 2 + 1`.
+    pub fn num_bins(&self) -> Samples {
+        Samples::from(self.plan.size() / 2 + 1)
+    }
+
+    /// Transforms the given real input into its spectrum.
+    /// This will panic if the input doesn't have the size of the FFT,
+    /// or if the output doesn't have `num_bins` bins.
+    pub fn forward<T: Sample>(&mut self, input: &[T], output: &mut [Complex]) {
+        assert_eq!(input.len(), self.plan.size());
+        assert_eq!(output.len(), self.num_bins().as_usize());
+
+        for (value, sample) in self.scratch.iter_mut().zip(input) {
+            *value = Complex::new(sample.to_f64(), 0.0);
+        }
+        self.plan.forward(&mut self.scratch);
+        output.copy_from_slice(&self.scratch[..output.len()]);
+    }
+
+    /// Transforms the given spectrum back into a real signal.
+    /// This will panic if the input doesn't have `num_bins` bins,
+    /// or if the output doesn't have the size of the FFT.
+    pub fn inverse<T: Sample>(&mut self, input: &[Complex], output: &mut [T]) {
+        let size = self.plan.size();
+        assert_eq!(input.len(), self.num_bins().as_usize());
+        assert_eq!(output.len(), size);
+
+        self.scratch[..input.len()].copy_from_slice(input);
+        for bin in input.len()..size {
+            self.scratch[bin] = self.scratch[size - bin].conj();
+        }
+        self.plan.inverse(&mut self.scratch);
+
+        for (sample, value) in output.iter_mut().zip(self.scratch.iter()) {
+            *sample = T::from_f64(value.re);
+        }
+    }
+
+    /// Transforms a window of every channel of the input buffer, starting at `offset`, into the
+    /// channels of the output buffer. Samples past the end of the input are treated as silence.
+    /// This will panic if the buffers don't have the same number of channels,
+    /// or if the output doesn't have `num_bins` samples.
+    pub fn forward_buffer<T: Sample>(
+        &mut self,
+        input: &Buffer<T>,
+        offset: Samples,
+        output: &mut Buffer<Complex>,
+    ) {
+        assert_eq!(input.num_channels(), output.num_channels());
+        assert_eq!(output.num_samples(), self.num_bins());
+
+        let start = offset.as_usize().min(input.num_samples().as_usize());
+        let end = (start + self.plan.size()).min(input.num_samples().as_usize());
+
+        for (channel, spectrum) in input.iter_chans().zip(output.iter_chans_mut()) {
+            self.scratch.fill(Complex::default());
+            for (value, sample) in self.scratch.iter_mut().zip(&channel[start..end]) {
+                value.re = sample.to_f64();
+            }
+            self.plan.forward(&mut self.scratch);
+            spectrum.copy_from_slice(&self.scratch[..spectrum.len()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Channels;
+
+    fn distance(a: Complex, b: Complex) -> f64 {
+        let difference = a - b;
+        difference.re.hypot(difference.im)
+    }
+
+    #[test]
+    fn impulse_has_flat_spectrum() {
+        let plan = FftPlan::new(8);
+        let mut data = [Complex::default(); 8];
+        data[0] = Complex::new(1.0, 0.0);
+
+        plan.forward(&mut data);
+
+        assert!(data.iter().all(|bin| *bin == Complex::new(1.0, 0.0)));
+    }
+
+    #[test]
+    fn matches_dft() {
+        let plan = FftPlan::new(16);
+        let input: Vec<_> = (0..16)
+            .map(|n| Complex::new((n as f64 * 0.7).sin(), (n as f64 * 0.3).cos()))
+            .collect();
+        let mut data = input.clone();
+
+        plan.forward(&mut data);
+
+        for (k, bin) in data.iter().enumerate() {
+            let expected = input
+                .iter()
+                .enumerate()
+                .fold(Complex::default(), |sum, (n, x)| {
+                    let phase = -2.0 * std::f64::consts::PI * (k * n) as f64 / 16.0;
+                    sum + *x * Complex::new(phase.cos(), phase.sin())
+                });
+            assert!(distance(*bin, expected) < 1e-9);
+        }
+    }
+
+    #[test]
+    fn inverse_restores_input() {
+        let plan = FftPlan::new(32);
+        let input: Vec<_> = (0..32).map(|n| Complex::new(n as f64, 0.0)).collect();
+        let mut data = input.clone();
+
+        plan.forward(&mut data);
+        plan.inverse(&mut data);
+
+        for (original, restored) in input.iter().zip(data.iter()) {
+            assert!(distance(*original, *restored) < 1e-9);
+        }
+    }
+
+    #[test]
+    fn real_fft_round_trip() {
+        let mut fft = RealFft::new(Samples::from(64));
+        let input: Vec<f64> = (0..64).map(|n| (n as f64 * 0.3).sin() + 0.25).collect();
+        let mut spectrum = vec![Complex::default(); 33];
+        let mut output = vec![0.0; 64];
+
+        fft.forward(&input, &mut spectrum);
+        fft.inverse(&spectrum, &mut output);
+
+        for (original, restored) in input.iter().zip(output.iter()) {
+            assert!((original - restored).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn forward_buffer_zero_pads_past_the_end() {
+        let mut fft = RealFft::new(Samples::from(8));
+        let mut input = Buffer::<f32>::allocate(Channels::from(2), Samples::from(10));
+        input.chan_mut(1)[9] = 1.0;
+        let mut output = Buffer::allocate(Channels::from(2), fft.num_bins());
+
+        fft.forward_buffer(&input, Samples::from(9), &mut output);
+
+        assert!(output.chan(0).iter().all(|bin| *bin == Complex::default()));
+        assert!(output
+            .chan(1)
+            .iter()
+            .all(|bin| *bin == Complex::new(1.0, 0.0)));
+    }
+}
@@ -0,0 +1,265 @@
+//! This module contains a filterbank that splits a signal into octave or third-octave bands,
+//! using the base-ten center frequencies of IEC 61260. It can report the level of every band,
+//! which is useful for room measurements and spectrum displays.
+//! ```rust
+//! use rabu::filterbank::{BandResolution, FilterBank};
+//! use rabu::units::{Frequency, SampleRate};
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let mut bank = FilterBank::new(
+//!     sample_rate,
+//!     BandResolution::Octave,
+//!     Frequency::from(30.0),
+//!     Frequency::from(17000.0),
+//! );
+//!
+//! let sine: Vec<f64> = (0..48000)
+//!     .map(|n| (2.0 * std::f64::consts::PI * 1000.0 * n as f64 / 48000.0).sin())
+//!     .collect();
+//! bank.process(&sine);
+//!
+//! let loudest = bank
+//!     .levels()
+//!     .into_iter()
+//!     .max_by(|a, b| a.level.partial_cmp(&b.level).unwrap())
+//!     .unwrap();
+//!
+//! assert_eq!(loudest.center.as_f64().round(), 1000.0);
+//! ```
+
+use crate::biquad::{resonant_band_pass_coefficients, BiquadFilter};
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::units::{Channels, Decibels, Frequency, SampleRate};
+
+/// The octave ratio for base-ten bands, as defined by IEC 61260.
+const OCTAVE_RATIO: f64 = 1.995_262_314_968_879_5;
+
+/// The width of the bands in a `FilterBank`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BandResolution {
+    Octave,
+    ThirdOctave,
+}
+
+impl BandResolution {
+    /// Returns the number of bands per octave.
+    pub fn bands_per_octave(&self) -> u32 {
+        match self {
+            BandResolution::Octave => 1,
+            BandResolution::ThirdOctave => 3,
+        }
+    }
+
+    /// Returns the exact center frequencies of all bands between the two given frequencies
+    /// (inclusive), referenced to 1 kHz.
+    /// ```
+    /// use rabu::filterbank::BandResolution;
+    /// use rabu::units::Frequency;
+    ///
+    /// let centers = BandResolution::ThirdOctave
+    ///     .center_frequencies(Frequency::from(900.0), Frequency::from(1300.0));
+    ///
+    /// let rounded: Vec<_> = centers.iter().map(|f| f.as_f64().round()).collect();
+    /// assert_eq!(rounded, vec![1000.0, 1259.0]);
+    /// ```
+    pub fn center_frequencies(&self, lowest: Frequency, highest: Frequency) -> Vec<Frequency> {
+        let bands_per_octave = self.bands_per_octave() as f64;
+        let index_of = |frequency: Frequency| {
+            bands_per_octave * (frequency.as_f64() / 1000.0).log(OCTAVE_RATIO)
+        };
+
+        let first = index_of(lowest).ceil() as i32;
+        let last = index_of(highest).floor() as i32;
+
+        (first..=last)
+            .map(|index| 1000.0 * OCTAVE_RATIO.powf(index as f64 / bands_per_octave))
+            .map(Frequency::from)
+            .collect()
+    }
+
+    /// Returns the Q factor of a band, based on its lower and upper band edge.
+    fn q(&self) -> f64 {
+        let half_band = OCTAVE_RATIO.powf(1.0 / (2.0 * self.bands_per_octave() as f64));
+        1.0 / (half_band - 1.0 / half_band)
+    }
+}
+
+/// The measured level of one band of a `FilterBank`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BandLevel {
+    pub center: Frequency,
+    pub level: Decibels,
+}
+
+/// Splits a mono signal into octave or third-octave bands using band pass biquads.
+/// The bank keeps track of the energy in every band, until the levels are reset.
+#[derive(Clone, Debug)]
+pub struct FilterBank {
+    centers: Vec<Frequency>,
+    filters: Vec<BiquadFilter>,
+    energies: Vec<f64>,
+    num_measured_samples: u64,
+}
+
+impl FilterBank {
+    /// Creates a new filterbank with all bands that have their center frequency between
+    /// `lowest` and `highest`. Bands with a center frequency above Nyquist are left out.
+    pub fn new(
+        sample_rate: SampleRate,
+        resolution: BandResolution,
+        lowest: Frequency,
+        highest: Frequency,
+    ) -> Self {
+        let nyquist = Frequency::from(sample_rate.as_f64() / 2.0);
+        let centers: Vec<_> = resolution
+            .center_frequencies(lowest, highest)
+            .into_iter()
+            .filter(|center| *center < nyquist)
+            .collect();
+
+        let filters = centers
+            .iter()
+            .map(|center| {
+                let coefficients =
+                    resonant_band_pass_coefficients(sample_rate, *center, resolution.q());
+                BiquadFilter::new(coefficients)
+            })
+            .collect();
+
+        Self {
+            energies: vec![0.0; centers.len()],
+            centers,
+            filters,
+            num_measured_samples: 0,
+        }
+    }
+
+    /// Returns the center frequencies of all bands.
+    pub fn centers(&self) -> &[Frequency] {
+        &self.centers
+    }
+
+    /// Returns the number of bands.
+    pub fn num_bands(&self) -> usize {
+        self.centers.len()
+    }
+
+    /// Processes the given block of audio, updating the level of every band.
+    pub fn process<T: Sample>(&mut self, block: &[T]) {
+        for (filter, energy) in self.filters.iter_mut().zip(self.energies.iter_mut()) {
+            for sample in block {
+                let output = filter.process(sample.to_f64());
+                *energy += output * output;
+            }
+        }
+        self.num_measured_samples += block.len() as u64;
+    }
+
+    /// Splits the given block of audio into the bands, writing each band to its own channel
+    /// of the output buffer. The levels are updated as well.
+    /// This will panic if the output buffer doesn't have one channel per band,
+    /// or if its length doesn't match the length of the input.
+    pub fn split<T: Sample>(&mut self, block: &[T], output: &mut Buffer<T>) {
+        assert_eq!(output.num_channels(), Channels::from(self.num_bands()));
+        assert_eq!(output.num_samples().as_usize(), block.len());
+
+        let bands = self.filters.iter_mut().zip(self.energies.iter_mut());
+        for ((filter, energy), channel) in bands.zip(output.iter_chans_mut()) {
+            for (input, out) in block.iter().zip(channel.iter_mut()) {
+                let band_sample = filter.process(input.to_f64());
+                *energy += band_sample * band_sample;
+                *out = T::from_f64(band_sample);
+            }
+        }
+        self.num_measured_samples += block.len() as u64;
+    }
+
+    /// Returns the RMS level of every band over all audio processed since the last reset.
+    pub fn levels(&self) -> Vec<BandLevel> {
+        let num_samples = self.num_measured_samples.max(1) as f64;
+        self.centers
+            .iter()
+            .zip(self.energies.iter())
+            .map(|(center, energy)| BandLevel {
+                center: *center,
+                level: Decibels::from_gain((energy / num_samples).sqrt()),
+            })
+            .collect()
+    }
+
+    /// Resets the measured levels, while keeping the filter state.
+    pub fn reset_levels(&mut self) {
+        self.energies.fill(0.0);
+        self.num_measured_samples = 0;
+    }
+
+    /// Resets the measured levels and the filter state.
+    pub fn reset(&mut self) {
+        self.reset_levels();
+        self.filters.iter_mut().for_each(BiquadFilter::reset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Samples;
+
+    #[test_case(BandResolution::Octave => 10; "octaves")]
+    #[test_case(BandResolution::ThirdOctave => 30; "third octaves")]
+    fn number_of_audible_bands(resolution: BandResolution) -> usize {
+        resolution
+            .center_frequencies(Frequency::from(20.0), Frequency::from(20000.0))
+            .len()
+    }
+
+    #[test]
+    fn octave_centers_are_base_ten() {
+        let centers = BandResolution::Octave
+            .center_frequencies(Frequency::from(60.0), Frequency::from(300.0));
+        let rounded: Vec<_> = centers.iter().map(|f| f.as_f64().round()).collect();
+
+        assert_eq!(rounded, vec![63.0, 126.0, 251.0]);
+    }
+
+    #[test]
+    fn bands_above_nyquist_are_left_out() {
+        let bank = FilterBank::new(
+            SampleRate::from(8000),
+            BandResolution::Octave,
+            Frequency::from(1000.0),
+            Frequency::from(16000.0),
+        );
+
+        assert_eq!(bank.num_bands(), 3);
+    }
+
+    #[test]
+    fn sine_in_band_is_measured_at_its_level() {
+        let sample_rate = SampleRate::from(48000);
+        let mut bank = FilterBank::new(
+            sample_rate,
+            BandResolution::ThirdOctave,
+            Frequency::from(500.0),
+            Frequency::from(2000.0),
+        );
+        let sine: Vec<f32> = (0..48000)
+            .map(|n| (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / 48000.0).sin())
+            .collect();
+
+        let mut output = Buffer::allocate(Channels::from(bank.num_bands()), Samples::from(48000));
+        bank.split(&sine, &mut output);
+
+        let levels = bank.levels();
+        let band = levels
+            .iter()
+            .find(|band| band.center.as_f64().round() == 1000.0)
+            .unwrap();
+        let rms_of_sine = Decibels::from_gain(0.5_f64.sqrt());
+        assert!((band.level - rms_of_sine).as_f64().abs() < 0.1);
+        assert!(levels.iter().all(|other| other.level <= band.level));
+    }
+}
@@ -0,0 +1,264 @@
+//! This module contains a FIR filter, together with functions to design linear-phase
+//! windowed-sinc low pass, high pass and band pass filters.
+//! ```rust
+//! use rabu::fir::{windowed_sinc_low_pass, FirFilter};
+//! use rabu::units::{Frequency, Latency, SampleRate, Samples};
+//! use rabu::windows::Window;
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let taps = windowed_sinc_low_pass(
+//!     sample_rate,
+//!     Frequency::from(1000.0),
+//!     Samples::from(97),
+//!     Window::Blackman,
+//! );
+//! let mut filter = FirFilter::new(taps);
+//!
+//! let mut block = [0.5_f32; 256];
+//! filter.process_block(&mut block);
+//!
+//! assert_eq!(filter.latency(sample_rate), Latency::from_secs_f64(0.001));
+//! ```
+
+use crate::processor::SampleProcessor;
+use crate::sample::Sample;
+use crate::units::{Frequency, Latency, SampleRate, Samples};
+use crate::windows::Window;
+
+/// A finite impulse response filter, processing audio by convolving it with its taps.
+#[derive(Clone, Debug)]
+pub struct FirFilter {
+    taps: Vec<f64>,
+    history: Vec<f64>,
+    write_index: usize,
+}
+
+impl FirFilter {
+    /// Creates a new FIR filter with the given taps (impulse response).
+    /// This will panic if no taps are given.
+    pub fn new(taps: Vec<f64>) -> Self {
+        assert!(!taps.is_empty());
+        Self {
+            history: vec![0.0; taps.len()],
+            taps,
+            write_index: 0,
+        }
+    }
+
+    /// Returns the taps of the filter.
+    pub fn taps(&self) -> &[f64] {
+        &self.taps
+    }
+
+    /// Returns the number of taps of the filter.
+    pub fn length(&self) -> Samples {
+        Samples::from(self.taps.len())
+    }
+
+    /// Returns the group delay in samples, assuming the filter is linear-phase (symmetric taps),
+    /// like the windowed-sinc designs in this module.
+    pub fn group_delay(&self) -> f64 {
+        (self.taps.len() - 1) as f64 / 2.0
+    }
+
+    /// Returns the group delay as a latency at the given sample rate.
+    pub fn latency(&self, sample_rate: SampleRate) -> Latency {
+        Latency::from_secs_f64(self.group_delay() / sample_rate.as_f64())
+    }
+
+    /// Clears the internal state of the filter, as if it never processed any audio.
+    pub fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.write_index = 0;
+    }
+
+    /// Processes one sample of input audio and produces the filter output sample.
+    pub fn process(&mut self, input: f64) -> f64 {
+        self.history[self.write_index] = input;
+
+        let (newer, older) = self.history.split_at(self.write_index + 1);
+        let output = newer
+            .iter()
+            .rev()
+            .chain(older.iter().rev())
+            .zip(self.taps.iter())
+            .map(|(sample, tap)| sample * tap)
+            .sum();
+
+        self.write_index = (self.write_index + 1) % self.history.len();
+        output
+    }
+
+    /// Filters the given block of audio in place.
+    pub fn process_block<T: Sample>(&mut self, block: &mut [T]) {
+        for sample in block.iter_mut() {
+            *sample = T::from_f64(self.process(sample.to_f64()));
+        }
+    }
+}
+
+impl SampleProcessor for FirFilter {
+    fn process(&mut self, input: f64) -> f64 {
+        FirFilter::process(self, input)
+    }
+}
+
+/// Designs the taps of a linear-phase low pass filter with the given cutoff frequency.
+/// The taps are normalized to unity gain at DC.
+pub fn windowed_sinc_low_pass(
+    sample_rate: SampleRate,
+    cutoff_frequency: Frequency,
+    length: Samples,
+    window: Window,
+) -> Vec<f64> {
+    let mut taps = windowed_sinc(sample_rate, cutoff_frequency, length, window);
+    let sum: f64 = taps.iter().sum();
+    taps.iter_mut().for_each(|tap| *tap /= sum);
+    taps
+}
+
+/// Designs the taps of a linear-phase high pass filter with the given cutoff frequency,
+/// using spectral inversion of the low pass design.
+/// This will panic if the length is even, since an even length high pass can't pass Nyquist.
+pub fn windowed_sinc_high_pass(
+    sample_rate: SampleRate,
+    cutoff_frequency: Frequency,
+    length: Samples,
+    window: Window,
+) -> Vec<f64> {
+    assert!(length.as_usize() % 2 == 1, "high pass length must be odd");
+
+    let mut taps = windowed_sinc_low_pass(sample_rate, cutoff_frequency, length, window);
+    taps.iter_mut().for_each(|tap| *tap = -*tap);
+    taps[length.as_usize() / 2] += 1.0;
+    taps
+}
+
+/// Designs the taps of a linear-phase band pass filter that passes everything between
+/// the two given frequencies.
+/// This will panic if the length is even.
+pub fn windowed_sinc_band_pass(
+    sample_rate: SampleRate,
+    low_frequency: Frequency,
+    high_frequency: Frequency,
+    length: Samples,
+    window: Window,
+) -> Vec<f64> {
+    let low_pass = windowed_sinc_low_pass(sample_rate, low_frequency, length, window);
+    let high_pass = windowed_sinc_high_pass(sample_rate, high_frequency, length, window);
+
+    // The band pass is the inverse of the band stop, which is the sum of both filters.
+    let mut taps: Vec<_> = low_pass
+        .iter()
+        .zip(high_pass.iter())
+        .map(|(low, high)| -(low + high))
+        .collect();
+    taps[length.as_usize() / 2] += 1.0;
+    taps
+}
+
+fn windowed_sinc(
+    sample_rate: SampleRate,
+    cutoff_frequency: Frequency,
+    length: Samples,
+    window: Window,
+) -> Vec<f64> {
+    let length = length.as_usize();
+    let normalized_cutoff = cutoff_frequency.as_f64() / sample_rate.as_f64();
+    let center = (length - 1) as f64 / 2.0;
+
+    (0..length)
+        .map(|index| {
+            let x = index as f64 - center;
+            let sinc = if x == 0.0 {
+                2.0 * normalized_cutoff
+            } else {
+                (2.0 * std::f64::consts::PI * normalized_cutoff * x).sin()
+                    / (std::f64::consts::PI * x)
+            };
+            sinc * window.value(index, length)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::{impulse_response, magnitude_response};
+
+    fn gain_at(taps: Vec<f64>, frequency: f64) -> f64 {
+        let sample_rate = SampleRate::from(48000);
+        let mut filter = FirFilter::new(taps);
+        let response = impulse_response(&mut filter, Samples::from(4800));
+        let magnitudes = magnitude_response(response.chan(0), sample_rate);
+        magnitudes[(frequency / 10.0) as usize].1
+    }
+
+    #[test]
+    fn impulse_response_equals_taps() {
+        let mut filter = FirFilter::new(vec![0.25, 0.5, 0.25]);
+        let output: Vec<_> = [1.0, 0.0, 0.0, 0.0]
+            .iter()
+            .map(|x| filter.process(*x))
+            .collect();
+
+        assert_eq!(output, vec![0.25, 0.5, 0.25, 0.0]);
+    }
+
+    #[test]
+    fn low_pass_passes_low_and_blocks_high() {
+        let design = || {
+            windowed_sinc_low_pass(
+                SampleRate::from(48000),
+                Frequency::from(1000.0),
+                Samples::from(255),
+                Window::Blackman,
+            )
+        };
+
+        assert!((gain_at(design(), 100.0) - 1.0).abs() < 1e-3);
+        assert!(gain_at(design(), 5000.0) < 1e-3);
+    }
+
+    #[test]
+    fn high_pass_passes_high_and_blocks_low() {
+        let design = || {
+            windowed_sinc_high_pass(
+                SampleRate::from(48000),
+                Frequency::from(1000.0),
+                Samples::from(255),
+                Window::Hamming,
+            )
+        };
+
+        assert!(gain_at(design(), 0.0) < 1e-3);
+        assert!((gain_at(design(), 10000.0) - 1.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn band_pass_passes_only_band() {
+        let design = || {
+            windowed_sinc_band_pass(
+                SampleRate::from(48000),
+                Frequency::from(1000.0),
+                Frequency::from(4000.0),
+                Samples::from(255),
+                Window::Blackman,
+            )
+        };
+
+        assert!(gain_at(design(), 100.0) < 1e-2);
+        assert!((gain_at(design(), 2000.0) - 1.0).abs() < 1e-2);
+        assert!(gain_at(design(), 10000.0) < 1e-2);
+    }
+
+    #[test]
+    fn group_delay_is_half_the_length() {
+        let filter = FirFilter::new(vec![0.0; 11]);
+        assert_eq!(filter.group_delay(), 5.0);
+        assert_eq!(
+            filter.latency(SampleRate::from(10)),
+            Latency::from_secs_f64(0.5)
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! This module contains a first-order Thiran all-pass filter, which delays a signal by a
+//! fractional number of samples while leaving its magnitude untouched. This is what tuned
+//! delay lines (e.g. in Karplus-Strong or flangers) need to get precise sub-sample delays.
+//! ```rust
+//! use rabu::fractional_delay::ThiranAllpass;
+//! use rabu::units::{Samples, SamplesF64};
+//!
+//! // A delay of 20.3 samples: 19 from a plain delay line, 1.3 from the all-pass.
+//! let (whole, fractional) = ThiranAllpass::split_delay(SamplesF64::from(20.3));
+//! assert_eq!(whole, Samples::from(19));
+//!
+//! let mut allpass = ThiranAllpass::new(fractional);
+//! let output = allpass.process(1.0);
+//! ```
+
+use crate::processor::SampleProcessor;
+use crate::units::{Samples, SamplesF64};
+
+/// A first-order Thiran all-pass filter with a fractional delay.
+/// It is most accurate for delays between 0.5 and 1.5 samples.
+#[derive(Clone, Debug)]
+pub struct ThiranAllpass {
+    delay: SamplesF64,
+    coefficient: f64,
+    x1: f64,
+    y1: f64,
+}
+
+impl ThiranAllpass {
+    /// Creates a new all-pass filter with the given delay.
+    pub fn new(delay: SamplesF64) -> Self {
+        Self {
+            delay,
+            coefficient: Self::coefficient_for(delay),
+            x1: 0.0,
+            y1: 0.0,
+        }
+    }
+
+    /// Splits a delay into a whole number of samples (for a plain delay line) and a fractional
+    /// delay between 0.5 and 1.5 samples (for the all-pass), which keeps the all-pass in its
+    /// most accurate range. Delays shorter than 0.5 samples are given to the all-pass entirely.
+    pub fn split_delay(delay: SamplesF64) -> (Samples, SamplesF64) {
+        if delay.as_f64() < 0.5 {
+            return (Samples::from(0), delay);
+        }
+        let whole = (delay.as_f64() - 0.5).floor();
+        (
+            Samples::from(whole as u64),
+            SamplesF64::from(delay.as_f64() - whole),
+        )
+    }
+
+    /// Returns the delay of the filter.
+    pub fn delay(&self) -> SamplesF64 {
+        self.delay
+    }
+
+    /// Changes the delay of the filter, keeping the internal state.
+    pub fn set_delay(&mut self, delay: SamplesF64) {
+        self.delay = delay;
+        self.coefficient = Self::coefficient_for(delay);
+    }
+
+    /// Clears the internal state of the filter, as if it never processed any audio.
+    pub fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.y1 = 0.0;
+    }
+
+    /// Processes one sample of input audio and produces the delayed output sample.
+    pub fn process(&mut self, input: f64) -> f64 {
+        let output = self.coefficient * (input - self.y1) + self.x1;
+        self.x1 = input;
+        self.y1 = output;
+        output
+    }
+
+    fn coefficient_for(delay: SamplesF64) -> f64 {
+        let delay = delay.as_f64();
+        (1.0 - delay) / (1.0 + delay)
+    }
+}
+
+impl SampleProcessor for ThiranAllpass {
+    fn process(&mut self, input: f64) -> f64 {
+        ThiranAllpass::process(self, input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(0.3 => (0, 0.3); "short delay")]
+    #[test_case(1.4 => (0, 1.4); "within range")]
+    #[test_case(1.5 => (1, 0.5); "upper bound")]
+    #[test_case(20.3 => (19, 1.3); "long delay")]
+    fn split_delay(delay: f64) -> (u64, f64) {
+        let (whole, fractional) = ThiranAllpass::split_delay(SamplesF64::from(delay));
+        (whole.as_u64(), (fractional.as_f64() * 1e9).round() / 1e9)
+    }
+
+    #[test_case(0.5; "half a sample")]
+    #[test_case(1.0; "one sample")]
+    #[test_case(1.3; "fractional")]
+    fn delays_low_frequencies_by_given_amount(delay: f64) {
+        let mut allpass = ThiranAllpass::new(SamplesF64::from(delay));
+        let w = 0.01;
+
+        for n in 0..5000 {
+            let output = allpass.process((w * n as f64).cos());
+            if n > 4000 {
+                assert!((output - (w * (n as f64 - delay)).cos()).abs() < 1e-4);
+            }
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! This module contains the utility block of a channel strip: gain, mute, phase invert, and for
+//! stereo signals balance and channel swap. Every change glides over a short time, so even
+//! muting, inverting or swapping doesn't click.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::gain_utility::GainUtility;
+//! use rabu::units::{Channels, Decibels, Pan, SampleRate, Samples};
+//!
+//! let mut utility = GainUtility::new(SampleRate::from(48000), Channels::from(2));
+//! utility.set_gain(Decibels::from(-6.0));
+//! utility.set_balance(Pan::from(0.5));
+//! utility.set_inverted(1, true);
+//! // Skips the glide, e.g. before playback starts.
+//! utility.reset();
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(4));
+//! buffer.chan_mut(0).fill(1.0);
+//! buffer.chan_mut(1).fill(1.0);
+//! utility.process(&mut buffer);
+//!
+//! assert!((buffer.chan(0)[0] - 0.25).abs() < 0.01);
+//! assert!((buffer.chan(1)[0] + 0.5).abs() < 0.01);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::panning::balance;
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::sample::Sample;
+use crate::smoother::{Smoother, SmoothingMode};
+use crate::units::{BufferSize, Channels, Decibels, Pan, SampleRate, Seconds};
+
+/// The time over which changes glide, in seconds.
+const SMOOTHING: f64 = 0.02;
+
+/// Applies gain, mute, phase invert, balance and channel swap.
+#[derive(Clone, Debug)]
+pub struct GainUtility {
+    gain: Decibels,
+    muted: bool,
+    inverted: Vec<bool>,
+    balance: Pan,
+    swapped: bool,
+    /// The gain of every channel, with the sign of the phase.
+    gains: Vec<Smoother<f64>>,
+    /// How far the channels are swapped, from 0 to 1.
+    swap: Smoother<f64>,
+}
+
+impl GainUtility {
+    /// Creates a new utility at 0 dB, that leaves the audio untouched.
+    pub fn new(sample_rate: SampleRate, num_channels: Channels) -> Self {
+        let smoother = |value| {
+            Smoother::new(
+                SmoothingMode::Linear,
+                Seconds::from(SMOOTHING),
+                sample_rate,
+                value,
+            )
+        };
+        Self {
+            gain: Decibels::from(0.0),
+            muted: false,
+            inverted: vec![false; num_channels.as_usize()],
+            balance: Pan::from(0.0),
+            swapped: false,
+            gains: vec![smoother(1.0); num_channels.as_usize()],
+            swap: smoother(0.0),
+        }
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.gains.len())
+    }
+
+    /// Changes the sample rate, which the glides are timed with.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        for smoother in self.gains.iter_mut().chain([&mut self.swap]) {
+            smoother.set_time(Seconds::from(SMOOTHING), sample_rate);
+        }
+    }
+
+    /// Returns the gain.
+    pub fn gain(&self) -> Decibels {
+        self.gain
+    }
+
+    /// Glides to the gain.
+    pub fn set_gain(&mut self, gain: Decibels) {
+        self.gain = gain;
+        self.update_gains();
+    }
+
+    /// Returns whether the audio is muted.
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Fades the audio out or back in.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        self.update_gains();
+    }
+
+    /// Returns whether the phase of the channel is inverted.
+    /// This will panic if the channel doesn't exist.
+    pub fn is_inverted(&self, channel: usize) -> bool {
+        self.inverted[channel]
+    }
+
+    /// Inverts the phase of the channel, or turns it back.
+    /// This will panic if the channel doesn't exist.
+    pub fn set_inverted(&mut self, channel: usize, inverted: bool) {
+        self.inverted[channel] = inverted;
+        self.update_gains();
+    }
+
+    /// Returns the balance between the left and right channel.
+    pub fn balance(&self) -> Pan {
+        self.balance
+    }
+
+    /// Glides to the balance between the left and right channel, which turns down the side
+    /// that is balanced away from. It only applies to stereo audio.
+    pub fn set_balance(&mut self, balance: Pan) {
+        self.balance = balance;
+        self.update_gains();
+    }
+
+    /// Returns whether the left and right channel are swapped.
+    pub fn is_swapped(&self) -> bool {
+        self.swapped
+    }
+
+    /// Crossfades the left and right channel to swap them, or back. It only applies to stereo
+    /// audio.
+    pub fn set_swapped(&mut self, swapped: bool) {
+        self.swapped = swapped;
+        self.swap.set_target(if swapped { 1.0 } else { 0.0 });
+    }
+
+    /// Jumps to the settings without gliding.
+    pub fn reset(&mut self) {
+        for smoother in self.gains.iter_mut().chain([&mut self.swap]) {
+            smoother.reset(smoother.target());
+        }
+    }
+
+    /// Processes the buffer in place.
+    /// This will panic if the buffer doesn't have the same number of channels as the utility.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+        let is_stereo = self.gains.len() == 2;
+
+        for index in buffer.sample_indices() {
+            let swap = self.swap.next_sample();
+            if is_stereo && swap > 0.0 {
+                let left = buffer.chan(0)[index].to_f64();
+                let right = buffer.chan(1)[index].to_f64();
+                buffer.chan_mut(0)[index] = T::from_f64(left + (right - left) * swap);
+                buffer.chan_mut(1)[index] = T::from_f64(right + (left - right) * swap);
+            }
+            for (channel, gain) in self.gains.iter_mut().enumerate() {
+                let sample = &mut buffer.chan_mut(channel)[index];
+                *sample = T::from_f64(sample.to_f64() * gain.next_sample());
+            }
+        }
+    }
+
+    fn update_gains(&mut self) {
+        let gain = if self.muted { 0.0 } else { self.gain.to_gain() };
+        let (left, right) = balance(self.balance);
+        let is_stereo = self.gains.len() == 2;
+
+        for (channel, smoother) in self.gains.iter_mut().enumerate() {
+            let balance = match (is_stereo, channel) {
+                (true, 0) => left,
+                (true, _) => right,
+                (false, _) => 1.0,
+            };
+            let sign = if self.inverted[channel] { -1.0 } else { 1.0 };
+            smoother.set_target(sign * gain * balance);
+        }
+    }
+}
+
+impl AudioProcessor for GainUtility {
+    /// Keeps the settings, also when the number of channels changes, but phase inverts of
+    /// channels that are gone are forgotten.
+    fn prepare(&mut self, sample_rate: SampleRate, _: BufferSize, num_channels: Channels) {
+        let mut utility = Self::new(sample_rate, num_channels);
+        utility.gain = self.gain;
+        utility.muted = self.muted;
+        utility.balance = self.balance;
+        for (to, from) in utility.inverted.iter_mut().zip(&self.inverted) {
+            *to = *from;
+        }
+        utility.update_gains();
+        utility.set_swapped(self.swapped);
+        utility.reset();
+        *self = utility;
+    }
+
+    fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+        GainUtility::process(self, buffer);
+    }
+
+    fn reset(&mut self) {
+        GainUtility::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Samples;
+
+    /// A utility at 1 kHz, so the glides take 20 samples.
+    fn utility(num_channels: usize) -> GainUtility {
+        GainUtility::new(SampleRate::from(1000), Channels::from(num_channels))
+    }
+
+    /// Processes a buffer where every channel has a constant value.
+    fn process(utility: &mut GainUtility, values: &[f32], length: usize) -> Buffer<f32> {
+        let mut buffer = Buffer::allocate(Channels::from(values.len()), Samples::from(length));
+        for (channel, value) in buffer.iter_chans_mut().zip(values) {
+            channel.fill(*value);
+        }
+        utility.process(&mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn leaves_the_audio_untouched_by_default() {
+        let buffer = process(&mut utility(3), &[0.5, -0.25, 1.0], 8);
+
+        assert_eq!(buffer.chan(0), &[0.5; 8]);
+        assert_eq!(buffer.chan(1), &[-0.25; 8]);
+        assert_eq!(buffer.chan(2), &[1.0; 8]);
+    }
+
+    #[test]
+    fn gain_glides_to_its_target() {
+        let mut utility = utility(1);
+        utility.set_gain(Decibels::from(-6.0));
+
+        let buffer = process(&mut utility, &[1.0], 40);
+        let target = Decibels::from(-6.0).to_gain() as f32;
+
+        assert!(buffer.chan(0)[0] > buffer.chan(0)[10]);
+        assert!(buffer.chan(0)[10] > target);
+        assert!((buffer.chan(0)[39] - target).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mute_fades_out_and_back_in() {
+        let mut utility = utility(1);
+        utility.set_muted(true);
+        let muted = process(&mut utility, &[1.0], 40);
+        utility.set_muted(false);
+        let unmuted = process(&mut utility, &[1.0], 40);
+
+        assert!(muted.chan(0)[0] > 0.9);
+        assert_eq!(muted.chan(0)[39], 0.0);
+        assert!(unmuted.chan(0)[0] < 0.1);
+        assert_eq!(unmuted.chan(0)[39], 1.0);
+    }
+
+    #[test]
+    fn inverts_the_phase_of_one_channel() {
+        let mut utility = utility(2);
+        utility.set_inverted(0, true);
+        utility.reset();
+
+        let buffer = process(&mut utility, &[0.5, 0.5], 4);
+
+        assert_eq!(buffer.chan(0), &[-0.5; 4]);
+        assert_eq!(buffer.chan(1), &[0.5; 4]);
+    }
+
+    #[test]
+    fn balance_turns_down_the_other_side() {
+        let mut utility = utility(2);
+        utility.set_balance(Pan::from(-0.5));
+        utility.reset();
+
+        let buffer = process(&mut utility, &[1.0, 1.0], 4);
+
+        assert_eq!(buffer.chan(0), &[1.0; 4]);
+        assert_eq!(buffer.chan(1), &[0.5; 4]);
+    }
+
+    #[test]
+    fn swap_crossfades_the_channels() {
+        let mut utility = utility(2);
+        utility.set_swapped(true);
+
+        let buffer = process(&mut utility, &[1.0, 0.0], 40);
+
+        assert!((buffer.chan(0)[9] - 0.5).abs() < 0.01);
+        assert!((buffer.chan(1)[9] - 0.5).abs() < 0.01);
+        assert_eq!(buffer.chan(0)[39], 0.0);
+        assert_eq!(buffer.chan(1)[39], 1.0);
+    }
+
+    #[test]
+    fn prepare_keeps_the_settings() {
+        let mut utility = utility(2);
+        utility.set_gain(Decibels::from(-6.0));
+        utility.set_inverted(1, true);
+        utility.set_swapped(true);
+
+        AudioProcessor::prepare(
+            &mut utility,
+            SampleRate::from(48000),
+            BufferSize::from(64),
+            Channels::from(2),
+        );
+        let buffer = process(&mut utility, &[1.0, 0.0], 1);
+        let gain = Decibels::from(-6.0).to_gain() as f32;
+
+        assert_eq!(buffer.chan(0)[0], 0.0);
+        assert!((buffer.chan(1)[0] + gain).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,381 @@
+//! This module contains a noise gate, which turns audio down while its level is below a
+//! threshold. With a finite ratio it works as a downward expander instead, which turns quiet
+//! audio down gradually. The gate has hysteresis and a hold time, so it doesn't chatter on
+//! audio that hovers around the threshold.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::gate::Gate;
+//! use rabu::units::{Channels, Decibels, SampleRate, Samples, Seconds};
+//!
+//! let mut gate = Gate::new(Decibels::from(-50.0), SampleRate::from(48000));
+//! gate.set_range(Decibels::from(-40.0));
+//! gate.set_hysteresis(Decibels::from(6.0));
+//! gate.set_hold(Seconds::from(0.05));
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! gate.process(&mut buffer);
+//!
+//! assert!(!gate.is_open());
+//! ```
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::buffer::Buffer;
+use crate::dynamics::{apply_gain, level_to_db, linked_peak, EnvelopeFollower};
+use crate::preset::Preset;
+use crate::processor::{AudioProcessor, ProcessContext, SampleProcessor};
+use crate::sample::Sample;
+use crate::units::{BufferSize, Channels, Decibels, Ratio, SampleRate, Seconds};
+
+/// A noise gate and downward expander, with all channels linked.
+#[derive(Clone, Debug)]
+pub struct Gate {
+    threshold: Decibels,
+    hysteresis: Decibels,
+    range: Decibels,
+    ratio: Ratio,
+    hold: Seconds,
+    sample_rate: SampleRate,
+    /// Smooths the gain in dB: the attack opens the gate, the release closes it.
+    follower: EnvelopeFollower,
+    open: bool,
+    /// The number of samples the gate stays open while the level is below the threshold.
+    hold_remaining: usize,
+    gain_reduction: Decibels,
+}
+
+impl Gate {
+    /// Creates a new closed gate with a range of -80 dB, no hysteresis or hold, 1 ms attack and
+    /// 100 ms release.
+    pub fn new(threshold: Decibels, sample_rate: SampleRate) -> Self {
+        let range = Decibels::from(-80.0);
+        let mut follower =
+            EnvelopeFollower::new(Seconds::from(0.001), Seconds::from(0.1), sample_rate);
+        follower.reset(range.as_f64());
+        Self {
+            threshold,
+            hysteresis: Decibels::from(0.0),
+            range,
+            ratio: Ratio::from(f64::INFINITY),
+            hold: Seconds::from(0.0),
+            sample_rate,
+            follower,
+            open: false,
+            hold_remaining: 0,
+            gain_reduction: Decibels::from(0.0),
+        }
+    }
+
+    /// Returns the threshold.
+    pub fn threshold(&self) -> Decibels {
+        self.threshold
+    }
+
+    /// Changes the level above which the gate opens.
+    pub fn set_threshold(&mut self, threshold: Decibels) {
+        self.threshold = threshold;
+    }
+
+    /// Returns the hysteresis.
+    pub fn hysteresis(&self) -> Decibels {
+        self.hysteresis
+    }
+
+    /// Changes how far the level has to fall below the threshold before the gate closes.
+    pub fn set_hysteresis(&mut self, hysteresis: Decibels) {
+        self.hysteresis = Decibels::from(hysteresis.as_f64().max(0.0));
+    }
+
+    /// Returns the range.
+    pub fn range(&self) -> Decibels {
+        self.range
+    }
+
+    /// Changes how far the gate turns the audio down when it's closed, e.g. -80 dB.
+    pub fn set_range(&mut self, range: Decibels) {
+        self.range = Decibels::from(range.as_f64().min(0.0));
+    }
+
+    /// Returns the ratio.
+    pub fn ratio(&self) -> Ratio {
+        self.ratio
+    }
+
+    /// Changes the expansion ratio below the threshold. An infinite ratio (the default) makes
+    /// a gate; with a ratio of 2, every dB below the threshold turns the audio down by 2 dB,
+    /// until the range is reached.
+    /// This will panic if the ratio is below 1.
+    pub fn set_ratio(&mut self, ratio: Ratio) {
+        assert!(ratio.as_f64() >= 1.0, "the ratio can't be below 1");
+        self.ratio = ratio;
+    }
+
+    /// Returns the hold time.
+    pub fn hold(&self) -> Seconds {
+        self.hold
+    }
+
+    /// Changes how long the gate stays open after the level fell below the threshold.
+    pub fn set_hold(&mut self, hold: Seconds) {
+        self.hold = hold;
+    }
+
+    /// Returns the attack time.
+    pub fn attack(&self) -> Seconds {
+        self.follower.attack()
+    }
+
+    /// Changes how fast the gate opens.
+    pub fn set_attack(&mut self, attack: Seconds) {
+        self.follower.set_attack(attack);
+    }
+
+    /// Returns the release time.
+    pub fn release(&self) -> Seconds {
+        self.follower.release()
+    }
+
+    /// Changes how fast the gate closes.
+    pub fn set_release(&mut self, release: Seconds) {
+        self.follower.set_release(release);
+    }
+
+    /// Changes the sample rate.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = sample_rate;
+        self.follower.set_sample_rate(sample_rate);
+    }
+
+    /// Returns whether the gate is open.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Returns the largest gain reduction of the last processed buffer, as a positive number,
+    /// for metering.
+    pub fn gain_reduction(&self) -> Decibels {
+        self.gain_reduction
+    }
+
+    /// Closes the gate, as if no audio was processed yet.
+    pub fn reset(&mut self) {
+        self.follower.reset(self.range.as_f64());
+        self.open = false;
+        self.hold_remaining = 0;
+        self.gain_reduction = Decibels::from(0.0);
+    }
+
+    /// Gates the buffer, based on its own level.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        self.gain_reduction = Decibels::from(0.0);
+        for index in buffer.sample_indices() {
+            let gain = self.next_gain(linked_peak(buffer, index));
+            apply_gain(buffer, index, gain);
+        }
+    }
+
+    /// Gates the buffer, based on the level of the sidechain. The sidechain can have a
+    /// different number of channels than the buffer.
+    /// This will panic if the sidechain and buffer don't have the same number of samples.
+    pub fn process_with_sidechain<T: Sample>(
+        &mut self,
+        buffer: &mut Buffer<T>,
+        sidechain: &Buffer<T>,
+    ) {
+        assert_eq!(buffer.num_samples(), sidechain.num_samples());
+        self.gain_reduction = Decibels::from(0.0);
+        for index in buffer.sample_indices() {
+            let gain = self.next_gain(linked_peak(sidechain, index));
+            apply_gain(buffer, index, gain);
+        }
+    }
+
+    fn next_gain(&mut self, level: f64) -> f64 {
+        let level = level_to_db(level);
+        let threshold = self.threshold.as_f64();
+
+        if level >= threshold {
+            self.open = true;
+            self.hold_remaining = self.hold.to_samples(self.sample_rate).as_usize();
+        } else if level < threshold - self.hysteresis.as_f64() {
+            if self.hold_remaining > 0 {
+                self.hold_remaining -= 1;
+            } else {
+                self.open = false;
+            }
+        }
+
+        let target = if self.open {
+            0.0
+        } else {
+            let expanded = (level - threshold) * (self.ratio.as_f64() - 1.0);
+            expanded.max(self.range.as_f64()).min(0.0)
+        };
+
+        let gain = self.follower.process(target);
+        if -gain > self.gain_reduction.as_f64() {
+            self.gain_reduction = Decibels::from(-gain);
+        }
+        Decibels::from(gain).to_gain()
+    }
+}
+
+impl AudioProcessor for Gate {
+    fn prepare(&mut self, sample_rate: SampleRate, _: BufferSize, _: Channels) {
+        self.set_sample_rate(sample_rate);
+    }
+
+    fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+        Gate::process(self, buffer);
+    }
+
+    fn reset(&mut self) {
+        Gate::reset(self);
+    }
+}
+
+/// The settings of a `Gate`, for presets.
+/// The default is a gate that is always open, with the settings of a new gate otherwise.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct GateSettings {
+    #[cfg_attr(feature = "serde", serde(with = "crate::preset::unlimited"))]
+    pub threshold: Decibels,
+    pub hysteresis: Decibels,
+    #[cfg_attr(feature = "serde", serde(with = "crate::preset::unlimited"))]
+    pub range: Decibels,
+    #[cfg_attr(feature = "serde", serde(with = "crate::preset::unlimited"))]
+    pub ratio: Ratio,
+    pub hold: Seconds,
+    pub attack: Seconds,
+    pub release: Seconds,
+}
+
+impl Default for GateSettings {
+    fn default() -> Self {
+        Self {
+            threshold: Decibels::from(f64::NEG_INFINITY),
+            hysteresis: Decibels::from(0.0),
+            range: Decibels::from(-80.0),
+            ratio: Ratio::from(f64::INFINITY),
+            hold: Seconds::from(0.0),
+            attack: Seconds::from(0.001),
+            release: Seconds::from(0.1),
+        }
+    }
+}
+
+impl Preset for Gate {
+    type Settings = GateSettings;
+    const PROCESSOR: &'static str = "gate";
+    const VERSION: u32 = 1;
+
+    fn settings(&self) -> GateSettings {
+        GateSettings {
+            threshold: self.threshold,
+            hysteresis: self.hysteresis,
+            range: self.range,
+            ratio: self.ratio,
+            hold: self.hold,
+            attack: self.attack(),
+            release: self.release(),
+        }
+    }
+
+    fn apply_settings(&mut self, settings: &GateSettings) {
+        self.set_threshold(settings.threshold);
+        self.set_hysteresis(settings.hysteresis);
+        self.set_range(settings.range);
+        self.set_ratio(settings.ratio);
+        self.set_hold(settings.hold);
+        self.set_attack(settings.attack);
+        self.set_release(settings.release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{Channels, Samples};
+
+    /// A gate at 1 kHz, so times in milliseconds are samples, that opens and closes instantly.
+    fn gate() -> Gate {
+        let mut gate = Gate::new(Decibels::from(-20.0), SampleRate::from(1000));
+        gate.set_attack(Seconds::from(0.0));
+        gate.set_release(Seconds::from(0.0));
+        gate.set_range(Decibels::from(-60.0));
+        gate
+    }
+
+    fn levels(levels: &[f64]) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(levels.len()));
+        buffer.chan_mut(0).copy_from_slice(levels);
+        buffer
+    }
+
+    fn gains(gate: &mut Gate, input: &[f64]) -> Vec<f64> {
+        let mut buffer = levels(input);
+        gate.process(&mut buffer);
+        buffer
+            .chan(0)
+            .iter()
+            .zip(input)
+            .map(|(output, input)| {
+                (Decibels::from_gain(output / input).as_f64() * 1e6).round() / 1e6
+            })
+            .collect()
+    }
+
+    #[test]
+    fn opens_above_and_closes_below_the_threshold() {
+        let mut gate = gate();
+
+        let gains = gains(&mut gate, &[0.01, 0.5, 0.5, 0.01]);
+
+        assert_eq!(gains, vec![-60.0, 0.0, 0.0, -60.0]);
+    }
+
+    #[test]
+    fn hysteresis_keeps_the_gate_open() {
+        let mut gate = gate();
+        gate.set_hysteresis(Decibels::from(10.0));
+
+        // -26 dB is below the threshold, but not below the threshold minus the hysteresis.
+        let gains = gains(&mut gate, &[0.5, 0.05, 0.05, 0.01]);
+
+        assert_eq!(gains, vec![0.0, 0.0, 0.0, -60.0]);
+    }
+
+    #[test]
+    fn hold_keeps_the_gate_open_for_a_while() {
+        let mut gate = gate();
+        gate.set_hold(Seconds::from(0.002));
+
+        let gains = gains(&mut gate, &[0.5, 0.01, 0.01, 0.01, 0.01]);
+
+        assert_eq!(gains, vec![0.0, 0.0, 0.0, -60.0, -60.0]);
+    }
+
+    #[test]
+    fn expander_turns_down_gradually() {
+        let mut gate = gate();
+        gate.set_ratio(Ratio::from(2.0));
+
+        // 10 dB and 20 dB below the threshold, and far below the range.
+        let gains = gains(&mut gate, &[0.0316227766, 0.01, 0.00001]);
+
+        assert_eq!(gains, vec![-10.0, -20.0, -60.0]);
+    }
+
+    #[test]
+    fn sidechain_opens_the_gate() {
+        let mut gate = gate();
+        let mut quiet = levels(&[0.01, 0.01]);
+
+        gate.process_with_sidechain(&mut quiet, &levels(&[0.01, 1.0]));
+
+        assert_eq!(quiet.chan(0), &[0.00001, 0.01]);
+        assert_eq!(gate.gain_reduction(), Decibels::from(60.0));
+    }
+}
@@ -0,0 +1,488 @@
+//! This module contains an audio graph: processors as nodes, with connections that route the
+//! output of one node to the input of another, mapping channels on the way. A node gets the sum
+//! of all its incoming connections. The nodes are processed in an order where every node comes
+//! after the nodes it gets audio from, so connections that would make a cycle are refused.
+//! The graph has an input node, which holds the audio the graph processes, and an output node,
+//! which collects the audio it gives back. The graph is an `AudioProcessor` itself, so graphs
+//! can be nested.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::dynamics::Compressor;
+//! use rabu::gate::Gate;
+//! use rabu::graph::Graph;
+//! use rabu::processor::{AudioProcessor, ProcessContext};
+//! use rabu::units::{BufferSize, Channels, Decibels, Ratio, SampleRate, Samples};
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let mut graph = Graph::new();
+//! let gate = graph.add_node(Gate::new(Decibels::from(-60.0), sample_rate), Channels::from(2));
+//! let compressor = graph.add_node(
+//!     Compressor::new(Decibels::from(-20.0), Ratio::from(4.0), sample_rate),
+//!     Channels::from(2),
+//! );
+//! assert!(graph.connect_all(Graph::INPUT, gate));
+//! assert!(graph.connect_all(gate, compressor));
+//! assert!(graph.connect_all(compressor, Graph::OUTPUT));
+//! // This would make a cycle.
+//! assert!(!graph.connect_all(compressor, gate));
+//!
+//! graph.prepare(sample_rate, BufferSize::from(512), Channels::from(2));
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! graph.process(&mut buffer, &ProcessContext::new(sample_rate));
+//! ```
+
+use crate::buffer::Buffer;
+use crate::buffer_pool::BufferPool;
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::units::{BufferSize, Channels, Latency, SampleRate, Samples};
+
+/// Identifies a node of a `Graph`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+/// A node: a processor with its output buffer. The input and output nodes have no processor.
+struct Node {
+    processor: Option<Box<dyn AudioProcessor>>,
+    num_channels: Channels,
+    buffer: Option<Buffer<f32>>,
+}
+
+/// Routes the channels of one node to the channels of another.
+#[derive(Clone, Debug, PartialEq)]
+struct Connection {
+    from: usize,
+    to: usize,
+    /// Pairs of a channel of the source and a channel of the destination.
+    channels: Vec<(usize, usize)>,
+}
+
+/// Processors connected into a graph.
+pub struct Graph {
+    nodes: Vec<Node>,
+    connections: Vec<Connection>,
+    /// The nodes in the order they are processed.
+    order: Vec<usize>,
+    pool: BufferPool<f32>,
+    sample_rate: SampleRate,
+    buffer_size: BufferSize,
+}
+
+impl Graph {
+    /// The node that holds the audio the graph processes.
+    pub const INPUT: NodeId = NodeId(0);
+    /// The node that collects the audio the graph gives back.
+    pub const OUTPUT: NodeId = NodeId(1);
+
+    /// Creates a new graph with only the input and output nodes, which aren't connected, so
+    /// it gives back silence.
+    pub fn new() -> Self {
+        let io_node = || Node {
+            processor: None,
+            num_channels: Channels::from(0),
+            buffer: None,
+        };
+        Self {
+            nodes: vec![io_node(), io_node()],
+            connections: Vec::new(),
+            order: vec![0, 1],
+            pool: BufferPool::new(Samples::from(0)),
+            sample_rate: SampleRate::from(44100),
+            buffer_size: BufferSize::from(0),
+        }
+    }
+
+    /// Adds a processor for audio with the given number of channels. Nodes that are added
+    /// after the graph was prepared are prepared right away.
+    pub fn add_node(
+        &mut self,
+        processor: impl AudioProcessor + 'static,
+        num_channels: Channels,
+    ) -> NodeId {
+        let mut processor: Box<dyn AudioProcessor> = Box::new(processor);
+        let mut buffer = None;
+        if self.buffer_size.as_u32() > 0 {
+            processor.prepare(self.sample_rate, self.buffer_size, num_channels);
+            buffer = Some(self.pool.take(num_channels));
+        }
+        self.nodes.push(Node {
+            processor: Some(processor),
+            num_channels,
+            buffer,
+        });
+        self.update_order();
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Returns the number of nodes, including the input and output nodes.
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the processor of the node, or `None` for the input and output nodes.
+    /// This will panic if the node isn't part of the graph.
+    pub fn processor(&self, node: NodeId) -> Option<&dyn AudioProcessor> {
+        self.nodes[node.0].processor.as_deref()
+    }
+
+    /// Returns the processor of the node, or `None` for the input and output nodes.
+    /// This will panic if the node isn't part of the graph.
+    pub fn processor_mut(&mut self, node: NodeId) -> Option<&mut (dyn AudioProcessor + 'static)> {
+        self.nodes[node.0].processor.as_deref_mut()
+    }
+
+    /// Routes channels of one node to channels of another, as pairs of a source and a
+    /// destination channel. Channels that a node doesn't have are skipped. This replaces an
+    /// earlier connection between the same nodes, and gives `false` without connecting when it
+    /// would make a cycle, or when it would go into the input or out of the output.
+    /// This will panic if a node isn't part of the graph.
+    pub fn connect(&mut self, from: NodeId, to: NodeId, channels: &[(usize, usize)]) -> bool {
+        assert!(from.0 < self.nodes.len() && to.0 < self.nodes.len());
+        if to == Self::INPUT || from == Self::OUTPUT || self.reaches(to.0, from.0) {
+            return false;
+        }
+        self.disconnect(from, to);
+        self.connections.push(Connection {
+            from: from.0,
+            to: to.0,
+            channels: channels.to_vec(),
+        });
+        self.update_order();
+        true
+    }
+
+    /// Routes every channel of one node to the same channel of another, as far as both have
+    /// it. The input and output nodes have as many channels as the graph is prepared for.
+    /// This will panic if a node isn't part of the graph.
+    pub fn connect_all(&mut self, from: NodeId, to: NodeId) -> bool {
+        let num_channels = [from, to]
+            .iter()
+            .filter(|node| **node != Self::INPUT && **node != Self::OUTPUT)
+            .map(|node| self.nodes[node.0].num_channels.as_usize())
+            .min();
+        // The channels of the input and output nodes can change, so all of them are mapped.
+        let channels: Vec<_> = (0..num_channels.unwrap_or(MAX_IO_CHANNELS))
+            .map(|channel| (channel, channel))
+            .collect();
+        self.connect(from, to, &channels)
+    }
+
+    /// Removes the connection between the nodes, if there is one.
+    pub fn disconnect(&mut self, from: NodeId, to: NodeId) {
+        self.connections
+            .retain(|connection| connection.from != from.0 || connection.to != to.0);
+        self.update_order();
+    }
+
+    /// Returns whether the node gets audio from the other node.
+    pub fn is_connected(&self, from: NodeId, to: NodeId) -> bool {
+        self.connections
+            .iter()
+            .any(|connection| connection.from == from.0 && connection.to == to.0)
+    }
+
+    /// Tells whether audio flows from one node to the other, directly or through other nodes.
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        let mut stack = vec![from];
+        let mut visited = vec![false; self.nodes.len()];
+        while let Some(node) = stack.pop() {
+            if node == to {
+                return true;
+            }
+            if !std::mem::replace(&mut visited[node], true) {
+                stack.extend(
+                    self.connections
+                        .iter()
+                        .filter(|connection| connection.from == node)
+                        .map(|connection| connection.to),
+                );
+            }
+        }
+        false
+    }
+
+    /// Sorts the nodes so every node comes after the nodes it gets audio from, keeping the
+    /// order they were added in where the connections allow it.
+    fn update_order(&mut self) {
+        let mut incoming = vec![0; self.nodes.len()];
+        for connection in &self.connections {
+            incoming[connection.to] += 1;
+        }
+        self.order.clear();
+        let mut done = vec![false; self.nodes.len()];
+        while self.order.len() < self.nodes.len() {
+            // The connections are acyclic, so there's always a node without unprocessed
+            // inputs. The output node goes last when nothing depends on it, which is always.
+            let next = (0..self.nodes.len())
+                .filter(|&node| node != Self::OUTPUT.0)
+                .chain([Self::OUTPUT.0])
+                .find(|&node| !done[node] && incoming[node] == 0)
+                .expect("the connections have no cycles");
+            done[next] = true;
+            self.order.push(next);
+            for connection in &self.connections {
+                if connection.from == next {
+                    incoming[connection.to] -= 1;
+                }
+            }
+        }
+    }
+
+    /// Returns the delay of the node, plus the longest delay of the nodes it gets audio from.
+    fn path_latency(&self, node: usize, latencies: &mut [Option<f64>]) -> f64 {
+        if let Some(latency) = latencies[node] {
+            return latency;
+        }
+        let own = self.nodes[node]
+            .processor
+            .as_ref()
+            .map_or(0.0, |processor| processor.latency().as_secs_f64());
+        let mut before: f64 = 0.0;
+        for connection in self.connections.iter().filter(|c| c.to == node) {
+            before = before.max(self.path_latency(connection.from, latencies));
+        }
+        latencies[node] = Some(own + before);
+        own + before
+    }
+}
+
+/// The most channels a connection from or to the input and output nodes maps with
+/// `connect_all`.
+const MAX_IO_CHANNELS: usize = 64;
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioProcessor for Graph {
+    /// Prepares all nodes, and takes a buffer for every node from the pool, so processing
+    /// doesn't allocate.
+    fn prepare(
+        &mut self,
+        sample_rate: SampleRate,
+        buffer_size: BufferSize,
+        num_channels: Channels,
+    ) {
+        self.sample_rate = sample_rate;
+        self.buffer_size = buffer_size;
+        self.pool.clear(buffer_size.as_samples());
+        for (index, node) in self.nodes.iter_mut().enumerate() {
+            if index == Self::INPUT.0 || index == Self::OUTPUT.0 {
+                node.num_channels = num_channels;
+            }
+            if let Some(processor) = &mut node.processor {
+                processor.prepare(sample_rate, buffer_size, node.num_channels);
+            }
+            node.buffer = Some(self.pool.take(node.num_channels));
+        }
+    }
+
+    /// Processes the nodes in order, and gives back the output node. The block can be
+    /// shorter than the buffer size the graph was prepared for.
+    /// This will panic if the graph wasn't prepared, or the block is longer than the buffer
+    /// size.
+    fn process(&mut self, buffer: &mut Buffer<f32>, context: &ProcessContext) {
+        let num_samples = buffer.num_samples();
+        assert!(
+            num_samples <= self.buffer_size.as_samples(),
+            "the block is longer than the buffer size the graph was prepared for"
+        );
+
+        for index in 0..self.order.len() {
+            let node = self.order[index];
+            let mut output = self.nodes[node]
+                .buffer
+                .take()
+                .expect("the graph must be prepared before processing");
+            output.set_num_samples(num_samples);
+            if node == Self::INPUT.0 {
+                mix_channels(
+                    buffer,
+                    &mut output,
+                    (0..buffer.num_channels().as_usize()).map(|channel| (channel, channel)),
+                );
+            }
+            for connection in self.connections.iter().filter(|c| c.to == node) {
+                let source = self.nodes[connection.from]
+                    .buffer
+                    .as_ref()
+                    .expect("the graph must be prepared before processing");
+                mix_channels(source, &mut output, connection.channels.iter().copied());
+            }
+            if let Some(processor) = &mut self.nodes[node].processor {
+                processor.process(&mut output, context);
+            }
+            self.nodes[node].buffer = Some(output);
+        }
+
+        buffer.fill_default();
+        if let Some(output) = &self.nodes[Self::OUTPUT.0].buffer {
+            mix_channels(
+                output,
+                buffer,
+                (0..output.num_channels().as_usize()).map(|channel| (channel, channel)),
+            );
+        }
+    }
+
+    /// Returns the longest delay of the paths from the input to the output. The graph doesn't
+    /// compensate for the paths with a shorter delay.
+    fn latency(&self) -> Latency {
+        let mut latencies = vec![None; self.nodes.len()];
+        Latency::from_secs_f64(self.path_latency(Self::OUTPUT.0, &mut latencies))
+    }
+
+    fn reset(&mut self) {
+        for node in &mut self.nodes {
+            if let Some(processor) = &mut node.processor {
+                processor.reset();
+            }
+        }
+    }
+}
+
+/// Adds channels of the source to channels of the destination, skipping the channels either
+/// of them doesn't have.
+fn mix_channels(
+    source: &Buffer<f32>,
+    destination: &mut Buffer<f32>,
+    channels: impl Iterator<Item = (usize, usize)>,
+) {
+    for (from, to) in channels {
+        if from < source.num_channels().as_usize() && to < destination.num_channels().as_usize() {
+            for (output, input) in destination.chan_mut(to).iter_mut().zip(source.chan(from)) {
+                *output += *input;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Multiplies the audio, with the given latency.
+    struct Gain(f32, f64);
+
+    impl AudioProcessor for Gain {
+        fn prepare(&mut self, _: SampleRate, _: BufferSize, _: Channels) {}
+
+        fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+            buffer.map_samples(|sample| sample * self.0);
+        }
+
+        fn latency(&self) -> Latency {
+            Latency::from_secs_f64(self.1)
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    fn run(graph: &mut Graph, input: &[&[f32]]) -> Vec<Vec<f32>> {
+        let mut buffer =
+            Buffer::allocate(Channels::from(input.len()), Samples::from(input[0].len()));
+        for (channel, samples) in buffer.iter_chans_mut().zip(input) {
+            channel.copy_from_slice(samples);
+        }
+        graph.process(&mut buffer, &ProcessContext::new(SampleRate::from(1000)));
+        buffer
+            .iter_chans()
+            .map(|channel| channel.to_vec())
+            .collect()
+    }
+
+    fn prepared(graph: &mut Graph, num_channels: usize) {
+        graph.prepare(
+            SampleRate::from(1000),
+            BufferSize::from(4),
+            Channels::from(num_channels),
+        );
+    }
+
+    #[test]
+    fn processes_a_chain_in_order() {
+        let mut graph = Graph::new();
+        let double = graph.add_node(Gain(2.0, 0.0), Channels::from(1));
+        let triple = graph.add_node(Gain(3.0, 0.0), Channels::from(1));
+        // Connected out of the order the nodes were added in.
+        graph.connect_all(triple, Graph::OUTPUT);
+        graph.connect_all(double, triple);
+        graph.connect_all(Graph::INPUT, double);
+        prepared(&mut graph, 1);
+
+        assert_eq!(run(&mut graph, &[&[1.0, 2.0]]), vec![vec![6.0, 12.0]]);
+    }
+
+    #[test]
+    fn parallel_paths_are_summed() {
+        let mut graph = Graph::new();
+        let double = graph.add_node(Gain(2.0, 0.0), Channels::from(1));
+        graph.connect_all(Graph::INPUT, double);
+        graph.connect_all(double, Graph::OUTPUT);
+        graph.connect_all(Graph::INPUT, Graph::OUTPUT);
+        prepared(&mut graph, 1);
+
+        assert_eq!(run(&mut graph, &[&[1.0, -1.0]]), vec![vec![3.0, -3.0]]);
+    }
+
+    #[test]
+    fn connections_map_channels() {
+        let mut graph = Graph::new();
+        let mono = graph.add_node(Gain(1.0, 0.0), Channels::from(1));
+        // Both channels into the mono node, and the mono node into the right channel.
+        graph.connect(Graph::INPUT, mono, &[(0, 0), (1, 0)]);
+        graph.connect(mono, Graph::OUTPUT, &[(0, 1)]);
+        prepared(&mut graph, 2);
+
+        assert_eq!(
+            run(&mut graph, &[&[1.0, 2.0], &[10.0, 20.0]]),
+            vec![vec![0.0, 0.0], vec![11.0, 22.0]]
+        );
+    }
+
+    #[test]
+    fn refuses_cycles() {
+        let mut graph = Graph::new();
+        let a = graph.add_node(Gain(1.0, 0.0), Channels::from(1));
+        let b = graph.add_node(Gain(1.0, 0.0), Channels::from(1));
+        let c = graph.add_node(Gain(1.0, 0.0), Channels::from(1));
+
+        assert!(graph.connect_all(a, b));
+        assert!(graph.connect_all(b, c));
+        assert!(!graph.connect_all(c, a));
+        assert!(!graph.connect_all(a, a));
+        assert!(!graph.connect_all(a, Graph::INPUT));
+        assert!(!graph.is_connected(c, a));
+    }
+
+    #[test]
+    fn latency_is_the_longest_path() {
+        let mut graph = Graph::new();
+        let short = graph.add_node(Gain(1.0, 0.001), Channels::from(1));
+        let long = graph.add_node(Gain(1.0, 0.002), Channels::from(1));
+        let after = graph.add_node(Gain(1.0, 0.003), Channels::from(1));
+        graph.connect_all(Graph::INPUT, short);
+        graph.connect_all(Graph::INPUT, long);
+        graph.connect_all(short, after);
+        graph.connect_all(long, after);
+        graph.connect_all(after, Graph::OUTPUT);
+
+        assert!((graph.latency().as_secs_f64() - 0.005).abs() < 1e-12);
+    }
+
+    #[test]
+    fn processes_blocks_shorter_than_the_buffer_size() {
+        let mut graph = Graph::new();
+        let double = graph.add_node(Gain(2.0, 0.0), Channels::from(1));
+        graph.connect_all(Graph::INPUT, double);
+        graph.connect_all(double, Graph::OUTPUT);
+        prepared(&mut graph, 1);
+
+        assert_eq!(
+            run(&mut graph, &[&[1.0, 2.0, 3.0, 4.0]]),
+            vec![vec![2.0, 4.0, 6.0, 8.0]]
+        );
+        assert_eq!(run(&mut graph, &[&[5.0]]), vec![vec![10.0]]);
+    }
+}
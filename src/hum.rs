@@ -0,0 +1,181 @@
+//! This module contains a filter that removes mains hum, by placing narrow notches at the mains
+//! frequency and its harmonics.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::hum::{HumFilter, MainsFrequency};
+//! use rabu::units::{Channels, Frequency, SampleRate, Samples};
+//!
+//! let mut filter = HumFilter::new(
+//!     SampleRate::from(48000),
+//!     Channels::from(2),
+//!     MainsFrequency::Hz50,
+//!     Frequency::from(1000.0),
+//! );
+//! assert_eq!(filter.notch_frequencies().len(), 20);
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! filter.process(&mut buffer);
+//! ```
+
+use crate::biquad::{notch_coefficients, MultiBiquad};
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::units::{Channels, Frequency, SampleRate};
+
+/// The frequency of the mains power, which differs per region.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MainsFrequency {
+    Hz50,
+    Hz60,
+}
+
+impl MainsFrequency {
+    /// Returns the mains frequency as a `Frequency`.
+    pub fn frequency(&self) -> Frequency {
+        match self {
+            MainsFrequency::Hz50 => Frequency::from(50.0),
+            MainsFrequency::Hz60 => Frequency::from(60.0),
+        }
+    }
+}
+
+/// Removes mains hum with a series of notch filters at the mains frequency and its harmonics.
+#[derive(Clone, Debug)]
+pub struct HumFilter {
+    frequencies: Vec<Frequency>,
+    notches: Vec<MultiBiquad>,
+}
+
+impl HumFilter {
+    /// The Q of the notches, which removes about 2 Hz around every harmonic.
+    pub const DEFAULT_Q: f64 = 30.0;
+
+    /// Creates a new hum filter with notches at all harmonics up to and including the given
+    /// highest frequency. Harmonics at or above Nyquist are left out.
+    pub fn new(
+        sample_rate: SampleRate,
+        num_channels: Channels,
+        mains: MainsFrequency,
+        highest_harmonic: Frequency,
+    ) -> Self {
+        Self::with_q(
+            sample_rate,
+            num_channels,
+            mains,
+            highest_harmonic,
+            Self::DEFAULT_Q,
+        )
+    }
+
+    /// Creates a new hum filter like `new`, but with a custom Q for the notches.
+    /// A higher Q gives narrower notches, which affect the audio less but take longer to settle.
+    pub fn with_q(
+        sample_rate: SampleRate,
+        num_channels: Channels,
+        mains: MainsFrequency,
+        highest_harmonic: Frequency,
+        q: f64,
+    ) -> Self {
+        let fundamental = mains.frequency().as_f64();
+        let nyquist = sample_rate.as_f64() / 2.0;
+        let num_harmonics = (highest_harmonic.as_f64() / fundamental).floor() as usize;
+
+        let frequencies: Vec<_> = (1..=num_harmonics)
+            .map(|harmonic| fundamental * harmonic as f64)
+            .take_while(|frequency| *frequency < nyquist)
+            .map(Frequency::from)
+            .collect();
+
+        let notches = frequencies
+            .iter()
+            .map(|frequency| {
+                let coefficients = notch_coefficients(sample_rate, *frequency, q);
+                MultiBiquad::new(coefficients, num_channels)
+            })
+            .collect();
+
+        Self {
+            frequencies,
+            notches,
+        }
+    }
+
+    /// Returns the frequencies that are notched out.
+    pub fn notch_frequencies(&self) -> &[Frequency] {
+        &self.frequencies
+    }
+
+    /// Sets whether the filter should be bypassed. The change is crossfaded.
+    pub fn set_bypassed(&mut self, bypassed: bool) {
+        for notch in &mut self.notches {
+            notch.set_bypassed(bypassed);
+        }
+    }
+
+    /// Clears the internal state of all notches.
+    pub fn reset(&mut self) {
+        self.notches.iter_mut().for_each(MultiBiquad::reset);
+    }
+
+    /// Removes the hum from the given buffer in place.
+    /// This will panic if the buffer doesn't have the number of channels the filter was
+    /// created for.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        for notch in &mut self.notches {
+            notch.process(buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Samples;
+
+    fn level_after_filtering(frequency: f64) -> f64 {
+        let sample_rate = SampleRate::from(48000);
+        let mut filter = HumFilter::new(
+            sample_rate,
+            Channels::from(1),
+            MainsFrequency::Hz60,
+            Frequency::from(500.0),
+        );
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(1), Samples::from(96000));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = (2.0 * std::f64::consts::PI * frequency * n as f64 / 48000.0).sin();
+        }
+
+        filter.process(&mut buffer);
+
+        buffer.chan(0)[48000..]
+            .iter()
+            .fold(0.0, |peak: f64, s| peak.max(s.abs()))
+    }
+
+    #[test_case(60.0; "fundamental")]
+    #[test_case(180.0; "third harmonic")]
+    #[test_case(480.0; "highest harmonic")]
+    fn removes_harmonics(frequency: f64) {
+        assert!(level_after_filtering(frequency) < 0.01);
+    }
+
+    #[test_case(90.0; "between harmonics")]
+    #[test_case(1000.0; "above highest harmonic")]
+    fn keeps_other_frequencies(frequency: f64) {
+        assert!(level_after_filtering(frequency) > 0.95);
+    }
+
+    #[test]
+    fn harmonics_stop_below_nyquist() {
+        let filter = HumFilter::new(
+            SampleRate::from(200),
+            Channels::from(1),
+            MainsFrequency::Hz50,
+            Frequency::from(1000.0),
+        );
+
+        assert_eq!(filter.notch_frequencies(), &[Frequency::from(50.0)]);
+    }
+}
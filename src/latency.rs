@@ -0,0 +1,211 @@
+//! This module aligns parallel paths of processing that have different latencies, e.g. a dry
+//! path next to a compressor with lookahead. Every path is delayed by the difference between
+//! its latency and the longest one, rounded to whole samples, so the paths sum in phase.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::latency::LatencyCompensator;
+//! use rabu::units::{Channels, Latency, SampleRate, Samples};
+//!
+//! let sample_rate = SampleRate::from(1000);
+//! // The latencies of the paths, e.g. from `AudioProcessor::latency`.
+//! let latencies = [Latency::from_secs_f64(0.0), Latency::from_secs_f64(0.003)];
+//! let mut compensator = LatencyCompensator::new(&latencies, Channels::from(1), sample_rate);
+//!
+//! assert_eq!(compensator.delay(0), Samples::from(3));
+//! assert_eq!(compensator.delay(1), Samples::from(0));
+//!
+//! let mut dry = Buffer::<f32>::allocate(Channels::from(1), Samples::from(8));
+//! dry.chan_mut(0)[0] = 1.0;
+//! compensator.process(0, &mut dry);
+//!
+//! assert_eq!(dry.chan(0), &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::delay::DelayLine;
+use crate::sample::Sample;
+use crate::units::{Channels, Latency, SampleRate, Samples};
+use crate::varispeed::Interpolation;
+
+/// Delays parallel paths so they all have the latency of the longest one.
+#[derive(Clone, Debug)]
+pub struct LatencyCompensator {
+    num_channels: Channels,
+    sample_rate: SampleRate,
+    latency: Samples,
+    paths: Vec<Path>,
+}
+
+/// The delay of one path, with a delay line per channel.
+#[derive(Clone, Debug)]
+struct Path {
+    delay: Samples,
+    lines: Vec<DelayLine>,
+}
+
+impl LatencyCompensator {
+    /// Creates a compensator for paths with the latencies, which process the number of channels.
+    pub fn new(latencies: &[Latency], num_channels: Channels, sample_rate: SampleRate) -> Self {
+        let mut compensator = Self {
+            num_channels,
+            sample_rate,
+            latency: Samples::from(0),
+            paths: Vec::new(),
+        };
+        compensator.set_latencies(latencies);
+        compensator
+    }
+
+    /// Sets the latencies of the paths, e.g. after a processor changed its latency.
+    /// This clears the delayed audio.
+    pub fn set_latencies(&mut self, latencies: &[Latency]) {
+        let latencies: Vec<_> = latencies
+            .iter()
+            .map(|latency| latency.as_seconds().to_samples(self.sample_rate))
+            .collect();
+        self.latency = latencies.iter().copied().max().unwrap_or(Samples::from(0));
+        self.paths = latencies
+            .into_iter()
+            .map(|latency| {
+                let delay = self.latency - latency;
+                Path {
+                    delay,
+                    lines: (0..self.num_channels.as_usize())
+                        .map(|_| DelayLine::new(delay, Interpolation::None))
+                        .collect(),
+                }
+            })
+            .collect();
+    }
+
+    /// Returns the number of paths.
+    pub fn num_paths(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        self.num_channels
+    }
+
+    /// Returns the delay that is added to the path.
+    /// This will panic if the path doesn't exist.
+    pub fn delay(&self, path: usize) -> Samples {
+        self.paths[path].delay
+    }
+
+    /// Returns the latency all paths have after compensation, which is the longest one.
+    pub fn latency(&self) -> Latency {
+        Latency::from(self.latency.to_seconds(self.sample_rate))
+    }
+
+    /// Delays the output of the path.
+    /// This will panic if the path doesn't exist, or if the buffer doesn't have the same number
+    /// of channels as the compensator.
+    pub fn process<T: Sample>(&mut self, path: usize, buffer: &mut Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels);
+        let path = &mut self.paths[path];
+        if path.delay == Samples::from(0) {
+            return;
+        }
+        for (channel, line) in buffer.iter_chans_mut().zip(&mut path.lines) {
+            for sample in channel.iter_mut() {
+                line.push(sample.to_f64());
+                *sample = T::from_f64(line.read(path.delay));
+            }
+        }
+    }
+
+    /// Clears the delayed audio of all paths.
+    pub fn reset(&mut self) {
+        for path in &mut self.paths {
+            path.lines.iter_mut().for_each(DelayLine::reset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::{AudioProcessor, ProcessContext};
+    use crate::units::BufferSize;
+
+    /// Delays mono audio by the maximum delay of the line, and reports it as its latency.
+    struct Delay(DelayLine);
+
+    impl AudioProcessor for Delay {
+        fn prepare(&mut self, _: SampleRate, _: BufferSize, _: Channels) {}
+
+        fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+            for sample in buffer.chan_mut(0) {
+                self.0.push(*sample as f64);
+                *sample = self.0.read(self.0.max_delay()) as f32;
+            }
+        }
+
+        fn latency(&self) -> Latency {
+            Latency::from(self.0.max_delay().to_seconds(SampleRate::from(1000)))
+        }
+
+        fn reset(&mut self) {
+            self.0.reset();
+        }
+    }
+
+    #[test]
+    fn aligns_the_paths_to_the_longest_latency() {
+        let sample_rate = SampleRate::from(48000);
+        let latencies = [
+            Latency::from(Samples::from(10).to_seconds(sample_rate)),
+            Latency::from(Samples::from(64).to_seconds(sample_rate)),
+            Latency::from(Samples::from(0).to_seconds(sample_rate)),
+        ];
+
+        let compensator = LatencyCompensator::new(&latencies, Channels::from(2), sample_rate);
+
+        assert_eq!(compensator.num_paths(), 3);
+        assert_eq!(compensator.delay(0), Samples::from(54));
+        assert_eq!(compensator.delay(1), Samples::from(0));
+        assert_eq!(compensator.delay(2), Samples::from(64));
+        assert_eq!(compensator.latency(), latencies[1]);
+    }
+
+    #[test]
+    fn parallel_paths_sum_in_phase() {
+        let sample_rate = SampleRate::from(1000);
+        let mut wet = Delay(DelayLine::new(Samples::from(7), Interpolation::None));
+        let latencies = [Latency::from_secs_f64(0.0), wet.latency()];
+        let mut compensator = LatencyCompensator::new(&latencies, Channels::from(1), sample_rate);
+
+        let mut dry = Buffer::<f32>::allocate(Channels::from(1), Samples::from(16));
+        dry.chan_mut(0)[2] = 1.0;
+        let mut processed = dry.clone();
+        wet.process(&mut processed, &ProcessContext::new(sample_rate));
+        compensator.process(0, &mut dry);
+        compensator.process(1, &mut processed);
+
+        assert_eq!(dry.data(), processed.data());
+        assert_eq!(dry.chan(0)[9], 1.0);
+    }
+
+    #[test]
+    fn delays_across_blocks() {
+        let sample_rate = SampleRate::from(1000);
+        let latencies = [Latency::from_secs_f64(0.0), Latency::from_secs_f64(0.005)];
+        let mut compensator = LatencyCompensator::new(&latencies, Channels::from(1), sample_rate);
+
+        let mut first = Buffer::<f32>::allocate(Channels::from(1), Samples::from(4));
+        first.chan_mut(0)[3] = 1.0;
+        let mut second = Buffer::<f32>::allocate(Channels::from(1), Samples::from(8));
+        compensator.process(0, &mut first);
+        compensator.process(0, &mut second);
+
+        assert!(first.is_default_filled());
+        assert_eq!(second.chan(0)[4], 1.0);
+
+        compensator.reset();
+        let mut third = Buffer::<f32>::allocate(Channels::from(1), Samples::from(8));
+        compensator.process(0, &mut third);
+        assert!(third.is_default_filled());
+    }
+}
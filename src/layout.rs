@@ -0,0 +1,363 @@
+//! This module describes which speaker every channel of a buffer belongs to. The channels of
+//! every layout are in the WAV (and SMPTE) order, and every speaker has a standard (ITU)
+//! direction, which surround panning and downmixing build on. A `ChannelMap` moves the
+//! channels of one layout or order to another, e.g. from a WAV file to a device that expects
+//! film order, so the center doesn't end up in the right speaker.
+//! ```rust
+//! use rabu::layout::{ChannelLayout, Speaker};
+//! use rabu::units::Channels;
+//!
+//! let layout = ChannelLayout::Surround5_1;
+//!
+//! assert_eq!(layout.num_channels(), Channels::from(6));
+//! assert_eq!(layout.index_of(Speaker::Center), Some(2));
+//! assert_eq!(Speaker::LeftSurround.azimuth(), Some(-110.0));
+//! ```
+//! Mapping between orders:
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::layout::{ChannelLayout, ChannelMap, ChannelOrder};
+//! use rabu::units::{Channels, Samples};
+//!
+//! let map = ChannelMap::reorder(ChannelLayout::Surround5_1, ChannelOrder::Wav, ChannelOrder::Film);
+//!
+//! let mut wav = Buffer::<f32>::allocate(Channels::from(6), Samples::from(4));
+//! for (index, channel) in wav.iter_chans_mut().enumerate() {
+//!     channel.fill(index as f32);
+//! }
+//! let mut film = Buffer::allocate(Channels::from(6), Samples::from(4));
+//! map.apply(&wav, &mut film);
+//!
+//! // L, C, R, Ls, Rs, LFE
+//! let order: Vec<_> = film.iter_chans().map(|channel| channel[0]).collect();
+//! assert_eq!(order, [0.0, 2.0, 1.0, 4.0, 5.0, 3.0]);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::units::Channels;
+
+/// A speaker position.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Speaker {
+    Mono,
+    Left,
+    Right,
+    Center,
+    /// The low frequency effects channel, or subwoofer.
+    Lfe,
+    LeftSurround,
+    RightSurround,
+    LeftBack,
+    RightBack,
+}
+
+impl Speaker {
+    /// Returns the direction of the speaker in degrees, where 0 is straight ahead and negative
+    /// angles are to the left. The LFE channel has no direction.
+    pub fn azimuth(&self) -> Option<f64> {
+        match self {
+            Speaker::Mono | Speaker::Center => Some(0.0),
+            Speaker::Left => Some(-30.0),
+            Speaker::Right => Some(30.0),
+            Speaker::Lfe => None,
+            Speaker::LeftSurround => Some(-110.0),
+            Speaker::RightSurround => Some(110.0),
+            Speaker::LeftBack => Some(-150.0),
+            Speaker::RightBack => Some(150.0),
+        }
+    }
+}
+
+/// A standard arrangement of speakers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChannelLayout {
+    Mono,
+    Stereo,
+    /// Left, right and center.
+    Lcr,
+    /// Left, right and the two surrounds.
+    Quad,
+    Surround5_1,
+    Surround7_1,
+}
+
+impl ChannelLayout {
+    /// Returns the speakers in channel order.
+    pub fn speakers(&self) -> &'static [Speaker] {
+        use Speaker::*;
+        match self {
+            ChannelLayout::Mono => &[Mono],
+            ChannelLayout::Stereo => &[Left, Right],
+            ChannelLayout::Lcr => &[Left, Right, Center],
+            ChannelLayout::Quad => &[Left, Right, LeftSurround, RightSurround],
+            ChannelLayout::Surround5_1 => &[Left, Right, Center, Lfe, LeftSurround, RightSurround],
+            ChannelLayout::Surround7_1 => &[
+                Left,
+                Right,
+                Center,
+                Lfe,
+                LeftBack,
+                RightBack,
+                LeftSurround,
+                RightSurround,
+            ],
+        }
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.speakers().len())
+    }
+
+    /// Returns the channel of the speaker, if the layout has it.
+    pub fn index_of(&self, speaker: Speaker) -> Option<usize> {
+        self.speakers().iter().position(|s| *s == speaker)
+    }
+
+    /// Returns the default layout for a number of channels, if there is one.
+    pub fn from_num_channels(num_channels: Channels) -> Option<Self> {
+        [
+            ChannelLayout::Mono,
+            ChannelLayout::Stereo,
+            ChannelLayout::Lcr,
+            ChannelLayout::Quad,
+            ChannelLayout::Surround5_1,
+            ChannelLayout::Surround7_1,
+        ]
+        .into_iter()
+        .find(|layout| layout.num_channels() == num_channels)
+    }
+}
+
+/// The order the channels of a layout come in. Files and devices don't all agree on it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChannelOrder {
+    /// The order of WAV files and SMPTE, which is the order of `ChannelLayout::speakers`.
+    Wav,
+    /// The order of film and many DAWs: the front speakers from left to right, the surrounds,
+    /// and the LFE channel last.
+    Film,
+}
+
+impl ChannelOrder {
+    /// Returns the speakers of the layout in this order.
+    pub fn speakers(&self, layout: ChannelLayout) -> Vec<Speaker> {
+        use Speaker::*;
+        let wav = layout.speakers();
+        match self {
+            ChannelOrder::Wav => wav.to_vec(),
+            ChannelOrder::Film => [
+                Mono,
+                Left,
+                Center,
+                Right,
+                LeftSurround,
+                RightSurround,
+                LeftBack,
+                RightBack,
+                Lfe,
+            ]
+            .into_iter()
+            .filter(|speaker| wav.contains(speaker))
+            .collect(),
+        }
+    }
+}
+
+/// Says for every channel of the destination which channel of the source it takes, if any.
+/// Source channels can be reordered, dropped and duplicated. Destination channels without a
+/// source are silent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelMap {
+    num_inputs: Channels,
+    sources: Vec<Option<usize>>,
+}
+
+impl ChannelMap {
+    /// Creates a map from the number of source channels with the source of every destination
+    /// channel.
+    /// This will panic if a source channel doesn't exist.
+    pub fn new(num_inputs: Channels, sources: Vec<Option<usize>>) -> Self {
+        assert!(sources
+            .iter()
+            .flatten()
+            .all(|source| *source < num_inputs.as_usize()));
+        Self {
+            num_inputs,
+            sources,
+        }
+    }
+
+    /// Creates a map that keeps every channel where it is.
+    pub fn identity(num_channels: Channels) -> Self {
+        Self::new(
+            num_channels,
+            (0..num_channels.as_usize()).map(Some).collect(),
+        )
+    }
+
+    /// Creates a map that takes every destination speaker from the same speaker in the source.
+    /// A mono source goes to every destination speaker but the LFE channel.
+    pub fn from_speakers(from: &[Speaker], to: &[Speaker]) -> Self {
+        let sources = to
+            .iter()
+            .map(|speaker| match from {
+                [Speaker::Mono] if *speaker != Speaker::Lfe => Some(0),
+                _ => from.iter().position(|source| source == speaker),
+            })
+            .collect();
+        Self::new(Channels::from(from.len()), sources)
+    }
+
+    /// Creates a map between two layouts, both in WAV order.
+    pub fn between(from: ChannelLayout, to: ChannelLayout) -> Self {
+        Self::from_speakers(from.speakers(), to.speakers())
+    }
+
+    /// Creates a map from one order of the layout to another.
+    pub fn reorder(layout: ChannelLayout, from: ChannelOrder, to: ChannelOrder) -> Self {
+        Self::from_speakers(&from.speakers(layout), &to.speakers(layout))
+    }
+
+    /// Returns the number of source channels.
+    pub fn num_inputs(&self) -> Channels {
+        self.num_inputs
+    }
+
+    /// Returns the number of destination channels.
+    pub fn num_outputs(&self) -> Channels {
+        Channels::from(self.sources.len())
+    }
+
+    /// Returns the source of every destination channel.
+    pub fn sources(&self) -> &[Option<usize>] {
+        &self.sources
+    }
+
+    /// Tells whether every channel stays where it is.
+    pub fn is_identity(&self) -> bool {
+        self.num_inputs.as_usize() == self.sources.len()
+            && self
+                .sources
+                .iter()
+                .enumerate()
+                .all(|(index, source)| *source == Some(index))
+    }
+
+    /// Returns the source channels that don't end up in the destination.
+    pub fn dropped(&self) -> Vec<usize> {
+        (0..self.num_inputs.as_usize())
+            .filter(|input| !self.sources.contains(&Some(*input)))
+            .collect()
+    }
+
+    /// Copies the channels of the input to the output, replacing what was in the output.
+    /// This will panic if the buffers don't have the numbers of channels of the map, or if they
+    /// don't have the same number of samples.
+    pub fn apply<T: Copy + Default>(&self, input: &Buffer<T>, output: &mut Buffer<T>) {
+        assert_eq!(input.num_channels(), self.num_inputs);
+        assert_eq!(output.num_channels(), self.num_outputs());
+        assert_eq!(input.num_samples(), output.num_samples());
+        for (channel, source) in output.iter_chans_mut().zip(&self.sources) {
+            match source {
+                Some(source) => channel.copy_from_slice(input.chan(*source)),
+                None => channel.fill(T::default()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Samples;
+
+    #[test_case(ChannelLayout::Mono => 1; "mono")]
+    #[test_case(ChannelLayout::Stereo => 2; "stereo")]
+    #[test_case(ChannelLayout::Lcr => 3; "lcr")]
+    #[test_case(ChannelLayout::Quad => 4; "quad")]
+    #[test_case(ChannelLayout::Surround5_1 => 6; "5.1")]
+    #[test_case(ChannelLayout::Surround7_1 => 8; "7.1")]
+    fn number_of_channels(layout: ChannelLayout) -> usize {
+        assert_eq!(
+            ChannelLayout::from_num_channels(layout.num_channels()),
+            Some(layout)
+        );
+        layout.num_channels().as_usize()
+    }
+
+    #[test]
+    fn lfe_has_no_direction() {
+        assert_eq!(ChannelLayout::Surround7_1.index_of(Speaker::Lfe), Some(3));
+        assert_eq!(Speaker::Lfe.azimuth(), None);
+    }
+
+    #[test]
+    fn reorders_7_1_to_film_and_back() {
+        let to_film = ChannelMap::reorder(
+            ChannelLayout::Surround7_1,
+            ChannelOrder::Wav,
+            ChannelOrder::Film,
+        );
+        let to_wav = ChannelMap::reorder(
+            ChannelLayout::Surround7_1,
+            ChannelOrder::Film,
+            ChannelOrder::Wav,
+        );
+        let mut wav = Buffer::<f32>::allocate(Channels::from(8), Samples::from(2));
+        for (index, channel) in wav.iter_chans_mut().enumerate() {
+            channel.fill(index as f32);
+        }
+        let mut film = Buffer::allocate(Channels::from(8), Samples::from(2));
+        let mut back = Buffer::allocate(Channels::from(8), Samples::from(2));
+
+        to_film.apply(&wav, &mut film);
+        to_wav.apply(&film, &mut back);
+
+        assert_eq!(
+            to_film.sources(),
+            [0, 2, 1, 6, 7, 4, 5, 3].map(Some).as_slice()
+        );
+        assert_eq!(back.data(), wav.data());
+        assert!(!to_film.is_identity());
+        assert!(to_film.dropped().is_empty());
+    }
+
+    #[test]
+    fn maps_between_layouts() {
+        let down = ChannelMap::between(ChannelLayout::Surround5_1, ChannelLayout::Stereo);
+        let up = ChannelMap::between(ChannelLayout::Stereo, ChannelLayout::Lcr);
+        let mono = ChannelMap::between(ChannelLayout::Mono, ChannelLayout::Surround5_1);
+
+        assert_eq!(down.sources(), &[Some(0), Some(1)]);
+        assert_eq!(down.dropped(), [2, 3, 4, 5]);
+        assert_eq!(up.sources(), &[Some(0), Some(1), None]);
+        assert_eq!(
+            mono.sources(),
+            &[Some(0), Some(0), Some(0), None, Some(0), Some(0)]
+        );
+        assert!(ChannelMap::between(ChannelLayout::Quad, ChannelLayout::Quad).is_identity());
+    }
+
+    #[test]
+    fn destination_channels_without_a_source_are_silent() {
+        let map = ChannelMap::new(Channels::from(1), vec![None, Some(0), Some(0)]);
+        let mut input = Buffer::<f32>::allocate(Channels::from(1), Samples::from(3));
+        input.map_samples(|_| 1.0);
+        let mut output = Buffer::allocate(Channels::from(3), Samples::from(3));
+        output.map_samples(|_| 5.0);
+
+        map.apply(&input, &mut output);
+
+        assert_eq!(output.chan(0), &[0.0; 3]);
+        assert_eq!(output.chan(2), &[1.0; 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn sources_that_do_not_exist_panic() {
+        ChannelMap::new(Channels::from(2), vec![Some(2)]);
+    }
+}
@@ -25,6 +25,102 @@
 //! }
 //! ```
 
+#[cfg(feature = "alloc-guard")]
+pub mod alloc_guard;
+pub mod arrangement;
 pub mod biquad;
+pub mod bitcrusher;
 pub mod buffer;
+pub mod buffer_pool;
+pub mod buffer_view;
+pub mod bypass;
+pub mod clip;
+pub mod clipping;
+pub mod convolution;
+pub mod convolution_reverb;
+pub mod crossover;
+pub mod de_esser;
+pub mod decay;
+pub mod delay;
+pub mod device;
+pub mod disk_stream;
+pub mod dither;
+pub mod dynamics;
+pub mod envelope;
+pub mod eq;
+pub mod events;
+pub mod fades;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fft;
+pub mod filterbank;
+pub mod fir;
+pub mod fractional_delay;
+pub mod gain_utility;
+pub mod gate;
+pub mod graph;
+pub mod hum;
+pub mod latency;
+pub mod layout;
+pub mod limiter;
+pub mod loudness;
+#[cfg(feature = "macros")]
+mod macros;
+pub mod measurement;
+pub mod mel;
+pub mod meter;
+pub mod metronome;
+pub mod midi;
+pub mod midi_file;
+pub mod mixer;
+pub mod modulation;
+pub mod noise;
+pub mod noise_reduction;
+pub mod normalization;
+pub mod onset;
+pub mod osc;
+pub mod oversampling;
+pub mod panning;
+pub mod parameter;
+pub mod pitch;
+pub mod pitch_shift;
+pub mod prelude;
+pub mod preset;
+pub mod probe;
+pub mod processor;
+pub mod quantize;
+pub mod recorder;
+pub mod render;
+pub mod resample;
+pub mod response;
+pub mod reverb;
+pub mod ring_modulator;
+pub mod sample;
+pub mod sampler;
+pub mod sections;
+pub mod segmentation;
+pub mod sequence;
+pub mod signals;
+pub mod smoother;
+pub mod soft_clipper;
+pub mod spectrum;
+pub mod stereo;
+pub mod stft;
+pub mod sweep;
+#[cfg(feature = "async")]
+pub mod tasks;
+pub mod tempo;
+pub mod thumbnail;
+pub mod time_stretch;
+pub mod transfer_function;
+pub mod transport;
+pub mod tremolo;
 pub mod units;
+pub mod varispeed;
+pub mod watchdog;
+pub mod wav;
+pub mod waveshaper;
+pub mod wavetable;
+#[cfg(feature = "web")]
+pub mod web;
+pub mod windows;
@@ -0,0 +1,399 @@
+//! This module contains a brickwall limiter with lookahead. The audio is delayed by the
+//! lookahead time, so the gain can already be turned down smoothly before a peak arrives, and
+//! no sample peak ever exceeds the ceiling. The delay is reported as a `Latency`, so a host can
+//! compensate for it. In true peak mode, the limiter looks at the oversampled waveform
+//! instead of the samples, so the peaks in between the samples stay below the ceiling too.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::limiter::Limiter;
+//! use rabu::units::{Channels, Decibels, SampleRate, Samples, Seconds};
+//!
+//! let mut limiter = Limiter::new(
+//!     Channels::from(2),
+//!     SampleRate::from(48000),
+//!     Seconds::from(0.005),
+//! );
+//! limiter.set_ceiling(Decibels::from(-1.0));
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! buffer.map_samples(|_| 2.0);
+//! limiter.process(&mut buffer);
+//!
+//! let ceiling = Decibels::from(-1.0).to_gain() as f32;
+//! assert!(buffer.data().iter().all(|sample| sample.abs() <= ceiling));
+//! assert_eq!(limiter.latency(), Samples::from(240).to_seconds(SampleRate::from(48000)).into());
+//! ```
+
+use std::collections::VecDeque;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::buffer::Buffer;
+use crate::dynamics::linked_peak;
+use crate::meter::TruePeakMeter;
+use crate::preset::Preset;
+use crate::sample::Sample;
+use crate::units::{Channels, Decibels, Latency, SampleRate, Samples, Seconds};
+
+/// A lookahead brickwall limiter, with all channels linked.
+#[derive(Clone, Debug)]
+pub struct Limiter {
+    ceiling: Decibels,
+    release: Seconds,
+    sample_rate: SampleRate,
+    release_coefficient: f64,
+    lookahead: usize,
+    /// Finds the true peaks, in true peak mode.
+    true_peak: Option<TruePeakMeter>,
+    /// The delay of the audio: the lookahead, plus the delay of the true peak detection.
+    delay_length: usize,
+    /// The delayed audio of every channel.
+    delay: Vec<Vec<f64>>,
+    /// The lowest gain needed over the lookahead window, as a queue of rising gains together
+    /// with the sample they were needed at.
+    minimum: VecDeque<(usize, f64)>,
+    /// The last held gains, averaged to turn the steps of the hold into ramps.
+    held: Vec<f64>,
+    held_sum: f64,
+    envelope: f64,
+    position: usize,
+    gain_reduction: Decibels,
+}
+
+impl Limiter {
+    /// Creates a new limiter with a ceiling of 0 dB and 100 ms release, which delays the audio
+    /// by the given lookahead time.
+    pub fn new(num_channels: Channels, sample_rate: SampleRate, lookahead: Seconds) -> Self {
+        let lookahead = lookahead.to_samples(sample_rate).as_usize();
+        let window = lookahead + 1;
+        let mut limiter = Self {
+            ceiling: Decibels::from(0.0),
+            release: Seconds::from(0.1),
+            sample_rate,
+            release_coefficient: 0.0,
+            lookahead,
+            true_peak: None,
+            delay_length: lookahead,
+            delay: vec![vec![0.0; lookahead]; num_channels.as_usize()],
+            minimum: VecDeque::with_capacity(window),
+            held: vec![1.0; window],
+            held_sum: 0.0,
+            envelope: 1.0,
+            position: 0,
+            gain_reduction: Decibels::from(0.0),
+        };
+        limiter.update_release();
+        limiter.reset();
+        limiter
+    }
+
+    /// Returns the ceiling.
+    pub fn ceiling(&self) -> Decibels {
+        self.ceiling
+    }
+
+    /// Changes the level no sample will exceed.
+    pub fn set_ceiling(&mut self, ceiling: Decibels) {
+        self.ceiling = ceiling;
+    }
+
+    /// Returns the release time.
+    pub fn release(&self) -> Seconds {
+        self.release
+    }
+
+    /// Changes how fast the gain recovers after a peak, as a time constant.
+    pub fn set_release(&mut self, release: Seconds) {
+        self.release = release;
+        self.update_release();
+    }
+
+    /// Returns the lookahead in samples.
+    pub fn lookahead(&self) -> Samples {
+        Samples::from(self.lookahead)
+    }
+
+    /// Returns the delay between input and output, which is the lookahead, plus a little more
+    /// in true peak mode.
+    pub fn latency(&self) -> Latency {
+        Latency::from(Samples::from(self.delay_length).to_seconds(self.sample_rate))
+    }
+
+    /// Tells whether the limiter keeps the true peaks below the ceiling, instead of only the
+    /// sample peaks.
+    pub fn is_true_peak(&self) -> bool {
+        self.true_peak.is_some()
+    }
+
+    /// Turns true peak mode on or off. The true peaks are found by oversampling, whose delay
+    /// is added to the latency. This clears the internal state.
+    pub fn set_true_peak(&mut self, true_peak: bool) {
+        let num_channels = Channels::from(self.delay.len());
+        self.true_peak =
+            true_peak.then(|| TruePeakMeter::for_sample_rate(num_channels, self.sample_rate));
+        let detection_delay = self
+            .true_peak
+            .as_ref()
+            .map_or(0, |meter| meter.latency_samples().as_usize());
+        self.delay_length = self.lookahead + detection_delay;
+        for channel in self.delay.iter_mut() {
+            channel.resize(self.delay_length, 0.0);
+        }
+        self.reset();
+    }
+
+    /// Returns the largest gain reduction of the last processed buffer, as a positive number,
+    /// for metering.
+    pub fn gain_reduction(&self) -> Decibels {
+        self.gain_reduction
+    }
+
+    /// Clears the internal state, as if no audio was processed yet.
+    pub fn reset(&mut self) {
+        self.delay.iter_mut().for_each(|channel| channel.fill(0.0));
+        if let Some(meter) = self.true_peak.as_mut() {
+            meter.reset();
+        }
+        self.minimum.clear();
+        self.held.fill(1.0);
+        self.held_sum = self.held.len() as f64;
+        self.envelope = 1.0;
+        self.position = 0;
+        self.gain_reduction = Decibels::from(0.0);
+    }
+
+    /// Limits the buffer.
+    /// This will panic if the buffer doesn't have the number of channels the limiter was made
+    /// for.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        assert_eq!(buffer.num_channels().as_usize(), self.delay.len());
+        let ceiling = self.ceiling.to_gain();
+        let mut lowest_gain = 1.0_f64;
+
+        for index in buffer.sample_indices() {
+            let peak = match self.true_peak.as_mut() {
+                Some(meter) => buffer.channel_indices().fold(0.0, |peak, channel| {
+                    let sample = buffer.chan(channel)[index].to_f64();
+                    meter.next_peak(channel, sample).max(peak)
+                }),
+                None => linked_peak(buffer, index),
+            };
+            let gain = self.next_gain(peak, ceiling);
+            lowest_gain = lowest_gain.min(gain);
+
+            let slot = self.position % self.delay_length.max(1);
+            for (channel, delay) in buffer.iter_chans_mut().zip(self.delay.iter_mut()) {
+                let input = channel[index].to_f64();
+                let delayed = if self.delay_length == 0 {
+                    input
+                } else {
+                    std::mem::replace(&mut delay[slot], input)
+                };
+                // The clamp only catches rounding errors of the running average.
+                channel[index] = T::from_f64((delayed * gain).clamp(-ceiling, ceiling));
+            }
+            self.position += 1;
+        }
+
+        self.gain_reduction = Decibels::from(-Decibels::from_gain(lowest_gain).as_f64());
+    }
+
+    /// Returns the gain for the sample that leaves the delay, given the peak of the sample that
+    /// enters it.
+    fn next_gain(&mut self, peak: f64, ceiling: f64) -> f64 {
+        let window = self.held.len();
+        let needed = if peak > ceiling { ceiling / peak } else { 1.0 };
+
+        // Holds the lowest gain needed within the window...
+        while self.minimum.back().is_some_and(|(_, gain)| *gain >= needed) {
+            self.minimum.pop_back();
+        }
+        self.minimum.push_back((self.position, needed));
+        while self
+            .minimum
+            .front()
+            .is_some_and(|(position, _)| position + window <= self.position)
+        {
+            self.minimum.pop_front();
+        }
+        let held = self.minimum.front().map_or(1.0, |(_, gain)| *gain);
+
+        // ...and averages it over the same window, so the gain ramps down in time for the peak.
+        let slot = self.position % window;
+        self.held_sum += held - self.held[slot];
+        self.held[slot] = held;
+        let average = (self.held_sum / window as f64).min(1.0);
+
+        self.envelope = if average < self.envelope {
+            average
+        } else {
+            average + (self.envelope - average) * self.release_coefficient
+        };
+        self.envelope
+    }
+
+    fn update_release(&mut self) {
+        let samples = self.release.as_f64() * self.sample_rate.as_f64();
+        self.release_coefficient = if samples > 0.0 {
+            (-1.0 / samples).exp()
+        } else {
+            0.0
+        };
+    }
+}
+
+/// The settings of a `Limiter`, for presets. The lookahead isn't part of them, as it is fixed
+/// when the limiter is created.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct LimiterSettings {
+    pub ceiling: Decibels,
+    pub release: Seconds,
+    pub true_peak: bool,
+}
+
+impl Default for LimiterSettings {
+    fn default() -> Self {
+        Self {
+            ceiling: Decibels::from(0.0),
+            release: Seconds::from(0.1),
+            true_peak: false,
+        }
+    }
+}
+
+impl Preset for Limiter {
+    type Settings = LimiterSettings;
+    const PROCESSOR: &'static str = "limiter";
+    const VERSION: u32 = 1;
+
+    fn settings(&self) -> LimiterSettings {
+        LimiterSettings {
+            ceiling: self.ceiling,
+            release: self.release,
+            true_peak: self.is_true_peak(),
+        }
+    }
+
+    /// Only clears the internal state when true peak mode changes.
+    fn apply_settings(&mut self, settings: &LimiterSettings) {
+        self.set_ceiling(settings.ceiling);
+        self.set_release(settings.release);
+        if settings.true_peak != self.is_true_peak() {
+            self.set_true_peak(settings.true_peak);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signals;
+
+    fn limiter(lookahead: usize) -> Limiter {
+        Limiter::new(
+            Channels::from(2),
+            SampleRate::from(1000),
+            Seconds::from(lookahead as f64 / 1000.0),
+        )
+    }
+
+    #[test]
+    fn peaks_never_exceed_the_ceiling() {
+        let mut limiter = Limiter::new(
+            Channels::from(2),
+            SampleRate::from(48000),
+            Seconds::from(0.002),
+        );
+        limiter.set_ceiling(Decibels::from(-3.0));
+        let mut noise = signals::white_noise::<f64>(Channels::from(2), Samples::from(48000), 1);
+        noise.map_samples(|sample| sample * 8.0);
+
+        limiter.process(&mut noise);
+
+        let ceiling = Decibels::from(-3.0).to_gain();
+        assert!(noise.data().iter().all(|sample| sample.abs() <= ceiling));
+        assert!(limiter.gain_reduction().as_f64() > 15.0);
+    }
+
+    #[test]
+    fn quiet_audio_is_only_delayed() {
+        let mut limiter = limiter(4);
+        let mut buffer = signals::impulse::<f64>(Channels::from(2), Samples::from(8));
+        buffer.map_samples(|sample| sample * 0.5);
+
+        limiter.process(&mut buffer);
+
+        for channel in buffer.iter_chans() {
+            assert_eq!(channel, &[0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0]);
+        }
+        assert_eq!(limiter.latency(), Latency::from_secs_f64(0.004));
+        assert_eq!(limiter.gain_reduction(), Decibels::from(0.0));
+    }
+
+    #[test]
+    fn gain_ramps_down_before_the_peak() {
+        let mut limiter = limiter(4);
+        limiter.set_release(Seconds::from(0.0));
+        let mut buffer = Buffer::allocate(Channels::from(2), Samples::from(12));
+        buffer.map_samples(|_| 0.5);
+        for channel in buffer.iter_chans_mut() {
+            channel[4] = 2.0;
+        }
+
+        limiter.process(&mut buffer);
+
+        // The spike leaves the delay at sample 8, turned down to the ceiling, and the gain
+        // ramps down in equal steps over the lookahead before it and back up after it.
+        let output = buffer.chan(0);
+        assert_eq!(output[8], 1.0);
+        let gains: Vec<f64> = [4, 5, 6, 7].iter().map(|n| output[*n] / 0.5).collect();
+        assert!(gains.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!((gains[0] - 0.9).abs() < 1e-9);
+        assert!((output[11] / 0.5 - 0.8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn zero_lookahead_still_limits() {
+        let mut limiter = limiter(0);
+        let mut buffer = signals::step::<f32>(Channels::from(2), Samples::from(4));
+        buffer.map_samples(|sample| sample * 4.0);
+
+        limiter.process(&mut buffer);
+
+        assert!(buffer.data().iter().all(|sample| *sample == 1.0));
+        assert_eq!(limiter.latency(), Latency::from_secs_f64(0.0));
+    }
+
+    #[test]
+    fn true_peak_mode_keeps_the_peaks_between_the_samples_down() {
+        let sample_rate = SampleRate::from(48000);
+        // A sine at a quarter of the sample rate with all samples at 45 degrees of phase, so
+        // the waveform peaks 3 dB above the samples, driven over the ceiling.
+        let true_peak = |true_peak_mode: bool| {
+            let mut limiter = Limiter::new(Channels::from(1), sample_rate, Seconds::from(0.002));
+            limiter.set_true_peak(true_peak_mode);
+            let mut buffer = Buffer::<f64>::allocate(Channels::from(1), Samples::from(4800));
+            for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+                let phase = std::f64::consts::FRAC_PI_2 * n as f64 + std::f64::consts::FRAC_PI_4;
+                *sample = 1.2 * phase.sin();
+            }
+
+            limiter.process(&mut buffer);
+
+            let mut meter = TruePeakMeter::new(Channels::from(1), 8);
+            let mut settled = Buffer::<f64>::allocate(Channels::from(1), Samples::from(2400));
+            settled.chan_mut(0).copy_from_slice(&buffer.chan(0)[2400..]);
+            meter.process(&settled);
+            (meter.true_peak(0).as_f64(), limiter.latency())
+        };
+
+        let (sample_peak_mode, _) = true_peak(false);
+        let (true_peak_mode, latency) = true_peak(true);
+
+        assert!(sample_peak_mode > 1.0, "{sample_peak_mode}");
+        assert!(true_peak_mode < 0.1, "{true_peak_mode}");
+        assert!(latency > Latency::from(Samples::from(96).to_seconds(sample_rate)));
+    }
+}
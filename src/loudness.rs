@@ -0,0 +1,600 @@
+//! This module contains a loudness meter after ITU BS.1770 and EBU R128, the way streaming
+//! platforms and broadcasters measure loudness. The audio is K-weighted (a filter that
+//! roughly follows the sensitivity of the ear), and its power is averaged over 400 ms for the
+//! momentary loudness, over 3 seconds for the short-term loudness, and over everything with
+//! quiet parts gated out for the integrated loudness. The loudness range tells how much the
+//! short-term loudness varies. The meter can also keep the true peak, which loudness targets
+//! usually come with.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::loudness::LoudnessMeter;
+//! use rabu::units::{Channels, SampleRate, Samples};
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let mut meter = LoudnessMeter::new(Channels::from(2), sample_rate);
+//!
+//! // A 1 kHz sine at -23 dBFS in both channels.
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(48000 * 4));
+//! let amplitude = 10.0_f32.powf(-23.0 / 20.0);
+//! for channel in buffer.iter_chans_mut() {
+//!     for (n, sample) in channel.iter_mut().enumerate() {
+//!         *sample = amplitude * (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / 48000.0).sin();
+//!     }
+//! }
+//! meter.process(&buffer);
+//!
+//! assert!((meter.integrated().as_f64() + 23.0).abs() < 0.1);
+//! ```
+//!
+//! For a report of a whole buffer, e.g. for quality control before delivery,
+//! `measure_loudness` gives the numbers together with the momentary and short-term loudness
+//! over time, to plot:
+//! ```rust
+//! use rabu::loudness::measure_loudness;
+//! use rabu::signals::pink_noise;
+//! use rabu::units::{Channels, SampleRate, Samples};
+//!
+//! let buffer = pink_noise::<f32>(Channels::from(2), Samples::from(48000 * 10), 1);
+//!
+//! let report = measure_loudness(&buffer, SampleRate::from(48000));
+//!
+//! // A momentary value every 100 ms once the first 400 ms are in.
+//! assert_eq!(report.momentary.len(), 97);
+//! assert!((report.momentary[0].0.as_secs_f64() - 0.4).abs() < 1e-9);
+//! assert_eq!(report.short_term.len(), 71);
+//! assert!(report.loudness_range.as_f64() < 1.0);
+//! ```
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+use crate::biquad::{BiquadCoefficients, BiquadFilter};
+use crate::buffer::Buffer;
+use crate::dynamics::SILENCE_DB;
+use crate::layout::{ChannelLayout, Speaker};
+use crate::meter::TruePeakMeter;
+use crate::sample::Sample;
+use crate::units::{Channels, Decibels, Lufs, SampleRate, TimePoint, TimeSection};
+
+/// The number of 100 ms steps in a momentary block.
+const MOMENTARY_STEPS: usize = 4;
+/// The number of 100 ms steps in a short-term block.
+const SHORT_TERM_STEPS: usize = 30;
+const ABSOLUTE_GATE: f64 = -70.0;
+const INTEGRATED_RELATIVE_GATE: f64 = -10.0;
+const RANGE_RELATIVE_GATE: f64 = -20.0;
+
+/// Returns the coefficients of the first stage of the K-weighting filter, a high shelf that
+/// models the head. The design follows the one in BS.1770, for any sample rate.
+pub fn k_weighting_shelf(sample_rate: SampleRate) -> BiquadCoefficients {
+    let f0 = 1681.974450955533;
+    let gain = 3.999843853973347;
+    let q = 0.7071752369554196;
+
+    let k = (PI * f0 / sample_rate.as_f64()).tan();
+    let vh = 10.0_f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+
+    BiquadCoefficients {
+        b0: (vh + vb * k / q + k * k) / a0,
+        b1: 2.0 * (k * k - vh) / a0,
+        b2: (vh - vb * k / q + k * k) / a0,
+        a1: 2.0 * (k * k - 1.0) / a0,
+        a2: (1.0 - k / q + k * k) / a0,
+    }
+}
+
+/// Returns the coefficients of the second stage of the K-weighting filter, a high pass (the
+/// "revised low-frequency B-curve").
+pub fn k_weighting_high_pass(sample_rate: SampleRate) -> BiquadCoefficients {
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+
+    let k = (PI * f0 / sample_rate.as_f64()).tan();
+    let a0 = 1.0 + k / q + k * k;
+
+    BiquadCoefficients {
+        b0: 1.0,
+        b1: -2.0,
+        b2: 1.0,
+        a1: 2.0 * (k * k - 1.0) / a0,
+        a2: (1.0 - k / q + k * k) / a0,
+    }
+}
+
+/// Measures loudness after ITU BS.1770-4 and EBU R128.
+#[derive(Clone, Debug)]
+pub struct LoudnessMeter {
+    sample_rate: SampleRate,
+    weights: Vec<f64>,
+    filters: Vec<(BiquadFilter, BiquadFilter)>,
+    step_length: usize,
+    /// The sum of the squared, weighted samples of every channel in the current step.
+    step_sums: Vec<f64>,
+    step_position: usize,
+    /// The mean squares of the last steps, summed over the channels.
+    steps: VecDeque<f64>,
+    /// The mean squares of every momentary block, for the integrated loudness.
+    momentary_blocks: Vec<f64>,
+    /// The mean squares of every short-term block, for the loudness range.
+    short_term_blocks: Vec<f64>,
+    /// Only there with true peak metering, as the oversampling costs more than the rest.
+    true_peaks: Option<TruePeakMeter>,
+}
+
+impl LoudnessMeter {
+    /// Creates a new meter where all channels count the same.
+    pub fn new(num_channels: Channels, sample_rate: SampleRate) -> Self {
+        let num_channels = num_channels.as_usize();
+        let filters = (
+            BiquadFilter::new(k_weighting_shelf(sample_rate)),
+            BiquadFilter::new(k_weighting_high_pass(sample_rate)),
+        );
+        Self {
+            sample_rate,
+            weights: vec![1.0; num_channels],
+            filters: vec![filters; num_channels],
+            step_length: ((sample_rate.as_f64() / 10.0).round() as usize).max(1),
+            step_sums: vec![0.0; num_channels],
+            step_position: 0,
+            steps: VecDeque::with_capacity(SHORT_TERM_STEPS),
+            momentary_blocks: Vec::new(),
+            short_term_blocks: Vec::new(),
+            true_peaks: None,
+        }
+    }
+
+    /// Creates a new meter for the layout, with the channel weights of BS.1770: the surround
+    /// channels count 1.5 dB more, and the LFE channel isn't measured.
+    pub fn for_layout(layout: ChannelLayout, sample_rate: SampleRate) -> Self {
+        let mut meter = Self::new(layout.num_channels(), sample_rate);
+        let weights: Vec<f64> = layout
+            .speakers()
+            .iter()
+            .map(|speaker| match speaker {
+                Speaker::Lfe => 0.0,
+                Speaker::LeftSurround
+                | Speaker::RightSurround
+                | Speaker::LeftBack
+                | Speaker::RightBack => 1.41,
+                _ => 1.0,
+            })
+            .collect();
+        meter.set_channel_weights(&weights);
+        meter
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.weights.len())
+    }
+
+    /// Changes how much every channel counts towards the loudness.
+    /// This will panic if there isn't a weight for every channel.
+    pub fn set_channel_weights(&mut self, weights: &[f64]) {
+        assert_eq!(
+            weights.len(),
+            self.weights.len(),
+            "a weight for every channel"
+        );
+        self.weights.copy_from_slice(weights);
+    }
+
+    /// Clears all measurements.
+    pub fn reset(&mut self) {
+        for (shelf, high_pass) in self.filters.iter_mut() {
+            shelf.reset();
+            high_pass.reset();
+        }
+        self.step_sums.fill(0.0);
+        self.step_position = 0;
+        self.steps.clear();
+        self.momentary_blocks.clear();
+        self.short_term_blocks.clear();
+        if let Some(true_peaks) = &mut self.true_peaks {
+            true_peaks.reset();
+        }
+    }
+
+    /// Measures the buffer.
+    /// This will panic if the buffer doesn't have the number of channels the meter was made
+    /// for.
+    pub fn process<T: Sample>(&mut self, buffer: &Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+
+        for index in buffer.sample_indices() {
+            for (channel, (shelf, high_pass)) in self.filters.iter_mut().enumerate() {
+                let sample = buffer.chan(channel)[index].to_f64();
+                if let Some(true_peaks) = &mut self.true_peaks {
+                    true_peaks.next_peak(channel, sample);
+                }
+                let weighted = high_pass.process(shelf.process(sample));
+                self.step_sums[channel] += weighted * weighted;
+            }
+            self.step_position += 1;
+            if self.step_position == self.step_length {
+                self.finish_step();
+            }
+        }
+    }
+
+    /// Returns the loudness of the last 400 ms.
+    pub fn momentary(&self) -> Lufs {
+        to_lufs(self.recent_mean_square(MOMENTARY_STEPS))
+    }
+
+    /// Returns the loudness of the last 3 seconds.
+    pub fn short_term(&self) -> Lufs {
+        to_lufs(self.recent_mean_square(SHORT_TERM_STEPS))
+    }
+
+    /// Returns the loudness of everything measured since the start or the last reset, leaving
+    /// out silence and the parts that are more than 10 LU quieter than the rest.
+    pub fn integrated(&self) -> Lufs {
+        let gated = gate(&self.momentary_blocks, INTEGRATED_RELATIVE_GATE);
+        to_lufs(mean(&gated))
+    }
+
+    /// Returns the loudness range in LU after EBU Tech 3342: the spread of the short-term
+    /// loudness between the 10th and the 95th percentile, leaving out silence and the parts
+    /// that are more than 20 LU quieter than the rest. It's 0 until at least 3 seconds were
+    /// measured.
+    pub fn loudness_range(&self) -> Decibels {
+        loudness_range(&self.short_term_blocks)
+    }
+
+    /// Returns the integrated loudness of the momentary blocks that lie within the section,
+    /// where the section starts at the start of the measurement or the last reset. This gives
+    /// the loudness of a part, e.g. a song of an album or a scene of a film.
+    pub fn integrated_in(&self, section: TimeSection) -> Lufs {
+        let blocks = self.blocks_in(&self.momentary_blocks, MOMENTARY_STEPS, section);
+        to_lufs(mean(&gate(&blocks, INTEGRATED_RELATIVE_GATE)))
+    }
+
+    /// Returns the loudness range of the short-term blocks that lie within the section, where
+    /// the section starts at the start of the measurement or the last reset.
+    pub fn loudness_range_in(&self, section: TimeSection) -> Decibels {
+        loudness_range(&self.blocks_in(&self.short_term_blocks, SHORT_TERM_STEPS, section))
+    }
+
+    /// Returns the momentary loudness every 100 ms, from the start of the measurement or the
+    /// last reset. Every value is paired with the time at the end of its 400 ms block, so the
+    /// first one is at 400 ms.
+    pub fn momentary_series(&self) -> Vec<(TimePoint, Lufs)> {
+        self.series(&self.momentary_blocks, MOMENTARY_STEPS)
+    }
+
+    /// Returns the short-term loudness every 100 ms, from the start of the measurement or the
+    /// last reset. Every value is paired with the time at the end of its 3 second block, so
+    /// the first one is at 3 seconds.
+    pub fn short_term_series(&self) -> Vec<(TimePoint, Lufs)> {
+        self.series(&self.short_term_blocks, SHORT_TERM_STEPS)
+    }
+
+    /// Tells whether the meter keeps the true peak.
+    pub fn is_true_peak(&self) -> bool {
+        self.true_peaks.is_some()
+    }
+
+    /// Turns true peak metering on or off. It's off by default, as the oversampling it takes
+    /// is slower than the loudness measurement itself. Turning it on starts from silence.
+    pub fn set_true_peak(&mut self, true_peak: bool) {
+        self.true_peaks = true_peak
+            .then(|| TruePeakMeter::for_sample_rate(self.num_channels(), self.sample_rate));
+    }
+
+    /// Returns the highest true peak of all channels in dBTP, or `None` when true peak
+    /// metering is off.
+    pub fn true_peak(&self) -> Option<Decibels> {
+        self.true_peaks.as_ref().map(TruePeakMeter::max_true_peak)
+    }
+
+    fn finish_step(&mut self) {
+        let step = self
+            .step_sums
+            .iter()
+            .zip(self.weights.iter())
+            .map(|(sum, weight)| weight * sum / self.step_length as f64)
+            .sum();
+        self.step_sums.fill(0.0);
+        self.step_position = 0;
+
+        if self.steps.len() == SHORT_TERM_STEPS {
+            self.steps.pop_front();
+        }
+        self.steps.push_back(step);
+        if self.steps.len() >= MOMENTARY_STEPS {
+            self.momentary_blocks
+                .push(self.recent_mean_square(MOMENTARY_STEPS));
+        }
+        if self.steps.len() == SHORT_TERM_STEPS {
+            self.short_term_blocks
+                .push(self.recent_mean_square(SHORT_TERM_STEPS));
+        }
+    }
+
+    /// The time at the end of the given step.
+    fn step_time(&self, step: usize) -> TimePoint {
+        TimePoint::from_secs_f64((step * self.step_length) as f64 / self.sample_rate.as_f64())
+    }
+
+    fn series(&self, blocks: &[f64], num_steps: usize) -> Vec<(TimePoint, Lufs)> {
+        blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (self.step_time(index + num_steps), to_lufs(*block)))
+            .collect()
+    }
+
+    /// The blocks that start and end within the section.
+    fn blocks_in(&self, blocks: &[f64], num_steps: usize, section: TimeSection) -> Vec<f64> {
+        // Allows for the rounding of the step length.
+        let margin = 1e-9;
+        blocks
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| {
+                let start = self.step_time(*index).as_secs_f64();
+                let end = self.step_time(index + num_steps).as_secs_f64();
+                start >= section.start.as_secs_f64() - margin
+                    && end <= section.end().as_secs_f64() + margin
+            })
+            .map(|(_, block)| *block)
+            .collect()
+    }
+
+    /// The mean square of the last steps, where missing steps count as silence.
+    fn recent_mean_square(&self, num_steps: usize) -> f64 {
+        self.steps.iter().rev().take(num_steps).sum::<f64>() / num_steps as f64
+    }
+}
+
+/// The loudness of a whole buffer, with the loudness over time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoudnessReport {
+    /// The integrated loudness.
+    pub integrated: Lufs,
+    /// The loudness range in LU.
+    pub loudness_range: Decibels,
+    /// The highest true peak of all channels, in dBTP.
+    pub true_peak: Decibels,
+    /// The momentary loudness every 100 ms, as given by `LoudnessMeter::momentary_series`.
+    pub momentary: Vec<(TimePoint, Lufs)>,
+    /// The short-term loudness every 100 ms, as given by `LoudnessMeter::short_term_series`.
+    pub short_term: Vec<(TimePoint, Lufs)>,
+}
+
+/// Measures the loudness of the buffer, where all channels count the same.
+pub fn measure_loudness<T: Sample>(buffer: &Buffer<T>, sample_rate: SampleRate) -> LoudnessReport {
+    let mut meter = LoudnessMeter::new(buffer.num_channels(), sample_rate);
+    meter.set_true_peak(true);
+    meter.process(buffer);
+    LoudnessReport {
+        integrated: meter.integrated(),
+        loudness_range: meter.loudness_range(),
+        true_peak: meter.true_peak().unwrap(),
+        momentary: meter.momentary_series(),
+        short_term: meter.short_term_series(),
+    }
+}
+
+fn to_lufs(mean_square: f64) -> Lufs {
+    if mean_square <= 0.0 {
+        return Lufs::from(SILENCE_DB);
+    }
+    Lufs::from((-0.691 + 10.0 * mean_square.log10()).max(SILENCE_DB))
+}
+
+/// The spread of the gated short-term blocks between the 10th and the 95th percentile.
+fn loudness_range(short_term_blocks: &[f64]) -> Decibels {
+    let mut loudness: Vec<f64> = gate(short_term_blocks, RANGE_RELATIVE_GATE)
+        .into_iter()
+        .map(|block| to_lufs(block).as_f64())
+        .collect();
+    if loudness.is_empty() {
+        return Decibels::from(0.0);
+    }
+    loudness.sort_by(f64::total_cmp);
+    let percentile = |p: f64| loudness[((loudness.len() - 1) as f64 * p).round() as usize];
+    Decibels::from(percentile(0.95) - percentile(0.10))
+}
+
+fn mean(blocks: &[f64]) -> f64 {
+    if blocks.is_empty() {
+        0.0
+    } else {
+        blocks.iter().sum::<f64>() / blocks.len() as f64
+    }
+}
+
+/// Leaves out the blocks below the absolute gate, and then those that are further below the
+/// loudness of the remaining blocks than the relative gate.
+fn gate(blocks: &[f64], relative_gate: f64) -> Vec<f64> {
+    let loud_enough: Vec<f64> = blocks
+        .iter()
+        .copied()
+        .filter(|block| to_lufs(*block).as_f64() > ABSOLUTE_GATE)
+        .collect();
+    let threshold = to_lufs(mean(&loud_enough)).as_f64() + relative_gate;
+    loud_enough
+        .into_iter()
+        .filter(|block| to_lufs(*block).as_f64() > threshold)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Samples;
+
+    const RATE: u32 = 48000;
+
+    /// Returns 1 kHz sine segments in every channel, of the given peak levels in dBFS and
+    /// lengths in seconds, one after the other.
+    fn sines(segments: &[(f64, f64)], num_channels: usize) -> Buffer<f64> {
+        let length: usize = segments
+            .iter()
+            .map(|(_, seconds)| (seconds * RATE as f64) as usize)
+            .sum();
+        let mut buffer = Buffer::allocate(Channels::from(num_channels), Samples::from(length));
+        let mut levels = segments.iter().flat_map(|(level, seconds)| {
+            std::iter::repeat_n(
+                Decibels::from(*level).to_gain(),
+                (seconds * RATE as f64) as usize,
+            )
+        });
+        for n in 0..length {
+            let level = levels.next().unwrap();
+            let sample = level * (2.0 * PI * 1000.0 * n as f64 / RATE as f64).sin();
+            for channel in 0..num_channels {
+                buffer.chan_mut(channel)[n] = sample;
+            }
+        }
+        buffer
+    }
+
+    fn measure(buffer: &Buffer<f64>) -> LoudnessMeter {
+        let mut meter = LoudnessMeter::new(buffer.num_channels(), SampleRate::from(RATE));
+        meter.process(buffer);
+        meter
+    }
+
+    #[test]
+    fn full_scale_sine_in_one_channel_is_minus_3_lufs() {
+        let mut meter = LoudnessMeter::new(Channels::from(1), SampleRate::from(RATE));
+        meter.set_true_peak(true);
+        meter.process(&sines(&[(0.0, 1.0)], 1));
+
+        assert!((meter.momentary().as_f64() + 3.01).abs() < 0.05);
+        assert!(meter.true_peak().unwrap().as_f64().abs() < 0.2);
+        assert_eq!(measure(&sines(&[(0.0, 1.0)], 1)).true_peak(), None);
+    }
+
+    #[test]
+    fn k_weighting_is_flat_around_1_khz_and_cuts_the_low_end() {
+        let sample_rate = SampleRate::from(RATE);
+        let response = |frequency: f64| {
+            let frequency = crate::units::Frequency::from(frequency);
+            k_weighting_shelf(sample_rate).magnitude_at(frequency, sample_rate)
+                + k_weighting_high_pass(sample_rate).magnitude_at(frequency, sample_rate)
+        };
+
+        assert!((response(1000.0).as_f64() - 0.69).abs() < 0.05);
+        assert!(response(20.0).as_f64() < -10.0);
+        assert!((response(10000.0).as_f64() - 4.0).abs() < 0.3);
+    }
+
+    #[test]
+    fn short_term_and_momentary_of_a_steady_signal_agree() {
+        let meter = measure(&sines(&[(-20.0, 4.0)], 2));
+
+        assert!((meter.momentary().as_f64() + 20.0).abs() < 0.05);
+        assert!((meter.short_term().as_f64() + 20.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn quiet_parts_are_gated_out_of_the_integrated_loudness() {
+        // After EBU Tech 3341, test 3, but shorter.
+        let meter = measure(&sines(&[(-36.0, 2.0), (-23.0, 12.0), (-36.0, 2.0)], 2));
+
+        assert!((meter.integrated().as_f64() + 23.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn loudness_range_of_two_levels() {
+        // After EBU Tech 3342, test 1: 20 seconds at -20 and 20 seconds at -30.
+        let meter = measure(&sines(&[(-20.0 - 3.01, 20.0), (-30.0 - 3.01, 20.0)], 2));
+
+        assert!((meter.loudness_range().as_f64() - 10.0).abs() < 0.2);
+    }
+
+    #[test]
+    fn loudness_range_of_a_gradual_change() {
+        // After EBU Tech 3342, test 3, but shorter and in mono: a level that changes 1 dB per
+        // second over 20 seconds, between two steady parts.
+        let mut segments = vec![(-20.0, 5.0)];
+        segments.extend((0..20).map(|step| (-21.0 - step as f64, 1.0)));
+        segments.push((-40.0, 5.0));
+        let meter = measure(&sines(&segments, 1));
+
+        // The 10th percentile lies in the quiet part, the 95th in the loud one.
+        assert!((meter.loudness_range().as_f64() - 20.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn series_follow_the_loudness_over_time() {
+        let meter = measure(&sines(&[(-20.0, 4.0), (-30.0, 4.0)], 2));
+
+        let momentary = meter.momentary_series();
+        let short_term = meter.short_term_series();
+
+        assert_eq!(momentary.len(), 77);
+        assert_eq!(short_term.len(), 51);
+        assert!((momentary[0].0.as_secs_f64() - 0.4).abs() < 1e-9);
+        assert!((short_term[0].0.as_secs_f64() - 3.0).abs() < 1e-9);
+        assert!((momentary.last().unwrap().0.as_secs_f64() - 8.0).abs() < 1e-9);
+        let at = |series: &[(TimePoint, Lufs)], seconds: f64| {
+            let index = series
+                .iter()
+                .position(|(time, _)| (time.as_secs_f64() - seconds).abs() < 1e-9)
+                .unwrap();
+            series[index].1.as_f64()
+        };
+        assert!((at(&momentary, 3.0) + 20.0).abs() < 0.05);
+        assert!((at(&momentary, 7.0) + 30.0).abs() < 0.05);
+        assert!((at(&short_term, 8.0) + 30.0).abs() < 0.05);
+        assert_eq!(momentary.last().unwrap().1, meter.momentary());
+    }
+
+    #[test]
+    fn sections_are_measured_on_their_own() {
+        let meter = measure(&sines(&[(-20.0, 5.0), (-30.0, 5.0)], 2));
+        let section = |start: f64, duration: f64| TimeSection {
+            start: TimePoint::from_secs_f64(start),
+            duration: crate::units::Duration::from_secs_f64(duration),
+        };
+
+        assert!((meter.integrated_in(section(0.0, 5.0)).as_f64() + 20.0).abs() < 0.05);
+        assert!((meter.integrated_in(section(5.0, 5.0)).as_f64() + 30.0).abs() < 0.05);
+        assert!(meter.loudness_range_in(section(5.0, 5.0)).as_f64() < 0.1);
+        assert!((meter.loudness_range_in(section(0.0, 10.0)).as_f64() - 10.0).abs() < 0.2);
+        assert_eq!(
+            meter.integrated_in(section(20.0, 5.0)),
+            Lufs::from(SILENCE_DB)
+        );
+    }
+
+    #[test]
+    fn report_of_a_buffer() {
+        let buffer = sines(&[(-20.0, 4.0)], 2);
+
+        let report = measure_loudness(&buffer, SampleRate::from(RATE));
+
+        let meter = measure(&buffer);
+        assert_eq!(report.integrated, meter.integrated());
+        assert!((report.true_peak.as_f64() + 20.0).abs() < 0.1);
+        assert_eq!(report.momentary, meter.momentary_series());
+        assert_eq!(report.short_term.len(), 11);
+    }
+
+    #[test]
+    fn silence_has_no_loudness() {
+        let meter = measure(&sines(&[(-200.0, 1.0)], 2));
+
+        assert_eq!(meter.integrated(), Lufs::from(SILENCE_DB));
+        assert_eq!(meter.loudness_range(), Decibels::from(0.0));
+    }
+
+    #[test]
+    fn lfe_is_not_measured() {
+        let mut meter =
+            LoudnessMeter::for_layout(ChannelLayout::Surround5_1, SampleRate::from(RATE));
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(6), Samples::from(RATE));
+        buffer.chan_mut(3).fill(0.5);
+
+        meter.process(&buffer);
+
+        assert_eq!(meter.momentary(), Lufs::from(SILENCE_DB));
+    }
+}
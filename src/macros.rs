@@ -0,0 +1,102 @@
+//! This module contains macros for unit literals, with the `macros` feature. They expand to
+//! the constructors of the units, so they can be used in constants as well.
+
+/// Creates a `Frequency` in Hz:
+/// ```
+/// use rabu::hz;
+/// use rabu::units::Frequency;
+///
+/// const A4: Frequency = hz!(440.0);
+///
+/// assert_eq!(A4, Frequency::from(440.0));
+/// ```
+#[macro_export]
+macro_rules! hz {
+    ($value: expr) => {
+        $crate::units::Frequency::new($value as f64)
+    };
+}
+
+/// Creates `Seconds`:
+/// ```
+/// use rabu::secs;
+/// use rabu::units::Seconds;
+///
+/// assert_eq!(secs!(1.5), Seconds::from(1.5));
+/// ```
+#[macro_export]
+macro_rules! secs {
+    ($value: expr) => {
+        $crate::units::Seconds::new($value as f64)
+    };
+}
+
+/// Creates a number of `Samples`:
+/// ```
+/// use rabu::samples;
+/// use rabu::units::Samples;
+///
+/// assert_eq!(samples!(512), Samples::from(512));
+/// ```
+#[macro_export]
+macro_rules! samples {
+    ($value: expr) => {
+        $crate::units::Samples::new($value as u64)
+    };
+}
+
+/// Creates `Decibels`:
+/// ```
+/// use rabu::db;
+/// use rabu::units::Decibels;
+///
+/// assert_eq!(db!(-6.0), Decibels::from(-6.0));
+/// ```
+#[macro_export]
+macro_rules! db {
+    ($value: expr) => {
+        $crate::units::Decibels::new($value as f64)
+    };
+}
+
+/// Creates a number of `Channels`:
+/// ```
+/// use rabu::chans;
+/// use rabu::units::Channels;
+///
+/// assert_eq!(chans!(2), Channels::from(2));
+/// ```
+#[macro_export]
+macro_rules! chans {
+    ($value: expr) => {
+        $crate::units::Channels::new($value as u32)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::units::{Channels, Decibels, Frequency, Samples, Seconds};
+
+    const FREQUENCY: Frequency = hz!(440);
+    const SECONDS: Seconds = secs!(0.5);
+    const SAMPLES: Samples = samples!(512);
+    const DECIBELS: Decibels = db!(-6);
+    const CHANNELS: Channels = chans!(2);
+
+    #[test]
+    fn expand_to_the_units_in_constants() {
+        assert_eq!(FREQUENCY, Frequency::from(440.0));
+        assert_eq!(SECONDS, Seconds::from(0.5));
+        assert_eq!(SAMPLES, Samples::from(512));
+        assert_eq!(DECIBELS, Decibels::from(-6.0));
+        assert_eq!(CHANNELS, Channels::from(2));
+    }
+
+    #[test]
+    fn take_expressions() {
+        let length = 256;
+
+        assert_eq!(samples!(length * 2), Samples::from(512));
+        assert_eq!(hz!(2.0 * 220.0), Frequency::from(440.0));
+    }
+}
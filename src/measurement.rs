@@ -0,0 +1,243 @@
+//! This module contains measurements for testing audio hardware and DSP chains with a sine:
+//! total harmonic distortion (THD), THD plus noise (THD+N) and the signal-to-noise ratio (SNR).
+//! The fundamental, its harmonics and the DC offset are fitted to the recording together with
+//! least squares, so the frequency doesn't have to fit a whole number of periods in the buffer,
+//! and no window smears the harmonics into the noise. Whatever the fit leaves over is noise.
+//! The harmonics up to the 10th are counted, as far as they are below the Nyquist frequency.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::measurement::measure_distortion;
+//! use rabu::units::{Channels, Frequency, SampleRate, Samples};
+//!
+//! // A 1 kHz sine with 1% of second harmonic.
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(1), Samples::from(4800));
+//! for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+//!     let phase = 2.0 * std::f32::consts::PI * 1000.0 * n as f32 / 48000.0;
+//!     *sample = 0.5 * phase.sin() + 0.005 * (2.0 * phase).sin();
+//! }
+//!
+//! let measurement = measure_distortion(&buffer, 0, SampleRate::from(48000), Frequency::from(1000.0));
+//!
+//! assert!((measurement.thd.as_f64() + 40.0).abs() < 0.01);
+//! assert!(measurement.thd_n >= measurement.thd);
+//! ```
+
+use std::f64::consts::PI;
+
+use crate::buffer::Buffer;
+use crate::dynamics::SILENCE_DB;
+use crate::sample::Sample;
+use crate::units::{Decibels, Frequency, SampleRate};
+
+/// The highest harmonic that counts as distortion.
+const MAX_HARMONIC: usize = 10;
+
+/// The outcome of a distortion measurement. The ratios are relative to the fundamental.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DistortionMeasurement {
+    /// The peak level of the fundamental, in dBFS.
+    pub fundamental: Decibels,
+    /// The power of the harmonics relative to the fundamental.
+    pub thd: Decibels,
+    /// The power of everything but the fundamental and DC, relative to the fundamental.
+    pub thd_n: Decibels,
+    /// The power of the fundamental relative to everything but the fundamental, its harmonics
+    /// and DC.
+    pub snr: Decibels,
+}
+
+/// Measures the distortion and noise of a channel of the buffer, which holds a recording of a
+/// sine at the frequency. Leave out the start of the recording if the device or the DSP
+/// needs time to settle.
+/// This will panic if the channel doesn't exist, or if the frequency isn't between 0 and the
+/// Nyquist frequency.
+pub fn measure_distortion<T: Sample>(
+    buffer: &Buffer<T>,
+    channel: usize,
+    sample_rate: SampleRate,
+    frequency: Frequency,
+) -> DistortionMeasurement {
+    let nyquist = sample_rate.as_f64() / 2.0;
+    assert!(
+        frequency.as_f64() > 0.0 && frequency.as_f64() < nyquist,
+        "the frequency must be between 0 and the Nyquist frequency"
+    );
+    let samples = buffer.chan(channel);
+    let num_harmonics = ((nyquist / frequency.as_f64()).ceil() as usize - 1).clamp(1, MAX_HARMONIC);
+    let step = 2.0 * PI * frequency.as_f64() / sample_rate.as_f64();
+
+    let amplitudes = fit(samples, step, num_harmonics);
+    let power = |harmonic: usize| {
+        let (cosine, sine) = (amplitudes[2 * harmonic - 1], amplitudes[2 * harmonic]);
+        (cosine * cosine + sine * sine) / 2.0
+    };
+    let fundamental_power = power(1);
+    let harmonic_power: f64 = (2..=num_harmonics).map(power).sum();
+
+    let mut basis = vec![0.0; 2 * num_harmonics + 1];
+    let noise_power = samples
+        .iter()
+        .enumerate()
+        .map(|(n, sample)| {
+            fill_basis(&mut basis, step * n as f64);
+            let fitted: f64 = basis.iter().zip(&amplitudes).map(|(b, a)| b * a).sum();
+            (sample.to_f64() - fitted).powi(2)
+        })
+        .sum::<f64>()
+        / samples.len() as f64;
+
+    DistortionMeasurement {
+        fundamental: Decibels::from(power_ratio(2.0 * fundamental_power, 1.0)),
+        thd: Decibels::from(power_ratio(harmonic_power, fundamental_power)),
+        thd_n: Decibels::from(power_ratio(harmonic_power + noise_power, fundamental_power)),
+        snr: Decibels::from(-power_ratio(noise_power, fundamental_power)),
+    }
+}
+
+/// Returns the ratio of the powers in decibels, kept within the range of `SILENCE_DB`.
+fn power_ratio(power: f64, reference: f64) -> f64 {
+    if power <= 0.0 {
+        return SILENCE_DB;
+    }
+    (10.0 * (power / reference).log10()).clamp(SILENCE_DB, -SILENCE_DB)
+}
+
+/// Fills the basis with DC, followed by the cosine and sine of every harmonic at the phase.
+fn fill_basis(basis: &mut [f64], phase: f64) {
+    let (sine, cosine) = phase.sin_cos();
+    let (mut harmonic_cosine, mut harmonic_sine) = (1.0, 0.0);
+    basis[0] = 1.0;
+    for pair in basis[1..].chunks_exact_mut(2) {
+        // Turns the phase one step further, with the angle sum formulas.
+        (harmonic_cosine, harmonic_sine) = (
+            harmonic_cosine * cosine - harmonic_sine * sine,
+            harmonic_sine * cosine + harmonic_cosine * sine,
+        );
+        pair[0] = harmonic_cosine;
+        pair[1] = harmonic_sine;
+    }
+}
+
+/// Returns the amplitudes of the basis functions that fit the samples best, by solving the
+/// normal equations of the least squares problem.
+fn fit<T: Sample>(samples: &[T], step: f64, num_harmonics: usize) -> Vec<f64> {
+    let size = 2 * num_harmonics + 1;
+    let mut matrix = vec![vec![0.0; size]; size];
+    let mut right = vec![0.0; size];
+    let mut basis = vec![0.0; size];
+
+    for (n, sample) in samples.iter().enumerate() {
+        fill_basis(&mut basis, step * n as f64);
+        for ((row, right), matrix_row) in right.iter_mut().enumerate().zip(matrix.iter_mut()) {
+            *right += basis[row] * sample.to_f64();
+            for (value, column) in matrix_row.iter_mut().zip(basis.iter()) {
+                *value += basis[row] * column;
+            }
+        }
+    }
+    solve(matrix, right)
+}
+
+/// Solves the system of linear equations with Gaussian elimination and partial pivoting.
+/// Unknowns without an equation that determines them (e.g. when the buffer is too short to
+/// tell harmonics apart) are left at 0.
+fn solve(mut matrix: Vec<Vec<f64>>, mut right: Vec<f64>) -> Vec<f64> {
+    let size = right.len();
+    let scale = matrix
+        .iter()
+        .flatten()
+        .fold(0.0_f64, |max, value| max.max(value.abs()));
+    let singular = scale * 1e-12;
+
+    for column in 0..size {
+        let pivot = (column..size)
+            .max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))
+            .unwrap();
+        matrix.swap(column, pivot);
+        right.swap(column, pivot);
+        if matrix[column][column].abs() <= singular {
+            continue;
+        }
+        let (upper, lower) = matrix.split_at_mut(column + 1);
+        let pivot_row = &upper[column];
+        for (row, lower_row) in lower.iter_mut().enumerate() {
+            let factor = lower_row[column] / pivot_row[column];
+            for (value, pivot) in lower_row[column..].iter_mut().zip(&pivot_row[column..]) {
+                *value -= factor * pivot;
+            }
+            right[column + 1 + row] -= factor * right[column];
+        }
+    }
+
+    let mut solution = vec![0.0; size];
+    for row in (0..size).rev() {
+        if matrix[row][row].abs() <= singular {
+            continue;
+        }
+        let known: f64 = (row + 1..size).map(|k| matrix[row][k] * solution[k]).sum();
+        solution[row] = (right[row] - known) / matrix[row][row];
+    }
+    solution
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::Random;
+    use crate::units::{Channels, Samples};
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn recording(signal: impl FnMut(f64) -> f64) -> Buffer<f64> {
+        let mut signal = signal;
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(4000));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            // 997 Hz is a common test frequency, because it doesn't fit the buffer evenly.
+            *sample = signal(2.0 * PI * 997.0 * n as f64 / SAMPLE_RATE as f64);
+        }
+        buffer
+    }
+
+    fn measure(buffer: &Buffer<f64>) -> DistortionMeasurement {
+        measure_distortion(
+            buffer,
+            0,
+            SampleRate::from(SAMPLE_RATE),
+            Frequency::from(997.0),
+        )
+    }
+
+    #[test]
+    fn clean_sine_has_no_distortion() {
+        let measurement = measure(&recording(|phase| 0.5 * (phase + 0.3).sin() + 0.1));
+
+        assert!((measurement.fundamental.as_f64() + 6.0206).abs() < 1e-3);
+        assert!(measurement.thd.as_f64() < -150.0);
+        assert!(measurement.snr.as_f64() > 150.0);
+    }
+
+    #[test]
+    fn measures_the_harmonics() {
+        // Harmonics at -40 and -50 dB sum to -39.59 dB.
+        let measurement = measure(&recording(|phase| {
+            phase.sin() + 0.01 * (2.0 * phase).cos() + 0.003_162_277_66 * (3.0 * phase).sin()
+        }));
+
+        assert!((measurement.thd.as_f64() + 39.586).abs() < 0.01);
+        assert!((measurement.thd_n.as_f64() - measurement.thd.as_f64()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn measures_the_noise() {
+        // Uniform noise from -a to a has a power of a² / 3, here 60 dB below the sine.
+        let mut random = Random::new(3);
+        let amplitude = (3.0_f64 * 0.5 * 1e-6).sqrt();
+        let measurement = measure(&recording(|phase| {
+            phase.sin() + amplitude * random.next_bipolar()
+        }));
+
+        assert!((measurement.snr.as_f64() - 60.0).abs() < 0.3);
+        assert!((measurement.thd_n.as_f64() + 60.0).abs() < 0.3);
+        assert!(measurement.thd.as_f64() < -75.0);
+    }
+}
@@ -0,0 +1,385 @@
+//! This module contains feature extraction on the mel scale: mel spectrograms and mel-frequency
+//! cepstral coefficients (MFCCs), which are the usual inputs for speech and music models.
+//! The mel scale follows the HTK formula, and the triangular filters are normalized to unit
+//! peak. Multichannel audio is mixed down to mono before analysis.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::mel::MelAnalyzer;
+//! use rabu::units::{Channels, SampleRate, Samples};
+//! use rabu::windows::Window;
+//!
+//! let mut analyzer = MelAnalyzer::new(
+//!     SampleRate::from(16000),
+//!     Samples::from(512),
+//!     Samples::from(160),
+//!     Window::Hann,
+//!     40,
+//! );
+//!
+//! let audio = Buffer::<f32>::allocate(Channels::from(1), Samples::from(16000));
+//! let spectrogram = analyzer.mel_spectrogram(&audio);
+//! let mfccs = analyzer.mfcc(&audio, 13);
+//!
+//! assert_eq!(spectrogram.len(), 100);
+//! assert_eq!(spectrogram[0].len(), 40);
+//! assert_eq!(mfccs[0].len(), 13);
+//! ```
+
+use std::f64::consts::PI;
+
+use crate::buffer::Buffer;
+use crate::fft::{Complex, RealFft};
+use crate::sample::Sample;
+use crate::units::{Frequency, FrequencyBin, SampleRate, Samples};
+use crate::windows::Window;
+
+/// The smallest energy that is passed to the logarithm of the MFCC, to keep silence finite.
+const MINIMUM_ENERGY: f64 = 1e-10;
+
+/// Converts a frequency to mels.
+pub fn frequency_to_mel(frequency: Frequency) -> f64 {
+    2595.0 * (1.0 + frequency.as_f64() / 700.0).log10()
+}
+
+/// Converts mels to a frequency.
+pub fn mel_to_frequency(mel: f64) -> Frequency {
+    Frequency::from(700.0 * (10.0_f64.powf(mel / 2595.0) - 1.0))
+}
+
+/// A bank of triangular filters that are evenly spaced on the mel scale, which turns a power
+/// spectrum into mel band energies.
+#[derive(Clone, Debug)]
+pub struct MelFilterBank {
+    filters: Vec<MelFilter>,
+    num_bins: usize,
+}
+
+#[derive(Clone, Debug)]
+struct MelFilter {
+    first_bin: usize,
+    weights: Vec<f64>,
+}
+
+impl MelFilterBank {
+    /// Creates a filter bank for the spectra of an FFT of the given size, with `num_bands`
+    /// bands that together span the range from `lowest` to `highest`.
+    /// This will panic if there are no bands or if `highest` isn't above `lowest`.
+    pub fn new(
+        sample_rate: SampleRate,
+        fft_size: Samples,
+        num_bands: usize,
+        lowest: Frequency,
+        highest: Frequency,
+    ) -> Self {
+        assert!(num_bands > 0);
+        assert!(highest.as_f64() > lowest.as_f64());
+
+        let num_bins = fft_size.as_usize() / 2 + 1;
+        let lowest_mel = frequency_to_mel(lowest);
+        let mel_step = (frequency_to_mel(highest) - lowest_mel) / (num_bands + 1) as f64;
+        let edges: Vec<f64> = (0..num_bands + 2)
+            .map(|index| mel_to_frequency(lowest_mel + index as f64 * mel_step).as_f64())
+            .collect();
+
+        let filters = edges
+            .windows(3)
+            .map(|edges| {
+                let (low, center, high) = (edges[0], edges[1], edges[2]);
+                let bins: Vec<(usize, f64)> = (0..num_bins)
+                    .map(|bin| {
+                        let frequency = FrequencyBin::from(bin)
+                            .to_frequency(sample_rate, fft_size)
+                            .as_f64();
+                        let weight = if frequency <= center {
+                            (frequency - low) / (center - low)
+                        } else {
+                            (high - frequency) / (high - center)
+                        };
+                        (bin, weight)
+                    })
+                    .filter(|(_, weight)| *weight > 0.0)
+                    .collect();
+
+                MelFilter {
+                    first_bin: bins.first().map_or(0, |(bin, _)| *bin),
+                    weights: bins.into_iter().map(|(_, weight)| weight).collect(),
+                }
+            })
+            .collect();
+
+        Self { filters, num_bins }
+    }
+
+    /// Returns the number of mel bands.
+    pub fn num_bands(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Computes the energy of every mel band from the given power spectrum.
+    /// This will panic if the power spectrum doesn't have one value per FFT bin, or if the
+    /// output doesn't have one value per band.
+    pub fn apply(&self, power_spectrum: &[f64], output: &mut [f64]) {
+        assert_eq!(power_spectrum.len(), self.num_bins);
+        assert_eq!(output.len(), self.filters.len());
+
+        for (energy, filter) in output.iter_mut().zip(&self.filters) {
+            *energy = power_spectrum[filter.first_bin..]
+                .iter()
+                .zip(&filter.weights)
+                .map(|(power, weight)| power * weight)
+                .sum();
+        }
+    }
+}
+
+/// Computes mel spectrograms and MFCCs from audio, frame by frame.
+#[derive(Clone, Debug)]
+pub struct MelAnalyzer {
+    fft: RealFft,
+    window: Vec<f64>,
+    hop_size: usize,
+    filter_bank: MelFilterBank,
+    frame: Vec<f64>,
+    bins: Vec<Complex>,
+    power: Vec<f64>,
+}
+
+impl MelAnalyzer {
+    /// Creates a new analyzer with `num_bands` mel bands between 0 Hz and Nyquist. A frame of
+    /// `fft_size` samples is analyzed every `hop_size` samples.
+    /// This will panic if the FFT size is not a power of two, or if the hop size is zero.
+    pub fn new(
+        sample_rate: SampleRate,
+        fft_size: Samples,
+        hop_size: Samples,
+        window: Window,
+        num_bands: usize,
+    ) -> Self {
+        let nyquist = Frequency::from(sample_rate.as_f64() / 2.0);
+        let filter_bank = MelFilterBank::new(
+            sample_rate,
+            fft_size,
+            num_bands,
+            Frequency::from(0.0),
+            nyquist,
+        );
+        Self::with_filter_bank(fft_size, hop_size, window, filter_bank)
+    }
+
+    /// Creates a new analyzer like `new`, but with a custom filter bank, e.g. to limit the
+    /// frequency range. The filter bank must be made for the same FFT size.
+    pub fn with_filter_bank(
+        fft_size: Samples,
+        hop_size: Samples,
+        window: Window,
+        filter_bank: MelFilterBank,
+    ) -> Self {
+        assert!(hop_size.as_usize() > 0, "hop size must be at least one");
+        let size = fft_size.as_usize();
+        let fft = RealFft::new(fft_size);
+        let num_bins = fft.num_bins().as_usize();
+        let mut window_values = vec![0.0; size];
+        window.fill_periodic(&mut window_values);
+
+        Self {
+            fft,
+            window: window_values,
+            hop_size: hop_size.as_usize(),
+            filter_bank,
+            frame: vec![0.0; size],
+            bins: vec![Complex::default(); num_bins],
+            power: vec![0.0; num_bins],
+        }
+    }
+
+    /// Returns the number of mel bands.
+    pub fn num_bands(&self) -> usize {
+        self.filter_bank.num_bands()
+    }
+
+    /// Returns the number of frames that the given number of samples results in.
+    /// Frames start at every multiple of the hop size, and the last frames are zero padded.
+    pub fn num_frames(&self, num_samples: Samples) -> usize {
+        num_samples.as_usize().div_ceil(self.hop_size)
+    }
+
+    /// Computes the mel spectrogram of the buffer: the energy of every mel band, per frame.
+    pub fn mel_spectrogram<T: Sample>(&mut self, buffer: &Buffer<T>) -> Vec<Vec<f64>> {
+        let num_frames = self.num_frames(buffer.num_samples());
+        (0..num_frames)
+            .map(|frame| {
+                let mut energies = vec![0.0; self.num_bands()];
+                self.analyze_frame(buffer, frame * self.hop_size, &mut energies);
+                energies
+            })
+            .collect()
+    }
+
+    /// Computes the first `num_coefficients` MFCCs of every frame of the buffer, using the
+    /// orthonormal DCT-II of the natural logarithm of the mel band energies.
+    /// This will panic if more coefficients are requested than there are mel bands.
+    pub fn mfcc<T: Sample>(
+        &mut self,
+        buffer: &Buffer<T>,
+        num_coefficients: usize,
+    ) -> Vec<Vec<f64>> {
+        let num_bands = self.num_bands();
+        assert!(num_coefficients <= num_bands);
+
+        self.mel_spectrogram(buffer)
+            .into_iter()
+            .map(|energies| {
+                let log_energies: Vec<f64> = energies
+                    .iter()
+                    .map(|energy| energy.max(MINIMUM_ENERGY).ln())
+                    .collect();
+                (0..num_coefficients)
+                    .map(|k| dct_coefficient(&log_energies, k))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn analyze_frame<T: Sample>(&mut self, buffer: &Buffer<T>, start: usize, output: &mut [f64]) {
+        let num_channels = buffer.num_channels().as_usize() as f64;
+        let num_samples = buffer.num_samples().as_usize();
+
+        for (index, (value, w)) in self.frame.iter_mut().zip(&self.window).enumerate() {
+            let position = start + index;
+            let mixed: f64 = if position < num_samples {
+                buffer
+                    .iter_chans()
+                    .map(|chan| chan[position].to_f64())
+                    .sum()
+            } else {
+                0.0
+            };
+            *value = mixed / num_channels * w;
+        }
+
+        self.fft.forward(&self.frame, &mut self.bins);
+        for (power, bin) in self.power.iter_mut().zip(&self.bins) {
+            *power = bin.norm_sqr();
+        }
+        self.filter_bank.apply(&self.power, output);
+    }
+}
+
+/// Computes coefficient `k` of the orthonormal DCT-II of the input.
+fn dct_coefficient(input: &[f64], k: usize) -> f64 {
+    let n = input.len() as f64;
+    let sum: f64 = input
+        .iter()
+        .enumerate()
+        .map(|(i, x)| x * (PI * k as f64 * (i as f64 + 0.5) / n).cos())
+        .sum();
+    let scale = if k == 0 {
+        (1.0 / n).sqrt()
+    } else {
+        (2.0 / n).sqrt()
+    };
+    sum * scale
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Channels;
+
+    #[test_case(0.0 => 0.0; "zero")]
+    #[test_case(700.0 => 781.0; "seven hundred")]
+    #[test_case(1000.0 => 1000.0; "one kilohertz")]
+    fn mel_scale(frequency: f64) -> f64 {
+        let mel = frequency_to_mel(Frequency::from(frequency));
+        assert!((mel_to_frequency(mel).as_f64() - frequency).abs() < 1e-9);
+        mel.round()
+    }
+
+    fn analyzer() -> MelAnalyzer {
+        MelAnalyzer::new(
+            SampleRate::from(16000),
+            Samples::from(512),
+            Samples::from(256),
+            Window::Hann,
+            24,
+        )
+    }
+
+    fn sine(frequency: f64, channels: usize) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(channels), Samples::from(4096));
+        for chan in buffer.iter_chans_mut() {
+            for (n, sample) in chan.iter_mut().enumerate() {
+                *sample = (2.0 * PI * frequency * n as f64 / 16000.0).sin();
+            }
+        }
+        buffer
+    }
+
+    fn loudest_band(energies: &[f64]) -> usize {
+        (0..energies.len())
+            .max_by(|a, b| energies[*a].partial_cmp(&energies[*b]).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn wider_bands_collect_more_energy_from_a_flat_spectrum() {
+        let bank = MelFilterBank::new(
+            SampleRate::from(16000),
+            Samples::from(512),
+            10,
+            Frequency::from(0.0),
+            Frequency::from(8000.0),
+        );
+        let mut energies = vec![0.0; 10];
+
+        // A flat power spectrum: every band sees the sum of its own weights.
+        bank.apply(&[1.0; 257], &mut energies);
+
+        assert!(energies.windows(2).all(|pair| pair[1] > pair[0]));
+    }
+
+    #[test]
+    fn higher_frequencies_land_in_higher_bands() {
+        let mut analyzer = analyzer();
+
+        let low = loudest_band(&analyzer.mel_spectrogram(&sine(300.0, 1))[4]);
+        let high = loudest_band(&analyzer.mel_spectrogram(&sine(3000.0, 1))[4]);
+
+        assert!(high > low);
+    }
+
+    #[test]
+    fn channels_are_mixed_down() {
+        let mut analyzer = analyzer();
+
+        let mono = analyzer.mel_spectrogram(&sine(1000.0, 1));
+        let stereo = analyzer.mel_spectrogram(&sine(1000.0, 2));
+
+        assert_eq!(mono, stereo);
+    }
+
+    #[test]
+    fn frames_cover_all_samples() {
+        let analyzer = analyzer();
+
+        assert_eq!(analyzer.num_frames(Samples::from(0)), 0);
+        assert_eq!(analyzer.num_frames(Samples::from(256)), 1);
+        assert_eq!(analyzer.num_frames(Samples::from(257)), 2);
+    }
+
+    #[test]
+    fn mfccs_of_silence_only_have_energy_in_the_first_coefficient() {
+        let mut analyzer = analyzer();
+        let silence = Buffer::<f32>::allocate(Channels::from(1), Samples::from(1024));
+
+        let mfccs = analyzer.mfcc(&silence, 13);
+
+        assert_eq!(mfccs.len(), 4);
+        for frame in mfccs {
+            assert!((frame[0] - MINIMUM_ENERGY.ln() * 24.0_f64.sqrt()).abs() < 1e-9);
+            assert!(frame[1..].iter().all(|c| c.abs() < 1e-9));
+        }
+    }
+}
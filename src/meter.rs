@@ -0,0 +1,420 @@
+//! This module contains level meters. An RMS meter shows the average power over a window,
+//! which is much closer to how loud something sounds than its peaks are. A true peak meter
+//! shows the peaks of the actual waveform, which can lie between the samples and go above
+//! the highest sample, and clip a DAC or a lossy encoder. A VU meter moves like the needle of
+//! an analog VU meter, which is slow enough to show the average level that matters for gain
+//! staging.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::meter::{RmsMeter, TruePeakMeter, VuMeter};
+//! use rabu::units::{Channels, SampleRate, Samples, Seconds};
+//!
+//! let mut meter = RmsMeter::new(Channels::from(2), Seconds::from(0.3), SampleRate::from(48000));
+//! let mut peaks = TruePeakMeter::for_sample_rate(Channels::from(2), SampleRate::from(48000));
+//! let mut vu = VuMeter::new(Channels::from(2), SampleRate::from(48000));
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(14400));
+//! buffer.map_samples(|_| 0.5);
+//! meter.process(&buffer);
+//! peaks.process(&buffer);
+//! vu.process(&buffer);
+//!
+//! assert!((meter.level(0).as_f64() + 6.02).abs() < 0.01);
+//! assert!(peaks.max_true_peak().as_f64() > -6.03);
+//! assert!(vu.vu(0).as_f64() > 0.0);
+//! ```
+
+use std::f64::consts::PI;
+
+use crate::buffer::Buffer;
+use crate::dynamics::level_to_db;
+use crate::oversampling::Oversampler;
+use crate::sample::Sample;
+use crate::units::{Channels, Decibels, SampleRate, Samples, Seconds};
+
+/// Measures the RMS level of every channel over a sliding window.
+#[derive(Clone, Debug)]
+pub struct RmsMeter {
+    /// The squares of the samples in the window, per channel.
+    squares: Vec<Vec<f64>>,
+    sums: Vec<f64>,
+    position: usize,
+}
+
+impl RmsMeter {
+    /// Creates a new meter with the given window, e.g. 300 ms. The window is at least one
+    /// sample long.
+    pub fn new(num_channels: Channels, window: Seconds, sample_rate: SampleRate) -> Self {
+        let length = window.to_samples(sample_rate).as_usize().max(1);
+        Self {
+            squares: vec![vec![0.0; length]; num_channels.as_usize()],
+            sums: vec![0.0; num_channels.as_usize()],
+            position: 0,
+        }
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.squares.len())
+    }
+
+    /// Returns the RMS level of the channel over the window.
+    /// This will panic if the channel doesn't exist.
+    pub fn level(&self, channel: usize) -> Decibels {
+        Decibels::from(level_to_db(self.rms(channel)))
+    }
+
+    /// Returns the RMS level of every channel over the window.
+    pub fn levels(&self) -> Vec<Decibels> {
+        (0..self.squares.len())
+            .map(|channel| self.level(channel))
+            .collect()
+    }
+
+    /// Returns the linear RMS value of the channel over the window.
+    /// This will panic if the channel doesn't exist.
+    pub fn rms(&self, channel: usize) -> f64 {
+        (self.sums[channel].max(0.0) / self.squares[channel].len() as f64).sqrt()
+    }
+
+    /// Empties the window.
+    pub fn reset(&mut self) {
+        for squares in self.squares.iter_mut() {
+            squares.fill(0.0);
+        }
+        self.sums.fill(0.0);
+        self.position = 0;
+    }
+
+    /// Adds the buffer to the window.
+    /// This will panic if the buffer doesn't have the number of channels the meter was made
+    /// for.
+    pub fn process<T: Sample>(&mut self, buffer: &Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+        let length = self.squares.first().map_or(1, Vec::len);
+
+        for index in buffer.sample_indices() {
+            for (channel, (squares, sum)) in self
+                .squares
+                .iter_mut()
+                .zip(self.sums.iter_mut())
+                .enumerate()
+            {
+                let square = buffer.chan(channel)[index].to_f64().powi(2);
+                *sum += square - squares[self.position];
+                squares[self.position] = square;
+            }
+            self.position += 1;
+            if self.position == length {
+                self.position = 0;
+                // The running sums slowly pick up rounding errors, so they start over once
+                // every window.
+                for (squares, sum) in self.squares.iter().zip(self.sums.iter_mut()) {
+                    *sum = squares.iter().sum();
+                }
+            }
+        }
+    }
+}
+
+/// Measures the true peak of every channel after ITU BS.1770-4, by oversampling the audio and
+/// taking the peak of the oversampled waveform. The peaks are held until a reset.
+#[derive(Clone, Debug)]
+pub struct TruePeakMeter {
+    oversamplers: Vec<Oversampler>,
+    peaks: Vec<f64>,
+}
+
+impl TruePeakMeter {
+    /// Creates a new meter that oversamples by the factor. BS.1770 asks for at least 4 times
+    /// at 48 kHz; 8 times reads a bit more precisely.
+    /// This will panic if the factor is 0.
+    pub fn new(num_channels: Channels, oversampling: usize) -> Self {
+        Self {
+            oversamplers: vec![Oversampler::new(oversampling); num_channels.as_usize()],
+            peaks: vec![0.0; num_channels.as_usize()],
+        }
+    }
+
+    /// Creates a new meter that oversamples enough for the sample rate: 4 times below 96 kHz,
+    /// 2 times below 192 kHz and not at all above.
+    pub fn for_sample_rate(num_channels: Channels, sample_rate: SampleRate) -> Self {
+        Self::new(num_channels, true_peak_oversampling(sample_rate))
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.peaks.len())
+    }
+
+    /// Returns how many samples the peaks lag behind the audio.
+    pub fn latency_samples(&self) -> Samples {
+        let oversampler = self.oversamplers.first();
+        Samples::from(oversampler.map_or(0, |o| o.latency_samples().as_usize() / 2))
+    }
+
+    /// Returns the highest true peak of the channel in dBTP.
+    /// This will panic if the channel doesn't exist.
+    pub fn true_peak(&self, channel: usize) -> Decibels {
+        Decibels::from(level_to_db(self.peaks[channel]))
+    }
+
+    /// Returns the highest true peak of all channels in dBTP.
+    pub fn max_true_peak(&self) -> Decibels {
+        Decibels::from(level_to_db(self.peaks.iter().copied().fold(0.0, f64::max)))
+    }
+
+    /// Clears the held peaks and the oversampling filters.
+    pub fn reset(&mut self) {
+        self.peaks.fill(0.0);
+        self.oversamplers.iter_mut().for_each(Oversampler::reset);
+    }
+
+    /// Measures the buffer.
+    /// This will panic if the buffer doesn't have the number of channels the meter was made
+    /// for.
+    pub fn process<T: Sample>(&mut self, buffer: &Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+        for (channel, samples) in buffer.iter_chans().enumerate() {
+            for sample in samples {
+                self.next_peak(channel, sample.to_f64());
+            }
+        }
+    }
+
+    /// Measures one sample of the channel, and returns the linear peak of the oversampled
+    /// waveform around it, `latency_samples` ago. The sample itself counts as well, so the
+    /// peak is never lower than the sample peak.
+    pub(crate) fn next_peak(&mut self, channel: usize, sample: f64) -> f64 {
+        let mut peak = sample.abs();
+        self.oversamplers[channel].upsample(sample, |oversampled| {
+            peak = peak.max(oversampled.abs());
+        });
+        self.peaks[channel] = self.peaks[channel].max(peak);
+        peak
+    }
+}
+
+/// Returns how many times true peak detection oversamples at the sample rate: 4 times below
+/// 96 kHz, 2 times below 192 kHz and not at all above.
+pub(crate) fn true_peak_oversampling(sample_rate: SampleRate) -> usize {
+    match sample_rate.as_f64() {
+        rate if rate < 96000.0 => 4,
+        rate if rate < 192000.0 => 2,
+        _ => 1,
+    }
+}
+
+/// Emulates the needle of a VU meter: it shows the average of the rectified signal, with a
+/// critically damped movement that reaches 99% of a step in 300 ms. The reading is scaled
+/// so a sine reads its peak level, relative to the reference level.
+#[derive(Clone, Debug)]
+pub struct VuMeter {
+    reference: Decibels,
+    coefficient: f64,
+    /// The two one-pole stages of every channel.
+    stages: Vec<(f64, f64)>,
+}
+
+impl VuMeter {
+    /// The time it takes the needle to reach 99% of a step.
+    const INTEGRATION_TIME: f64 = 0.3;
+    /// Two one-poles reach 99% of a step after this many time constants.
+    const TIME_CONSTANTS_TO_99_PERCENT: f64 = 6.638;
+
+    /// Creates a new meter where a sine with a peak level of -18 dBFS reads 0 VU, which is
+    /// the EBU alignment for +4 dBu.
+    pub fn new(num_channels: Channels, sample_rate: SampleRate) -> Self {
+        let time_constant = Self::INTEGRATION_TIME / Self::TIME_CONSTANTS_TO_99_PERCENT;
+        Self {
+            reference: Decibels::from(-18.0),
+            coefficient: (-1.0 / (time_constant * sample_rate.as_f64())).exp(),
+            stages: vec![(0.0, 0.0); num_channels.as_usize()],
+        }
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.stages.len())
+    }
+
+    /// Returns the level that reads 0 VU.
+    pub fn reference(&self) -> Decibels {
+        self.reference
+    }
+
+    /// Changes the peak level of a sine that reads 0 VU, e.g. -20 dBFS for the SMPTE
+    /// alignment.
+    pub fn set_reference(&mut self, reference: Decibels) {
+        self.reference = reference;
+    }
+
+    /// Returns where the needle of the channel is, in VU.
+    /// This will panic if the channel doesn't exist.
+    pub fn vu(&self, channel: usize) -> Decibels {
+        // A sine averages to 2 / pi of its peak after rectifying.
+        let level = self.stages[channel].1 * PI / 2.0;
+        Decibels::from(level_to_db(level)) - self.reference
+    }
+
+    /// Returns where the needles of all channels are, in VU.
+    pub fn vus(&self) -> Vec<Decibels> {
+        (0..self.stages.len())
+            .map(|channel| self.vu(channel))
+            .collect()
+    }
+
+    /// Drops the needles to rest.
+    pub fn reset(&mut self) {
+        self.stages.fill((0.0, 0.0));
+    }
+
+    /// Moves the needles with the buffer.
+    /// This will panic if the buffer doesn't have the number of channels the meter was made
+    /// for.
+    pub fn process<T: Sample>(&mut self, buffer: &Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+        let coefficient = self.coefficient;
+        for (samples, (first, second)) in buffer.iter_chans().zip(self.stages.iter_mut()) {
+            for sample in samples {
+                let rectified = sample.to_f64().abs();
+                *first = rectified + (*first - rectified) * coefficient;
+                *second = *first + (*second - *first) * coefficient;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::fades::{fade_in, FadeCurve};
+
+    fn meter() -> RmsMeter {
+        RmsMeter::new(
+            Channels::from(1),
+            Seconds::from(0.1),
+            SampleRate::from(1000),
+        )
+    }
+
+    fn buffer(samples: impl Fn(usize) -> f64, length: usize) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(length));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = samples(n);
+        }
+        buffer
+    }
+
+    #[test]
+    fn sine_is_3_db_below_its_peak() {
+        let mut meter = meter();
+        meter.process(&buffer(
+            |n| (n as f64 * 0.2 * std::f64::consts::PI).sin(),
+            1000,
+        ));
+
+        assert!((meter.level(0).as_f64() + 3.01).abs() < 0.01);
+    }
+
+    #[test]
+    fn window_slides() {
+        let mut meter = meter();
+        meter.process(&buffer(|_| 1.0, 150));
+        assert!((meter.rms(0) - 1.0).abs() < 1e-12);
+
+        // Half of the window is silent now.
+        meter.process(&buffer(|_| 0.0, 50));
+        assert!((meter.rms(0) - 0.5_f64.sqrt()).abs() < 1e-12);
+
+        meter.process(&buffer(|_| 0.0, 50));
+        assert_eq!(meter.rms(0), 0.0);
+    }
+
+    #[test]
+    fn silence_has_a_floor() {
+        let meter = meter();
+        assert_eq!(meter.levels(), vec![Decibels::from(-200.0)]);
+    }
+
+    #[test]
+    fn processing_in_blocks_is_the_same_as_at_once() {
+        let signal = |n| (n as f64 * 0.37).sin();
+        let mut once = meter();
+        let mut blocks = meter();
+
+        once.process(&buffer(signal, 250));
+        for start in (0..250).step_by(10) {
+            blocks.process(&buffer(|n| signal(start + n), 10));
+        }
+
+        assert!((once.rms(0) - blocks.rms(0)).abs() < 1e-12);
+    }
+
+    /// Returns a sine at a quarter of the sample rate, with the samples at 45 degrees of
+    /// phase, so they all lie 3 dB below the peaks of the waveform. It fades in, because a
+    /// sudden start would overshoot.
+    fn quarter_rate_sine(length: usize) -> Buffer<f64> {
+        let mut sine = buffer(
+            |n| (std::f64::consts::FRAC_PI_2 * n as f64 + std::f64::consts::FRAC_PI_4).sin(),
+            length,
+        );
+        fade_in(&mut sine, Samples::from(200), FadeCurve::SCurve);
+        sine
+    }
+
+    #[test_case(4; "4x")]
+    #[test_case(8; "8x")]
+    fn true_peak_finds_peaks_between_the_samples(oversampling: usize) {
+        let sine = quarter_rate_sine(1000);
+        let mut meter = TruePeakMeter::new(Channels::from(1), oversampling);
+
+        meter.process(&sine);
+
+        let sample_peak = Decibels::from_gain(sine.chan(0).iter().fold(0.0, |p, s| s.abs().max(p)));
+        assert!((sample_peak.as_f64() + 3.01).abs() < 0.01);
+        assert!(meter.true_peak(0).as_f64().abs() < 0.05);
+    }
+
+    #[test]
+    fn true_peak_is_held_until_reset() {
+        let mut meter = TruePeakMeter::new(Channels::from(1), 4);
+        meter.process(&buffer(|n| if n == 10 { 0.5 } else { 0.0 }, 100));
+        meter.process(&buffer(|_| 0.0, 100));
+        assert!(meter.max_true_peak().as_f64() >= Decibels::from_gain(0.5).as_f64());
+
+        meter.reset();
+        assert_eq!(meter.max_true_peak(), Decibels::from(-200.0));
+    }
+
+    fn vu_meter() -> VuMeter {
+        VuMeter::new(Channels::from(1), SampleRate::from(1000))
+    }
+
+    #[test]
+    fn sine_at_the_reference_reads_zero_vu() {
+        let mut meter = vu_meter();
+        let amplitude = Decibels::from(-18.0).to_gain();
+
+        meter.process(&buffer(|n| amplitude * (n as f64 * 0.7).sin(), 3000));
+
+        assert!(meter.vu(0).as_f64().abs() < 0.2);
+    }
+
+    #[test]
+    fn needle_reaches_99_percent_in_300_ms() {
+        let mut meter = vu_meter();
+        meter.set_reference(Decibels::from(0.0));
+        // A constant level of 2 / pi reads like a full scale sine.
+        let level = 2.0 / std::f64::consts::PI;
+
+        meter.process(&buffer(|_| level, 250));
+        assert!(meter.vu(0).to_gain() < 0.99);
+        meter.process(&buffer(|_| level, 50));
+        assert!((meter.vu(0).to_gain() - 0.99).abs() < 0.001);
+        meter.process(&buffer(|_| level, 1000));
+        assert!(meter.vu(0).as_f64().abs() < 1e-6);
+    }
+}
@@ -0,0 +1,276 @@
+//! This module contains a metronome, which clicks along with a transport. The first beat of
+//! every bar is accented with a higher and louder click, and beats can be subdivided into
+//! softer clicks in between. The clicks follow from the position on the timeline, so they stay
+//! in time when the transport seeks or loops.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::metronome::Metronome;
+//! use rabu::transport::Transport;
+//! use rabu::units::{Channels, SampleRate, Samples, Tempo, TimeSignature};
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let mut metronome = Metronome::new(sample_rate);
+//! metronome.set_tempo(Tempo::from(120.0));
+//! metronome.set_time_signature(TimeSignature::new(3, 4));
+//!
+//! let mut transport = Transport::new(sample_rate);
+//! transport.play();
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! metronome.render(&mut transport, &mut buffer);
+//!
+//! // The first click starts right away.
+//! assert!(buffer.chan(0).iter().any(|sample| sample.abs() > 0.1));
+//! assert_eq!(transport.position(), Samples::from(512));
+//! ```
+
+use std::f64::consts::PI;
+
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::transport::Transport;
+use crate::units::{Decibels, Frequency, SampleRate, Samples, Tempo, TimeSignature};
+
+/// The length of a click.
+const CLICK_LENGTH: f64 = 0.03;
+/// The time constant of the decay of a click.
+const CLICK_DECAY: f64 = 0.006;
+
+/// The kinds of clicks, from loud to soft.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Click {
+    Accent,
+    Beat,
+    Subdivision,
+}
+
+impl Click {
+    fn frequency(&self) -> Frequency {
+        match self {
+            Click::Accent => Frequency::from(1760.0),
+            Click::Beat => Frequency::from(1320.0),
+            Click::Subdivision => Frequency::from(880.0),
+        }
+    }
+
+    fn gain(&self) -> Decibels {
+        match self {
+            Click::Accent => Decibels::from(0.0),
+            Click::Beat => Decibels::from(-6.0),
+            Click::Subdivision => Decibels::from(-14.0),
+        }
+    }
+}
+
+/// Renders clicks on the beats of the timeline.
+#[derive(Clone, Debug)]
+pub struct Metronome {
+    sample_rate: SampleRate,
+    tempo: Tempo,
+    time_signature: TimeSignature,
+    subdivisions: usize,
+    volume: Decibels,
+}
+
+impl Metronome {
+    /// Creates a new metronome at 120 BPM in 4/4, without subdivisions, with the accent at
+    /// -6 dBFS.
+    pub fn new(sample_rate: SampleRate) -> Self {
+        Self {
+            sample_rate,
+            tempo: Tempo::from(120.0),
+            time_signature: TimeSignature::default(),
+            subdivisions: 1,
+            volume: Decibels::from(-6.0),
+        }
+    }
+
+    /// Returns the tempo.
+    pub fn tempo(&self) -> Tempo {
+        self.tempo
+    }
+
+    /// Changes the tempo, in beats of the time signature per minute.
+    pub fn set_tempo(&mut self, tempo: Tempo) {
+        self.tempo = tempo;
+    }
+
+    /// Returns the time signature.
+    pub fn time_signature(&self) -> TimeSignature {
+        self.time_signature
+    }
+
+    /// Changes the time signature, which sets the number of beats between two accents.
+    pub fn set_time_signature(&mut self, time_signature: TimeSignature) {
+        self.time_signature = time_signature;
+    }
+
+    /// Returns the number of clicks per beat.
+    pub fn subdivisions(&self) -> usize {
+        self.subdivisions
+    }
+
+    /// Changes the number of clicks per beat, e.g. 2 for eighth notes in 4/4. The clicks in
+    /// between the beats are softer.
+    /// This will panic if the number of clicks is 0.
+    pub fn set_subdivisions(&mut self, subdivisions: usize) {
+        assert!(subdivisions > 0, "a beat needs at least one click");
+        self.subdivisions = subdivisions;
+    }
+
+    /// Returns the level of the accent.
+    pub fn volume(&self) -> Decibels {
+        self.volume
+    }
+
+    /// Changes the level of the accent. The other clicks are softer by a fixed amount.
+    pub fn set_volume(&mut self, volume: Decibels) {
+        self.volume = volume;
+    }
+
+    /// Changes the sample rate.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Advances the transport by the length of the buffer, and adds the clicks of the regions
+    /// it covers to every channel of the buffer. A stopped transport renders nothing.
+    pub fn render<T: Sample>(&self, transport: &mut Transport, buffer: &mut Buffer<T>) {
+        for region in transport.advance(buffer.num_samples()) {
+            let offset = region.offset.as_usize();
+            for index in 0..region.section.length.as_usize() {
+                let position = region.section.start + Samples::from(index);
+                let click = self.click_at(position);
+                if click == 0.0 {
+                    continue;
+                }
+                for channel in buffer.iter_chans_mut() {
+                    let sample = &mut channel[offset + index];
+                    *sample = T::from_f64(sample.to_f64() + click);
+                }
+            }
+        }
+    }
+
+    /// Returns the click sample at the position of the timeline. The first beat is at the
+    /// start of the timeline, and the first beat of every bar is accented.
+    fn click_at(&self, position: Samples) -> f64 {
+        let click_length = self.tempo.beat_length().as_f64() * self.sample_rate.as_f64()
+            / self.subdivisions as f64;
+        let index = (position.as_f64() / click_length).floor();
+        let age = (position.as_f64() - index * click_length) / self.sample_rate.as_f64();
+        if age >= CLICK_LENGTH {
+            return 0.0;
+        }
+
+        let index = index as u64;
+        let subdivisions = self.subdivisions as u64;
+        let beats_per_bar = self.time_signature.numerator() as u64;
+        let click = if !index.is_multiple_of(subdivisions) {
+            Click::Subdivision
+        } else if (index / subdivisions).is_multiple_of(beats_per_bar) {
+            Click::Accent
+        } else {
+            Click::Beat
+        };
+
+        let gain = (self.volume + click.gain()).to_gain();
+        let fade_out = 1.0 - age / CLICK_LENGTH;
+        gain * fade_out
+            * (-age / CLICK_DECAY).exp()
+            * (2.0 * PI * click.frequency().as_f64() * age).sin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{Channels, Duration, TimePoint, TimeSection};
+
+    const SAMPLE_RATE: u32 = 8000;
+    /// The length of a beat at 600 BPM.
+    const BEAT: usize = 800;
+
+    /// The peak level of the clicks, one per beat.
+    fn click_peaks(metronome: &Metronome, transport: &mut Transport, beats: usize) -> Vec<f64> {
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(1), Samples::from(BEAT * beats));
+        metronome.render(transport, &mut buffer);
+        buffer
+            .chan(0)
+            .chunks(BEAT)
+            .map(|beat| beat.iter().fold(0.0_f64, |peak, s| peak.max(s.abs())))
+            .collect()
+    }
+
+    fn metronome() -> Metronome {
+        let mut metronome = Metronome::new(SampleRate::from(SAMPLE_RATE));
+        // 600 BPM makes a beat 100 ms long.
+        metronome.set_tempo(Tempo::from(600.0));
+        metronome.set_volume(Decibels::from(0.0));
+        metronome
+    }
+
+    fn playing() -> Transport {
+        let mut transport = Transport::new(SampleRate::from(SAMPLE_RATE));
+        transport.play();
+        transport
+    }
+
+    #[test]
+    fn accents_the_first_beat_of_every_bar() {
+        let mut metronome = metronome();
+        metronome.set_time_signature(TimeSignature::new(3, 4));
+
+        let peaks = click_peaks(&metronome, &mut playing(), 6);
+
+        assert!(peaks[0] > peaks[1] && peaks[0] > peaks[2]);
+        assert_eq!(peaks[3], peaks[0]);
+        assert_eq!(peaks[4], peaks[1]);
+    }
+
+    #[test]
+    fn subdivisions_click_softer_in_between() {
+        let mut metronome = metronome();
+        metronome.set_subdivisions(2);
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(1), Samples::from(BEAT));
+
+        metronome.render(&mut playing(), &mut buffer);
+
+        let peak = |range: std::ops::Range<usize>| {
+            buffer.chan(0)[range]
+                .iter()
+                .fold(0.0_f64, |peak, s| peak.max(s.abs()))
+        };
+        assert!(peak(400..800) > 0.0);
+        assert!(peak(400..800) < peak(0..400));
+    }
+
+    #[test]
+    fn stopped_transport_is_silent() {
+        let metronome = metronome();
+        let mut transport = Transport::new(SampleRate::from(SAMPLE_RATE));
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(1), Samples::from(3 * BEAT));
+
+        metronome.render(&mut transport, &mut buffer);
+
+        assert!(buffer.is_default_filled());
+    }
+
+    #[test]
+    fn clicks_follow_the_loop() {
+        let metronome = metronome();
+        let mut transport = playing();
+        // A loop over the second beat keeps playing the click of that beat.
+        transport.set_loop(Some(TimeSection {
+            start: TimePoint::from_secs_f64(0.1),
+            duration: Duration::from_secs_f64(0.1),
+        }));
+        transport.seek(Samples::from(BEAT));
+
+        let peaks = click_peaks(&metronome, &mut transport, 3);
+
+        assert!(peaks[0] > 0.0);
+        assert_eq!(peaks[1], peaks[0]);
+        assert_eq!(peaks[2], peaks[0]);
+    }
+}
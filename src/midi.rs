@@ -0,0 +1,405 @@
+//! This module contains MIDI channel voice messages: notes, controllers, program changes,
+//! pressure and pitch bend, with their raw bytes. A MIDI stream may leave out the status byte
+//! when it's the same as the one of the message before (running status), so the parser keeps
+//! track of the last status byte, and the writer leaves it out where it can.
+//! ```rust
+//! use rabu::midi::{MidiMessage, MidiParser, MidiWriter};
+//! use rabu::units::{MidiNote, Velocity};
+//!
+//! let messages = [
+//!     MidiMessage::NoteOn { channel: 0, note: MidiNote::from(60), velocity: Velocity::from(100) },
+//!     MidiMessage::NoteOn { channel: 0, note: MidiNote::from(64), velocity: Velocity::from(90) },
+//!     MidiMessage::PitchBend { channel: 0, bend: -2048 },
+//! ];
+//!
+//! let mut bytes = Vec::new();
+//! let mut writer = MidiWriter::new();
+//! for message in &messages {
+//!     writer.write(message, &mut bytes);
+//! }
+//! // The second note on leaves out its status byte.
+//! assert_eq!(bytes, [0x90, 60, 100, 64, 90, 0xE0, 0x00, 0x30]);
+//!
+//! let mut parser = MidiParser::new();
+//! let parsed: Vec<_> = parser.parse(&bytes).collect();
+//! assert_eq!(parsed, messages);
+//! ```
+
+use crate::units::{MidiNote, Velocity};
+
+/// A MIDI channel voice message. Channels go from 0 to 15, and the other values from 0 to 127,
+/// except for pitch bend, which goes from -8192 to 8191 with 0 in the middle.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MidiMessage {
+    /// Releases a note.
+    NoteOff {
+        channel: u8,
+        note: MidiNote,
+        velocity: Velocity,
+    },
+    /// Starts a note. A velocity of 0 releases the note instead.
+    NoteOn {
+        channel: u8,
+        note: MidiNote,
+        velocity: Velocity,
+    },
+    /// Changes the pressure on a held note.
+    PolyPressure {
+        channel: u8,
+        note: MidiNote,
+        pressure: u8,
+    },
+    /// Changes the value of a controller.
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    /// Selects a program (patch).
+    ProgramChange { channel: u8, program: u8 },
+    /// Changes the pressure on all held notes of the channel.
+    ChannelPressure { channel: u8, pressure: u8 },
+    /// Bends the pitch of the channel.
+    PitchBend { channel: u8, bend: i16 },
+}
+
+impl MidiMessage {
+    /// Creates the message from its status byte and data bytes, or gives `None` when the status
+    /// byte isn't the one of a channel voice message, or there are too few data bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&status, data) = bytes.split_first()?;
+        let length = data_length(status)?;
+        if data.len() < length || data[..length].iter().any(|&byte| byte > 0x7F) {
+            return None;
+        }
+        Some(Self::from_parts(status, data))
+    }
+
+    /// Returns the raw bytes of the message, with the status byte. This allocates, so on the
+    /// audio thread `encode` is the one to use.
+    /// This will panic if the channel is above 15, or a data value above 127, or the pitch
+    /// bend outside its range.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (bytes, length) = self.encode();
+        bytes[..length].to_vec()
+    }
+
+    /// Returns the raw bytes of the message, with the status byte, without allocating: the
+    /// first of the three bytes are the ones of the message, as many as the returned length.
+    /// This will panic if the channel is above 15, or a data value above 127, or the pitch
+    /// bend outside its range.
+    #[must_use]
+    pub fn encode(&self) -> ([u8; 3], usize) {
+        let mut bytes = [0; 3];
+        let mut length = 0;
+        self.write_data(|byte| {
+            bytes[length] = byte;
+            length += 1;
+        });
+        (bytes, length)
+    }
+
+    /// Returns the channel of the message.
+    pub fn channel(&self) -> u8 {
+        match *self {
+            MidiMessage::NoteOff { channel, .. }
+            | MidiMessage::NoteOn { channel, .. }
+            | MidiMessage::PolyPressure { channel, .. }
+            | MidiMessage::ControlChange { channel, .. }
+            | MidiMessage::ProgramChange { channel, .. }
+            | MidiMessage::ChannelPressure { channel, .. }
+            | MidiMessage::PitchBend { channel, .. } => channel,
+        }
+    }
+
+    /// Returns the status byte of the message.
+    /// This will panic if the channel is above 15.
+    pub fn status(&self) -> u8 {
+        let kind = match self {
+            MidiMessage::NoteOff { .. } => 0x80,
+            MidiMessage::NoteOn { .. } => 0x90,
+            MidiMessage::PolyPressure { .. } => 0xA0,
+            MidiMessage::ControlChange { .. } => 0xB0,
+            MidiMessage::ProgramChange { .. } => 0xC0,
+            MidiMessage::ChannelPressure { .. } => 0xD0,
+            MidiMessage::PitchBend { .. } => 0xE0,
+        };
+        let channel = self.channel();
+        assert!(channel < 16, "MIDI channels go from 0 to 15");
+        kind | channel
+    }
+
+    /// Tells whether the message starts a note.
+    pub fn is_note_on(&self) -> bool {
+        matches!(self, MidiMessage::NoteOn { velocity, .. } if velocity.as_u8() > 0)
+    }
+
+    /// Tells whether the message releases a note, which includes a note on with a velocity of 0.
+    pub fn is_note_off(&self) -> bool {
+        match self {
+            MidiMessage::NoteOff { .. } => true,
+            MidiMessage::NoteOn { velocity, .. } => velocity.as_u8() == 0,
+            _ => false,
+        }
+    }
+
+    /// Creates the message from a status byte of a channel voice message and enough data bytes.
+    pub(crate) fn from_parts(status: u8, data: &[u8]) -> Self {
+        let channel = status & 0x0F;
+        match status & 0xF0 {
+            0x80 => MidiMessage::NoteOff {
+                channel,
+                note: MidiNote::from(data[0]),
+                velocity: Velocity::from(data[1]),
+            },
+            0x90 => MidiMessage::NoteOn {
+                channel,
+                note: MidiNote::from(data[0]),
+                velocity: Velocity::from(data[1]),
+            },
+            0xA0 => MidiMessage::PolyPressure {
+                channel,
+                note: MidiNote::from(data[0]),
+                pressure: data[1],
+            },
+            0xB0 => MidiMessage::ControlChange {
+                channel,
+                controller: data[0],
+                value: data[1],
+            },
+            0xC0 => MidiMessage::ProgramChange {
+                channel,
+                program: data[0],
+            },
+            0xD0 => MidiMessage::ChannelPressure {
+                channel,
+                pressure: data[0],
+            },
+            _ => MidiMessage::PitchBend {
+                channel,
+                bend: ((data[1] as i16) << 7 | data[0] as i16) - 8192,
+            },
+        }
+    }
+
+    /// Writes the status byte and the data bytes.
+    fn write_data(&self, mut push: impl FnMut(u8)) {
+        push(self.status());
+        let data = |value: u8| {
+            assert!(value < 128, "MIDI data values go from 0 to 127");
+            value
+        };
+        let (first, second) = match *self {
+            MidiMessage::NoteOff { note, velocity, .. }
+            | MidiMessage::NoteOn { note, velocity, .. } => (note.as_u8(), Some(velocity.as_u8())),
+            MidiMessage::PolyPressure { note, pressure, .. } => {
+                (note.as_u8(), Some(data(pressure)))
+            }
+            MidiMessage::ControlChange {
+                controller, value, ..
+            } => (data(controller), Some(data(value))),
+            MidiMessage::ProgramChange { program, .. } => (data(program), None),
+            MidiMessage::ChannelPressure { pressure, .. } => (data(pressure), None),
+            MidiMessage::PitchBend { bend, .. } => {
+                assert!(
+                    (-8192..8192).contains(&bend),
+                    "MIDI pitch bend goes from -8192 to 8191"
+                );
+                let value = (bend + 8192) as u16;
+                ((value & 0x7F) as u8, Some((value >> 7) as u8))
+            }
+        };
+        push(first);
+        if let Some(second) = second {
+            push(second);
+        }
+    }
+}
+
+/// Returns the number of data bytes that follow the status byte of a channel voice message,
+/// or `None` when the byte isn't the status byte of a channel voice message.
+pub(crate) fn data_length(status: u8) -> Option<usize> {
+    match status & 0xF0 {
+        0x80 | 0x90 | 0xA0 | 0xB0 | 0xE0 => Some(2),
+        0xC0 | 0xD0 => Some(1),
+        _ => None,
+    }
+}
+
+/// Turns a stream of MIDI bytes into channel voice messages, keeping track of the running
+/// status. System messages are skipped: real-time messages don't interrupt the message they
+/// appear in, and the other system messages cancel the running status.
+#[derive(Clone, Debug, Default)]
+pub struct MidiParser {
+    running_status: Option<u8>,
+    data: [u8; 2],
+    received: usize,
+}
+
+impl MidiParser {
+    /// Creates a new parser without a running status.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the running status and any partly received message.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Takes the next byte of the stream, and gives the message it completes, if any.
+    pub fn push(&mut self, byte: u8) -> Option<MidiMessage> {
+        if byte >= 0xF8 {
+            return None;
+        }
+        if byte >= 0x80 {
+            self.running_status = data_length(byte).map(|_| byte);
+            self.received = 0;
+            return None;
+        }
+
+        let status = self.running_status?;
+        self.data[self.received] = byte;
+        self.received += 1;
+        if self.received < data_length(status)? {
+            return None;
+        }
+        self.received = 0;
+        Some(MidiMessage::from_parts(status, &self.data))
+    }
+
+    /// Returns the messages that the bytes complete, as the next part of the stream.
+    pub fn parse<'a>(&'a mut self, bytes: &'a [u8]) -> impl Iterator<Item = MidiMessage> + 'a {
+        bytes.iter().filter_map(move |&byte| self.push(byte))
+    }
+}
+
+/// Turns channel voice messages into a stream of MIDI bytes, leaving out status bytes that are
+/// the same as the running status.
+#[derive(Clone, Debug, Default)]
+pub struct MidiWriter {
+    running_status: Option<u8>,
+}
+
+impl MidiWriter {
+    /// Creates a new writer without a running status, so the first message gets its status
+    /// byte.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the running status, e.g. after other bytes were sent in between.
+    pub fn reset(&mut self) {
+        self.running_status = None;
+    }
+
+    /// Appends the bytes of the message.
+    /// This will panic if the channel is above 15, or a data value above 127, or the pitch
+    /// bend outside its range.
+    pub fn write(&mut self, message: &MidiMessage, bytes: &mut Vec<u8>) {
+        let (encoded, length) = message.encode();
+        let status = encoded[0];
+        let start = usize::from(self.running_status == Some(status));
+        bytes.extend_from_slice(&encoded[start..length]);
+        self.running_status = Some(status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn note_on(note: u8, velocity: u8) -> MidiMessage {
+        MidiMessage::NoteOn {
+            channel: 3,
+            note: MidiNote::from(note),
+            velocity: Velocity::from(velocity),
+        }
+    }
+
+    #[test_case(note_on(60, 100), &[0x93, 60, 100]; "note on message")]
+    #[test_case(MidiMessage::NoteOff { channel: 0, note: MidiNote::from(1), velocity: Velocity::from(2) }, &[0x80, 1, 2]; "note off")]
+    #[test_case(MidiMessage::PolyPressure { channel: 15, note: MidiNote::from(1), pressure: 2 }, &[0xAF, 1, 2]; "poly pressure")]
+    #[test_case(MidiMessage::ControlChange { channel: 1, controller: 7, value: 127 }, &[0xB1, 7, 127]; "control change")]
+    #[test_case(MidiMessage::ProgramChange { channel: 2, program: 5 }, &[0xC2, 5]; "program change")]
+    #[test_case(MidiMessage::ChannelPressure { channel: 2, pressure: 9 }, &[0xD2, 9]; "channel pressure")]
+    #[test_case(MidiMessage::PitchBend { channel: 0, bend: 0 }, &[0xE0, 0x00, 0x40]; "pitch bend center")]
+    #[test_case(MidiMessage::PitchBend { channel: 0, bend: -8192 }, &[0xE0, 0x00, 0x00]; "pitch bend down")]
+    #[test_case(MidiMessage::PitchBend { channel: 0, bend: 8191 }, &[0xE0, 0x7F, 0x7F]; "pitch bend up")]
+    fn converts_to_and_from_bytes(message: MidiMessage, bytes: &[u8]) {
+        assert_eq!(message.to_bytes(), bytes);
+        let (encoded, length) = message.encode();
+        assert_eq!(&encoded[..length], bytes);
+        assert_eq!(MidiMessage::from_bytes(bytes), Some(message));
+    }
+
+    #[test_case(&[]; "empty")]
+    #[test_case(&[0x90, 60]; "too short")]
+    #[test_case(&[0xF0, 1, 2]; "system message")]
+    #[test_case(&[60, 100]; "no status")]
+    #[test_case(&[0x90, 60, 0x80]; "status as data")]
+    fn rejects_invalid_bytes(bytes: &[u8]) {
+        assert_eq!(MidiMessage::from_bytes(bytes), None);
+    }
+
+    #[test]
+    fn note_on_without_velocity_is_a_note_off() {
+        assert!(note_on(60, 0).is_note_off());
+        assert!(!note_on(60, 0).is_note_on());
+        assert!(note_on(60, 1).is_note_on());
+    }
+
+    #[test]
+    fn parser_follows_the_running_status() {
+        let mut parser = MidiParser::new();
+
+        // The real-time message in the middle of the third note leaves it unfinished.
+        let messages: Vec<_> = parser.parse(&[0x93, 60, 100, 62, 0, 0xF8, 64]).collect();
+
+        assert_eq!(messages, vec![note_on(60, 100), note_on(62, 0)]);
+    }
+
+    #[test]
+    fn parser_continues_a_message_over_several_parts() {
+        let mut parser = MidiParser::new();
+
+        assert_eq!(parser.parse(&[0x93, 60]).count(), 0);
+        assert_eq!(
+            parser.parse(&[100, 61]).collect::<Vec<_>>(),
+            [note_on(60, 100)]
+        );
+        assert_eq!(parser.parse(&[90]).collect::<Vec<_>>(), [note_on(61, 90)]);
+    }
+
+    #[test]
+    fn system_messages_cancel_the_running_status() {
+        let mut parser = MidiParser::new();
+
+        let messages: Vec<_> = parser
+            .parse(&[0x93, 60, 100, 0xF0, 1, 2, 0xF7, 61, 90])
+            .collect();
+
+        assert_eq!(messages, vec![note_on(60, 100)]);
+    }
+
+    #[test]
+    fn writer_uses_the_running_status() {
+        let mut writer = MidiWriter::new();
+        let mut bytes = Vec::new();
+
+        writer.write(&note_on(60, 100), &mut bytes);
+        writer.write(&note_on(60, 0), &mut bytes);
+        writer.write(
+            &MidiMessage::ProgramChange {
+                channel: 3,
+                program: 1,
+            },
+            &mut bytes,
+        );
+        writer.write(&note_on(61, 90), &mut bytes);
+
+        assert_eq!(bytes, [0x93, 60, 100, 60, 0, 0xC3, 1, 0x93, 61, 90]);
+    }
+}
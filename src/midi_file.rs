@@ -0,0 +1,516 @@
+//! This module contains a reader for standard MIDI files (format 0 and 1). The events of all
+//! tracks are merged in order of their position in ticks. How long a tick lasts follows from
+//! the tempo events in the file, which make up the tempo map, so it converts ticks to a time or
+//! a sample position.
+//! ```rust
+//! use rabu::midi::MidiMessage;
+//! use rabu::midi_file::MidiFile;
+//! use rabu::units::{SampleRate, Samples};
+//!
+//! let bytes = [
+//!     b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96, // format 0, 1 track, 96 ticks per quarter
+//!     b'M', b'T', b'r', b'k', 0, 0, 0, 19,
+//!     0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // 120 BPM
+//!     0x00, 0x90, 60, 100, // note on at tick 0
+//!     0x60, 0x80, 60, 0, // note off at tick 96
+//!     0x00, 0xFF, 0x2F, 0x00, // end of track
+//! ];
+//!
+//! let file = MidiFile::parse(&bytes).unwrap();
+//! let sample_rate = SampleRate::from(48000);
+//! let positions: Vec<_> = file
+//!     .events()
+//!     .iter()
+//!     .map(|event| file.tempo_map().to_samples(event.tick, sample_rate))
+//!     .collect();
+//!
+//! // A quarter note at 120 BPM lasts half a second.
+//! assert_eq!(positions, [Samples::from(0), Samples::from(24000)]);
+//! assert!(file.events()[1].message.is_note_off());
+//! ```
+
+use crate::midi::{data_length, MidiMessage};
+use crate::units::{SampleRate, Samples, Seconds, Tempo, TimePoint};
+
+/// A channel voice message in a MIDI file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MidiFileEvent {
+    /// The position from the start of the file, in ticks.
+    pub tick: u64,
+    /// The index of the track the event is in.
+    pub track: usize,
+    /// The message.
+    pub message: MidiMessage,
+}
+
+/// A standard MIDI file, with the events of all tracks in order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MidiFile {
+    format: u16,
+    num_tracks: usize,
+    events: Vec<MidiFileEvent>,
+    length: u64,
+    tempo_map: TempoMap,
+}
+
+impl MidiFile {
+    /// Reads a standard MIDI file, or gives `None` when the bytes aren't one, or it's a format
+    /// 2 file. Chunks of unknown types are skipped, and so are system exclusive and meta
+    /// events, apart from the tempo events that make up the tempo map.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(4)? != b"MThd" {
+            return None;
+        }
+        let header_length = reader.u32()? as usize;
+        let header = reader.take(header_length)?;
+        if header.len() < 6 {
+            return None;
+        }
+        let format = u16::from_be_bytes([header[0], header[1]]);
+        let num_tracks = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let division = u16::from_be_bytes([header[4], header[5]]);
+        if format > 1 {
+            return None;
+        }
+
+        let mut tempo_map = TempoMap::from_division(division)?;
+        let mut events = Vec::new();
+        let mut length = 0;
+        let mut track = 0;
+        while track < num_tracks {
+            let kind = reader.take(4)?;
+            let chunk_length = reader.u32()? as usize;
+            let chunk = reader.take(chunk_length)?;
+            if kind == b"MTrk" {
+                length = length.max(read_track(chunk, track, &mut events, &mut tempo_map)?);
+                track += 1;
+            }
+        }
+        // The sort is stable, so events at the same tick keep the order of their tracks.
+        events.sort_by_key(|event| event.tick);
+
+        Some(Self {
+            format,
+            num_tracks,
+            events,
+            length,
+            tempo_map,
+        })
+    }
+
+    /// Returns the format of the file: 0 for a single track, 1 for tracks played together.
+    pub fn format(&self) -> u16 {
+        self.format
+    }
+
+    /// Returns the number of tracks.
+    pub fn num_tracks(&self) -> usize {
+        self.num_tracks
+    }
+
+    /// Returns the channel voice messages of all tracks, in order of their position.
+    pub fn events(&self) -> &[MidiFileEvent] {
+        &self.events
+    }
+
+    /// Returns the position of the end of the longest track, in ticks.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Returns the length of the file as a time.
+    pub fn duration(&self) -> Seconds {
+        self.tempo_map.to_seconds(self.length)
+    }
+
+    /// Returns the tempo map, which converts ticks to time.
+    pub fn tempo_map(&self) -> &TempoMap {
+        &self.tempo_map
+    }
+}
+
+/// The tempo changes of a MIDI file, which convert positions in ticks to times. Until the first
+/// tempo change, the tempo is 120 BPM.
+/// ```rust
+/// use rabu::midi_file::TempoMap;
+/// use rabu::units::{Seconds, Tempo};
+///
+/// let mut tempo_map = TempoMap::new(480);
+/// tempo_map.insert(960, Tempo::from(60.0));
+///
+/// // Two quarter notes at 120 BPM, then one at 60 BPM.
+/// assert_eq!(tempo_map.to_seconds(1440), Seconds::from(2.0));
+/// assert_eq!(tempo_map.tempo_at(1000), Tempo::from(60.0));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct TempoMap {
+    timing: Timing,
+    /// The tempo changes in order, with the time they happen at.
+    changes: Vec<TempoChange>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Timing {
+    /// Ticks per quarter note, so their length follows the tempo.
+    Metrical(u16),
+    /// Ticks per second, from SMPTE frames, regardless of the tempo.
+    Timecode(f64),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct TempoChange {
+    tick: u64,
+    seconds: f64,
+    tempo: Tempo,
+}
+
+impl TempoMap {
+    /// Creates a tempo map without tempo changes, with the given number of ticks per quarter
+    /// note.
+    /// This will panic if the number of ticks is 0.
+    pub fn new(ticks_per_quarter: u16) -> Self {
+        assert!(
+            ticks_per_quarter > 0,
+            "a quarter note needs at least one tick"
+        );
+        Self {
+            timing: Timing::Metrical(ticks_per_quarter),
+            changes: vec![TempoChange {
+                tick: 0,
+                seconds: 0.0,
+                tempo: Tempo::from(120.0),
+            }],
+        }
+    }
+
+    /// Creates the tempo map of the division field of a MIDI file header, or gives `None` when
+    /// it has no ticks.
+    fn from_division(division: u16) -> Option<Self> {
+        if division & 0x8000 == 0 {
+            return (division > 0).then(|| Self::new(division));
+        }
+        // The upper byte holds the negative number of frames per second, where -29 means
+        // 29.97 (drop frame), and the lower byte the ticks per frame.
+        let frames_per_second = match (division >> 8) as u8 as i8 {
+            -29 => 29.97,
+            frames => -(frames as f64),
+        };
+        let ticks_per_frame = (division & 0xFF) as f64;
+        let ticks_per_second = frames_per_second * ticks_per_frame;
+        (ticks_per_second > 0.0).then(|| Self {
+            timing: Timing::Timecode(ticks_per_second),
+            changes: vec![TempoChange {
+                tick: 0,
+                seconds: 0.0,
+                tempo: Tempo::from(120.0),
+            }],
+        })
+    }
+
+    /// Changes the tempo from the tick on. A later change at the same tick replaces the
+    /// earlier one.
+    pub fn insert(&mut self, tick: u64, tempo: Tempo) {
+        let index = self.changes.partition_point(|change| change.tick <= tick);
+        let before = self.changes[index - 1];
+        let change = TempoChange {
+            tick,
+            seconds: before.seconds + self.seconds_between(&before, tick),
+            tempo,
+        };
+        if before.tick == tick {
+            self.changes[index - 1] = change;
+        } else {
+            self.changes.insert(index, change);
+        }
+        // The changes after this one now happen at another time.
+        for index in index..self.changes.len() {
+            let before = self.changes[index - 1];
+            let tick = self.changes[index].tick;
+            self.changes[index].seconds = before.seconds + self.seconds_between(&before, tick);
+        }
+    }
+
+    /// Returns the tempo at the tick.
+    pub fn tempo_at(&self, tick: u64) -> Tempo {
+        self.change_before(tick).tempo
+    }
+
+    /// Returns the time from the start to the tick.
+    pub fn to_seconds(&self, tick: u64) -> Seconds {
+        let change = self.change_before(tick);
+        Seconds::from(change.seconds + self.seconds_between(change, tick))
+    }
+
+    /// Returns the time of the tick.
+    pub fn to_time_point(&self, tick: u64) -> TimePoint {
+        TimePoint::from(self.to_seconds(tick))
+    }
+
+    /// Returns the sample nearest to the time of the tick.
+    pub fn to_samples(&self, tick: u64, sample_rate: SampleRate) -> Samples {
+        self.to_seconds(tick).to_samples(sample_rate)
+    }
+
+    fn change_before(&self, tick: u64) -> &TempoChange {
+        let index = self.changes.partition_point(|change| change.tick <= tick);
+        &self.changes[index - 1]
+    }
+
+    /// Returns the time between the change and a later tick, at the tempo of the change.
+    fn seconds_between(&self, change: &TempoChange, tick: u64) -> f64 {
+        let ticks = (tick - change.tick) as f64;
+        match self.timing {
+            Timing::Metrical(ticks_per_quarter) => {
+                ticks * change.tempo.beat_length().as_f64() / ticks_per_quarter as f64
+            }
+            Timing::Timecode(ticks_per_second) => ticks / ticks_per_second,
+        }
+    }
+}
+
+/// Reads the events of a track chunk, and returns the position of its end.
+fn read_track(
+    chunk: &[u8],
+    track: usize,
+    events: &mut Vec<MidiFileEvent>,
+    tempo_map: &mut TempoMap,
+) -> Option<u64> {
+    let mut reader = Reader {
+        bytes: chunk,
+        position: 0,
+    };
+    let mut tick = 0;
+    let mut running_status = None;
+    while reader.position < chunk.len() {
+        tick += reader.variable_length()?;
+        let mut status = reader.u8()?;
+        match status {
+            0xFF => {
+                let kind = reader.u8()?;
+                let length = reader.variable_length()? as usize;
+                let data = reader.take(length)?;
+                running_status = None;
+                match (kind, data) {
+                    (0x2F, _) => break,
+                    (0x51, &[a, b, c]) => {
+                        let micros_per_quarter = u32::from_be_bytes([0, a, b, c]);
+                        if micros_per_quarter > 0 {
+                            let beat_length = Seconds::from(micros_per_quarter as f64 / 1e6);
+                            tempo_map.insert(tick, Tempo::from_beat_length(beat_length));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            0xF0 | 0xF7 => {
+                let length = reader.variable_length()? as usize;
+                reader.take(length)?;
+                running_status = None;
+            }
+            _ => {
+                if status < 0x80 {
+                    // Running status: the byte is the first data byte.
+                    reader.position -= 1;
+                    status = running_status?;
+                }
+                let data = reader.take(data_length(status)?)?;
+                if data.iter().any(|&byte| byte > 0x7F) {
+                    return None;
+                }
+                running_status = Some(status);
+                events.push(MidiFileEvent {
+                    tick,
+                    track,
+                    message: MidiMessage::from_parts(status, data),
+                });
+            }
+        }
+    }
+    Some(tick)
+}
+
+/// Reads big-endian numbers and variable-length quantities from bytes.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position.checked_add(length)?)?;
+        self.position += length;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    /// Reads a number of at most four bytes, with seven bits per byte, where the top bit tells
+    /// whether another byte follows.
+    fn variable_length(&mut self) -> Option<u64> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = (value << 7) | (byte & 0x7F) as u64;
+            if byte < 0x80 {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{MidiNote, Velocity};
+
+    fn file(format: u16, division: u16, tracks: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = b"MThd".to_vec();
+        bytes.extend(6_u32.to_be_bytes());
+        bytes.extend(format.to_be_bytes());
+        bytes.extend((tracks.len() as u16).to_be_bytes());
+        bytes.extend(division.to_be_bytes());
+        for track in tracks {
+            bytes.extend(b"MTrk");
+            bytes.extend((track.len() as u32).to_be_bytes());
+            bytes.extend(*track);
+        }
+        bytes
+    }
+
+    fn note_on(channel: u8, note: u8) -> MidiMessage {
+        MidiMessage::NoteOn {
+            channel,
+            note: MidiNote::from(note),
+            velocity: Velocity::from(100),
+        }
+    }
+
+    #[test]
+    fn merges_the_tracks_in_order() {
+        let bytes = file(
+            1,
+            96,
+            &[
+                &[
+                    0x00, 0x90, 60, 100, 0x81, 0x40, 0x90, 62, 100, 0x00, 0xFF, 0x2F, 0x00,
+                ],
+                &[
+                    0x60, 0x91, 61, 100, 0x60, 0x91, 63, 100, 0x00, 0xFF, 0x2F, 0x00,
+                ],
+            ],
+        );
+
+        let file = MidiFile::parse(&bytes).unwrap();
+
+        let events: Vec<_> = file
+            .events()
+            .iter()
+            .map(|event| (event.tick, event.track, event.message))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (0, 0, note_on(0, 60)),
+                (96, 1, note_on(1, 61)),
+                (192, 0, note_on(0, 62)),
+                (192, 1, note_on(1, 63)),
+            ]
+        );
+        assert_eq!(file.num_tracks(), 2);
+        assert_eq!(file.length(), 192);
+    }
+
+    #[test]
+    fn reads_running_status_and_skips_system_exclusive() {
+        let bytes = file(
+            0,
+            96,
+            &[&[
+                0x00, 0x90, 60, 100, 0x00, 61, 100, 0x00, 0xF0, 0x02, 0x01, 0xF7, 0x00, 0x90, 62,
+                100, 0x00, 0xFF, 0x2F, 0x00,
+            ]],
+        );
+
+        let file = MidiFile::parse(&bytes).unwrap();
+
+        let messages: Vec<_> = file.events().iter().map(|event| event.message).collect();
+        assert_eq!(
+            messages,
+            vec![note_on(0, 60), note_on(0, 61), note_on(0, 62)]
+        );
+    }
+
+    #[test]
+    fn tempo_changes_convert_ticks_to_samples() {
+        // 60 BPM from the second quarter note on.
+        let bytes = file(
+            1,
+            100,
+            &[
+                &[
+                    0x64, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40, 0x00, 0xFF, 0x2F, 0x00,
+                ],
+                &[0x81, 0x48, 0x90, 60, 100, 0x00, 0xFF, 0x2F, 0x00],
+            ],
+        );
+
+        let file = MidiFile::parse(&bytes).unwrap();
+        let sample_rate = SampleRate::from(1000);
+
+        assert_eq!(
+            file.tempo_map().to_samples(100, sample_rate),
+            Samples::from(500)
+        );
+        assert_eq!(
+            file.tempo_map().to_samples(150, sample_rate),
+            Samples::from(1000)
+        );
+        assert_eq!(file.tempo_map().tempo_at(150), Tempo::from(60.0));
+        assert_eq!(file.events()[0].tick, 200);
+        assert_eq!(file.duration(), Seconds::from(1.5));
+    }
+
+    #[test]
+    fn timecode_division_ignores_the_tempo() {
+        // 25 frames per second with 40 ticks per frame: a millisecond per tick.
+        let division = u16::from_be_bytes([(-25_i8) as u8, 40]);
+        let bytes = file(0, division, &[&[0x00, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40]]);
+
+        let file = MidiFile::parse(&bytes).unwrap();
+
+        assert_eq!(file.tempo_map().to_seconds(250), Seconds::from(0.25));
+    }
+
+    #[test]
+    fn inserting_a_tempo_moves_the_later_changes() {
+        let mut tempo_map = TempoMap::new(1);
+        tempo_map.insert(2, Tempo::from(60.0));
+        tempo_map.insert(1, Tempo::from(30.0));
+
+        assert_eq!(tempo_map.to_seconds(2), Seconds::from(2.5));
+        assert_eq!(tempo_map.to_seconds(3), Seconds::from(3.5));
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        assert_eq!(MidiFile::parse(b"RIFF"), None);
+        assert_eq!(MidiFile::parse(&file(2, 96, &[])), None);
+        assert_eq!(MidiFile::parse(&file(0, 0, &[])), None);
+        // A data byte without a running status.
+        assert_eq!(MidiFile::parse(&file(0, 96, &[&[0x00, 60, 100]])), None);
+        // A truncated track.
+        let mut bytes = file(0, 96, &[&[0x00, 0x90, 60, 100]]);
+        bytes.pop();
+        assert_eq!(MidiFile::parse(&bytes), None);
+    }
+}
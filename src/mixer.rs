@@ -0,0 +1,380 @@
+//! This module contains a matrix mixer, which sends every input channel to every output channel
+//! with its own gain. Routing, downmixing, upmixing and monitoring feeds are all a matter of
+//! filling in the matrix. Gain changes glide over a short time, so they don't click. The
+//! common downmixes come as presets.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::mixer::MatrixMixer;
+//! use rabu::units::{Channels, SampleRate, Samples, Seconds};
+//!
+//! // Stereo to mono.
+//! let mut mixer = MatrixMixer::new(
+//!     Channels::from(2),
+//!     Channels::from(1),
+//!     Seconds::from(0.01),
+//!     SampleRate::from(48000),
+//! );
+//! mixer.set_gain(0, 0, 0.5);
+//! mixer.set_gain(1, 0, 0.5);
+//! mixer.reset();
+//!
+//! let mut stereo = Buffer::<f32>::allocate(Channels::from(2), Samples::from(4));
+//! stereo.chan_mut(0).fill(1.0);
+//! let mut mono = Buffer::<f32>::allocate(Channels::from(1), Samples::from(4));
+//! mixer.process(&stereo, &mut mono);
+//!
+//! assert_eq!(mono.chan(0), &[0.5; 4]);
+//! ```
+//! A stereo monitoring feed of a 5.1 mix:
+//! ```rust
+//! use rabu::mixer::{Downmix, MatrixMixer};
+//! use rabu::units::{SampleRate, Seconds};
+//!
+//! let mixer = MatrixMixer::downmix(Downmix::Surround5_1ToStereo, Seconds::from(0.01), SampleRate::from(48000));
+//!
+//! // The center goes to both sides at -3 dB.
+//! assert!((mixer.gain(2, 0) - 0.7071).abs() < 1e-4);
+//! assert_eq!(mixer.gain(2, 0), mixer.gain(2, 1));
+//! ```
+
+use std::f64::consts::FRAC_1_SQRT_2;
+
+use crate::buffer::Buffer;
+use crate::layout::{ChannelLayout, Speaker};
+use crate::sample::Sample;
+use crate::smoother::{Smoother, SmoothingMode};
+use crate::units::{Channels, SampleRate, Seconds};
+
+/// Mixes a number of input channels into a number of output channels through a matrix of
+/// gains.
+#[derive(Clone, Debug)]
+pub struct MatrixMixer {
+    num_inputs: usize,
+    num_outputs: usize,
+    /// The gains from every input to the first output, then to the second, and so on.
+    gains: Vec<Smoother<f64>>,
+}
+
+impl MatrixMixer {
+    /// Creates a new mixer where all gains are 0, and changes glide over the smoothing time.
+    pub fn new(
+        num_inputs: Channels,
+        num_outputs: Channels,
+        smoothing: Seconds,
+        sample_rate: SampleRate,
+    ) -> Self {
+        let num_inputs = num_inputs.as_usize();
+        let num_outputs = num_outputs.as_usize();
+        let gain = Smoother::new(SmoothingMode::Linear, smoothing, sample_rate, 0.0);
+        Self {
+            num_inputs,
+            num_outputs,
+            gains: vec![gain; num_inputs * num_outputs],
+        }
+    }
+
+    /// Creates a new mixer that passes every channel to the same output channel.
+    pub fn identity(channels: Channels, smoothing: Seconds, sample_rate: SampleRate) -> Self {
+        let mut mixer = Self::new(channels, channels, smoothing, sample_rate);
+        for channel in 0..channels.as_usize() {
+            mixer.gain_mut(channel, channel).reset(1.0);
+        }
+        mixer
+    }
+
+    /// Creates a new mixer with the matrix of the downmix, which starts at its gains.
+    pub fn downmix(downmix: Downmix, smoothing: Seconds, sample_rate: SampleRate) -> Self {
+        let mut mixer = Self::new(
+            downmix.from().num_channels(),
+            downmix.to().num_channels(),
+            smoothing,
+            sample_rate,
+        );
+        mixer.set_matrix(&downmix.matrix());
+        mixer.reset();
+        mixer
+    }
+
+    /// Returns the number of input channels.
+    pub fn num_inputs(&self) -> Channels {
+        Channels::from(self.num_inputs)
+    }
+
+    /// Returns the number of output channels.
+    pub fn num_outputs(&self) -> Channels {
+        Channels::from(self.num_outputs)
+    }
+
+    /// Returns the gain from the input to the output channel that the mixer is going to.
+    /// This will panic if either channel doesn't exist.
+    pub fn gain(&self, input: usize, output: usize) -> f64 {
+        self.gains[self.index(input, output)].target()
+    }
+
+    /// Glides the gain from the input to the output channel to a new value.
+    /// This will panic if either channel doesn't exist.
+    pub fn set_gain(&mut self, input: usize, output: usize, gain: f64) {
+        self.gain_mut(input, output).set_target(gain);
+    }
+
+    /// Glides all gains to the matrix, which has a row of gains for every output channel, with
+    /// a gain for every input channel.
+    /// This will panic if the matrix doesn't have that shape.
+    pub fn set_matrix(&mut self, matrix: &[Vec<f64>]) {
+        assert_eq!(matrix.len(), self.num_outputs, "a row for every output");
+        for (output, row) in matrix.iter().enumerate() {
+            assert_eq!(row.len(), self.num_inputs, "a gain for every input");
+            for (input, gain) in row.iter().enumerate() {
+                self.set_gain(input, output, *gain);
+            }
+        }
+    }
+
+    /// Changes the time over which gain changes glide.
+    pub fn set_smoothing(&mut self, smoothing: Seconds, sample_rate: SampleRate) {
+        for gain in self.gains.iter_mut() {
+            gain.set_time(smoothing, sample_rate);
+        }
+    }
+
+    /// Jumps all gains to their new values, without gliding.
+    pub fn reset(&mut self) {
+        for gain in self.gains.iter_mut() {
+            gain.reset(gain.target());
+        }
+    }
+
+    /// Mixes the input into the output, replacing what was in the output.
+    /// This will panic if the buffers don't have the number of channels of the mixer, or if
+    /// they don't have the same number of samples.
+    pub fn process<T: Sample>(&mut self, input: &Buffer<T>, output: &mut Buffer<T>) {
+        assert_eq!(input.num_channels().as_usize(), self.num_inputs);
+        assert_eq!(output.num_channels().as_usize(), self.num_outputs);
+        assert_eq!(input.num_samples(), output.num_samples());
+
+        // Without inputs, there is nothing to mix into the outputs.
+        if self.num_inputs == 0 {
+            output.fill_default();
+            return;
+        }
+
+        for index in input.sample_indices() {
+            for (out, gains) in self.gains.chunks_mut(self.num_inputs).enumerate() {
+                let mixed = gains
+                    .iter_mut()
+                    .zip(input.iter_chans())
+                    .map(|(gain, channel)| gain.next_sample() * channel[index].to_f64())
+                    .sum();
+                output.chan_mut(out)[index] = T::from_f64(mixed);
+            }
+        }
+    }
+
+    fn index(&self, input: usize, output: usize) -> usize {
+        assert!(input < self.num_inputs && output < self.num_outputs);
+        output * self.num_inputs + input
+    }
+
+    fn gain_mut(&mut self, input: usize, output: usize) -> &mut Smoother<f64> {
+        let index = self.index(input, output);
+        &mut self.gains[index]
+    }
+}
+
+/// A common downmix from one layout to a smaller one. The downmixes to stereo and mono have the
+/// coefficients of ITU-R BS.775, and leave the LFE channel out, as the recommendation does.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Downmix {
+    /// The surrounds and the center go to the sides at -3 dB.
+    Surround5_1ToStereo,
+    /// The sides and backs go to the surrounds at -3 dB each, so their power adds up, and the
+    /// LFE channel stays. BS.775 has no 7.1 layout, so this is not from the recommendation.
+    Surround7_1To5_1,
+    /// Both sides go to the center at -3 dB.
+    StereoToMono,
+}
+
+impl Downmix {
+    /// Returns the layout that is downmixed.
+    pub fn from(&self) -> ChannelLayout {
+        match self {
+            Downmix::Surround5_1ToStereo => ChannelLayout::Surround5_1,
+            Downmix::Surround7_1To5_1 => ChannelLayout::Surround7_1,
+            Downmix::StereoToMono => ChannelLayout::Stereo,
+        }
+    }
+
+    /// Returns the layout it's downmixed to.
+    pub fn to(&self) -> ChannelLayout {
+        match self {
+            Downmix::Surround5_1ToStereo => ChannelLayout::Stereo,
+            Downmix::Surround7_1To5_1 => ChannelLayout::Surround5_1,
+            Downmix::StereoToMono => ChannelLayout::Mono,
+        }
+    }
+
+    /// Returns the gains of every speaker that goes into the speaker.
+    fn sources(&self, speaker: Speaker) -> &'static [(Speaker, f64)] {
+        use Speaker::*;
+        const HALF_POWER: f64 = FRAC_1_SQRT_2;
+        match (self, speaker) {
+            (Downmix::Surround5_1ToStereo, Left) => &[
+                (Left, 1.0),
+                (Center, HALF_POWER),
+                (LeftSurround, HALF_POWER),
+            ],
+            (Downmix::Surround5_1ToStereo, Right) => &[
+                (Right, 1.0),
+                (Center, HALF_POWER),
+                (RightSurround, HALF_POWER),
+            ],
+            (Downmix::Surround7_1To5_1, LeftSurround) => {
+                &[(LeftSurround, HALF_POWER), (LeftBack, HALF_POWER)]
+            }
+            (Downmix::Surround7_1To5_1, RightSurround) => {
+                &[(RightSurround, HALF_POWER), (RightBack, HALF_POWER)]
+            }
+            (Downmix::Surround7_1To5_1, Left) => &[(Left, 1.0)],
+            (Downmix::Surround7_1To5_1, Right) => &[(Right, 1.0)],
+            (Downmix::Surround7_1To5_1, Center) => &[(Center, 1.0)],
+            (Downmix::Surround7_1To5_1, Lfe) => &[(Lfe, 1.0)],
+            (Downmix::StereoToMono, Mono) => &[(Left, HALF_POWER), (Right, HALF_POWER)],
+            _ => &[],
+        }
+    }
+
+    /// Returns the matrix for a `MatrixMixer`, with a row of gains for every output channel,
+    /// with a gain for every input channel.
+    pub fn matrix(&self) -> Vec<Vec<f64>> {
+        let from = self.from();
+        self.to()
+            .speakers()
+            .iter()
+            .map(|speaker| {
+                let mut row = vec![0.0; from.num_channels().as_usize()];
+                for (source, gain) in self.sources(*speaker) {
+                    if let Some(index) = from.index_of(*source) {
+                        row[index] = *gain;
+                    }
+                }
+                row
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Samples;
+
+    fn mixer(inputs: usize, outputs: usize) -> MatrixMixer {
+        MatrixMixer::new(
+            Channels::from(inputs),
+            Channels::from(outputs),
+            Seconds::from(0.004),
+            SampleRate::from(1000),
+        )
+    }
+
+    fn buffer(channels: &[&[f64]]) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(
+            Channels::from(channels.len()),
+            Samples::from(channels[0].len()),
+        );
+        for (index, channel) in channels.iter().enumerate() {
+            buffer.chan_mut(index).copy_from_slice(channel);
+        }
+        buffer
+    }
+
+    #[test]
+    fn routes_and_mixes_into_another_channel_count() {
+        let mut mixer = mixer(2, 3);
+        mixer.set_matrix(&[vec![1.0, 0.0], vec![0.0, 1.0], vec![0.5, 0.5]]);
+        mixer.reset();
+        let input = buffer(&[&[1.0, 2.0], &[3.0, 4.0]]);
+        let mut output = Buffer::allocate(Channels::from(3), Samples::from(2));
+
+        mixer.process(&input, &mut output);
+
+        assert_eq!(output.chan(0), &[1.0, 2.0]);
+        assert_eq!(output.chan(1), &[3.0, 4.0]);
+        assert_eq!(output.chan(2), &[2.0, 3.0]);
+    }
+
+    #[test]
+    fn gain_changes_glide() {
+        let mut mixer = MatrixMixer::identity(
+            Channels::from(1),
+            Seconds::from(0.004),
+            SampleRate::from(1000),
+        );
+        mixer.set_gain(0, 0, 0.0);
+        let input = buffer(&[&[1.0; 6]]);
+        let mut output = Buffer::allocate(Channels::from(1), Samples::from(6));
+
+        mixer.process(&input, &mut output);
+
+        assert_eq!(output.chan(0), &[0.75, 0.5, 0.25, 0.0, 0.0, 0.0]);
+        assert_eq!(mixer.gain(0, 0), 0.0);
+    }
+
+    #[test]
+    fn mixer_without_inputs_gives_silence() {
+        let mut mixer = mixer(0, 2);
+        let input = Buffer::allocate(Channels::from(0), Samples::from(3));
+        let mut output = buffer(&[&[1.0; 3], &[1.0; 3]]);
+
+        mixer.process(&input, &mut output);
+
+        assert!(output.is_default_filled());
+    }
+
+    #[test]
+    #[should_panic]
+    fn output_must_have_the_channels_of_the_mixer() {
+        let mut mixer = mixer(2, 1);
+        let input = buffer(&[&[0.0], &[0.0]]);
+        let mut output = Buffer::allocate(Channels::from(2), Samples::from(1));
+        mixer.process(&input, &mut output);
+    }
+
+    #[test]
+    fn downmixes_5_1_to_stereo() {
+        let mut mixer = MatrixMixer::downmix(
+            Downmix::Surround5_1ToStereo,
+            Seconds::from(0.004),
+            SampleRate::from(1000),
+        );
+        // L, R, C, LFE, Ls, Rs
+        let input = buffer(&[&[1.0], &[0.0], &[1.0], &[1.0], &[0.0], &[1.0]]);
+        let mut output = Buffer::allocate(Channels::from(2), Samples::from(1));
+
+        mixer.process(&input, &mut output);
+
+        assert!((output.chan(0)[0] - (1.0 + FRAC_1_SQRT_2)).abs() < 1e-12);
+        assert!((output.chan(1)[0] - 2.0 * FRAC_1_SQRT_2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn downmixes_7_1_to_5_1() {
+        let matrix = Downmix::Surround7_1To5_1.matrix();
+
+        assert_eq!(matrix.len(), 6);
+        // The LFE channel stays.
+        assert_eq!(matrix[3], [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+        // The left back and side go to the left surround.
+        assert_eq!(
+            matrix[4],
+            [0.0, 0.0, 0.0, 0.0, FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2, 0.0]
+        );
+    }
+
+    #[test]
+    fn downmixes_stereo_to_mono_keeping_the_power() {
+        let matrix = Downmix::StereoToMono.matrix();
+
+        assert_eq!(matrix, [vec![FRAC_1_SQRT_2, FRAC_1_SQRT_2]]);
+    }
+}
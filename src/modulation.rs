@@ -0,0 +1,503 @@
+//! This module contains a modulation matrix, which connects modulation sources (LFOs,
+//! envelopes, MIDI controllers and the velocity) to the parameters of a synth or an effect.
+//! Every route has its own depth and curve, and a parameter adds up the routes to it. The
+//! sources are evaluated once per block, or once per part of a block when the block is split
+//! at the MIDI events in it, which is how parameters usually are updated.
+//! ```rust
+//! use rabu::envelope::Adsr;
+//! use rabu::events::TimedEvent;
+//! use rabu::midi::MidiMessage;
+//! use rabu::modulation::{ModulationCurve, ModulationMatrix, ModulationSource};
+//! use rabu::osc::{Oscillator, Waveform};
+//! use rabu::units::{
+//!     Frequency, MidiNote, NormalizedValue, SampleRate, SampleSection, Samples, Seconds,
+//!     Velocity,
+//! };
+//!
+//! #[derive(Copy, Clone, Debug, PartialEq)]
+//! enum Parameter {
+//!     Cutoff,
+//!     Gain,
+//! }
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let mut matrix = ModulationMatrix::new();
+//! let lfo = matrix.add_source(ModulationSource::Lfo(Oscillator::new(
+//!     Waveform::Triangle,
+//!     Frequency::from(2.0),
+//!     sample_rate,
+//! )));
+//! let envelope = matrix.add_source(ModulationSource::Envelope(Adsr::new(
+//!     Seconds::from(0.01),
+//!     Seconds::from(0.2),
+//!     NormalizedValue::from(0.5),
+//!     Seconds::from(0.3),
+//!     sample_rate,
+//! )));
+//! let mod_wheel = matrix.add_source(ModulationSource::Controller(1));
+//! matrix.add_route(lfo, Parameter::Cutoff, 0.2, ModulationCurve::Linear);
+//! matrix.add_route(mod_wheel, Parameter::Cutoff, 0.5, ModulationCurve::Quadratic);
+//! matrix.add_route(envelope, Parameter::Gain, 1.0, ModulationCurve::Linear);
+//!
+//! let events = [TimedEvent {
+//!     position: Samples::from(100),
+//!     event: MidiMessage::NoteOn { channel: 0, note: MidiNote::from(60), velocity: Velocity::from(100) },
+//! }];
+//! let block = SampleSection { start: Samples::from(0), length: Samples::from(256) };
+//! matrix.process_block(block, &events, |part, matrix| {
+//!     let gain = matrix.value(Parameter::Gain);
+//!     let cutoff = 1000.0 * 2.0_f64.powf(4.0 * matrix.value(Parameter::Cutoff));
+//!     // Process the samples of the part with the gain and the cutoff.
+//! });
+//! ```
+
+use crate::envelope::Adsr;
+use crate::events::{split_block, SubBlock, TimedEvent};
+use crate::midi::MidiMessage;
+use crate::osc::Oscillator;
+use crate::units::{SampleRate, SampleSection, Samples};
+
+/// Something that modulates parameters.
+#[derive(Clone, Debug)]
+pub enum ModulationSource {
+    /// An LFO, between -1 and 1.
+    Lfo(Oscillator),
+    /// An envelope, between 0 and 1, which starts at every note-on and releases at every
+    /// note-off.
+    Envelope(Adsr),
+    /// The value of the MIDI controller with the given number, between 0 and 1.
+    Controller(u8),
+    /// The velocity of the last note-on, between 0 and 1.
+    Velocity,
+}
+
+/// How the value of a source is bent before it is scaled by the depth of a route. The curves
+/// keep the sign of the value, so bipolar sources stay bipolar, and 0, 1 and -1 stay where they
+/// are.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ModulationCurve {
+    /// Passes the value as is.
+    Linear,
+    /// Squares the value, which gives finer control near 0.
+    Quadratic,
+    /// Cubes the value, which gives even finer control near 0.
+    Cubic,
+    /// Takes the square root of the value, which reacts strongly near 0.
+    SquareRoot,
+}
+
+impl ModulationCurve {
+    /// Returns the bent value.
+    pub fn apply(&self, value: f64) -> f64 {
+        match self {
+            ModulationCurve::Linear => value,
+            ModulationCurve::Quadratic => value * value.abs(),
+            ModulationCurve::Cubic => value * value * value,
+            ModulationCurve::SquareRoot => value.signum() * value.abs().sqrt(),
+        }
+    }
+}
+
+/// Identifies a source of a `ModulationMatrix`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SourceId(usize);
+
+/// Identifies a route of a `ModulationMatrix`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RouteId(usize);
+
+/// A connection from a source to a destination.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Route<D> {
+    /// The source of the modulation.
+    pub source: SourceId,
+    /// The parameter that is modulated.
+    pub destination: D,
+    /// How much the parameter is modulated, which can be negative to invert the source.
+    pub depth: f64,
+    /// How the value of the source is bent.
+    pub curve: ModulationCurve,
+}
+
+/// Routes modulation sources to destinations of type `D`, e.g. an enum with the parameters of
+/// a synth.
+#[derive(Clone, Debug)]
+pub struct ModulationMatrix<D> {
+    sources: Vec<ModulationSource>,
+    /// The value of every source at the start of the current block.
+    values: Vec<f64>,
+    /// The routes, with a hole where a route was removed, so the ids stay valid.
+    routes: Vec<Option<Route<D>>>,
+    controllers: [f64; 128],
+    velocity: f64,
+}
+
+impl<D: Copy + PartialEq> Default for ModulationMatrix<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: Copy + PartialEq> ModulationMatrix<D> {
+    /// Creates a new matrix without sources or routes.
+    pub fn new() -> Self {
+        Self {
+            sources: Vec::new(),
+            values: Vec::new(),
+            routes: Vec::new(),
+            controllers: [0.0; 128],
+            velocity: 0.0,
+        }
+    }
+
+    /// Adds a source, and returns its id.
+    /// This will panic if the source is a controller above 127.
+    pub fn add_source(&mut self, source: ModulationSource) -> SourceId {
+        if let ModulationSource::Controller(controller) = source {
+            assert!(controller < 128, "MIDI controllers go from 0 to 127");
+        }
+        self.sources.push(source);
+        self.values.push(0.0);
+        self.update_value(self.sources.len() - 1);
+        SourceId(self.sources.len() - 1)
+    }
+
+    /// Returns the source.
+    /// This will panic if the source isn't part of the matrix.
+    pub fn source(&self, id: SourceId) -> &ModulationSource {
+        &self.sources[id.0]
+    }
+
+    /// Returns the source, to change its settings.
+    /// This will panic if the source isn't part of the matrix.
+    pub fn source_mut(&mut self, id: SourceId) -> &mut ModulationSource {
+        &mut self.sources[id.0]
+    }
+
+    /// Returns the value of the source at the start of the current block.
+    /// This will panic if the source isn't part of the matrix.
+    pub fn source_value(&self, id: SourceId) -> f64 {
+        self.values[id.0]
+    }
+
+    /// Connects the source to the destination, and returns the id of the route.
+    /// This will panic if the source isn't part of the matrix.
+    pub fn add_route(
+        &mut self,
+        source: SourceId,
+        destination: D,
+        depth: f64,
+        curve: ModulationCurve,
+    ) -> RouteId {
+        assert!(source.0 < self.sources.len(), "the source doesn't exist");
+        self.routes.push(Some(Route {
+            source,
+            destination,
+            depth,
+            curve,
+        }));
+        RouteId(self.routes.len() - 1)
+    }
+
+    /// Returns the route, or `None` if it was removed.
+    pub fn route(&self, id: RouteId) -> Option<&Route<D>> {
+        self.routes.get(id.0).and_then(Option::as_ref)
+    }
+
+    /// Changes the depth of the route. Does nothing if the route was removed.
+    pub fn set_depth(&mut self, id: RouteId, depth: f64) {
+        if let Some(Some(route)) = self.routes.get_mut(id.0) {
+            route.depth = depth;
+        }
+    }
+
+    /// Changes the curve of the route. Does nothing if the route was removed.
+    pub fn set_curve(&mut self, id: RouteId, curve: ModulationCurve) {
+        if let Some(Some(route)) = self.routes.get_mut(id.0) {
+            route.curve = curve;
+        }
+    }
+
+    /// Removes the route, and returns it, or `None` if it was removed already.
+    pub fn remove_route(&mut self, id: RouteId) -> Option<Route<D>> {
+        self.routes.get_mut(id.0).and_then(Option::take)
+    }
+
+    /// Returns the routes that weren't removed, with their ids.
+    pub fn routes(&self) -> impl Iterator<Item = (RouteId, &Route<D>)> + '_ {
+        self.routes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, route)| route.as_ref().map(|route| (RouteId(index), route)))
+    }
+
+    /// Returns the sum of the modulation of all routes to the destination, for the current
+    /// block. This is 0 when nothing is routed to it.
+    pub fn value(&self, destination: D) -> f64 {
+        self.routes()
+            .filter(|(_, route)| route.destination == destination)
+            .map(|(_, route)| route.depth * route.curve.apply(self.values[route.source.0]))
+            .sum()
+    }
+
+    /// Changes the sample rate of the LFOs and envelopes.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        for source in self.sources.iter_mut() {
+            match source {
+                ModulationSource::Lfo(oscillator) => oscillator.set_sample_rate(sample_rate),
+                ModulationSource::Envelope(envelope) => envelope.set_sample_rate(sample_rate),
+                ModulationSource::Controller(_) | ModulationSource::Velocity => (),
+            }
+        }
+    }
+
+    /// Starts the LFOs from the start of their cycle, silences the envelopes, and sets the
+    /// controllers and the velocity back to 0.
+    pub fn reset(&mut self) {
+        for source in self.sources.iter_mut() {
+            match source {
+                ModulationSource::Lfo(oscillator) => oscillator.reset(),
+                ModulationSource::Envelope(envelope) => envelope.reset(),
+                ModulationSource::Controller(_) | ModulationSource::Velocity => (),
+            }
+        }
+        self.controllers = [0.0; 128];
+        self.velocity = 0.0;
+        self.update_values();
+    }
+
+    /// Changes the value of the controller, between 0 and 127.
+    /// This will panic if the controller is above 127.
+    pub fn set_controller(&mut self, controller: u8, value: u8) {
+        self.controllers[controller as usize] = value.min(127) as f64 / 127.0;
+        self.update_values();
+    }
+
+    /// Follows a MIDI message: note-ons start the envelopes and set the velocity, note-offs
+    /// release the envelopes, and control changes set the controllers. The MIDI channel is
+    /// ignored.
+    pub fn handle_midi(&mut self, message: &MidiMessage) {
+        match *message {
+            MidiMessage::ControlChange {
+                controller, value, ..
+            } => self.set_controller(controller, value),
+            MidiMessage::NoteOn { velocity, .. } if message.is_note_on() => {
+                self.velocity = velocity.to_normalized().as_f64();
+                self.for_each_envelope(Adsr::note_on);
+            }
+            _ if message.is_note_off() => self.for_each_envelope(Adsr::note_off),
+            _ => (),
+        }
+    }
+
+    /// Runs the LFOs and envelopes for the given number of samples, and takes their values at
+    /// the start of the next block.
+    pub fn advance(&mut self, num_samples: Samples) {
+        for source in self.sources.iter_mut() {
+            match source {
+                ModulationSource::Lfo(oscillator) => {
+                    (0..num_samples.as_usize()).for_each(|_| {
+                        oscillator.next_sample();
+                    });
+                }
+                ModulationSource::Envelope(envelope) => {
+                    (0..num_samples.as_usize()).for_each(|_| {
+                        envelope.next_sample();
+                    });
+                }
+                ModulationSource::Controller(_) | ModulationSource::Velocity => (),
+            }
+        }
+        self.update_values();
+    }
+
+    /// Splits the block at the MIDI events in it, and calls `process` for every part in order
+    /// with the matrix, after the events at the start of the part are handled. The matrix
+    /// advances by the length of every part. The events must be sorted by position.
+    pub fn process_block(
+        &mut self,
+        block: SampleSection,
+        events: &[TimedEvent<MidiMessage>],
+        mut process: impl FnMut(SubBlock, &Self),
+    ) {
+        split_block(block, events, |part, events| {
+            for event in events {
+                self.handle_midi(&event.event);
+            }
+            process(part, self);
+            self.advance(part.section.length);
+        });
+    }
+
+    fn for_each_envelope(&mut self, mut action: impl FnMut(&mut Adsr)) {
+        for source in self.sources.iter_mut() {
+            if let ModulationSource::Envelope(envelope) = source {
+                action(envelope);
+            }
+        }
+        self.update_values();
+    }
+
+    fn update_values(&mut self) {
+        (0..self.sources.len()).for_each(|index| self.update_value(index));
+    }
+
+    /// Takes the current value of the source, without running it.
+    fn update_value(&mut self, index: usize) {
+        self.values[index] = match &self.sources[index] {
+            ModulationSource::Lfo(oscillator) => oscillator
+                .waveform()
+                .value_at(oscillator.phase() + oscillator.phase_offset()),
+            ModulationSource::Envelope(envelope) => envelope.value(),
+            ModulationSource::Controller(controller) => self.controllers[*controller as usize],
+            ModulationSource::Velocity => self.velocity,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osc::Waveform;
+    use crate::units::{Frequency, MidiNote, NormalizedValue, Seconds, Velocity};
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    enum Parameter {
+        Cutoff,
+        Gain,
+    }
+
+    const SAMPLE_RATE: u32 = 1000;
+
+    fn lfo() -> ModulationSource {
+        ModulationSource::Lfo(Oscillator::new(
+            Waveform::Saw,
+            Frequency::from(10.0),
+            SampleRate::from(SAMPLE_RATE),
+        ))
+    }
+
+    fn envelope() -> ModulationSource {
+        ModulationSource::Envelope(Adsr::new(
+            Seconds::from(0.01),
+            Seconds::from(0.0),
+            NormalizedValue::from(1.0),
+            Seconds::from(0.01),
+            SampleRate::from(SAMPLE_RATE),
+        ))
+    }
+
+    fn note_on() -> MidiMessage {
+        MidiMessage::NoteOn {
+            channel: 0,
+            note: MidiNote::from(60),
+            velocity: Velocity::from(127),
+        }
+    }
+
+    #[test]
+    fn curves_keep_the_ends_and_the_sign() {
+        for curve in [
+            ModulationCurve::Linear,
+            ModulationCurve::Quadratic,
+            ModulationCurve::Cubic,
+            ModulationCurve::SquareRoot,
+        ] {
+            assert_eq!(curve.apply(1.0), 1.0);
+            assert_eq!(curve.apply(-1.0), -1.0);
+            assert_eq!(curve.apply(0.0), 0.0);
+        }
+        assert_eq!(ModulationCurve::Quadratic.apply(-0.5), -0.25);
+        assert_eq!(ModulationCurve::SquareRoot.apply(0.25), 0.5);
+    }
+
+    #[test]
+    fn routes_to_a_destination_add_up() {
+        let mut matrix = ModulationMatrix::new();
+        let wheel = matrix.add_source(ModulationSource::Controller(1));
+        let velocity = matrix.add_source(ModulationSource::Velocity);
+        matrix.add_route(wheel, Parameter::Cutoff, 0.5, ModulationCurve::Linear);
+        matrix.add_route(velocity, Parameter::Cutoff, -0.25, ModulationCurve::Linear);
+
+        matrix.set_controller(1, 127);
+        matrix.handle_midi(&note_on());
+
+        assert_eq!(matrix.value(Parameter::Cutoff), 0.25);
+        assert_eq!(matrix.value(Parameter::Gain), 0.0);
+    }
+
+    #[test]
+    fn lfo_is_evaluated_at_the_start_of_every_block() {
+        let mut matrix = ModulationMatrix::new();
+        let lfo = matrix.add_source(lfo());
+        matrix.add_route(lfo, Parameter::Cutoff, 1.0, ModulationCurve::Linear);
+
+        assert_eq!(matrix.value(Parameter::Cutoff), -1.0);
+        matrix.advance(Samples::from(50));
+        assert!(matrix.value(Parameter::Cutoff).abs() < 1e-9);
+        matrix.advance(Samples::from(25));
+        assert!((matrix.value(Parameter::Cutoff) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn envelopes_follow_the_notes() {
+        let mut matrix = ModulationMatrix::new();
+        let envelope = matrix.add_source(envelope());
+        matrix.add_route(envelope, Parameter::Gain, 1.0, ModulationCurve::Linear);
+
+        matrix.handle_midi(&note_on());
+        matrix.advance(Samples::from(20));
+        assert_eq!(matrix.value(Parameter::Gain), 1.0);
+
+        matrix.handle_midi(&MidiMessage::NoteOff {
+            channel: 0,
+            note: MidiNote::from(60),
+            velocity: Velocity::from(0),
+        });
+        matrix.advance(Samples::from(20));
+        assert_eq!(matrix.value(Parameter::Gain), 0.0);
+    }
+
+    #[test]
+    fn process_block_handles_the_events_at_their_position() {
+        let mut matrix = ModulationMatrix::new();
+        let wheel = matrix.add_source(ModulationSource::Controller(1));
+        matrix.add_route(wheel, Parameter::Cutoff, 1.0, ModulationCurve::Linear);
+        let events = [TimedEvent {
+            position: Samples::from(40),
+            event: MidiMessage::ControlChange {
+                channel: 0,
+                controller: 1,
+                value: 127,
+            },
+        }];
+
+        let mut parts = Vec::new();
+        matrix.process_block(
+            SampleSection {
+                start: Samples::from(0),
+                length: Samples::from(64),
+            },
+            &events,
+            |part, matrix| parts.push((part.offset.as_usize(), matrix.value(Parameter::Cutoff))),
+        );
+
+        assert_eq!(parts, [(0, 0.0), (40, 1.0)]);
+    }
+
+    #[test]
+    fn removed_routes_stop_modulating() {
+        let mut matrix = ModulationMatrix::new();
+        let velocity = matrix.add_source(ModulationSource::Velocity);
+        let route = matrix.add_route(velocity, Parameter::Gain, 1.0, ModulationCurve::Linear);
+        matrix.handle_midi(&note_on());
+
+        assert_eq!(
+            matrix.remove_route(route).map(|route| route.depth),
+            Some(1.0)
+        );
+        assert_eq!(matrix.value(Parameter::Gain), 0.0);
+        assert!(matrix.route(route).is_none());
+        assert_eq!(matrix.routes().count(), 0);
+    }
+}
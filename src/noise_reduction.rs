@@ -0,0 +1,304 @@
+//! This module contains a spectral noise reducer, for steady noise like hiss, fan noise or the
+//! hum of a room. It learns the spectrum of the noise from a section that contains only noise,
+//! and then turns every bin of the STFT down by a Wiener gain: the part of the power of the bin
+//! that isn't noise, with the noise subtracted a few times over. Bins that are mostly noise are
+//! removed, while bins well above the noise pass unchanged. The gains are smoothed over time,
+//! which hides the "musical noise" of bins that flicker on and off.
+//! ```rust
+//! use rabu::noise_reduction::NoiseReducer;
+//! use rabu::signals::white_noise;
+//! use rabu::units::{
+//!     Channels, Duration, NormalizedValue, SampleRate, Samples, TimePoint, TimeSection,
+//! };
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let mut recording = white_noise::<f32>(Channels::from(1), Samples::from(48000), 1);
+//!
+//! let mut reducer = NoiseReducer::new(Channels::from(1), sample_rate);
+//! let noise_only = TimeSection {
+//!     start: TimePoint::from_secs_f64(0.0),
+//!     duration: Duration::from_secs_f64(0.5),
+//! };
+//! assert!(reducer.learn_noise(&recording, noise_only));
+//!
+//! reducer.set_strength(NormalizedValue::from(0.8));
+//! reducer.process(&mut recording);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::fft::RealFft;
+use crate::sample::Sample;
+use crate::stft::Stft;
+use crate::units::{
+    Channels, Latency, NormalizedValue, SampleRate, SampleSection, Samples, TimeSection,
+};
+use crate::windows::Window;
+
+/// The size of the frames the noise reducer analyses.
+pub const FFT_SIZE: usize = 2048;
+
+/// The number of samples between the starts of two frames.
+pub const HOP_SIZE: usize = FFT_SIZE / 4;
+
+/// How many times the learned noise is subtracted. The power of noise in a bin varies a lot
+/// from frame to frame, so subtracting it only once leaves most of it.
+const OVER_SUBTRACTION: f64 = 4.0;
+
+/// Removes steady noise from audio, using the spectrum of the noise it learned.
+#[derive(Clone, Debug)]
+pub struct NoiseReducer {
+    stft: Stft,
+    sample_rate: SampleRate,
+    /// The average power of the noise per bin, or empty when no noise was learned yet.
+    noise_profile: Vec<f64>,
+    /// The smoothed gains of the bins of every channel.
+    gains: Vec<Vec<f64>>,
+    strength: NormalizedValue,
+    smoothing: NormalizedValue,
+}
+
+impl NoiseReducer {
+    /// Creates a noise reducer for the number of channels, which doesn't know the noise yet.
+    /// Until it learns the noise, it only delays the audio by its latency.
+    /// The strength is 100% and the smoothing 50%.
+    pub fn new(num_channels: Channels, sample_rate: SampleRate) -> Self {
+        let stft = Stft::new(
+            num_channels,
+            Samples::from(FFT_SIZE),
+            Samples::from(HOP_SIZE),
+            Window::Hann,
+        );
+        let num_bins = stft.num_bins().as_usize();
+        Self {
+            stft,
+            sample_rate,
+            noise_profile: Vec::new(),
+            gains: vec![vec![1.0; num_bins]; num_channels.as_usize()],
+            strength: NormalizedValue::from(1.0),
+            smoothing: NormalizedValue::from(0.5),
+        }
+    }
+
+    /// Learns the spectrum of the noise from the section of the buffer, averaged over its
+    /// channels. The section should contain only noise, and it needs to be at least a frame
+    /// long. Gives back `false` when the part of the section within the buffer is too short,
+    /// in which case the noise it knew is kept.
+    /// This doesn't allocate the frames in advance, so don't call it on the audio thread.
+    pub fn learn_noise<T: Sample>(&mut self, buffer: &Buffer<T>, section: TimeSection) -> bool {
+        let buffer_section = SampleSection {
+            start: Samples::from(0),
+            length: buffer.num_samples(),
+        };
+        let section = match SampleSection::from_time_section(section, self.sample_rate)
+            .get_overlap(buffer_section)
+        {
+            Some(section) if section.length.as_usize() >= FFT_SIZE => section,
+            _ => return false,
+        };
+
+        let mut fft = RealFft::new(Samples::from(FFT_SIZE));
+        let mut window = vec![0.0; FFT_SIZE];
+        Window::Hann.fill_periodic(&mut window);
+        let mut frame = vec![0.0; FFT_SIZE];
+        let mut spectrum = vec![Default::default(); fft.num_bins().as_usize()];
+        let mut profile = vec![0.0; spectrum.len()];
+
+        let start = section.start.as_usize();
+        let num_frames = (section.length.as_usize() - FFT_SIZE) / HOP_SIZE + 1;
+        for channel in buffer.iter_chans() {
+            for index in 0..num_frames {
+                let offset = start + index * HOP_SIZE;
+                let samples = &channel[offset..offset + FFT_SIZE];
+                for ((value, sample), w) in frame.iter_mut().zip(samples).zip(&window) {
+                    *value = sample.to_f64() * w;
+                }
+                fft.forward(&frame, &mut spectrum);
+                for (power, bin) in profile.iter_mut().zip(&spectrum) {
+                    *power += bin.norm_sqr();
+                }
+            }
+        }
+
+        let num_frames = (num_frames * buffer.num_channels().as_usize()) as f64;
+        profile.iter_mut().for_each(|power| *power /= num_frames);
+        self.noise_profile = profile;
+        true
+    }
+
+    /// Tells whether the noise reducer learned the noise.
+    pub fn has_noise_profile(&self) -> bool {
+        !self.noise_profile.is_empty()
+    }
+
+    /// Forgets the noise, after which the audio is only delayed.
+    pub fn clear_noise_profile(&mut self) {
+        self.noise_profile.clear();
+    }
+
+    /// Returns how much of the noise is removed.
+    pub fn strength(&self) -> NormalizedValue {
+        self.strength
+    }
+
+    /// Sets how much of the noise is removed: at 0 the audio passes unchanged, and at 1 the
+    /// bins get their full Wiener gain.
+    pub fn set_strength(&mut self, strength: NormalizedValue) {
+        self.strength = strength;
+    }
+
+    /// Returns how much the gains are smoothed over time.
+    pub fn smoothing(&self) -> NormalizedValue {
+        self.smoothing
+    }
+
+    /// Sets how much the gains are smoothed over time: at 0 every frame gets its own gains, and
+    /// closer to 1 the gains follow the audio ever more slowly.
+    pub fn set_smoothing(&mut self, smoothing: NormalizedValue) {
+        self.smoothing = smoothing;
+    }
+
+    /// Returns the delay between input and output.
+    pub fn latency(&self) -> Latency {
+        self.stft.latency(self.sample_rate)
+    }
+
+    /// Removes the noise from the buffer in place.
+    /// This will panic if the buffer doesn't have the same number of channels as the reducer.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        let Self {
+            stft,
+            noise_profile,
+            gains,
+            strength,
+            smoothing,
+            ..
+        } = self;
+        if noise_profile.is_empty() {
+            stft.process(buffer, |_, _| {});
+            return;
+        }
+
+        let strength = strength.as_f64();
+        let smoothing = smoothing.as_f64();
+        stft.process(buffer, |channel, bins| {
+            let channel_gains = &mut gains[channel];
+            for ((bin, gain), noise) in bins.iter_mut().zip(channel_gains).zip(&*noise_profile) {
+                let power = bin.norm_sqr();
+                let wiener = if power > 0.0 {
+                    (1.0 - OVER_SUBTRACTION * noise / power).max(0.0)
+                } else {
+                    0.0
+                };
+                let target = 1.0 - strength * (1.0 - wiener);
+                *gain = smoothing * *gain + (1.0 - smoothing) * target;
+                *bin = *bin * *gain;
+            }
+        });
+    }
+
+    /// Clears the audio and the gains, as if no audio was processed yet. The noise it learned
+    /// is kept.
+    pub fn reset(&mut self) {
+        self.stft.reset();
+        self.gains.iter_mut().for_each(|gains| gains.fill(1.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signals::{sine, white_noise};
+    use crate::units::{Duration, Frequency, TimePoint};
+
+    const SAMPLE_RATE: usize = 48000;
+
+    fn section(start: f64, duration: f64) -> TimeSection {
+        TimeSection {
+            start: TimePoint::from_secs_f64(start),
+            duration: Duration::from_secs_f64(duration),
+        }
+    }
+
+    fn rms(samples: &[f64]) -> f64 {
+        (samples.iter().map(|s| s * s).sum::<f64>() / samples.len() as f64).sqrt()
+    }
+
+    /// A second of noise at -40 dB, with a loud sine in the second half.
+    fn recording() -> (Buffer<f64>, Buffer<f64>) {
+        let sample_rate = SampleRate::from(SAMPLE_RATE);
+        let num_samples = Samples::from(SAMPLE_RATE);
+        let mut tone = sine::<f64>(
+            Channels::from(1),
+            num_samples,
+            Frequency::from(1000.0),
+            sample_rate,
+        );
+        tone.chan_mut(0)[..SAMPLE_RATE / 2].fill(0.0);
+        let mut recording = white_noise::<f64>(Channels::from(1), num_samples, 7);
+        for (sample, tone) in recording.chan_mut(0).iter_mut().zip(tone.chan(0)) {
+            *sample = *sample * 0.01 + tone * 0.5;
+        }
+        (recording, tone)
+    }
+
+    #[test]
+    fn removes_the_noise_and_keeps_the_tone() {
+        let (mut recording, tone) = recording();
+        let mut reducer = NoiseReducer::new(Channels::from(1), SampleRate::from(SAMPLE_RATE));
+        assert!(reducer.learn_noise(&recording, section(0.0, 0.4)));
+
+        let noise_before = rms(&recording.chan(0)[8000..20000]);
+        reducer.process(&mut recording);
+
+        let output = &recording.chan(0)[FFT_SIZE..];
+        let noise_after = rms(&output[8000..20000]);
+        assert!(noise_after < noise_before * 0.1);
+
+        let tone = &tone.chan(0)[30000..44000];
+        let residual: Vec<_> = output[30000..44000]
+            .iter()
+            .zip(tone)
+            .map(|(output, tone)| output - tone * 0.5)
+            .collect();
+        assert!(rms(&residual) < rms(tone) * 0.5 * 0.05);
+    }
+
+    #[test]
+    fn without_a_profile_the_audio_is_only_delayed() {
+        let (mut recording, _) = recording();
+        let original = recording.clone();
+        let mut reducer = NoiseReducer::new(Channels::from(1), SampleRate::from(SAMPLE_RATE));
+
+        reducer.process(&mut recording);
+
+        assert!(!reducer.has_noise_profile());
+        for (output, input) in recording.chan(0)[FFT_SIZE..].iter().zip(original.chan(0)) {
+            assert!((output - input).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn zero_strength_leaves_the_audio_unchanged() {
+        let (mut recording, _) = recording();
+        let original = recording.clone();
+        let mut reducer = NoiseReducer::new(Channels::from(1), SampleRate::from(SAMPLE_RATE));
+        reducer.learn_noise(&recording, section(0.0, 0.4));
+        reducer.set_strength(NormalizedValue::from(0.0));
+
+        reducer.process(&mut recording);
+
+        for (output, input) in recording.chan(0)[FFT_SIZE..].iter().zip(original.chan(0)) {
+            assert!((output - input).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn a_section_shorter_than_a_frame_is_not_learned() {
+        let (recording, _) = recording();
+        let mut reducer = NoiseReducer::new(Channels::from(1), SampleRate::from(SAMPLE_RATE));
+
+        assert!(!reducer.learn_noise(&recording, section(0.0, 0.01)));
+        assert!(!reducer.learn_noise(&recording, section(0.99, 0.5)));
+        assert!(!reducer.has_noise_profile());
+    }
+}
@@ -0,0 +1,189 @@
+//! This module contains loudness normalization, the way audio is usually prepared for
+//! streaming platforms and broadcast: the whole buffer is measured with a BS.1770 loudness
+//! meter, and turned up or down to reach a target loudness. Turning up can push the peaks
+//! above a true peak ceiling, so a true peak limiter can catch those, at the cost of lowering
+//! the loudness slightly.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::normalization::normalize_loudness;
+//! use rabu::units::{Channels, Decibels, Lufs, SampleRate, Samples};
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(48000));
+//! for channel in buffer.iter_chans_mut() {
+//!     for (n, sample) in channel.iter_mut().enumerate() {
+//!         *sample = 0.05 * (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / 48000.0).sin();
+//!     }
+//! }
+//!
+//! let normalization = normalize_loudness(
+//!     &mut buffer,
+//!     sample_rate,
+//!     Lufs::from(-14.0),
+//!     Some(Decibels::from(-1.0)),
+//! );
+//!
+//! assert!(normalization.gain.as_f64() > 0.0);
+//! assert!((normalization.after.integrated.as_f64() + 14.0).abs() < 0.1);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::dynamics::SILENCE_DB;
+use crate::limiter::Limiter;
+use crate::loudness::LoudnessMeter;
+use crate::sample::Sample;
+use crate::units::{Decibels, Lufs, SampleRate, Seconds};
+
+/// The loudness measurements of a whole buffer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LoudnessStats {
+    /// The integrated loudness.
+    pub integrated: Lufs,
+    /// The highest true peak of all channels, in dBTP.
+    pub true_peak: Decibels,
+    /// The loudness range, in LU.
+    pub loudness_range: Decibels,
+}
+
+/// The outcome of a loudness normalization.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Normalization {
+    /// The gain that was applied, not counting the gain reduction of the limiter.
+    pub gain: Decibels,
+    /// The measurements before normalizing.
+    pub before: LoudnessStats,
+    /// The measurements after normalizing.
+    pub after: LoudnessStats,
+}
+
+/// Measures the loudness of the whole buffer.
+pub fn measure_loudness<T: Sample>(buffer: &Buffer<T>, sample_rate: SampleRate) -> LoudnessStats {
+    let mut meter = LoudnessMeter::new(buffer.num_channels(), sample_rate);
+    meter.set_true_peak(true);
+    meter.process(buffer);
+    LoudnessStats {
+        integrated: meter.integrated(),
+        true_peak: meter.true_peak().unwrap(),
+        loudness_range: meter.loudness_range(),
+    }
+}
+
+/// Turns the buffer up or down to the target integrated loudness. With a ceiling, a true peak
+/// limiter keeps the true peaks below it, which can leave the loudness a little under the
+/// target. A buffer without any measurable loudness (silence, or shorter than the 400 ms of a
+/// single block) is left as it is.
+pub fn normalize_loudness<T: Sample>(
+    buffer: &mut Buffer<T>,
+    sample_rate: SampleRate,
+    target: Lufs,
+    true_peak_ceiling: Option<Decibels>,
+) -> Normalization {
+    let before = measure_loudness(buffer, sample_rate);
+    if before.integrated.as_f64() <= SILENCE_DB {
+        return Normalization {
+            gain: Decibels::from(0.0),
+            before,
+            after: before,
+        };
+    }
+
+    let gain = target - before.integrated;
+    let linear_gain = gain.to_gain();
+    buffer.map_samples(|sample| T::from_f64(sample.to_f64() * linear_gain));
+
+    if let Some(ceiling) = true_peak_ceiling {
+        limit(buffer, sample_rate, ceiling);
+    }
+
+    Normalization {
+        gain,
+        before,
+        after: measure_loudness(buffer, sample_rate),
+    }
+}
+
+/// Runs a true peak limiter over the buffer, without delaying it: the limiter is fed silence
+/// for its latency at the end, and its output is moved back by the latency.
+fn limit<T: Sample>(buffer: &mut Buffer<T>, sample_rate: SampleRate, ceiling: Decibels) {
+    let mut limiter = Limiter::new(buffer.num_channels(), sample_rate, Seconds::from(0.005));
+    limiter.set_ceiling(ceiling);
+    limiter.set_true_peak(true);
+    let latency = limiter.latency().as_seconds().to_samples(sample_rate);
+
+    let mut padded = Buffer::<f64>::allocate(buffer.num_channels(), buffer.num_samples() + latency);
+    for (padded, channel) in padded.iter_chans_mut().zip(buffer.iter_chans()) {
+        for (padded, sample) in padded.iter_mut().zip(channel) {
+            *padded = sample.to_f64();
+        }
+    }
+    limiter.process(&mut padded);
+
+    for (channel, padded) in buffer.iter_chans_mut().zip(padded.iter_chans()) {
+        for (sample, limited) in channel.iter_mut().zip(&padded[latency.as_usize()..]) {
+            *sample = T::from_f64(*limited);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{Channels, Samples};
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn sine(amplitude: f64, length: usize) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(length));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            let phase = 2.0 * std::f64::consts::PI * 1000.0 * n as f64 / SAMPLE_RATE as f64;
+            *sample = amplitude * phase.sin();
+        }
+        buffer
+    }
+
+    #[test]
+    fn reaches_the_target_loudness() {
+        let mut buffer = sine(0.01, 24000);
+
+        let normalization = normalize_loudness(
+            &mut buffer,
+            SampleRate::from(SAMPLE_RATE),
+            Lufs::from(-20.0),
+            None,
+        );
+
+        assert!((normalization.after.integrated.as_f64() + 20.0).abs() < 0.05);
+        let expected_gain = Lufs::from(-20.0) - normalization.before.integrated;
+        assert_eq!(normalization.gain, expected_gain);
+    }
+
+    #[test]
+    fn keeps_the_true_peak_below_the_ceiling() {
+        let mut buffer = sine(0.1, 24000);
+
+        let normalization = normalize_loudness(
+            &mut buffer,
+            SampleRate::from(SAMPLE_RATE),
+            Lufs::from(-5.0),
+            Some(Decibels::from(-1.0)),
+        );
+
+        assert!(normalization.after.true_peak.as_f64() < -0.9);
+        assert!(normalization.after.integrated.as_f64() < -5.0);
+    }
+
+    #[test]
+    fn leaves_silence_as_it_is() {
+        let mut buffer = sine(0.0, 24000);
+
+        let normalization = normalize_loudness(
+            &mut buffer,
+            SampleRate::from(SAMPLE_RATE),
+            Lufs::from(-14.0),
+            Some(Decibels::from(-1.0)),
+        );
+
+        assert_eq!(normalization.gain, Decibels::from(0.0));
+        assert!(buffer.is_default_filled());
+    }
+}
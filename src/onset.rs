@@ -0,0 +1,353 @@
+//! This module contains onset detection with spectral flux: how much the log-compressed
+//! magnitude spectrum grew since the previous frame, counting only the bins that got louder.
+//! A note or hit makes the flux jump, so onsets are the peaks of the flux that rise above its
+//! recent average by the threshold, and come at least the minimum interval after the previous
+//! onset. Multichannel audio is mixed down to mono before analysis.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::onset::OnsetDetector;
+//! use rabu::units::{Channels, SampleRate, Samples};
+//!
+//! let mut detector = OnsetDetector::new(SampleRate::from(48000), Samples::from(1024), Samples::from(256));
+//!
+//! // Silence, with a decaying click after half a second.
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(1), Samples::from(48000));
+//! for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate().skip(24000) {
+//!     *sample = (0.3 * n as f32).sin() * (-((n - 24000) as f32) / 2000.0).exp();
+//! }
+//!
+//! let onsets = detector.detect(&buffer);
+//! assert_eq!(onsets.len(), 1);
+//! assert!((onsets[0].as_usize() as i64 - 24000).abs() < 512);
+//! ```
+
+use std::collections::VecDeque;
+
+use crate::buffer::Buffer;
+use crate::fft::{Complex, RealFft};
+use crate::sample::Sample;
+use crate::units::{SampleRate, Samples, Seconds, TimePoint};
+use crate::windows::Window;
+
+/// How much the magnitudes are boosted before taking the logarithm. Larger values make quiet
+/// changes count more.
+const COMPRESSION: f64 = 1000.0;
+/// The number of frames the flux is averaged over for the adaptive threshold.
+const AVERAGE_FRAMES: usize = 8;
+
+/// Finds the onsets in audio, offline or as it streams in.
+#[derive(Clone, Debug)]
+pub struct OnsetDetector {
+    sample_rate: SampleRate,
+    fft: RealFft,
+    window: Vec<f64>,
+    hop_size: usize,
+    threshold: f64,
+    min_interval: Seconds,
+    input: Vec<f64>,
+    position: usize,
+    frame: Vec<f64>,
+    spectrum: Vec<Complex>,
+    magnitudes: Vec<f64>,
+    /// The flux of the last frames, the newest at the back, for the adaptive threshold.
+    history: VecDeque<f64>,
+    /// The flux of the two frames before the current one, to find the peaks.
+    previous: (f64, f64),
+    /// The number of samples processed since the start or the last reset.
+    processed: usize,
+    last_onset: Option<usize>,
+}
+
+impl OnsetDetector {
+    /// Creates a new detector that analyzes frames of `fft_size` samples every `hop_size`
+    /// samples, with a threshold of 0.2 and a minimum interval of 50 ms. The hop size is the
+    /// resolution of the onset positions.
+    /// This will panic if the FFT size is not a power of two, or if the hop size is zero or
+    /// larger than the FFT size.
+    pub fn new(sample_rate: SampleRate, fft_size: Samples, hop_size: Samples) -> Self {
+        let size = fft_size.as_usize();
+        let hop = hop_size.as_usize();
+        assert!(
+            hop > 0 && hop <= size,
+            "hop size must be between 1 and the FFT size"
+        );
+        let mut window = vec![0.0; size];
+        Window::Hann.fill_periodic(&mut window);
+        let fft = RealFft::new(fft_size);
+        let num_bins = fft.num_bins().as_usize();
+
+        Self {
+            sample_rate,
+            fft,
+            window,
+            hop_size: hop,
+            threshold: 0.2,
+            min_interval: Seconds::from(0.05),
+            input: vec![0.0; size],
+            position: size - hop,
+            frame: vec![0.0; size],
+            spectrum: vec![Complex::default(); num_bins],
+            magnitudes: vec![0.0; num_bins],
+            history: VecDeque::with_capacity(AVERAGE_FRAMES),
+            previous: (0.0, 0.0),
+            processed: 0,
+            last_onset: None,
+        }
+    }
+
+    /// Returns the threshold.
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Changes how far the flux must rise above its recent average to count as an onset.
+    /// Lower thresholds make the detector more sensitive, and pick up softer onsets, but also
+    /// more false ones. Usual values are between 0.05 and 1.
+    pub fn set_threshold(&mut self, threshold: f64) {
+        self.threshold = threshold.max(0.0);
+    }
+
+    /// Returns the minimum interval.
+    pub fn min_interval(&self) -> Seconds {
+        self.min_interval
+    }
+
+    /// Changes the shortest time between two onsets. Onsets that follow the previous one
+    /// sooner are ignored, which keeps a single hit with a rough attack from counting twice.
+    pub fn set_min_interval(&mut self, min_interval: Seconds) {
+        self.min_interval = min_interval;
+    }
+
+    /// Clears the internal state, as if no audio was processed yet.
+    pub fn reset(&mut self) {
+        self.input.fill(0.0);
+        self.position = self.input.len() - self.hop_size;
+        self.magnitudes.fill(0.0);
+        self.history.clear();
+        self.previous = (0.0, 0.0);
+        self.processed = 0;
+        self.last_onset = None;
+    }
+
+    /// Analyzes the buffer as the next part of the stream, and calls the closure with the
+    /// position of every onset found, counted from the start of the stream. An onset is found
+    /// one hop after the frame it's in, so it can be told apart from a rise that goes on.
+    pub fn process<T: Sample>(&mut self, buffer: &Buffer<T>, mut on_onset: impl FnMut(Samples)) {
+        self.feed(buffer, |detector| {
+            let flux = detector.flux();
+            if let Some(onset) = detector.pick(flux) {
+                on_onset(Samples::from(onset));
+            }
+        });
+    }
+
+    /// Returns the spectral flux of every frame of the buffer, which rises wherever something
+    /// starts: the onset strength. Frame `i` ends at sample `(i + 1) * hop_size`. This resets
+    /// the detector first.
+    pub fn onset_strength<T: Sample>(&mut self, buffer: &Buffer<T>) -> Vec<f64> {
+        self.reset();
+        let mut strength = Vec::new();
+        self.feed(buffer, |detector| strength.push(detector.flux()));
+        strength
+    }
+
+    /// Returns the positions of all onsets in the buffer. This resets the detector first, and
+    /// adds one hop of silence at the end to confirm an onset in the last frame too.
+    pub fn detect<T: Sample>(&mut self, buffer: &Buffer<T>) -> Vec<Samples> {
+        self.reset();
+        let mut onsets = Vec::new();
+        self.process(buffer, |onset| onsets.push(onset));
+        let tail = Buffer::<f64>::allocate(buffer.num_channels(), Samples::from(self.hop_size));
+        self.process(&tail, |onset| onsets.push(onset));
+        onsets
+    }
+
+    /// Returns the times of all onsets in the buffer, like `detect`.
+    pub fn detect_times<T: Sample>(&mut self, buffer: &Buffer<T>) -> Vec<TimePoint> {
+        let sample_rate = self.sample_rate;
+        self.detect(buffer)
+            .into_iter()
+            .map(|onset| TimePoint::from(onset.to_seconds(sample_rate)))
+            .collect()
+    }
+
+    /// Adds the buffer to the input, mixed down to mono, and calls the closure every time a
+    /// frame is complete.
+    fn feed<T: Sample>(&mut self, buffer: &Buffer<T>, mut on_frame: impl FnMut(&mut Self)) {
+        let channels = buffer.num_channels().as_usize() as f64;
+        let size = self.input.len();
+        for index in buffer.sample_indices() {
+            self.input[self.position] = buffer
+                .iter_chans()
+                .map(|channel| channel[index].to_f64())
+                .sum::<f64>()
+                / channels;
+            self.position += 1;
+            self.processed += 1;
+
+            if self.position == size {
+                self.position = size - self.hop_size;
+                on_frame(self);
+                self.input.copy_within(self.hop_size.., 0);
+            }
+        }
+    }
+
+    /// Computes the flux of the frame that was just completed.
+    fn flux(&mut self) -> f64 {
+        for ((value, input), w) in self.frame.iter_mut().zip(&self.input).zip(&self.window) {
+            *value = input * w;
+        }
+        self.fft.forward(&self.frame, &mut self.spectrum);
+
+        // Scaled so a full scale sine has a magnitude of 1.
+        let scale = 4.0 / self.input.len() as f64;
+        let mut flux = 0.0;
+        for (magnitude, bin) in self.magnitudes.iter_mut().zip(&self.spectrum) {
+            let compressed = (1.0 + COMPRESSION * scale * bin.norm()).ln();
+            flux += (compressed - *magnitude).max(0.0);
+            *magnitude = compressed;
+        }
+        flux / self.magnitudes.len() as f64
+    }
+
+    /// Takes the flux of the frame that was just completed, and gives the position of the
+    /// previous frame when its flux turns out to be an onset.
+    fn pick(&mut self, flux: f64) -> Option<usize> {
+        // The previous frame is an onset when it's a peak that rises enough above the average
+        // of the frames before it.
+        let (before, candidate) = self.previous;
+        let average = if self.history.len() > 1 {
+            self.history.iter().rev().skip(1).sum::<f64>() / (self.history.len() - 1) as f64
+        } else {
+            0.0
+        };
+        let is_peak = candidate > before && candidate >= flux;
+
+        self.previous = (candidate, flux);
+        if self.history.len() == AVERAGE_FRAMES {
+            self.history.pop_front();
+        }
+        self.history.push_back(flux);
+
+        // The center of the previous frame.
+        let position = self
+            .processed
+            .checked_sub(self.hop_size + self.input.len() / 2)?;
+        let min_interval = self.min_interval.to_samples(self.sample_rate).as_usize();
+        let is_spaced = self
+            .last_onset
+            .is_none_or(|last| position >= last + min_interval);
+        if is_peak && candidate > average + self.threshold && is_spaced {
+            self.last_onset = Some(position);
+            return Some(position);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::Random;
+    use crate::units::Channels;
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    fn detector() -> OnsetDetector {
+        OnsetDetector::new(
+            SampleRate::from(SAMPLE_RATE),
+            Samples::from(512),
+            Samples::from(128),
+        )
+    }
+
+    fn mono(samples: &[f64]) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(samples.len()));
+        buffer.chan_mut(0).copy_from_slice(samples);
+        buffer
+    }
+
+    /// Plucks at the positions: decaying sines with a little noise.
+    fn plucks(positions: &[usize], length: usize) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(length));
+        let mut random = Random::new(11);
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = 0.001 * random.next_bipolar();
+            for &start in positions.iter().filter(|&&start| start <= n) {
+                let t = (n - start) as f64 / SAMPLE_RATE as f64;
+                *sample += 0.5 * (2.0 * std::f64::consts::PI * 330.0 * t).sin() * (-t * 8.0).exp();
+            }
+        }
+        buffer
+    }
+
+    #[test]
+    fn finds_every_pluck() {
+        let positions = [2000, 6000, 9000, 14000];
+        let onsets = detector().detect(&plucks(&positions, 16000));
+
+        assert_eq!(onsets.len(), positions.len(), "{onsets:?}");
+        for (onset, position) in onsets.iter().zip(positions) {
+            assert!((onset.as_usize() as i64 - position as i64).abs() <= 256);
+        }
+    }
+
+    #[test]
+    fn steady_sound_has_no_onsets() {
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(1), Samples::from(16000));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = 0.5 * (0.1 * n as f64).sin();
+        }
+        let (start, steady) = buffer.chan(0).split_at(4000);
+        let mut detector = detector();
+        detector.process(&mono(start), |_| {});
+        let mut onsets = 0;
+
+        detector.process(&mono(steady), |_| onsets += 1);
+
+        assert_eq!(onsets, 0);
+    }
+
+    #[test]
+    fn min_interval_drops_close_onsets() {
+        let mut detector = detector();
+        detector.set_min_interval(Seconds::from(0.5));
+
+        let onsets = detector.detect(&plucks(&[2000, 4000, 12000], 16000));
+
+        assert_eq!(onsets.len(), 2);
+    }
+
+    #[test]
+    fn streaming_finds_the_same_onsets() {
+        let buffer = plucks(&[3000, 10000], 16000);
+        let offline = detector().detect(&buffer);
+        let mut streaming = Vec::new();
+        let mut detector = detector();
+
+        for start in (0..16000).step_by(100) {
+            let mut block = Buffer::allocate(Channels::from(1), Samples::from(100));
+            block
+                .chan_mut(0)
+                .copy_from_slice(&buffer.chan(0)[start..start + 100]);
+            detector.process(&block, |onset| streaming.push(onset));
+        }
+
+        assert_eq!(streaming, offline);
+    }
+
+    #[test]
+    fn onset_strength_peaks_at_the_pluck() {
+        let mut detector = detector();
+
+        let strength = detector.onset_strength(&plucks(&[4000], 8000));
+
+        let loudest = (0..strength.len())
+            .max_by(|&a, &b| strength[a].total_cmp(&strength[b]))
+            .unwrap();
+        // Frame `i` ends at `(i + 1) * 128`, and the pluck is in its second half.
+        let end = (loudest + 1) * 128;
+        assert!(end > 4000 && end <= 4000 + 256, "{end}");
+    }
+}
@@ -0,0 +1,351 @@
+//! This module contains an oscillator with the standard waveforms, for test tones, LFOs and
+//! simple synth voices. The phase is kept in cycles (between 0 and 1), so a phase offset of 0.25
+//! shifts the waveform by a quarter period.
+//! The naive waveforms alias badly at audio rates, so the oscillator can also be band-limited,
+//! which smooths the discontinuities with PolyBLEP (jumps) and PolyBLAMP (corners).
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::osc::{Oscillator, Waveform};
+//! use rabu::units::{Channels, Frequency, SampleRate, Samples};
+//!
+//! let mut osc = Oscillator::new(Waveform::Sine, Frequency::from(440.0), SampleRate::from(48000));
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! osc.render(&mut buffer);
+//!
+//! assert_eq!(buffer.chan(0), buffer.chan(1));
+//! ```
+
+use std::f64::consts::PI;
+
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::units::{Frequency, SampleRate};
+
+/// The shape of an oscillator. All waveforms run between -1 and 1.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Waveform {
+    Sine,
+    /// A triangle that is in phase with the sine: it starts at 0 and rises.
+    Triangle,
+    /// A rising sawtooth, starting at -1.
+    Saw,
+    Square,
+    /// A pulse that is high for the given part of the period, between 0 and 1.
+    /// A width of 0.5 is a square wave.
+    Pulse {
+        width: f64,
+    },
+}
+
+impl Waveform {
+    /// Returns the value of the waveform at the given phase, in cycles.
+    pub fn value_at(&self, phase: f64) -> f64 {
+        let phase = phase.rem_euclid(1.0);
+        match self {
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * ((phase + 0.25).rem_euclid(1.0) - 0.5).abs(),
+            Waveform::Saw => 2.0 * phase - 1.0,
+            Waveform::Square => Waveform::Pulse { width: 0.5 }.value_at(phase),
+            Waveform::Pulse { width } => {
+                if phase < *width {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+        }
+    }
+}
+
+/// An oscillator that produces one of the standard waveforms.
+#[derive(Clone, Debug)]
+pub struct Oscillator {
+    waveform: Waveform,
+    frequency: Frequency,
+    sample_rate: SampleRate,
+    increment: f64,
+    phase: f64,
+    phase_offset: f64,
+    band_limited: bool,
+}
+
+impl Oscillator {
+    /// Creates a new oscillator that starts at phase 0.
+    pub fn new(waveform: Waveform, frequency: Frequency, sample_rate: SampleRate) -> Self {
+        Self {
+            waveform,
+            frequency,
+            sample_rate,
+            increment: frequency.as_f64() / sample_rate.as_f64(),
+            phase: 0.0,
+            phase_offset: 0.0,
+            band_limited: false,
+        }
+    }
+
+    /// Returns the waveform.
+    pub fn waveform(&self) -> Waveform {
+        self.waveform
+    }
+
+    /// Changes the waveform, keeping the phase.
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+
+    /// Returns whether the waveforms are band-limited.
+    pub fn is_band_limited(&self) -> bool {
+        self.band_limited
+    }
+
+    /// Chooses between the naive waveforms, which are fine for LFOs, and band-limited
+    /// waveforms, which are needed for audio rate oscillators to not alias.
+    /// The sine is the same either way.
+    pub fn set_band_limited(&mut self, band_limited: bool) {
+        self.band_limited = band_limited;
+    }
+
+    /// Returns the frequency.
+    pub fn frequency(&self) -> Frequency {
+        self.frequency
+    }
+
+    /// Changes the frequency, keeping the phase.
+    pub fn set_frequency(&mut self, frequency: Frequency) {
+        self.frequency = frequency;
+        self.update_increment();
+    }
+
+    /// Changes the sample rate, keeping the phase.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = sample_rate;
+        self.update_increment();
+    }
+
+    /// Returns the current phase in cycles, between 0 and 1, without the phase offset.
+    pub fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    /// Jumps to the given phase in cycles.
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    /// Returns the phase offset in cycles.
+    pub fn phase_offset(&self) -> f64 {
+        self.phase_offset
+    }
+
+    /// Shifts the waveform by the given number of cycles, e.g. 0.25 turns a sine into a cosine.
+    /// This is useful to keep several oscillators at a fixed phase relation.
+    pub fn set_phase_offset(&mut self, offset: f64) {
+        self.phase_offset = offset;
+    }
+
+    /// Resets the phase to 0, so the next sample is the start of the waveform (plus the offset).
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    /// Produces the next sample.
+    pub fn next_sample(&mut self) -> f64 {
+        let phase = (self.phase + self.phase_offset).rem_euclid(1.0);
+        let mut value = self.waveform.value_at(phase);
+        if self.band_limited {
+            value += self.correction(phase);
+        }
+        self.phase = (self.phase + self.increment).rem_euclid(1.0);
+        value
+    }
+
+    /// Fills the given slice with the next samples.
+    pub fn render_into<T: Sample>(&mut self, output: &mut [T]) {
+        for sample in output.iter_mut() {
+            *sample = T::from_f64(self.next_sample());
+        }
+    }
+
+    /// Fills every channel of the buffer with the same next samples.
+    pub fn render<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        for index in buffer.sample_indices() {
+            let value = T::from_f64(self.next_sample());
+            for channel in buffer.iter_chans_mut() {
+                channel[index] = value;
+            }
+        }
+    }
+
+    /// Returns what needs to be added to the naive waveform to smooth out its discontinuities.
+    fn correction(&self, phase: f64) -> f64 {
+        let dt = self.increment.abs();
+        let distance = |edge: f64| (phase - edge).rem_euclid(1.0);
+        match self.waveform {
+            Waveform::Sine => 0.0,
+            Waveform::Saw => -poly_blep(phase, dt),
+            Waveform::Square => Self::pulse_correction(0.5, distance, dt),
+            Waveform::Pulse { width } => Self::pulse_correction(width, distance, dt),
+            // The slope changes by 8 per cycle at the top (0.25) and bottom (0.75).
+            Waveform::Triangle => {
+                4.0 * dt * (poly_blamp(distance(0.75), dt) - poly_blamp(distance(0.25), dt))
+            }
+        }
+    }
+
+    fn pulse_correction(width: f64, distance: impl Fn(f64) -> f64, dt: f64) -> f64 {
+        poly_blep(distance(0.0), dt) - poly_blep(distance(width), dt)
+    }
+
+    fn update_increment(&mut self) {
+        self.increment = self.frequency.as_f64() / self.sample_rate.as_f64();
+    }
+}
+
+/// The polynomial band-limited step residual for a jump of -2 at phase 0, where `t` is the
+/// phase in cycles and `dt` the phase increment per sample.
+fn poly_blep(t: f64, dt: f64) -> f64 {
+    if t < dt {
+        let x = t / dt;
+        2.0 * x - x * x - 1.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt;
+        x * x + 2.0 * x + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// The polynomial band-limited ramp residual (the integral of `poly_blep`) for a corner at
+/// phase 0, scaled to a change in slope of 1 per sample.
+fn poly_blamp(t: f64, dt: f64) -> f64 {
+    if t < dt {
+        let x = t / dt - 1.0;
+        -x * x * x / 3.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt + 1.0;
+        x * x * x / 3.0
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::fft::{Complex, RealFft};
+    use crate::units::Samples;
+
+    fn render(waveform: Waveform, num_samples: usize) -> Vec<f64> {
+        // A frequency of a quarter of the sample rate gives four samples per period.
+        let mut osc = Oscillator::new(waveform, Frequency::from(12000.0), SampleRate::from(48000));
+        let mut output = vec![0.0_f64; num_samples];
+        osc.render_into(&mut output);
+        output.iter().map(|v| (v * 1e9).round() / 1e9).collect()
+    }
+
+    #[test_case(Waveform::Sine => vec![0.0, 1.0, 0.0, -1.0, 0.0]; "sine")]
+    #[test_case(Waveform::Triangle => vec![0.0, 1.0, 0.0, -1.0, 0.0]; "triangle")]
+    #[test_case(Waveform::Saw => vec![-1.0, -0.5, 0.0, 0.5, -1.0]; "saw")]
+    #[test_case(Waveform::Square => vec![1.0, 1.0, -1.0, -1.0, 1.0]; "square")]
+    #[test_case(Waveform::Pulse { width: 0.25 } => vec![1.0, -1.0, -1.0, -1.0, 1.0]; "pulse")]
+    fn waveforms(waveform: Waveform) -> Vec<f64> {
+        render(waveform, 5)
+    }
+
+    #[test]
+    fn phase_offset_shifts_the_waveform() {
+        let mut osc = Oscillator::new(Waveform::Sine, Frequency::from(1.0), SampleRate::from(4));
+        osc.set_phase_offset(0.25);
+
+        assert!((osc.next_sample() - 1.0).abs() < 1e-12);
+        assert!((osc.phase() - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn reset_restarts_the_waveform() {
+        let mut osc = Oscillator::new(
+            Waveform::Saw,
+            Frequency::from(100.0),
+            SampleRate::from(48000),
+        );
+        let first = osc.next_sample();
+        osc.next_sample();
+
+        osc.reset();
+
+        assert_eq!(osc.next_sample(), first);
+    }
+
+    #[test]
+    fn frequency_change_keeps_the_phase() {
+        let mut osc = Oscillator::new(Waveform::Saw, Frequency::from(1.0), SampleRate::from(8));
+        osc.next_sample();
+        osc.next_sample();
+
+        osc.set_frequency(Frequency::from(2.0));
+
+        assert_eq!(osc.phase(), 0.25);
+        assert_eq!(osc.next_sample(), -0.5);
+        assert_eq!(osc.phase(), 0.5);
+    }
+
+    /// Returns the part of the energy that ends up in between the harmonics, which is where
+    /// aliasing lands. The frequency is chosen so every harmonic falls exactly on a bin.
+    fn aliasing(waveform: Waveform, band_limited: bool) -> f64 {
+        let harmonic_spacing = 233;
+        let frequency = 48000.0 * harmonic_spacing as f64 / 4096.0;
+        let mut osc = Oscillator::new(
+            waveform,
+            Frequency::from(frequency),
+            SampleRate::from(48000),
+        );
+        osc.set_band_limited(band_limited);
+
+        let mut signal = vec![0.0_f64; 4096];
+        osc.render_into(&mut signal);
+        let mut bins = vec![Complex::default(); 2049];
+        RealFft::new(Samples::from(4096)).forward(&signal, &mut bins);
+
+        let (aliased, total) =
+            bins.iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(aliased, total), (index, bin)| {
+                    let energy = bin.norm_sqr();
+                    let is_harmonic = index % harmonic_spacing == 0;
+                    (
+                        aliased + if is_harmonic { 0.0 } else { energy },
+                        total + energy,
+                    )
+                });
+        aliased / total
+    }
+
+    #[test_case(Waveform::Saw; "saw")]
+    #[test_case(Waveform::Square; "square")]
+    #[test_case(Waveform::Pulse { width: 0.3 }; "pulse")]
+    #[test_case(Waveform::Triangle; "triangle")]
+    fn band_limited_waveforms_alias_less(waveform: Waveform) {
+        let naive = aliasing(waveform, false);
+        let band_limited = aliasing(waveform, true);
+
+        assert!(band_limited < naive * 0.25, "{band_limited} vs {naive}");
+    }
+
+    #[test]
+    fn band_limited_sine_is_unchanged() {
+        let mut naive = Oscillator::new(
+            Waveform::Sine,
+            Frequency::from(997.0),
+            SampleRate::from(48000),
+        );
+        let mut band_limited = naive.clone();
+        band_limited.set_band_limited(true);
+
+        for _ in 0..100 {
+            assert_eq!(naive.next_sample(), band_limited.next_sample());
+        }
+    }
+}
@@ -0,0 +1,487 @@
+//! This module contains an oversampler, which runs a nonlinear process (like a waveshaper or
+//! clipper) at a multiple of the sample rate. The harmonics the process creates then have room
+//! above the original Nyquist frequency, where they are filtered out before going back down,
+//! instead of folding back into the audible range as aliasing. The `HalfbandOversampler` gets
+//! to a power of two with a cascade of halfband filters, which are cheaper, because half of
+//! their taps are 0.
+//! ```rust
+//! use rabu::oversampling::Oversampler;
+//! use rabu::units::Samples;
+//!
+//! let mut oversampler = Oversampler::new(4);
+//!
+//! let output = oversampler.process(0.5, |sample| sample.clamp(-0.3, 0.3));
+//!
+//! assert_eq!(oversampler.latency_samples(), Samples::from(48));
+//! ```
+//! ```rust
+//! use rabu::oversampling::HalfbandOversampler;
+//! use rabu::units::Samples;
+//!
+//! let mut oversampler = HalfbandOversampler::new(4);
+//!
+//! let output = oversampler.process(0.5, |sample| sample.tanh());
+//!
+//! assert_eq!(oversampler.latency_samples(), Samples::from(55));
+//! ```
+
+use crate::fir::{windowed_sinc_low_pass, FirFilter};
+use crate::units::{Frequency, Latency, SampleRate, Samples};
+use crate::windows::Window;
+
+/// Runs a mono process at a multiple of the sample rate.
+#[derive(Clone, Debug)]
+pub struct Oversampler {
+    factor: usize,
+    up: Upsampler,
+    down: FirFilter,
+}
+
+/// The upsampling filter, split into one set of taps per phase, so the zeros that would be
+/// stuffed in between the input samples are never multiplied.
+#[derive(Clone, Debug)]
+struct Upsampler {
+    phases: Vec<Vec<f64>>,
+    history: Vec<f64>,
+    position: usize,
+}
+
+impl Upsampler {
+    fn new(taps: &[f64], factor: usize) -> Self {
+        // Zero stuffing lowers the level by the factor, which is made up for here. The taps
+        // are reversed, so they line up with the history, which runs from old to new.
+        let phases: Vec<Vec<f64>> = (0..factor)
+            .map(|phase| {
+                let mut phase_taps: Vec<f64> = taps
+                    .iter()
+                    .skip(phase)
+                    .step_by(factor)
+                    .map(|tap| tap * factor as f64)
+                    .collect();
+                phase_taps.reverse();
+                phase_taps
+            })
+            .collect();
+        Self {
+            // The history is stored twice, so the last samples are always one slice.
+            history: vec![0.0; 2 * phases[0].len()],
+            phases,
+            position: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.position = 0;
+    }
+
+    fn process(&mut self, input: f64, mut each: impl FnMut(f64)) {
+        let length = self.history.len() / 2;
+        self.history[self.position] = input;
+        self.history[self.position + length] = input;
+        self.position = (self.position + 1) % length;
+        let recent = &self.history[self.position..self.position + length];
+
+        for taps in self.phases.iter() {
+            // Shorter phases leave out the oldest sample.
+            let recent = &recent[length - taps.len()..];
+            each(
+                taps.iter()
+                    .zip(recent)
+                    .map(|(tap, sample)| tap * sample)
+                    .sum(),
+            );
+        }
+    }
+}
+
+impl Oversampler {
+    /// The number of input samples the filters reach on either side.
+    const HALF_WIDTH: usize = 24;
+
+    /// Creates a new oversampler for the given factor. A factor of 1 runs the process as is.
+    /// This will panic if the factor is 0.
+    pub fn new(factor: usize) -> Self {
+        assert!(factor > 0, "the oversampling factor must be at least 1");
+        // Only the ratio between the cutoff and the sample rate matters, so any rate will do.
+        let rate = 48000 * factor as u32;
+        let taps = windowed_sinc_low_pass(
+            SampleRate::from(rate),
+            Frequency::from(0.45 * 48000.0),
+            Samples::from(2 * Self::HALF_WIDTH * factor + 1),
+            Window::Blackman,
+        );
+        Self {
+            factor,
+            up: Upsampler::new(&taps, factor),
+            down: FirFilter::new(taps),
+        }
+    }
+
+    /// Returns the oversampling factor.
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    /// Returns the delay of the filters in samples at the original rate.
+    pub fn latency_samples(&self) -> Samples {
+        if self.factor == 1 {
+            Samples::from(0)
+        } else {
+            Samples::from(2 * Self::HALF_WIDTH)
+        }
+    }
+
+    /// Returns the delay of the filters.
+    pub fn latency(&self, sample_rate: SampleRate) -> Latency {
+        Latency::from(self.latency_samples().to_seconds(sample_rate))
+    }
+
+    /// Clears the internal state, as if no audio was processed yet.
+    pub fn reset(&mut self) {
+        self.up.reset();
+        self.down.reset();
+    }
+
+    /// Upsamples one input sample, and hands every oversampled sample to the closure. This
+    /// only uses the upsampling filter, whose delay is half of `latency_samples`.
+    pub fn upsample(&mut self, input: f64, mut each: impl FnMut(f64)) {
+        if self.factor == 1 {
+            return each(input);
+        }
+        self.up.process(input, each);
+    }
+
+    /// Processes one input sample: upsamples it, runs the process on every oversampled
+    /// sample, and downsamples the result again.
+    pub fn process(&mut self, input: f64, mut process: impl FnMut(f64) -> f64) -> f64 {
+        if self.factor == 1 {
+            return process(input);
+        }
+        let mut output = None;
+        let down = &mut self.down;
+        self.up.process(input, |upsampled| {
+            let filtered = down.process(process(upsampled));
+            // Only the first of every factor samples is kept.
+            output.get_or_insert(filtered);
+        });
+        output.unwrap_or_default()
+    }
+}
+
+/// One stage of a halfband oversampler, which doubles the rate on the way up, and halves it
+/// again on the way down. The filter has a tap of 0.5 in the center, and every other tap is 0,
+/// except the ones at an odd distance from the center.
+#[derive(Clone, Debug)]
+struct HalfbandStage {
+    /// The taps at an odd distance from the center, from the center outwards.
+    taps: Vec<f64>,
+    /// The last input samples on the way up, newest first from the position.
+    up: Vec<f64>,
+    up_position: usize,
+    /// The last even samples on the way down, newest first from the position.
+    even: Vec<f64>,
+    even_position: usize,
+    /// The last odd samples on the way down, of which only the oldest is used.
+    odd: Vec<f64>,
+    odd_position: usize,
+}
+
+impl HalfbandStage {
+    fn new(half_width: usize) -> Self {
+        let length = 4 * half_width - 1;
+        let center = 2 * half_width - 1;
+        let mut taps: Vec<f64> = (0..half_width)
+            .map(|j| {
+                let distance = 2 * j + 1;
+                let sign = if j % 2 == 0 { 1.0 } else { -1.0 };
+                let sinc = sign / (std::f64::consts::PI * distance as f64);
+                // The window reaches one sample further, so the outer taps aren't 0.
+                sinc * Window::Blackman.value(center + distance + 1, length + 2)
+            })
+            .collect();
+        // Together with the center, the taps on both sides add up to a gain of 1.
+        let sum: f64 = taps.iter().sum();
+        taps.iter_mut().for_each(|tap| *tap *= 0.25 / sum);
+        Self {
+            taps,
+            up: vec![0.0; 2 * half_width],
+            up_position: 0,
+            even: vec![0.0; 2 * half_width],
+            even_position: 0,
+            odd: vec![0.0; half_width],
+            odd_position: 0,
+        }
+    }
+
+    /// Returns the delay of one filter, in samples at the higher rate.
+    fn delay(&self) -> usize {
+        2 * self.taps.len() - 1
+    }
+
+    fn reset(&mut self) {
+        self.up.fill(0.0);
+        self.even.fill(0.0);
+        self.odd.fill(0.0);
+    }
+
+    /// Sums the taps with the pairs of samples that are the same distance from the center,
+    /// where `delayed(d)` returns the sample of `d` samples ago.
+    fn symmetric_sum(&self, delayed: impl Fn(usize) -> f64) -> f64 {
+        let half_width = self.taps.len();
+        self.taps
+            .iter()
+            .enumerate()
+            .map(|(j, tap)| tap * (delayed(half_width - 1 - j) + delayed(half_width + j)))
+            .sum()
+    }
+
+    /// Turns one input sample into two samples at the doubled rate.
+    fn upsample(&mut self, input: f64) -> (f64, f64) {
+        let length = self.up.len();
+        self.up_position = (self.up_position + 1) % length;
+        self.up[self.up_position] = input;
+        let up = &self.up;
+        let position = self.up_position;
+        let delayed = |delay: usize| up[(position + length - delay) % length];
+        // Zero stuffing lowers the level by 2, which is made up for here. The odd samples
+        // only meet the center tap.
+        let even = 2.0 * self.symmetric_sum(delayed);
+        let odd = delayed(self.taps.len() - 1);
+        (even, odd)
+    }
+
+    /// Turns two samples at the doubled rate into one output sample.
+    fn downsample(&mut self, even: f64, odd: f64) -> f64 {
+        let length = self.even.len();
+        self.even_position = (self.even_position + 1) % length;
+        self.even[self.even_position] = even;
+        let samples = &self.even;
+        let position = self.even_position;
+        let output = self.symmetric_sum(|delay| samples[(position + length - delay) % length])
+            + 0.5 * self.odd[self.odd_position];
+        self.odd[self.odd_position] = odd;
+        self.odd_position = (self.odd_position + 1) % self.odd.len();
+        output
+    }
+}
+
+/// Runs a mono process at a power of two times the sample rate, by doubling the rate a number
+/// of times with halfband filters. The first stage does the heavy filtering, the later ones
+/// only need to remove the images far above the audio, so they are short.
+#[derive(Clone, Debug)]
+pub struct HalfbandOversampler {
+    stages: Vec<HalfbandStage>,
+    /// Delays the oversampled audio, so the latency is a whole number of samples.
+    padding: Vec<f64>,
+    padding_position: usize,
+    samples: Vec<f64>,
+    scratch: Vec<f64>,
+}
+
+impl HalfbandOversampler {
+    /// The number of nonzero taps on either side of the center of every stage.
+    const HALF_WIDTHS: [usize; 3] = [24, 8, 5];
+
+    /// Creates a new oversampler for the given factor, which is 1, 2, 4 or 8. A factor of 1
+    /// runs the process as is.
+    /// This will panic if the factor is anything else.
+    pub fn new(factor: usize) -> Self {
+        assert!(
+            factor.is_power_of_two() && factor <= 1 << Self::HALF_WIDTHS.len(),
+            "the halfband oversampling factor must be 1, 2, 4 or 8"
+        );
+        let stages: Vec<HalfbandStage> = Self::HALF_WIDTHS
+            .iter()
+            .take(factor.trailing_zeros() as usize)
+            .map(|half_width| HalfbandStage::new(*half_width))
+            .collect();
+        let mut oversampler = Self {
+            stages,
+            padding: Vec::new(),
+            padding_position: 0,
+            samples: vec![0.0; factor],
+            scratch: vec![0.0; factor],
+        };
+        let delay = oversampler.filter_delay();
+        oversampler.padding = vec![0.0; (factor - delay % factor) % factor];
+        oversampler
+    }
+
+    /// Returns the oversampling factor.
+    pub fn factor(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns the delay of the filters, in samples at the oversampled rate.
+    fn filter_delay(&self) -> usize {
+        // Both filters of a stage delay by its delay, at the rate of the stage.
+        let factor = self.factor();
+        self.stages
+            .iter()
+            .enumerate()
+            .map(|(index, stage)| 2 * stage.delay() * (factor >> (index + 1)))
+            .sum()
+    }
+
+    /// Returns the delay of the filters in samples at the original rate.
+    pub fn latency_samples(&self) -> Samples {
+        Samples::from((self.filter_delay() + self.padding.len()) / self.factor())
+    }
+
+    /// Returns the delay of the filters.
+    pub fn latency(&self, sample_rate: SampleRate) -> Latency {
+        Latency::from(self.latency_samples().to_seconds(sample_rate))
+    }
+
+    /// Clears the internal state, as if no audio was processed yet.
+    pub fn reset(&mut self) {
+        self.stages.iter_mut().for_each(HalfbandStage::reset);
+        self.padding.fill(0.0);
+        self.padding_position = 0;
+    }
+
+    /// Processes one input sample: upsamples it, runs the process on every oversampled
+    /// sample, and downsamples the result again.
+    pub fn process(&mut self, input: f64, mut process: impl FnMut(f64) -> f64) -> f64 {
+        self.samples[0] = input;
+        let mut length = 1;
+        for stage in self.stages.iter_mut() {
+            for index in 0..length {
+                let (even, odd) = stage.upsample(self.samples[index]);
+                self.scratch[2 * index] = even;
+                self.scratch[2 * index + 1] = odd;
+            }
+            std::mem::swap(&mut self.samples, &mut self.scratch);
+            length *= 2;
+        }
+
+        for sample in self.samples.iter_mut() {
+            *sample = process(*sample);
+            if !self.padding.is_empty() {
+                std::mem::swap(sample, &mut self.padding[self.padding_position]);
+                self.padding_position = (self.padding_position + 1) % self.padding.len();
+            }
+        }
+
+        for stage in self.stages.iter_mut().rev() {
+            length /= 2;
+            // Every output only reads samples at or after its own index, so this can be done
+            // in place.
+            for index in 0..length {
+                self.samples[index] =
+                    stage.downsample(self.samples[2 * index], self.samples[2 * index + 1]);
+            }
+        }
+        self.samples[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(2; "2x")]
+    #[test_case(4; "4x")]
+    #[test_case(8; "8x")]
+    fn passes_audio_delayed_by_the_latency(factor: usize) {
+        let mut oversampler = Oversampler::new(factor);
+        let latency = oversampler.latency_samples().as_usize();
+        let w = 0.2;
+
+        for n in 0..1000 {
+            let output = oversampler.process((w * n as f64).sin(), |sample| sample);
+            if n > 200 {
+                let expected = (w * (n - latency) as f64).sin();
+                assert!((output - expected).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn upsampling_interpolates_between_the_samples() {
+        let mut oversampler = Oversampler::new(4);
+        let mut upsampled = Vec::new();
+        let w = 0.3;
+
+        for n in 0..200 {
+            oversampler.upsample((w * n as f64).sin(), |sample| upsampled.push(sample));
+        }
+
+        // The upsampling filter delays by half of the latency, at the original rate.
+        let delay = oversampler.latency_samples().as_f64() / 2.0;
+        for (n, sample) in upsampled.iter().enumerate().skip(400) {
+            let expected = (w * (n as f64 / 4.0 - delay)).sin();
+            assert!((sample - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn factor_of_one_runs_the_process_directly() {
+        let mut oversampler = Oversampler::new(1);
+
+        assert_eq!(oversampler.process(0.5, |sample| sample * 2.0), 1.0);
+        assert_eq!(oversampler.latency_samples(), Samples::from(0));
+    }
+
+    #[test]
+    fn process_runs_once_per_oversampled_sample() {
+        let mut oversampler = Oversampler::new(4);
+        let mut calls = 0;
+
+        oversampler.process(0.0, |sample| {
+            calls += 1;
+            sample
+        });
+
+        assert_eq!(calls, 4);
+    }
+
+    #[test_case(2; "2x")]
+    #[test_case(4; "4x")]
+    #[test_case(8; "8x")]
+    fn halfband_passes_audio_delayed_by_the_latency(factor: usize) {
+        let mut oversampler = HalfbandOversampler::new(factor);
+        let latency = oversampler.latency_samples().as_usize();
+        let w = 0.2;
+
+        for n in 0..1000 {
+            let output = oversampler.process((w * n as f64).sin(), |sample| sample);
+            if n > 200 {
+                let expected = (w * (n - latency) as f64).sin();
+                assert!((output - expected).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test_case(1 => 0; "1x")]
+    #[test_case(2 => 47; "2x")]
+    #[test_case(4 => 55; "4x")]
+    #[test_case(8 => 57; "8x")]
+    fn halfband_latency(factor: usize) -> usize {
+        HalfbandOversampler::new(factor)
+            .latency_samples()
+            .as_usize()
+    }
+
+    #[test]
+    fn halfband_runs_the_process_once_per_oversampled_sample() {
+        let mut oversampler = HalfbandOversampler::new(4);
+        let mut calls = 0;
+
+        oversampler.process(0.0, |sample| {
+            calls += 1;
+            sample
+        });
+
+        assert_eq!(calls, 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn halfband_factor_must_be_a_power_of_two() {
+        HalfbandOversampler::new(3);
+    }
+}
@@ -0,0 +1,189 @@
+//! This module contains the math for placing a signal between speakers. A pan law decides how
+//! loud a signal is on both sides: a centered signal comes out of two speakers, so it needs to
+//! be turned down on each to sound as loud as a signal from one. How much depends on the room
+//! and on whether the mix will be summed to mono, hence the choice of laws.
+//! ```rust
+//! use rabu::layout::ChannelLayout;
+//! use rabu::panning::{balance, surround_gains, PanLaw};
+//! use rabu::units::{Decibels, Pan};
+//!
+//! let (left, right) = PanLaw::ConstantPower.gains(Pan::CENTER);
+//! assert!((Decibels::from_gain(left).as_f64() + 3.01).abs() < 0.01);
+//! assert!((left - right).abs() < 1e-12);
+//!
+//! // Balance only turns one side down.
+//! assert_eq!(balance(Pan::from(0.5)), (0.5, 1.0));
+//!
+//! // Straight to the left surround speaker of a 5.1 layout.
+//! let gains = surround_gains(ChannelLayout::Surround5_1, -110.0, PanLaw::ConstantPower);
+//! assert!((gains[4] - 1.0).abs() < 1e-12);
+//! ```
+
+use std::f64::consts::FRAC_PI_2;
+
+use crate::layout::ChannelLayout;
+use crate::units::Pan;
+
+/// How the level of a panned signal is spread over two speakers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PanLaw {
+    /// The gains move in a straight line, which is -6 dB in the center. Sums to mono without a
+    /// change in level, but sounds quieter in the center on speakers.
+    Linear,
+    /// Sine and cosine gains, which is -3 dB in the center and keeps the power the same at
+    /// every position.
+    ConstantPower,
+    /// The compromise between the two above, -4.5 dB in the center.
+    Minus4_5Db,
+    /// Squared sine and cosine gains, -6 dB in the center like linear, but smoother near the
+    /// edges.
+    Minus6Db,
+}
+
+impl PanLaw {
+    /// Returns the gains of the left and right speaker for the pan position.
+    pub fn gains(&self, pan: Pan) -> (f64, f64) {
+        // How far the position is to the right, from 0 to 1.
+        let position = (pan.as_f64() + 1.0) / 2.0;
+        let linear = (1.0 - position, position);
+        let angle = position * FRAC_PI_2;
+        let power = (angle.cos(), angle.sin());
+
+        match self {
+            PanLaw::Linear => linear,
+            PanLaw::ConstantPower => power,
+            PanLaw::Minus4_5Db => ((linear.0 * power.0).sqrt(), (linear.1 * power.1).sqrt()),
+            PanLaw::Minus6Db => (power.0 * power.0, power.1 * power.1),
+        }
+    }
+}
+
+/// Returns the gains of the left and right channel of a stereo signal for a balance position:
+/// the side that is panned away from is turned down linearly, the other side stays as it is.
+pub fn balance(pan: Pan) -> (f64, f64) {
+    let pan = pan.as_f64();
+    ((1.0 - pan).min(1.0), (1.0 + pan).min(1.0))
+}
+
+/// Returns the gain of every channel of the layout for a signal coming from the direction, in
+/// degrees where 0 is straight ahead and negative angles are to the left. The signal is panned
+/// between the two speakers around the direction with the pan law; all other speakers,
+/// including the LFE channel, get a gain of 0.
+pub fn surround_gains(layout: ChannelLayout, azimuth: f64, law: PanLaw) -> Vec<f64> {
+    let speakers = layout.speakers();
+    let mut gains = vec![0.0; speakers.len()];
+
+    let mut directions: Vec<(usize, f64)> = speakers
+        .iter()
+        .enumerate()
+        .filter_map(|(index, speaker)| Some((index, speaker.azimuth()?.rem_euclid(360.0))))
+        .collect();
+    if directions.len() == 1 {
+        gains[directions[0].0] = 1.0;
+        return gains;
+    }
+    directions.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let azimuth = azimuth.rem_euclid(360.0);
+    // The pair of neighbouring speakers that encloses the direction, going clockwise. When
+    // no pair does, the direction lies in the gap between the last and the first speaker.
+    let (first, second) = directions
+        .windows(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|(first, second)| (first.1..=second.1).contains(&azimuth))
+        .unwrap_or((directions[directions.len() - 1], directions[0]));
+
+    let width = (second.1 - first.1).rem_euclid(360.0);
+    let offset = (azimuth - first.1).rem_euclid(360.0);
+    let (first_gain, second_gain) = law.gains(Pan::from(2.0 * offset / width - 1.0));
+    gains[first.0] = first_gain;
+    gains[second.0] = second_gain;
+    gains
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Decibels;
+
+    #[test_case(PanLaw::Linear => -6.02; "linear")]
+    #[test_case(PanLaw::ConstantPower => -3.01; "constant power")]
+    #[test_case(PanLaw::Minus4_5Db => -4.52; "compromise")]
+    #[test_case(PanLaw::Minus6Db => -6.02; "minus 6 dB")]
+    fn level_in_the_center(law: PanLaw) -> f64 {
+        let (left, right) = law.gains(Pan::CENTER);
+        assert!((left - right).abs() < 1e-12);
+        (Decibels::from_gain(left).as_f64() * 100.0).round() / 100.0
+    }
+
+    #[test_case(PanLaw::Linear; "linear")]
+    #[test_case(PanLaw::ConstantPower; "constant power")]
+    #[test_case(PanLaw::Minus4_5Db; "compromise")]
+    #[test_case(PanLaw::Minus6Db; "minus 6 dB")]
+    fn hard_panned_is_one_speaker(law: PanLaw) {
+        let (left, right) = law.gains(Pan::LEFT);
+        assert!((left - 1.0).abs() < 1e-12 && right.abs() < 1e-12);
+        let (left, right) = law.gains(Pan::RIGHT);
+        assert!(left.abs() < 1e-12 && (right - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn constant_power_keeps_the_power() {
+        for n in -10..=10 {
+            let (left, right) = PanLaw::ConstantPower.gains(Pan::from(n as f64 / 10.0));
+            assert!((left * left + right * right - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn linear_keeps_the_sum() {
+        for n in -10..=10 {
+            let (left, right) = PanLaw::Linear.gains(Pan::from(n as f64 / 10.0));
+            assert!((left + right - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test_case(-1.0 => (1.0, 0.0); "left")]
+    #[test_case(0.0 => (1.0, 1.0); "center")]
+    #[test_case(0.25 => (0.75, 1.0); "a bit right")]
+    fn balance_turns_one_side_down(pan: f64) -> (f64, f64) {
+        balance(Pan::from(pan))
+    }
+
+    #[test]
+    fn surround_pans_between_neighbouring_speakers() {
+        // Halfway between center (0) and right (30).
+        let gains = surround_gains(ChannelLayout::Surround5_1, 15.0, PanLaw::ConstantPower);
+
+        assert!((gains[2] - gains[1]).abs() < 1e-12);
+        assert!((gains[1] * gains[1] + gains[2] * gains[2] - 1.0).abs() < 1e-12);
+        assert_eq!(gains[0], 0.0);
+        assert_eq!(gains[3], 0.0);
+    }
+
+    #[test]
+    fn surround_pans_behind_through_the_back_gap() {
+        // Straight behind is between the two surrounds.
+        let gains = surround_gains(ChannelLayout::Surround5_1, 180.0, PanLaw::Linear);
+
+        assert_eq!(gains, vec![0.0, 0.0, 0.0, 0.0, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn surround_on_a_speaker_is_that_speaker() {
+        let gains = surround_gains(ChannelLayout::Quad, 110.0, PanLaw::ConstantPower);
+
+        assert!((gains[3] - 1.0).abs() < 1e-12);
+        assert!(gains[..3].iter().all(|gain| gain.abs() < 1e-12));
+    }
+
+    #[test]
+    fn surround_on_mono_is_always_the_one_speaker() {
+        assert_eq!(
+            surround_gains(ChannelLayout::Mono, 90.0, PanLaw::Linear),
+            vec![1.0]
+        );
+    }
+}
@@ -0,0 +1,552 @@
+//! This module contains typed parameters, which describe the controls of a processor in one
+//! way: the range of a control in its own unit (e.g. a `Frequency` from 20 Hz to 20 kHz), how
+//! it maps to the normalized 0 to 1 range hosts automate with, how its value is shown as text
+//! and read back from text the user typed, and how it glides to new values at audio rate, so
+//! automation doesn't cause zipper noise.
+//! ```rust
+//! use rabu::parameter::{Parameter, ParameterMapping};
+//! use rabu::units::{Decibels, Frequency, NormalizedValue, SampleRate};
+//!
+//! let mut cutoff = Parameter::frequency("Cutoff", Frequency::from(1000.0));
+//! let mut gain = Parameter::new(
+//!     "Gain",
+//!     Decibels::from(-24.0),
+//!     Decibels::from(24.0),
+//!     Decibels::from(0.0),
+//!     ParameterMapping::Linear,
+//! );
+//! cutoff.set_sample_rate(SampleRate::from(48000));
+//! gain.set_sample_rate(SampleRate::from(48000));
+//!
+//! // The host moves the knob to the middle, which is 632 Hz on a logarithmic scale.
+//! cutoff.set_normalized(NormalizedValue::from(0.5));
+//! assert_eq!(cutoff.display(), "632.46 Hz");
+//!
+//! // The user types a value.
+//! let value = gain.parse("-6 dB").unwrap();
+//! gain.set_value(value);
+//! assert_eq!(gain.normalized().as_f64(), 0.375);
+//!
+//! for _ in 0..256 {
+//!     let cutoff = cutoff.next_sample();
+//!     let gain = gain.next_sample().to_gain();
+//!     // Process a sample with the cutoff and the gain.
+//! }
+//! ```
+
+use crate::smoother::{Smoother, SmoothingMode};
+use crate::units::{
+    Decibels, Frequency, NormalizedValue, Percentage, Ratio, SampleRate, Samples, Seconds,
+    Semitones,
+};
+
+/// A type that can be the value of a parameter.
+pub trait ParameterValue: Copy {
+    /// The unit that is shown after the value, e.g. "Hz".
+    const UNIT: &'static str;
+
+    /// Returns the raw value.
+    fn to_f64(self) -> f64;
+
+    /// Creates a value from a raw value.
+    fn from_f64(value: f64) -> Self;
+
+    /// Shows the value as text, with the given number of decimals.
+    fn format(self, precision: usize) -> String {
+        let value = self.to_f64();
+        if Self::UNIT.is_empty() {
+            format!("{value:.precision$}")
+        } else {
+            format!("{value:.precision$} {}", Self::UNIT)
+        }
+    }
+}
+
+macro_rules! impl_parameter_value {
+    ($value_type: ty, $unit: literal) => {
+        impl ParameterValue for $value_type {
+            const UNIT: &'static str = $unit;
+
+            fn to_f64(self) -> f64 {
+                self.as_f64()
+            }
+
+            fn from_f64(value: f64) -> Self {
+                Self::from(value)
+            }
+        }
+    };
+}
+
+impl_parameter_value!(Decibels, "dB");
+impl_parameter_value!(Seconds, "s");
+impl_parameter_value!(Semitones, "st");
+impl_parameter_value!(Percentage, "%");
+
+impl ParameterValue for f64 {
+    const UNIT: &'static str = "";
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+impl ParameterValue for Frequency {
+    const UNIT: &'static str = "Hz";
+
+    fn to_f64(self) -> f64 {
+        self.as_f64()
+    }
+
+    fn from_f64(value: f64) -> Self {
+        Self::from(value)
+    }
+
+    /// Shows frequencies from 1 kHz up in kHz.
+    fn format(self, precision: usize) -> String {
+        let value = self.as_f64();
+        if value.abs() >= 1000.0 {
+            format!("{:.precision$} kHz", value / 1000.0)
+        } else {
+            format!("{value:.precision$} Hz")
+        }
+    }
+}
+
+impl ParameterValue for Ratio {
+    const UNIT: &'static str = ":1";
+
+    fn to_f64(self) -> f64 {
+        self.as_f64()
+    }
+
+    fn from_f64(value: f64) -> Self {
+        Self::from(value)
+    }
+
+    fn format(self, precision: usize) -> String {
+        format!("{:.precision$}:1", self.as_f64())
+    }
+}
+
+/// How the range of a parameter maps to the normalized range.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ParameterMapping {
+    /// Equal steps in the normalized range are equal steps in value.
+    Linear,
+    /// Equal steps in the normalized range multiply the value by the same factor, e.g. the
+    /// same number of octaves for frequencies. Needs a range above 0.
+    Logarithmic,
+    /// Bends the linear mapping: the normalized value is raised to the given power, so a power
+    /// above 1 gives more room to the lower part of the range.
+    Power(f64),
+}
+
+/// A control of a processor with a typed value in a fixed range.
+#[derive(Clone, Debug)]
+pub struct Parameter<T> {
+    name: String,
+    min: T,
+    max: T,
+    default: T,
+    mapping: ParameterMapping,
+    precision: usize,
+    /// The value, as set by the user or the host.
+    value: T,
+    /// Glides to the normalized value, so logarithmic parameters glide evenly as well.
+    smoother: Smoother<f64>,
+    smoothing_mode: SmoothingMode,
+    smoothing_time: Seconds,
+    sample_rate: SampleRate,
+}
+
+impl<T: ParameterValue> Parameter<T> {
+    /// Creates a new parameter, which sits at its default. It shows two decimals, and glides to
+    /// new values linearly in 20 ms.
+    /// This will panic if the range is empty or not finite, or if a logarithmic range doesn't
+    /// stay above 0.
+    pub fn new(
+        name: impl Into<String>,
+        min: T,
+        max: T,
+        default: T,
+        mapping: ParameterMapping,
+    ) -> Self {
+        let (low, high) = (min.to_f64(), max.to_f64());
+        assert!(
+            low.is_finite() && high.is_finite() && low < high,
+            "the range of a parameter must be finite and not empty"
+        );
+        if mapping == ParameterMapping::Logarithmic {
+            assert!(low > 0.0, "a logarithmic range must stay above 0");
+        }
+        let smoothing_mode = SmoothingMode::Linear;
+        let smoothing_time = Seconds::from(0.02);
+        let sample_rate = SampleRate::from(48000);
+        let mut parameter = Self {
+            name: name.into(),
+            min,
+            max,
+            default,
+            mapping,
+            precision: 2,
+            value: default,
+            smoother: Smoother::new(smoothing_mode, smoothing_time, sample_rate, 0.0),
+            smoothing_mode,
+            smoothing_time,
+            sample_rate,
+        };
+        parameter.default = parameter.clamp(default);
+        parameter.value = parameter.default;
+        parameter.reset();
+        parameter
+    }
+
+    /// Returns the name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the lowest value.
+    pub fn min(&self) -> T {
+        self.min
+    }
+
+    /// Returns the highest value.
+    pub fn max(&self) -> T {
+        self.max
+    }
+
+    /// Returns the value the parameter starts at.
+    pub fn default(&self) -> T {
+        self.default
+    }
+
+    /// Returns the mapping to the normalized range.
+    pub fn mapping(&self) -> ParameterMapping {
+        self.mapping
+    }
+
+    /// Returns the number of decimals the value is shown with.
+    pub fn precision(&self) -> usize {
+        self.precision
+    }
+
+    /// Changes the number of decimals the value is shown with.
+    pub fn set_precision(&mut self, precision: usize) {
+        self.precision = precision;
+    }
+
+    /// Returns the value, which the smoothed value glides to.
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    /// Changes the value, clamped to the range, and starts gliding to it.
+    pub fn set_value(&mut self, value: T) {
+        self.value = self.clamp(value);
+        self.smoother
+            .set_target(self.to_normalized(self.value).as_f64());
+    }
+
+    /// Returns the value in the normalized range, e.g. to send to the host.
+    pub fn normalized(&self) -> NormalizedValue {
+        self.to_normalized(self.value)
+    }
+
+    /// Changes the value from the normalized range, e.g. from host automation, and starts
+    /// gliding to it.
+    pub fn set_normalized(&mut self, normalized: NormalizedValue) {
+        self.set_value(self.from_normalized(normalized));
+    }
+
+    /// Maps a value to the normalized range, clamped to the range.
+    pub fn to_normalized(&self, value: T) -> NormalizedValue {
+        let (low, high) = (self.min.to_f64(), self.max.to_f64());
+        let value = self.clamp(value).to_f64();
+        let normalized = match self.mapping {
+            ParameterMapping::Linear => (value - low) / (high - low),
+            ParameterMapping::Logarithmic => (value / low).ln() / (high / low).ln(),
+            ParameterMapping::Power(power) => ((value - low) / (high - low)).powf(1.0 / power),
+        };
+        NormalizedValue::from(normalized)
+    }
+
+    /// Maps a normalized value to a value in the range.
+    pub fn from_normalized(&self, normalized: NormalizedValue) -> T {
+        let (low, high) = (self.min.to_f64(), self.max.to_f64());
+        let normalized = normalized.as_f64();
+        let value = match self.mapping {
+            ParameterMapping::Linear => low + (high - low) * normalized,
+            ParameterMapping::Logarithmic => low * (high / low).powf(normalized),
+            ParameterMapping::Power(power) => low + (high - low) * normalized.powf(power),
+        };
+        T::from_f64(value.clamp(low, high))
+    }
+
+    /// Shows the value as text, e.g. "1.50 kHz".
+    pub fn display(&self) -> String {
+        self.format(self.value)
+    }
+
+    /// Shows a value as text with the precision of the parameter.
+    pub fn format(&self, value: T) -> String {
+        value.format(self.precision)
+    }
+
+    /// Reads a value from text, e.g. what the user typed in a text field, clamped to the range.
+    /// The unit is optional, and "k" multiplies by 1000, so "1.5 kHz", "1.5k" and "1500" all
+    /// give 1500 Hz. Returns `None` if the text isn't a number.
+    pub fn parse(&self, text: &str) -> Option<T> {
+        let text = text.trim();
+        let text = match text.len().checked_sub(T::UNIT.len()) {
+            Some(end)
+                if !T::UNIT.is_empty()
+                    && text.is_char_boundary(end)
+                    && text[end..].eq_ignore_ascii_case(T::UNIT) =>
+            {
+                text[..end].trim_end()
+            }
+            _ => text,
+        };
+        let (text, factor) = match text.strip_suffix(['k', 'K']) {
+            Some(text) => (text.trim_end(), 1000.0),
+            None => (text, 1.0),
+        };
+        let value = text.parse::<f64>().ok()? * factor;
+        if value.is_nan() {
+            return None;
+        }
+        Some(self.clamp(T::from_f64(value)))
+    }
+
+    /// Returns the smoothing mode.
+    pub fn smoothing_mode(&self) -> SmoothingMode {
+        self.smoothing_mode
+    }
+
+    /// Changes how the smoothed value glides, which applies from the next value on.
+    pub fn set_smoothing_mode(&mut self, mode: SmoothingMode) {
+        self.smoothing_mode = mode;
+        self.update_smoother();
+    }
+
+    /// Returns the time the smoothed value takes to reach a new value.
+    pub fn smoothing_time(&self) -> Seconds {
+        self.smoothing_time
+    }
+
+    /// Changes the time the smoothed value takes to reach a new value, which applies from the
+    /// next value on. A time of 0 turns smoothing off.
+    pub fn set_smoothing_time(&mut self, time: Seconds) {
+        self.smoothing_time = time;
+        self.update_smoother();
+    }
+
+    /// Changes the sample rate the smoothed value runs at, which applies from the next value
+    /// on.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = sample_rate;
+        self.update_smoother();
+    }
+
+    /// Returns the smoothed value, without moving it.
+    pub fn current(&self) -> T {
+        self.from_normalized(NormalizedValue::from(self.smoother.current()))
+    }
+
+    /// Returns whether the smoothed value hasn't reached the value yet.
+    pub fn is_smoothing(&self) -> bool {
+        self.smoother.is_smoothing()
+    }
+
+    /// Produces the next smoothed value.
+    pub fn next_sample(&mut self) -> T {
+        let normalized = self.smoother.next_sample();
+        self.from_normalized(NormalizedValue::from(normalized))
+    }
+
+    /// Moves the smoothed value as many samples as given, e.g. when it is only needed once
+    /// per block.
+    pub fn skip(&mut self, num_samples: Samples) {
+        self.smoother.skip(num_samples);
+    }
+
+    /// Jumps the smoothed value to the value, without gliding.
+    pub fn reset(&mut self) {
+        self.smoother.reset(self.to_normalized(self.value).as_f64());
+    }
+
+    fn clamp(&self, value: T) -> T {
+        let value = value.to_f64();
+        T::from_f64(value.clamp(self.min.to_f64(), self.max.to_f64()))
+    }
+
+    /// Recreates the smoother with the current settings, at the current smoothed value, still
+    /// gliding to the value.
+    fn update_smoother(&mut self) {
+        let current = self.smoother.current();
+        let target = self.smoother.target();
+        self.smoother = Smoother::new(
+            self.smoothing_mode,
+            self.smoothing_time,
+            self.sample_rate,
+            current,
+        );
+        if target != current {
+            self.smoother.set_target(target);
+        }
+    }
+}
+
+impl Parameter<Frequency> {
+    /// Creates a frequency parameter over the range of hearing, 20 Hz to 20 kHz, with a
+    /// logarithmic mapping.
+    pub fn frequency(name: impl Into<String>, default: Frequency) -> Self {
+        Self::new(
+            name,
+            Frequency::from(20.0),
+            Frequency::from(20000.0),
+            default,
+            ParameterMapping::Logarithmic,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn gain() -> Parameter<Decibels> {
+        let mut gain = Parameter::new(
+            "Gain",
+            Decibels::from(-60.0),
+            Decibels::from(12.0),
+            Decibels::from(0.0),
+            ParameterMapping::Linear,
+        );
+        gain.set_smoothing_time(Seconds::from(0.01));
+        gain.set_sample_rate(SampleRate::from(1000));
+        gain
+    }
+
+    #[test]
+    fn starts_at_the_default() {
+        let gain = gain();
+
+        assert_eq!(gain.value(), Decibels::from(0.0));
+        assert_eq!(gain.current(), Decibels::from(0.0));
+        assert!(!gain.is_smoothing());
+    }
+
+    #[test_case(ParameterMapping::Linear, 0.5, 10010.0)]
+    #[test_case(ParameterMapping::Logarithmic, 0.5, 632.455)]
+    #[test_case(ParameterMapping::Power(2.0), 0.5, 5015.0)]
+    fn mappings(mapping: ParameterMapping, normalized: f64, expected: f64) {
+        let parameter = Parameter::new(
+            "Frequency",
+            Frequency::from(20.0),
+            Frequency::from(20000.0),
+            Frequency::from(1000.0),
+            mapping,
+        );
+
+        let value = parameter.from_normalized(NormalizedValue::from(normalized));
+
+        assert!((value.as_f64() - expected).abs() < 0.001, "{value:?}");
+        let back = parameter.to_normalized(value).as_f64();
+        assert!((back - normalized).abs() < 1e-12);
+    }
+
+    #[test]
+    fn values_are_clamped_to_the_range() {
+        let mut gain = gain();
+
+        gain.set_value(Decibels::from(100.0));
+        assert_eq!(gain.value(), Decibels::from(12.0));
+
+        gain.set_value(Decibels::from(f64::NEG_INFINITY));
+        assert_eq!(gain.value(), Decibels::from(-60.0));
+        assert_eq!(gain.normalized().as_f64(), 0.0);
+    }
+
+    #[test_case(Frequency::from(440.0), "440.00 Hz")]
+    #[test_case(Frequency::from(1500.0), "1.50 kHz")]
+    fn formats_frequencies(value: Frequency, expected: &str) {
+        let parameter = Parameter::frequency("Cutoff", value);
+
+        assert_eq!(parameter.display(), expected);
+    }
+
+    #[test_case("1500", Some(1500.0))]
+    #[test_case("1.5 kHz", Some(1500.0))]
+    #[test_case("1.5k", Some(1500.0))]
+    #[test_case(" 440 hz ", Some(440.0))]
+    #[test_case("5", Some(20.0))]
+    #[test_case("loud", None)]
+    #[test_case("", None)]
+    fn parses_frequencies(text: &str, expected: Option<f64>) {
+        let parameter = Parameter::frequency("Cutoff", Frequency::from(1000.0));
+
+        assert_eq!(parameter.parse(text).map(|value| value.as_f64()), expected);
+    }
+
+    #[test]
+    fn formatted_values_parse_back() {
+        let mut ratio = Parameter::new(
+            "Ratio",
+            Ratio::from(1.0),
+            Ratio::from(20.0),
+            Ratio::from(4.0),
+            ParameterMapping::Linear,
+        );
+        ratio.set_precision(1);
+
+        assert_eq!(ratio.display(), "4.0:1");
+        assert_eq!(ratio.parse(&ratio.display()), Some(Ratio::from(4.0)));
+        let gain = gain();
+        assert_eq!(gain.parse(&gain.display()), Some(Decibels::from(0.0)));
+    }
+
+    #[test]
+    fn glides_to_new_values() {
+        let mut gain = gain();
+
+        gain.set_value(Decibels::from(-36.0));
+
+        let values: Vec<f64> = (0..10).map(|_| gain.next_sample().as_f64()).collect();
+        assert!((values[0] + 3.6).abs() < 1e-9);
+        assert!((values[4] + 18.0).abs() < 1e-9);
+        assert_eq!(values[9], -36.0);
+        assert!(!gain.is_smoothing());
+    }
+
+    #[test]
+    fn logarithmic_parameters_glide_evenly_in_octaves() {
+        let mut cutoff = Parameter::frequency("Cutoff", Frequency::from(100.0));
+        cutoff.set_smoothing_time(Seconds::from(0.002));
+        cutoff.set_sample_rate(SampleRate::from(1000));
+
+        cutoff.set_value(Frequency::from(400.0));
+
+        assert!((cutoff.next_sample().as_f64() - 200.0).abs() < 1e-9);
+        assert!((cutoff.next_sample().as_f64() - 400.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn reset_jumps_to_the_value() {
+        let mut gain = gain();
+        gain.set_value(Decibels::from(-12.0));
+
+        gain.reset();
+
+        assert!(!gain.is_smoothing());
+        assert_eq!(gain.current(), Decibels::from(-12.0));
+    }
+}
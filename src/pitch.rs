@@ -0,0 +1,319 @@
+//! This module contains pitch detection with the YIN algorithm, which finds the period of a
+//! signal as the lag at which it differs least from itself. The difference is normalized by
+//! its running mean, so the first dip below a threshold is the period, and not one of its
+//! multiples. How deep that dip goes tells how periodic the signal is, which is given as the
+//! confidence. Multichannel audio is mixed down to mono before analysis.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::pitch::PitchDetector;
+//! use rabu::units::{Channels, Frequency, SampleRate, Samples};
+//!
+//! let detector = PitchDetector::new(
+//!     SampleRate::from(48000),
+//!     Frequency::from(60.0),
+//!     Frequency::from(1000.0),
+//! );
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(1), detector.window_length());
+//! for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+//!     *sample = (2.0 * std::f32::consts::PI * 220.0 * n as f32 / 48000.0).sin();
+//! }
+//!
+//! let pitch = detector.detect(&buffer).unwrap();
+//! assert!((pitch.frequency.as_f64() - 220.0).abs() < 0.5);
+//! assert!(pitch.confidence.as_f64() > 0.9);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::units::{Frequency, NormalizedValue, SampleRate, Samples};
+
+/// A detected pitch.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pitch {
+    /// The fundamental frequency.
+    pub frequency: Frequency,
+    /// How periodic the signal is, from 0 (noise) to 1 (perfectly periodic).
+    pub confidence: NormalizedValue,
+}
+
+/// Detects the pitch of a block of audio.
+#[derive(Clone, Debug)]
+pub struct PitchDetector {
+    sample_rate: SampleRate,
+    min_lag: usize,
+    max_lag: usize,
+    threshold: f64,
+}
+
+impl PitchDetector {
+    /// Creates a new detector for pitches between the lowest and the highest frequency, with
+    /// a threshold of 0.15.
+    /// This will panic if the lowest frequency isn't below the highest.
+    pub fn new(sample_rate: SampleRate, lowest: Frequency, highest: Frequency) -> Self {
+        assert!(
+            lowest < highest,
+            "the lowest frequency must be below the highest"
+        );
+        let max_lag = (sample_rate.as_f64() / lowest.as_f64()).ceil() as usize;
+        let min_lag = ((sample_rate.as_f64() / highest.as_f64()).floor() as usize).max(2);
+        Self {
+            sample_rate,
+            min_lag,
+            max_lag,
+            threshold: 0.15,
+        }
+    }
+
+    /// Returns the number of samples a block needs at least: two periods of the lowest
+    /// frequency.
+    pub fn window_length(&self) -> Samples {
+        Samples::from(2 * self.max_lag)
+    }
+
+    /// Returns the threshold.
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Changes how far the normalized difference must dip for a lag to count as the period.
+    /// Lower thresholds give fewer, but more reliable detections. Usual values are between
+    /// 0.1 and 0.2.
+    pub fn set_threshold(&mut self, threshold: f64) {
+        self.threshold = threshold;
+    }
+
+    /// Detects the pitch of the buffer, or gives `None` when it isn't periodic enough.
+    /// This will panic if the buffer is shorter than the window length.
+    pub fn detect<T: Sample>(&self, buffer: &Buffer<T>) -> Option<Pitch> {
+        assert!(
+            buffer.num_samples() >= self.window_length(),
+            "the buffer must be at least as long as the window length"
+        );
+        let channels = buffer.num_channels().as_usize() as f64;
+        let mono: Vec<f64> = buffer
+            .sample_indices()
+            .map(|index| {
+                buffer
+                    .iter_chans()
+                    .map(|channel| channel[index].to_f64())
+                    .sum::<f64>()
+                    / channels
+            })
+            .collect();
+        self.detect_samples(&mono)
+    }
+
+    fn detect_samples(&self, samples: &[f64]) -> Option<Pitch> {
+        let differences = self.normalized_differences(samples);
+
+        // The first lag that dips below the threshold, followed down to the bottom of the dip.
+        let mut lag =
+            (self.min_lag..=self.max_lag).find(|&lag| differences[lag] < self.threshold)?;
+        while lag < self.max_lag && differences[lag + 1] < differences[lag] {
+            lag += 1;
+        }
+
+        let period = lag as f64 + parabolic_offset(&differences, lag);
+        Some(Pitch {
+            frequency: Frequency::from(self.sample_rate.as_f64() / period),
+            confidence: NormalizedValue::from(1.0 - differences[lag]),
+        })
+    }
+
+    /// The difference of the samples with themselves at every lag up to the maximum lag (plus
+    /// one, for the interpolation), divided by the mean difference of the lags below it.
+    fn normalized_differences(&self, samples: &[f64]) -> Vec<f64> {
+        let last_lag = (self.max_lag + 1).min(samples.len() - 1);
+        let length = samples.len() - last_lag;
+        let mut differences = vec![1.0; self.max_lag + 2];
+        let mut running_sum = 0.0;
+
+        for lag in 1..=last_lag {
+            let difference: f64 = samples[..length]
+                .iter()
+                .zip(&samples[lag..lag + length])
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum();
+            running_sum += difference;
+            if running_sum > 0.0 {
+                differences[lag] = difference * lag as f64 / running_sum;
+            }
+        }
+        differences
+    }
+}
+
+/// Returns where the bottom of a parabola through the value at the index and its neighbours
+/// lies, relative to the index.
+fn parabolic_offset(values: &[f64], index: usize) -> f64 {
+    let (left, middle, right) = (values[index - 1], values[index], values[index + 1]);
+    let curvature = left - 2.0 * middle + right;
+    if curvature <= 0.0 {
+        0.0
+    } else {
+        (0.5 * (left - right) / curvature).clamp(-0.5, 0.5)
+    }
+}
+
+/// Tracks the pitch of a live signal, by detecting the pitch of the last window of samples
+/// every hop.
+#[derive(Clone, Debug)]
+pub struct PitchTracker {
+    detector: PitchDetector,
+    hop_size: usize,
+    /// The last window of mono samples, as a ring buffer.
+    history: Vec<f64>,
+    position: usize,
+    since_detection: usize,
+    window: Vec<f64>,
+    pitch: Option<Pitch>,
+}
+
+impl PitchTracker {
+    /// Creates a new tracker that runs the detector every hop.
+    /// This will panic if the hop size is 0.
+    pub fn new(detector: PitchDetector, hop_size: Samples) -> Self {
+        assert!(hop_size.as_usize() > 0, "the hop size must be at least 1");
+        let window_length = detector.window_length().as_usize();
+        Self {
+            detector,
+            hop_size: hop_size.as_usize(),
+            history: vec![0.0; window_length],
+            position: 0,
+            since_detection: 0,
+            window: vec![0.0; window_length],
+            pitch: None,
+        }
+    }
+
+    /// Returns the detector.
+    pub fn detector(&self) -> &PitchDetector {
+        &self.detector
+    }
+
+    /// Returns the detector, to change its settings.
+    pub fn detector_mut(&mut self) -> &mut PitchDetector {
+        &mut self.detector
+    }
+
+    /// Returns the pitch of the last detection.
+    pub fn pitch(&self) -> Option<Pitch> {
+        self.pitch
+    }
+
+    /// Clears the internal state, as if no audio was processed yet.
+    pub fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.position = 0;
+        self.since_detection = 0;
+        self.pitch = None;
+    }
+
+    /// Adds the buffer to the history, detecting the pitch at every hop that passes.
+    pub fn process<T: Sample>(&mut self, buffer: &Buffer<T>) {
+        let channels = buffer.num_channels().as_usize() as f64;
+        for index in buffer.sample_indices() {
+            let sample = buffer
+                .iter_chans()
+                .map(|channel| channel[index].to_f64())
+                .sum::<f64>()
+                / channels;
+            self.history[self.position] = sample;
+            self.position = (self.position + 1) % self.history.len();
+            self.since_detection += 1;
+            if self.since_detection == self.hop_size {
+                self.since_detection = 0;
+                self.detect();
+            }
+        }
+    }
+
+    fn detect(&mut self) {
+        let (newest, oldest) = self.history.split_at(self.position);
+        self.window[..oldest.len()].copy_from_slice(oldest);
+        self.window[oldest.len()..].copy_from_slice(newest);
+        self.pitch = self.detector.detect_samples(&self.window);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::noise::Random;
+    use crate::units::Channels;
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    fn detector() -> PitchDetector {
+        PitchDetector::new(
+            SampleRate::from(SAMPLE_RATE),
+            Frequency::from(50.0),
+            Frequency::from(2000.0),
+        )
+    }
+
+    fn buffer(length: usize, signal: impl Fn(f64) -> f64) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(length));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = signal(n as f64 / SAMPLE_RATE as f64);
+        }
+        buffer
+    }
+
+    fn tone(frequency: f64) -> impl Fn(f64) -> f64 {
+        // A few harmonics, with a weak fundamental, like many real instruments.
+        move |t| {
+            let phase = 2.0 * std::f64::consts::PI * frequency * t;
+            0.3 * phase.sin() + 0.5 * (2.0 * phase).sin() + 0.4 * (3.0 * phase).sin()
+        }
+    }
+
+    #[test_case(82.41; "low e")]
+    #[test_case(220.0; "a3")]
+    #[test_case(440.0; "a4")]
+    #[test_case(1234.5; "high")]
+    fn detects_the_fundamental(frequency: f64) {
+        let detector = detector();
+        let length = detector.window_length().as_usize();
+
+        let pitch = detector.detect(&buffer(length, tone(frequency))).unwrap();
+
+        let cents = 1200.0 * (pitch.frequency.as_f64() / frequency).log2();
+        assert!(cents.abs() < 5.0, "{cents} cents off");
+        assert!(pitch.confidence.as_f64() > 0.9);
+    }
+
+    #[test]
+    fn silence_has_no_pitch() {
+        let detector = detector();
+        let length = detector.window_length().as_usize();
+
+        assert_eq!(detector.detect(&buffer(length, |_| 0.0)), None);
+    }
+
+    #[test]
+    fn noise_has_no_pitch() {
+        let detector = detector();
+        let mut random = Random::new(7);
+        let mut buffer = buffer(detector.window_length().as_usize(), |_| 0.0);
+        buffer.map_samples(|_| random.next_bipolar());
+
+        assert_eq!(detector.detect(&buffer), None);
+    }
+
+    #[test]
+    fn tracker_follows_a_pitch_change() {
+        let mut tracker = PitchTracker::new(detector(), Samples::from(256));
+
+        tracker.process(&buffer(1600, tone(200.0)));
+        let first = tracker.pitch().unwrap().frequency.as_f64();
+        tracker.process(&buffer(1600, tone(300.0)));
+        let second = tracker.pitch().unwrap().frequency.as_f64();
+
+        assert!((first - 200.0).abs() < 1.0);
+        assert!((second - 300.0).abs() < 1.0);
+    }
+}
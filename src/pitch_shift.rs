@@ -0,0 +1,274 @@
+//! This module contains pitch shifting, which transposes audio without changing its length.
+//! Buffers are shifted offline by time-stretching them and playing them back faster or slower,
+//! which keeps the timing and sounds clean. The `PitchShifter` shifts a stream in real time with
+//! two crossfaded taps that sweep through a short delay line, which costs a little quality and
+//! has a fixed latency.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::pitch_shift::{shift_pitch, PitchShifter};
+//! use rabu::units::{Cents, Channels, SampleRate, Samples, Semitones};
+//!
+//! let sample_rate = SampleRate::from(8000);
+//! let clip = Buffer::<f32>::allocate(Channels::from(2), Samples::from(8000));
+//!
+//! // A fifth up, offline.
+//! let shifted = shift_pitch(&clip, Semitones::from(7.0), sample_rate);
+//! assert_eq!(shifted.num_samples(), clip.num_samples());
+//!
+//! // Slightly detuned, in real time.
+//! let mut shifter = PitchShifter::new(Semitones::from(Cents::from(-15.0)), sample_rate, Channels::from(2));
+//! let mut block = Buffer::<f32>::allocate(Channels::from(2), Samples::from(256));
+//! shifter.process(&mut block);
+//!
+//! assert!(shifter.latency().as_secs_f64() > 0.0);
+//! ```
+
+use std::f64::consts::PI;
+
+use crate::buffer::Buffer;
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::resample::{resample_with_step, ResampleQuality};
+use crate::sample::Sample;
+use crate::time_stretch::stretch;
+use crate::units::{BufferSize, Channels, Latency, SampleRate, Samples, Semitones};
+
+/// The length of the delay line of the `PitchShifter` in seconds. Longer windows smear less
+/// on low notes, but add more latency.
+const WINDOW_LENGTH: f64 = 0.05;
+
+/// Shifts the pitch of the buffer by the interval, keeping its length.
+pub fn shift_pitch<T: Sample>(
+    input: &Buffer<T>,
+    shift: Semitones,
+    sample_rate: SampleRate,
+) -> Buffer<T> {
+    let ratio = shift.to_ratio();
+    let stretched = stretch(input, ratio, sample_rate);
+    resample_with_step(
+        &stretched,
+        ratio,
+        input.num_samples(),
+        ResampleQuality::default(),
+    )
+}
+
+/// Shifts the pitch of a stream in real time. Two taps read a delay line at the shifted speed,
+/// half a window apart, and fade in and out so neither is heard while it jumps back.
+#[derive(Clone, Debug)]
+pub struct PitchShifter {
+    shift: Semitones,
+    sample_rate: SampleRate,
+    window: usize,
+    lines: Vec<Vec<f64>>,
+    write: usize,
+    /// The delay of the first tap, relative to the window.
+    phase: f64,
+}
+
+impl PitchShifter {
+    /// Creates a new pitch shifter for the given number of channels.
+    pub fn new(shift: Semitones, sample_rate: SampleRate, num_channels: Channels) -> Self {
+        let window = ((WINDOW_LENGTH * sample_rate.as_f64()) as usize).max(2);
+        Self {
+            shift,
+            sample_rate,
+            window,
+            // One extra sample to interpolate the longest delay.
+            lines: vec![vec![0.0; window + 2]; num_channels.as_usize()],
+            write: 0,
+            phase: 0.0,
+        }
+    }
+
+    /// Returns the interval the audio is shifted by.
+    pub fn shift(&self) -> Semitones {
+        self.shift
+    }
+
+    /// Changes the interval the audio is shifted by, which takes effect without a jump.
+    pub fn set_shift(&mut self, shift: Semitones) {
+        self.shift = shift;
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.lines.len())
+    }
+
+    /// Returns the average delay of the taps, which is exact when the pitch isn't shifted.
+    pub fn latency(&self) -> Latency {
+        Latency::from(Samples::from(self.window / 2).to_seconds(self.sample_rate))
+    }
+
+    /// Clears the delay line.
+    pub fn reset(&mut self) {
+        for line in &mut self.lines {
+            line.fill(0.0);
+        }
+        self.write = 0;
+        self.phase = 0.0;
+    }
+
+    /// Shifts the pitch of the buffer in place.
+    /// This will panic if the buffer has more channels than the pitch shifter.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        assert!(
+            buffer.num_channels() <= self.num_channels(),
+            "the buffer has more channels than the pitch shifter"
+        );
+        let window = self.window as f64;
+        let step = (1.0 - self.shift.to_ratio()) / window;
+        let mut end = (self.phase, self.write);
+
+        for (channel, line) in buffer.iter_chans_mut().zip(&mut self.lines) {
+            let (mut phase, mut write) = (self.phase, self.write);
+            for sample in channel.iter_mut() {
+                line[write] = sample.to_f64();
+                let output = [phase, (phase + 0.5) % 1.0]
+                    .into_iter()
+                    .map(|phase| {
+                        let gain = (PI * phase).sin().powi(2);
+                        gain * read(line, write, phase * window)
+                    })
+                    .sum();
+                *sample = T::from_f64(output);
+
+                write = (write + 1) % line.len();
+                phase = (phase + step).rem_euclid(1.0);
+            }
+            end = (phase, write);
+        }
+
+        (self.phase, self.write) = end;
+    }
+}
+
+/// Reads the delay line the given number of samples before the write position, interpolating
+/// linearly.
+fn read(line: &[f64], write: usize, delay: f64) -> f64 {
+    let whole = delay.floor() as usize;
+    let fraction = delay - whole as f64;
+    let at = |delay: usize| line[(write + line.len() - delay) % line.len()];
+    let (newer, older) = (at(whole), at(whole + 1));
+    newer + (older - newer) * fraction
+}
+
+impl AudioProcessor for PitchShifter {
+    fn prepare(&mut self, sample_rate: SampleRate, _: BufferSize, num_channels: Channels) {
+        *self = Self::new(self.shift, sample_rate, num_channels);
+    }
+
+    fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+        PitchShifter::process(self, buffer);
+    }
+
+    fn latency(&self) -> Latency {
+        PitchShifter::latency(self)
+    }
+
+    fn reset(&mut self) {
+        PitchShifter::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::pitch::PitchDetector;
+    use crate::units::Frequency;
+
+    const SAMPLE_RATE: u32 = 8000;
+
+    fn sine(frequency: f64, length: usize) -> Buffer<f32> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(length));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = (2.0 * PI * frequency * n as f64 / SAMPLE_RATE as f64).sin() as f32;
+        }
+        buffer
+    }
+
+    /// Detects the pitch in the middle of the buffer.
+    fn detect(buffer: &Buffer<f32>) -> f64 {
+        let detector = PitchDetector::new(
+            SampleRate::from(SAMPLE_RATE),
+            Frequency::from(100.0),
+            Frequency::from(1000.0),
+        );
+        let window = detector.window_length();
+        let middle = buffer.num_samples().as_usize() / 2;
+        let mut part = Buffer::<f32>::allocate(Channels::from(1), window);
+        part.chan_mut(0)
+            .copy_from_slice(&buffer.chan(0)[middle..middle + window.as_usize()]);
+        detector.detect(&part).unwrap().frequency.as_f64()
+    }
+
+    #[test_case(12.0, 440.0; "octave up")]
+    #[test_case(-7.0, 146.83; "fifth down")]
+    fn shifts_buffers_offline(semitones: f64, expected: f64) {
+        let input = sine(220.0, 8000);
+
+        let output = shift_pitch(
+            &input,
+            Semitones::from(semitones),
+            SampleRate::from(SAMPLE_RATE),
+        );
+
+        assert_eq!(output.num_samples(), input.num_samples());
+        assert!((detect(&output) - expected).abs() < expected * 0.01);
+    }
+
+    #[test_case(12.0, 440.0; "octave up")]
+    #[test_case(-5.0, 164.81; "fourth down")]
+    fn shifts_streams(semitones: f64, expected: f64) {
+        let mut buffer = sine(220.0, 8000);
+        let mut shifter = PitchShifter::new(
+            Semitones::from(semitones),
+            SampleRate::from(SAMPLE_RATE),
+            Channels::from(1),
+        );
+
+        shifter.process(&mut buffer);
+
+        assert!((detect(&buffer) - expected).abs() < expected * 0.02);
+    }
+
+    #[test]
+    fn without_a_shift_the_stream_is_delayed_by_the_latency() {
+        let input = sine(220.0, 1000);
+        let mut output = input.clone();
+        let sample_rate = SampleRate::from(SAMPLE_RATE);
+        let mut shifter = PitchShifter::new(Semitones::from(0.0), sample_rate, Channels::from(1));
+
+        shifter.process(&mut output);
+
+        let latency = shifter
+            .latency()
+            .as_seconds()
+            .to_samples(sample_rate)
+            .as_usize();
+        for (output, input) in output.chan(0)[latency..].iter().zip(input.chan(0)) {
+            assert!((output - input).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn blocks_give_the_same_output_as_one_buffer() {
+        let input = sine(220.0, 1024);
+        let sample_rate = SampleRate::from(SAMPLE_RATE);
+        let mut whole = input.clone();
+        let mut shifter = PitchShifter::new(Semitones::from(3.0), sample_rate, Channels::from(1));
+        shifter.process(&mut whole);
+
+        shifter.reset();
+        let mut block = Buffer::<f32>::allocate(Channels::from(1), Samples::from(128));
+        for start in (0..1024).step_by(128) {
+            block
+                .chan_mut(0)
+                .copy_from_slice(&input.chan(0)[start..start + 128]);
+            shifter.process(&mut block);
+            assert_eq!(block.chan(0), &whole.chan(0)[start..start + 128]);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//! This module re-exports what most code that uses this crate needs: the buffers and their
+//! iterators, the processor and sample traits, and all units, so one import is enough.
+//! ```rust
+//! use rabu::prelude::*;
+//!
+//! struct Gain(Decibels);
+//!
+//! impl AudioProcessor for Gain {
+//!     fn prepare(&mut self, _: SampleRate, _: BufferSize, _: Channels) {}
+//!
+//!     fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+//!         let gain = self.0.to_gain() as f32;
+//!         buffer.map_samples(|sample| sample * gain);
+//!     }
+//!
+//!     fn reset(&mut self) {}
+//! }
+//!
+//! let mut buffer = Buffer::allocate(Channels::from(2), Samples::from(4));
+//! buffer.chan_mut(0).fill(1.0);
+//! Gain(Decibels::from(-20.0)).process(&mut buffer, &ProcessContext::new(SampleRate::from(48000)));
+//!
+//! assert!((buffer.chan(0)[0] - 0.1).abs() < 1e-6);
+//! ```
+
+pub use crate::buffer::{Buffer, ChannelIterator, InterleavedIterator, MutChannelIterator};
+pub use crate::buffer_view::{BufferView, BufferViewMut};
+pub use crate::processor::{AudioProcessor, ProcessContext, SampleProcessor};
+pub use crate::sample::Sample;
+pub use crate::units::*;
@@ -0,0 +1,269 @@
+//! This module contains presets, which store the settings of a processor so they can be saved
+//! and shared. Every processor with presets has a plain settings struct, e.g.
+//! `CompressorSettings`, which can be serialized with serde when the `serde` feature is on.
+//! `save_preset` wraps the settings together with the kind of processor and the version of
+//! its settings, and `load_preset` checks both before it applies them, so a preset for another
+//! processor, or from a newer version of the crate, is refused instead of misread. Fields added
+//! to a settings struct later on take their default when an older preset is read. Settings that
+//! can be unlimited, e.g. the range of a compressor, are stored as `null` when they are, as
+//! formats like JSON can't store infinity.
+//! ```rust
+//! use rabu::dynamics::Compressor;
+//! use rabu::preset::{load_preset, save_preset};
+//! use rabu::units::{Decibels, Ratio, SampleRate, Seconds};
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let mut compressor = Compressor::new(Decibels::from(-18.0), Ratio::from(4.0), sample_rate);
+//! compressor.set_attack(Seconds::from(0.005));
+//!
+//! // Serialize the preset with any serde format, e.g. JSON.
+//! let preset = save_preset(&compressor);
+//! assert_eq!(preset.processor, "compressor");
+//!
+//! let mut other = Compressor::new(Decibels::from(0.0), Ratio::from(1.0), sample_rate);
+//! assert!(load_preset(&mut other, &preset));
+//! assert_eq!(other.threshold(), Decibels::from(-18.0));
+//! assert_eq!(other.attack(), Seconds::from(0.005));
+//! ```
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "serde")]
+use crate::units::{Decibels, Ratio};
+
+/// A processor whose settings can be saved as a preset.
+pub trait Preset {
+    /// The settings that make up a preset.
+    type Settings: Clone;
+
+    /// Identifies the kind of processor, so presets of other processors are refused.
+    const PROCESSOR: &'static str;
+
+    /// The version of the settings, which goes up when they change in a way older versions
+    /// can't read.
+    const VERSION: u32;
+
+    /// Returns the current settings.
+    fn settings(&self) -> Self::Settings;
+
+    /// Changes all settings at once.
+    fn apply_settings(&mut self, settings: &Self::Settings);
+}
+
+/// The settings of a processor, together with what they belong to.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PresetFile<S> {
+    /// The kind of processor the settings are for.
+    pub processor: String,
+    /// The version of the settings.
+    pub version: u32,
+    /// The settings.
+    pub settings: S,
+}
+
+/// Takes the settings of the processor as a preset.
+pub fn save_preset<P: Preset>(processor: &P) -> PresetFile<P::Settings> {
+    PresetFile {
+        processor: P::PROCESSOR.to_string(),
+        version: P::VERSION,
+        settings: processor.settings(),
+    }
+}
+
+/// Applies the preset to the processor. Returns `false`, and leaves the processor as it is,
+/// when the preset is for another kind of processor, or has a newer version than the
+/// processor understands.
+pub fn load_preset<P: Preset>(processor: &mut P, preset: &PresetFile<P::Settings>) -> bool {
+    if preset.processor != P::PROCESSOR || preset.version > P::VERSION {
+        return false;
+    }
+    processor.apply_settings(&preset.settings);
+    true
+}
+
+/// A setting that can be unlimited, which is stored as `None` by `unlimited`.
+#[cfg(feature = "serde")]
+pub(crate) trait Unlimited: Copy {
+    /// The value that means unlimited.
+    fn unlimited() -> Self;
+
+    fn is_unlimited(&self) -> bool;
+}
+
+/// An unlimited level, e.g. a range or threshold, is minus infinity.
+#[cfg(feature = "serde")]
+impl Unlimited for Decibels {
+    fn unlimited() -> Self {
+        Decibels::from(f64::NEG_INFINITY)
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.as_f64() == f64::NEG_INFINITY
+    }
+}
+
+/// An unlimited ratio is infinite.
+#[cfg(feature = "serde")]
+impl Unlimited for Ratio {
+    fn unlimited() -> Self {
+        Ratio::from(f64::INFINITY)
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.as_f64() == f64::INFINITY
+    }
+}
+
+/// Serializes a setting that can be unlimited as an `Option`, with `None` for unlimited, for
+/// use with `#[serde(with = "crate::preset::unlimited")]`.
+#[cfg(feature = "serde")]
+pub(crate) mod unlimited {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Unlimited;
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Unlimited + Serialize,
+        S: Serializer,
+    {
+        (!value.is_unlimited())
+            .then_some(value)
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Unlimited + Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_else(T::unlimited))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biquad::FilterType;
+    use crate::dynamics::{Compressor, CompressorSettings};
+    use crate::eq::{EqBand, Equalizer};
+    use crate::gate::Gate;
+    use crate::limiter::Limiter;
+    use crate::units::{Channels, Decibels, Frequency, Ratio, SampleRate, Seconds};
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn compressor() -> Compressor {
+        Compressor::new(
+            Decibels::from(0.0),
+            Ratio::from(1.0),
+            SampleRate::from(SAMPLE_RATE),
+        )
+    }
+
+    #[test]
+    fn compressor_settings_survive_a_round_trip() {
+        let mut compressor = compressor();
+        compressor.set_threshold(Decibels::from(-24.0));
+        compressor.set_ratio(Ratio::from(8.0));
+        compressor.set_knee(Decibels::from(6.0));
+        compressor.set_makeup_gain(Decibels::from(3.0));
+        compressor.set_range(Decibels::from(-12.0));
+        compressor.set_release(Seconds::from(0.25));
+
+        let preset = save_preset(&compressor);
+        let mut loaded = self::compressor();
+
+        assert!(load_preset(&mut loaded, &preset));
+        assert_eq!(loaded.settings(), compressor.settings());
+        assert_eq!(preset.version, Compressor::VERSION);
+    }
+
+    #[test]
+    fn equalizer_settings_survive_a_round_trip() {
+        let sample_rate = SampleRate::from(SAMPLE_RATE);
+        let mut band = EqBand::new(
+            FilterType::Peak,
+            Frequency::from(2500.0),
+            1.4,
+            Decibels::from(-4.5),
+        );
+        band.enabled = false;
+        let equalizer = Equalizer::new(sample_rate, Channels::from(2), vec![band]);
+
+        let preset = save_preset(&equalizer);
+        let mut loaded = Equalizer::new(sample_rate, Channels::from(2), Vec::new());
+
+        assert!(load_preset(&mut loaded, &preset));
+        assert_eq!(loaded.bands(), [band]);
+    }
+
+    #[test]
+    fn gate_and_limiter_settings_survive_a_round_trip() {
+        let sample_rate = SampleRate::from(SAMPLE_RATE);
+        let mut gate = Gate::new(Decibels::from(-40.0), sample_rate);
+        gate.set_hold(Seconds::from(0.05));
+        let mut limiter = Limiter::new(Channels::from(2), sample_rate, Seconds::from(0.005));
+        limiter.set_ceiling(Decibels::from(-1.0));
+        limiter.set_true_peak(true);
+
+        let mut loaded_gate = Gate::new(Decibels::from(0.0), sample_rate);
+        let mut loaded_limiter = Limiter::new(Channels::from(2), sample_rate, Seconds::from(0.005));
+
+        assert!(load_preset(&mut loaded_gate, &save_preset(&gate)));
+        assert!(load_preset(&mut loaded_limiter, &save_preset(&limiter)));
+        assert_eq!(loaded_gate.settings(), gate.settings());
+        assert_eq!(loaded_limiter.settings(), limiter.settings());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn unlimited_settings_survive_json() {
+        let sample_rate = SampleRate::from(SAMPLE_RATE);
+        let mut compressor = Compressor::new(
+            Decibels::from(f64::NEG_INFINITY),
+            Ratio::from(f64::INFINITY),
+            sample_rate,
+        );
+        compressor.set_knee(Decibels::from(6.0));
+        let gate = Gate::new(Decibels::from(f64::NEG_INFINITY), sample_rate);
+
+        let json = serde_json::to_string(&save_preset(&compressor)).unwrap();
+        let loaded: PresetFile<CompressorSettings> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.settings, compressor.settings());
+        assert!(json.contains(r#""range":null"#), "{json}");
+
+        let json = serde_json::to_string(&save_preset(&gate)).unwrap();
+        let loaded: PresetFile<crate::gate::GateSettings> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.settings, gate.settings());
+    }
+
+    #[test]
+    fn presets_of_other_processors_are_refused() {
+        let mut preset = save_preset(&compressor());
+        preset.processor = "limiter".to_string();
+        preset.settings.threshold = Decibels::from(-30.0);
+        let mut compressor = compressor();
+
+        assert!(!load_preset(&mut compressor, &preset));
+        assert_eq!(compressor.threshold(), Decibels::from(0.0));
+    }
+
+    #[test]
+    fn presets_from_newer_versions_are_refused() {
+        let preset = PresetFile {
+            processor: Compressor::PROCESSOR.to_string(),
+            version: Compressor::VERSION + 1,
+            settings: CompressorSettings {
+                threshold: Decibels::from(-30.0),
+                ..CompressorSettings::default()
+            },
+        };
+        let mut compressor = compressor();
+
+        assert!(!load_preset(&mut compressor, &preset));
+        assert_eq!(compressor.threshold(), Decibels::from(0.0));
+    }
+}
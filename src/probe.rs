@@ -0,0 +1,205 @@
+//! This module reads the properties of an audio file from its header, without decoding the
+//! audio, so it's fast enough to fill a file browser. WAV files (also RF64) and FLAC files are
+//! recognized.
+//! ```rust
+//! use std::io::Cursor;
+//!
+//! use rabu::buffer::Buffer;
+//! use rabu::probe::probe;
+//! use rabu::quantize::SampleFormat;
+//! use rabu::units::{BitDepth, Channels, Duration, SampleRate, Samples};
+//! use rabu::wav::WavWriter;
+//!
+//! let format = SampleFormat::Int(BitDepth::Bits24);
+//! let mut bytes = Cursor::new(Vec::new());
+//! let mut writer = WavWriter::new(&mut bytes, SampleRate::from(48000), Channels::from(2), format)
+//!     .unwrap();
+//! writer.write(&Buffer::<f32>::allocate(Channels::from(2), Samples::from(24000)));
+//! drop(writer);
+//!
+//! // Usually with `probe_file`, which reads from a path.
+//! let info = probe(Cursor::new(bytes.into_inner())).unwrap();
+//!
+//! assert_eq!(info.sample_rate, SampleRate::from(48000));
+//! assert_eq!(info.num_channels, Channels::from(2));
+//! assert_eq!(info.format, format);
+//! assert_eq!(info.duration, Duration::from_secs_f64(0.5));
+//! assert_eq!(info.codec, "PCM");
+//! ```
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::quantize::SampleFormat;
+use crate::units::{BitDepth, Channels, Duration, SampleRate, Samples};
+use crate::wav::WavReader;
+
+/// The properties of an audio file.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AudioFileInfo {
+    /// The sample rate.
+    pub sample_rate: SampleRate,
+    /// The number of channels.
+    pub num_channels: Channels,
+    /// The format the samples are stored in, or decode to for compressed files.
+    pub format: SampleFormat,
+    /// The number of samples per channel.
+    pub num_samples: Samples,
+    /// The length of the audio.
+    pub duration: Duration,
+    /// The name of the codec, e.g. "PCM" or "FLAC".
+    pub codec: &'static str,
+}
+
+/// Reads the properties of the file at the path, or gives `None` when it can't be read or
+/// isn't a recognized audio file.
+pub fn probe_file(path: impl AsRef<Path>) -> Option<AudioFileInfo> {
+    probe(BufReader::new(File::open(path).ok()?))
+}
+
+/// Reads the properties of the audio file in the reader, or gives `None` when it isn't a
+/// recognized audio file.
+pub fn probe<R: Read + Seek>(mut reader: R) -> Option<AudioFileInfo> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic).ok()?;
+    reader.seek(SeekFrom::Start(0)).ok()?;
+
+    match &magic {
+        b"RIFF" | b"RF64" => {
+            let wav = WavReader::new(reader)?;
+            let codec = match wav.format() {
+                SampleFormat::Int(_) => "PCM",
+                SampleFormat::Float32 => "IEEE float",
+            };
+            Some(info(
+                wav.sample_rate(),
+                wav.num_channels(),
+                wav.format(),
+                wav.num_samples(),
+                codec,
+            ))
+        }
+        b"fLaC" => probe_flac(reader),
+        _ => None,
+    }
+}
+
+/// Reads the stream info block, which always comes first in a FLAC file.
+fn probe_flac<R: Read>(mut reader: R) -> Option<AudioFileInfo> {
+    // The marker, the header of the block, and the block of 34 bytes.
+    let mut header = [0; 4 + 4 + 34];
+    reader.read_exact(&mut header).ok()?;
+    let (block_header, stream_info) = header[4..].split_at(4);
+    if block_header[0] & 0x7F != 0 {
+        return None;
+    }
+
+    // The stream info packs these after the block and frame sizes:
+    // 20 bits sample rate, 3 bits channels - 1, 5 bits bits per sample - 1, 36 bits samples.
+    let packed = u64::from_be_bytes(stream_info[10..18].try_into().ok()?);
+    let sample_rate = (packed >> 44) as u32;
+    let num_channels = ((packed >> 41) & 0x7) as usize + 1;
+    let bits = ((packed >> 36) & 0x1F) + 1;
+    let num_samples = packed & 0xF_FFFF_FFFF;
+
+    let bit_depth = match bits {
+        8 => BitDepth::Bits8,
+        16 => BitDepth::Bits16,
+        24 => BitDepth::Bits24,
+        32 => BitDepth::Bits32,
+        _ => return None,
+    };
+    if sample_rate == 0 {
+        return None;
+    }
+    Some(info(
+        SampleRate::from(sample_rate),
+        Channels::from(num_channels),
+        SampleFormat::Int(bit_depth),
+        Samples::from(num_samples),
+        "FLAC",
+    ))
+}
+
+fn info(
+    sample_rate: SampleRate,
+    num_channels: Channels,
+    format: SampleFormat,
+    num_samples: Samples,
+    codec: &'static str,
+) -> AudioFileInfo {
+    AudioFileInfo {
+        sample_rate,
+        num_channels,
+        format,
+        num_samples,
+        duration: Duration::from(num_samples.to_seconds(sample_rate)),
+        codec,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::buffer::Buffer;
+    use crate::wav::WavWriter;
+
+    /// The start of a FLAC file with the stream info of the properties.
+    fn flac(sample_rate: u64, num_channels: u64, bits: u64, num_samples: u64) -> Vec<u8> {
+        let mut bytes = b"fLaC".to_vec();
+        // The last metadata block, of type stream info, with a length of 34.
+        bytes.extend([0x80, 0, 0, 34]);
+        bytes.extend([0x10, 0, 0x10, 0, 0, 0, 0, 0, 0, 0]);
+        let packed = sample_rate << 44 | (num_channels - 1) << 41 | (bits - 1) << 36 | num_samples;
+        bytes.extend(packed.to_be_bytes());
+        bytes.extend([0; 16]);
+        bytes
+    }
+
+    #[test]
+    fn probes_wav_files() {
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = WavWriter::new(
+            &mut bytes,
+            SampleRate::from(1000),
+            Channels::from(3),
+            SampleFormat::Float32,
+        )
+        .unwrap();
+        writer.write(&Buffer::<f32>::allocate(
+            Channels::from(3),
+            Samples::from(250),
+        ));
+        drop(writer);
+
+        let info = probe(Cursor::new(bytes.into_inner())).unwrap();
+
+        assert_eq!(info.num_channels, Channels::from(3));
+        assert_eq!(info.format, SampleFormat::Float32);
+        assert_eq!(info.num_samples, Samples::from(250));
+        assert_eq!(info.duration, Duration::from_secs_f64(0.25));
+        assert_eq!(info.codec, "IEEE float");
+    }
+
+    #[test]
+    fn probes_flac_files() {
+        let info = probe(Cursor::new(flac(44100, 2, 16, 88200))).unwrap();
+
+        assert_eq!(info.sample_rate, SampleRate::from(44100));
+        assert_eq!(info.num_channels, Channels::from(2));
+        assert_eq!(info.format, SampleFormat::Int(BitDepth::Bits16));
+        assert_eq!(info.duration, Duration::from_secs_f64(2.0));
+        assert_eq!(info.codec, "FLAC");
+    }
+
+    #[test]
+    fn rejects_unrecognized_files() {
+        assert!(probe(Cursor::new(b"ID3\x04 an mp3 file".to_vec())).is_none());
+        assert!(probe(Cursor::new(flac(44100, 2, 20, 100))).is_none());
+        assert!(probe(Cursor::new(b"fLaC".to_vec())).is_none());
+        assert!(probe_file("does/not/exist.wav").is_none());
+    }
+}
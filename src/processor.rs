@@ -0,0 +1,279 @@
+//! This module contains the traits that are shared by the processors in this crate.
+//! The `SampleProcessor` trait is implemented for closures as well, so any `FnMut(f64) -> f64`
+//! can be used wherever a processor is expected:
+//! ```rust
+//! use rabu::processor::SampleProcessor;
+//!
+//! let mut gain = |sample: f64| sample * 0.5;
+//!
+//! assert_eq!(gain.process(1.0), 0.5);
+//! ```
+//!
+//! The `AudioProcessor` trait is for processors that work on whole blocks, like the effects
+//! of a host. The host prepares them once with the format of the audio, and then calls them
+//! for every block:
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::dynamics::Compressor;
+//! use rabu::gate::Gate;
+//! use rabu::processor::{AudioProcessor, ProcessContext};
+//! use rabu::units::{BufferSize, Channels, Decibels, Ratio, SampleRate, Samples};
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let mut chain: Vec<Box<dyn AudioProcessor>> = vec![
+//!     Box::new(Gate::new(Decibels::from(-60.0), sample_rate)),
+//!     Box::new(Compressor::new(Decibels::from(-20.0), Ratio::from(4.0), sample_rate)),
+//! ];
+//! for processor in &mut chain {
+//!     processor.prepare(sample_rate, BufferSize::from(256), Channels::from(2));
+//! }
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(256));
+//! let context = ProcessContext::new(sample_rate);
+//! for processor in &mut chain {
+//!     processor.process(&mut buffer, &context);
+//! }
+//! ```
+//!
+//! Processors that can be cloned can also take a `ProcessorSnapshot` of their settings and
+//! internal state, to compare two versions of the settings or to undo a change:
+//! ```rust
+//! use rabu::dynamics::Compressor;
+//! use rabu::gate::Gate;
+//! use rabu::processor::{Snapshot, SnapshotProcessor};
+//! use rabu::units::{Decibels, Ratio, SampleRate};
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let mut compressor = Compressor::new(Decibels::from(-20.0), Ratio::from(4.0), sample_rate);
+//! let a = compressor.snapshot();
+//! compressor.set_threshold(Decibels::from(-30.0));
+//!
+//! assert!(compressor.restore(&a));
+//! assert_eq!(compressor.threshold(), Decibels::from(-20.0));
+//!
+//! // A host can take snapshots of a whole chain.
+//! let chain: Vec<Box<dyn SnapshotProcessor>> = vec![
+//!     Box::new(Gate::new(Decibels::from(-60.0), sample_rate)),
+//!     Box::new(compressor),
+//! ];
+//! let snapshots: Vec<_> = chain.iter().map(|processor| processor.snapshot()).collect();
+//! ```
+
+use std::any::Any;
+
+use crate::buffer::Buffer;
+use crate::units::{BufferSize, Channels, Latency, SampleRate, Samples, Tempo, TimeSignature};
+
+/// Something that processes a mono signal one sample at a time, like a filter.
+pub trait SampleProcessor {
+    /// Processes one sample of input audio and produces one output sample.
+    fn process(&mut self, input: f64) -> f64;
+}
+
+impl<F> SampleProcessor for F
+where
+    F: FnMut(f64) -> f64,
+{
+    fn process(&mut self, input: f64) -> f64 {
+        self(input)
+    }
+}
+
+/// What a processor knows about the block it processes, apart from the audio.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ProcessContext {
+    /// The sample rate of the audio.
+    pub sample_rate: SampleRate,
+    /// The position of the first sample of the block on the timeline.
+    pub position: Samples,
+    /// Whether the timeline is playing.
+    pub is_playing: bool,
+    /// The tempo of the timeline, if it has one.
+    pub tempo: Option<Tempo>,
+    /// The time signature of the timeline, if it has one.
+    pub time_signature: Option<TimeSignature>,
+}
+
+impl ProcessContext {
+    /// Creates a new context for a stopped timeline at the start, without a tempo.
+    pub fn new(sample_rate: SampleRate) -> Self {
+        Self {
+            sample_rate,
+            position: Samples::from(0),
+            is_playing: false,
+            tempo: None,
+            time_signature: None,
+        }
+    }
+}
+
+/// Something that processes blocks of audio in place, like an effect in a host.
+pub trait AudioProcessor {
+    /// Gets the processor ready for audio in the given format, before processing starts or when
+    /// the format changes. The blocks will be no longer than the buffer size.
+    fn prepare(&mut self, sample_rate: SampleRate, buffer_size: BufferSize, num_channels: Channels);
+
+    /// Processes a block of audio in place.
+    fn process(&mut self, buffer: &mut Buffer<f32>, context: &ProcessContext);
+
+    /// Returns the delay between input and output. This is 0 unless the processor says
+    /// otherwise.
+    fn latency(&self) -> Latency {
+        Latency::from_secs_f64(0.0)
+    }
+
+    /// Clears the internal state, as if no audio was processed yet.
+    fn reset(&mut self);
+}
+
+impl<P: AudioProcessor + ?Sized> AudioProcessor for Box<P> {
+    fn prepare(
+        &mut self,
+        sample_rate: SampleRate,
+        buffer_size: BufferSize,
+        num_channels: Channels,
+    ) {
+        (**self).prepare(sample_rate, buffer_size, num_channels)
+    }
+
+    fn process(&mut self, buffer: &mut Buffer<f32>, context: &ProcessContext) {
+        (**self).process(buffer, context)
+    }
+
+    fn latency(&self) -> Latency {
+        (**self).latency()
+    }
+
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+}
+
+/// A copy of the settings and internal state of a processor, taken with `Snapshot::snapshot`.
+pub struct ProcessorSnapshot(Box<dyn Any + Send>);
+
+impl std::fmt::Debug for ProcessorSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProcessorSnapshot").finish_non_exhaustive()
+    }
+}
+
+/// Something that can save and bring back its settings together with its internal state, like
+/// the filter memory and envelopes. This is implemented for every processor that can be cloned.
+pub trait Snapshot {
+    /// Takes a copy of the settings and internal state. This allocates, so it should be called
+    /// outside of the audio thread.
+    fn snapshot(&self) -> ProcessorSnapshot;
+
+    /// Brings the processor back to the snapshot, as if the processing since the snapshot
+    /// didn't happen. This clones the snapshot, which allocates for most processors, so it
+    /// should be called outside of the audio thread too, e.g. on a copy of the processor that
+    /// is swapped in afterwards. Returns `false`, and leaves the processor as it is, when the
+    /// snapshot was taken of another kind of processor.
+    fn restore(&mut self, snapshot: &ProcessorSnapshot) -> bool;
+}
+
+impl<P: AudioProcessor + Clone + Send + 'static> Snapshot for P {
+    fn snapshot(&self) -> ProcessorSnapshot {
+        ProcessorSnapshot(Box::new(self.clone()))
+    }
+
+    fn restore(&mut self, snapshot: &ProcessorSnapshot) -> bool {
+        match snapshot.0.downcast_ref::<P>() {
+            Some(state) => {
+                self.clone_from(state);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// An `AudioProcessor` that supports snapshots, so a host can keep processors of different
+/// kinds as `Box<dyn SnapshotProcessor>` and still take snapshots of them.
+pub trait SnapshotProcessor: AudioProcessor + Snapshot {}
+
+impl<P: AudioProcessor + Snapshot> SnapshotProcessor for P {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biquad::FilterType;
+    use crate::dynamics::Compressor;
+    use crate::eq::{EqBand, Equalizer};
+    use crate::gate::Gate;
+    use crate::signals::white_noise;
+    use crate::units::{Decibels, Frequency, Ratio};
+
+    #[test]
+    fn prepare_adapts_the_equalizer_to_the_format() {
+        let band = EqBand::new(
+            FilterType::LowPass,
+            Frequency::from(100.0),
+            0.7,
+            Decibels::from(0.0),
+        );
+        let mut processor: Box<dyn AudioProcessor> = Box::new(Equalizer::new(
+            SampleRate::from(48000),
+            Channels::from(1),
+            vec![band],
+        ));
+        processor.prepare(
+            SampleRate::from(8000),
+            BufferSize::from(64),
+            Channels::from(2),
+        );
+
+        // A tone at 4 kHz is at the Nyquist frequency at 8 kHz, far above the cutoff.
+        let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(64));
+        for channel in buffer.iter_chans_mut() {
+            for (n, sample) in channel.iter_mut().enumerate() {
+                *sample = if n % 2 == 0 { 1.0 } else { -1.0 };
+            }
+        }
+        processor.process(&mut buffer, &ProcessContext::new(SampleRate::from(8000)));
+
+        assert_eq!(processor.latency(), Latency::from_secs_f64(0.0));
+        for channel in buffer.iter_chans() {
+            assert!(channel[32..].iter().all(|sample| sample.abs() < 0.01));
+        }
+    }
+
+    fn compressor() -> Compressor {
+        Compressor::new(
+            Decibels::from(-20.0),
+            Ratio::from(4.0),
+            SampleRate::from(48000),
+        )
+    }
+
+    #[test]
+    fn restore_brings_back_the_settings_and_the_state() {
+        let noise = white_noise::<f32>(Channels::from(1), Samples::from(512), 3);
+        let context = ProcessContext::new(SampleRate::from(48000));
+        let mut compressor = compressor();
+        AudioProcessor::process(&mut compressor, &mut noise.clone(), &context);
+        let snapshot = compressor.snapshot();
+
+        let mut expected = noise.clone();
+        AudioProcessor::process(&mut compressor, &mut expected, &context);
+
+        compressor.set_threshold(Decibels::from(-40.0));
+        AudioProcessor::process(&mut compressor, &mut noise.clone(), &context);
+        assert!(compressor.restore(&snapshot));
+
+        let mut restored = noise.clone();
+        AudioProcessor::process(&mut compressor, &mut restored, &context);
+        assert_eq!(compressor.threshold(), Decibels::from(-20.0));
+        assert_eq!(restored.chan(0), expected.chan(0));
+    }
+
+    #[test]
+    fn snapshots_of_other_processors_are_refused() {
+        let gate = Gate::new(Decibels::from(-60.0), SampleRate::from(48000));
+        let mut processor: Box<dyn SnapshotProcessor> = Box::new(compressor());
+
+        assert!(!processor.restore(&gate.snapshot()));
+        assert!(processor.restore(&processor.snapshot()));
+    }
+}
@@ -0,0 +1,350 @@
+//! This module converts floating point buffers to the integer (or float) PCM that files and
+//! streams expect. Samples beyond full scale can't be stored in an integer format and are
+//! clipped, so every conversion also reports which samples were clipped. A [`Dither`] can be
+//! passed along to dither the audio on the way down.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::dither::Dither;
+//! use rabu::quantize::{to_bytes, to_pcm, SampleFormat};
+//! use rabu::units::{BitDepth, Channels, Samples};
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(4));
+//! buffer.chan_mut(0)[1] = 0.5;
+//! buffer.chan_mut(1)[3] = 1.5;
+//!
+//! let (pcm, clipping) = to_pcm(&buffer, BitDepth::Bits16, None);
+//! assert_eq!(pcm.chan(0)[1], 16384);
+//! assert_eq!(pcm.chan(1)[3], 32767);
+//! assert_eq!(clipping.count(), 1);
+//!
+//! let mut dither = Dither::new(BitDepth::Bits24, Channels::from(2), 7);
+//! let (bytes, _) = to_bytes(&buffer, SampleFormat::Int(BitDepth::Bits24), Some(&mut dither));
+//! assert_eq!(bytes.len(), 2 * 4 * 3);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::dither::Dither;
+use crate::sample::Sample;
+use crate::units::{BitDepth, Channels, Samples};
+
+/// The way samples are stored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SampleFormat {
+    /// Signed integers of the bit depth, except for 8 bits, which is stored unsigned with 128
+    /// as silence, like in WAV files.
+    Int(BitDepth),
+    /// 32 bit floats, where full scale is 1.
+    Float32,
+}
+
+impl SampleFormat {
+    /// Returns the number of bytes one sample takes up.
+    pub fn bytes_per_sample(&self) -> usize {
+        match self {
+            SampleFormat::Int(bit_depth) => bit_depth.to_u16() as usize / 8,
+            SampleFormat::Float32 => 4,
+        }
+    }
+}
+
+impl From<BitDepth> for SampleFormat {
+    fn from(bit_depth: BitDepth) -> Self {
+        SampleFormat::Int(bit_depth)
+    }
+}
+
+/// The place of a sample that was beyond full scale.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClippedSample {
+    pub channel: usize,
+    pub position: Samples,
+}
+
+/// The samples that were beyond full scale during a conversion, ordered by channel and then by
+/// position.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Clipping {
+    clipped: Vec<ClippedSample>,
+}
+
+impl Clipping {
+    /// Returns the number of clipped samples.
+    pub fn count(&self) -> usize {
+        self.clipped.len()
+    }
+
+    /// Tells whether any sample clipped.
+    pub fn is_clipped(&self) -> bool {
+        !self.clipped.is_empty()
+    }
+
+    /// Returns the clipped samples.
+    pub fn positions(&self) -> &[ClippedSample] {
+        &self.clipped
+    }
+
+    fn check(&mut self, channel: usize, position: usize, sample: f64) {
+        if sample.abs() > 1.0 {
+            self.clipped.push(ClippedSample {
+                channel,
+                position: Samples::from(position),
+            });
+        }
+    }
+}
+
+/// Converts the buffer to integers of the bit depth, where full scale is 2^(bits - 1). Without
+/// a dither, the samples are rounded.
+/// This will panic if the dither is for another bit depth or number of channels.
+pub fn to_pcm<T: Sample>(
+    buffer: &Buffer<T>,
+    bit_depth: BitDepth,
+    mut dither: Option<&mut Dither>,
+) -> (Buffer<i32>, Clipping) {
+    if let Some(dither) = &dither {
+        assert_eq!(
+            dither.bit_depth(),
+            bit_depth,
+            "the dither has another bit depth"
+        );
+    }
+    let full_scale = 2.0_f64.powi(bit_depth.to_u16() as i32 - 1);
+    let mut pcm = Buffer::allocate(buffer.num_channels(), buffer.num_samples());
+    let mut clipping = Clipping::default();
+
+    for channel in buffer.channel_indices() {
+        let input = buffer.chan(channel);
+        for (position, output) in pcm.chan_mut(channel).iter_mut().enumerate() {
+            let sample = input[position].to_f64();
+            clipping.check(channel, position, sample);
+            *output = match dither.as_deref_mut() {
+                Some(dither) => dither.quantize(channel, sample),
+                None => (sample * full_scale)
+                    .round()
+                    .clamp(-full_scale, full_scale - 1.0) as i32,
+            };
+        }
+    }
+    (pcm, clipping)
+}
+
+/// Converts the buffer to interleaved little endian bytes of the format, ready to be written to
+/// a WAV file or a stream. The dither is only used for integer formats. Float samples are
+/// stored as they are, but samples beyond full scale are still reported, because they will
+/// clip on playback.
+/// This will panic if the dither is for another bit depth or number of channels.
+pub fn to_bytes<T: Sample>(
+    buffer: &Buffer<T>,
+    format: SampleFormat,
+    dither: Option<&mut Dither>,
+) -> (Vec<u8>, Clipping) {
+    let num_channels = buffer.num_channels().as_usize();
+    let mut bytes = Vec::with_capacity(
+        buffer.num_samples().as_usize() * num_channels * format.bytes_per_sample(),
+    );
+
+    match format {
+        SampleFormat::Int(bit_depth) => {
+            let (pcm, clipping) = to_pcm(buffer, bit_depth, dither);
+            for sample in pcm.iter_interleaved() {
+                match bit_depth {
+                    BitDepth::Bits8 => bytes.push((sample + 128) as u8),
+                    BitDepth::Bits16 => bytes.extend_from_slice(&(sample as i16).to_le_bytes()),
+                    BitDepth::Bits24 => bytes.extend_from_slice(&sample.to_le_bytes()[..3]),
+                    BitDepth::Bits32 => bytes.extend_from_slice(&sample.to_le_bytes()),
+                }
+            }
+            (bytes, clipping)
+        }
+        SampleFormat::Float32 => {
+            let mut clipping = Clipping::default();
+            for channel in buffer.channel_indices() {
+                for (position, sample) in buffer.chan(channel).iter().enumerate() {
+                    clipping.check(channel, position, sample.to_f64());
+                }
+            }
+            for sample in buffer.iter_interleaved() {
+                bytes.extend_from_slice(&(sample.to_f64() as f32).to_le_bytes());
+            }
+            (bytes, clipping)
+        }
+    }
+}
+
+/// Converts interleaved little endian bytes of the format back to a buffer, which is the
+/// opposite of `to_bytes`. Integers are scaled so full scale is 1, and bytes of an incomplete
+/// frame at the end are left out.
+/// This will panic if there are no channels.
+pub fn from_bytes<T: Sample>(
+    bytes: &[u8],
+    format: SampleFormat,
+    num_channels: Channels,
+) -> Buffer<T> {
+    assert!(num_channels.as_usize() > 0, "there must be channels");
+    let frame_size = format.bytes_per_sample() * num_channels.as_usize();
+    let mut buffer = Buffer::allocate(num_channels, Samples::from(bytes.len() / frame_size));
+    from_bytes_into(bytes, format, &mut buffer);
+    buffer
+}
+
+/// Converts interleaved little endian bytes like `from_bytes`, into a buffer that is already
+/// allocated. The bytes are read with the channels of the buffer, which is resized to the
+/// number of whole frames, so this doesn't allocate when the buffer held at least as many
+/// samples.
+/// This will panic if the buffer has no channels.
+pub fn from_bytes_into<T: Sample>(bytes: &[u8], format: SampleFormat, buffer: &mut Buffer<T>) {
+    let num_channels = buffer.num_channels();
+    assert!(num_channels.as_usize() > 0, "there must be channels");
+    let bytes_per_sample = format.bytes_per_sample();
+    let frame_size = bytes_per_sample * num_channels.as_usize();
+    let num_samples = bytes.len() / frame_size;
+    buffer.set_num_samples(Samples::from(num_samples));
+
+    for (index, sample) in bytes
+        .chunks_exact(bytes_per_sample)
+        .take(num_samples * num_channels.as_usize())
+        .enumerate()
+    {
+        let value = match format {
+            SampleFormat::Int(BitDepth::Bits8) => (sample[0] as f64 - 128.0) / 128.0,
+            SampleFormat::Int(BitDepth::Bits16) => {
+                i16::from_le_bytes([sample[0], sample[1]]) as f64 / 32768.0
+            }
+            SampleFormat::Int(BitDepth::Bits24) => {
+                // Places the 24 bits at the top, so the sign is kept.
+                i32::from_le_bytes([0, sample[0], sample[1], sample[2]]) as f64 / 2_147_483_648.0
+            }
+            SampleFormat::Int(BitDepth::Bits32) => {
+                i32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]) as f64
+                    / 2_147_483_648.0
+            }
+            SampleFormat::Float32 => {
+                f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]) as f64
+            }
+        };
+        let channel = index % num_channels.as_usize();
+        buffer.chan_mut(channel)[index / num_channels.as_usize()] = T::from_f64(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn mono(samples: &[f64]) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(samples.len()));
+        buffer.chan_mut(0).copy_from_slice(samples);
+        buffer
+    }
+
+    #[test_case(BitDepth::Bits8, 0.5 => 64; "8 bits")]
+    #[test_case(BitDepth::Bits16, -0.25 => -8192; "16 bits")]
+    #[test_case(BitDepth::Bits24, 1.0 => 8388607; "24 bits full scale")]
+    #[test_case(BitDepth::Bits32, -1.0 => i32::MIN; "32 bits negative full scale")]
+    #[test_case(BitDepth::Bits16, 3.0 => 32767; "clips")]
+    fn converts_to_integers(bit_depth: BitDepth, sample: f64) -> i32 {
+        to_pcm(&mono(&[sample]), bit_depth, None).0.chan(0)[0]
+    }
+
+    #[test]
+    fn reports_the_clipped_samples() {
+        let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(4));
+        buffer.chan_mut(0)[2] = -1.2;
+        buffer.chan_mut(1)[0] = 1.0;
+        buffer.chan_mut(1)[1] = 1.01;
+
+        let (_, clipping) = to_pcm(&buffer, BitDepth::Bits16, None);
+
+        assert_eq!(
+            clipping.positions(),
+            &[
+                ClippedSample {
+                    channel: 0,
+                    position: Samples::from(2)
+                },
+                ClippedSample {
+                    channel: 1,
+                    position: Samples::from(1)
+                },
+            ]
+        );
+    }
+
+    #[test_case(SampleFormat::Int(BitDepth::Bits8) => vec![128, 192, 0]; "8 bits is unsigned")]
+    #[test_case(SampleFormat::Int(BitDepth::Bits16) => vec![0, 0, 0, 64, 0, 128]; "16 bits")]
+    #[test_case(SampleFormat::Int(BitDepth::Bits24) => vec![0, 0, 0, 0, 0, 64, 0, 0, 128]; "24 bits")]
+    #[test_case(SampleFormat::Float32 => vec![0, 0, 0, 0, 0, 0, 0, 63, 0, 0, 128, 191]; "float")]
+    fn converts_to_little_endian_bytes(format: SampleFormat) -> Vec<u8> {
+        to_bytes(&mono(&[0.0, 0.5, -1.0]), format, None).0
+    }
+
+    #[test]
+    fn bytes_are_interleaved() {
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(2), Samples::from(2));
+        buffer.chan_mut(0).copy_from_slice(&[0.5, 0.0]);
+        buffer.chan_mut(1).copy_from_slice(&[-0.5, 0.0]);
+
+        let (bytes, _) = to_bytes(&buffer, BitDepth::Bits8.into(), None);
+
+        assert_eq!(bytes, vec![192, 64, 128, 128]);
+    }
+
+    #[test_case(SampleFormat::Int(BitDepth::Bits8); "8 bits")]
+    #[test_case(SampleFormat::Int(BitDepth::Bits16); "16 bits")]
+    #[test_case(SampleFormat::Int(BitDepth::Bits24); "24 bits")]
+    #[test_case(SampleFormat::Int(BitDepth::Bits32); "32 bits")]
+    #[test_case(SampleFormat::Float32; "float")]
+    fn converts_back_from_bytes(format: SampleFormat) {
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(2), Samples::from(3));
+        buffer.chan_mut(0).copy_from_slice(&[0.0, 0.5, -1.0]);
+        buffer.chan_mut(1).copy_from_slice(&[-0.5, 0.25, 0.0]);
+
+        let (mut bytes, _) = to_bytes(&buffer, format, None);
+        bytes.push(0);
+        let converted = from_bytes::<f64>(&bytes, format, Channels::from(2));
+
+        assert_eq!(converted.data(), buffer.data());
+    }
+
+    #[test]
+    fn converts_back_into_a_larger_buffer() {
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(2), Samples::from(3));
+        buffer.chan_mut(0).copy_from_slice(&[0.0, 0.5, -1.0]);
+        let (bytes, _) = to_bytes(&buffer, SampleFormat::Float32, None);
+
+        let mut converted = Buffer::<f64>::allocate(Channels::from(2), Samples::from(10));
+        from_bytes_into(&bytes, SampleFormat::Float32, &mut converted);
+
+        assert_eq!(converted.num_samples(), Samples::from(3));
+        assert_eq!(converted.data(), buffer.data());
+    }
+
+    #[test]
+    fn float_keeps_samples_beyond_full_scale_but_reports_them() {
+        let (bytes, clipping) = to_bytes(&mono(&[2.0]), SampleFormat::Float32, None);
+
+        assert_eq!(bytes, 2.0_f32.to_le_bytes());
+        assert_eq!(clipping.count(), 1);
+    }
+
+    #[test]
+    fn dither_is_applied() {
+        let mut dither = Dither::new(BitDepth::Bits16, Channels::from(1), 1);
+        let buffer = mono(&[0.4 / 32768.0; 1000]);
+
+        let (plain, _) = to_pcm(&buffer, BitDepth::Bits16, None);
+        let (dithered, _) = to_pcm(&buffer, BitDepth::Bits16, Some(&mut dither));
+
+        assert!(plain.is_default_filled());
+        assert!(!dithered.is_default_filled());
+    }
+
+    #[test]
+    #[should_panic]
+    fn dither_must_match_the_bit_depth() {
+        let mut dither = Dither::new(BitDepth::Bits24, Channels::from(1), 1);
+        to_pcm(&mono(&[0.0]), BitDepth::Bits16, Some(&mut dither));
+    }
+}
@@ -0,0 +1,410 @@
+//! This module contains a recorder that captures input audio into a buffer. The buffer is
+//! allocated up front, so recording never allocates on the audio thread, and input that doesn't
+//! fit anymore is counted as an overrun instead. The recorder is armed first, and records from
+//! the next block, or only between the punch in and punch out points when it has them. It can be
+//! fed with the blocks of a processor, or with the interleaved input of a device callback.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::recorder::{Recorder, RecorderState};
+//! use rabu::units::{Channels, Duration, SampleRate, Samples, TimePoint, TimeSection};
+//!
+//! let mut recorder = Recorder::new(
+//!     SampleRate::from(1000),
+//!     Channels::from(2),
+//!     Duration::from_secs_f64(10.0),
+//! );
+//! recorder.set_punch(Some(TimeSection {
+//!     start: TimePoint::from_secs_f64(0.1),
+//!     duration: Duration::from_secs_f64(0.2),
+//! }));
+//! recorder.arm();
+//!
+//! // Every block of the input, with its position on the timeline.
+//! let mut input = Buffer::<f32>::allocate(Channels::from(2), Samples::from(64));
+//! for block in 0..8 {
+//!     input.chan_mut(0).fill(block as f32);
+//!     recorder.process(&input, Samples::from(block * 64));
+//! }
+//!
+//! assert_eq!(recorder.state(), RecorderState::Finished);
+//! assert_eq!(recorder.start(), Some(Samples::from(100)));
+//! let recording = recorder.take_recording();
+//! assert_eq!(recording.num_samples(), Samples::from(200));
+//! assert_eq!(recording.chan(0)[0], 1.0);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::sample::Sample;
+use crate::units::{
+    BufferSize, Channels, Duration, SampleRate, SampleSection, Samples, TimeSection,
+};
+
+/// What the recorder is doing.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RecorderState {
+    /// It doesn't record.
+    Idle,
+    /// It starts recording with the next block, or at the punch in point.
+    Armed,
+    /// It records.
+    Recording,
+    /// It recorded a take, which stopped at the punch out point, when the position jumped, or
+    /// when it was stopped.
+    Finished,
+}
+
+/// Records input audio into a buffer that is allocated up front.
+#[derive(Clone, Debug)]
+pub struct Recorder {
+    sample_rate: SampleRate,
+    state: RecorderState,
+    punch: Option<TimeSection>,
+    recording: Buffer<f32>,
+    num_recorded: usize,
+    /// The position on the timeline where the take starts.
+    start: Option<Samples>,
+    /// The position on the timeline where the next block is expected while recording.
+    next_position: Samples,
+    overruns: Samples,
+}
+
+impl Recorder {
+    /// Creates an idle recorder that can record up to the capacity.
+    pub fn new(sample_rate: SampleRate, num_channels: Channels, capacity: Duration) -> Self {
+        Self {
+            sample_rate,
+            state: RecorderState::Idle,
+            punch: None,
+            recording: Buffer::allocate(num_channels, capacity.to_samples(sample_rate)),
+            num_recorded: 0,
+            start: None,
+            next_position: Samples::from(0),
+            overruns: Samples::from(0),
+        }
+    }
+
+    /// Returns the sample rate.
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        self.recording.num_channels()
+    }
+
+    /// Returns the number of samples that fit in the recording.
+    pub fn capacity(&self) -> Samples {
+        self.recording.num_samples()
+    }
+
+    /// Returns what the recorder is doing.
+    pub fn state(&self) -> RecorderState {
+        self.state
+    }
+
+    /// Returns the section of the timeline that is recorded, if any.
+    pub fn punch(&self) -> Option<TimeSection> {
+        self.punch
+    }
+
+    /// Only records the section of the timeline, or from where it's armed without one.
+    pub fn set_punch(&mut self, punch: Option<TimeSection>) {
+        self.punch = punch;
+    }
+
+    /// Starts a new take, which starts recording with the next block, or at the punch in point.
+    /// The take that was recorded before is cleared.
+    pub fn arm(&mut self) {
+        self.clear();
+        self.state = RecorderState::Armed;
+    }
+
+    /// Stops recording, which finishes the take. A recorder that is armed but didn't record yet
+    /// becomes idle.
+    pub fn stop(&mut self) {
+        self.state = match self.state {
+            RecorderState::Recording | RecorderState::Finished => RecorderState::Finished,
+            RecorderState::Idle | RecorderState::Armed => RecorderState::Idle,
+        };
+    }
+
+    /// Clears the take, and makes the recorder idle.
+    pub fn clear(&mut self) {
+        self.state = RecorderState::Idle;
+        self.num_recorded = 0;
+        self.start = None;
+        self.overruns = Samples::from(0);
+    }
+
+    /// Returns where the take starts on the timeline, once it started.
+    pub fn start(&self) -> Option<Samples> {
+        self.start
+    }
+
+    /// Returns the number of samples per channel that are recorded.
+    pub fn num_recorded(&self) -> Samples {
+        Samples::from(self.num_recorded)
+    }
+
+    /// Returns the number of samples per channel that were left out of the take because it was
+    /// full.
+    pub fn overruns(&self) -> Samples {
+        self.overruns
+    }
+
+    /// Returns the recorded samples of the channel.
+    /// This will panic if the channel doesn't exist.
+    pub fn recorded_chan(&self, channel: usize) -> &[f32] {
+        &self.recording.chan(channel)[..self.num_recorded]
+    }
+
+    /// Gives back a copy of the take, and makes the recorder idle. This allocates, so it's meant
+    /// to be called after recording, away from the audio thread.
+    pub fn take_recording(&mut self) -> Buffer<f32> {
+        let mut take = Buffer::allocate(self.num_channels(), self.num_recorded());
+        for (channel, samples) in take.iter_chans_mut().enumerate() {
+            samples.copy_from_slice(self.recorded_chan(channel));
+        }
+        self.clear();
+        take
+    }
+
+    /// Records the input block, which starts at the position on the timeline.
+    /// This will panic if the input doesn't have the same number of channels as the recorder.
+    pub fn process<T: Sample>(&mut self, input: &Buffer<T>, position: Samples) {
+        assert_eq!(input.num_channels(), self.num_channels());
+        self.record(position, input.num_samples(), |channel, index| {
+            input.chan(channel)[index].to_f64() as f32
+        });
+    }
+
+    /// Records the interleaved input of a device callback, which starts at the position on the
+    /// timeline.
+    /// A recorder without channels has nothing to record, so it ignores the input.
+    /// This will panic if the input doesn't hold whole frames of all channels.
+    pub fn process_interleaved(&mut self, input: &[f32], position: Samples) {
+        let num_channels = self.num_channels().as_usize();
+        if num_channels == 0 {
+            return;
+        }
+        assert_eq!(input.len() % num_channels, 0);
+        let length = Samples::from(input.len() / num_channels);
+        self.record(position, length, |channel, index| {
+            input[index * num_channels + channel]
+        });
+    }
+
+    fn record(&mut self, position: Samples, length: Samples, sample: impl Fn(usize, usize) -> f32) {
+        let block = SampleSection {
+            start: position,
+            length,
+        };
+        if self.state == RecorderState::Recording && position != self.next_position {
+            self.state = RecorderState::Finished;
+        }
+        if !matches!(self.state, RecorderState::Armed | RecorderState::Recording) {
+            return;
+        }
+
+        let punch = self
+            .punch
+            .map(|punch| SampleSection::from_time_section(punch, self.sample_rate));
+        let section = match punch {
+            Some(punch) => match block.get_overlap(punch) {
+                Some(section) => section,
+                None if self.state == RecorderState::Recording || position >= punch.end() => {
+                    self.state = RecorderState::Finished;
+                    return;
+                }
+                None => return,
+            },
+            None => block,
+        };
+        if self.state == RecorderState::Armed {
+            self.state = RecorderState::Recording;
+            self.start = Some(section.start);
+        }
+
+        let offset = (section.start - position).as_usize();
+        let count = section
+            .length
+            .as_usize()
+            .min(self.capacity().as_usize() - self.num_recorded);
+        for channel in self.recording.channel_indices() {
+            let recording = &mut self.recording.chan_mut(channel)[self.num_recorded..];
+            for (index, recorded) in recording.iter_mut().take(count).enumerate() {
+                *recorded = sample(channel, offset + index);
+            }
+        }
+        self.num_recorded += count;
+        self.overruns += section.length - Samples::from(count);
+        self.next_position = position + length;
+
+        if punch.is_some_and(|punch| section.end() == punch.end()) {
+            self.state = RecorderState::Finished;
+        }
+    }
+}
+
+impl AudioProcessor for Recorder {
+    /// Keeps the capacity in time, but clears the take when the sample rate or the number of
+    /// channels changes.
+    fn prepare(&mut self, sample_rate: SampleRate, _: BufferSize, num_channels: Channels) {
+        if sample_rate != self.sample_rate || num_channels != self.num_channels() {
+            let capacity = Duration::from(self.capacity().to_seconds(self.sample_rate));
+            *self = Self {
+                punch: self.punch,
+                ..Self::new(sample_rate, num_channels, capacity)
+            };
+        }
+    }
+
+    /// Records the buffer while the timeline plays, and leaves it untouched.
+    fn process(&mut self, buffer: &mut Buffer<f32>, context: &ProcessContext) {
+        if context.is_playing {
+            Recorder::process(self, buffer, context.position);
+        }
+    }
+
+    fn reset(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::TimePoint;
+
+    /// A recorder at 1 kHz, so samples and milliseconds are the same.
+    fn recorder(capacity: f64) -> Recorder {
+        Recorder::new(
+            SampleRate::from(1000),
+            Channels::from(1),
+            Duration::from_secs_f64(capacity),
+        )
+    }
+
+    fn punch(start: f64, duration: f64) -> Option<TimeSection> {
+        Some(TimeSection {
+            start: TimePoint::from_secs_f64(start),
+            duration: Duration::from_secs_f64(duration),
+        })
+    }
+
+    /// Feeds blocks of 10 samples, where every sample is its position, from the start.
+    fn feed(recorder: &mut Recorder, start: usize, num_blocks: usize) {
+        let mut block = Buffer::<f32>::allocate(Channels::from(1), Samples::from(10));
+        for position in (start..).step_by(10).take(num_blocks) {
+            for (index, sample) in block.chan_mut(0).iter_mut().enumerate() {
+                *sample = (position + index) as f32;
+            }
+            recorder.process(&block, Samples::from(position));
+        }
+    }
+
+    #[test]
+    fn only_records_when_armed() {
+        let mut recorder = recorder(1.0);
+        feed(&mut recorder, 0, 2);
+        assert_eq!(recorder.state(), RecorderState::Idle);
+
+        recorder.arm();
+        feed(&mut recorder, 20, 2);
+
+        assert_eq!(recorder.state(), RecorderState::Recording);
+        assert_eq!(recorder.start(), Some(Samples::from(20)));
+        assert_eq!(recorder.recorded_chan(0)[0], 20.0);
+        assert_eq!(recorder.recorded_chan(0).len(), 20);
+    }
+
+    #[test]
+    fn punches_in_and_out_within_blocks() {
+        let mut recorder = recorder(1.0);
+        recorder.set_punch(punch(0.015, 0.02));
+        recorder.arm();
+
+        feed(&mut recorder, 0, 1);
+        assert_eq!(recorder.state(), RecorderState::Armed);
+        feed(&mut recorder, 10, 5);
+
+        let expected: Vec<f32> = (15..35).map(|n| n as f32).collect();
+        assert_eq!(recorder.state(), RecorderState::Finished);
+        assert_eq!(recorder.start(), Some(Samples::from(15)));
+        assert_eq!(recorder.recorded_chan(0), expected);
+    }
+
+    #[test]
+    fn a_jump_finishes_the_take() {
+        let mut recorder = recorder(1.0);
+        recorder.arm();
+        feed(&mut recorder, 0, 2);
+
+        feed(&mut recorder, 100, 1);
+
+        assert_eq!(recorder.state(), RecorderState::Finished);
+        assert_eq!(recorder.num_recorded(), Samples::from(20));
+    }
+
+    #[test]
+    fn counts_what_doesnt_fit_as_overruns() {
+        let mut recorder = recorder(0.025);
+        recorder.arm();
+
+        feed(&mut recorder, 0, 4);
+
+        assert_eq!(recorder.num_recorded(), Samples::from(25));
+        assert_eq!(recorder.overruns(), Samples::from(15));
+        assert_eq!(recorder.state(), RecorderState::Recording);
+    }
+
+    #[test]
+    fn records_interleaved_input() {
+        let mut recorder = Recorder::new(
+            SampleRate::from(1000),
+            Channels::from(2),
+            Duration::from_secs_f64(1.0),
+        );
+        recorder.arm();
+
+        recorder.process_interleaved(&[1.0, -1.0, 2.0, -2.0], Samples::from(0));
+        recorder.process_interleaved(&[3.0, -3.0], Samples::from(2));
+        recorder.stop();
+        let take = recorder.take_recording();
+
+        assert_eq!(take.chan(0), &[1.0, 2.0, 3.0]);
+        assert_eq!(take.chan(1), &[-1.0, -2.0, -3.0]);
+        assert_eq!(recorder.state(), RecorderState::Idle);
+        assert_eq!(recorder.num_recorded(), Samples::from(0));
+    }
+
+    #[test]
+    fn a_recorder_without_channels_ignores_interleaved_input() {
+        let mut recorder = Recorder::new(
+            SampleRate::from(1000),
+            Channels::from(0),
+            Duration::from_secs_f64(1.0),
+        );
+        recorder.arm();
+
+        recorder.process_interleaved(&[1.0, 2.0], Samples::from(0));
+
+        assert_eq!(recorder.num_recorded(), Samples::from(0));
+    }
+
+    #[test]
+    fn records_while_the_timeline_plays() {
+        let mut recorder = recorder(1.0);
+        recorder.arm();
+        let mut buffer = Buffer::<f32>::allocate(Channels::from(1), Samples::from(10));
+        buffer.chan_mut(0).fill(0.5);
+        let mut context = ProcessContext::new(SampleRate::from(1000));
+
+        AudioProcessor::process(&mut recorder, &mut buffer, &context);
+        context.is_playing = true;
+        AudioProcessor::process(&mut recorder, &mut buffer, &context);
+
+        assert_eq!(recorder.recorded_chan(0), &[0.5; 10]);
+        assert_eq!(buffer.chan(0), &[0.5; 10]);
+    }
+}
@@ -0,0 +1,283 @@
+//! This module contains an offline renderer, which bounces a section of the timeline through a
+//! processor, e.g. a `Graph`, as fast as it can. The processor gets silent blocks with the
+//! position of the timeline, and the rendered blocks are collected into a buffer or handed to
+//! a sink, e.g. a file writer. The output is shifted by the latency of the processor, so it
+//! lines up with the timeline. The progress is reported after every block, and rendering stops
+//! when the progress callback asks for it.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::processor::{AudioProcessor, ProcessContext};
+//! use rabu::render::OfflineRenderer;
+//! use rabu::units::{BufferSize, Channels, Duration, SampleRate, Samples, TimePoint, TimeSection};
+//!
+//! /// Writes the position on the timeline into every sample.
+//! struct Ramp;
+//!
+//! impl AudioProcessor for Ramp {
+//!     fn prepare(&mut self, _: SampleRate, _: BufferSize, _: Channels) {}
+//!
+//!     fn process(&mut self, buffer: &mut Buffer<f32>, context: &ProcessContext) {
+//!         for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+//!             *sample = (context.position.as_usize() + n) as f32;
+//!         }
+//!     }
+//!
+//!     fn reset(&mut self) {}
+//! }
+//!
+//! let renderer = OfflineRenderer::new(SampleRate::from(1000), BufferSize::from(64), Channels::from(1));
+//! let section = TimeSection {
+//!     start: TimePoint::from_secs_f64(1.0),
+//!     duration: Duration::from_secs_f64(0.5),
+//! };
+//!
+//! let mut progress = Vec::new();
+//! let buffer = renderer
+//!     .render(&mut Ramp, section, |percentage| {
+//!         progress.push(percentage.as_f64());
+//!         true
+//!     })
+//!     .unwrap();
+//!
+//! assert_eq!(buffer.num_samples(), Samples::from(500));
+//! assert_eq!(buffer.chan(0)[0], 1000.0);
+//! assert_eq!(progress.last(), Some(&100.0));
+//! ```
+
+use crate::buffer::Buffer;
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::units::{
+    BufferSize, Channels, Percentage, SampleRate, SampleSection, Samples, TimeSection,
+};
+
+/// Renders sections of the timeline through a processor, block by block.
+#[derive(Copy, Clone, Debug)]
+pub struct OfflineRenderer {
+    sample_rate: SampleRate,
+    buffer_size: BufferSize,
+    num_channels: Channels,
+}
+
+impl OfflineRenderer {
+    /// Creates a new renderer for audio in the given format.
+    /// This will panic if the buffer size is 0.
+    pub fn new(sample_rate: SampleRate, buffer_size: BufferSize, num_channels: Channels) -> Self {
+        assert!(buffer_size.as_u32() > 0, "the buffer size must be above 0");
+        Self {
+            sample_rate,
+            buffer_size,
+            num_channels,
+        }
+    }
+
+    /// Returns the sample rate.
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// Returns the largest number of samples per block.
+    pub fn buffer_size(&self) -> BufferSize {
+        self.buffer_size
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        self.num_channels
+    }
+
+    /// Renders the section into a buffer, or gives `None` when the progress callback returned
+    /// `false` to cancel.
+    pub fn render(
+        &self,
+        processor: &mut dyn AudioProcessor,
+        section: TimeSection,
+        progress: impl FnMut(Percentage) -> bool,
+    ) -> Option<Buffer<f32>> {
+        let length = SampleSection::from_time_section(section, self.sample_rate).length;
+        let mut output = Buffer::allocate(self.num_channels, length);
+        let mut position = 0;
+        let completed = self.render_blocks(
+            processor,
+            section,
+            |block| {
+                for (channel, samples) in output.iter_chans_mut().zip(block.iter_chans()) {
+                    channel[position..position + samples.len()].copy_from_slice(samples);
+                }
+                position += block.num_samples().as_usize();
+            },
+            progress,
+        );
+        completed.then_some(output)
+    }
+
+    /// Renders the section, and hands the blocks to the sink as they are ready. The blocks are
+    /// never longer than the buffer size. Gives `false` when the progress callback returned
+    /// `false` to cancel.
+    pub fn render_blocks(
+        &self,
+        processor: &mut dyn AudioProcessor,
+        section: TimeSection,
+        mut sink: impl FnMut(&Buffer<f32>),
+        mut progress: impl FnMut(Percentage) -> bool,
+    ) -> bool {
+        processor.prepare(self.sample_rate, self.buffer_size, self.num_channels);
+        processor.reset();
+
+        let section = SampleSection::from_time_section(section, self.sample_rate);
+        let latency = processor
+            .latency()
+            .as_seconds()
+            .to_samples(self.sample_rate);
+        let block_size = self.buffer_size.as_samples();
+        let mut block = Buffer::allocate(self.num_channels, block_size);
+        // The output that is ready, after the latency is skipped.
+        let mut pending = Buffer::allocate(self.num_channels, block_size);
+        let mut num_pending = 0;
+
+        // The processor renders from the start, plus the latency, so the output that comes out
+        // after the latency is the one of the section.
+        let total = section.length + latency;
+        let mut rendered = Samples::from(0);
+        while rendered < total {
+            let length = block_size.min(total - rendered);
+            block.set_num_samples(length);
+            let context = ProcessContext {
+                is_playing: true,
+                position: section.start + rendered,
+                ..ProcessContext::new(self.sample_rate)
+            };
+            processor.process(&mut block, &context);
+
+            // Skips what is still in the latency, and passes on the rest in full blocks.
+            let skip = latency.as_usize().saturating_sub(rendered.as_usize());
+            let mut index = skip.min(length.as_usize());
+            while index < length.as_usize() {
+                let count = (length.as_usize() - index).min(block_size.as_usize() - num_pending);
+                for (to, from) in pending.iter_chans_mut().zip(block.iter_chans()) {
+                    to[num_pending..num_pending + count]
+                        .copy_from_slice(&from[index..index + count]);
+                }
+                num_pending += count;
+                index += count;
+                if num_pending == block_size.as_usize() {
+                    sink(&pending);
+                    num_pending = 0;
+                }
+            }
+            rendered += length;
+
+            let done = rendered.as_f64() / total.as_f64();
+            if !progress(Percentage::from(100.0 * done)) {
+                return false;
+            }
+        }
+
+        if num_pending > 0 {
+            block.set_num_samples(Samples::from(num_pending));
+            pending.clone_resized_into(&mut block);
+            sink(&block);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{Duration, Latency, TimePoint};
+
+    /// Writes the position on the timeline into every sample, with the given latency.
+    struct Ramp {
+        latency: usize,
+        resets: usize,
+    }
+
+    impl AudioProcessor for Ramp {
+        fn prepare(&mut self, _: SampleRate, _: BufferSize, _: Channels) {}
+
+        fn process(&mut self, buffer: &mut Buffer<f32>, context: &ProcessContext) {
+            for channel in buffer.iter_chans_mut() {
+                for (n, sample) in channel.iter_mut().enumerate() {
+                    let position = context.position.as_usize() + n;
+                    *sample = position.saturating_sub(self.latency) as f32;
+                }
+            }
+        }
+
+        fn latency(&self) -> Latency {
+            Latency::from(Samples::from(self.latency).to_seconds(SampleRate::from(1000)))
+        }
+
+        fn reset(&mut self) {
+            self.resets += 1;
+        }
+    }
+
+    fn section(start: f64, duration: f64) -> TimeSection {
+        TimeSection {
+            start: TimePoint::from_secs_f64(start),
+            duration: Duration::from_secs_f64(duration),
+        }
+    }
+
+    fn renderer() -> OfflineRenderer {
+        OfflineRenderer::new(
+            SampleRate::from(1000),
+            BufferSize::from(64),
+            Channels::from(2),
+        )
+    }
+
+    #[test]
+    fn renders_the_section_in_blocks() {
+        let mut ramp = Ramp {
+            latency: 0,
+            resets: 0,
+        };
+        let mut lengths = Vec::new();
+
+        let completed = renderer().render_blocks(
+            &mut ramp,
+            section(0.5, 0.2),
+            |block| lengths.push(block.num_samples().as_usize()),
+            |_| true,
+        );
+
+        assert!(completed);
+        assert_eq!(lengths, vec![64, 64, 64, 8]);
+        assert_eq!(ramp.resets, 1);
+    }
+
+    #[test]
+    fn compensates_the_latency() {
+        let mut ramp = Ramp {
+            latency: 100,
+            resets: 0,
+        };
+
+        let buffer = renderer()
+            .render(&mut ramp, section(0.5, 0.2), |_| true)
+            .unwrap();
+
+        let expected: Vec<f32> = (500..700).map(|position| position as f32).collect();
+        assert_eq!(buffer.chan(0), expected);
+        assert_eq!(buffer.chan(1), expected);
+    }
+
+    #[test]
+    fn progress_rises_to_100_and_can_cancel() {
+        let mut ramp = Ramp {
+            latency: 0,
+            resets: 0,
+        };
+        let mut progress = Vec::new();
+
+        let buffer = renderer().render(&mut ramp, section(0.0, 0.256), |percentage| {
+            progress.push(percentage.as_f64());
+            progress.len() < 3
+        });
+
+        assert!(buffer.is_none());
+        assert_eq!(progress, vec![25.0, 50.0, 75.0]);
+    }
+}
@@ -0,0 +1,639 @@
+//! This module contains offline sample rate conversion of whole buffers.
+//! Two qualities are available: linear interpolation, which is cheap but lets through aliasing
+//! and dulls the highs, and polyphase windowed-sinc interpolation, which is suitable for
+//! mastering quality conversion.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::resample::{output_length, resample, ResampleQuality};
+//! use rabu::units::{Channels, SampleRate, Samples};
+//!
+//! let from = SampleRate::from(44100);
+//! let to = SampleRate::from(48000);
+//! let input = Buffer::<f32>::allocate(Channels::from(2), Samples::from(44100));
+//!
+//! let output = resample(&input, from, to, ResampleQuality::default());
+//!
+//! assert_eq!(output.num_channels(), Channels::from(2));
+//! assert_eq!(output.num_samples(), Samples::from(48000));
+//! assert_eq!(output.num_samples(), output_length(input.num_samples(), from, to));
+//! ```
+
+use std::f64::consts::PI;
+
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::units::{Channels, Latency, SampleRate, Samples};
+use crate::windows::Window;
+
+/// The quality of a sample rate conversion.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResampleQuality {
+    /// Linear interpolation between neighbouring samples.
+    Linear,
+    /// Windowed-sinc interpolation, with the given number of zero crossings of the sinc on
+    /// either side. More zero crossings give a steeper anti-aliasing filter at a higher cost;
+    /// 16 is good, 32 is transparent.
+    Sinc { zero_crossings: usize },
+}
+
+impl Default for ResampleQuality {
+    fn default() -> Self {
+        ResampleQuality::Sinc { zero_crossings: 16 }
+    }
+}
+
+/// Returns the number of samples that converting `input_length` samples between the given
+/// sample rates results in, which is rounded up so no audio is lost.
+pub fn output_length(input_length: Samples, from: SampleRate, to: SampleRate) -> Samples {
+    let length = input_length.as_f64() * to.as_f64() / from.as_f64();
+    Samples::from(length.ceil() as u64)
+}
+
+/// Converts the buffer from one sample rate to the other, keeping all channels.
+/// The output has `output_length` samples, and sample `n` of the output lines up with time
+/// `n / to` in the input.
+pub fn resample<T: Sample>(
+    input: &Buffer<T>,
+    from: SampleRate,
+    to: SampleRate,
+    quality: ResampleQuality,
+) -> Buffer<T> {
+    let length = output_length(input.num_samples(), from, to);
+    resample_with_step(input, from.as_f64() / to.as_f64(), length, quality)
+}
+
+/// Converts the buffer like `resample`, into an output that is already allocated. The output
+/// keeps its channels, and is shortened to `output_length` when it's longer than that. Returns
+/// the number of samples that were written.
+/// The sinc quality builds its kernel table on every call, so only the linear quality doesn't
+/// allocate at all. Use a `StreamingResampler` to convert on the audio thread.
+#[must_use]
+pub fn resample_into<T: Sample>(
+    input: &Buffer<T>,
+    from: SampleRate,
+    to: SampleRate,
+    quality: ResampleQuality,
+    output: &mut Buffer<T>,
+) -> Samples {
+    let length = output_length(input.num_samples(), from, to).min(output.num_samples());
+    output.set_num_samples(length);
+    resample_with_step_into(input, from.as_f64() / to.as_f64(), quality, output);
+    length
+}
+
+/// Reads the buffer at a fixed step, so sample `n` of the output lines up with sample
+/// `n * step` in the input, e.g. to play it faster without changing the sample rate.
+pub(crate) fn resample_with_step<T: Sample>(
+    input: &Buffer<T>,
+    step: f64,
+    length: Samples,
+    quality: ResampleQuality,
+) -> Buffer<T> {
+    let mut output = Buffer::allocate(input.num_channels(), length);
+    resample_with_step_into(input, step, quality, &mut output);
+    output
+}
+
+/// Fills the whole output by reading the input at a fixed step.
+fn resample_with_step_into<T: Sample>(
+    input: &Buffer<T>,
+    step: f64,
+    quality: ResampleQuality,
+    output: &mut Buffer<T>,
+) {
+    match quality {
+        ResampleQuality::Linear => {
+            for (input, output) in input.iter_chans().zip(output.iter_chans_mut()) {
+                for (index, sample) in output.iter_mut().enumerate() {
+                    let position = index as f64 * step;
+                    let whole = position.floor() as usize;
+                    let fraction = position - whole as f64;
+                    let current = sample_at(input, whole as isize);
+                    let next = sample_at(input, whole as isize + 1);
+                    *sample = T::from_f64(current + (next - current) * fraction);
+                }
+            }
+        }
+        ResampleQuality::Sinc { zero_crossings } => {
+            let kernel = SincKernel::new(zero_crossings, step.recip().min(1.0));
+            for (input, output) in input.iter_chans().zip(output.iter_chans_mut()) {
+                for (index, sample) in output.iter_mut().enumerate() {
+                    let position = index as f64 * step;
+                    let value = kernel.interpolate(position, |n| sample_at(input, n));
+                    *sample = T::from_f64(value);
+                }
+            }
+        }
+    }
+}
+
+/// Reads a sample, treating everything outside the slice as silence.
+pub(crate) fn sample_at<T: Sample>(samples: &[T], index: isize) -> f64 {
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| samples.get(index))
+        .map_or(0.0, |sample| sample.to_f64())
+}
+
+/// A Kaiser-windowed sinc low-pass, stored as a table with a fixed number of phases per
+/// sample. Values between the phases are linearly interpolated, so any fractional position
+/// can be used, which also makes it usable for varying conversion ratios.
+#[derive(Clone, Debug)]
+pub(crate) struct SincKernel {
+    table: Vec<f64>,
+    zero_crossings: usize,
+    cutoff: f64,
+}
+
+impl SincKernel {
+    const PHASES: usize = 512;
+    const KAISER_BETA: f64 = 8.6;
+    /// The cutoff is placed slightly below Nyquist, so the transition band has room to
+    /// attenuate before aliasing sets in.
+    const CUTOFF_MARGIN: f64 = 0.95;
+
+    /// Creates a kernel with the given number of zero crossings on either side, and a cutoff
+    /// relative to the Nyquist frequency of the input (1 when upsampling, the ratio of the
+    /// sample rates when downsampling).
+    pub(crate) fn new(zero_crossings: usize, cutoff: f64) -> Self {
+        assert!(
+            zero_crossings > 0,
+            "the sinc needs at least one zero crossing"
+        );
+        let window = Window::Kaiser {
+            beta: Self::KAISER_BETA,
+        };
+        let length = zero_crossings * Self::PHASES;
+        let table = (0..=length + 1)
+            .map(|index| {
+                let x = index as f64 / Self::PHASES as f64;
+                let sinc = if index == 0 {
+                    1.0
+                } else {
+                    (PI * x).sin() / (PI * x)
+                };
+                // The window is evaluated over its right half, from its peak to its end.
+                let w = window.value(length + index.min(length), 2 * length + 1);
+                sinc * w
+            })
+            .collect();
+
+        Self {
+            table,
+            zero_crossings,
+            cutoff: cutoff * Self::CUTOFF_MARGIN,
+        }
+    }
+
+    /// Returns the number of input samples on either side of a position that contribute to it.
+    pub(crate) fn half_width(&self) -> usize {
+        (self.zero_crossings as f64 / self.cutoff).ceil() as usize
+    }
+
+    /// Interpolates the signal at the given (fractional) position, reading input samples
+    /// through the given function.
+    pub(crate) fn interpolate(&self, position: f64, mut sample: impl FnMut(isize) -> f64) -> f64 {
+        let whole = position.floor() as isize;
+        let half_width = self.half_width() as isize;
+        (whole - half_width + 1..=whole + half_width)
+            .map(|index| sample(index) * self.value(position - index as f64))
+            .sum()
+    }
+
+    /// Evaluates the low-pass impulse response at an offset in input samples.
+    fn value(&self, offset: f64) -> f64 {
+        let x = (offset * self.cutoff).abs() * Self::PHASES as f64;
+        let index = x.floor() as usize;
+        if index >= self.zero_crossings * Self::PHASES {
+            return 0.0;
+        }
+        let fraction = x - index as f64;
+        let value = self.table[index] + (self.table[index + 1] - self.table[index]) * fraction;
+        value * self.cutoff
+    }
+}
+
+/// A sample rate converter for streams, which takes input blocks and produces output blocks of
+/// independent sizes, e.g. to bridge a 44.1 kHz stream into a 48 kHz device callback.
+/// The conversion ratio can be adjusted slightly while running, to compensate for drift between
+/// two clocks. All memory is allocated up front, so pushing and pulling are real-time safe.
+/// ```
+/// use rabu::buffer::Buffer;
+/// use rabu::resample::StreamingResampler;
+/// use rabu::units::{Channels, SampleRate, Samples};
+///
+/// let mut resampler = StreamingResampler::new(
+///     Channels::from(2),
+///     SampleRate::from(44100),
+///     SampleRate::from(48000),
+///     16,
+///     Samples::from(4096),
+/// );
+///
+/// let input = Buffer::<f32>::allocate(Channels::from(2), Samples::from(441));
+/// let mut output = Buffer::<f32>::allocate(Channels::from(2), Samples::from(480));
+///
+/// for _ in 0..10 {
+///     resampler.push(&input);
+///     resampler.pull(&mut output);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct StreamingResampler {
+    kernel: SincKernel,
+    input_rate: SampleRate,
+    nominal_ratio: f64,
+    ratio: f64,
+    capacity: usize,
+    channels: Vec<Vec<f64>>,
+    position: f64,
+}
+
+impl StreamingResampler {
+    /// How far the ratio can be moved away from the nominal ratio, as a fraction of it.
+    pub const MAX_RATIO_DEVIATION: f64 = 0.05;
+
+    /// Creates a new converter between the given sample rates, with `zero_crossings` zero
+    /// crossings of the sinc on either side (see `ResampleQuality::Sinc`). It can hold up to
+    /// `capacity` input samples that haven't been converted yet.
+    pub fn new(
+        num_channels: Channels,
+        from: SampleRate,
+        to: SampleRate,
+        zero_crossings: usize,
+        capacity: Samples,
+    ) -> Self {
+        let nominal_ratio = to.as_f64() / from.as_f64();
+        // The filter is made for the lowest ratio allowed, so adjusting never causes aliasing.
+        let lowest_ratio = nominal_ratio * (1.0 - Self::MAX_RATIO_DEVIATION);
+        let kernel = SincKernel::new(zero_crossings, lowest_ratio.min(1.0));
+        let length = capacity.as_usize() + 2 * kernel.half_width();
+
+        let mut resampler = Self {
+            kernel,
+            input_rate: from,
+            nominal_ratio,
+            ratio: nominal_ratio,
+            capacity: length,
+            channels: (0..num_channels.as_usize())
+                .map(|_| Vec::with_capacity(length))
+                .collect(),
+            position: 0.0,
+        };
+        resampler.reset();
+        resampler
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.channels.len())
+    }
+
+    /// Returns the ratio between the output and input sample rates.
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    /// Changes the ratio between the output and input sample rates, e.g. to follow the drift
+    /// between two clocks. The change applies from the next output sample on.
+    /// This will panic if the ratio differs more than `MAX_RATIO_DEVIATION` from the ratio of
+    /// the sample rates given at creation.
+    pub fn set_ratio(&mut self, ratio: f64) {
+        let deviation = (ratio / self.nominal_ratio - 1.0).abs();
+        assert!(
+            deviation <= Self::MAX_RATIO_DEVIATION + 1e-12,
+            "the ratio can only be adjusted slightly"
+        );
+        self.ratio = ratio;
+    }
+
+    /// Returns the delay that the interpolation filter adds, which is the time it needs to
+    /// look ahead in the input. Audio that is waiting in the converter adds to this.
+    pub fn latency(&self) -> Latency {
+        let samples = Samples::from(self.kernel.half_width());
+        Latency::from(samples.to_seconds(self.input_rate))
+    }
+
+    /// Returns the number of input samples that were pushed but haven't been reached by the
+    /// conversion yet.
+    pub fn buffered(&self) -> Samples {
+        let reached = self.position.floor() as usize;
+        Samples::from(self.len().saturating_sub(reached))
+    }
+
+    /// Returns the number of output samples that can be pulled right now.
+    pub fn available(&self) -> Samples {
+        // The same condition as in `pull`: the kernel has to fit in the input that's there.
+        let end = self.len() as f64 - self.kernel.half_width() as f64;
+        if self.position >= end {
+            return Samples::from(0);
+        }
+        Samples::from(((end - self.position) * self.ratio).ceil() as u64)
+    }
+
+    /// Adds the given input to the converter, and returns how many samples were accepted.
+    /// Samples that don't fit are dropped, so pull often enough to keep room for new input.
+    /// This will panic if the number of channels doesn't match.
+    pub fn push<T: Sample>(&mut self, input: &Buffer<T>) -> Samples {
+        assert_eq!(input.num_channels(), self.num_channels());
+        let room = self.capacity - self.len();
+        let accepted = input.num_samples().as_usize().min(room);
+
+        for (samples, input) in self.channels.iter_mut().zip(input.iter_chans()) {
+            samples.extend(input[..accepted].iter().map(|sample| sample.to_f64()));
+        }
+        Samples::from(accepted)
+    }
+
+    /// Fills the output with converted audio for as far as the input allows, and returns how
+    /// many samples were written. The rest of the output is left untouched.
+    /// This will panic if the number of channels doesn't match.
+    pub fn pull<T: Sample>(&mut self, output: &mut Buffer<T>) -> Samples {
+        assert_eq!(output.num_channels(), self.num_channels());
+        let half_width = self.kernel.half_width();
+        let step = self.ratio.recip();
+        let mut written = 0;
+
+        for index in output.sample_indices() {
+            if self.position.floor() as usize + half_width >= self.len() {
+                break;
+            }
+            for (samples, output) in self.channels.iter().zip(output.iter_chans_mut()) {
+                let value = self
+                    .kernel
+                    .interpolate(self.position, |n| samples[n as usize]);
+                output[index] = T::from_f64(value);
+            }
+            self.position += step;
+            written += 1;
+        }
+
+        self.discard_used_input();
+        Samples::from(written)
+    }
+
+    /// Clears all buffered audio, as if the converter was just created.
+    pub fn reset(&mut self) {
+        let half_width = self.kernel.half_width();
+        for samples in &mut self.channels {
+            samples.clear();
+            samples.resize(half_width, 0.0);
+        }
+        self.position = half_width as f64;
+    }
+
+    fn len(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+
+    /// Removes the input samples that no future output sample needs anymore.
+    fn discard_used_input(&mut self) {
+        let first_needed = self.position.floor() as usize + 1 - self.kernel.half_width();
+        let first_needed = first_needed.min(self.len());
+        for samples in &mut self.channels {
+            samples.drain(..first_needed);
+        }
+        self.position -= first_needed as f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn sine(frequency: f64, sample_rate: f64, num_samples: usize) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(num_samples));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = (2.0 * PI * frequency * n as f64 / sample_rate).sin();
+        }
+        buffer
+    }
+
+    fn largest_error(output: &Buffer<f64>, frequency: f64, sample_rate: f64) -> f64 {
+        let samples = output.chan(0);
+        let middle = samples.len() / 4..3 * samples.len() / 4;
+        samples[middle.clone()]
+            .iter()
+            .zip(middle)
+            .map(|(s, n)| (s - (2.0 * PI * frequency * n as f64 / sample_rate).sin()).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test_case(44100, 44100, 48000 => 48000; "up")]
+    #[test_case(48000, 48000, 44100 => 44100; "down")]
+    #[test_case(100, 3, 2 => 67; "rounds up")]
+    #[test_case(0, 44100, 48000 => 0; "empty")]
+    fn output_lengths(length: u64, from: u32, to: u32) -> u64 {
+        output_length(
+            Samples::from(length),
+            SampleRate::from(from),
+            SampleRate::from(to),
+        )
+        .as_u64()
+    }
+
+    #[test_case(ResampleQuality::Linear, 1e-2; "linear")]
+    #[test_case(ResampleQuality::Sinc { zero_crossings: 16 }, 1e-3; "sinc")]
+    fn upsampled_sine_stays_a_sine(quality: ResampleQuality, tolerance: f64) {
+        let input = sine(1000.0, 44100.0, 4410);
+
+        let output = resample(
+            &input,
+            SampleRate::from(44100),
+            SampleRate::from(48000),
+            quality,
+        );
+
+        assert_eq!(output.num_samples(), Samples::from(4800));
+        assert!(largest_error(&output, 1000.0, 48000.0) < tolerance);
+    }
+
+    #[test]
+    fn downsampled_sine_stays_a_sine() {
+        let input = sine(1000.0, 96000.0, 9600);
+
+        let output = resample(
+            &input,
+            SampleRate::from(96000),
+            SampleRate::from(44100),
+            ResampleQuality::default(),
+        );
+
+        assert!(largest_error(&output, 1000.0, 44100.0) < 1e-3);
+    }
+
+    #[test]
+    fn downsampling_removes_content_above_the_new_nyquist() {
+        let input = sine(20000.0, 48000.0, 4800);
+
+        let output = resample(
+            &input,
+            SampleRate::from(48000),
+            SampleRate::from(32000),
+            ResampleQuality::default(),
+        );
+
+        let samples = output.chan(0);
+        let middle = &samples[samples.len() / 4..3 * samples.len() / 4];
+        assert!(middle.iter().all(|s| s.abs() < 0.01));
+    }
+
+    #[test]
+    fn channels_are_kept_apart() {
+        let mut input = Buffer::<f32>::allocate(Channels::from(2), Samples::from(100));
+        input.chan_mut(0).fill(1.0);
+
+        let output = resample(
+            &input,
+            SampleRate::from(10),
+            SampleRate::from(20),
+            ResampleQuality::Linear,
+        );
+
+        assert_eq!(output.num_channels(), Channels::from(2));
+        assert_eq!(output.chan(0)[50], 1.0);
+        assert!(output.chan(1).iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn resampling_into_a_buffer_matches_resampling() {
+        let input = sine(1000.0, 44100.0, 441);
+        let expected = resample(
+            &input,
+            SampleRate::from(44100),
+            SampleRate::from(48000),
+            ResampleQuality::default(),
+        );
+
+        let mut output = Buffer::allocate(Channels::from(1), Samples::from(1000));
+        let length = resample_into(
+            &input,
+            SampleRate::from(44100),
+            SampleRate::from(48000),
+            ResampleQuality::default(),
+            &mut output,
+        );
+
+        assert_eq!(length, Samples::from(480));
+        assert_eq!(output.chan(0), expected.chan(0));
+    }
+
+    fn streaming_resampler(capacity: u64) -> StreamingResampler {
+        StreamingResampler::new(
+            Channels::from(1),
+            SampleRate::from(44100),
+            SampleRate::from(48000),
+            16,
+            Samples::from(capacity),
+        )
+    }
+
+    #[test]
+    fn streaming_matches_offline_conversion() {
+        let input = sine(1000.0, 44100.0, 4410);
+        let mut resampler = streaming_resampler(1024);
+        let mut block = Buffer::<f64>::allocate(Channels::from(1), Samples::from(300));
+        let mut streamed = Vec::new();
+
+        for chunk in input.chan(0).chunks(441) {
+            let mut pushed = Buffer::allocate(Channels::from(1), Samples::from(chunk.len()));
+            pushed.chan_mut(0).copy_from_slice(chunk);
+            assert_eq!(resampler.push(&pushed), Samples::from(chunk.len()));
+
+            loop {
+                let available = resampler.available();
+                let written = resampler.pull(&mut block);
+                assert_eq!(written, available.min(Samples::from(300)));
+                streamed.extend_from_slice(&block.chan(0)[..written.as_usize()]);
+                if written < Samples::from(300) {
+                    break;
+                }
+            }
+        }
+
+        let offline = resample(
+            &input,
+            SampleRate::from(44100),
+            SampleRate::from(48000),
+            ResampleQuality::Sinc { zero_crossings: 16 },
+        );
+        let streamed_length = streamed.len();
+        assert!(streamed_length > 4700);
+        // The kernels differ slightly because the streaming one leaves room for ratio changes.
+        for (streamed, offline) in streamed.iter().zip(offline.chan(0)).take(streamed_length) {
+            assert!((streamed - offline).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn push_accepts_what_fits() {
+        let mut resampler = streaming_resampler(100);
+        let input = Buffer::<f32>::allocate(Channels::from(1), Samples::from(1000));
+
+        let accepted = resampler.push(&input);
+
+        assert!(accepted < Samples::from(1000));
+        assert_eq!(resampler.push(&input), Samples::from(0));
+        assert_eq!(resampler.buffered(), accepted);
+    }
+
+    #[test]
+    fn pulling_before_any_input_leaves_nothing_available() {
+        let mut resampler = streaming_resampler(1024);
+        let mut output = Buffer::<f32>::allocate(Channels::from(1), Samples::from(64));
+
+        assert_eq!(resampler.pull(&mut output), Samples::from(0));
+        assert_eq!(resampler.available(), Samples::from(0));
+        assert_eq!(resampler.buffered(), Samples::from(0));
+
+        resampler.push(&Buffer::<f32>::allocate(
+            Channels::from(1),
+            Samples::from(441),
+        ));
+        let available = resampler.available();
+        assert!(available > Samples::from(0));
+        output = Buffer::allocate(Channels::from(1), Samples::from(1000));
+        assert_eq!(resampler.pull(&mut output), available);
+    }
+
+    #[test]
+    fn higher_ratio_gives_more_output() {
+        let input = Buffer::<f32>::allocate(Channels::from(1), Samples::from(441));
+        let mut output = Buffer::<f32>::allocate(Channels::from(1), Samples::from(1000));
+        let mut total = [0, 0];
+
+        for (index, ratio) in [48000.0 / 44100.0, 48048.0 / 44100.0]
+            .into_iter()
+            .enumerate()
+        {
+            let mut resampler = streaming_resampler(1024);
+            resampler.set_ratio(ratio);
+            for _ in 0..100 {
+                resampler.push(&input);
+                total[index] += resampler.pull(&mut output).as_usize();
+            }
+        }
+
+        let difference = total[1] as i64 - total[0] as i64;
+        assert!((difference - 48).abs() <= 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn ratio_can_only_be_adjusted_slightly() {
+        streaming_resampler(1024).set_ratio(1.0);
+    }
+
+    #[test]
+    fn latency_is_the_look_ahead_of_the_filter() {
+        let resampler = streaming_resampler(1024);
+        let look_ahead = resampler.kernel.half_width();
+
+        assert_eq!(
+            resampler.latency(),
+            Latency::from(Samples::from(look_ahead).to_seconds(SampleRate::from(44100)))
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! This module contains utilities to capture the impulse and step response of a processor.
+//! This is useful for testing filters and for visually inspecting what they do.
+//! ```rust
+//! use rabu::biquad::{low_pass_coefficients, BiquadFilter};
+//! use rabu::response::{impulse_response, magnitude_response};
+//! use rabu::units::{Frequency, SampleRate, Samples};
+//!
+//! let sample_rate = SampleRate::from(44100);
+//! let mut filter = BiquadFilter::new(low_pass_coefficients(sample_rate, Frequency::from(1000.0)));
+//!
+//! let response = impulse_response(&mut filter, Samples::from(1024));
+//! let magnitudes = magnitude_response(response.chan(0), sample_rate);
+//!
+//! let (_, dc_gain) = magnitudes[0];
+//! assert!((dc_gain - 1.0).abs() < 1e-3);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::processor::SampleProcessor;
+use crate::units::{Channels, Frequency, SampleRate, Samples};
+
+/// Feeds a unit impulse through the given processor and returns the first `length` output
+/// samples as a mono buffer.
+pub fn impulse_response(processor: &mut impl SampleProcessor, length: Samples) -> Buffer<f64> {
+    capture(
+        processor,
+        length,
+        |index| if index == 0 { 1.0 } else { 0.0 },
+    )
+}
+
+/// Feeds a unit step through the given processor and returns the first `length` output
+/// samples as a mono buffer.
+pub fn step_response(processor: &mut impl SampleProcessor, length: Samples) -> Buffer<f64> {
+    capture(processor, length, |_| 1.0)
+}
+
+/// Calculates the linear magnitude of every frequency bin of the given response, from DC up to
+/// and including the Nyquist frequency. The frequency resolution depends on the length of the
+/// response, so use a longer response for more detail.
+///
+/// This uses a plain DFT, so it is meant for analysis and testing and not for real-time use.
+pub fn magnitude_response(response: &[f64], sample_rate: SampleRate) -> Vec<(Frequency, f64)> {
+    let length = response.len();
+    let bin_width = sample_rate.as_f64() / length as f64;
+
+    (0..=length / 2)
+        .map(|bin| {
+            let (mut re, mut im) = (0.0, 0.0);
+            for (n, sample) in response.iter().enumerate() {
+                let phase = -2.0 * std::f64::consts::PI * (bin * n) as f64 / length as f64;
+                re += sample * phase.cos();
+                im += sample * phase.sin();
+            }
+            (Frequency::from(bin as f64 * bin_width), re.hypot(im))
+        })
+        .collect()
+}
+
+fn capture(
+    processor: &mut impl SampleProcessor,
+    length: Samples,
+    input: impl Fn(usize) -> f64,
+) -> Buffer<f64> {
+    let mut buffer = Buffer::allocate(Channels::from(1), length);
+
+    for (index, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+        *sample = processor.process(input(index));
+    }
+
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biquad::{high_pass_coefficients, low_pass_coefficients, BiquadFilter};
+
+    #[test]
+    fn impulse_response_of_identity_is_impulse() {
+        let response = impulse_response(&mut |x| x, Samples::from(4));
+        assert_eq!(response.chan(0), &[1.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn low_pass_step_response_settles_at_one() {
+        let sample_rate = SampleRate::from(44100);
+        let coefficients = low_pass_coefficients(sample_rate, Frequency::from(1000.0));
+        let mut filter = BiquadFilter::new(coefficients);
+
+        let response = step_response(&mut filter, Samples::from(4410));
+
+        let last = *response.chan(0).last().unwrap();
+        assert!((last - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn high_pass_blocks_dc() {
+        let sample_rate = SampleRate::from(44100);
+        let coefficients = high_pass_coefficients(sample_rate, Frequency::from(1000.0));
+        let mut filter = BiquadFilter::new(coefficients);
+
+        let response = impulse_response(&mut filter, Samples::from(2048));
+        let magnitudes = magnitude_response(response.chan(0), sample_rate);
+
+        assert!(magnitudes[0].1 < 1e-3);
+        assert!((magnitudes.last().unwrap().1 - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn magnitude_response_of_impulse_is_flat() {
+        let impulse = [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        let magnitudes = magnitude_response(&impulse, SampleRate::from(8));
+
+        assert_eq!(magnitudes.len(), 5);
+        assert_eq!(magnitudes[2].0, Frequency::from(2.0));
+        assert!(magnitudes.iter().all(|(_, m)| (m - 1.0).abs() < 1e-12));
+    }
+}
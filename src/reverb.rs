@@ -0,0 +1,260 @@
+//! This module contains the classic building blocks of algorithmic reverbs: the feedback comb
+//! filter, which makes a decaying train of echoes, and the Schroeder all-pass filter, which
+//! smears echoes into a dense tail without coloring the sound. A Schroeder or Freeverb style
+//! reverb is a bank of parallel combs followed by a chain of all-passes.
+//! ```rust
+//! use rabu::processor::SampleProcessor;
+//! use rabu::reverb::{CombFilter, SchroederAllpass};
+//! use rabu::units::{SampleRate, Samples, Seconds};
+//!
+//! let sample_rate = SampleRate::from(44100);
+//! let mut combs: Vec<CombFilter> = [1116, 1188, 1277, 1356]
+//!     .iter()
+//!     .map(|delay| {
+//!         let mut comb = CombFilter::new(Samples::from(*delay));
+//!         comb.set_feedback(0.84);
+//!         comb.set_damping(0.2);
+//!         comb
+//!     })
+//!     .collect();
+//! let mut allpass = SchroederAllpass::from_seconds(Seconds::from(0.005), sample_rate, 0.5);
+//!
+//! let input = 1.0;
+//! let wet: f64 = combs.iter_mut().map(|comb| comb.process(input)).sum();
+//! let output = allpass.process(wet);
+//! ```
+
+use crate::delay::DelayLine;
+use crate::processor::SampleProcessor;
+use crate::sample::Sample;
+use crate::units::{SampleRate, Samples, Seconds};
+use crate::varispeed::Interpolation;
+
+/// A feedback comb filter with a low-pass filter in the loop, so high frequencies die out
+/// faster, like they do in a room.
+#[derive(Clone, Debug)]
+pub struct CombFilter {
+    line: DelayLine,
+    delay: Samples,
+    feedback: f64,
+    damping: f64,
+    filter_state: f64,
+}
+
+impl CombFilter {
+    /// Creates a new comb filter with the given delay, no feedback and no damping.
+    /// This will panic if the delay is 0.
+    pub fn new(delay: Samples) -> Self {
+        assert!(delay.as_usize() > 0, "a comb filter needs a delay");
+        Self {
+            line: DelayLine::new(delay, Interpolation::None),
+            delay,
+            feedback: 0.0,
+            damping: 0.0,
+            filter_state: 0.0,
+        }
+    }
+
+    /// Creates a new comb filter with the delay in seconds, rounded to whole samples.
+    /// This will panic if the delay is shorter than one sample.
+    pub fn from_seconds(delay: Seconds, sample_rate: SampleRate) -> Self {
+        Self::new(delay.to_samples(sample_rate))
+    }
+
+    /// Returns the delay.
+    pub fn delay(&self) -> Samples {
+        self.delay
+    }
+
+    /// Returns the feedback.
+    pub fn feedback(&self) -> f64 {
+        self.feedback
+    }
+
+    /// Changes how much of every echo comes back in the next one. Keep it below 1 in
+    /// magnitude, or the echoes will grow.
+    pub fn set_feedback(&mut self, feedback: f64) {
+        self.feedback = feedback;
+    }
+
+    /// Returns the damping.
+    pub fn damping(&self) -> f64 {
+        self.damping
+    }
+
+    /// Changes how much the high frequencies are damped in every echo, from 0 (not at all) to
+    /// 1 (the echoes stop).
+    pub fn set_damping(&mut self, damping: f64) {
+        self.damping = damping.clamp(0.0, 1.0);
+    }
+
+    /// Silences the filter.
+    pub fn reset(&mut self) {
+        self.line.reset();
+        self.filter_state = 0.0;
+    }
+
+    /// Processes one sample.
+    pub fn process(&mut self, input: f64) -> f64 {
+        // Before the push, the sample that was pushed `delay` samples ago is one less delayed.
+        let output = self.line.read(Samples::from(self.delay.as_usize() - 1));
+        self.filter_state = output * (1.0 - self.damping) + self.filter_state * self.damping;
+        self.line.push(input + self.filter_state * self.feedback);
+        output
+    }
+
+    /// Processes the samples in place.
+    pub fn process_block<T: Sample>(&mut self, samples: &mut [T]) {
+        for sample in samples.iter_mut() {
+            *sample = T::from_f64(self.process(sample.to_f64()));
+        }
+    }
+}
+
+impl SampleProcessor for CombFilter {
+    fn process(&mut self, input: f64) -> f64 {
+        CombFilter::process(self, input)
+    }
+}
+
+/// A Schroeder all-pass filter: it passes all frequencies at the same level, but spreads every
+/// input sample over a decaying train of echoes.
+#[derive(Clone, Debug)]
+pub struct SchroederAllpass {
+    line: DelayLine,
+    delay: Samples,
+    gain: f64,
+}
+
+impl SchroederAllpass {
+    /// Creates a new all-pass filter with the given delay and gain, e.g. 0.5 or 0.7.
+    /// This will panic if the delay is 0.
+    pub fn new(delay: Samples, gain: f64) -> Self {
+        assert!(delay.as_usize() > 0, "an all-pass filter needs a delay");
+        Self {
+            line: DelayLine::new(delay, Interpolation::None),
+            delay,
+            gain,
+        }
+    }
+
+    /// Creates a new all-pass filter with the delay in seconds, rounded to whole samples.
+    /// This will panic if the delay is shorter than one sample.
+    pub fn from_seconds(delay: Seconds, sample_rate: SampleRate, gain: f64) -> Self {
+        Self::new(delay.to_samples(sample_rate), gain)
+    }
+
+    /// Returns the delay.
+    pub fn delay(&self) -> Samples {
+        self.delay
+    }
+
+    /// Returns the gain.
+    pub fn gain(&self) -> f64 {
+        self.gain
+    }
+
+    /// Changes the gain, which sets how fast the echoes decay. Keep it below 1 in magnitude.
+    pub fn set_gain(&mut self, gain: f64) {
+        self.gain = gain;
+    }
+
+    /// Silences the filter.
+    pub fn reset(&mut self) {
+        self.line.reset();
+    }
+
+    /// Processes one sample.
+    pub fn process(&mut self, input: f64) -> f64 {
+        let delayed = self.line.read(Samples::from(self.delay.as_usize() - 1));
+        let state = input + self.gain * delayed;
+        self.line.push(state);
+        delayed - self.gain * state
+    }
+
+    /// Processes the samples in place.
+    pub fn process_block<T: Sample>(&mut self, samples: &mut [T]) {
+        for sample in samples.iter_mut() {
+            *sample = T::from_f64(self.process(sample.to_f64()));
+        }
+    }
+}
+
+impl SampleProcessor for SchroederAllpass {
+    fn process(&mut self, input: f64) -> f64 {
+        SchroederAllpass::process(self, input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn impulse_response(processor: &mut impl SampleProcessor, length: usize) -> Vec<f64> {
+        (0..length)
+            .map(|n| processor.process(if n == 0 { 1.0 } else { 0.0 }))
+            .collect()
+    }
+
+    #[test]
+    fn comb_makes_decaying_echoes() {
+        let mut comb = CombFilter::new(Samples::from(3));
+        comb.set_feedback(0.5);
+
+        let response = impulse_response(&mut comb, 10);
+
+        assert_eq!(
+            response,
+            vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.25]
+        );
+    }
+
+    #[test]
+    fn damping_smears_the_echoes() {
+        let mut comb = CombFilter::new(Samples::from(3));
+        comb.set_feedback(0.5);
+        comb.set_damping(0.5);
+
+        let response = impulse_response(&mut comb, 8);
+
+        // The second echo is low-passed: half of it arrives, the rest trails behind.
+        assert_eq!(response[6], 0.25);
+        assert_eq!(response[7], 0.125);
+    }
+
+    #[test]
+    fn delay_in_seconds_is_rounded_to_samples() {
+        let comb = CombFilter::from_seconds(Seconds::from(0.0251), SampleRate::from(1000));
+        assert_eq!(comb.delay(), Samples::from(25));
+    }
+
+    #[test_case(0.5; "gain of 0.5")]
+    #[test_case(0.7; "gain of 0.7")]
+    #[test_case(-0.6; "negative gain")]
+    fn allpass_keeps_the_energy(gain: f64) {
+        let mut allpass = SchroederAllpass::new(Samples::from(7), gain);
+
+        let response = impulse_response(&mut allpass, 2000);
+        let energy: f64 = response.iter().map(|sample| sample * sample).sum();
+
+        assert_eq!(response[0], -gain);
+        assert!((energy - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn allpass_passes_a_sine_at_the_same_level() {
+        let mut allpass = SchroederAllpass::new(Samples::from(13), 0.7);
+        let w = 0.3;
+
+        let output: Vec<f64> = (0..4000)
+            .map(|n| allpass.process((w * n as f64).sin()))
+            .collect();
+        let peak = output[3000..]
+            .iter()
+            .fold(0.0_f64, |peak, s| peak.max(s.abs()));
+
+        assert!((peak - 1.0).abs() < 0.01);
+    }
+}
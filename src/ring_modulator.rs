@@ -0,0 +1,216 @@
+//! This module contains a ring modulator, which multiplies audio with a carrier: an internal
+//! oscillator, or the audio of a sidechain. Multiplying two signals gives their sum and
+//! difference frequencies instead of the originals, which sounds metallic or bell-like. The
+//! mix blends the modulated audio with the dry audio.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::ring_modulator::RingModulator;
+//! use rabu::units::{Channels, Frequency, NormalizedValue, SampleRate, Samples};
+//!
+//! let mut modulator = RingModulator::new(Frequency::from(440.0), SampleRate::from(48000));
+//! modulator.set_mix(NormalizedValue::from(0.5));
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! modulator.process(&mut buffer);
+//!
+//! // Or with another signal as the carrier.
+//! let sidechain = Buffer::<f32>::allocate(Channels::from(1), Samples::from(512));
+//! modulator.process_sidechain(&mut buffer, &sidechain);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::osc::{Oscillator, Waveform};
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::sample::Sample;
+use crate::units::{BufferSize, Channels, Frequency, NormalizedValue, SampleRate};
+
+/// Multiplies audio with an oscillator or a sidechain.
+#[derive(Clone, Debug)]
+pub struct RingModulator {
+    oscillator: Oscillator,
+    mix: NormalizedValue,
+}
+
+impl RingModulator {
+    /// Creates a new fully wet ring modulator with a sine carrier at the frequency.
+    pub fn new(frequency: Frequency, sample_rate: SampleRate) -> Self {
+        Self {
+            oscillator: Oscillator::new(Waveform::Sine, frequency, sample_rate),
+            mix: NormalizedValue::from(1.0),
+        }
+    }
+
+    /// Returns the frequency of the carrier.
+    pub fn frequency(&self) -> Frequency {
+        self.oscillator.frequency()
+    }
+
+    /// Changes the frequency of the carrier, keeping its phase.
+    pub fn set_frequency(&mut self, frequency: Frequency) {
+        self.oscillator.set_frequency(frequency);
+    }
+
+    /// Returns the waveform of the carrier.
+    pub fn waveform(&self) -> Waveform {
+        self.oscillator.waveform()
+    }
+
+    /// Changes the waveform of the carrier, which is band-limited so it doesn't alias.
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.oscillator.set_waveform(waveform);
+        self.oscillator.set_band_limited(waveform != Waveform::Sine);
+    }
+
+    /// Changes the sample rate, keeping the phase of the carrier.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.oscillator.set_sample_rate(sample_rate);
+    }
+
+    /// Returns the mix, from 0 (only the dry audio) to 1 (only the modulated audio).
+    pub fn mix(&self) -> NormalizedValue {
+        self.mix
+    }
+
+    /// Sets the mix, from 0 (only the dry audio) to 1 (only the modulated audio).
+    pub fn set_mix(&mut self, mix: NormalizedValue) {
+        self.mix = mix;
+    }
+
+    /// Restarts the carrier.
+    pub fn reset(&mut self) {
+        self.oscillator.reset();
+    }
+
+    /// Multiplies every channel of the buffer with the same oscillator in place.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        let mix = self.mix.as_f64();
+        for index in buffer.sample_indices() {
+            let gain = 1.0 - mix + mix * self.oscillator.next_sample();
+            for channel in buffer.iter_chans_mut() {
+                channel[index] = T::from_f64(channel[index].to_f64() * gain);
+            }
+        }
+    }
+
+    /// Multiplies the buffer with the sidechain in place, instead of with the oscillator. A
+    /// mono sidechain is used for all channels, otherwise every channel is multiplied with the
+    /// sidechain channel with the same index.
+    /// This will panic if the buffers don't have the same number of samples, or if the
+    /// sidechain is not mono and doesn't have the same number of channels as the buffer.
+    pub fn process_sidechain<T: Sample>(&mut self, buffer: &mut Buffer<T>, sidechain: &Buffer<T>) {
+        assert_eq!(buffer.num_samples(), sidechain.num_samples());
+        let num_carriers = sidechain.num_channels().as_usize();
+        assert!(num_carriers == 1 || sidechain.num_channels() == buffer.num_channels());
+
+        let mix = self.mix.as_f64();
+        for (index, channel) in buffer.iter_chans_mut().enumerate() {
+            let carrier = sidechain.chan(index.min(num_carriers - 1));
+            for (sample, carrier) in channel.iter_mut().zip(carrier) {
+                let gain = 1.0 - mix + mix * carrier.to_f64();
+                *sample = T::from_f64(sample.to_f64() * gain);
+            }
+        }
+    }
+}
+
+impl AudioProcessor for RingModulator {
+    fn prepare(&mut self, sample_rate: SampleRate, _: BufferSize, _: Channels) {
+        self.set_sample_rate(sample_rate);
+    }
+
+    fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+        RingModulator::process(self, buffer);
+    }
+
+    fn reset(&mut self) {
+        RingModulator::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Samples;
+
+    fn constant(values: &[f32], length: usize) -> Buffer<f32> {
+        let mut buffer = Buffer::allocate(Channels::from(values.len()), Samples::from(length));
+        for (channel, value) in buffer.iter_chans_mut().zip(values) {
+            channel.fill(*value);
+        }
+        buffer
+    }
+
+    #[test]
+    fn multiplies_every_channel_with_the_oscillator() {
+        // A carrier of 250 Hz at 1 kHz is 0, 1, 0, -1.
+        let mut modulator = RingModulator::new(Frequency::from(250.0), SampleRate::from(1000));
+        let mut buffer = constant(&[1.0, 0.5], 4);
+
+        modulator.process(&mut buffer);
+
+        for (sample, expected) in buffer.chan(0).iter().zip([0.0, 1.0, 0.0, -1.0]) {
+            assert!((sample - expected).abs() < 1e-6);
+        }
+        for (sample, expected) in buffer.chan(1).iter().zip([0.0, 0.5, 0.0, -0.5]) {
+            assert!((sample - expected).abs() < 1e-6);
+        }
+    }
+
+    #[test_case(0.0, 1.0; "dry")]
+    #[test_case(0.5, 0.0; "half")]
+    #[test_case(1.0, -1.0; "wet")]
+    fn mix_blends_with_the_dry_audio(mix: f64, expected: f32) {
+        let mut modulator = RingModulator::new(Frequency::from(250.0), SampleRate::from(1000));
+        modulator.set_mix(NormalizedValue::from(mix));
+        let mut buffer = constant(&[1.0], 4);
+
+        modulator.process(&mut buffer);
+
+        assert!((buffer.chan(0)[3] - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn multiplies_with_the_sidechain() {
+        let mut modulator = RingModulator::new(Frequency::from(250.0), SampleRate::from(1000));
+        let mut mono = constant(&[0.5, 2.0], 3);
+        let mut stereo = mono.clone();
+
+        modulator.process_sidechain(&mut mono, &constant(&[-1.0], 3));
+        modulator.process_sidechain(&mut stereo, &constant(&[0.5, 0.25], 3));
+
+        assert_eq!(mono.chan(0), &[-0.5; 3]);
+        assert_eq!(mono.chan(1), &[-2.0; 3]);
+        assert_eq!(stereo.chan(0), &[0.25; 3]);
+        assert_eq!(stereo.chan(1), &[0.5; 3]);
+    }
+
+    #[test]
+    fn gives_the_sum_and_difference_frequencies() {
+        let sample_rate = 8000.0;
+        let mut modulator = RingModulator::new(Frequency::from(100.0), SampleRate::from(8000));
+        let mut buffer = Buffer::<f64>::allocate(Channels::from(1), Samples::from(8000));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = (2.0 * PI * 1000.0 * n as f64 / sample_rate).sin();
+        }
+
+        modulator.process(&mut buffer);
+
+        // sin(a) sin(b) = (cos(a - b) - cos(a + b)) / 2
+        for (n, sample) in buffer.chan(0).iter().enumerate() {
+            let time = 2.0 * PI * n as f64 / sample_rate;
+            let expected = ((900.0 * time).cos() - (1100.0 * time).cos()) / 2.0;
+            assert!((sample - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn sidechain_of_another_length_panics() {
+        let mut modulator = RingModulator::new(Frequency::from(250.0), SampleRate::from(1000));
+        modulator.process_sidechain(&mut constant(&[1.0], 4), &constant(&[1.0], 3));
+    }
+}
@@ -0,0 +1,58 @@
+//! This module contains the `Sample` trait, which abstracts over the sample types that
+//! the processors in this crate can work with. Processing is done in `f64` internally,
+//! so a `Buffer<f32>` can be processed just as well as a `Buffer<f64>`.
+//!
+//! The filters keep their state in a `Float`, which is `f64` by default. A filter created with
+//! `with_precision::<f32>` keeps it in `f32` instead, which processes twice as many channels
+//! per SIMD instruction. Their coefficients are always computed in `f64`.
+//! ```rust
+//! use rabu::biquad::{low_pass_coefficients, BiquadFilter};
+//! use rabu::units::{Frequency, SampleRate};
+//!
+//! let coefficients = low_pass_coefficients(SampleRate::from(48000), Frequency::from(1000.0));
+//! let mut filter = BiquadFilter::<f32>::with_precision(coefficients);
+//!
+//! let output: f32 = filter.process_float(0.5);
+//! ```
+
+use std::fmt::Debug;
+use std::ops::{Add, Mul, Sub};
+
+/// A type that can be used as an audio sample by the processors in this crate.
+pub trait Sample: Copy + Default + PartialEq {
+    /// Converts the sample to a `f64`.
+    fn to_f64(self) -> f64;
+
+    /// Creates a sample from a `f64`.
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Sample for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+impl Sample for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+/// A float type that filters can keep their state in.
+pub trait Float:
+    Sample + Debug + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self>
+{
+}
+
+impl Float for f32 {}
+
+impl Float for f64 {}
@@ -0,0 +1,328 @@
+//! This module contains a sampler voice, which plays a clip at the pitch of a note: the clip is
+//! recorded at its root note, and other notes speed it up or slow it down by their interval to
+//! the root, like a tape. A loop keeps a held note sounding past the end of the clip, and an
+//! envelope shapes the amplitude. The clip is shared, so copies of the voice for every note of
+//! a sequence don't copy the audio.
+//! ```rust
+//! use std::sync::Arc;
+//!
+//! use rabu::buffer::Buffer;
+//! use rabu::clip::AudioClip;
+//! use rabu::envelope::Adsr;
+//! use rabu::sampler::SamplerVoice;
+//! use rabu::sequence::Voice;
+//! use rabu::units::{
+//!     BufferSize, Channels, MidiNote, NormalizedValue, SampleRate, SampleSection, Samples,
+//!     Seconds, Velocity,
+//! };
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let clip = Arc::new(AudioClip::new(
+//!     Buffer::<f32>::allocate(Channels::from(1), Samples::from(44100)),
+//!     SampleRate::from(44100),
+//! ));
+//! let envelope = Adsr::new(
+//!     Seconds::from(0.001),
+//!     Seconds::from(0.0),
+//!     NormalizedValue::from(1.0),
+//!     Seconds::from(0.1),
+//!     sample_rate,
+//! );
+//!
+//! let mut voice = SamplerVoice::new(clip, MidiNote::from(60), envelope);
+//! voice.set_loop(Some(SampleSection { start: Samples::from(22050), length: Samples::from(4410) }));
+//! voice.prepare(sample_rate, BufferSize::from(256), Channels::from(2));
+//!
+//! // A fifth above the root.
+//! voice.note_on(MidiNote::from(67), Velocity::from(100));
+//! let mut output = Buffer::<f32>::allocate(Channels::from(2), Samples::from(256));
+//! voice.render(&mut output);
+//! ```
+
+use std::sync::Arc;
+
+use crate::buffer::Buffer;
+use crate::clip::AudioClip;
+use crate::envelope::Adsr;
+use crate::sequence::Voice;
+use crate::units::{
+    BufferSize, Channels, MidiNote, PlaybackRate, SampleRate, SampleSection, Samples, SamplesF64,
+    Semitones, Velocity,
+};
+use crate::varispeed::{Interpolation, PlaybackMode, VarispeedReader};
+
+/// Plays a clip at the pitch of the notes.
+#[derive(Clone, Debug)]
+pub struct SamplerVoice {
+    clip: Arc<AudioClip<f32>>,
+    root: MidiNote,
+    transpose: Semitones,
+    loop_section: Option<SampleSection>,
+    envelope: Adsr,
+    reader: VarispeedReader,
+    sample_rate: SampleRate,
+    gain: f64,
+    /// The audio read from the clip, in the channels of the clip.
+    read: Buffer<f32>,
+}
+
+impl SamplerVoice {
+    /// Creates a new voice for the clip, which sounds at its original pitch at the root note.
+    /// It plays the clip once, with cubic interpolation.
+    pub fn new(clip: Arc<AudioClip<f32>>, root: MidiNote, envelope: Adsr) -> Self {
+        let read = Buffer::allocate(clip.num_channels(), Samples::from(0));
+        Self {
+            clip,
+            root,
+            transpose: Semitones::from(0.0),
+            loop_section: None,
+            envelope,
+            reader: VarispeedReader::new(Interpolation::Cubic),
+            sample_rate: SampleRate::from(48000),
+            gain: 0.0,
+            read,
+        }
+    }
+
+    /// Returns the clip.
+    pub fn clip(&self) -> &Arc<AudioClip<f32>> {
+        &self.clip
+    }
+
+    /// Returns the root note.
+    pub fn root(&self) -> MidiNote {
+        self.root
+    }
+
+    /// Changes the note at which the clip sounds at its original pitch, which applies from the
+    /// next note on.
+    pub fn set_root(&mut self, root: MidiNote) {
+        self.root = root;
+    }
+
+    /// Returns the transposition.
+    pub fn transpose(&self) -> Semitones {
+        self.transpose
+    }
+
+    /// Changes the interval every note is shifted by on top of its distance to the root, e.g.
+    /// to fine tune the clip. This applies from the next note on.
+    pub fn set_transpose(&mut self, transpose: Semitones) {
+        self.transpose = transpose;
+    }
+
+    /// Returns the loop, in samples of the clip.
+    pub fn loop_section(&self) -> Option<SampleSection> {
+        self.loop_section
+    }
+
+    /// Changes the section of the clip that repeats once the playhead reaches it, or plays the
+    /// clip once without a loop. This applies from the next note on.
+    /// This will panic if the loop is empty.
+    pub fn set_loop(&mut self, loop_section: Option<SampleSection>) {
+        if let Some(section) = loop_section {
+            assert!(
+                section.length > Samples::from(0),
+                "a loop must contain at least one sample"
+            );
+        }
+        self.loop_section = loop_section;
+    }
+
+    /// Returns the interpolation.
+    pub fn interpolation(&self) -> Interpolation {
+        self.reader.interpolation()
+    }
+
+    /// Changes how the samples in between the samples of the clip are calculated.
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.reader.set_interpolation(interpolation);
+    }
+
+    /// Returns the amplitude envelope.
+    pub fn envelope(&self) -> &Adsr {
+        &self.envelope
+    }
+
+    /// Returns the amplitude envelope, to change it.
+    pub fn envelope_mut(&mut self) -> &mut Adsr {
+        &mut self.envelope
+    }
+}
+
+impl Voice for SamplerVoice {
+    fn prepare(&mut self, sample_rate: SampleRate, buffer_size: BufferSize, _: Channels) {
+        self.sample_rate = sample_rate;
+        self.envelope.set_sample_rate(sample_rate);
+        self.read = Buffer::allocate(self.clip.num_channels(), buffer_size.as_samples());
+    }
+
+    fn note_on(&mut self, note: MidiNote, velocity: Velocity) {
+        let interval = note.as_u8() as f64 - self.root.as_u8() as f64 + self.transpose.as_f64();
+        self.reader
+            .set_rate(PlaybackRate::from(Semitones::from(interval)));
+        self.reader.set_mode(match self.loop_section {
+            Some(section) => PlaybackMode::Loop {
+                start: section.start,
+                end: section.end(),
+            },
+            None => PlaybackMode::OneShot,
+        });
+        self.reader.set_playhead(SamplesF64::from(0.0));
+        self.envelope.note_on();
+        self.gain = velocity.to_normalized().as_f64();
+    }
+
+    fn note_off(&mut self) {
+        self.envelope.note_off();
+    }
+
+    /// The voice is done when the envelope is, or when it played a clip without a loop to the
+    /// end.
+    fn is_active(&self) -> bool {
+        self.envelope.is_active() && !self.reader.is_finished()
+    }
+
+    /// Renders the clip into the buffer. When the buffer has more channels than the clip, the
+    /// channels of the clip are repeated, so a mono clip plays in every channel.
+    fn render(&mut self, buffer: &mut Buffer<f32>) {
+        self.read.set_num_samples(buffer.num_samples());
+        self.reader
+            .read_clip(&self.clip, self.sample_rate, &mut self.read);
+
+        let num_read = self.read.num_channels().as_usize();
+        for index in buffer.sample_indices() {
+            let gain = self.gain * self.envelope.next_sample();
+            for (channel, samples) in buffer.iter_chans_mut().enumerate() {
+                let sample = self.read.chan(channel % num_read)[index];
+                samples[index] = (sample as f64 * gain) as f32;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{NormalizedValue, Seconds};
+
+    const SAMPLE_RATE: u32 = 1000;
+
+    /// A clip that counts up from 0, one per sample.
+    fn ramp(length: usize) -> Arc<AudioClip<f32>> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(length));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = n as f32;
+        }
+        Arc::new(AudioClip::new(buffer, SampleRate::from(SAMPLE_RATE)))
+    }
+
+    /// A voice that is at full level right away, and stops right away.
+    fn voice(clip: Arc<AudioClip<f32>>) -> SamplerVoice {
+        let sample_rate = SampleRate::from(SAMPLE_RATE);
+        let envelope = Adsr::new(
+            Seconds::from(0.0),
+            Seconds::from(0.0),
+            NormalizedValue::from(1.0),
+            Seconds::from(0.0),
+            sample_rate,
+        );
+        let mut voice = SamplerVoice::new(clip, MidiNote::from(60), envelope);
+        voice.prepare(sample_rate, BufferSize::from(64), Channels::from(1));
+        voice
+    }
+
+    fn render(voice: &mut SamplerVoice, num_channels: usize, length: usize) -> Buffer<f32> {
+        let mut buffer = Buffer::allocate(Channels::from(num_channels), Samples::from(length));
+        voice.render(&mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn root_note_plays_the_clip_as_is() {
+        let mut voice = voice(ramp(100));
+
+        voice.note_on(MidiNote::from(60), Velocity::from(127));
+
+        let output = render(&mut voice, 1, 10);
+        assert_eq!(
+            output.chan(0),
+            [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]
+        );
+    }
+
+    #[test]
+    fn an_octave_up_plays_twice_as_fast() {
+        let mut voice = voice(ramp(100));
+
+        voice.note_on(MidiNote::from(72), Velocity::from(127));
+
+        let output = render(&mut voice, 1, 4);
+        assert_eq!(output.chan(0), [0.0, 2.0, 4.0, 6.0]);
+    }
+
+    #[test]
+    fn transpose_shifts_every_note() {
+        let mut voice = voice(ramp(100));
+        voice.set_transpose(Semitones::from(-12.0));
+
+        voice.note_on(MidiNote::from(72), Velocity::from(127));
+
+        let output = render(&mut voice, 1, 4);
+        assert_eq!(output.chan(0), [0.0, 1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn velocity_sets_the_level() {
+        let mut voice = voice(ramp(100));
+
+        voice.note_on(MidiNote::from(60), Velocity::from(0));
+
+        assert!(render(&mut voice, 1, 10).is_default_filled());
+    }
+
+    #[test]
+    fn without_a_loop_the_voice_stops_at_the_end_of_the_clip() {
+        let mut voice = voice(ramp(10));
+
+        voice.note_on(MidiNote::from(60), Velocity::from(127));
+        let output = render(&mut voice, 1, 20);
+
+        assert_eq!(output.chan(0)[9], 9.0);
+        assert!(output.chan(0)[10..].iter().all(|sample| *sample == 0.0));
+        assert!(!voice.is_active());
+    }
+
+    #[test]
+    fn loop_keeps_a_held_note_going_until_the_release() {
+        let mut voice = voice(ramp(10));
+        voice.set_loop(Some(SampleSection {
+            start: Samples::from(6),
+            length: Samples::from(3),
+        }));
+
+        voice.note_on(MidiNote::from(60), Velocity::from(127));
+        let output = render(&mut voice, 1, 14);
+
+        assert_eq!(
+            output.chan(0),
+            [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 6.0, 7.0, 8.0, 6.0, 7.0]
+        );
+        assert!(voice.is_active());
+
+        voice.note_off();
+        render(&mut voice, 1, 2);
+        assert!(!voice.is_active());
+    }
+
+    #[test]
+    fn mono_clip_plays_in_every_channel() {
+        let mut voice = voice(ramp(100));
+
+        voice.note_on(MidiNote::from(60), Velocity::from(127));
+
+        let output = render(&mut voice, 2, 4);
+        assert_eq!(output.chan(0), output.chan(1));
+        assert_eq!(output.chan(1), [0.0, 1.0, 2.0, 3.0]);
+    }
+}
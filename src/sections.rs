@@ -0,0 +1,265 @@
+//! This module contains a list of time sections, e.g. the regions where something happens in a
+//! recording. The sections are kept in order, and sections that overlap or touch are merged,
+//! so every moment is covered at most once.
+//! ```rust
+//! use rabu::sections::SectionList;
+//! use rabu::units::{Duration, TimePoint, TimeSection};
+//!
+//! let section = |start: f64, duration: f64| TimeSection {
+//!     start: TimePoint::from_secs_f64(start),
+//!     duration: Duration::from_secs_f64(duration),
+//! };
+//!
+//! let list: SectionList = [section(4.0, 1.0), section(1.0, 2.0), section(2.5, 1.0)]
+//!     .into_iter()
+//!     .collect();
+//!
+//! assert_eq!(list.as_slice(), &[section(1.0, 2.5), section(4.0, 1.0)]);
+//! assert_eq!(list.gaps(section(0.0, 6.0)).as_slice(), &[section(0.0, 1.0), section(3.5, 0.5), section(5.0, 1.0)]);
+//! ```
+
+use partial_min_max::{max, min};
+
+use crate::units::{Duration, TimePoint, TimeSection};
+
+/// An ordered list of time sections that don't overlap.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SectionList {
+    sections: Vec<TimeSection>,
+}
+
+impl SectionList {
+    /// Creates an empty list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the section, merging it with the sections it overlaps or touches.
+    pub fn insert(&mut self, section: TimeSection) {
+        let first = self
+            .sections
+            .partition_point(|existing| existing.end() < section.start);
+        let last = self
+            .sections
+            .partition_point(|existing| existing.start <= section.end());
+
+        let mut start = section.start;
+        let mut end = section.end();
+        for existing in &self.sections[first..last] {
+            start = min(start, existing.start);
+            end = max(end, existing.end());
+        }
+        self.sections.splice(
+            first..last,
+            [TimeSection {
+                start,
+                duration: end - start,
+            }],
+        );
+    }
+
+    /// Returns the number of sections.
+    pub fn len(&self) -> usize {
+        self.sections.len()
+    }
+
+    /// Tells whether the list has no sections.
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// Returns the sections in order.
+    pub fn as_slice(&self) -> &[TimeSection] {
+        &self.sections
+    }
+
+    /// Returns an iterator over the sections in order.
+    pub fn iter(&self) -> impl Iterator<Item = &TimeSection> {
+        self.sections.iter()
+    }
+
+    /// Returns the time covered by all sections together.
+    pub fn total_duration(&self) -> Duration {
+        Duration::from_secs_f64(
+            self.sections
+                .iter()
+                .map(|section| section.duration.as_secs_f64())
+                .sum(),
+        )
+    }
+
+    /// Returns the section from the start of the first section to the end of the last one, or
+    /// `None` when the list is empty.
+    pub fn bounds(&self) -> Option<TimeSection> {
+        let (first, last) = (self.sections.first()?, self.sections.last()?);
+        Some(section_between(first.start, last.end()))
+    }
+
+    /// Returns the parts of the range that none of the sections cover.
+    pub fn gaps(&self, range: TimeSection) -> SectionList {
+        let mut gaps = SectionList::new();
+        let mut position = range.start;
+        for section in self.sections.iter().filter_map(|s| s.get_overlap(range)) {
+            if section.start > position {
+                gaps.sections.push(section_between(position, section.start));
+            }
+            position = section.end();
+        }
+        if range.end() > position {
+            gaps.sections.push(section_between(position, range.end()));
+        }
+        gaps
+    }
+}
+
+/// Returns the time covered by the sections together, counting overlapping time once.
+/// ```
+/// use rabu::sections::total_duration;
+/// use rabu::units::{Duration, TimePoint, TimeSection};
+///
+/// let section = |start: f64, duration: f64| TimeSection {
+///     start: TimePoint::from_secs_f64(start),
+///     duration: Duration::from_secs_f64(duration),
+/// };
+///
+/// let duration = total_duration([section(0.0, 2.0), section(1.0, 2.0), section(5.0, 1.0)]);
+///
+/// assert_eq!(duration, Duration::from_secs_f64(4.0));
+/// ```
+pub fn total_duration(sections: impl IntoIterator<Item = TimeSection>) -> Duration {
+    sections
+        .into_iter()
+        .collect::<SectionList>()
+        .total_duration()
+}
+
+/// Returns the parts between the sections that none of them cover, from the start of the
+/// first section to the end of the last one.
+pub fn gaps_between(sections: impl IntoIterator<Item = TimeSection>) -> SectionList {
+    let list: SectionList = sections.into_iter().collect();
+    match list.bounds() {
+        Some(bounds) => list.gaps(bounds),
+        None => list,
+    }
+}
+
+/// Returns the section from the earliest start to the latest end of the sections, e.g. the
+/// range to export, or `None` when there are no sections.
+pub fn bounding_section(sections: impl IntoIterator<Item = TimeSection>) -> Option<TimeSection> {
+    sections
+        .into_iter()
+        .map(|section| (section.start, section.end()))
+        .reduce(|(start, end), (other_start, other_end)| {
+            (min(start, other_start), max(end, other_end))
+        })
+        .map(|(start, end)| section_between(start, end))
+}
+
+fn section_between(start: TimePoint, end: TimePoint) -> TimeSection {
+    TimeSection {
+        start,
+        duration: end - start,
+    }
+}
+
+impl FromIterator<TimeSection> for SectionList {
+    fn from_iter<I: IntoIterator<Item = TimeSection>>(iter: I) -> Self {
+        let mut list = SectionList::new();
+        for section in iter {
+            list.insert(section);
+        }
+        list
+    }
+}
+
+impl IntoIterator for SectionList {
+    type Item = TimeSection;
+    type IntoIter = std::vec::IntoIter<TimeSection>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.sections.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a SectionList {
+    type Item = &'a TimeSection;
+    type IntoIter = std::slice::Iter<'a, TimeSection>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.sections.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn section(start: f64, duration: f64) -> TimeSection {
+        TimeSection {
+            start: TimePoint::from_secs_f64(start),
+            duration: Duration::from_secs_f64(duration),
+        }
+    }
+
+    #[test_case(section(5.0, 1.0) => vec![section(1.0, 1.0), section(3.0, 1.0), section(5.0, 1.0)]; "after")]
+    #[test_case(section(0.0, 0.5) => vec![section(0.0, 0.5), section(1.0, 1.0), section(3.0, 1.0)]; "before")]
+    #[test_case(section(2.0, 1.0) => vec![section(1.0, 3.0)]; "touching both")]
+    #[test_case(section(1.5, 2.0) => vec![section(1.0, 3.0)]; "overlapping both")]
+    #[test_case(section(0.0, 10.0) => vec![section(0.0, 10.0)]; "covering all")]
+    #[test_case(section(3.2, 0.5) => vec![section(1.0, 1.0), section(3.0, 1.0)]; "inside")]
+    fn insert_merges_overlapping_sections(new: TimeSection) -> Vec<TimeSection> {
+        let mut list: SectionList = [section(1.0, 1.0), section(3.0, 1.0)].into_iter().collect();
+        list.insert(new);
+        list.into_iter().collect()
+    }
+
+    #[test]
+    fn total_duration_sums_the_sections() {
+        let list: SectionList = [section(0.0, 1.0), section(0.5, 1.0), section(3.0, 0.25)]
+            .into_iter()
+            .collect();
+
+        assert_eq!(list.total_duration(), Duration::from_secs_f64(1.75));
+    }
+
+    #[test]
+    fn gaps_of_an_empty_list_are_the_range() {
+        let list = SectionList::new();
+
+        assert_eq!(
+            list.gaps(section(1.0, 2.0)).as_slice(),
+            &[section(1.0, 2.0)]
+        );
+    }
+
+    #[test]
+    fn total_duration_counts_overlaps_once() {
+        let sections = [section(0.0, 4.0), section(1.0, 1.0), section(3.0, 2.0)];
+
+        assert_eq!(total_duration(sections), Duration::from_secs_f64(5.0));
+        assert_eq!(total_duration([]), Duration::from_secs_f64(0.0));
+    }
+
+    #[test]
+    fn gaps_between_lie_within_the_sections() {
+        let sections = [section(6.0, 1.0), section(1.0, 2.0), section(2.0, 2.0)];
+
+        let gaps = gaps_between(sections);
+
+        assert_eq!(gaps.as_slice(), &[section(4.0, 2.0)]);
+        assert!(gaps_between([]).is_empty());
+    }
+
+    #[test]
+    fn bounding_section_spans_all_sections() {
+        let sections = [section(3.0, 1.0), section(1.0, 10.0), section(0.5, 1.0)];
+
+        assert_eq!(bounding_section(sections), Some(section(0.5, 10.5)));
+        assert_eq!(bounding_section([]), None);
+
+        let list: SectionList = sections.into_iter().collect();
+        assert_eq!(list.bounds(), Some(section(0.5, 10.5)));
+    }
+}
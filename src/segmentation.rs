@@ -0,0 +1,309 @@
+//! This module contains activity detection, which splits long recordings into the regions
+//! where something is going on and the silence in between, e.g. to strip the pauses out of
+//! a podcast. A region starts once the level has stayed above the threshold for the hold
+//! time, which keeps clicks and short noises out. It ends when the level has stayed below the
+//! threshold for the hangover time, which keeps short pauses and quiet word endings in.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::segmentation::Segmenter;
+//! use rabu::units::{Channels, SampleRate, Samples, Seconds, TimePoint, TimeSection};
+//!
+//! // One second of silence, one second of a tone, one second of silence.
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(1), Samples::from(3000));
+//! for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate().skip(1000).take(1000) {
+//!     *sample = 0.5 * (0.3 * n as f32).sin();
+//! }
+//!
+//! let mut segmenter = Segmenter::new(SampleRate::from(1000));
+//! segmenter.set_hangover(Seconds::from(0.1));
+//! let active = segmenter.segment(&buffer);
+//!
+//! assert_eq!(active.len(), 1);
+//! let region = active.as_slice()[0];
+//! assert!((region.start.as_secs_f64() - 1.0).abs() < 0.01);
+//! assert!((region.end().as_secs_f64() - 2.1).abs() < 0.01);
+//!
+//! let whole = TimeSection { start: TimePoint::from_secs_f64(0.0), duration: Seconds::from(3.0).into() };
+//! assert_eq!(active.gaps(whole).len(), 2);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::sections::SectionList;
+use crate::units::{Decibels, SampleRate, Samples, Seconds, TimePoint, TimeSection};
+
+/// The length of the window the RMS level is measured over.
+const LEVEL_WINDOW: f64 = 0.01;
+
+/// Finds the active regions of audio, offline or as it streams in.
+#[derive(Clone, Debug)]
+pub struct Segmenter {
+    sample_rate: SampleRate,
+    threshold: Decibels,
+    hold: Seconds,
+    hangover: Seconds,
+    /// The squares of the last window of samples of the loudest channel, with their sum.
+    squares: Vec<f64>,
+    sum: f64,
+    square_position: usize,
+    state: State,
+    /// The number of samples processed since the start or the last reset.
+    processed: usize,
+    sections: SectionList,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum State {
+    /// Silent, possibly with the level above the threshold since the given sample.
+    Silent { above_since: Option<usize> },
+    /// Active since the given sample, possibly with the level below the threshold since the
+    /// other given sample.
+    Active {
+        start: usize,
+        below_since: Option<usize>,
+    },
+}
+
+impl Segmenter {
+    /// Creates a new segmenter with a threshold of -45 dBFS RMS, a hold time of 20 ms and a
+    /// hangover time of 300 ms.
+    pub fn new(sample_rate: SampleRate) -> Self {
+        Self {
+            sample_rate,
+            threshold: Decibels::from(-45.0),
+            hold: Seconds::from(0.02),
+            hangover: Seconds::from(0.3),
+            squares: vec![
+                0.0;
+                Seconds::from(LEVEL_WINDOW)
+                    .to_samples(sample_rate)
+                    .as_usize()
+                    .max(1)
+            ],
+            sum: 0.0,
+            square_position: 0,
+            state: State::Silent { above_since: None },
+            processed: 0,
+            sections: SectionList::new(),
+        }
+    }
+
+    /// Returns the threshold.
+    pub fn threshold(&self) -> Decibels {
+        self.threshold
+    }
+
+    /// Changes the RMS level above which the audio counts as active. The loudest channel
+    /// counts.
+    pub fn set_threshold(&mut self, threshold: Decibels) {
+        self.threshold = threshold;
+    }
+
+    /// Returns the hold time.
+    pub fn hold(&self) -> Seconds {
+        self.hold
+    }
+
+    /// Changes how long the level must stay above the threshold to start a region.
+    pub fn set_hold(&mut self, hold: Seconds) {
+        self.hold = hold;
+    }
+
+    /// Returns the hangover time.
+    pub fn hangover(&self) -> Seconds {
+        self.hangover
+    }
+
+    /// Changes how long the level must stay below the threshold to end a region. The region
+    /// includes the hangover.
+    pub fn set_hangover(&mut self, hangover: Seconds) {
+        self.hangover = hangover;
+    }
+
+    /// Clears the internal state and the regions found so far, as if no audio was processed
+    /// yet.
+    pub fn reset(&mut self) {
+        self.squares.fill(0.0);
+        self.sum = 0.0;
+        self.square_position = 0;
+        self.state = State::Silent { above_since: None };
+        self.processed = 0;
+        self.sections = SectionList::new();
+    }
+
+    /// Returns the regions that have ended so far, from the start of the stream.
+    pub fn sections(&self) -> &SectionList {
+        &self.sections
+    }
+
+    /// Analyzes the buffer as the next part of the stream.
+    pub fn process<T: Sample>(&mut self, buffer: &Buffer<T>) {
+        let threshold = self.threshold.to_gain();
+        let hold = self.hold.to_samples(self.sample_rate).as_usize();
+        let hangover = self.hangover.to_samples(self.sample_rate).as_usize();
+
+        for index in buffer.sample_indices() {
+            let square = buffer
+                .iter_chans()
+                .map(|channel| channel[index].to_f64().powi(2))
+                .fold(0.0, f64::max);
+            let is_above = self.next_rms(square) > threshold;
+            let position = self.processed;
+            self.processed += 1;
+
+            self.state = match self.state {
+                State::Silent { above_since } => match (is_above, above_since) {
+                    (false, _) => State::Silent { above_since: None },
+                    (true, Some(start)) if position + 1 - start >= hold => State::Active {
+                        start,
+                        below_since: None,
+                    },
+                    (true, above_since) => State::Silent {
+                        above_since: above_since.or(Some(position)),
+                    },
+                },
+                State::Active { start, below_since } => match (is_above, below_since) {
+                    (true, _) => State::Active {
+                        start,
+                        below_since: None,
+                    },
+                    (false, Some(end)) if position + 1 - end >= hangover => {
+                        self.add_section(start, end + hangover);
+                        State::Silent { above_since: None }
+                    }
+                    (false, below_since) => State::Active {
+                        start,
+                        below_since: below_since.or(Some(position)),
+                    },
+                },
+            };
+        }
+    }
+
+    /// Ends the stream, and returns all regions, including the one that is still going on.
+    /// This resets the segmenter.
+    pub fn finish(&mut self) -> SectionList {
+        if let State::Active { start, below_since } = self.state {
+            let hangover = self.hangover.to_samples(self.sample_rate).as_usize();
+            let end = below_since.map_or(self.processed, |end| end + hangover);
+            self.add_section(start, end.min(self.processed));
+        }
+        let sections = std::mem::take(&mut self.sections);
+        self.reset();
+        sections
+    }
+
+    /// Returns the active regions of the buffer. This resets the segmenter first.
+    pub fn segment<T: Sample>(&mut self, buffer: &Buffer<T>) -> SectionList {
+        self.reset();
+        self.process(buffer);
+        self.finish()
+    }
+
+    /// Adds the square to the window, and returns the RMS level of the window.
+    fn next_rms(&mut self, square: f64) -> f64 {
+        self.sum += square - self.squares[self.square_position];
+        self.squares[self.square_position] = square;
+        self.square_position += 1;
+        if self.square_position == self.squares.len() {
+            // Summing from scratch now and then keeps rounding errors from adding up.
+            self.square_position = 0;
+            self.sum = self.squares.iter().sum();
+        }
+        (self.sum.max(0.0) / self.squares.len() as f64).sqrt()
+    }
+
+    fn add_section(&mut self, start: usize, end: usize) {
+        let to_time =
+            |position: usize| TimePoint::from(Samples::from(position).to_seconds(self.sample_rate));
+        let start = to_time(start);
+        self.sections.insert(TimeSection {
+            start,
+            duration: to_time(end) - start,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Channels;
+
+    const SAMPLE_RATE: u32 = 1000;
+
+    /// Tone in the given regions of samples, silence elsewhere.
+    fn tone(regions: &[(usize, usize)], length: usize) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(length));
+        for &(start, end) in regions {
+            for n in start..end {
+                buffer.chan_mut(0)[n] = 0.5 * (0.3 * n as f64).sin();
+            }
+        }
+        buffer
+    }
+
+    fn segmenter() -> Segmenter {
+        let mut segmenter = Segmenter::new(SampleRate::from(SAMPLE_RATE));
+        segmenter.set_hold(Seconds::from(0.05));
+        segmenter.set_hangover(Seconds::from(0.2));
+        segmenter
+    }
+
+    /// Checks the starts and ends of the sections, allowing for the window of the level.
+    fn assert_bounds(sections: &SectionList, expected: &[(f64, f64)]) {
+        assert_eq!(sections.len(), expected.len(), "{sections:?}");
+        for (section, (start, end)) in sections.iter().zip(expected) {
+            assert!(
+                (section.start.as_secs_f64() - start).abs() < 0.005,
+                "{section:?}"
+            );
+            assert!(
+                (section.end().as_secs_f64() - end).abs() < 0.05,
+                "{section:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn finds_the_regions_with_their_hangover() {
+        let sections = segmenter().segment(&tone(&[(500, 1000), (2000, 2500)], 4000));
+
+        assert_bounds(&sections, &[(0.5, 1.2), (2.0, 2.7)]);
+    }
+
+    #[test]
+    fn short_pauses_stay_inside_a_region() {
+        let sections = segmenter().segment(&tone(&[(500, 1000), (1100, 1500)], 3000));
+
+        assert_bounds(&sections, &[(0.5, 1.7)]);
+    }
+
+    #[test]
+    fn short_noises_are_held_out() {
+        let sections = segmenter().segment(&tone(&[(500, 520), (1000, 1500)], 3000));
+
+        assert_bounds(&sections, &[(1.0, 1.7)]);
+    }
+
+    #[test]
+    fn region_at_the_end_is_cut_off_at_the_end() {
+        let sections = segmenter().segment(&tone(&[(500, 1900)], 2000));
+
+        assert_bounds(&sections, &[(0.5, 2.0)]);
+    }
+
+    #[test]
+    fn streaming_finds_the_same_regions() {
+        let buffer = tone(&[(300, 800), (1500, 2600)], 4000);
+        let offline = segmenter().segment(&buffer);
+        let mut segmenter = segmenter();
+
+        for block in buffer.chan(0).chunks(64) {
+            let mut part = Buffer::allocate(Channels::from(1), Samples::from(block.len()));
+            part.chan_mut(0).copy_from_slice(block);
+            segmenter.process(&part);
+        }
+
+        assert_eq!(segmenter.finish(), offline);
+    }
+}
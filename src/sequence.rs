@@ -0,0 +1,494 @@
+//! This module contains a renderer that plays a sequence of notes on a synth voice, straight
+//! into a buffer, e.g. for tests and demos. Every note gets a voice of its own, so notes can
+//! overlap. The blocks are split at the note-ons and note-offs, so every note starts and stops
+//! at exactly its sample. After the last note-off, the renderer keeps going until every
+//! release has faded out.
+//! ```rust
+//! use rabu::envelope::Adsr;
+//! use rabu::osc::{Oscillator, Waveform};
+//! use rabu::sequence::{OscillatorVoice, SequenceRenderer};
+//! use rabu::units::{
+//!     BufferSize, Channels, Duration, Frequency, MidiNote, NormalizedValue, SampleRate,
+//!     Seconds, TimePoint, TimeSection, Velocity,
+//! };
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let voice = OscillatorVoice::new(
+//!     Oscillator::new(Waveform::Saw, Frequency::from(440.0), sample_rate),
+//!     Adsr::new(
+//!         Seconds::from(0.005),
+//!         Seconds::from(0.1),
+//!         NormalizedValue::from(0.6),
+//!         Seconds::from(0.2),
+//!         sample_rate,
+//!     ),
+//! );
+//! let note = |note: u8, start: f64| {
+//!     let section = TimeSection {
+//!         start: TimePoint::from_secs_f64(start),
+//!         duration: Duration::from_secs_f64(0.25),
+//!     };
+//!     (MidiNote::from(note), Velocity::from(100), section)
+//! };
+//!
+//! let renderer = SequenceRenderer::new(sample_rate, BufferSize::from(256), Channels::from(2));
+//! let audio = renderer.render(&[note(60, 0.0), note(64, 0.25), note(67, 0.5)], &voice);
+//!
+//! // The last note ends at 0.75 seconds, and its release takes another 0.2 seconds.
+//! assert!(audio.num_samples().to_seconds(sample_rate).as_f64() >= 0.95);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::envelope::Adsr;
+use crate::events::{split_block, TimedEvent};
+use crate::osc::Oscillator;
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::units::{
+    BufferSize, Channels, MidiNote, SampleRate, SampleSection, Samples, Seconds, TimeSection,
+    Velocity,
+};
+
+/// A voice of a synth, which plays one note at a time.
+pub trait Voice {
+    /// Gets the voice ready for audio in the given format, before the first note.
+    fn prepare(&mut self, sample_rate: SampleRate, buffer_size: BufferSize, num_channels: Channels);
+
+    /// Starts playing the note.
+    fn note_on(&mut self, note: MidiNote, velocity: Velocity);
+
+    /// Releases the note.
+    fn note_off(&mut self);
+
+    /// Returns whether the voice is still making sound, so the renderer knows when it's done.
+    fn is_active(&self) -> bool;
+
+    /// Renders the next samples of the voice into the buffer, replacing what was in it.
+    fn render(&mut self, buffer: &mut Buffer<f32>);
+}
+
+/// The classic synth voice: an oscillator at the pitch of the note, shaped by an envelope, and
+/// as loud as the velocity.
+#[derive(Clone, Debug)]
+pub struct OscillatorVoice {
+    oscillator: Oscillator,
+    envelope: Adsr,
+    gain: f64,
+}
+
+impl OscillatorVoice {
+    /// Creates a new voice from the oscillator and the envelope. The frequency of the
+    /// oscillator is replaced by the one of the note.
+    pub fn new(oscillator: Oscillator, envelope: Adsr) -> Self {
+        Self {
+            oscillator,
+            envelope,
+            gain: 0.0,
+        }
+    }
+
+    /// Returns the oscillator.
+    pub fn oscillator(&self) -> &Oscillator {
+        &self.oscillator
+    }
+
+    /// Returns the envelope.
+    pub fn envelope(&self) -> &Adsr {
+        &self.envelope
+    }
+}
+
+impl Voice for OscillatorVoice {
+    fn prepare(&mut self, sample_rate: SampleRate, _: BufferSize, _: Channels) {
+        self.oscillator.set_sample_rate(sample_rate);
+        self.envelope.set_sample_rate(sample_rate);
+    }
+
+    fn note_on(&mut self, note: MidiNote, velocity: Velocity) {
+        self.oscillator.set_frequency(note.to_frequency());
+        self.oscillator.reset();
+        self.envelope.note_on();
+        self.gain = velocity.to_normalized().as_f64();
+    }
+
+    fn note_off(&mut self) {
+        self.envelope.note_off();
+    }
+
+    fn is_active(&self) -> bool {
+        self.envelope.is_active()
+    }
+
+    fn render(&mut self, buffer: &mut Buffer<f32>) {
+        for index in buffer.sample_indices() {
+            let value = self.gain * self.oscillator.next_sample() * self.envelope.next_sample();
+            for channel in buffer.iter_chans_mut() {
+                channel[index] = value as f32;
+            }
+        }
+    }
+}
+
+/// A voice with a processor after it, e.g. a filter or a distortion, so any `AudioProcessor`
+/// can be part of the sound of a note. The voice is done when the voice before the processor
+/// is, so the tail of e.g. a reverb is cut off.
+#[derive(Clone, Debug)]
+pub struct ProcessedVoice<V, P> {
+    voice: V,
+    processor: P,
+    context: ProcessContext,
+}
+
+impl<V: Voice, P: AudioProcessor> ProcessedVoice<V, P> {
+    /// Creates a new voice that runs the output of the voice through the processor.
+    pub fn new(voice: V, processor: P) -> Self {
+        Self {
+            voice,
+            processor,
+            context: ProcessContext::new(SampleRate::from(48000)),
+        }
+    }
+
+    /// Returns the voice before the processor.
+    pub fn voice(&self) -> &V {
+        &self.voice
+    }
+
+    /// Returns the processor.
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+}
+
+impl<V: Voice, P: AudioProcessor> Voice for ProcessedVoice<V, P> {
+    fn prepare(
+        &mut self,
+        sample_rate: SampleRate,
+        buffer_size: BufferSize,
+        num_channels: Channels,
+    ) {
+        self.voice.prepare(sample_rate, buffer_size, num_channels);
+        self.processor
+            .prepare(sample_rate, buffer_size, num_channels);
+        self.context = ProcessContext::new(sample_rate);
+    }
+
+    /// Starts the note with a clean processor, whose position starts at the note.
+    fn note_on(&mut self, note: MidiNote, velocity: Velocity) {
+        self.processor.reset();
+        self.context.position = Samples::from(0);
+        self.context.is_playing = true;
+        self.voice.note_on(note, velocity);
+    }
+
+    fn note_off(&mut self) {
+        self.voice.note_off();
+    }
+
+    fn is_active(&self) -> bool {
+        self.voice.is_active()
+    }
+
+    fn render(&mut self, buffer: &mut Buffer<f32>) {
+        self.voice.render(buffer);
+        self.processor.process(buffer, &self.context);
+        self.context.position += buffer.num_samples();
+    }
+}
+
+/// A note-on or note-off of the note at the index in the sequence.
+#[derive(Copy, Clone, Debug)]
+enum NoteEvent {
+    Off(usize),
+    On(usize, MidiNote, Velocity),
+}
+
+/// A voice that plays the note at the index in the sequence.
+struct ActiveVoice<V> {
+    index: usize,
+    voice: V,
+}
+
+/// Renders sequences of notes on copies of a voice, block by block.
+#[derive(Copy, Clone, Debug)]
+pub struct SequenceRenderer {
+    sample_rate: SampleRate,
+    buffer_size: BufferSize,
+    num_channels: Channels,
+    max_tail: Seconds,
+}
+
+impl SequenceRenderer {
+    /// Creates a new renderer for audio in the given format, which lets the releases ring for
+    /// at most 10 seconds after the last note-off.
+    /// This will panic if the buffer size is 0.
+    pub fn new(sample_rate: SampleRate, buffer_size: BufferSize, num_channels: Channels) -> Self {
+        assert!(buffer_size.as_u32() > 0, "the buffer size must be above 0");
+        Self {
+            sample_rate,
+            buffer_size,
+            num_channels,
+            max_tail: Seconds::from(10.0),
+        }
+    }
+
+    /// Returns the sample rate.
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// Returns the largest number of samples the voices render at once.
+    pub fn buffer_size(&self) -> BufferSize {
+        self.buffer_size
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        self.num_channels
+    }
+
+    /// Returns the longest time the releases can ring after the last note-off.
+    pub fn max_tail(&self) -> Seconds {
+        self.max_tail
+    }
+
+    /// Changes the longest time the releases can ring after the last note-off, which stops
+    /// voices that never end from rendering forever.
+    pub fn set_max_tail(&mut self, max_tail: Seconds) {
+        self.max_tail = max_tail;
+    }
+
+    /// Renders the notes, each on a copy of the voice, from the start of the timeline. The
+    /// buffer lasts until the last voice is done, rounded up to a whole block. Notes that are
+    /// shorter than a sample are left out.
+    pub fn render<V: Voice + Clone>(
+        &self,
+        notes: &[(MidiNote, Velocity, TimeSection)],
+        voice: &V,
+    ) -> Buffer<f32> {
+        let mut template = voice.clone();
+        template.prepare(self.sample_rate, self.buffer_size, self.num_channels);
+
+        let mut events = Vec::with_capacity(2 * notes.len());
+        for (index, (note, velocity, section)) in notes.iter().enumerate() {
+            let section = SampleSection::from_time_section(*section, self.sample_rate);
+            if section.length > Samples::from(0) {
+                events.push(TimedEvent {
+                    position: section.start,
+                    event: NoteEvent::On(index, *note, *velocity),
+                });
+                events.push(TimedEvent {
+                    position: section.end(),
+                    event: NoteEvent::Off(index),
+                });
+            }
+        }
+        // A note-off goes before a note-on at the same sample, so the voice of a note that
+        // ends right where the next one starts is released first.
+        events.sort_by_key(|event| {
+            (
+                event.position,
+                matches!(event.event, NoteEvent::On(_, _, _)),
+            )
+        });
+
+        let last_event = events
+            .last()
+            .map_or(Samples::from(0), |event| event.position);
+        let length = last_event + self.max_tail.to_samples(self.sample_rate);
+        let mut output = Buffer::allocate(self.num_channels, length);
+        let mut block = Buffer::allocate(self.num_channels, self.buffer_size.as_samples());
+        let mut voices: Vec<ActiveVoice<V>> = Vec::new();
+
+        let mut position = Samples::from(0);
+        while position < length && (position <= last_event || !voices.is_empty()) {
+            let section = SampleSection {
+                start: position,
+                length: self.buffer_size.as_samples().min(length - position),
+            };
+            split_block(section, &events, |part, events| {
+                for event in events {
+                    match event.event {
+                        NoteEvent::On(index, note, velocity) => {
+                            let mut voice = template.clone();
+                            voice.note_on(note, velocity);
+                            voices.push(ActiveVoice { index, voice });
+                        }
+                        NoteEvent::Off(index) => {
+                            if let Some(active) = voices.iter_mut().find(|v| v.index == index) {
+                                active.voice.note_off();
+                            }
+                        }
+                    }
+                }
+
+                let start = part.section.start.as_usize();
+                block.set_num_samples(part.section.length);
+                for active in voices.iter_mut() {
+                    active.voice.render(&mut block);
+                    for (to, from) in output.iter_chans_mut().zip(block.iter_chans()) {
+                        for (to, from) in to[start..].iter_mut().zip(from) {
+                            *to += *from;
+                        }
+                    }
+                }
+            });
+            voices.retain(|active| active.voice.is_active());
+            position = section.end();
+        }
+
+        output.clone_resized(self.num_channels, position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osc::Waveform;
+    use crate::units::{Duration, Frequency, NormalizedValue, TimePoint};
+
+    const SAMPLE_RATE: u32 = 1000;
+
+    /// Plays the velocity as a constant, and stops right away at the note-off.
+    #[derive(Clone, Debug, Default)]
+    struct Constant {
+        value: Option<f32>,
+    }
+
+    impl Voice for Constant {
+        fn prepare(&mut self, _: SampleRate, _: BufferSize, _: Channels) {}
+
+        fn note_on(&mut self, _: MidiNote, velocity: Velocity) {
+            self.value = Some(velocity.as_u8() as f32);
+        }
+
+        fn note_off(&mut self) {
+            self.value = None;
+        }
+
+        fn is_active(&self) -> bool {
+            self.value.is_some()
+        }
+
+        fn render(&mut self, buffer: &mut Buffer<f32>) {
+            buffer.map_samples(|_| self.value.unwrap_or_default());
+        }
+    }
+
+    fn note(velocity: u8, start: usize, length: usize) -> (MidiNote, Velocity, TimeSection) {
+        let seconds = |samples: usize| samples as f64 / SAMPLE_RATE as f64;
+        let section = TimeSection {
+            start: TimePoint::from_secs_f64(seconds(start)),
+            duration: Duration::from_secs_f64(seconds(length)),
+        };
+        (MidiNote::from(60), Velocity::from(velocity), section)
+    }
+
+    fn renderer() -> SequenceRenderer {
+        SequenceRenderer::new(
+            SampleRate::from(SAMPLE_RATE),
+            BufferSize::from(64),
+            Channels::from(1),
+        )
+    }
+
+    #[test]
+    fn notes_start_and_stop_at_their_samples() {
+        let audio = renderer().render(&[note(10, 100, 30)], &Constant::default());
+
+        let samples = audio.chan(0);
+        assert!(samples[..100].iter().all(|sample| *sample == 0.0));
+        assert!(samples[100..130].iter().all(|sample| *sample == 10.0));
+        assert!(samples[130..].iter().all(|sample| *sample == 0.0));
+        assert_eq!(audio.num_samples(), Samples::from(192));
+    }
+
+    #[test]
+    fn overlapping_notes_are_mixed() {
+        let audio = renderer().render(&[note(10, 0, 20), note(5, 10, 20)], &Constant::default());
+
+        let samples = audio.chan(0);
+        assert_eq!(samples[5], 10.0);
+        assert_eq!(samples[15], 15.0);
+        assert_eq!(samples[25], 5.0);
+        assert_eq!(samples[35], 0.0);
+    }
+
+    #[test]
+    fn notes_that_follow_each_other_get_their_own_voice() {
+        let audio = renderer().render(&[note(10, 0, 20), note(5, 20, 20)], &Constant::default());
+
+        let samples = audio.chan(0);
+        assert_eq!(samples[19], 10.0);
+        assert_eq!(samples[20], 5.0);
+        assert_eq!(samples[40], 0.0);
+    }
+
+    #[test]
+    fn releases_ring_after_the_last_note() {
+        let sample_rate = SampleRate::from(SAMPLE_RATE);
+        let voice = OscillatorVoice::new(
+            Oscillator::new(Waveform::Square, Frequency::from(1.0), sample_rate),
+            Adsr::new(
+                Seconds::from(0.0),
+                Seconds::from(0.0),
+                NormalizedValue::from(1.0),
+                Seconds::from(0.3),
+                sample_rate,
+            ),
+        );
+
+        let audio = renderer().render(&[note(127, 0, 100)], &voice);
+
+        // The release of 300 samples ends in the block of samples 384 to 448.
+        assert_eq!(audio.num_samples(), Samples::from(448));
+        assert!(audio.chan(0)[150].abs() > 0.1);
+        assert!(audio.chan(0)[400..].iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn max_tail_stops_voices_that_never_end() {
+        /// Never stops, even after the note-off.
+        #[derive(Clone)]
+        struct Drone;
+
+        impl Voice for Drone {
+            fn prepare(&mut self, _: SampleRate, _: BufferSize, _: Channels) {}
+            fn note_on(&mut self, _: MidiNote, _: Velocity) {}
+            fn note_off(&mut self) {}
+            fn is_active(&self) -> bool {
+                true
+            }
+            fn render(&mut self, buffer: &mut Buffer<f32>) {
+                buffer.map_samples(|_| 1.0);
+            }
+        }
+
+        let mut renderer = renderer();
+        renderer.set_max_tail(Seconds::from(0.5));
+
+        let audio = renderer.render(&[note(1, 0, 100)], &Drone);
+
+        assert_eq!(audio.num_samples(), Samples::from(600));
+    }
+
+    #[test]
+    fn processed_voice_runs_the_processor_after_the_voice() {
+        /// Halves the audio.
+        #[derive(Clone)]
+        struct Half;
+
+        impl AudioProcessor for Half {
+            fn prepare(&mut self, _: SampleRate, _: BufferSize, _: Channels) {}
+            fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+                buffer.map_samples(|sample| sample / 2.0);
+            }
+            fn reset(&mut self) {}
+        }
+
+        let voice = ProcessedVoice::new(Constant::default(), Half);
+
+        let audio = renderer().render(&[note(10, 0, 20)], &voice);
+
+        assert_eq!(audio.chan(0)[10], 5.0);
+    }
+}
@@ -0,0 +1,213 @@
+//! This module contains generators for the standard test signals used to measure and test audio
+//! processing: impulses, steps, sines, sweeps and noise. Every generator gives back a new buffer
+//! with the same signal (or, for noise, independent noise) on every channel, at full scale.
+//! ```rust
+//! use rabu::signals;
+//! use rabu::units::{Channels, Duration, Frequency, SampleRate, Samples};
+//!
+//! let sample_rate = SampleRate::from(48000);
+//!
+//! let impulse = signals::impulse::<f32>(Channels::from(1), Samples::from(1024));
+//! assert_eq!(impulse.chan(0)[0], 1.0);
+//!
+//! let sweep = signals::exponential_sweep::<f32>(
+//!     Channels::from(2),
+//!     Frequency::from(20.0),
+//!     Frequency::from(20000.0),
+//!     Duration::from_secs_f64(2.0),
+//!     sample_rate,
+//! );
+//! assert_eq!(sweep.num_samples(), Samples::from(96000));
+//! ```
+
+use std::f64::consts::PI;
+
+use crate::buffer::Buffer;
+use crate::noise::{NoiseColor, NoiseGenerator};
+use crate::sample::Sample;
+use crate::units::{Channels, Duration, Frequency, SampleRate, Samples};
+
+/// Creates a unit impulse: a single sample of 1 at the start, followed by silence.
+pub fn impulse<T: Sample>(num_channels: Channels, num_samples: Samples) -> Buffer<T> {
+    let mut buffer = Buffer::allocate(num_channels, num_samples);
+    if num_samples.as_usize() > 0 {
+        for channel in buffer.iter_chans_mut() {
+            channel[0] = T::from_f64(1.0);
+        }
+    }
+    buffer
+}
+
+/// Creates a DC step: every sample is 1.
+pub fn step<T: Sample>(num_channels: Channels, num_samples: Samples) -> Buffer<T> {
+    let mut buffer = Buffer::allocate(num_channels, num_samples);
+    buffer.map_samples(|_| T::from_f64(1.0));
+    buffer
+}
+
+/// Creates a sine at the given frequency, starting at phase 0.
+pub fn sine<T: Sample>(
+    num_channels: Channels,
+    num_samples: Samples,
+    frequency: Frequency,
+    sample_rate: SampleRate,
+) -> Buffer<T> {
+    let increment = 2.0 * PI * frequency.as_f64() / sample_rate.as_f64();
+    from_fn(num_channels, num_samples, |n| (increment * n as f64).sin())
+}
+
+/// Creates an exponential (logarithmic) sine sweep from the start to the end frequency, which
+/// spends the same time in every octave. This is the sweep that is used to measure impulse
+/// responses, as described by Farina.
+/// This will panic if one of the frequencies is not above 0.
+pub fn exponential_sweep<T: Sample>(
+    num_channels: Channels,
+    start: Frequency,
+    end: Frequency,
+    duration: Duration,
+    sample_rate: SampleRate,
+) -> Buffer<T> {
+    let (start, end) = (start.as_f64(), end.as_f64());
+    assert!(
+        start > 0.0 && end > 0.0,
+        "the frequencies of a sweep must be above 0"
+    );
+
+    let seconds = duration.as_secs_f64();
+    let sr = sample_rate.as_f64();
+    let octaves = (end / start).ln();
+    let phase = |t: f64| {
+        if octaves == 0.0 {
+            2.0 * PI * start * t
+        } else {
+            2.0 * PI * start * seconds / octaves * ((t * octaves / seconds).exp() - 1.0)
+        }
+    };
+
+    from_fn(num_channels, duration.to_samples(sample_rate), |n| {
+        phase(n as f64 / sr).sin()
+    })
+}
+
+/// Creates white noise between -1 and 1, with different noise on every channel.
+pub fn white_noise<T: Sample>(
+    num_channels: Channels,
+    num_samples: Samples,
+    seed: u64,
+) -> Buffer<T> {
+    noise(NoiseColor::White, num_channels, num_samples, seed)
+}
+
+/// Creates pink noise, with different noise on every channel.
+pub fn pink_noise<T: Sample>(num_channels: Channels, num_samples: Samples, seed: u64) -> Buffer<T> {
+    noise(NoiseColor::Pink, num_channels, num_samples, seed)
+}
+
+fn noise<T: Sample>(
+    color: NoiseColor,
+    num_channels: Channels,
+    num_samples: Samples,
+    seed: u64,
+) -> Buffer<T> {
+    let mut buffer = Buffer::allocate(num_channels, num_samples);
+    NoiseGenerator::new(color, seed).render(&mut buffer);
+    buffer
+}
+
+fn from_fn<T: Sample>(
+    num_channels: Channels,
+    num_samples: Samples,
+    signal: impl Fn(usize) -> f64,
+) -> Buffer<T> {
+    let mut buffer = Buffer::allocate(num_channels, num_samples);
+    for channel in buffer.iter_chans_mut() {
+        for (n, sample) in channel.iter_mut().enumerate() {
+            *sample = T::from_f64(signal(n));
+        }
+    }
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    /// Counts the upward zero crossings in the slice.
+    fn zero_crossings(samples: &[f64]) -> usize {
+        samples
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count()
+    }
+
+    #[test]
+    fn impulse_is_one_followed_by_silence() {
+        let buffer = impulse::<f32>(Channels::from(2), Samples::from(4));
+
+        for channel in buffer.iter_chans() {
+            assert_eq!(channel, &[1.0, 0.0, 0.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn empty_impulse_does_not_panic() {
+        let buffer = impulse::<f32>(Channels::from(1), Samples::from(0));
+        assert_eq!(buffer.num_samples(), Samples::from(0));
+    }
+
+    #[test]
+    fn step_is_all_ones() {
+        let buffer = step::<f64>(Channels::from(2), Samples::from(16));
+        assert!(buffer.data().iter().all(|s| *s == 1.0));
+    }
+
+    #[test_case(100.0; "100 Hz")]
+    #[test_case(1000.0; "1 kHz")]
+    fn sine_has_the_given_frequency(frequency: f64) {
+        let buffer = sine::<f64>(
+            Channels::from(1),
+            Samples::from(48001),
+            Frequency::from(frequency),
+            SampleRate::from(48000),
+        );
+        assert_eq!(buffer.chan(0)[0], 0.0);
+        // A crossing can land exactly on the last sample, so allow one off.
+        let crossings = zero_crossings(buffer.chan(0)) as f64;
+        assert!((crossings - frequency).abs() <= 1.0);
+    }
+
+    #[test]
+    fn sweep_spends_the_same_time_in_every_octave() {
+        let buffer = exponential_sweep::<f64>(
+            Channels::from(1),
+            Frequency::from(100.0),
+            Frequency::from(1600.0),
+            Duration::from_secs_f64(4.0),
+            SampleRate::from(48000),
+        );
+        assert_eq!(buffer.num_samples(), Samples::from(192000));
+
+        // Four octaves in four seconds: each second covers one octave, so the number of cycles
+        // doubles every second (100 Hz to 200 Hz is about 144 cycles).
+        let cycles: Vec<usize> = buffer.chan(0).chunks(48000).map(zero_crossings).collect();
+        for (octave, count) in cycles.iter().enumerate() {
+            let expected = 144.27 * 2.0_f64.powi(octave as i32);
+            assert!((*count as f64 - expected).abs() <= 2.0);
+        }
+    }
+
+    #[test]
+    fn noise_is_reproducible_and_differs_per_channel() {
+        let first = pink_noise::<f32>(Channels::from(2), Samples::from(256), 5);
+        let second = pink_noise::<f32>(Channels::from(2), Samples::from(256), 5);
+
+        assert_eq!(first.data(), second.data());
+        assert_ne!(first.chan(0), first.chan(1));
+        assert_ne!(
+            white_noise::<f32>(Channels::from(1), Samples::from(256), 5).chan(0),
+            first.chan(0)
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! This module contains a parameter smoother. Changing a gain, frequency or pan position in one
+//! step between two samples causes audible clicks ("zipper noise"), so a `Smoother` glides from
+//! the old value to the new one over a set time instead.
+//! ```rust
+//! use rabu::smoother::{Smoother, SmoothingMode};
+//! use rabu::units::{SampleRate, Seconds};
+//!
+//! let mut gain = Smoother::<f32>::new(
+//!     SmoothingMode::Linear,
+//!     Seconds::from(0.01),
+//!     SampleRate::from(48000),
+//!     1.0,
+//! );
+//!
+//! gain.set_target(0.0);
+//! let mut audio = vec![1.0_f32; 480];
+//! for sample in audio.iter_mut() {
+//!     *sample *= gain.next_sample();
+//! }
+//!
+//! assert_eq!(gain.current(), 0.0);
+//! assert!(!gain.is_smoothing());
+//! ```
+
+use std::marker::PhantomData;
+
+use crate::sample::Sample;
+use crate::units::{SampleRate, Samples, Seconds};
+
+/// How the smoother moves towards its target.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SmoothingMode {
+    /// Moves in equal steps, reaching the target after exactly the smoothing time.
+    Linear,
+    /// Moves with a one-pole filter, fast at first and slower near the target. After the
+    /// smoothing time it is within 60 dB of the target, and snaps to it.
+    Exponential,
+}
+
+/// Glides a parameter to new values over a set time.
+#[derive(Clone, Debug)]
+pub struct Smoother<T> {
+    mode: SmoothingMode,
+    length: usize,
+    /// The feedback coefficient of the exponential mode.
+    coefficient: f64,
+    current: f64,
+    target: f64,
+    /// The step of the linear mode.
+    step: f64,
+    /// The number of samples until the target is reached.
+    remaining: usize,
+    _sample: PhantomData<T>,
+}
+
+impl<T: Sample> Smoother<T> {
+    /// Creates a new smoother that sits at the given value.
+    pub fn new(mode: SmoothingMode, time: Seconds, sample_rate: SampleRate, value: T) -> Self {
+        let mut smoother = Self {
+            mode,
+            length: 0,
+            coefficient: 0.0,
+            current: value.to_f64(),
+            target: value.to_f64(),
+            step: 0.0,
+            remaining: 0,
+            _sample: PhantomData,
+        };
+        smoother.set_time(time, sample_rate);
+        smoother
+    }
+
+    /// Changes the smoothing time, which applies from the next target on.
+    pub fn set_time(&mut self, time: Seconds, sample_rate: SampleRate) {
+        self.length = time.to_samples(sample_rate).as_usize();
+        self.coefficient = if self.length == 0 {
+            0.0
+        } else {
+            // Shrinks the distance to the target by 60 dB over the smoothing time.
+            (0.001_f64.ln() / self.length as f64).exp()
+        };
+    }
+
+    /// Returns the value the smoother is moving to.
+    pub fn target(&self) -> T {
+        T::from_f64(self.target)
+    }
+
+    /// Starts gliding from the current value to the given target.
+    pub fn set_target(&mut self, target: T) {
+        self.target = target.to_f64();
+        self.remaining = self.length;
+        if self.remaining == 0 {
+            self.current = self.target;
+        }
+        self.step = (self.target - self.current) / self.length.max(1) as f64;
+    }
+
+    /// Returns the current value.
+    pub fn current(&self) -> T {
+        T::from_f64(self.current)
+    }
+
+    /// Returns whether the smoother hasn't reached its target yet.
+    pub fn is_smoothing(&self) -> bool {
+        self.remaining > 0
+    }
+
+    /// Jumps to the given value without smoothing.
+    pub fn reset(&mut self, value: T) {
+        self.current = value.to_f64();
+        self.target = self.current;
+        self.remaining = 0;
+    }
+
+    /// Produces the next value.
+    pub fn next_sample(&mut self) -> T {
+        self.skip(Samples::from(1));
+        self.current()
+    }
+
+    /// Advances the smoother as many samples as given, as if the values were produced but not
+    /// used. This is cheap, so blocks in which a parameter isn't needed can be skipped.
+    pub fn skip(&mut self, num_samples: Samples) {
+        if self.remaining == 0 {
+            return;
+        }
+        let steps = num_samples.as_usize().min(self.remaining);
+        self.remaining -= steps;
+
+        if self.remaining == 0 {
+            self.current = self.target;
+            return;
+        }
+        match self.mode {
+            SmoothingMode::Linear => self.current += self.step * steps as f64,
+            SmoothingMode::Exponential => {
+                let decay = self.coefficient.powi(steps as i32);
+                self.current = self.target + (self.current - self.target) * decay;
+            }
+        }
+    }
+
+    /// Fills the given slice with the next values.
+    pub fn render_into(&mut self, output: &mut [T]) {
+        for sample in output.iter_mut() {
+            *sample = self.next_sample();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn run(smoother: &mut Smoother<f64>, length: usize) -> Vec<f64> {
+        (0..length).map(|_| smoother.next_sample()).collect()
+    }
+
+    fn smoother(mode: SmoothingMode, samples: u32) -> Smoother<f64> {
+        // At 1 kHz, milliseconds are samples.
+        Smoother::new(
+            mode,
+            Seconds::from(samples as f64 / 1000.0),
+            SampleRate::from(1000),
+            0.0,
+        )
+    }
+
+    #[test]
+    fn linear_ramps_in_equal_steps() {
+        let mut smoother = smoother(SmoothingMode::Linear, 4);
+        smoother.set_target(1.0);
+
+        let values = run(&mut smoother, 5);
+
+        assert_eq!(values, vec![0.25, 0.5, 0.75, 1.0, 1.0]);
+        assert!(!smoother.is_smoothing());
+    }
+
+    #[test]
+    fn exponential_is_within_60_db_before_it_snaps() {
+        let mut smoother = smoother(SmoothingMode::Exponential, 100);
+        smoother.set_target(1.0);
+
+        let values = run(&mut smoother, 100);
+
+        assert!(values.windows(2).all(|pair| pair[1] > pair[0]));
+        assert!(values[49] > 0.96 && values[49] < 0.97);
+        assert!(1.0 - values[98] < 0.0011);
+        assert_eq!(values[99], 1.0);
+    }
+
+    #[test_case(SmoothingMode::Linear; "linear")]
+    #[test_case(SmoothingMode::Exponential; "exponential")]
+    fn skipping_matches_producing_values(mode: SmoothingMode) {
+        let mut produced = smoother(mode, 100);
+        let mut skipped = produced.clone();
+        produced.set_target(2.0);
+        skipped.set_target(2.0);
+
+        run(&mut produced, 30);
+        skipped.skip(Samples::from(30));
+
+        assert!((produced.current() - skipped.current()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn new_target_glides_from_the_current_value() {
+        let mut smoother = smoother(SmoothingMode::Linear, 4);
+        smoother.set_target(1.0);
+        smoother.skip(Samples::from(2));
+
+        smoother.set_target(0.0);
+
+        assert_eq!(smoother.next_sample(), 0.375);
+    }
+
+    #[test]
+    fn zero_time_jumps_to_the_target() {
+        let mut smoother = smoother(SmoothingMode::Exponential, 0);
+        smoother.set_target(3.0);
+
+        assert_eq!(smoother.current(), 3.0);
+        assert!(!smoother.is_smoothing());
+    }
+}
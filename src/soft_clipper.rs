@@ -0,0 +1,291 @@
+//! This module contains a soft clipper, the safe output stage of a synth or a mixer: it rounds
+//! off everything that goes near the ceiling, instead of letting it clip hard further down the
+//! line. Rounding off creates harmonics, so the clipper runs oversampled with halfband filters
+//! to keep them from aliasing, and reports the delay of the filters as a `Latency`.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::soft_clipper::{ClipCurve, SoftClipper};
+//! use rabu::units::{Channels, Decibels, SampleRate, Samples};
+//!
+//! let mut clipper = SoftClipper::new(SampleRate::from(48000), Channels::from(2));
+//! clipper.set_curve(ClipCurve::Sine);
+//! clipper.set_ceiling(Decibels::from(-1.0));
+//! clipper.set_oversampling(4);
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! buffer.map_samples(|_| 2.0);
+//! clipper.process(&mut buffer);
+//!
+//! assert_eq!(clipper.latency(), Samples::from(55).to_seconds(SampleRate::from(48000)).into());
+//! ```
+
+use crate::buffer::Buffer;
+use crate::oversampling::HalfbandOversampler;
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::sample::Sample;
+use crate::units::{BufferSize, Channels, Decibels, Latency, SampleRate, Samples};
+
+/// The shape of the clipper, relative to the ceiling, which every curve reaches at 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClipCurve {
+    /// The hyperbolic tangent, which bends the soonest, but never quite reaches the ceiling.
+    Tanh,
+    /// A cubic curve, which reaches the ceiling at 1 without a bend.
+    Cubic,
+    /// A quarter of a sine, which reaches the ceiling at 1 without a bend.
+    Sine,
+    /// Cuts everything above the ceiling, which keeps the audio below it untouched, but
+    /// creates the most harmonics.
+    Hard,
+}
+
+impl ClipCurve {
+    /// Returns the output of the curve for the given input.
+    pub fn apply(&self, input: f64) -> f64 {
+        match self {
+            ClipCurve::Tanh => input.tanh(),
+            ClipCurve::Cubic => {
+                let x = input.clamp(-1.0, 1.0);
+                1.5 * (x - x * x * x / 3.0)
+            }
+            ClipCurve::Sine => (std::f64::consts::FRAC_PI_2 * input.clamp(-1.0, 1.0)).sin(),
+            ClipCurve::Hard => input.clamp(-1.0, 1.0),
+        }
+    }
+}
+
+/// Soft clips audio below a ceiling, oversampled.
+#[derive(Clone, Debug)]
+pub struct SoftClipper {
+    curve: ClipCurve,
+    ceiling: Decibels,
+    sample_rate: SampleRate,
+    /// An oversampler for every channel.
+    oversamplers: Vec<HalfbandOversampler>,
+}
+
+impl SoftClipper {
+    /// Creates a new soft clipper with a tanh curve, a ceiling of 0 dB and 2x oversampling.
+    pub fn new(sample_rate: SampleRate, num_channels: Channels) -> Self {
+        Self {
+            curve: ClipCurve::Tanh,
+            ceiling: Decibels::from(0.0),
+            sample_rate,
+            oversamplers: vec![HalfbandOversampler::new(2); num_channels.as_usize()],
+        }
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.oversamplers.len())
+    }
+
+    /// Returns the curve.
+    pub fn curve(&self) -> ClipCurve {
+        self.curve
+    }
+
+    /// Changes the curve.
+    pub fn set_curve(&mut self, curve: ClipCurve) {
+        self.curve = curve;
+    }
+
+    /// Returns the ceiling.
+    pub fn ceiling(&self) -> Decibels {
+        self.ceiling
+    }
+
+    /// Changes the level the curves flatten out at. The filters of the oversampling can ring
+    /// a little above it, so leave some headroom when the output must not go over it.
+    pub fn set_ceiling(&mut self, ceiling: Decibels) {
+        self.ceiling = ceiling;
+    }
+
+    /// Returns the oversampling factor.
+    pub fn oversampling(&self) -> usize {
+        self.oversamplers
+            .first()
+            .map_or(1, HalfbandOversampler::factor)
+    }
+
+    /// Changes the oversampling factor to 1, 2, 4 or 8, where 1 turns oversampling off. This
+    /// clears the internal state, and changes the latency.
+    /// This will panic if the factor is anything else.
+    pub fn set_oversampling(&mut self, factor: usize) {
+        let oversampler = HalfbandOversampler::new(factor);
+        self.oversamplers.fill(oversampler);
+    }
+
+    /// Returns the delay caused by oversampling.
+    pub fn latency(&self) -> Latency {
+        let latency = self
+            .oversamplers
+            .first()
+            .map_or(Samples::from(0), HalfbandOversampler::latency_samples);
+        Latency::from(latency.to_seconds(self.sample_rate))
+    }
+
+    /// Changes the sample rate, which only changes the latency in seconds.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Clears the internal state, as if no audio was processed yet.
+    pub fn reset(&mut self) {
+        self.oversamplers
+            .iter_mut()
+            .for_each(HalfbandOversampler::reset);
+    }
+
+    /// Clips the buffer in place.
+    /// This will panic if the buffer doesn't have the same number of channels as the clipper.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+        let curve = self.curve;
+        let ceiling = self.ceiling.to_gain();
+        for (samples, oversampler) in buffer.iter_chans_mut().zip(&mut self.oversamplers) {
+            for sample in samples.iter_mut() {
+                let clipped = oversampler.process(sample.to_f64(), |sample| {
+                    ceiling * curve.apply(sample / ceiling)
+                });
+                *sample = T::from_f64(clipped);
+            }
+        }
+    }
+}
+
+impl AudioProcessor for SoftClipper {
+    fn prepare(&mut self, sample_rate: SampleRate, _: BufferSize, num_channels: Channels) {
+        self.set_sample_rate(sample_rate);
+        let oversampler = HalfbandOversampler::new(self.oversampling());
+        self.oversamplers = vec![oversampler; num_channels.as_usize()];
+    }
+
+    fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+        SoftClipper::process(self, buffer);
+    }
+
+    fn latency(&self) -> Latency {
+        SoftClipper::latency(self)
+    }
+
+    fn reset(&mut self) {
+        SoftClipper::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::fft::{Complex, RealFft};
+
+    #[test_case(ClipCurve::Tanh, 30.0 => 1.0; "tanh saturates")]
+    #[test_case(ClipCurve::Cubic, 1.0 => 1.0; "cubic reaches 1")]
+    #[test_case(ClipCurve::Cubic, -3.0 => -1.0; "cubic stays at 1")]
+    #[test_case(ClipCurve::Sine, 1.0 => 1.0; "sine reaches 1")]
+    #[test_case(ClipCurve::Sine, 3.0 => 1.0; "sine stays at 1")]
+    #[test_case(ClipCurve::Hard, 0.5 => 0.5; "hard is linear below 1")]
+    fn curves(curve: ClipCurve, input: f64) -> f64 {
+        (curve.apply(input) * 1e9).round() / 1e9
+    }
+
+    fn sine(amplitude: f64, w: f64, length: usize) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(length));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = amplitude * (w * n as f64).sin();
+        }
+        buffer
+    }
+
+    #[test_case(ClipCurve::Tanh)]
+    #[test_case(ClipCurve::Cubic)]
+    #[test_case(ClipCurve::Sine)]
+    #[test_case(ClipCurve::Hard)]
+    fn keeps_loud_audio_around_the_ceiling(curve: ClipCurve) {
+        let mut clipper = SoftClipper::new(SampleRate::from(48000), Channels::from(1));
+        clipper.set_curve(curve);
+        clipper.set_ceiling(Decibels::from(-6.0));
+        let mut buffer = sine(4.0, 0.05, 4000);
+
+        clipper.process(&mut buffer);
+
+        let peak = buffer.chan(0).iter().fold(0.0, |peak, s| s.abs().max(peak));
+        let ceiling = Decibels::from(-6.0).to_gain();
+        assert!(peak < ceiling * 1.1 && peak > ceiling * 0.9, "{peak}");
+    }
+
+    #[test]
+    fn quiet_audio_only_gets_delayed_by_the_hard_curve() {
+        let mut clipper = SoftClipper::new(SampleRate::from(48000), Channels::from(1));
+        clipper.set_curve(ClipCurve::Hard);
+        clipper.set_oversampling(4);
+        let latency = 55;
+        let w = 0.1;
+        let mut buffer = sine(0.5, w, 1000);
+
+        clipper.process(&mut buffer);
+
+        for (n, sample) in buffer.chan(0).iter().enumerate().skip(200) {
+            assert!((sample - 0.5 * (w * (n - latency) as f64).sin()).abs() < 1e-3);
+        }
+    }
+
+    /// Returns the part of the energy that ends up in between the harmonics of a clipped sine,
+    /// which is where aliasing lands.
+    fn aliasing(oversampling: usize) -> f64 {
+        let harmonic_spacing = 233;
+        let w = 2.0 * std::f64::consts::PI * harmonic_spacing as f64 / 4096.0;
+        let mut clipper = SoftClipper::new(SampleRate::from(48000), Channels::from(1));
+        clipper.set_curve(ClipCurve::Cubic);
+        clipper.set_oversampling(oversampling);
+        let mut buffer = sine(4.0, w, 8192);
+
+        clipper.process(&mut buffer);
+
+        let mut bins = vec![Complex::default(); 2049];
+        RealFft::new(Samples::from(4096)).forward(&buffer.chan(0)[4096..], &mut bins);
+        let (aliased, total) =
+            bins.iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(aliased, total), (index, bin)| {
+                    let is_harmonic = index % harmonic_spacing == 0;
+                    let energy = bin.norm_sqr();
+                    (
+                        aliased + if is_harmonic { 0.0 } else { energy },
+                        total + energy,
+                    )
+                });
+        aliased / total
+    }
+
+    #[test]
+    fn oversampling_reduces_aliasing() {
+        let plain = aliasing(1);
+        let twice = aliasing(2);
+        let four_times = aliasing(4);
+
+        assert!(twice < plain * 0.1, "{twice} vs {plain}");
+        assert!(four_times < twice, "{four_times} vs {twice}");
+    }
+
+    #[test]
+    fn prepare_keeps_the_oversampling_and_reports_the_latency() {
+        let mut clipper = SoftClipper::new(SampleRate::from(48000), Channels::from(1));
+        clipper.set_oversampling(4);
+
+        clipper.prepare(
+            SampleRate::from(44100),
+            BufferSize::from(256),
+            Channels::from(2),
+        );
+
+        assert_eq!(clipper.num_channels(), Channels::from(2));
+        assert_eq!(clipper.oversampling(), 4);
+        assert_eq!(
+            AudioProcessor::latency(&clipper),
+            Latency::from(Samples::from(55).to_seconds(SampleRate::from(44100)))
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! This module contains a spectrum analyzer for displays and metering. It collects incoming
+//! audio into overlapping windowed frames, measures the magnitude of every bin, and keeps both a
+//! smoothed and a peak-hold version of the spectrum. Levels are corrected for the window, so a
+//! full scale sine wave reads as 0 dB.
+//! ```rust
+//! use rabu::spectrum::SpectrumAnalyzer;
+//! use rabu::units::{Frequency, SampleRate, Samples};
+//! use rabu::windows::Window;
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let mut analyzer = SpectrumAnalyzer::new(sample_rate, Samples::from(2048), Window::Hann);
+//!
+//! let sine: Vec<f32> = (0..48000)
+//!     .map(|n| (2.0 * std::f32::consts::PI * 1500.0 * n as f32 / 48000.0).sin())
+//!     .collect();
+//! analyzer.process(&sine);
+//!
+//! // 64 points between 20 Hz and 20 kHz, spaced evenly on a logarithmic axis.
+//! let display = analyzer.log_spectrum(Frequency::from(20.0), Frequency::from(20000.0), 64);
+//! let (loudest, _) = display
+//!     .iter()
+//!     .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+//!     .unwrap();
+//! assert!((loudest.as_f64() - 1500.0).abs() < 100.0);
+//! ```
+
+use crate::fft::{Complex, RealFft};
+use crate::sample::Sample;
+use crate::units::{Decibels, Frequency, FrequencyBin, SampleRate, Samples};
+use crate::windows::Window;
+
+/// Measures the magnitude spectrum of a mono signal, with optional smoothing and peak hold.
+/// Processing doesn't allocate, so it can be fed from the audio thread.
+#[derive(Clone, Debug)]
+pub struct SpectrumAnalyzer {
+    sample_rate: SampleRate,
+    fft: RealFft,
+    window: Vec<f64>,
+    magnitude_scale: f64,
+    hop_size: usize,
+    history: Vec<f64>,
+    write_position: usize,
+    samples_until_frame: usize,
+    frame: Vec<f64>,
+    bins: Vec<Complex>,
+    smoothing: f64,
+    averaged: Vec<f64>,
+    peaks: Vec<f64>,
+}
+
+impl SpectrumAnalyzer {
+    /// The level in dB that is reported for bins without any energy.
+    pub const FLOOR_DB: f64 = -200.0;
+
+    /// Creates a new analyzer with the given FFT size and window. A new frame is analyzed every
+    /// half FFT size.
+    /// This will panic if the FFT size is not a power of two.
+    pub fn new(sample_rate: SampleRate, fft_size: Samples, window: Window) -> Self {
+        let size = fft_size.as_usize();
+        let fft = RealFft::new(fft_size);
+        let num_bins = fft.num_bins().as_usize();
+        let mut window_values = vec![0.0; size];
+        window.fill_periodic(&mut window_values);
+
+        Self {
+            sample_rate,
+            fft,
+            window: window_values,
+            magnitude_scale: 2.0 / (size as f64 * window.coherent_gain(size)),
+            hop_size: (size / 2).max(1),
+            history: vec![0.0; size],
+            write_position: 0,
+            samples_until_frame: size,
+            frame: vec![0.0; size],
+            bins: vec![Complex::default(); num_bins],
+            smoothing: 0.0,
+            averaged: vec![0.0; num_bins],
+            peaks: vec![0.0; num_bins],
+        }
+    }
+
+    /// Returns the size of the FFT.
+    pub fn fft_size(&self) -> Samples {
+        self.fft.size()
+    }
+
+    /// Returns the number of bins in the spectrum.
+    pub fn num_bins(&self) -> Samples {
+        self.fft.num_bins()
+    }
+
+    /// Sets how much of the previous spectrum is kept when a new frame is analyzed, between 0
+    /// (no smoothing) and 1 (frozen). Values around 0.8 give a calm display.
+    /// This will panic if the smoothing is outside that range.
+    pub fn set_smoothing(&mut self, smoothing: f64) {
+        assert!((0.0..=1.0).contains(&smoothing));
+        self.smoothing = smoothing;
+    }
+
+    /// Returns the current smoothing factor.
+    pub fn smoothing(&self) -> f64 {
+        self.smoothing
+    }
+
+    /// Feeds the given samples to the analyzer. Every time enough new samples came in, a new
+    /// frame is analyzed and the smoothed and peak spectra are updated.
+    pub fn process<T: Sample>(&mut self, input: &[T]) {
+        let size = self.history.len();
+        for sample in input {
+            self.history[self.write_position] = sample.to_f64();
+            self.write_position = (self.write_position + 1) % size;
+            self.samples_until_frame -= 1;
+
+            if self.samples_until_frame == 0 {
+                self.samples_until_frame = self.hop_size;
+                self.analyze_frame();
+            }
+        }
+    }
+
+    /// Returns the frequency and smoothed level of every bin, from DC up to Nyquist.
+    pub fn spectrum(&self) -> impl Iterator<Item = (Frequency, Decibels)> + '_ {
+        self.with_frequencies(&self.averaged)
+    }
+
+    /// Returns the frequency and highest level seen so far of every bin, from DC up to Nyquist.
+    pub fn peaks(&self) -> impl Iterator<Item = (Frequency, Decibels)> + '_ {
+        self.with_frequencies(&self.peaks)
+    }
+
+    /// Returns the smoothed spectrum at `num_points` frequencies that are evenly spaced on a
+    /// logarithmic axis between `lowest` and `highest` (inclusive), which is what most displays
+    /// draw. Every point shows the loudest bin within its share of the axis, or an interpolated
+    /// level where the bins are further apart than the points.
+    /// This will panic if fewer than two points are requested.
+    pub fn log_spectrum(
+        &self,
+        lowest: Frequency,
+        highest: Frequency,
+        num_points: usize,
+    ) -> Vec<(Frequency, Decibels)> {
+        assert!(num_points >= 2);
+        let ratio = (highest.as_f64() / lowest.as_f64()).powf(1.0 / (num_points - 1) as f64);
+        let half_step = ratio.sqrt();
+        let bin_width = self.sample_rate.as_f64() / self.fft_size().as_f64();
+        let last_bin = self.averaged.len() - 1;
+
+        (0..num_points)
+            .map(|point| {
+                let center = lowest.as_f64() * ratio.powi(point as i32);
+                let first = ((center / half_step) / bin_width).ceil() as usize;
+                let last = ((center * half_step) / bin_width).floor() as usize;
+
+                let magnitude = if first <= last && first <= last_bin {
+                    self.averaged[first..=last.min(last_bin)]
+                        .iter()
+                        .fold(0.0, |loudest: f64, m| loudest.max(*m))
+                } else {
+                    let position = (center / bin_width).min(last_bin as f64);
+                    let below = position.floor() as usize;
+                    let above = (below + 1).min(last_bin);
+                    let fraction = position - below as f64;
+                    self.averaged[below] * (1.0 - fraction) + self.averaged[above] * fraction
+                };
+
+                (Frequency::from(center), Self::level(magnitude))
+            })
+            .collect()
+    }
+
+    /// Forgets the highest levels, so peak hold starts over.
+    pub fn reset_peaks(&mut self) {
+        self.peaks.fill(0.0);
+    }
+
+    /// Clears all collected audio and measurements.
+    pub fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.write_position = 0;
+        self.samples_until_frame = self.history.len();
+        self.averaged.fill(0.0);
+        self.reset_peaks();
+    }
+
+    fn analyze_frame(&mut self) {
+        // The oldest sample in the history is the one that will be overwritten next.
+        let (newest, oldest) = self.history.split_at(self.write_position);
+        let samples = oldest.iter().chain(newest);
+        for ((value, sample), w) in self.frame.iter_mut().zip(samples).zip(&self.window) {
+            *value = sample * w;
+        }
+        self.fft.forward(&self.frame, &mut self.bins);
+
+        let last_bin = self.bins.len() - 1;
+        for (index, bin) in self.bins.iter().enumerate() {
+            // DC and Nyquist have no mirrored half, so they don't get the factor two.
+            let scale = if index == 0 || index == last_bin {
+                self.magnitude_scale / 2.0
+            } else {
+                self.magnitude_scale
+            };
+            let magnitude = bin.norm() * scale;
+
+            let averaged = &mut self.averaged[index];
+            *averaged = self.smoothing * *averaged + (1.0 - self.smoothing) * magnitude;
+            self.peaks[index] = self.peaks[index].max(magnitude);
+        }
+    }
+
+    fn with_frequencies<'a>(
+        &'a self,
+        magnitudes: &'a [f64],
+    ) -> impl Iterator<Item = (Frequency, Decibels)> + 'a {
+        let fft_size = self.fft_size();
+        magnitudes
+            .iter()
+            .enumerate()
+            .map(move |(index, magnitude)| {
+                let frequency = FrequencyBin::from(index).to_frequency(self.sample_rate, fft_size);
+                (frequency, Self::level(*magnitude))
+            })
+    }
+
+    fn level(magnitude: f64) -> Decibels {
+        let level = if magnitude > 0.0 {
+            Decibels::from_gain(magnitude).as_f64()
+        } else {
+            Self::FLOOR_DB
+        };
+        Decibels::from(level.max(Self::FLOOR_DB))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn analyze_sine(amplitude: f64, frequency: f64, window: Window) -> SpectrumAnalyzer {
+        let mut analyzer =
+            SpectrumAnalyzer::new(SampleRate::from(48000), Samples::from(1024), window);
+        let sine: Vec<f64> = (0..4096)
+            .map(|n| {
+                amplitude * (2.0 * std::f64::consts::PI * frequency * n as f64 / 48000.0).sin()
+            })
+            .collect();
+        analyzer.process(&sine);
+        analyzer
+    }
+
+    fn loudest(spectrum: impl Iterator<Item = (Frequency, Decibels)>) -> (Frequency, Decibels) {
+        spectrum
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .unwrap()
+    }
+
+    #[test_case(1.0, Window::Hann => 0.0; "full scale hann")]
+    #[test_case(0.5, Window::Hann => -6.0; "half scale hann")]
+    #[test_case(1.0, Window::BlackmanHarris => 0.0; "full scale blackman harris")]
+    #[test_case(1.0, Window::Rectangular => 0.0; "full scale rectangular")]
+    fn sine_on_a_bin_reads_its_amplitude(amplitude: f64, window: Window) -> f64 {
+        // Bin 32 of a 1024 point FFT at 48 kHz.
+        let analyzer = analyze_sine(amplitude, 1500.0, window);
+        let (frequency, level) = loudest(analyzer.spectrum());
+
+        assert_eq!(frequency, Frequency::from(1500.0));
+        level.as_f64().round()
+    }
+
+    #[test]
+    fn peaks_are_held_after_the_signal_stops() {
+        let mut analyzer = analyze_sine(1.0, 1500.0, Window::Hann);
+        analyzer.process(&[0.0_f32; 4096]);
+
+        assert_eq!(
+            loudest(analyzer.spectrum()).1,
+            Decibels::from(SpectrumAnalyzer::FLOOR_DB)
+        );
+        assert_eq!(loudest(analyzer.peaks()).0, Frequency::from(1500.0));
+
+        analyzer.reset_peaks();
+        assert_eq!(
+            loudest(analyzer.peaks()).1,
+            Decibels::from(SpectrumAnalyzer::FLOOR_DB)
+        );
+    }
+
+    #[test]
+    fn smoothing_slows_down_decay() {
+        let mut analyzer = analyze_sine(1.0, 1500.0, Window::Hann);
+        analyzer.set_smoothing(0.5);
+        analyzer.process(&[0.0_f32; 1024]);
+
+        let (_, level) = loudest(analyzer.spectrum());
+        assert!(level.as_f64() < -3.0 && level.as_f64() > -30.0);
+    }
+
+    #[test]
+    fn log_spectrum_spans_the_requested_range() {
+        let analyzer = analyze_sine(1.0, 1500.0, Window::Hann);
+        let display = analyzer.log_spectrum(Frequency::from(20.0), Frequency::from(20000.0), 31);
+
+        assert_eq!(display.len(), 31);
+        assert!((display[0].0.as_f64() - 20.0).abs() < 1e-9);
+        assert!((display[30].0.as_f64() - 20000.0).abs() < 1e-6);
+
+        let (frequency, level) = loudest(display.into_iter());
+        assert!((frequency.as_f64() - 1500.0).abs() < 200.0);
+        assert!(level.as_f64() > -1.0);
+    }
+}
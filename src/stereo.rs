@@ -0,0 +1,294 @@
+//! This module contains tools for working with stereo audio as mid (what both channels have in
+//! common) and side (where they differ). Turning the side up makes a mix wider and turning it
+//! down makes it narrower, until it's mono. Too much side makes the channels cancel each other
+//! when the mix is played back in mono, which the correlation shows: 1 is mono, 0 is unrelated
+//! and below 0 cancels.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::stereo::{correlation, StereoWidth};
+//! use rabu::units::{Channels, Frequency, SampleRate, Samples};
+//!
+//! let mut width = StereoWidth::new(SampleRate::from(48000));
+//! width.set_width(1.5);
+//! width.set_mono_below(Some(Frequency::from(120.0)));
+//! width.set_mono_safe(true);
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! width.process(&mut buffer);
+//!
+//! assert!(width.correlation() >= 0.0);
+//! assert_eq!(correlation(&buffer), width.correlation());
+//! ```
+
+use crate::biquad::{high_pass_coefficients, BiquadFilter};
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::smoother::{Smoother, SmoothingMode};
+use crate::units::{Frequency, SampleRate, Seconds};
+
+/// Returns the mid and side of a left and right sample.
+pub fn to_mid_side(left: f64, right: f64) -> (f64, f64) {
+    ((left + right) / 2.0, (left - right) / 2.0)
+}
+
+/// Returns the left and right of a mid and side sample, the inverse of `to_mid_side`.
+pub fn to_left_right(mid: f64, side: f64) -> (f64, f64) {
+    (mid + side, mid - side)
+}
+
+/// Returns the correlation between the two channels of the buffer, from -1 (opposite) through
+/// 0 (unrelated) to 1 (the same, apart from the level). Silence has a correlation of 0.
+/// This will panic if the buffer isn't stereo.
+pub fn correlation<T: Sample>(buffer: &Buffer<T>) -> f64 {
+    assert_eq!(
+        buffer.num_channels().as_usize(),
+        2,
+        "the buffer must be stereo"
+    );
+    let (product, left_energy, right_energy) = buffer.chan(0).iter().zip(buffer.chan(1)).fold(
+        (0.0, 0.0, 0.0),
+        |(product, left_energy, right_energy), (left, right)| {
+            let (left, right) = (left.to_f64(), right.to_f64());
+            (
+                product + left * right,
+                left_energy + left * left,
+                right_energy + right * right,
+            )
+        },
+    );
+    let energy = (left_energy * right_energy).sqrt();
+    if energy == 0.0 {
+        0.0
+    } else {
+        (product / energy).clamp(-1.0, 1.0)
+    }
+}
+
+/// Makes stereo audio narrower or wider by scaling the side.
+#[derive(Clone, Debug)]
+pub struct StereoWidth {
+    sample_rate: SampleRate,
+    width: f64,
+    mono_below: Option<Frequency>,
+    side_filter: BiquadFilter,
+    mono_safe: bool,
+    side_gain: Smoother<f64>,
+    correlation: f64,
+}
+
+impl StereoWidth {
+    /// Creates a new width processor that leaves the audio as it is.
+    pub fn new(sample_rate: SampleRate) -> Self {
+        Self {
+            sample_rate,
+            width: 1.0,
+            mono_below: None,
+            side_filter: BiquadFilter::new(high_pass_coefficients(
+                sample_rate,
+                Frequency::from(20.0),
+            )),
+            mono_safe: false,
+            side_gain: Smoother::new(SmoothingMode::Linear, Seconds::from(0.01), sample_rate, 1.0),
+            correlation: 0.0,
+        }
+    }
+
+    /// Returns the width.
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+
+    /// Changes the width: 0 is mono, 1 leaves the audio as it is and above 1 widens it. The
+    /// width is kept at 0 or more.
+    pub fn set_width(&mut self, width: f64) {
+        self.width = width.max(0.0);
+    }
+
+    /// Returns the frequency below which the audio is made mono.
+    pub fn mono_below(&self) -> Option<Frequency> {
+        self.mono_below
+    }
+
+    /// Makes the audio below the frequency mono, by filtering the low end out of the side.
+    /// Widening the bass mostly makes it cancel on mono playback, and doesn't sound wider.
+    pub fn set_mono_below(&mut self, frequency: Option<Frequency>) {
+        self.mono_below = frequency;
+        if let Some(frequency) = frequency {
+            self.side_filter
+                .set_coefficients(high_pass_coefficients(self.sample_rate, frequency));
+        }
+    }
+
+    /// Tells whether the width is limited to keep the mix mono compatible.
+    pub fn is_mono_safe(&self) -> bool {
+        self.mono_safe
+    }
+
+    /// When enabled, the width is turned down where needed to keep the correlation of every
+    /// block at 0 or above, so the channels never cancel on mono playback.
+    pub fn set_mono_safe(&mut self, mono_safe: bool) {
+        self.mono_safe = mono_safe;
+    }
+
+    /// Changes the sample rate.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = sample_rate;
+        self.side_gain.set_time(Seconds::from(0.01), sample_rate);
+        self.set_mono_below(self.mono_below);
+    }
+
+    /// Returns the correlation of the last processed block.
+    pub fn correlation(&self) -> f64 {
+        self.correlation
+    }
+
+    /// Clears the internal state, as if no audio was processed yet.
+    pub fn reset(&mut self) {
+        self.side_filter.reset();
+        self.side_gain.reset(self.width);
+        self.correlation = 0.0;
+    }
+
+    /// Changes the width of the buffer.
+    /// This will panic if the buffer isn't stereo.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        assert_eq!(
+            buffer.num_channels().as_usize(),
+            2,
+            "the buffer must be stereo"
+        );
+        let mut mid_energy = 0.0;
+        let mut side_energy = 0.0;
+
+        // First the buffer is turned into mid and side, with the side filtered.
+        for index in buffer.sample_indices() {
+            let (mid, mut side) = to_mid_side(
+                buffer.chan(0)[index].to_f64(),
+                buffer.chan(1)[index].to_f64(),
+            );
+            if self.mono_below.is_some() {
+                side = self.side_filter.process(side);
+            }
+            mid_energy += mid * mid;
+            side_energy += side * side;
+            buffer.chan_mut(0)[index] = T::from_f64(mid);
+            buffer.chan_mut(1)[index] = T::from_f64(side);
+        }
+
+        // Left times right sums to the mid energy minus the scaled side energy, so the
+        // correlation stays at 0 or above as long as the side gain is at most this.
+        let mut side_gain = self.width;
+        if self.mono_safe && side_energy > 0.0 {
+            side_gain = side_gain.min((mid_energy / side_energy).sqrt());
+        }
+        self.side_gain.set_target(side_gain);
+
+        for index in buffer.sample_indices() {
+            let mid = buffer.chan(0)[index].to_f64();
+            let side = buffer.chan(1)[index].to_f64() * self.side_gain.next_sample();
+            let (left, right) = to_left_right(mid, side);
+            buffer.chan_mut(0)[index] = T::from_f64(left);
+            buffer.chan_mut(1)[index] = T::from_f64(right);
+        }
+        self.correlation = correlation(buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::{Channels, Samples};
+
+    fn stereo(left: impl Fn(f64) -> f64, right: impl Fn(f64) -> f64) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(2), Samples::from(4800));
+        for n in buffer.sample_indices() {
+            buffer.chan_mut(0)[n] = left(n as f64);
+            buffer.chan_mut(1)[n] = right(n as f64);
+        }
+        buffer
+    }
+
+    fn sine(frequency: f64) -> impl Fn(f64) -> f64 {
+        move |n| (2.0 * std::f64::consts::PI * frequency * n / 48000.0).sin()
+    }
+
+    #[test]
+    fn mid_side_round_trips() {
+        let (mid, side) = to_mid_side(0.75, -0.25);
+        assert_eq!((mid, side), (0.25, 0.5));
+        assert_eq!(to_left_right(mid, side), (0.75, -0.25));
+    }
+
+    #[test_case(1.0 => 1.0; "same")]
+    #[test_case(-1.0 => -1.0; "opposite")]
+    #[test_case(0.5 => 1.0; "level doesn't matter")]
+    fn correlation_of_scaled_copies(scale: f64) -> f64 {
+        let buffer = stereo(sine(1000.0), move |n| scale * sine(1000.0)(n));
+        (correlation(&buffer) * 1e9).round() / 1e9
+    }
+
+    #[test]
+    fn correlation_of_unrelated_signals_is_near_zero() {
+        let buffer = stereo(sine(1000.0), sine(1500.0));
+        assert!(correlation(&buffer).abs() < 0.01);
+    }
+
+    #[test]
+    fn zero_width_is_mono() {
+        let mut width = StereoWidth::new(SampleRate::from(48000));
+        width.set_width(0.0);
+        width.reset();
+        let mut buffer = stereo(sine(1000.0), sine(1500.0));
+
+        width.process(&mut buffer);
+
+        assert_eq!(buffer.chan(0), buffer.chan(1));
+        assert!((width.correlation() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn widening_lowers_the_correlation() {
+        let mut width = StereoWidth::new(SampleRate::from(48000));
+        width.set_width(2.0);
+        width.reset();
+        let mut buffer = stereo(sine(1000.0), |n| {
+            0.5 * sine(1000.0)(n) + 0.3 * sine(1500.0)(n)
+        });
+        let before = correlation(&buffer);
+
+        width.process(&mut buffer);
+
+        assert!(width.correlation() < before);
+    }
+
+    #[test]
+    fn mono_safe_keeps_the_correlation_positive() {
+        let mut width = StereoWidth::new(SampleRate::from(48000));
+        width.set_width(4.0);
+        width.set_mono_safe(true);
+        width.reset();
+
+        for _ in 0..10 {
+            let mut buffer = stereo(sine(1000.0), sine(1500.0));
+            width.process(&mut buffer);
+        }
+
+        assert!(width.correlation() >= -1e-9);
+    }
+
+    #[test]
+    fn bass_is_made_mono() {
+        let mut width = StereoWidth::new(SampleRate::from(48000));
+        width.set_mono_below(Some(Frequency::from(200.0)));
+        let mut buffer = stereo(sine(30.0), |n| -sine(30.0)(n));
+
+        width.process(&mut buffer);
+
+        let peak = buffer.chan(0)[2400..]
+            .iter()
+            .fold(0.0_f64, |peak, s| peak.max(s.abs()));
+        assert!(peak < 0.05);
+    }
+}
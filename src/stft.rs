@@ -0,0 +1,268 @@
+//! This module contains a short-time Fourier transform processor, which is the scaffolding for
+//! spectral effects. It splits the audio into overlapping windowed frames, hands the spectrum of
+//! every frame to a callback, and reconstructs the audio with overlap-add.
+//! The same window is applied before the FFT and after the inverse FFT, and the output is scaled
+//! so that the result is unity gain for windows that overlap-add to a constant when squared
+//! (e.g. Hann with a hop of a quarter of the FFT size).
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::stft::Stft;
+//! use rabu::units::{Channels, Samples};
+//! use rabu::windows::Window;
+//!
+//! let mut stft = Stft::new(Channels::from(2), Samples::from(1024), Samples::from(256), Window::Hann);
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//!
+//! // A crude spectral gate: silence all quiet bins.
+//! stft.process(&mut buffer, |_channel, bins| {
+//!     for bin in bins.iter_mut().filter(|bin| bin.norm() < 0.01) {
+//!         *bin = Default::default();
+//!     }
+//! });
+//!
+//! assert_eq!(stft.latency_samples(), Samples::from(1024));
+//! ```
+
+use crate::buffer::Buffer;
+use crate::fft::{Complex, RealFft};
+use crate::sample::Sample;
+use crate::units::{Channels, Latency, SampleRate, Samples};
+use crate::windows::Window;
+
+/// A streaming short-time Fourier transform with overlap-add reconstruction.
+/// All memory is allocated up front, so processing is real-time safe.
+#[derive(Clone, Debug)]
+pub struct Stft {
+    fft: RealFft,
+    window: Vec<f64>,
+    hop_size: usize,
+    output_scale: f64,
+    channels: Vec<ChannelState>,
+    frame: Vec<f64>,
+    spectrum: Vec<Complex>,
+}
+
+#[derive(Clone, Debug)]
+struct ChannelState {
+    input: Vec<f64>,
+    output: Vec<f64>,
+    accumulator: Vec<f64>,
+    position: usize,
+}
+
+impl Stft {
+    /// Creates a new STFT for the given number of channels, with frames of `fft_size` samples
+    /// that start every `hop_size` samples.
+    /// This will panic if the FFT size is not a power of two, or if the hop size is zero or
+    /// larger than the FFT size.
+    pub fn new(
+        num_channels: Channels,
+        fft_size: Samples,
+        hop_size: Samples,
+        window: Window,
+    ) -> Self {
+        let size = fft_size.as_usize();
+        let hop = hop_size.as_usize();
+        assert!(
+            hop > 0 && hop <= size,
+            "hop size must be between 1 and the FFT size"
+        );
+
+        let mut window_values = vec![0.0; size];
+        window.fill_periodic(&mut window_values);
+        let sum_of_squares: f64 = window_values.iter().map(|w| w * w).sum();
+
+        let fft = RealFft::new(fft_size);
+        let channel = ChannelState {
+            input: vec![0.0; size],
+            output: vec![0.0; size],
+            accumulator: vec![0.0; size],
+            position: size - hop,
+        };
+
+        Self {
+            spectrum: vec![Complex::default(); fft.num_bins().as_usize()],
+            fft,
+            window: window_values,
+            hop_size: hop,
+            output_scale: hop as f64 / sum_of_squares,
+            channels: vec![channel; num_channels.as_usize()],
+            frame: vec![0.0; size],
+        }
+    }
+
+    /// Returns the number of channels this STFT processes.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.channels.len())
+    }
+
+    /// Returns the size of the frames.
+    pub fn fft_size(&self) -> Samples {
+        self.fft.size()
+    }
+
+    /// Returns the number of samples between the starts of two consecutive frames.
+    pub fn hop_size(&self) -> Samples {
+        Samples::from(self.hop_size)
+    }
+
+    /// Returns the number of bins that the callback receives per frame.
+    pub fn num_bins(&self) -> Samples {
+        self.fft.num_bins()
+    }
+
+    /// Returns the delay between input and output in samples, which equals the FFT size:
+    /// a sample is only complete once the last frame that overlaps it has been added.
+    pub fn latency_samples(&self) -> Samples {
+        self.fft.size()
+    }
+
+    /// Returns the delay between input and output.
+    pub fn latency(&self, sample_rate: SampleRate) -> Latency {
+        Latency::from(self.latency_samples().to_seconds(sample_rate))
+    }
+
+    /// Clears the internal state, as if no audio was processed yet.
+    pub fn reset(&mut self) {
+        let start = self.window.len() - self.hop_size;
+        for channel in &mut self.channels {
+            channel.input.fill(0.0);
+            channel.output.fill(0.0);
+            channel.accumulator.fill(0.0);
+            channel.position = start;
+        }
+    }
+
+    /// Processes the given buffer in place. Every time a frame is complete, the callback is
+    /// called with the channel index and the spectrum of the frame, which it can modify.
+    /// This will panic if the buffer doesn't have the same number of channels as the STFT.
+    pub fn process<T: Sample>(
+        &mut self,
+        buffer: &mut Buffer<T>,
+        mut callback: impl FnMut(usize, &mut [Complex]),
+    ) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+
+        let size = self.window.len();
+        let start = size - self.hop_size;
+
+        for (index, samples) in buffer.iter_chans_mut().enumerate() {
+            let channel = &mut self.channels[index];
+            for sample in samples.iter_mut() {
+                channel.input[channel.position] = sample.to_f64();
+                *sample = T::from_f64(channel.output[channel.position - start]);
+                channel.position += 1;
+
+                if channel.position == size {
+                    channel.position = start;
+                    Self::process_frame(
+                        &mut self.fft,
+                        &self.window,
+                        self.hop_size,
+                        self.output_scale,
+                        &mut self.frame,
+                        &mut self.spectrum,
+                        channel,
+                        |bins| callback(index, bins),
+                    );
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_frame(
+        fft: &mut RealFft,
+        window: &[f64],
+        hop_size: usize,
+        output_scale: f64,
+        frame: &mut [f64],
+        spectrum: &mut [Complex],
+        channel: &mut ChannelState,
+        mut callback: impl FnMut(&mut [Complex]),
+    ) {
+        for ((value, input), w) in frame.iter_mut().zip(&channel.input).zip(window) {
+            *value = input * w;
+        }
+        fft.forward(frame, spectrum);
+
+        callback(spectrum);
+
+        fft.inverse(spectrum, frame);
+        for ((sum, value), w) in channel.accumulator.iter_mut().zip(frame.iter()).zip(window) {
+            *sum += value * w * output_scale;
+        }
+
+        channel.output[..hop_size].copy_from_slice(&channel.accumulator[..hop_size]);
+        channel.accumulator.copy_within(hop_size.., 0);
+        let size = window.len();
+        channel.accumulator[size - hop_size..].fill(0.0);
+        channel.input.copy_within(hop_size.., 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine_buffer(num_samples: usize) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(num_samples));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = (n as f64 * 0.05).sin() + 0.3 * (n as f64 * 0.31).cos();
+        }
+        buffer
+    }
+
+    #[test]
+    fn unmodified_frames_reconstruct_the_input() {
+        let mut stft = Stft::new(
+            Channels::from(1),
+            Samples::from(64),
+            Samples::from(16),
+            Window::Hann,
+        );
+        let input = sine_buffer(1000);
+        let mut buffer = input.clone();
+
+        stft.process(&mut buffer, |_, _| {});
+
+        let latency = stft.latency_samples().as_usize();
+        for n in 64..1000 {
+            assert!((buffer.chan(0)[n] - input.chan(0)[n - latency]).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn callback_is_called_once_per_hop_per_channel() {
+        let mut stft = Stft::new(
+            Channels::from(2),
+            Samples::from(32),
+            Samples::from(8),
+            Window::Hann,
+        );
+        let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(80));
+        let mut calls = [0, 0];
+
+        stft.process(&mut buffer, |channel, bins| {
+            assert_eq!(bins.len(), 17);
+            calls[channel] += 1;
+        });
+
+        assert_eq!(calls, [10, 10]);
+    }
+
+    #[test]
+    fn cleared_spectrum_gives_silence() {
+        let mut stft = Stft::new(
+            Channels::from(1),
+            Samples::from(64),
+            Samples::from(16),
+            Window::Hann,
+        );
+        let mut buffer = sine_buffer(500);
+
+        stft.process(&mut buffer, |_, bins| bins.fill(Complex::default()));
+
+        assert!(buffer.chan(0).iter().all(|s| s.abs() < 1e-12));
+    }
+}
@@ -0,0 +1,298 @@
+//! This module contains impulse response measurement with exponential sine sweeps, as described
+//! by Farina: play a sweep through a room or a device, record it, and deconvolve the recording
+//! with the sweep. Because the sweep spends the same time in every octave, the distortion of
+//! the device ends up before the impulse response instead of on top of it: the harmonics of
+//! every order show up as their own impulse response at a fixed time before the linear one,
+//! so they can be cut out and looked at separately.
+//! ```rust
+//! use rabu::sweep::Sweep;
+//! use rabu::units::{Channels, Duration, Frequency, SampleRate, Samples};
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let sweep = Sweep {
+//!     start: Frequency::from(20.0),
+//!     end: Frequency::from(20000.0),
+//!     duration: Duration::from_secs_f64(1.0),
+//! };
+//!
+//! // Play the sweep through the device, and record it with some extra time for the tail.
+//! let played = sweep.generate::<f32>(Channels::from(1), sample_rate);
+//! let mut recording = played.clone_resized(Channels::from(1), Samples::from(60000));
+//! recording.map_samples(|sample| 0.5 * sample);
+//!
+//! let response = sweep.deconvolve(&recording, sample_rate, Samples::from(4800), 3);
+//!
+//! assert_eq!(response.linear.num_samples(), Samples::from(4800));
+//! assert_eq!(response.harmonics.len(), 3);
+//! // The sweep stops at 20 kHz, so the impulse is band limited, with its peak at the start.
+//! let peak = response.linear.chan(0).iter().copied().fold(0.0, f32::max);
+//! assert_eq!(response.linear.chan(0)[0], peak);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::fft::{Complex, RealFft};
+use crate::sample::Sample;
+use crate::signals::exponential_sweep;
+use crate::units::{Channels, Duration, Frequency, SampleRate, Samples, Seconds};
+
+/// How far the regularization of the deconvolution lies below the loudest bin of the sweep.
+/// This keeps the frequencies outside of the sweep, where the recording has little more than
+/// noise, from being boosted.
+const REGULARIZATION_DB: f64 = -60.0;
+
+/// The settings of an exponential sine sweep.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sweep {
+    /// The frequency the sweep starts at.
+    pub start: Frequency,
+    /// The frequency the sweep ends at.
+    pub end: Frequency,
+    /// The length of the sweep. Longer sweeps give a better signal-to-noise ratio, and more
+    /// room in between the harmonics.
+    pub duration: Duration,
+}
+
+/// The impulse responses found in a sweep recording, with the channels of the recording.
+#[derive(Clone, Debug)]
+pub struct SweepResponse {
+    /// The impulse response of the linear part of the system.
+    pub linear: Buffer<f32>,
+    /// The impulse responses of the harmonics, starting at the second harmonic. They are
+    /// shorter than the linear one when there isn't enough time in between them.
+    pub harmonics: Vec<Buffer<f32>>,
+}
+
+impl Sweep {
+    /// Creates the sweep, to play through the system that is measured.
+    /// This will panic if one of the frequencies is not above 0.
+    pub fn generate<T: Sample>(
+        &self,
+        num_channels: Channels,
+        sample_rate: SampleRate,
+    ) -> Buffer<T> {
+        exponential_sweep(
+            num_channels,
+            self.start,
+            self.end,
+            self.duration,
+            sample_rate,
+        )
+    }
+
+    /// Returns how long before the linear impulse response the impulse response of the
+    /// harmonic shows up, e.g. 2 for the second harmonic. This is 0 for the first harmonic,
+    /// which is the linear response.
+    /// This will panic if the harmonic is 0.
+    pub fn harmonic_offset(&self, harmonic: usize) -> Seconds {
+        assert!(harmonic > 0, "the first harmonic is 1");
+        let octaves = (self.end.as_f64() / self.start.as_f64()).ln();
+        Seconds::from(self.duration.as_secs_f64() * (harmonic as f64).ln() / octaves)
+    }
+
+    /// Deconvolves every channel of the recording with the sweep, and returns the linear
+    /// impulse response and the responses of the given number of harmonics, each at most
+    /// `length` long. The recording should start when the sweep starts playing, and go on for
+    /// long enough to hold the tail of the response; a delay before the recording shows up as
+    /// a delay in the responses.
+    /// This will panic if the sweep goes down in frequency.
+    pub fn deconvolve<T: Sample>(
+        &self,
+        recording: &Buffer<T>,
+        sample_rate: SampleRate,
+        length: Samples,
+        num_harmonics: usize,
+    ) -> SweepResponse {
+        assert!(
+            self.end.as_f64() > self.start.as_f64(),
+            "the sweep must go up in frequency"
+        );
+        let sweep = self.generate::<f64>(Channels::from(1), sample_rate);
+        let sweep = sweep.chan(0);
+        let num_samples = recording.num_samples().as_usize();
+        // Leaves room for the harmonics before the start, which wrap around to the end.
+        let size = (num_samples + sweep.len()).next_power_of_two();
+        let mut fft = RealFft::new(Samples::from(size));
+        let num_bins = fft.num_bins().as_usize();
+
+        let inverse = inverse_spectrum(&mut fft, sweep);
+        let mut padded = vec![0.0; size];
+        let mut spectrum = vec![Complex::default(); num_bins];
+        let mut deconvolved =
+            Buffer::<f64>::allocate(recording.num_channels(), Samples::from(size));
+        for (channel, output) in recording.iter_chans().zip(deconvolved.iter_chans_mut()) {
+            for (value, sample) in padded.iter_mut().zip(channel) {
+                *value = sample.to_f64();
+            }
+            fft.forward(&padded, &mut spectrum);
+            for (bin, inverse) in spectrum.iter_mut().zip(&inverse) {
+                *bin = *bin * *inverse;
+            }
+            fft.inverse(&spectrum, output);
+        }
+
+        let length = length.as_usize();
+        let offsets: Vec<usize> = (1..=num_harmonics + 1)
+            .map(|harmonic| {
+                let offset = self.harmonic_offset(harmonic).as_f64() * sample_rate.as_f64();
+                (offset.round() as usize).min(size)
+            })
+            .collect();
+        let linear = cut(&deconvolved, 0, length);
+        let harmonics = offsets
+            .windows(2)
+            .map(|pair| {
+                let available = pair[1] - pair[0];
+                cut(&deconvolved, size - pair[1], length.min(available))
+            })
+            .collect();
+
+        SweepResponse { linear, harmonics }
+    }
+}
+
+/// Returns the regularized inverse of the spectrum of the sweep, zero padded to the size of the
+/// FFT.
+fn inverse_spectrum(fft: &mut RealFft, sweep: &[f64]) -> Vec<Complex> {
+    let mut padded = vec![0.0; fft.size().as_usize()];
+    padded[..sweep.len()].copy_from_slice(sweep);
+    let mut spectrum = vec![Complex::default(); fft.num_bins().as_usize()];
+    fft.forward(&padded, &mut spectrum);
+
+    let peak = spectrum.iter().map(Complex::norm_sqr).fold(0.0, f64::max);
+    let regularization = peak * 10.0_f64.powf(REGULARIZATION_DB / 10.0);
+    spectrum
+        .iter()
+        .map(|bin| bin.conj() * (1.0 / (bin.norm_sqr() + regularization)))
+        .collect()
+}
+
+/// Copies `length` samples of every channel from `start` on, wrapping around at the end.
+fn cut(buffer: &Buffer<f64>, start: usize, length: usize) -> Buffer<f32> {
+    let size = buffer.num_samples().as_usize();
+    let mut output = Buffer::allocate(buffer.num_channels(), Samples::from(length));
+    for (input, output) in buffer.iter_chans().zip(output.iter_chans_mut()) {
+        for (index, sample) in output.iter_mut().enumerate() {
+            *sample = input[(start + index) % size] as f32;
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn sweep() -> Sweep {
+        Sweep {
+            start: Frequency::from(10.0),
+            end: Frequency::from(24000.0),
+            duration: Duration::from_secs_f64(1.0),
+        }
+    }
+
+    /// Plays the sweep through the system, given as a function of the current and earlier
+    /// samples of the sweep.
+    fn record(system: impl Fn(&[f64], usize) -> f64) -> Buffer<f64> {
+        let sample_rate = SampleRate::from(SAMPLE_RATE);
+        let played = sweep().generate::<f64>(Channels::from(1), sample_rate);
+        let played = played.clone_resized(Channels::from(1), Samples::from(SAMPLE_RATE + 4800));
+        let mut recording = played.clone();
+        for (n, sample) in recording.chan_mut(0).iter_mut().enumerate() {
+            *sample = system(played.chan(0), n);
+        }
+        recording
+    }
+
+    fn peak(samples: &[f32]) -> (usize, f32) {
+        samples
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+            .unwrap()
+    }
+
+    #[test]
+    fn harmonics_show_up_earlier_for_higher_orders() {
+        let sweep = sweep();
+        let octaves = (24000.0_f64 / 10.0).ln();
+
+        assert_eq!(sweep.harmonic_offset(1), Seconds::from(0.0));
+        assert!((sweep.harmonic_offset(2).as_f64() - 2.0_f64.ln() / octaves).abs() < 1e-12);
+        assert!(sweep.harmonic_offset(3) > sweep.harmonic_offset(2));
+    }
+
+    #[test]
+    fn finds_the_reflections_of_a_room() {
+        let recording = record(|sweep, n| {
+            let delayed = |delay: usize| n.checked_sub(delay).map_or(0.0, |n| sweep[n]);
+            0.8 * delayed(0) + 0.4 * delayed(100) - 0.2 * delayed(1000)
+        });
+
+        let response = sweep().deconvolve(
+            &recording,
+            SampleRate::from(SAMPLE_RATE),
+            Samples::from(2048),
+            0,
+        );
+
+        let linear = response.linear.chan(0);
+        assert!((linear[0] - 0.8).abs() < 0.02, "{}", linear[0]);
+        assert!((linear[100] - 0.4).abs() < 0.02, "{}", linear[100]);
+        assert!((linear[1000] + 0.2).abs() < 0.02, "{}", linear[1000]);
+        assert!(linear[200..900].iter().all(|sample| sample.abs() < 0.02));
+        assert!(response.harmonics.is_empty());
+    }
+
+    #[test]
+    fn distortion_is_separated_from_the_linear_response() {
+        // A square term adds a second harmonic, a cube term a third one.
+        let recording = record(|sweep, n| {
+            let x = sweep[n];
+            x + 0.1 * x * x + 0.05 * x * x * x
+        });
+
+        let response = sweep().deconvolve(
+            &recording,
+            SampleRate::from(SAMPLE_RATE),
+            Samples::from(1024),
+            2,
+        );
+
+        let (position, value) = peak(response.linear.chan(0));
+        assert_eq!(position, 0);
+        assert!(value > 0.9);
+        assert!(response.linear.chan(0)[100..]
+            .iter()
+            .all(|sample| sample.abs() < 0.01));
+        for harmonic in &response.harmonics {
+            let (position, value) = peak(harmonic.chan(0));
+            assert!(position < 10, "{position}");
+            assert!(value.abs() > 0.01, "{value}");
+        }
+    }
+
+    #[test]
+    fn harmonics_are_cut_short_when_they_are_close_together() {
+        let sweep = sweep();
+        let sample_rate = SampleRate::from(SAMPLE_RATE);
+        let recording = sweep.generate::<f32>(Channels::from(2), sample_rate);
+
+        let response = sweep.deconvolve(&recording, sample_rate, Samples::from(48000), 3);
+
+        assert_eq!(response.linear.num_samples(), Samples::from(48000));
+        assert_eq!(response.linear.num_channels(), Channels::from(2));
+        let gap = |a: usize, b: usize| {
+            let offset = |harmonic| sweep.harmonic_offset(harmonic).as_f64() * 48000.0;
+            (offset(b).round() - offset(a).round()) as usize
+        };
+        let lengths: Vec<usize> = response
+            .harmonics
+            .iter()
+            .map(|harmonic| harmonic.num_samples().as_usize())
+            .collect();
+        assert_eq!(lengths, [gap(1, 2), gap(2, 3), gap(3, 4)]);
+    }
+}
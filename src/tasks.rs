@@ -0,0 +1,414 @@
+//! This module contains async versions of the slow offline work, with the `async` feature:
+//! decoding a file, rendering and measuring loudness. Every task runs on its own thread, and is
+//! a `Future` that can be awaited on any runtime, e.g. tokio, without blocking it. The progress
+//! can be read while it runs, and the work stops when the task is cancelled or dropped, in
+//! which case it gives `None`. Outside of async code, `Task::wait` blocks until it's done.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::tasks::{measure_loudness, spawn};
+//! use rabu::units::{Channels, Percentage, SampleRate, Samples};
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(48000));
+//! for channel in buffer.iter_chans_mut() {
+//!     for (n, sample) in channel.iter_mut().enumerate() {
+//!         *sample = 0.1 * (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / 48000.0).sin();
+//!     }
+//! }
+//!
+//! // In async code: `let stats = measure_loudness(buffer, sample_rate).await;`
+//! let task = measure_loudness(buffer, SampleRate::from(48000));
+//! let stats = task.wait().unwrap();
+//! assert!((stats.integrated.as_f64() + 20.0).abs() < 1.0);
+//!
+//! // Any work can be a task, as long as it reports progress and stops when asked to.
+//! let task = spawn(|progress| {
+//!     for step in 0..10 {
+//!         if !progress.report(Percentage::from(step as f64 * 10.0)) {
+//!             return None;
+//!         }
+//!     }
+//!     Some(42)
+//! });
+//! assert_eq!(task.wait(), Some(42));
+//! ```
+
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::buffer::Buffer;
+use crate::loudness::LoudnessMeter;
+use crate::normalization::LoudnessStats;
+use crate::processor::AudioProcessor;
+use crate::render::OfflineRenderer;
+use crate::sample::Sample;
+use crate::units::{Percentage, SampleRate, Samples, Seconds, TimeSection};
+use crate::wav::{WavFile, WavReader};
+
+/// The length of the blocks that are decoded and measured between progress reports, in
+/// seconds.
+const BLOCK_LENGTH: f64 = 1.0;
+
+/// Work that runs on its own thread, and gives its output when awaited.
+#[derive(Debug)]
+pub struct Task<T> {
+    state: Arc<State<T>>,
+}
+
+/// Lets the work report its progress, and tells it when it should stop.
+#[derive(Debug)]
+pub struct Progress {
+    percentage: AtomicU64,
+    is_cancelled: AtomicBool,
+}
+
+#[derive(Debug)]
+struct State<T> {
+    progress: Progress,
+    outcome: Mutex<Outcome<T>>,
+    finished: Condvar,
+}
+
+#[derive(Debug)]
+struct Outcome<T> {
+    /// The output once the work is done, which is `None` when it stopped.
+    output: Option<Option<T>>,
+    waker: Option<Waker>,
+}
+
+/// Runs the work on its own thread. The work should stop and give `None` when reporting its
+/// progress tells it to.
+pub fn spawn<T, F>(work: F) -> Task<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Progress) -> Option<T> + Send + 'static,
+{
+    let state = Arc::new(State {
+        progress: Progress {
+            percentage: AtomicU64::new(0.0_f64.to_bits()),
+            is_cancelled: AtomicBool::new(false),
+        },
+        outcome: Mutex::new(Outcome {
+            output: None,
+            waker: None,
+        }),
+        finished: Condvar::new(),
+    });
+
+    let thread_state = state.clone();
+    std::thread::spawn(move || {
+        // Work that panics gives `None`, so awaiting it doesn't hang.
+        let output = catch_unwind(AssertUnwindSafe(|| work(&thread_state.progress)))
+            .ok()
+            .flatten()
+            .filter(|_| !thread_state.progress.is_cancelled());
+        if output.is_some() {
+            thread_state.progress.report(Percentage::from(100.0));
+        }
+
+        let mut outcome = thread_state.outcome.lock().unwrap();
+        outcome.output = Some(output);
+        if let Some(waker) = outcome.waker.take() {
+            waker.wake();
+        }
+        thread_state.finished.notify_all();
+    });
+    Task { state }
+}
+
+impl<T> Task<T> {
+    /// Returns how far the work is.
+    pub fn progress(&self) -> Percentage {
+        self.state.progress.percentage()
+    }
+
+    /// Tells whether the work is done, or stopped.
+    pub fn is_finished(&self) -> bool {
+        self.state.outcome.lock().unwrap().output.is_some()
+    }
+
+    /// Asks the work to stop, after which the task gives `None`.
+    pub fn cancel(&self) {
+        self.state
+            .progress
+            .is_cancelled
+            .store(true, Ordering::Relaxed);
+    }
+
+    /// Blocks until the work is done, and gives its output, or `None` when it stopped.
+    pub fn wait(self) -> Option<T> {
+        let mut outcome = self.state.outcome.lock().unwrap();
+        while outcome.output.is_none() {
+            outcome = self.state.finished.wait(outcome).unwrap();
+        }
+        outcome.output.take().flatten()
+    }
+}
+
+impl<T> Future for Task<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut outcome = self.state.outcome.lock().unwrap();
+        match outcome.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                outcome.waker = Some(context.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for Task<T> {
+    /// Stops the work, because nobody can get its output anymore.
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl Progress {
+    /// Reports how far the work is, and gives `false` when it should stop.
+    pub fn report(&self, percentage: Percentage) -> bool {
+        self.percentage
+            .store(percentage.as_f64().to_bits(), Ordering::Relaxed);
+        !self.is_cancelled()
+    }
+
+    /// Returns the progress that was reported last.
+    pub fn percentage(&self) -> Percentage {
+        Percentage::from(f64::from_bits(self.percentage.load(Ordering::Relaxed)))
+    }
+
+    /// Tells whether the work should stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Reads the WAV file at the path, or gives `None` when it can't be read or isn't a supported
+/// WAV file.
+pub fn decode_file(path: impl AsRef<Path>) -> Task<WavFile> {
+    let path = path.as_ref().to_path_buf();
+    spawn(move |progress| {
+        let mut reader = WavReader::open(path)?;
+        let mut audio = Buffer::allocate(reader.num_channels(), reader.num_samples());
+        let block_length = Seconds::from(BLOCK_LENGTH).to_samples(reader.sample_rate());
+
+        while reader.position() < reader.num_samples() {
+            let start = reader.position().as_usize();
+            let block = reader.read(block_length)?;
+            for (to, from) in audio.iter_chans_mut().zip(block.iter_chans()) {
+                to[start..start + from.len()].copy_from_slice(from);
+            }
+            if !progress.report(percentage(reader.position(), reader.num_samples())) {
+                return None;
+            }
+        }
+        Some(WavFile::new(audio, reader.sample_rate(), reader.format()))
+    })
+}
+
+/// Renders the section through the processor into a buffer, see `OfflineRenderer::render`.
+pub fn render<P>(
+    renderer: OfflineRenderer,
+    mut processor: P,
+    section: TimeSection,
+) -> Task<Buffer<f32>>
+where
+    P: AudioProcessor + Send + 'static,
+{
+    spawn(move |progress| {
+        renderer.render(&mut processor, section, |percentage| {
+            progress.report(percentage)
+        })
+    })
+}
+
+/// Measures the loudness of the whole buffer, see `normalization::measure_loudness`.
+pub fn measure_loudness<T>(buffer: Buffer<T>, sample_rate: SampleRate) -> Task<LoudnessStats>
+where
+    T: Sample + Send + 'static,
+{
+    spawn(move |progress| {
+        let mut meter = LoudnessMeter::new(buffer.num_channels(), sample_rate);
+        meter.set_true_peak(true);
+        let block_length = Seconds::from(BLOCK_LENGTH).to_samples(sample_rate);
+        let mut block = Buffer::<T>::allocate(buffer.num_channels(), block_length);
+
+        let mut start = 0;
+        while start < buffer.num_samples().as_usize() {
+            let length = block_length.min(buffer.num_samples() - Samples::from(start));
+            block.set_num_samples(length);
+            for (to, from) in block.iter_chans_mut().zip(buffer.iter_chans()) {
+                to.copy_from_slice(&from[start..start + length.as_usize()]);
+            }
+            meter.process(&block);
+            start += length.as_usize();
+            if !progress.report(percentage(Samples::from(start), buffer.num_samples())) {
+                return None;
+            }
+        }
+
+        Some(LoudnessStats {
+            integrated: meter.integrated(),
+            true_peak: meter.true_peak().unwrap(),
+            loudness_range: meter.loudness_range(),
+        })
+    })
+}
+
+fn percentage(done: Samples, total: Samples) -> Percentage {
+    Percentage::from(100.0 * done.as_f64() / total.as_f64().max(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::task::Wake;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::normalization;
+    use crate::processor::ProcessContext;
+    use crate::quantize::SampleFormat;
+    use crate::units::{BufferSize, Channels, TimePoint};
+    use crate::wav::WavWriter;
+
+    /// A waker that unparks the thread that polls.
+    struct ThreadWaker(std::thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Polls the future on this thread until it's ready, the way an executor would.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut context = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    fn sine(length: usize) -> Buffer<f32> {
+        let mut buffer = Buffer::allocate(Channels::from(2), Samples::from(length));
+        for channel in buffer.iter_chans_mut() {
+            for (n, sample) in channel.iter_mut().enumerate() {
+                *sample = 0.25 * (n as f32 * 0.1).sin();
+            }
+        }
+        buffer
+    }
+
+    #[test]
+    fn awaits_the_output() {
+        let task = spawn(|progress| {
+            progress.report(Percentage::from(50.0));
+            Some("done")
+        });
+
+        assert_eq!(block_on(task), Some("done"));
+    }
+
+    #[test]
+    fn cancelling_stops_the_work() {
+        let (started, wait_for_start) = mpsc::channel();
+        let task = spawn(move |progress| {
+            progress.report(Percentage::from(10.0));
+            started.send(()).unwrap();
+            while progress.report(Percentage::from(10.0)) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            Some(())
+        });
+        wait_for_start.recv().unwrap();
+
+        task.cancel();
+
+        assert_eq!(task.progress(), Percentage::from(10.0));
+        assert_eq!(block_on(task), None);
+    }
+
+    #[test]
+    fn work_that_panics_gives_none() {
+        let task = spawn(|_| -> Option<()> { panic!("the work failed") });
+
+        assert!(task.wait().is_none());
+    }
+
+    #[test]
+    fn measures_the_same_loudness_in_blocks() {
+        let buffer = sine(120_000);
+        let expected = normalization::measure_loudness(&buffer, SampleRate::from(48000));
+
+        let task = measure_loudness(buffer, SampleRate::from(48000));
+        let stats = block_on(task).unwrap();
+
+        assert!((stats.integrated.as_f64() - expected.integrated.as_f64()).abs() < 1e-9);
+        assert!((stats.true_peak.as_f64() - expected.true_peak.as_f64()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn decodes_a_file() {
+        let path = std::env::temp_dir().join("rabu_tasks_decodes_a_file.wav");
+        let audio = sine(3000);
+        let mut writer = WavWriter::create(
+            &path,
+            SampleRate::from(1000),
+            Channels::from(2),
+            SampleFormat::Float32,
+        )
+        .unwrap();
+        writer.write(&audio);
+        drop(writer);
+
+        let task = decode_file(&path);
+        let file = block_on(task).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(file.sample_rate(), SampleRate::from(1000));
+        assert_eq!(file.audio().data(), audio.data());
+        assert!(block_on(decode_file("does/not/exist.wav")).is_none());
+    }
+
+    #[test]
+    fn renders_a_section() {
+        struct Constant;
+
+        impl AudioProcessor for Constant {
+            fn prepare(&mut self, _: SampleRate, _: BufferSize, _: Channels) {}
+
+            fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+                buffer.chan_mut(0).fill(0.5);
+            }
+
+            fn reset(&mut self) {}
+        }
+
+        let renderer = OfflineRenderer::new(
+            SampleRate::from(1000),
+            BufferSize::from(64),
+            Channels::from(1),
+        );
+        let section = TimeSection {
+            start: TimePoint::from_secs_f64(0.0),
+            duration: crate::units::Duration::from_secs_f64(0.5),
+        };
+
+        let task = render(renderer, Constant, section);
+        let buffer = block_on(task).unwrap();
+
+        assert_eq!(buffer.chan(0), &[0.5; 500]);
+    }
+}
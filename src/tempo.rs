@@ -0,0 +1,289 @@
+//! This module contains tempo estimation and beat tracking. The onset strength of the audio
+//! (the spectral flux of the `onset` module) repeats at the beat period, so the tempo is the
+//! lag at which its autocorrelation peaks, with a preference for tempos around 120 BPM to
+//! choose between a tempo and its double or half. The beats are then placed on strong onsets,
+//! while keeping their spacing close to the beat period, with dynamic programming after Ellis
+//! (2007), so they follow small drifts in the timing.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::tempo::TempoEstimator;
+//! use rabu::units::{Channels, SampleRate, Samples};
+//!
+//! // A click every half second: 120 BPM.
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(1), Samples::from(16000 * 6));
+//! for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+//!     let since_click = n % 8000;
+//!     *sample = (1.3 * n as f32).sin() * (-(since_click as f32) / 200.0).exp();
+//! }
+//!
+//! let mut estimator = TempoEstimator::new(SampleRate::from(16000));
+//! let estimate = estimator.analyze(&buffer).unwrap();
+//!
+//! assert!((estimate.tempo.as_f64() - 120.0).abs() < 1.0);
+//! assert!(estimate.beats.len() >= 11);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::onset::OnsetDetector;
+use crate::sample::Sample;
+use crate::units::{SampleRate, Samples, Tempo, TimePoint};
+
+/// The tempo that is preferred when the audio fits several tempos equally well.
+const PREFERRED_TEMPO: f64 = 120.0;
+/// How strictly the beats keep to the beat period. Higher values follow the onsets less.
+const TIGHTNESS: f64 = 100.0;
+
+/// An estimated tempo, together with the beats it was found in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TempoEstimate {
+    /// The tempo.
+    pub tempo: Tempo,
+    /// The times of the beats, from the start of the audio.
+    pub beats: Vec<TimePoint>,
+}
+
+/// Estimates the tempo and the beats of audio, or of a list of onsets.
+#[derive(Clone, Debug)]
+pub struct TempoEstimator {
+    sample_rate: SampleRate,
+    detector: OnsetDetector,
+    fft_size: usize,
+    hop_size: usize,
+    min_tempo: Tempo,
+    max_tempo: Tempo,
+}
+
+impl TempoEstimator {
+    /// Creates a new estimator for tempos between 60 and 200 BPM, that analyzes the onset
+    /// strength every 10 ms.
+    pub fn new(sample_rate: SampleRate) -> Self {
+        let fft_size = ((sample_rate.as_f64() * 0.04) as usize).next_power_of_two();
+        let hop_size = (sample_rate.as_f64() / 100.0).round().max(1.0) as usize;
+        Self {
+            sample_rate,
+            detector: OnsetDetector::new(
+                sample_rate,
+                Samples::from(fft_size),
+                Samples::from(hop_size),
+            ),
+            fft_size,
+            hop_size,
+            min_tempo: Tempo::from(60.0),
+            max_tempo: Tempo::from(200.0),
+        }
+    }
+
+    /// Returns the slowest and the fastest tempo that can be found.
+    pub fn tempo_range(&self) -> (Tempo, Tempo) {
+        (self.min_tempo, self.max_tempo)
+    }
+
+    /// Changes the slowest and the fastest tempo that can be found.
+    /// This will panic if the slowest tempo isn't above 0 and below the fastest.
+    pub fn set_tempo_range(&mut self, min_tempo: Tempo, max_tempo: Tempo) {
+        assert!(
+            min_tempo.as_f64() > 0.0 && min_tempo < max_tempo,
+            "the slowest tempo must be above 0 and below the fastest"
+        );
+        self.min_tempo = min_tempo;
+        self.max_tempo = max_tempo;
+    }
+
+    /// Estimates the tempo and the beats of the buffer, or gives `None` when there's no
+    /// steady beat in it, or it's too short to hold two beats of the slowest tempo.
+    pub fn analyze<T: Sample>(&mut self, buffer: &Buffer<T>) -> Option<TempoEstimate> {
+        let strength = self.detector.onset_strength(buffer);
+        // Frame `i` ends at `(i + 1) * hop_size`, so its center is half a frame earlier.
+        let first_center = self.hop_size as f64 - self.fft_size as f64 / 2.0;
+        self.estimate(&strength, first_center)
+    }
+
+    /// Estimates the tempo and the beats of a list of onsets, e.g. from an `OnsetDetector` or
+    /// the notes of a MIDI file, in audio that is `length` long.
+    pub fn analyze_onsets(&self, onsets: &[Samples], length: Samples) -> Option<TempoEstimate> {
+        let mut strength = vec![0.0; length.as_usize().div_ceil(self.hop_size)];
+        for onset in onsets {
+            let frame = (onset.as_f64() / self.hop_size as f64).round() as usize;
+            if let Some(strength) = strength.get_mut(frame) {
+                *strength += 1.0;
+            }
+        }
+        self.estimate(&strength, 0.0)
+    }
+
+    /// Estimates the tempo and the beats of the onset strength, where the first frame is
+    /// centered at the given sample.
+    fn estimate(&self, strength: &[f64], first_center: f64) -> Option<TempoEstimate> {
+        let frame_rate = self.sample_rate.as_f64() / self.hop_size as f64;
+        let period = self.beat_period(strength, frame_rate)?;
+        let beats = track_beats(strength, period)
+            .into_iter()
+            .map(|frame| {
+                let position = (first_center + (frame * self.hop_size) as f64).max(0.0);
+                TimePoint::from(
+                    Samples::from(position.round() as usize).to_seconds(self.sample_rate),
+                )
+            })
+            .collect();
+        Some(TempoEstimate {
+            tempo: Tempo::from(60.0 * frame_rate / period),
+            beats,
+        })
+    }
+
+    /// Returns the beat period in frames: the lag within the tempo range where the
+    /// autocorrelation of the onset strength, weighted towards the preferred tempo, peaks.
+    fn beat_period(&self, strength: &[f64], frame_rate: f64) -> Option<f64> {
+        let min_lag = ((60.0 * frame_rate / self.max_tempo.as_f64()).floor() as usize).max(1);
+        let max_lag = (60.0 * frame_rate / self.min_tempo.as_f64()).ceil() as usize;
+        if strength.len() < 2 * (max_lag + 1) {
+            return None;
+        }
+        let mean = strength.iter().sum::<f64>() / strength.len() as f64;
+        let centered: Vec<f64> = strength.iter().map(|value| value - mean).collect();
+
+        let scores: Vec<f64> = (0..=max_lag + 1)
+            .map(|lag| {
+                let correlation = centered
+                    .iter()
+                    .zip(&centered[lag..])
+                    .map(|(a, b)| a * b)
+                    .sum::<f64>()
+                    / (centered.len() - lag) as f64;
+                let tempo = 60.0 * frame_rate / lag.max(1) as f64;
+                let octaves = (tempo / PREFERRED_TEMPO).log2();
+                correlation * (-0.5 * octaves * octaves).exp()
+            })
+            .collect();
+
+        let lag = (min_lag..=max_lag).max_by(|&a, &b| scores[a].total_cmp(&scores[b]))?;
+        if scores[lag] <= 0.0 {
+            return None;
+        }
+        let (left, middle, right) = (scores[lag - 1], scores[lag], scores[lag + 1]);
+        let curvature = left - 2.0 * middle + right;
+        let offset = if curvature < 0.0 {
+            (0.5 * (left - right) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        Some(lag as f64 + offset)
+    }
+}
+
+/// Returns the frames of the beats: the path through the onset strength that collects the most
+/// strength, with a penalty for every step between two beats that differs from the period.
+fn track_beats(strength: &[f64], period: f64) -> Vec<usize> {
+    let deviation = {
+        let mean = strength.iter().sum::<f64>() / strength.len() as f64;
+        let variance =
+            strength.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / strength.len() as f64;
+        variance.sqrt().max(f64::MIN_POSITIVE)
+    };
+    let shortest = (period / 2.0).round().max(1.0) as usize;
+    let longest = (2.0 * period).round() as usize;
+
+    let mut scores = vec![0.0; strength.len()];
+    let mut previous = vec![None; strength.len()];
+    for frame in 0..strength.len() {
+        let best = (frame.saturating_sub(longest)..=frame.saturating_sub(shortest))
+            .filter(|&before| before + shortest <= frame)
+            .map(|before| {
+                let step = ((frame - before) as f64 / period).ln();
+                (before, scores[before] - TIGHTNESS * step * step)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        scores[frame] = strength[frame] / deviation;
+        if let Some((before, score)) = best {
+            scores[frame] += score;
+            previous[frame] = Some(before);
+        }
+    }
+
+    // The last beat is the best one in the last period, from where the path is followed back.
+    let last_period = strength.len().saturating_sub(period.round() as usize);
+    let mut beat = (last_period..strength.len()).max_by(|&a, &b| scores[a].total_cmp(&scores[b]));
+    let mut beats = Vec::new();
+    while let Some(frame) = beat {
+        beats.push(frame);
+        beat = previous[frame];
+    }
+    beats.reverse();
+    beats
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Channels;
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    /// Clicks at the tempo, starting at the offset.
+    fn clicks(tempo: f64, offset: usize, length: usize) -> Buffer<f64> {
+        let beat_length = (60.0 / tempo * SAMPLE_RATE as f64) as usize;
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(length));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate().skip(offset) {
+            let since_click = (n - offset) % beat_length;
+            *sample = 0.5 * (1.3 * n as f64).sin() * (-(since_click as f64) / 200.0).exp();
+        }
+        buffer
+    }
+
+    #[test_case(90.0; "90 bpm")]
+    #[test_case(120.0; "120 bpm")]
+    #[test_case(150.0; "150 bpm")]
+    fn estimates_the_tempo_of_a_click_track(tempo: f64) {
+        let mut estimator = TempoEstimator::new(SampleRate::from(SAMPLE_RATE));
+
+        let estimate = estimator.analyze(&clicks(tempo, 0, 8 * 16000)).unwrap();
+
+        assert!(
+            (estimate.tempo.as_f64() - tempo).abs() < 1.5,
+            "{estimate:?}"
+        );
+    }
+
+    #[test]
+    fn beats_fall_on_the_clicks() {
+        let mut estimator = TempoEstimator::new(SampleRate::from(SAMPLE_RATE));
+
+        let estimate = estimator.analyze(&clicks(120.0, 3000, 8 * 16000)).unwrap();
+
+        assert!(estimate.beats.len() >= 14);
+        for beat in estimate.beats {
+            let since_click = (beat.as_secs_f64() - 3000.0 / 16000.0).rem_euclid(0.5);
+            let distance = since_click.min(0.5 - since_click);
+            assert!(distance < 0.02, "{beat:?}");
+        }
+    }
+
+    #[test]
+    fn estimates_the_tempo_of_onsets() {
+        let estimator = TempoEstimator::new(SampleRate::from(SAMPLE_RATE));
+        // 100 BPM, with a little timing jitter.
+        let onsets: Vec<Samples> = (0..16)
+            .map(|beat| Samples::from(beat * 9600 + [0, 40, 0, 80][beat % 4]))
+            .collect();
+
+        let estimate = estimator
+            .analyze_onsets(&onsets, Samples::from(16 * 9600))
+            .unwrap();
+
+        assert!(
+            (estimate.tempo.as_f64() - 100.0).abs() < 1.5,
+            "{estimate:?}"
+        );
+        assert_eq!(estimate.beats.len(), 16);
+    }
+
+    #[test]
+    fn silence_has_no_tempo() {
+        let mut estimator = TempoEstimator::new(SampleRate::from(SAMPLE_RATE));
+        let buffer = Buffer::<f64>::allocate(Channels::from(1), Samples::from(4 * 16000));
+
+        assert_eq!(estimator.analyze(&buffer), None);
+    }
+}
@@ -0,0 +1,546 @@
+//! This module contains waveform thumbnails: the peaks of audio at several zoom levels, so a
+//! waveform can be drawn at any zoom without going over the audio again. The first level has a
+//! peak for every 256 samples, and every next level combines 4 peaks of the level before.
+//! A `ThumbnailCache` stores the thumbnails of files in small sidecar files, together with the
+//! length and the modification time of the audio file, so the thumbnail is only computed again
+//! when the file changed.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::thumbnail::Thumbnail;
+//! use rabu::units::{Channels, SampleRate, SampleSection, Samples};
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(1), Samples::from(48000));
+//! buffer.chan_mut(0)[1000] = 0.5;
+//! buffer.chan_mut(0)[30000] = -0.25;
+//!
+//! let thumbnail = Thumbnail::from_buffer(&buffer, SampleRate::from(48000));
+//!
+//! // Two pixels for the whole second.
+//! let section = SampleSection { start: Samples::from(0), length: Samples::from(48000) };
+//! let peaks = thumbnail.peaks(0, section, 2);
+//! assert_eq!((peaks[0].min, peaks[0].max), (0.0, 0.5));
+//! assert_eq!((peaks[1].min, peaks[1].max), (-0.25, 0.0));
+//! ```
+
+use std::fs;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+use crate::units::{Channels, SampleRate, SampleSection, Samples};
+use crate::wav::WavReader;
+
+/// The number of samples of a peak of the first level.
+pub const SAMPLES_PER_PEAK: usize = 256;
+
+/// The number of peaks of a level that make up one peak of the next level.
+const LEVEL_FACTOR: usize = 4;
+
+/// The number of peaks of the first level that are computed from a block of a file at once.
+const PEAKS_PER_BLOCK: usize = 1024;
+
+const MAGIC: &[u8; 4] = b"RBTH";
+const VERSION: u8 = 1;
+const HEADER_LENGTH: usize = 4 + 1 + 8 + 8 + 4 + 4 + 8;
+
+/// The lowest and the highest sample in a part of the audio.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Peak {
+    /// The lowest sample.
+    pub min: f32,
+    /// The highest sample.
+    pub max: f32,
+}
+
+impl Peak {
+    fn of<T: Sample>(samples: &[T]) -> Self {
+        samples
+            .iter()
+            .map(|sample| {
+                let sample = sample.to_f64() as f32;
+                Self {
+                    min: sample,
+                    max: sample,
+                }
+            })
+            .reduce(Self::merge)
+            .unwrap_or_default()
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+/// The peaks of audio at several zoom levels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Thumbnail {
+    sample_rate: SampleRate,
+    num_samples: Samples,
+    /// The peaks of every level, per channel.
+    levels: Vec<Vec<Vec<Peak>>>,
+}
+
+impl Thumbnail {
+    /// Computes the thumbnail of the buffer.
+    pub fn from_buffer<T: Sample>(buffer: &Buffer<T>, sample_rate: SampleRate) -> Self {
+        let peaks = buffer
+            .iter_chans()
+            .map(|channel| channel.chunks(SAMPLES_PER_PEAK).map(Peak::of).collect())
+            .collect();
+        Self::from_peaks(sample_rate, buffer.num_samples(), peaks)
+    }
+
+    /// Computes the thumbnail of the WAV file in the reader, a block at a time, so the file
+    /// doesn't have to fit in memory. Gives `None` when the file can't be read.
+    pub fn from_wav<R: Read + Seek>(mut reader: WavReader<R>) -> Option<Self> {
+        let mut peaks = vec![Vec::new(); reader.num_channels().as_usize()];
+        reader.seek(Samples::from(0)).then_some(())?;
+        loop {
+            let block = reader.read(Samples::from(SAMPLES_PER_PEAK * PEAKS_PER_BLOCK))?;
+            if block.num_samples() == Samples::from(0) {
+                break;
+            }
+            for (peaks, channel) in peaks.iter_mut().zip(block.iter_chans()) {
+                peaks.extend(channel.chunks(SAMPLES_PER_PEAK).map(Peak::of));
+            }
+        }
+        Some(Self::from_peaks(
+            reader.sample_rate(),
+            reader.num_samples(),
+            peaks,
+        ))
+    }
+
+    /// Builds the levels on top of the peaks of the first level.
+    fn from_peaks(sample_rate: SampleRate, num_samples: Samples, peaks: Vec<Vec<Peak>>) -> Self {
+        let mut levels = vec![peaks];
+        while levels
+            .last()
+            .is_some_and(|level| level.first().is_some_and(|peaks| peaks.len() > 1))
+        {
+            let next = levels[levels.len() - 1]
+                .iter()
+                .map(|peaks| {
+                    peaks
+                        .chunks(LEVEL_FACTOR)
+                        .map(|chunk| chunk.iter().copied().reduce(Peak::merge).unwrap())
+                        .collect()
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self {
+            sample_rate,
+            num_samples,
+            levels,
+        }
+    }
+
+    /// Returns the sample rate of the audio.
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.levels[0].len())
+    }
+
+    /// Returns the number of samples per channel of the audio.
+    pub fn num_samples(&self) -> Samples {
+        self.num_samples
+    }
+
+    /// Returns the number of zoom levels.
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Returns the peaks of the channel in the section, divided into the number of equal parts,
+    /// e.g. one for every pixel. The peaks come from the coarsest level that is still detailed
+    /// enough, so they are never finer than a peak of the first level, and the parts are
+    /// rounded to the peaks of that level.
+    /// This will panic if the channel doesn't exist.
+    pub fn peaks(&self, channel: usize, section: SampleSection, num_peaks: usize) -> Vec<Peak> {
+        assert!(channel < self.num_channels().as_usize());
+        let samples_per_peak = section.length.as_usize() / num_peaks.max(1);
+        let mut level = 0;
+        let mut level_length = SAMPLES_PER_PEAK;
+        while level + 1 < self.levels.len() && level_length * LEVEL_FACTOR <= samples_per_peak {
+            level += 1;
+            level_length *= LEVEL_FACTOR;
+        }
+        let peaks = &self.levels[level][channel];
+
+        (0..num_peaks)
+            .map(|index| {
+                let start =
+                    section.start.as_usize() + index * section.length.as_usize() / num_peaks;
+                let end =
+                    section.start.as_usize() + (index + 1) * section.length.as_usize() / num_peaks;
+                // Rounded to the nearest peaks, so neighbouring parts don't share peaks.
+                let first = ((start + level_length / 2) / level_length).min(peaks.len());
+                let last = ((end + level_length / 2) / level_length)
+                    .max(first + 1)
+                    .min(peaks.len());
+                peaks[first..last]
+                    .iter()
+                    .copied()
+                    .reduce(Peak::merge)
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// Stores the thumbnail in a compact form, with the stamp of the file it belongs to. Only
+    /// the first level is stored, at 16 bits.
+    pub fn to_bytes(&self, stamp: FileStamp) -> Vec<u8> {
+        let base = &self.levels[0];
+        let num_peaks = base.first().map_or(0, Vec::len);
+        let mut bytes = Vec::with_capacity(HEADER_LENGTH + base.len() * num_peaks * 4);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&stamp.length.to_le_bytes());
+        bytes.extend_from_slice(&stamp.modified.to_le_bytes());
+        bytes.extend_from_slice(&self.sample_rate.as_u32().to_le_bytes());
+        bytes.extend_from_slice(&(base.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.num_samples.as_u64().to_le_bytes());
+        for peaks in base {
+            for peak in peaks {
+                bytes.extend_from_slice(&to_i16(peak.min).to_le_bytes());
+                bytes.extend_from_slice(&to_i16(peak.max).to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Reads a thumbnail stored with `to_bytes`, or gives `None` when the bytes aren't a
+    /// thumbnail, or belong to a file with another stamp.
+    pub fn from_bytes(bytes: &[u8], stamp: FileStamp) -> Option<Self> {
+        if bytes.len() < HEADER_LENGTH || &bytes[..4] != MAGIC || bytes[4] != VERSION {
+            return None;
+        }
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let stored = FileStamp {
+            length: u64_at(5),
+            modified: u64_at(13),
+        };
+        if stored != stamp {
+            return None;
+        }
+        let sample_rate = SampleRate::from(u32_at(21));
+        let num_channels = u32_at(25) as usize;
+        let num_samples = Samples::from(u64_at(29));
+
+        let num_peaks = num_samples.as_usize().div_ceil(SAMPLES_PER_PEAK);
+        let channel_length = num_peaks.checked_mul(4)?;
+        let data = &bytes[HEADER_LENGTH..];
+        if num_channels == 0 || Some(data.len()) != num_channels.checked_mul(channel_length) {
+            return None;
+        }
+        if num_peaks == 0 {
+            return Some(Self::from_peaks(
+                sample_rate,
+                num_samples,
+                vec![Vec::new(); num_channels],
+            ));
+        }
+        let peaks = data
+            .chunks(channel_length)
+            .map(|channel| {
+                channel
+                    .chunks(4)
+                    .map(|peak| Peak {
+                        min: from_i16([peak[0], peak[1]]),
+                        max: from_i16([peak[2], peak[3]]),
+                    })
+                    .collect()
+            })
+            .collect();
+        Some(Self::from_peaks(sample_rate, num_samples, peaks))
+    }
+}
+
+fn to_i16(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+fn from_i16(bytes: [u8; 2]) -> f32 {
+    i16::from_le_bytes(bytes) as f32 / i16::MAX as f32
+}
+
+/// Hashes the bytes with 64 bit FNV-1a. Unlike the hasher of the standard library, this gives
+/// the same hash with every version of Rust, so stored sidecar names keep matching.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The length and modification time of a file, which tell whether a thumbnail still belongs
+/// to it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FileStamp {
+    /// The length in bytes.
+    pub length: u64,
+    /// The modification time in nanoseconds since the Unix epoch.
+    pub modified: u64,
+}
+
+impl FileStamp {
+    /// Reads the stamp of the file at the path, or gives `None` when it can't be read.
+    pub fn of_file(path: impl AsRef<Path>) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            length: metadata.len(),
+            modified: modified.as_nanos() as u64,
+        })
+    }
+}
+
+/// Keeps the thumbnails of audio files in sidecar files, either next to the audio files or in a
+/// directory of their own.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThumbnailCache {
+    directory: Option<PathBuf>,
+}
+
+impl ThumbnailCache {
+    /// Creates a cache that keeps the thumbnail of a file next to it, with ".peaks" added to its
+    /// name.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a cache that keeps the thumbnails in the directory, e.g. when the audio files
+    /// are read only.
+    pub fn in_directory(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: Some(directory.into()),
+        }
+    }
+
+    /// Returns the path of the sidecar file of the audio file.
+    pub fn sidecar_path(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        match &self.directory {
+            Some(directory) => {
+                // Files with the same name in different directories get different sidecars.
+                let hash = fnv1a(path.to_string_lossy().as_bytes());
+                name.push(format!("-{hash:016x}.peaks"));
+                directory.join(name)
+            }
+            None => {
+                name.push(".peaks");
+                path.with_file_name(name)
+            }
+        }
+    }
+
+    /// Loads the thumbnail of the audio file, or gives `None` when there is none, or when the
+    /// file changed since it was stored.
+    pub fn load(&self, path: impl AsRef<Path>) -> Option<Thumbnail> {
+        let stamp = FileStamp::of_file(&path)?;
+        let bytes = fs::read(self.sidecar_path(&path)).ok()?;
+        Thumbnail::from_bytes(&bytes, stamp)
+    }
+
+    /// Loads the thumbnail of the WAV file, or computes it and stores it when there is none
+    /// that is still valid. Gives `None` when the file can't be read. A thumbnail that can't be
+    /// stored is still given back.
+    pub fn load_or_create(&self, path: impl AsRef<Path>) -> Option<Thumbnail> {
+        if let Some(thumbnail) = self.load(&path) {
+            return Some(thumbnail);
+        }
+        let stamp = FileStamp::of_file(&path)?;
+        let thumbnail = Thumbnail::from_wav(WavReader::open(&path)?)?;
+        let _ = fs::write(self.sidecar_path(&path), thumbnail.to_bytes(stamp));
+        Some(thumbnail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantize::SampleFormat;
+    use crate::wav::WavWriter;
+
+    fn noise(num_channels: usize, num_samples: usize) -> Buffer<f32> {
+        let mut buffer = Buffer::allocate(Channels::from(num_channels), Samples::from(num_samples));
+        for (channel, samples) in buffer.iter_chans_mut().enumerate() {
+            for (index, sample) in samples.iter_mut().enumerate() {
+                *sample =
+                    (((index * 7919 + channel * 104729) % 2001) as f32 - 1000.0) / 1000.0 * 0.9;
+            }
+        }
+        buffer
+    }
+
+    #[test]
+    fn levels_cover_the_audio_down_to_one_peak() {
+        let thumbnail = Thumbnail::from_buffer(&noise(2, 100_000), SampleRate::from(48000));
+
+        // 391 peaks, then 98, 25, 7, 2 and 1.
+        assert_eq!(thumbnail.num_levels(), 6);
+        assert_eq!(thumbnail.levels[0][1].len(), 391);
+        assert_eq!(thumbnail.levels[5][0].len(), 1);
+    }
+
+    #[test]
+    fn peaks_match_the_audio_at_every_zoom() {
+        let buffer = noise(1, 200_000);
+        let thumbnail = Thumbnail::from_buffer(&buffer, SampleRate::from(48000));
+
+        for num_peaks in [1, 8, 128] {
+            let section = SampleSection {
+                start: Samples::from(0),
+                length: Samples::from(SAMPLES_PER_PEAK * 512),
+            };
+            let peaks = thumbnail.peaks(0, section, num_peaks);
+            let length = section.length.as_usize() / num_peaks;
+            for (index, peak) in peaks.iter().enumerate() {
+                let exact = Peak::of(&buffer.chan(0)[index * length..(index + 1) * length]);
+                assert_eq!(*peak, exact);
+            }
+        }
+    }
+
+    #[test]
+    fn peaks_of_one_sided_audio_do_not_include_zero() {
+        let mut buffer = Buffer::<f32>::allocate(Channels::from(1), Samples::from(512));
+        buffer.chan_mut(0)[..256].fill(0.5);
+        buffer.chan_mut(0)[256..].fill(-0.25);
+        buffer.chan_mut(0)[300] = -0.75;
+
+        let thumbnail = Thumbnail::from_buffer(&buffer, SampleRate::from(48000));
+
+        assert_eq!(thumbnail.levels[0][0][0], Peak { min: 0.5, max: 0.5 });
+        assert_eq!(
+            thumbnail.levels[0][0][1],
+            Peak {
+                min: -0.75,
+                max: -0.25
+            }
+        );
+    }
+
+    #[test]
+    fn peaks_past_the_end_are_silent() {
+        let thumbnail = Thumbnail::from_buffer(&noise(1, 1000), SampleRate::from(48000));
+        let section = SampleSection {
+            start: Samples::from(5000),
+            length: Samples::from(1000),
+        };
+
+        assert_eq!(thumbnail.peaks(0, section, 4), vec![Peak::default(); 4]);
+    }
+
+    #[test]
+    fn bytes_only_load_for_the_same_file() {
+        let thumbnail = Thumbnail::from_buffer(&noise(2, 10_000), SampleRate::from(44100));
+        let stamp = FileStamp {
+            length: 1234,
+            modified: 5678,
+        };
+
+        let bytes = thumbnail.to_bytes(stamp);
+        let loaded = Thumbnail::from_bytes(&bytes, stamp).unwrap();
+
+        assert_eq!(bytes.len(), HEADER_LENGTH + 2 * 40 * 4);
+        assert_eq!(loaded.num_channels(), Channels::from(2));
+        assert_eq!(loaded.num_samples(), Samples::from(10_000));
+        assert_eq!(loaded.sample_rate(), SampleRate::from(44100));
+        for (a, b) in loaded
+            .levels
+            .iter()
+            .flatten()
+            .flatten()
+            .zip(thumbnail.levels.iter().flatten().flatten())
+        {
+            assert!((a.min - b.min).abs() < 1e-4 && (a.max - b.max).abs() < 1e-4);
+        }
+        let changed = FileStamp {
+            modified: 5679,
+            ..stamp
+        };
+        assert!(Thumbnail::from_bytes(&bytes, changed).is_none());
+        assert!(Thumbnail::from_bytes(&bytes[..bytes.len() - 1], stamp).is_none());
+    }
+
+    #[test]
+    fn sidecar_names_have_a_fixed_hash() {
+        let cache = ThumbnailCache::in_directory("cache");
+
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(
+            cache.sidecar_path("audio/a"),
+            Path::new("cache").join(format!("a-{:016x}.peaks", fnv1a(b"audio/a")))
+        );
+    }
+
+    #[test]
+    fn empty_thumbnails_survive_a_round_trip() {
+        let buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(0));
+        let thumbnail = Thumbnail::from_buffer(&buffer, SampleRate::from(48000));
+        let stamp = FileStamp {
+            length: 44,
+            modified: 1,
+        };
+
+        let bytes = thumbnail.to_bytes(stamp);
+        let loaded = Thumbnail::from_bytes(&bytes, stamp).unwrap();
+
+        assert_eq!(loaded, thumbnail);
+        assert_eq!(loaded.num_channels(), Channels::from(2));
+        // A header that claims more peaks than fit in memory is refused as well.
+        let mut corrupt = bytes;
+        corrupt[25..29].copy_from_slice(&u32::MAX.to_le_bytes());
+        corrupt[29..37].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(Thumbnail::from_bytes(&corrupt, stamp).is_none());
+    }
+
+    #[test]
+    fn caches_the_thumbnails_of_files() {
+        let directory = std::env::temp_dir().join(format!("rabu-thumbnail-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("noise.wav");
+        let buffer = noise(2, 300_000);
+        let mut writer = WavWriter::create(
+            &path,
+            SampleRate::from(48000),
+            Channels::from(2),
+            SampleFormat::Float32,
+        )
+        .unwrap();
+        writer.write(&buffer);
+        drop(writer);
+        let cache = ThumbnailCache::new();
+        let elsewhere = ThumbnailCache::in_directory(&directory);
+
+        assert!(cache.load(&path).is_none());
+        let created = cache.load_or_create(&path).unwrap();
+        let loaded = cache.load(&path).unwrap();
+
+        assert_eq!(cache.sidecar_path(&path), directory.join("noise.wav.peaks"));
+        assert_ne!(elsewhere.sidecar_path(&path), cache.sidecar_path(&path));
+        assert_eq!(
+            created,
+            Thumbnail::from_buffer(&buffer, SampleRate::from(48000))
+        );
+        assert_eq!(loaded.num_levels(), created.num_levels());
+        assert!(elsewhere.load(&path).is_none());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,220 @@
+//! This module contains offline time-stretching of whole buffers, which changes the length of
+//! the audio without changing its pitch, e.g. to fit a clip to a number of bars. It uses WSOLA
+//! (waveform similarity overlap-add): the output is built from overlapping windowed frames of
+//! the input, and every frame is taken from where it lines up best with the one before it, so
+//! the waveform continues without phase jumps. This works best for monophonic and rhythmic
+//! material; stretching far beyond a factor of 2 makes transients smear.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::time_stretch::{stretch, stretch_to};
+//! use rabu::units::{Channels, Duration, SampleRate, Samples};
+//!
+//! let sample_rate = SampleRate::from(8000);
+//! let clip = Buffer::<f32>::allocate(Channels::from(2), Samples::from(8000));
+//!
+//! let slower = stretch(&clip, 1.5, sample_rate);
+//! assert_eq!(slower.num_samples(), Samples::from(12000));
+//!
+//! // Fits the clip to 4 bars of 4/4 at 120 BPM, which is 8 seconds.
+//! let bars = 4.0 * 4.0 * 60.0 / 120.0;
+//! let fitted = stretch_to(&clip, Duration::from_secs_f64(bars), sample_rate);
+//! assert_eq!(fitted.num_samples(), Samples::from(64000));
+//! ```
+
+use crate::buffer::Buffer;
+use crate::resample::sample_at;
+use crate::sample::Sample;
+use crate::units::{Duration, SampleRate, Samples};
+use crate::windows::Window;
+
+/// The length of the frames in seconds, which is longer than a period of most pitched sounds.
+const FRAME_LENGTH: f64 = 0.04;
+
+/// Returns the number of samples that stretching `input_length` samples by the factor results
+/// in, rounded to the nearest sample.
+pub fn stretched_length(input_length: Samples, factor: f64) -> Samples {
+    Samples::from((input_length.as_f64() * factor).round() as u64)
+}
+
+/// Stretches the buffer by the factor, keeping its pitch: 2 makes it twice as long, 0.5 half as
+/// long. All channels are stretched the same way, so the stereo image stays intact. The output
+/// has `stretched_length` samples.
+/// This will panic if the factor is not above 0.
+pub fn stretch<T: Sample>(input: &Buffer<T>, factor: f64, sample_rate: SampleRate) -> Buffer<T> {
+    assert!(factor > 0.0, "the stretch factor must be above 0");
+    let length = stretched_length(input.num_samples(), factor).as_usize();
+
+    // Periodic Hann windows at half overlap add up to exactly 1.
+    let frame_length = ((FRAME_LENGTH * sample_rate.as_f64()) as usize / 2 * 2).max(2);
+    let hop = frame_length / 2;
+    let tolerance = frame_length / 4;
+    let window: Vec<f64> = (0..frame_length)
+        .map(|n| Window::Hann.periodic_value(n, frame_length))
+        .collect();
+
+    let mut output = vec![vec![0.0; length + frame_length]; input.num_channels().as_usize()];
+    // The first frame starts half a frame before the output, so every sample of the output is
+    // covered by two frames.
+    let mut previous = None;
+    let mut start = -(hop as isize);
+    while start < length as isize {
+        let nominal = (start as f64 / factor).round() as isize;
+        let position = match previous {
+            None => nominal,
+            Some(previous) => {
+                best_position(input, nominal, previous + hop as isize, tolerance, hop)
+            }
+        };
+
+        for (input, output) in input.iter_chans().zip(output.iter_mut()) {
+            for (n, weight) in window.iter().enumerate() {
+                let Ok(index) = usize::try_from(start + n as isize) else {
+                    continue;
+                };
+                output[index] += weight * sample_at(input, position + n as isize);
+            }
+        }
+
+        previous = Some(position);
+        start += hop as isize;
+    }
+
+    let mut buffer = Buffer::allocate(input.num_channels(), Samples::from(length));
+    for (output, channel) in output.iter().zip(buffer.iter_chans_mut()) {
+        for (sample, value) in channel.iter_mut().zip(output) {
+            *sample = T::from_f64(*value);
+        }
+    }
+    buffer
+}
+
+/// Stretches the buffer to the duration, keeping its pitch.
+/// This will panic if the buffer or the duration is empty.
+pub fn stretch_to<T: Sample>(
+    input: &Buffer<T>,
+    duration: Duration,
+    sample_rate: SampleRate,
+) -> Buffer<T> {
+    assert!(
+        input.num_samples() > Samples::from(0),
+        "an empty buffer can't be stretched"
+    );
+    let factor = duration.to_samples(sample_rate).as_f64() / input.num_samples().as_f64();
+    stretch(input, factor, sample_rate)
+}
+
+/// Finds the position within the tolerance around the nominal position where the input looks
+/// most like the natural continuation of the previous frame, over the part where the frames
+/// overlap. The nominal position wins ties, so a factor of 1 gives back the input.
+fn best_position<T: Sample>(
+    input: &Buffer<T>,
+    nominal: isize,
+    continuation: isize,
+    tolerance: usize,
+    overlap: usize,
+) -> isize {
+    let similarity = |position: isize| {
+        let mut correlation = 0.0;
+        let mut energy = 0.0;
+        for channel in input.iter_chans() {
+            for n in 0..overlap as isize {
+                let candidate = sample_at(channel, position + n);
+                correlation += candidate * sample_at(channel, continuation + n);
+                energy += candidate * candidate;
+            }
+        }
+        if energy > 0.0 {
+            correlation / energy.sqrt()
+        } else {
+            0.0
+        }
+    };
+
+    let tolerance = tolerance as isize;
+    let mut best = (nominal, similarity(nominal));
+    for position in nominal - tolerance..=nominal + tolerance {
+        let score = similarity(position);
+        if score > best.1 {
+            best = (position, score);
+        }
+    }
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::noise::{NoiseColor, NoiseGenerator};
+    use crate::pitch::PitchDetector;
+    use crate::units::{Channels, Frequency};
+
+    const SAMPLE_RATE: u32 = 8000;
+
+    fn sine(frequency: f64, length: usize) -> Buffer<f32> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(length));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = (2.0 * PI * frequency * n as f64 / SAMPLE_RATE as f64).sin() as f32;
+        }
+        buffer
+    }
+
+    #[test_case(2.0, 8000, 16000; "twice as long")]
+    #[test_case(0.5, 8000, 4000; "half as long")]
+    #[test_case(1.25, 1001, 1251; "rounds to the nearest sample")]
+    fn length_follows_the_factor(factor: f64, input: u64, output: u64) {
+        let input = Buffer::<f32>::allocate(Channels::from(2), Samples::from(input));
+        let output_buffer = stretch(&input, factor, SampleRate::from(SAMPLE_RATE));
+
+        assert_eq!(output_buffer.num_samples(), Samples::from(output));
+        assert_eq!(output_buffer.num_channels(), Channels::from(2));
+    }
+
+    #[test]
+    fn factor_of_one_gives_back_the_input() {
+        let mut noise = NoiseGenerator::new(NoiseColor::White, 7);
+        let mut input = Buffer::<f32>::allocate(Channels::from(2), Samples::from(2000));
+        noise.render(&mut input);
+
+        let output = stretch(&input, 1.0, SampleRate::from(SAMPLE_RATE));
+
+        for (output, input) in output.iter_chans().zip(input.iter_chans()) {
+            for (output, input) in output.iter().zip(input) {
+                assert!((output - input).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test_case(2.0; "slower")]
+    #[test_case(0.6; "faster")]
+    fn keeps_the_pitch(factor: f64) {
+        let sample_rate = SampleRate::from(SAMPLE_RATE);
+        let output = stretch(&sine(220.0, 8000), factor, sample_rate);
+
+        let detector =
+            PitchDetector::new(sample_rate, Frequency::from(100.0), Frequency::from(1000.0));
+        let middle = output.num_samples().as_usize() / 2;
+        let window = detector.window_length();
+        let mut part = Buffer::<f32>::allocate(Channels::from(1), window);
+        part.chan_mut(0)
+            .copy_from_slice(&output.chan(0)[middle..middle + window.as_usize()]);
+        let pitch = detector.detect(&part).unwrap();
+
+        assert!((pitch.frequency.as_f64() - 220.0).abs() < 2.0);
+    }
+
+    #[test]
+    fn stretches_to_the_duration() {
+        let input = sine(220.0, 3000);
+        let output = stretch_to(
+            &input,
+            Duration::from_secs_f64(0.5),
+            SampleRate::from(SAMPLE_RATE),
+        );
+
+        assert_eq!(output.num_samples(), Samples::from(4000));
+    }
+}
@@ -0,0 +1,297 @@
+//! This module contains transfer function estimation, which measures what a device or an
+//! unknown plugin does to a signal: play a reference (e.g. white noise) through it, record
+//! the result, and compare the two. Both signals are cut into overlapping windowed frames,
+//! and the cross spectrum between them is averaged over the frames and divided by the
+//! spectrum of the reference (the H1 estimate), so noise in the recording averages out. The
+//! coherence tells per bin how much of the recording is explained by the reference: close to
+//! 1 where the estimate can be trusted, lower where noise or distortion take over.
+//! ```rust
+//! use rabu::signals::white_noise;
+//! use rabu::transfer_function::estimate_transfer_function;
+//! use rabu::units::{Channels, Frequency, SampleRate, Samples};
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let reference = white_noise::<f32>(Channels::from(1), Samples::from(48000), 1);
+//! let mut recording = reference.clone();
+//! recording.map_samples(|sample| 0.5 * sample);
+//!
+//! let response = estimate_transfer_function(
+//!     reference.chan(0),
+//!     recording.chan(0),
+//!     sample_rate,
+//!     Samples::from(1024),
+//! );
+//!
+//! let (_, magnitude) = response.magnitude_at(Frequency::from(1000.0));
+//! assert!((magnitude.as_f64() + 6.02).abs() < 0.01);
+//! ```
+
+use crate::fft::{Complex, RealFft};
+use crate::sample::Sample;
+use crate::units::{Decibels, Frequency, FrequencyBin, SampleRate, Samples};
+use crate::windows::Window;
+
+/// The estimated frequency response between a reference and a recording of it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferFunction {
+    sample_rate: SampleRate,
+    fft_size: Samples,
+    bins: Vec<Complex>,
+    coherence: Vec<f64>,
+}
+
+impl TransferFunction {
+    /// Returns the size of the FFT the response was estimated with.
+    pub fn fft_size(&self) -> Samples {
+        self.fft_size
+    }
+
+    /// Returns the number of bins, from DC up to Nyquist.
+    pub fn num_bins(&self) -> Samples {
+        Samples::from(self.bins.len())
+    }
+
+    /// Returns the complex response of every bin, from DC up to Nyquist.
+    pub fn bins(&self) -> &[Complex] {
+        &self.bins
+    }
+
+    /// Returns the frequency and gain of every bin, from DC up to Nyquist.
+    pub fn magnitude(&self) -> impl Iterator<Item = (Frequency, Decibels)> + '_ {
+        self.with_frequencies(|bin| Decibels::from_gain(bin.norm()))
+    }
+
+    /// Returns the frequency and phase shift in radians of every bin, from DC up to Nyquist.
+    /// The phase is wrapped between -π and π.
+    pub fn phase(&self) -> impl Iterator<Item = (Frequency, f64)> + '_ {
+        self.with_frequencies(Complex::arg)
+    }
+
+    /// Returns the frequency and coherence of every bin, from DC up to Nyquist, between 0 and
+    /// 1.
+    pub fn coherence(&self) -> impl Iterator<Item = (Frequency, f64)> + '_ {
+        let frequencies = self.with_frequencies(|_| ());
+        frequencies
+            .zip(&self.coherence)
+            .map(|((frequency, _), coherence)| (frequency, *coherence))
+    }
+
+    /// Returns the frequency and gain of the bin closest to the frequency.
+    pub fn magnitude_at(&self, frequency: Frequency) -> (Frequency, Decibels) {
+        let bin = self.nearest(frequency);
+        (
+            bin.to_frequency(self.sample_rate, self.fft_size),
+            Decibels::from_gain(self.bins[bin.as_usize()].norm()),
+        )
+    }
+
+    /// Returns the frequency and phase shift in radians of the bin closest to the frequency.
+    pub fn phase_at(&self, frequency: Frequency) -> (Frequency, f64) {
+        let bin = self.nearest(frequency);
+        (
+            bin.to_frequency(self.sample_rate, self.fft_size),
+            self.bins[bin.as_usize()].arg(),
+        )
+    }
+
+    fn nearest(&self, frequency: Frequency) -> FrequencyBin {
+        let bin = FrequencyBin::nearest(frequency, self.sample_rate, self.fft_size);
+        FrequencyBin::from(bin.as_usize().min(self.bins.len() - 1))
+    }
+
+    fn with_frequencies<'a, V>(
+        &'a self,
+        value: impl Fn(&Complex) -> V + 'a,
+    ) -> impl Iterator<Item = (Frequency, V)> + 'a {
+        self.bins.iter().enumerate().map(move |(index, bin)| {
+            (
+                FrequencyBin::from(index).to_frequency(self.sample_rate, self.fft_size),
+                value(bin),
+            )
+        })
+    }
+}
+
+/// Estimates the transfer function from the reference to the recording, with Hann windowed
+/// frames of the FFT size that overlap by half. The recording should be lined up with the
+/// reference: a delay of more than a small part of the FFT size smears the estimate and lowers
+/// the coherence, so compensate for the latency of the device first. Longer signals average
+/// more frames, which gives a cleaner estimate; larger FFT sizes give a finer frequency
+/// resolution. A signal shorter than the FFT size is padded with silence.
+/// This will panic if the signals don't have the same length, or if the FFT size is not a
+/// power of two.
+pub fn estimate_transfer_function<T: Sample, U: Sample>(
+    reference: &[T],
+    recording: &[U],
+    sample_rate: SampleRate,
+    fft_size: Samples,
+) -> TransferFunction {
+    assert_eq!(reference.len(), recording.len());
+    let size = fft_size.as_usize();
+    let mut fft = RealFft::new(fft_size);
+    let num_bins = fft.num_bins().as_usize();
+    let mut window = vec![0.0; size];
+    Window::Hann.fill_periodic(&mut window);
+
+    let mut frame = vec![0.0; size];
+    let mut reference_bins = vec![Complex::default(); num_bins];
+    let mut recording_bins = vec![Complex::default(); num_bins];
+    let mut cross = vec![Complex::default(); num_bins];
+    let mut reference_power = vec![0.0; num_bins];
+    let mut recording_power = vec![0.0; num_bins];
+
+    let hop_size = (size / 2).max(1);
+    let num_frames = reference.len().saturating_sub(size) / hop_size + 1;
+    for start in (0..num_frames).map(|index| index * hop_size) {
+        window_frame(reference, start, &window, &mut frame);
+        fft.forward(&frame, &mut reference_bins);
+        window_frame(recording, start, &window, &mut frame);
+        fft.forward(&frame, &mut recording_bins);
+
+        for (bin, (x, y)) in reference_bins.iter().zip(&recording_bins).enumerate() {
+            cross[bin] += x.conj() * *y;
+            reference_power[bin] += x.norm_sqr();
+            recording_power[bin] += y.norm_sqr();
+        }
+    }
+
+    let (bins, coherence) = cross
+        .iter()
+        .zip(reference_power.iter().zip(&recording_power))
+        .map(|(cross, (reference, recording))| {
+            if *reference > 0.0 && *recording > 0.0 {
+                (
+                    *cross * (1.0 / reference),
+                    cross.norm_sqr() / (reference * recording),
+                )
+            } else {
+                (Complex::default(), 0.0)
+            }
+        })
+        .unzip();
+
+    TransferFunction {
+        sample_rate,
+        fft_size,
+        bins,
+        coherence,
+    }
+}
+
+/// Copies the frame that starts at `start` into `frame` with the window applied, with silence
+/// past the end of the signal.
+fn window_frame<T: Sample>(signal: &[T], start: usize, window: &[f64], frame: &mut [f64]) {
+    frame.fill(0.0);
+    let signal = signal.get(start..).unwrap_or_default();
+    for ((value, sample), gain) in frame.iter_mut().zip(signal).zip(window) {
+        *value = sample.to_f64() * gain;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::biquad::{peak_coefficients, MultiBiquad};
+    use crate::signals::white_noise;
+    use crate::units::Channels;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn noise(seed: u64) -> Vec<f64> {
+        white_noise::<f64>(Channels::from(1), Samples::from(SAMPLE_RATE), seed)
+            .chan(0)
+            .to_vec()
+    }
+
+    #[test]
+    fn delay_shows_up_as_a_linear_phase() {
+        let reference = noise(1);
+        let delay = 3;
+        let recording: Vec<f64> = (0..reference.len())
+            .map(|n| n.checked_sub(delay).map_or(0.0, |n| 2.0 * reference[n]))
+            .collect();
+
+        let response = estimate_transfer_function(
+            &reference,
+            &recording,
+            SampleRate::from(SAMPLE_RATE),
+            Samples::from(1024),
+        );
+
+        for (frequency, phase) in response.phase().skip(1).take(100) {
+            let expected = -2.0 * PI * frequency.as_f64() * delay as f64 / SAMPLE_RATE as f64;
+            assert!((phase - expected).abs() < 0.01, "{frequency:?}");
+        }
+        let (_, magnitude) = response.magnitude_at(Frequency::from(5000.0));
+        assert!((magnitude.as_f64() - 6.02).abs() < 0.1);
+    }
+
+    #[test]
+    fn finds_the_response_of_a_filter() {
+        let sample_rate = SampleRate::from(SAMPLE_RATE);
+        let coefficients = peak_coefficients(
+            sample_rate,
+            Frequency::from(2000.0),
+            1.0,
+            Decibels::from(9.0),
+        );
+        let reference = white_noise::<f64>(Channels::from(1), Samples::from(SAMPLE_RATE), 7);
+        let mut recording = reference.clone();
+        MultiBiquad::new(coefficients, Channels::from(1)).process(&mut recording);
+
+        let response = estimate_transfer_function(
+            reference.chan(0),
+            recording.chan(0),
+            sample_rate,
+            Samples::from(2048),
+        );
+
+        for frequency in [200.0, 1000.0, 2000.0, 4000.0, 12000.0] {
+            let (frequency, magnitude) = response.magnitude_at(Frequency::from(frequency));
+            let expected = coefficients.magnitude_at(frequency, sample_rate).as_f64();
+            assert!((magnitude.as_f64() - expected).abs() < 0.1, "{frequency:?}");
+        }
+    }
+
+    #[test]
+    fn noise_in_the_recording_lowers_the_coherence() {
+        let reference = noise(1);
+        let unrelated = noise(2);
+        let clean = reference.clone();
+        let noisy: Vec<f64> = reference
+            .iter()
+            .zip(&unrelated)
+            .map(|(a, b)| a + b)
+            .collect();
+
+        let mean_coherence = |recording: &[f64]| {
+            let response = estimate_transfer_function(
+                &reference,
+                recording,
+                SampleRate::from(SAMPLE_RATE),
+                Samples::from(512),
+            );
+            let total: f64 = response.coherence().map(|(_, coherence)| coherence).sum();
+            total / response.num_bins().as_f64()
+        };
+
+        assert!(mean_coherence(&clean) > 0.99);
+        assert!((mean_coherence(&noisy) - 0.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn silent_reference_gives_an_empty_response() {
+        let response = estimate_transfer_function(
+            &[0.0_f32; 100],
+            &[0.5_f32; 100],
+            SampleRate::from(SAMPLE_RATE),
+            Samples::from(256),
+        );
+
+        assert_eq!(response.num_bins(), Samples::from(129));
+        assert!(response.bins().iter().all(|bin| *bin == Complex::default()));
+        assert!(response.coherence().all(|(_, coherence)| coherence == 0.0));
+    }
+}
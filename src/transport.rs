@@ -0,0 +1,351 @@
+//! This module contains a transport: the play state and the playhead of a player or sequencer.
+//! Every block, the transport advances by the block length and tells which parts of the
+//! timeline the block covers. Without a loop, that's one region. With a loop, the playhead
+//! jumps back to the loop start when it reaches the loop end, which splits the block into a
+//! region before and after the jump (or more, when the loop is shorter than the block).
+//! ```rust
+//! use rabu::transport::Transport;
+//! use rabu::units::{Duration, SampleRate, SampleSection, Samples, TimePoint, TimeSection};
+//!
+//! let mut transport = Transport::new(SampleRate::from(1000));
+//! transport.set_loop(Some(TimeSection {
+//!     start: TimePoint::from_secs_f64(1.0),
+//!     duration: Duration::from_secs_f64(1.0),
+//! }));
+//! transport.seek(Samples::from(1900));
+//! transport.play();
+//!
+//! let regions: Vec<_> = transport.advance(Samples::from(256)).collect();
+//!
+//! assert_eq!(regions[0].section, SampleSection { start: Samples::from(1900), length: Samples::from(100) });
+//! assert_eq!(regions[1].section, SampleSection { start: Samples::from(1000), length: Samples::from(156) });
+//! assert_eq!(regions[1].offset, Samples::from(100));
+//! assert_eq!(transport.position(), Samples::from(1156));
+//! ```
+
+use crate::units::{SampleRate, SampleSection, Samples, TimePoint, TimeSection};
+
+/// A part of the timeline that a block covers.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TransportRegion {
+    /// The samples of the timeline.
+    pub section: SampleSection,
+    /// Where in the block the region starts.
+    pub offset: Samples,
+}
+
+/// The play state and the playhead, with an optional loop.
+#[derive(Clone, Debug)]
+pub struct Transport {
+    sample_rate: SampleRate,
+    playing: bool,
+    position: Samples,
+    loop_section: Option<TimeSection>,
+}
+
+impl Transport {
+    /// Creates a new transport that is stopped at the start, without a loop.
+    pub fn new(sample_rate: SampleRate) -> Self {
+        Self {
+            sample_rate,
+            playing: false,
+            position: Samples::from(0),
+            loop_section: None,
+        }
+    }
+
+    /// Returns the sample rate.
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// Changes the sample rate, keeping the playhead at the same time.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        let time = self.time();
+        self.sample_rate = sample_rate;
+        self.seek_time(time);
+    }
+
+    /// Tells whether the transport is playing.
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Starts playing from the playhead.
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    /// Stops playing, leaving the playhead where it is.
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    /// Returns the playhead.
+    pub fn position(&self) -> Samples {
+        self.position
+    }
+
+    /// Returns the playhead as a time.
+    pub fn time(&self) -> TimePoint {
+        TimePoint::from(self.position.to_seconds(self.sample_rate))
+    }
+
+    /// Moves the playhead.
+    pub fn seek(&mut self, position: Samples) {
+        self.position = position;
+    }
+
+    /// Moves the playhead to the sample nearest to the time.
+    pub fn seek_time(&mut self, time: TimePoint) {
+        self.position = time.as_seconds().to_samples(self.sample_rate);
+    }
+
+    /// Returns the loop.
+    pub fn loop_section(&self) -> Option<TimeSection> {
+        self.loop_section
+    }
+
+    /// Changes the loop, or turns looping off with `None`. The playhead only loops when it's
+    /// before the loop end, so it can still play on past a loop it was moved beyond.
+    pub fn set_loop(&mut self, loop_section: Option<TimeSection>) {
+        self.loop_section = loop_section;
+    }
+
+    /// Moves the playhead by the block length, and returns the regions of the timeline the
+    /// block covers, in order. A stopped transport doesn't move and covers nothing.
+    pub fn advance(&mut self, block: Samples) -> impl Iterator<Item = TransportRegion> {
+        let block = if self.playing {
+            block
+        } else {
+            Samples::from(0)
+        };
+        let (position, regions) =
+            advance_position(self.position, block, self.loop_section, self.sample_rate);
+        self.position = position;
+        regions
+    }
+}
+
+/// Advances a playhead at the position by the block length, within the loop if there is one,
+/// like `Transport::advance` does. Gives back the position after the block, and the regions of
+/// the timeline the block covers, in order: one, two when the block wraps around the loop end,
+/// or more when the loop is shorter than the block.
+/// ```
+/// use rabu::transport::advance_position;
+/// use rabu::units::{Duration, SampleRate, Samples, TimePoint, TimeSection};
+///
+/// let loop_section = TimeSection {
+///     start: TimePoint::from_secs_f64(0.0),
+///     duration: Duration::from_secs_f64(1.0),
+/// };
+/// let (position, regions) = advance_position(
+///     Samples::from(900),
+///     Samples::from(200),
+///     Some(loop_section),
+///     SampleRate::from(1000),
+/// );
+///
+/// let regions: Vec<_> = regions.map(|region| (region.section.start, region.offset)).collect();
+/// assert_eq!(regions, [(Samples::from(900), Samples::from(0)), (Samples::from(0), Samples::from(100))]);
+/// assert_eq!(position, Samples::from(100));
+/// ```
+pub fn advance_position(
+    position: Samples,
+    block: Samples,
+    loop_section: Option<TimeSection>,
+    sample_rate: SampleRate,
+) -> (Samples, impl Iterator<Item = TransportRegion>) {
+    let regions = Regions {
+        position,
+        remaining: block,
+        offset: Samples::from(0),
+        loop_section: loop_section
+            .map(|section| SampleSection::from_time_section(section, sample_rate))
+            .filter(|section| section.length > Samples::from(0)),
+    };
+    let mut position = position;
+    if let Some(last) = regions.clone().last() {
+        position = last.section.end();
+        if let Some(loop_section) = regions.loop_section {
+            if position == loop_section.end() {
+                position = loop_section.start;
+            }
+        }
+    }
+    (position, regions)
+}
+
+/// The regions of a block, which are worked out one at a time, so advancing doesn't allocate.
+#[derive(Clone, Debug)]
+struct Regions {
+    position: Samples,
+    remaining: Samples,
+    offset: Samples,
+    loop_section: Option<SampleSection>,
+}
+
+impl Iterator for Regions {
+    type Item = TransportRegion;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == Samples::from(0) {
+            return None;
+        }
+        let looping = self
+            .loop_section
+            .filter(|section| self.position < section.end());
+        let length = match looping {
+            Some(section) => self.remaining.min(section.end() - self.position),
+            None => self.remaining,
+        };
+        let region = TransportRegion {
+            section: SampleSection {
+                start: self.position,
+                length,
+            },
+            offset: self.offset,
+        };
+
+        self.position += length;
+        self.offset += length;
+        self.remaining -= length;
+        if let Some(section) = looping {
+            if self.position == section.end() {
+                self.position = section.start;
+            }
+        }
+        Some(region)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Duration;
+
+    fn transport(loop_start: f64, loop_length: f64) -> Transport {
+        let mut transport = Transport::new(SampleRate::from(1000));
+        transport.set_loop(Some(TimeSection {
+            start: TimePoint::from_secs_f64(loop_start),
+            duration: Duration::from_secs_f64(loop_length),
+        }));
+        transport.play();
+        transport
+    }
+
+    fn sections(regions: impl Iterator<Item = TransportRegion>) -> Vec<(u64, u64, u64)> {
+        regions
+            .map(|region| {
+                (
+                    region.section.start.as_u64(),
+                    region.section.length.as_u64(),
+                    region.offset.as_u64(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plays_straight_through_without_a_loop() {
+        let mut transport = Transport::new(SampleRate::from(1000));
+        transport.play();
+
+        assert_eq!(
+            sections(transport.advance(Samples::from(64))),
+            vec![(0, 64, 0)]
+        );
+        assert_eq!(
+            sections(transport.advance(Samples::from(64))),
+            vec![(64, 64, 0)]
+        );
+        assert_eq!(transport.position(), Samples::from(128));
+    }
+
+    #[test]
+    fn stopped_transport_stays_put() {
+        let mut transport = Transport::new(SampleRate::from(1000));
+        transport.seek(Samples::from(10));
+
+        assert_eq!(transport.advance(Samples::from(64)).count(), 0);
+        assert_eq!(transport.position(), Samples::from(10));
+    }
+
+    #[test]
+    fn block_ending_on_the_loop_end_jumps_back() {
+        let mut transport = transport(0.1, 0.1);
+        transport.seek(Samples::from(136));
+
+        assert_eq!(
+            sections(transport.advance(Samples::from(64))),
+            vec![(136, 64, 0)]
+        );
+        assert_eq!(transport.position(), Samples::from(100));
+    }
+
+    #[test]
+    fn loop_shorter_than_the_block_wraps_several_times() {
+        let mut transport = transport(0.1, 0.02);
+        transport.seek(Samples::from(110));
+
+        assert_eq!(
+            sections(transport.advance(Samples::from(64))),
+            vec![(110, 10, 0), (100, 20, 10), (100, 20, 30), (100, 14, 50)]
+        );
+        assert_eq!(transport.position(), Samples::from(114));
+    }
+
+    #[test]
+    fn playhead_before_the_loop_runs_into_it() {
+        let mut transport = transport(0.1, 0.05);
+        transport.seek(Samples::from(90));
+
+        assert_eq!(
+            sections(transport.advance(Samples::from(100))),
+            vec![(90, 60, 0), (100, 40, 60)]
+        );
+    }
+
+    #[test]
+    fn playhead_past_the_loop_plays_on() {
+        let mut transport = transport(0.1, 0.05);
+        transport.seek(Samples::from(500));
+
+        assert_eq!(
+            sections(transport.advance(Samples::from(64))),
+            vec![(500, 64, 0)]
+        );
+    }
+
+    #[test]
+    fn advances_a_position_without_a_transport() {
+        let loop_section = TimeSection {
+            start: TimePoint::from_secs_f64(0.1),
+            duration: Duration::from_secs_f64(0.1),
+        };
+        let sample_rate = SampleRate::from(1000);
+
+        let (position, regions) = advance_position(
+            Samples::from(180),
+            Samples::from(32),
+            Some(loop_section),
+            sample_rate,
+        );
+        assert_eq!(sections(regions), vec![(180, 20, 0), (100, 12, 20)]);
+        assert_eq!(position, Samples::from(112));
+
+        let (position, regions) =
+            advance_position(Samples::from(180), Samples::from(32), None, sample_rate);
+        assert_eq!(sections(regions), vec![(180, 32, 0)]);
+        assert_eq!(position, Samples::from(212));
+    }
+
+    #[test]
+    fn seeking_by_time_rounds_to_samples() {
+        let mut transport = Transport::new(SampleRate::from(48000));
+        transport.seek_time(TimePoint::from_secs_f64(1.5));
+
+        assert_eq!(transport.position(), Samples::from(72000));
+        assert_eq!(transport.time(), TimePoint::from_secs_f64(1.5));
+    }
+}
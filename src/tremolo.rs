@@ -0,0 +1,348 @@
+//! This module contains a tremolo, which modulates the gain of audio with an LFO, and can also
+//! pan stereo audio back and forth (auto-pan). The LFO runs at a rate in Hz, or in sync with
+//! the tempo at a note value. In sync, the LFO follows the position of the timeline, so it
+//! lands on the same place of the waveform at the same place of the song every time.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::osc::Waveform;
+//! use rabu::tremolo::{LfoRate, Tremolo, TremoloMode};
+//! use rabu::units::{Channels, NormalizedValue, NoteValue, SampleRate, Samples, Tempo};
+//!
+//! let mut tremolo = Tremolo::new(SampleRate::from(48000), Channels::from(2));
+//! tremolo.set_mode(TremoloMode::AutoPan);
+//! tremolo.set_waveform(Waveform::Triangle);
+//! tremolo.set_rate(LfoRate::Synced(NoteValue::new(8).dotted()));
+//! tremolo.set_tempo(Tempo::from(128.0));
+//! tremolo.set_depth(NormalizedValue::from(0.8));
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! tremolo.process(&mut buffer);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::osc::{Oscillator, Waveform};
+use crate::panning::balance;
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::sample::Sample;
+use crate::units::{
+    BufferSize, Channels, Frequency, NormalizedValue, NoteValue, Pan, SampleRate, Tempo,
+};
+
+/// What the LFO modulates.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TremoloMode {
+    /// The gain of every channel, which dips by the depth at the bottom of the LFO.
+    Tremolo,
+    /// The balance of stereo audio, which swings to either side by the depth.
+    AutoPan,
+}
+
+/// The rate of an LFO.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LfoRate {
+    /// A free running rate.
+    Hertz(Frequency),
+    /// A cycle every note value at the tempo.
+    Synced(NoteValue),
+}
+
+impl LfoRate {
+    /// Returns the frequency of the LFO at the tempo.
+    pub fn to_frequency(&self, tempo: Tempo) -> Frequency {
+        match self {
+            LfoRate::Hertz(frequency) => *frequency,
+            LfoRate::Synced(note_value) => note_value.to_frequency(tempo),
+        }
+    }
+}
+
+/// Modulates the gain or the balance of audio with an LFO.
+#[derive(Clone, Debug)]
+pub struct Tremolo {
+    mode: TremoloMode,
+    waveform: Waveform,
+    rate: LfoRate,
+    tempo: Tempo,
+    depth: NormalizedValue,
+    stereo_phase: f64,
+    /// An LFO for every channel, which only differ in their phase offset.
+    lfos: Vec<Oscillator>,
+}
+
+impl Tremolo {
+    /// Creates a new tremolo with a sine LFO at 5 Hz, at full depth.
+    pub fn new(sample_rate: SampleRate, num_channels: Channels) -> Self {
+        let rate = LfoRate::Hertz(Frequency::from(5.0));
+        let tempo = Tempo::from(120.0);
+        let lfo = Oscillator::new(Waveform::Sine, rate.to_frequency(tempo), sample_rate);
+        Self {
+            mode: TremoloMode::Tremolo,
+            waveform: Waveform::Sine,
+            rate,
+            tempo,
+            depth: NormalizedValue::from(1.0),
+            stereo_phase: 0.0,
+            lfos: vec![lfo; num_channels.as_usize()],
+        }
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        Channels::from(self.lfos.len())
+    }
+
+    /// Changes the sample rate, keeping the phase of the LFO.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        for lfo in &mut self.lfos {
+            lfo.set_sample_rate(sample_rate);
+        }
+    }
+
+    /// Returns what the LFO modulates.
+    pub fn mode(&self) -> TremoloMode {
+        self.mode
+    }
+
+    /// Changes what the LFO modulates.
+    pub fn set_mode(&mut self, mode: TremoloMode) {
+        self.mode = mode;
+        self.update_phase_offsets();
+    }
+
+    /// Returns the waveform of the LFO.
+    pub fn waveform(&self) -> Waveform {
+        self.waveform
+    }
+
+    /// Changes the waveform of the LFO, keeping its phase.
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+        for lfo in &mut self.lfos {
+            lfo.set_waveform(waveform);
+        }
+    }
+
+    /// Returns the rate of the LFO.
+    pub fn rate(&self) -> LfoRate {
+        self.rate
+    }
+
+    /// Changes the rate of the LFO, keeping its phase.
+    pub fn set_rate(&mut self, rate: LfoRate) {
+        self.rate = rate;
+        self.update_frequency();
+    }
+
+    /// Returns the tempo a synced rate follows.
+    pub fn tempo(&self) -> Tempo {
+        self.tempo
+    }
+
+    /// Changes the tempo a synced rate follows. As a processor, the tempo comes from the
+    /// context.
+    pub fn set_tempo(&mut self, tempo: Tempo) {
+        self.tempo = tempo;
+        self.update_frequency();
+    }
+
+    /// Returns how far the LFO modulates.
+    pub fn depth(&self) -> NormalizedValue {
+        self.depth
+    }
+
+    /// Changes how far the LFO modulates: at 1 the tremolo dips to silence and the auto-pan
+    /// swings all the way to either side, at 0 the audio is left untouched.
+    pub fn set_depth(&mut self, depth: NormalizedValue) {
+        self.depth = depth;
+    }
+
+    /// Returns how far the LFO of every channel is ahead of the one before it, in cycles.
+    pub fn stereo_phase(&self) -> f64 {
+        self.stereo_phase
+    }
+
+    /// Puts the LFO of every channel ahead of the one before it, in cycles, e.g. 0.5 makes the
+    /// left and right channel of a tremolo dip in turn. It doesn't apply to the auto-pan.
+    pub fn set_stereo_phase(&mut self, stereo_phase: f64) {
+        self.stereo_phase = stereo_phase;
+        self.update_phase_offsets();
+    }
+
+    /// Jumps the LFO to the given phase in cycles.
+    pub fn set_phase(&mut self, phase: f64) {
+        for lfo in &mut self.lfos {
+            lfo.set_phase(phase);
+        }
+    }
+
+    /// Restarts the LFO.
+    pub fn reset(&mut self) {
+        self.set_phase(0.0);
+    }
+
+    /// Modulates the buffer in place.
+    /// This will panic if the buffer doesn't have the same number of channels as the tremolo,
+    /// or if an auto-pan doesn't get stereo audio.
+    pub fn process<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        assert_eq!(buffer.num_channels(), self.num_channels());
+        assert!(
+            self.mode == TremoloMode::Tremolo || self.lfos.len() == 2,
+            "the auto-pan needs stereo audio"
+        );
+        let depth = self.depth.as_f64();
+
+        for (channel, (samples, lfo)) in buffer.iter_chans_mut().zip(&mut self.lfos).enumerate() {
+            for sample in samples.iter_mut() {
+                let value = lfo.next_sample();
+                let gain = match self.mode {
+                    TremoloMode::Tremolo => 1.0 - depth * (1.0 - value) / 2.0,
+                    TremoloMode::AutoPan => {
+                        let (left, right) = balance(Pan::from(depth * value));
+                        if channel == 0 {
+                            left
+                        } else {
+                            right
+                        }
+                    }
+                };
+                *sample = T::from_f64(sample.to_f64() * gain);
+            }
+        }
+    }
+
+    fn update_frequency(&mut self) {
+        let frequency = self.rate.to_frequency(self.tempo);
+        for lfo in &mut self.lfos {
+            lfo.set_frequency(frequency);
+        }
+    }
+
+    fn update_phase_offsets(&mut self) {
+        for (channel, lfo) in self.lfos.iter_mut().enumerate() {
+            let offset = match self.mode {
+                TremoloMode::Tremolo => channel as f64 * self.stereo_phase,
+                TremoloMode::AutoPan => 0.0,
+            };
+            lfo.set_phase_offset(offset);
+        }
+    }
+}
+
+impl AudioProcessor for Tremolo {
+    fn prepare(&mut self, sample_rate: SampleRate, _: BufferSize, num_channels: Channels) {
+        let frequency = self.rate.to_frequency(self.tempo);
+        let lfo = self
+            .lfos
+            .first()
+            .cloned()
+            .unwrap_or_else(|| Oscillator::new(self.waveform, frequency, sample_rate));
+        self.lfos = vec![lfo; num_channels.as_usize()];
+        self.set_sample_rate(sample_rate);
+        self.update_phase_offsets();
+    }
+
+    /// Follows the tempo of the context, and while playing with a synced rate, the position of
+    /// the timeline.
+    fn process(&mut self, buffer: &mut Buffer<f32>, context: &ProcessContext) {
+        if let Some(tempo) = context.tempo {
+            self.set_tempo(tempo);
+        }
+        if let (LfoRate::Synced(note_value), true) = (self.rate, context.is_playing) {
+            let position = context.position.to_seconds(context.sample_rate).as_f64();
+            self.set_phase(position / note_value.to_seconds(self.tempo).as_f64());
+        }
+        Tremolo::process(self, buffer);
+    }
+
+    fn reset(&mut self) {
+        Tremolo::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Samples;
+
+    /// A tremolo at 1 kHz with a 10 Hz LFO, so a cycle is 100 samples.
+    fn tremolo(num_channels: usize) -> Tremolo {
+        let mut tremolo = Tremolo::new(SampleRate::from(1000), Channels::from(num_channels));
+        tremolo.set_rate(LfoRate::Hertz(Frequency::from(10.0)));
+        tremolo
+    }
+
+    fn ones(num_channels: usize, length: usize) -> Buffer<f32> {
+        let mut buffer = Buffer::allocate(Channels::from(num_channels), Samples::from(length));
+        buffer.map_samples(|_| 1.0);
+        buffer
+    }
+
+    #[test_case(1.0, 0.0; "full depth dips to silence")]
+    #[test_case(0.5, 0.5; "half depth dips halfway")]
+    #[test_case(0.0, 1.0; "no depth leaves the audio untouched")]
+    fn tremolo_dips_by_the_depth(depth: f64, lowest: f32) {
+        let mut tremolo = tremolo(1);
+        tremolo.set_depth(NormalizedValue::from(depth));
+        let mut buffer = ones(1, 100);
+
+        tremolo.process(&mut buffer);
+
+        // The sine LFO starts at 0, peaks at a quarter and bottoms out at three quarters.
+        assert!((buffer.chan(0)[25] - 1.0).abs() < 1e-6);
+        assert!((buffer.chan(0)[75] - lowest).abs() < 1e-6);
+    }
+
+    #[test]
+    fn stereo_phase_offsets_the_channels() {
+        let mut tremolo = tremolo(2);
+        tremolo.set_stereo_phase(0.5);
+        let mut buffer = ones(2, 100);
+
+        tremolo.process(&mut buffer);
+
+        assert!((buffer.chan(0)[75]).abs() < 1e-6);
+        assert!((buffer.chan(1)[75] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn auto_pan_swings_between_the_sides() {
+        let mut tremolo = tremolo(2);
+        tremolo.set_mode(TremoloMode::AutoPan);
+        let mut buffer = ones(2, 100);
+
+        tremolo.process(&mut buffer);
+
+        assert_eq!((buffer.chan(0)[0], buffer.chan(1)[0]), (1.0, 1.0));
+        assert!(buffer.chan(0)[25].abs() < 1e-6);
+        assert!(buffer.chan(1)[75].abs() < 1e-6);
+    }
+
+    #[test]
+    #[should_panic]
+    fn auto_pan_needs_stereo() {
+        let mut tremolo = tremolo(1);
+        tremolo.set_mode(TremoloMode::AutoPan);
+        tremolo.process(&mut ones(1, 10));
+    }
+
+    #[test]
+    fn synced_rate_follows_the_tempo_and_the_position() {
+        let mut tremolo = tremolo(1);
+        tremolo.set_rate(LfoRate::Synced(NoteValue::new(16)));
+        let context = ProcessContext {
+            is_playing: true,
+            tempo: Some(Tempo::from(150.0)),
+            // A sixteenth at 150 BPM is 100 ms, and this is three quarters into one.
+            position: Samples::from(1075),
+            ..ProcessContext::new(SampleRate::from(1000))
+        };
+        let mut buffer = ones(1, 1);
+
+        AudioProcessor::process(&mut tremolo, &mut buffer, &context);
+
+        assert_eq!(tremolo.tempo(), Tempo::from(150.0));
+        assert!(buffer.chan(0)[0].abs() < 1e-6);
+    }
+}
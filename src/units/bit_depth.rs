@@ -2,6 +2,7 @@
 use serde::{Deserialize, Serialize};
 
 /// Represents a bit depth for an audio file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum BitDepth {
     Bits8,
@@ -0,0 +1,62 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::units::Samples;
+
+/// Represents the largest number of samples per channel in the buffers of a block based
+/// process, as agreed on before processing starts:
+/// ```
+/// use rabu::units::{BufferSize, Samples};
+///
+/// let buffer_size = BufferSize::from(512);
+///
+/// assert_eq!(buffer_size.as_samples(), Samples::from(512));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BufferSize(u32);
+
+impl BufferSize {
+    /// Gives back the raw value as a `u32`.
+    pub fn as_u32(&self) -> u32 {
+        self.0
+    }
+
+    /// Gives back the raw value as a `usize`.
+    pub fn as_usize(&self) -> usize {
+        self.as_u32() as usize
+    }
+
+    /// Gives back the buffer size as a number of samples.
+    pub fn as_samples(&self) -> Samples {
+        Samples::from(self.0)
+    }
+}
+
+macro_rules! impl_int_conversions {
+    ($int_type:ty) => {
+        impl From<$int_type> for BufferSize {
+            fn from(value: $int_type) -> Self {
+                Self(value as _)
+            }
+        }
+
+        impl From<BufferSize> for $int_type {
+            fn from(value: BufferSize) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_int_conversions!(u64);
+impl_int_conversions!(u32);
+impl_int_conversions!(u16);
+impl_int_conversions!(u8);
+impl_int_conversions!(usize);
+
+impl_int_conversions!(i64);
+impl_int_conversions!(i32);
+impl_int_conversions!(i16);
+impl_int_conversions!(i8);
+impl_int_conversions!(isize);
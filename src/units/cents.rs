@@ -0,0 +1,55 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::units::Semitones;
+
+/// Represents a musical interval in cents, where 100 cents is a semitone. Cents are used for
+/// fine tuning, e.g. to detune an oscillator by a few cents:
+/// ```
+/// use rabu::units::{Cents, Semitones};
+///
+/// let detune = Cents::from(-50.0);
+///
+/// assert_eq!(Semitones::from(detune), Semitones::from(-0.5));
+/// assert!((Cents::from(1200.0).to_ratio() - 2.0).abs() < 1e-12);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Cents(f64);
+
+impl Cents {
+    /// Gives back the raw value as a `f64`.
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+
+    /// Returns the ratio of the frequencies of the interval, e.g. 2 for 1200 cents.
+    pub fn to_ratio(&self) -> f64 {
+        Semitones::from(*self).to_ratio()
+    }
+}
+
+impl From<Semitones> for Cents {
+    fn from(value: Semitones) -> Self {
+        Self(value.as_f64() * 100.0)
+    }
+}
+
+macro_rules! impl_float_conversions {
+    ($float_type: ty) => {
+        impl From<$float_type> for Cents {
+            fn from(value: $float_type) -> Self {
+                Self(value as _)
+            }
+        }
+
+        impl From<Cents> for $float_type {
+            fn from(value: Cents) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_float_conversions!(f32);
+impl_float_conversions!(f64);
@@ -8,6 +8,11 @@ use serde::{Deserialize, Serialize};
 pub struct Channels(u32);
 
 impl Channels {
+    /// Creates a number of channels, also in constant expressions.
+    pub const fn new(value: u32) -> Self {
+        Self(value)
+    }
+
     /// Gives back the raw value as a `u32`.
     pub fn as_u32(&self) -> u32 {
         self.0
@@ -0,0 +1,59 @@
+use derive_more::{Add, AddAssign, Neg, Sub, SubAssign};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Represents a level or gain in decibels.
+/// Can be converted to and from a linear gain factor:
+/// ```
+/// use rabu::units::Decibels;
+///
+/// let gain = Decibels::from(-6.0).to_gain();
+///
+/// assert!((gain - 0.501).abs() < 0.001);
+/// assert_eq!(Decibels::from_gain(1.0), Decibels::from(0.0));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Add, Sub, AddAssign, SubAssign, Neg, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Decibels(f64);
+
+impl Decibels {
+    /// Creates a level in decibels, also in constant expressions.
+    pub const fn new(value: f64) -> Self {
+        Self(value)
+    }
+
+    /// Gives back the raw value as a `f64`.
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+
+    /// Converts the decibels to a linear (amplitude) gain factor.
+    pub fn to_gain(&self) -> f64 {
+        10.0_f64.powf(self.0 / 20.0)
+    }
+
+    /// Creates decibels from a linear (amplitude) gain factor.
+    /// A gain of zero results in negative infinity.
+    pub fn from_gain(gain: f64) -> Self {
+        Self(20.0 * gain.abs().log10())
+    }
+}
+
+macro_rules! impl_float_conversions {
+    ($float_type: ty) => {
+        impl From<$float_type> for Decibels {
+            fn from(value: $float_type) -> Self {
+                Self(value as _)
+            }
+        }
+
+        impl From<Decibels> for $float_type {
+            fn from(value: Decibels) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_float_conversions!(f32);
+impl_float_conversions!(f64);
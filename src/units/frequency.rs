@@ -9,6 +9,11 @@ use serde::{Deserialize, Serialize};
 pub struct Frequency(f64);
 
 impl Frequency {
+    /// Creates a frequency in Hz, also in constant expressions.
+    pub const fn new(value: f64) -> Self {
+        Self(value)
+    }
+
     /// Gets the raw value of the frequency as `f64`.
     pub fn as_f64(&self) -> f64 {
         self.0
@@ -0,0 +1,52 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::units::{Frequency, SampleRate, Samples};
+
+/// Represents the index of a bin in the spectrum produced by an FFT.
+/// The frequency of a bin depends on the sample rate and the size of the FFT:
+/// ```
+/// use rabu::units::{Frequency, FrequencyBin, SampleRate, Samples};
+///
+/// let sample_rate = SampleRate::from(48000);
+/// let fft_size = Samples::from(1024);
+///
+/// let bin = FrequencyBin::from(64);
+/// assert_eq!(bin.to_frequency(sample_rate, fft_size), Frequency::from(3000.0));
+///
+/// let nearest = FrequencyBin::nearest(Frequency::from(3010.0), sample_rate, fft_size);
+/// assert_eq!(nearest, bin);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FrequencyBin(usize);
+
+impl FrequencyBin {
+    /// Gives back the raw index as a `usize`.
+    pub fn as_usize(&self) -> usize {
+        self.0
+    }
+
+    /// Returns the center frequency of this bin.
+    pub fn to_frequency(&self, sample_rate: SampleRate, fft_size: Samples) -> Frequency {
+        Frequency::from(self.0 as f64 * sample_rate.as_f64() / fft_size.as_f64())
+    }
+
+    /// Returns the bin whose center frequency is closest to the given frequency.
+    pub fn nearest(frequency: Frequency, sample_rate: SampleRate, fft_size: Samples) -> Self {
+        let index = frequency.as_f64() * fft_size.as_f64() / sample_rate.as_f64();
+        Self(index.round().max(0.0) as usize)
+    }
+}
+
+impl From<usize> for FrequencyBin {
+    fn from(value: usize) -> Self {
+        Self(value)
+    }
+}
+
+impl From<FrequencyBin> for usize {
+    fn from(value: FrequencyBin) -> Self {
+        value.0
+    }
+}
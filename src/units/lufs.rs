@@ -0,0 +1,63 @@
+use std::ops::{Add, Sub};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::units::Decibels;
+
+/// Represents a loudness in LUFS (loudness units relative to full scale), as measured by an
+/// ITU BS.1770 meter. A difference between two loudnesses is in LU, which are decibels:
+/// ```
+/// use rabu::units::{Decibels, Lufs};
+///
+/// let measured = Lufs::from(-18.0);
+/// let target = Lufs::from(-14.0);
+///
+/// assert_eq!(target - measured, Decibels::from(4.0));
+/// assert_eq!(measured + Decibels::from(4.0), target);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Lufs(f64);
+
+impl Lufs {
+    /// Gives back the raw value as a `f64`.
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+}
+
+impl Sub for Lufs {
+    type Output = Decibels;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Decibels::from(self.0 - rhs.0)
+    }
+}
+
+impl Add<Decibels> for Lufs {
+    type Output = Lufs;
+
+    fn add(self, rhs: Decibels) -> Self::Output {
+        Lufs(self.0 + rhs.as_f64())
+    }
+}
+
+macro_rules! impl_float_conversions {
+    ($float_type: ty) => {
+        impl From<$float_type> for Lufs {
+            fn from(value: $float_type) -> Self {
+                Self(value as _)
+            }
+        }
+
+        impl From<Lufs> for $float_type {
+            fn from(value: Lufs) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_float_conversions!(f32);
+impl_float_conversions!(f64);
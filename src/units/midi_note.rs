@@ -0,0 +1,65 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::units::Frequency;
+
+/// Represents a MIDI note number, from 0 to 127, where 69 is the A above middle C at 440 Hz.
+/// Values above 127 are clamped when converting:
+/// ```
+/// use rabu::units::{Frequency, MidiNote};
+///
+/// let note = MidiNote::from(69);
+///
+/// assert_eq!(note.to_frequency(), Frequency::from(440.0));
+/// assert_eq!(MidiNote::from_frequency(Frequency::from(261.0)), MidiNote::from(60));
+/// assert_eq!(MidiNote::from(200).as_u8(), 127);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MidiNote(u8);
+
+impl MidiNote {
+    /// Gives back the raw value as a `u8`.
+    pub fn as_u8(&self) -> u8 {
+        self.0
+    }
+
+    /// Gives back the frequency of the note in twelve-tone equal temperament.
+    pub fn to_frequency(&self) -> Frequency {
+        Frequency::from(440.0 * 2.0_f64.powf((self.0 as f64 - 69.0) / 12.0))
+    }
+
+    /// Creates the note nearest to the frequency, clamped to the range of MIDI notes.
+    pub fn from_frequency(frequency: Frequency) -> Self {
+        let note = 69.0 + 12.0 * (frequency.as_f64() / 440.0).log2();
+        Self(note.round().clamp(0.0, 127.0) as u8)
+    }
+}
+
+macro_rules! impl_int_conversions {
+    ($int_type:ty) => {
+        impl From<$int_type> for MidiNote {
+            fn from(value: $int_type) -> Self {
+                Self((value as i64).clamp(0, 127) as u8)
+            }
+        }
+
+        impl From<MidiNote> for $int_type {
+            fn from(value: MidiNote) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_int_conversions!(u64);
+impl_int_conversions!(u32);
+impl_int_conversions!(u16);
+impl_int_conversions!(u8);
+impl_int_conversions!(usize);
+
+impl_int_conversions!(i64);
+impl_int_conversions!(i32);
+impl_int_conversions!(i16);
+impl_int_conversions!(i8);
+impl_int_conversions!(isize);
@@ -4,25 +4,59 @@
 //! when given a `SampleRate` value.
 
 pub use bit_depth::BitDepth;
+pub use buffer_size::BufferSize;
+pub use cents::Cents;
 pub use channels::Channels;
+pub use decibels::Decibels;
 pub use duration::Duration;
 pub use frequency::Frequency;
+pub use frequency_bin::FrequencyBin;
 pub use latency::Latency;
+pub use lufs::Lufs;
+pub use midi_note::MidiNote;
+pub use normalized_value::NormalizedValue;
+pub use note_value::NoteValue;
+pub use pan::Pan;
 pub use percentage::Percentage;
+pub use playback_rate::PlaybackRate;
+pub use ratio::Ratio;
 pub use sample_rate::SampleRate;
+pub use sample_section::SampleSection;
 pub use samples::Samples;
+pub use samples_f64::SamplesF64;
 pub use seconds::Seconds;
+pub use semitones::Semitones;
+pub use tempo::Tempo;
 pub use time_point::TimePoint;
 pub use time_section::TimeSection;
+pub use time_signature::TimeSignature;
+pub use velocity::Velocity;
 
 mod bit_depth;
+mod buffer_size;
+mod cents;
 mod channels;
+mod decibels;
 mod duration;
 mod frequency;
+mod frequency_bin;
 mod latency;
+mod lufs;
+mod midi_note;
+mod normalized_value;
+mod note_value;
+mod pan;
 mod percentage;
+mod playback_rate;
+mod ratio;
 mod sample_rate;
+mod sample_section;
 mod samples;
+mod samples_f64;
 mod seconds;
+mod semitones;
+mod tempo;
 mod time_point;
 mod time_section;
+mod time_signature;
+mod velocity;
@@ -0,0 +1,41 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Represents a value between 0 and 1, e.g. a sustain level or the position of a knob.
+/// Values outside that range are clamped when converting:
+/// ```
+/// use rabu::units::NormalizedValue;
+///
+/// assert_eq!(NormalizedValue::from(0.25).as_f64(), 0.25);
+/// assert_eq!(NormalizedValue::from(1.5).as_f64(), 1.0);
+/// assert_eq!(NormalizedValue::from(-2.0).as_f64(), 0.0);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NormalizedValue(f64);
+
+impl NormalizedValue {
+    /// Gives back the raw value as a `f64`.
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+}
+
+macro_rules! impl_float_conversions {
+    ($float_type: ty) => {
+        impl From<$float_type> for NormalizedValue {
+            fn from(value: $float_type) -> Self {
+                Self((value as f64).clamp(0.0, 1.0))
+            }
+        }
+
+        impl From<NormalizedValue> for $float_type {
+            fn from(value: NormalizedValue) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_float_conversions!(f32);
+impl_float_conversions!(f64);
@@ -0,0 +1,82 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::units::{Frequency, Seconds, Tempo};
+
+/// Represents the length of a note relative to a whole note, e.g. 1/4 for a quarter note, to
+/// sync times and rates to the tempo. The tempo counts quarter notes:
+/// ```
+/// use rabu::units::{NoteValue, Seconds, Tempo};
+///
+/// let eighth = NoteValue::new(8);
+/// let tempo = Tempo::from(120.0);
+///
+/// assert_eq!(eighth.to_seconds(tempo), Seconds::from(0.25));
+/// assert_eq!(eighth.dotted().to_seconds(tempo), Seconds::from(0.375));
+/// assert_eq!(NoteValue::new(4).triplet(), NoteValue::from(1.0 / 6.0));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NoteValue(f64);
+
+impl NoteValue {
+    /// Creates the note value of one over the denominator, e.g. 4 for a quarter note.
+    /// This will panic if the denominator is 0.
+    pub fn new(denominator: u32) -> Self {
+        assert!(
+            denominator > 0,
+            "a note value can't have a denominator of 0"
+        );
+        Self(1.0 / denominator as f64)
+    }
+
+    /// Gives back the length in whole notes as a `f64`.
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+
+    /// Returns the note that is half as long again.
+    pub fn dotted(&self) -> Self {
+        Self(self.0 * 1.5)
+    }
+
+    /// Returns the note of which three fit in the time of two.
+    pub fn triplet(&self) -> Self {
+        Self(self.0 * 2.0 / 3.0)
+    }
+
+    /// Returns the length of the note at the tempo.
+    pub fn to_seconds(&self, tempo: Tempo) -> Seconds {
+        Seconds::from(self.0 * 4.0 * tempo.beat_length().as_f64())
+    }
+
+    /// Returns the frequency of something that repeats every note at the tempo, e.g. an LFO.
+    pub fn to_frequency(&self, tempo: Tempo) -> Frequency {
+        Frequency::from(1.0 / self.to_seconds(tempo).as_f64())
+    }
+}
+
+impl Default for NoteValue {
+    fn default() -> Self {
+        Self::new(4)
+    }
+}
+
+macro_rules! impl_float_conversions {
+    ($float_type: ty) => {
+        impl From<$float_type> for NoteValue {
+            fn from(value: $float_type) -> Self {
+                Self(value as _)
+            }
+        }
+
+        impl From<NoteValue> for $float_type {
+            fn from(value: NoteValue) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_float_conversions!(f32);
+impl_float_conversions!(f64);
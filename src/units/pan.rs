@@ -0,0 +1,45 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Represents a position in the stereo field, from -1 (left) through 0 (center) to 1 (right).
+/// Values outside that range are clamped when converting:
+/// ```
+/// use rabu::units::Pan;
+///
+/// assert_eq!(Pan::from(-0.5).as_f64(), -0.5);
+/// assert_eq!(Pan::from(3.0).as_f64(), 1.0);
+/// assert_eq!(Pan::default(), Pan::CENTER);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Pan(f64);
+
+impl Pan {
+    pub const LEFT: Pan = Pan(-1.0);
+    pub const CENTER: Pan = Pan(0.0);
+    pub const RIGHT: Pan = Pan(1.0);
+
+    /// Gives back the raw value as a `f64`.
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+}
+
+macro_rules! impl_float_conversions {
+    ($float_type: ty) => {
+        impl From<$float_type> for Pan {
+            fn from(value: $float_type) -> Self {
+                Self((value as f64).clamp(-1.0, 1.0))
+            }
+        }
+
+        impl From<Pan> for $float_type {
+            fn from(value: Pan) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_float_conversions!(f32);
+impl_float_conversions!(f64);
@@ -6,6 +6,13 @@ use serde::{Deserialize, Serialize};
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Percentage(f64);
 
+impl Percentage {
+    /// Gives back the raw value as a `f64`, where 100 is the whole.
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+}
+
 macro_rules! impl_float_conversions {
     ($float_type: ty) => {
         impl From<$float_type> for Percentage {
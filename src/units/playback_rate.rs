@@ -0,0 +1,58 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Represents the speed at which audio is played back, relative to its original speed.
+/// A rate of 2 plays twice as fast (an octave up), a negative rate plays in reverse:
+/// ```
+/// use rabu::units::PlaybackRate;
+///
+/// let rate = PlaybackRate::from_semitones(12.0);
+///
+/// assert!((rate.as_f64() - 2.0).abs() < 1e-12);
+/// assert_eq!(PlaybackRate::default(), PlaybackRate::from(1.0));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PlaybackRate(f64);
+
+impl PlaybackRate {
+    /// Gives back the raw value as a `f64`.
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+
+    /// Creates the rate that transposes audio by the given number of semitones.
+    pub fn from_semitones(semitones: f64) -> Self {
+        Self(2.0_f64.powf(semitones / 12.0))
+    }
+
+    /// Returns whether this rate plays audio in reverse.
+    pub fn is_reverse(&self) -> bool {
+        self.0 < 0.0
+    }
+}
+
+impl Default for PlaybackRate {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+macro_rules! impl_float_conversions {
+    ($float_type: ty) => {
+        impl From<$float_type> for PlaybackRate {
+            fn from(value: $float_type) -> Self {
+                Self(value as _)
+            }
+        }
+
+        impl From<PlaybackRate> for $float_type {
+            fn from(value: PlaybackRate) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_float_conversions!(f32);
+impl_float_conversions!(f64);
@@ -0,0 +1,49 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Represents a compression ratio, e.g. 4 for a compressor that lets through 1 dB for every
+/// 4 dB the input rises above its threshold. A ratio of 1 leaves the signal untouched and an
+/// infinite ratio limits:
+/// ```
+/// use rabu::units::Ratio;
+///
+/// let ratio = Ratio::from(4.0);
+///
+/// assert_eq!(ratio.as_f64(), 4.0);
+/// assert_eq!(Ratio::default(), Ratio::from(1.0));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Ratio(f64);
+
+impl Ratio {
+    /// Gives back the raw value as a `f64`.
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+}
+
+impl Default for Ratio {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+macro_rules! impl_float_conversions {
+    ($float_type: ty) => {
+        impl From<$float_type> for Ratio {
+            fn from(value: $float_type) -> Self {
+                Self(value as _)
+            }
+        }
+
+        impl From<Ratio> for $float_type {
+            fn from(value: Ratio) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_float_conversions!(f32);
+impl_float_conversions!(f64);
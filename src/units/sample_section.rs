@@ -0,0 +1,74 @@
+use std::cmp::{max, min};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::units::{SampleRate, Samples, TimePoint, TimeSection};
+
+/// Represents a section of samples, e.g. the part of a file that is played in a block.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SampleSection {
+    pub start: Samples,
+    pub length: Samples,
+}
+
+impl SampleSection {
+    /// Returns the sample right after the section.
+    pub fn end(&self) -> Samples {
+        self.start + self.length
+    }
+
+    /// Returns the overlap (if any) between this section and another:
+    /// ```
+    /// use rabu::units::{SampleSection, Samples};
+    ///
+    /// let a = SampleSection { start: Samples::from(100), length: Samples::from(50) };
+    /// let b = SampleSection { start: Samples::from(120), length: Samples::from(100) };
+    ///
+    /// let overlap = a.get_overlap(b).unwrap();
+    ///
+    /// assert_eq!(overlap, SampleSection { start: Samples::from(120), length: Samples::from(30) });
+    /// ```
+    pub fn get_overlap(&self, other: Self) -> Option<Self> {
+        if self.end() <= other.start || other.end() <= self.start {
+            return None;
+        }
+        let start = max(self.start, other.start);
+        let end = min(self.end(), other.end());
+        Some(Self {
+            start,
+            length: end - start,
+        })
+    }
+
+    /// Converts the section to a time section using the given sample rate.
+    pub fn to_time_section(&self, sample_rate: SampleRate) -> TimeSection {
+        TimeSection {
+            start: TimePoint::from(self.start.to_seconds(sample_rate)),
+            duration: self.length.to_seconds(sample_rate).into(),
+        }
+    }
+
+    /// Converts a time section to samples, rounding its start and end to the nearest sample:
+    /// ```
+    /// use rabu::units::{Duration, SampleRate, SampleSection, Samples, TimePoint, TimeSection};
+    ///
+    /// let section = TimeSection {
+    ///     start: TimePoint::from_secs_f64(0.5),
+    ///     duration: Duration::from_secs_f64(0.25),
+    /// };
+    ///
+    /// let samples = SampleSection::from_time_section(section, SampleRate::from(48000));
+    ///
+    /// assert_eq!(samples, SampleSection { start: Samples::from(24000), length: Samples::from(12000) });
+    /// ```
+    pub fn from_time_section(section: TimeSection, sample_rate: SampleRate) -> Self {
+        let start = section.start.as_seconds().to_samples(sample_rate);
+        let end = section.end().as_seconds().to_samples(sample_rate);
+        Self {
+            start,
+            length: end - start,
+        }
+    }
+}
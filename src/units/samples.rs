@@ -10,6 +10,11 @@ use crate::units::{SampleRate, Seconds};
 pub struct Samples(u64);
 
 impl Samples {
+    /// Creates a number of samples, also in constant expressions.
+    pub const fn new(value: u64) -> Self {
+        Self(value)
+    }
+
     /// Gives back the raw value as `u64`.
     pub fn as_u64(&self) -> u64 {
         self.0
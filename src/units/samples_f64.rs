@@ -0,0 +1,61 @@
+use derive_more::{Add, AddAssign, Sub, SubAssign};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::units::{SampleRate, Samples, Seconds};
+
+/// Represents a fractional number of samples, e.g. a sub-sample delay or playhead position.
+#[derive(Copy, Clone, Debug, PartialEq, Add, Sub, AddAssign, SubAssign, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SamplesF64(f64);
+
+impl SamplesF64 {
+    /// Gives back the raw value as a `f64`.
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+
+    /// Converts to seconds using the given sample rate.
+    pub fn to_seconds(&self, sr: SampleRate) -> Seconds {
+        Seconds::from(self.0 / sr.as_f64())
+    }
+
+    /// Splits the value into whole samples (rounded down) and the fractional part:
+    /// ```
+    /// use rabu::units::{Samples, SamplesF64};
+    ///
+    /// let (whole, fraction) = SamplesF64::from(10.25).split();
+    ///
+    /// assert_eq!(whole, Samples::from(10));
+    /// assert_eq!(fraction, 0.25);
+    /// ```
+    pub fn split(&self) -> (Samples, f64) {
+        let whole = self.0.floor();
+        (Samples::from(whole as u64), self.0 - whole)
+    }
+}
+
+impl From<Samples> for SamplesF64 {
+    fn from(value: Samples) -> Self {
+        Self(value.as_f64())
+    }
+}
+
+macro_rules! impl_float_conversions {
+    ($float_type: ty) => {
+        impl From<$float_type> for SamplesF64 {
+            fn from(value: $float_type) -> Self {
+                Self(value as _)
+            }
+        }
+
+        impl From<SamplesF64> for $float_type {
+            fn from(value: SamplesF64) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_float_conversions!(f32);
+impl_float_conversions!(f64);
@@ -10,6 +10,11 @@ use crate::units::{SampleRate, Samples, TimePoint};
 pub struct Seconds(f64);
 
 impl Seconds {
+    /// Creates a number of seconds, also in constant expressions.
+    pub const fn new(value: f64) -> Self {
+        Self(value)
+    }
+
     /// Convert to samples using the given sample rate.
     /// ```
     /// use rabu::units::{SampleRate, Samples, Seconds};
@@ -0,0 +1,68 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::units::{Cents, PlaybackRate};
+
+/// Represents a musical interval in semitones, e.g. 12 for an octave up or -7 for a fifth
+/// down. Fractions of a semitone are allowed:
+/// ```
+/// use rabu::units::{Cents, Semitones};
+///
+/// let fifth = Semitones::from(7.0);
+///
+/// assert!((Semitones::from(12.0).to_ratio() - 2.0).abs() < 1e-12);
+/// assert_eq!(Cents::from(fifth), Cents::from(700.0));
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Semitones(f64);
+
+impl Semitones {
+    /// Gives back the raw value as a `f64`.
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+
+    /// Returns the ratio of the frequencies of the interval, e.g. 2 for an octave.
+    pub fn to_ratio(&self) -> f64 {
+        2.0_f64.powf(self.0 / 12.0)
+    }
+
+    /// Returns the interval between two frequencies with the given ratio.
+    /// This will panic if the ratio is not above 0.
+    pub fn from_ratio(ratio: f64) -> Self {
+        assert!(ratio > 0.0, "the frequency ratio must be above 0");
+        Self(12.0 * ratio.log2())
+    }
+}
+
+impl From<Cents> for Semitones {
+    fn from(value: Cents) -> Self {
+        Self(value.as_f64() / 100.0)
+    }
+}
+
+impl From<Semitones> for PlaybackRate {
+    fn from(value: Semitones) -> Self {
+        PlaybackRate::from(value.to_ratio())
+    }
+}
+
+macro_rules! impl_float_conversions {
+    ($float_type: ty) => {
+        impl From<$float_type> for Semitones {
+            fn from(value: $float_type) -> Self {
+                Self(value as _)
+            }
+        }
+
+        impl From<Semitones> for $float_type {
+            fn from(value: Semitones) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_float_conversions!(f32);
+impl_float_conversions!(f64);
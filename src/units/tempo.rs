@@ -0,0 +1,53 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::units::Seconds;
+
+/// Represents a tempo in beats per minute:
+/// ```
+/// use rabu::units::{Seconds, Tempo};
+///
+/// let tempo = Tempo::from(120.0);
+///
+/// assert_eq!(tempo.beat_length(), Seconds::from(0.5));
+/// assert_eq!(Tempo::from_beat_length(Seconds::from(0.5)), tempo);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Tempo(f64);
+
+impl Tempo {
+    /// Gives back the beats per minute as a `f64`.
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+
+    /// Gives back the time between two beats.
+    pub fn beat_length(&self) -> Seconds {
+        Seconds::from(60.0 / self.0)
+    }
+
+    /// Creates the tempo with the given time between two beats.
+    pub fn from_beat_length(beat_length: Seconds) -> Self {
+        Self(60.0 / beat_length.as_f64())
+    }
+}
+
+macro_rules! impl_float_conversions {
+    ($float_type: ty) => {
+        impl From<$float_type> for Tempo {
+            fn from(value: $float_type) -> Self {
+                Self(value as _)
+            }
+        }
+
+        impl From<Tempo> for $float_type {
+            fn from(value: Tempo) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_float_conversions!(f32);
+impl_float_conversions!(f64);
@@ -0,0 +1,51 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Represents a time signature: the number of beats in a bar, and the note value of a beat.
+/// ```
+/// use rabu::units::TimeSignature;
+///
+/// let waltz = TimeSignature::new(3, 4);
+///
+/// assert_eq!(waltz.numerator(), 3);
+/// assert_eq!(waltz.denominator(), 4);
+/// assert_eq!(TimeSignature::default(), TimeSignature::new(4, 4));
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimeSignature {
+    numerator: u32,
+    denominator: u32,
+}
+
+impl TimeSignature {
+    /// Creates a new time signature with the number of beats in a bar, and the note value of
+    /// a beat (4 for quarter notes, 8 for eighth notes, etc.).
+    /// This will panic if either of them is 0.
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        assert!(
+            numerator > 0 && denominator > 0,
+            "a time signature can't have 0 beats or a beat of 0"
+        );
+        Self {
+            numerator,
+            denominator,
+        }
+    }
+
+    /// Returns the number of beats in a bar.
+    pub fn numerator(&self) -> u32 {
+        self.numerator
+    }
+
+    /// Returns the note value of a beat.
+    pub fn denominator(&self) -> u32 {
+        self.denominator
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self::new(4, 4)
+    }
+}
@@ -0,0 +1,64 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::units::NormalizedValue;
+
+/// Represents the velocity of a MIDI note, from 0 to 127. Values above 127 are clamped when
+/// converting:
+/// ```
+/// use rabu::units::{NormalizedValue, Velocity};
+///
+/// let velocity = Velocity::from(127);
+///
+/// assert_eq!(velocity.to_normalized(), NormalizedValue::from(1.0));
+/// assert_eq!(Velocity::from_normalized(NormalizedValue::from(0.5)), Velocity::from(64));
+/// assert_eq!(Velocity::from(300).as_u8(), 127);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Velocity(u8);
+
+impl Velocity {
+    /// Gives back the raw value as a `u8`.
+    pub fn as_u8(&self) -> u8 {
+        self.0
+    }
+
+    /// Gives back the velocity scaled to the range from 0 to 1.
+    pub fn to_normalized(&self) -> NormalizedValue {
+        NormalizedValue::from(self.0 as f64 / 127.0)
+    }
+
+    /// Creates the velocity nearest to the value scaled from the range from 0 to 1.
+    pub fn from_normalized(value: NormalizedValue) -> Self {
+        Self((value.as_f64() * 127.0).round() as u8)
+    }
+}
+
+macro_rules! impl_int_conversions {
+    ($int_type:ty) => {
+        impl From<$int_type> for Velocity {
+            fn from(value: $int_type) -> Self {
+                Self((value as i64).clamp(0, 127) as u8)
+            }
+        }
+
+        impl From<Velocity> for $int_type {
+            fn from(value: Velocity) -> Self {
+                value.0 as _
+            }
+        }
+    };
+}
+
+impl_int_conversions!(u64);
+impl_int_conversions!(u32);
+impl_int_conversions!(u16);
+impl_int_conversions!(u8);
+impl_int_conversions!(usize);
+
+impl_int_conversions!(i64);
+impl_int_conversions!(i32);
+impl_int_conversions!(i16);
+impl_int_conversions!(i8);
+impl_int_conversions!(isize);
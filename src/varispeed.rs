@@ -0,0 +1,335 @@
+//! This module contains a reader that plays audio at an arbitrary speed, like a tape machine or
+//! a sampler. It keeps a fractional playhead, interpolates between samples, and either stops at
+//! the end of the audio or loops a section of it.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::clip::AudioClip;
+//! use rabu::units::{Channels, PlaybackRate, SampleRate, Samples};
+//! use rabu::varispeed::{Interpolation, VarispeedReader};
+//!
+//! let clip = AudioClip::new(
+//!     Buffer::<f32>::allocate(Channels::from(2), Samples::from(44100)),
+//!     SampleRate::from(44100),
+//! );
+//!
+//! // Play a fifth up, at a device that runs at 48 kHz.
+//! let mut reader = VarispeedReader::new(Interpolation::Cubic);
+//! reader.set_rate(PlaybackRate::from_semitones(7.0));
+//!
+//! let mut output = Buffer::<f32>::allocate(Channels::from(2), Samples::from(512));
+//! reader.read_clip(&clip, SampleRate::from(48000), &mut output);
+//! ```
+
+use crate::buffer::Buffer;
+use crate::clip::AudioClip;
+use crate::sample::Sample;
+use crate::units::{PlaybackRate, SampleRate, Samples, SamplesF64};
+
+/// How the reader calculates samples between the samples of the source.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Takes the sample just before the playhead, which is cheap and sounds gritty.
+    None,
+    /// Interpolates linearly between the two neighbouring samples.
+    Linear,
+    /// Interpolates with a four point Hermite curve, which keeps the highs cleaner.
+    Cubic,
+}
+
+impl Interpolation {
+    /// Interpolates between `sample(0)` and `sample(1)` at the fraction `t`. The cubic curve
+    /// also looks at `sample(-1)` and `sample(2)`.
+    pub(crate) fn interpolate(&self, sample: impl Fn(isize) -> f64, t: f64) -> f64 {
+        match self {
+            Interpolation::None => sample(0),
+            Interpolation::Linear => {
+                let (x0, x1) = (sample(0), sample(1));
+                x0 + (x1 - x0) * t
+            }
+            Interpolation::Cubic => {
+                let (xm1, x0, x1, x2) = (sample(-1), sample(0), sample(1), sample(2));
+                let c1 = 0.5 * (x1 - xm1);
+                let c2 = xm1 - 2.5 * x0 + 2.0 * x1 - 0.5 * x2;
+                let c3 = 0.5 * (x2 - xm1) + 1.5 * (x0 - x1);
+                ((c3 * t + c2) * t + c1) * t + x0
+            }
+        }
+    }
+}
+
+/// What happens when the playhead reaches the end of the audio.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlaybackMode {
+    /// Playback stops at the end (or the start, when playing in reverse).
+    OneShot,
+    /// Playback loops between the given start (inclusive) and end (exclusive).
+    Loop { start: Samples, end: Samples },
+}
+
+/// Reads audio at an arbitrary playback rate.
+#[derive(Clone, Debug)]
+pub struct VarispeedReader {
+    interpolation: Interpolation,
+    mode: PlaybackMode,
+    rate: PlaybackRate,
+    playhead: SamplesF64,
+    finished: bool,
+}
+
+impl VarispeedReader {
+    /// Creates a new reader at the start of the audio, playing once at the original speed.
+    pub fn new(interpolation: Interpolation) -> Self {
+        Self {
+            interpolation,
+            mode: PlaybackMode::OneShot,
+            rate: PlaybackRate::default(),
+            playhead: SamplesF64::from(0.0),
+            finished: false,
+        }
+    }
+
+    /// Returns the interpolation that is used.
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    /// Changes the interpolation.
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
+
+    /// Returns the playback mode.
+    pub fn mode(&self) -> PlaybackMode {
+        self.mode
+    }
+
+    /// Changes the playback mode.
+    /// This will panic if the loop is empty.
+    pub fn set_mode(&mut self, mode: PlaybackMode) {
+        if let PlaybackMode::Loop { start, end } = mode {
+            assert!(start < end, "a loop must contain at least one sample");
+        }
+        self.mode = mode;
+    }
+
+    /// Returns the playback rate.
+    pub fn rate(&self) -> PlaybackRate {
+        self.rate
+    }
+
+    /// Changes the playback rate, which applies from the next sample on.
+    pub fn set_rate(&mut self, rate: PlaybackRate) {
+        self.rate = rate;
+    }
+
+    /// Returns the position of the playhead in samples of the source.
+    pub fn playhead(&self) -> SamplesF64 {
+        self.playhead
+    }
+
+    /// Moves the playhead to the given position, which also restarts playback if it finished.
+    pub fn set_playhead(&mut self, playhead: SamplesF64) {
+        self.playhead = playhead;
+        self.finished = false;
+    }
+
+    /// Returns whether one-shot playback ran past the end of the audio.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Reads from the clip into the output, taking into account that the clip may have a
+    /// different sample rate than the output. Returns the number of samples that were read;
+    /// the output is silent after that.
+    /// This will panic if the clip and output don't have the same number of channels.
+    pub fn read_clip<T: Sample>(
+        &mut self,
+        clip: &AudioClip<T>,
+        output_sample_rate: SampleRate,
+        output: &mut Buffer<T>,
+    ) -> Samples {
+        let step = self.rate.as_f64() * clip.sample_rate().as_f64() / output_sample_rate.as_f64();
+        self.read_with_step(clip.buffer(), step, output)
+    }
+
+    /// Reads from the source into the output, advancing the playhead by the playback rate for
+    /// every sample. Returns the number of samples that were read; the output is silent after
+    /// that.
+    /// This will panic if the source and output don't have the same number of channels.
+    pub fn read<T: Sample>(&mut self, source: &Buffer<T>, output: &mut Buffer<T>) -> Samples {
+        self.read_with_step(source, self.rate.as_f64(), output)
+    }
+
+    fn read_with_step<T: Sample>(
+        &mut self,
+        source: &Buffer<T>,
+        step: f64,
+        output: &mut Buffer<T>,
+    ) -> Samples {
+        assert_eq!(source.num_channels(), output.num_channels());
+        let length = source.num_samples().as_usize() as f64;
+        let mut written = 0;
+
+        for index in output.sample_indices() {
+            if !self.finished {
+                let position = self.playhead.as_f64();
+                if position < 0.0 || position >= length {
+                    self.finished = true;
+                }
+            }
+
+            if self.finished {
+                for channel in output.iter_chans_mut() {
+                    channel[index] = T::default();
+                }
+                continue;
+            }
+
+            let position = self.playhead.as_f64();
+            for (source, output) in source.iter_chans().zip(output.iter_chans_mut()) {
+                output[index] = T::from_f64(self.interpolate(source, position));
+            }
+            written += 1;
+            self.playhead = SamplesF64::from(self.wrap(position + step));
+        }
+
+        Samples::from(written)
+    }
+
+    fn interpolate<T: Sample>(&self, source: &[T], position: f64) -> f64 {
+        let whole = position.floor() as isize;
+        let t = position - whole as f64;
+        let sample = |offset: isize| self.sample_at(source, whole + offset);
+
+        self.interpolation.interpolate(sample, t)
+    }
+
+    /// Reads a source sample, wrapping around inside the loop and treating everything outside
+    /// the audio as silence.
+    fn sample_at<T: Sample>(&self, source: &[T], index: isize) -> f64 {
+        let index = match self.mode {
+            PlaybackMode::Loop { start, end } if index >= start.as_usize() as isize => {
+                let (start, end) = (start.as_usize() as isize, end.as_usize() as isize);
+                start + (index - start).rem_euclid(end - start)
+            }
+            _ => index,
+        };
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| source.get(index))
+            .map_or(0.0, |sample| sample.to_f64())
+    }
+
+    /// Keeps the playhead inside the loop once it has entered it.
+    fn wrap(&self, position: f64) -> f64 {
+        match self.mode {
+            PlaybackMode::OneShot => position,
+            PlaybackMode::Loop { start, end } => {
+                let (start, end) = (start.as_f64(), end.as_f64());
+                if position >= end || (position < start && self.rate.is_reverse()) {
+                    start + (position - start).rem_euclid(end - start)
+                } else {
+                    position
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Channels;
+
+    fn ramp(length: usize) -> Buffer<f64> {
+        let mut buffer = Buffer::allocate(Channels::from(1), Samples::from(length));
+        for (n, sample) in buffer.chan_mut(0).iter_mut().enumerate() {
+            *sample = n as f64;
+        }
+        buffer
+    }
+
+    fn read(reader: &mut VarispeedReader, source: &Buffer<f64>, length: usize) -> Vec<f64> {
+        let mut output = Buffer::allocate(Channels::from(1), Samples::from(length));
+        reader.read(source, &mut output);
+        output.chan(0).to_vec()
+    }
+
+    #[test_case(Interpolation::None => vec![1.0, 1.0, 2.0, 2.0, 3.0]; "none")]
+    #[test_case(Interpolation::Linear => vec![1.0, 1.5, 2.0, 2.5, 3.0]; "linear")]
+    #[test_case(Interpolation::Cubic => vec![1.0, 1.5, 2.0, 2.5, 3.0]; "cubic")]
+    fn half_speed_interpolates(interpolation: Interpolation) -> Vec<f64> {
+        let mut reader = VarispeedReader::new(interpolation);
+        reader.set_rate(PlaybackRate::from(0.5));
+        reader.set_playhead(SamplesF64::from(1.0));
+
+        read(&mut reader, &ramp(8), 5)
+    }
+
+    #[test]
+    fn one_shot_stops_at_the_end() {
+        let mut reader = VarispeedReader::new(Interpolation::Linear);
+        reader.set_rate(PlaybackRate::from(2.0));
+        let mut output = Buffer::allocate(Channels::from(1), Samples::from(6));
+
+        let read = reader.read(&ramp(6), &mut output);
+
+        assert_eq!(read, Samples::from(3));
+        assert_eq!(output.chan(0), &[0.0, 2.0, 4.0, 0.0, 0.0, 0.0]);
+        assert!(reader.is_finished());
+    }
+
+    #[test]
+    fn reverse_playback_stops_at_the_start() {
+        let mut reader = VarispeedReader::new(Interpolation::None);
+        reader.set_rate(PlaybackRate::from(-1.0));
+        reader.set_playhead(SamplesF64::from(2.0));
+
+        assert_eq!(read(&mut reader, &ramp(4), 4), vec![2.0, 1.0, 0.0, 0.0]);
+        assert!(reader.is_finished());
+    }
+
+    #[test]
+    fn loop_wraps_the_playhead() {
+        let mut reader = VarispeedReader::new(Interpolation::None);
+        reader.set_mode(PlaybackMode::Loop {
+            start: Samples::from(2),
+            end: Samples::from(5),
+        });
+
+        let output = read(&mut reader, &ramp(8), 10);
+
+        assert_eq!(
+            output,
+            vec![0.0, 1.0, 2.0, 3.0, 4.0, 2.0, 3.0, 4.0, 2.0, 3.0]
+        );
+        assert!(!reader.is_finished());
+    }
+
+    #[test]
+    fn linear_interpolation_wraps_across_the_loop_end() {
+        let mut reader = VarispeedReader::new(Interpolation::Linear);
+        reader.set_mode(PlaybackMode::Loop {
+            start: Samples::from(0),
+            end: Samples::from(4),
+        });
+        reader.set_playhead(SamplesF64::from(3.5));
+
+        // Halfway between the last sample of the loop (3) and the first (0).
+        assert_eq!(read(&mut reader, &ramp(8), 1), vec![1.5]);
+    }
+
+    #[test]
+    fn clip_sample_rate_is_taken_into_account() {
+        let clip = AudioClip::new(ramp(100), SampleRate::from(24000));
+        let mut reader = VarispeedReader::new(Interpolation::Linear);
+        let mut output = Buffer::allocate(Channels::from(1), Samples::from(3));
+
+        reader.read_clip(&clip, SampleRate::from(48000), &mut output);
+
+        assert_eq!(output.chan(0), &[0.0, 0.5, 1.0]);
+        assert_eq!(reader.playhead(), SamplesF64::from(1.5));
+    }
+}
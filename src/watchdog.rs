@@ -0,0 +1,249 @@
+//! This module contains a watchdog for the audio callback, which finds the causes of dropouts.
+//! Every callback is expected to come one block after the previous one, and to finish within
+//! the length of a block. A callback that comes too late means the device ran out of audio (an
+//! underrun), and a callback that takes longer than a block means the processing can't keep up
+//! (an overrun). The watchdog counts both, remembers the worst cases, and shows the load: the
+//! part of the block the processing takes, on average over the recent callbacks.
+//! ```rust
+//! use std::time::Instant;
+//!
+//! use rabu::units::Duration;
+//! use rabu::watchdog::CallbackWatchdog;
+//!
+//! // Blocks of 256 samples at 48 kHz.
+//! let mut watchdog = CallbackWatchdog::new(Duration::from_secs_f64(256.0 / 48000.0));
+//!
+//! // In the audio callback.
+//! watchdog.measure(|| {
+//!     // Process the block.
+//! });
+//!
+//! // On another thread, from a copy of the statistics.
+//! let stats = watchdog.stats();
+//! assert_eq!(stats.num_callbacks, 1);
+//! assert_eq!(stats.num_overruns, 0);
+//! assert!(stats.load.as_f64() < 100.0);
+//! ```
+
+use std::time::Instant;
+
+use crate::units::{Duration, Percentage};
+
+/// The number of callbacks the load is averaged over.
+pub const RECENT_CALLBACKS: usize = 32;
+
+/// The statistics of the callbacks a `CallbackWatchdog` has seen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CallbackStats {
+    /// The number of callbacks.
+    pub num_callbacks: u64,
+    /// The number of callbacks that came too late after the previous one.
+    pub num_underruns: u64,
+    /// The number of callbacks that took longer than a block.
+    pub num_overruns: u64,
+    /// The longest time between the starts of two callbacks.
+    pub worst_interval: Duration,
+    /// The longest time a callback took.
+    pub worst_duration: Duration,
+    /// The part of a block the callbacks took, on average over the recent callbacks.
+    pub load: Percentage,
+    /// The largest part of a block a callback took.
+    pub worst_load: Percentage,
+}
+
+/// Watches the timing of an audio callback. It doesn't allocate, so it can be used in the
+/// callback itself.
+#[derive(Clone, Debug)]
+pub struct CallbackWatchdog {
+    block_duration: Duration,
+    tolerance: Percentage,
+    last_start: Option<Instant>,
+    recent_loads: [f64; RECENT_CALLBACKS],
+    stats: CallbackStats,
+}
+
+impl CallbackWatchdog {
+    /// Creates a watchdog for callbacks that process blocks of the duration.
+    /// A callback may come 50% of a block late before it counts as an underrun.
+    /// This will panic if the duration isn't positive.
+    pub fn new(block_duration: Duration) -> Self {
+        assert!(block_duration.as_secs_f64() > 0.0);
+        Self {
+            block_duration,
+            tolerance: Percentage::from(50.0),
+            last_start: None,
+            recent_loads: [0.0; RECENT_CALLBACKS],
+            stats: CallbackStats {
+                num_callbacks: 0,
+                num_underruns: 0,
+                num_overruns: 0,
+                worst_interval: Duration::from_secs_f64(0.0),
+                worst_duration: Duration::from_secs_f64(0.0),
+                load: Percentage::from(0.0),
+                worst_load: Percentage::from(0.0),
+            },
+        }
+    }
+
+    /// Returns the duration of a block.
+    pub fn block_duration(&self) -> Duration {
+        self.block_duration
+    }
+
+    /// Returns how late a callback may come, as a part of a block, before it counts as an
+    /// underrun.
+    pub fn tolerance(&self) -> Percentage {
+        self.tolerance
+    }
+
+    /// Sets how late a callback may come, as a part of a block, before it counts as an underrun.
+    pub fn set_tolerance(&mut self, tolerance: Percentage) {
+        self.tolerance = tolerance;
+    }
+
+    /// Calls `callback`, and records when it started and how long it took.
+    pub fn measure<R>(&mut self, callback: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = callback();
+        self.record(start, Instant::now());
+        result
+    }
+
+    /// Records a callback that started and finished at the instants.
+    pub fn record(&mut self, start: Instant, end: Instant) {
+        let duration = end.duration_since(start).as_secs_f64();
+        let block = self.block_duration.as_secs_f64();
+
+        if let Some(last_start) = self.last_start {
+            let interval = start.saturating_duration_since(last_start).as_secs_f64();
+            if interval > block * (1.0 + self.tolerance.as_f64() / 100.0) {
+                self.stats.num_underruns += 1;
+            }
+            if interval > self.stats.worst_interval.as_secs_f64() {
+                self.stats.worst_interval = Duration::from_secs_f64(interval);
+            }
+        }
+        self.last_start = Some(start);
+
+        if duration > block {
+            self.stats.num_overruns += 1;
+        }
+        if duration > self.stats.worst_duration.as_secs_f64() {
+            self.stats.worst_duration = Duration::from_secs_f64(duration);
+        }
+
+        let load = duration / block * 100.0;
+        let index = (self.stats.num_callbacks % RECENT_CALLBACKS as u64) as usize;
+        self.recent_loads[index] = load;
+        self.stats.num_callbacks += 1;
+        let num_recent = (self.stats.num_callbacks as usize).min(RECENT_CALLBACKS);
+        let total: f64 = self.recent_loads[..num_recent].iter().sum();
+        self.stats.load = Percentage::from(total / num_recent as f64);
+        if load > self.stats.worst_load.as_f64() {
+            self.stats.worst_load = Percentage::from(load);
+        }
+    }
+
+    /// Returns the statistics of the callbacks so far.
+    pub fn stats(&self) -> CallbackStats {
+        self.stats
+    }
+
+    /// Forgets all callbacks, e.g. after the stream was restarted.
+    pub fn reset(&mut self) {
+        *self = Self {
+            tolerance: self.tolerance,
+            ..Self::new(self.block_duration)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: std::time::Duration = std::time::Duration::from_millis(10);
+
+    fn watchdog() -> CallbackWatchdog {
+        CallbackWatchdog::new(Duration::from(BLOCK))
+    }
+
+    #[test]
+    fn regular_callbacks_are_fine() {
+        let mut watchdog = watchdog();
+        let start = Instant::now();
+
+        for index in 0..10 {
+            let callback_start = start + BLOCK * index;
+            watchdog.record(callback_start, callback_start + BLOCK / 4);
+        }
+
+        let stats = watchdog.stats();
+        assert_eq!(stats.num_callbacks, 10);
+        assert_eq!(stats.num_underruns, 0);
+        assert_eq!(stats.num_overruns, 0);
+        assert!((stats.load.as_f64() - 25.0).abs() < 1e-6);
+        assert!((stats.worst_interval.as_secs_f64() - 0.01).abs() < 1e-9);
+    }
+
+    #[test]
+    fn detects_late_callbacks() {
+        let mut watchdog = watchdog();
+        let start = Instant::now();
+
+        watchdog.record(start, start);
+        watchdog.record(start + BLOCK * 3, start + BLOCK * 3);
+        watchdog.record(start + BLOCK * 4, start + BLOCK * 4);
+
+        let stats = watchdog.stats();
+        assert_eq!(stats.num_underruns, 1);
+        assert!((stats.worst_interval.as_secs_f64() - 0.03).abs() < 1e-9);
+    }
+
+    #[test]
+    fn detects_slow_callbacks() {
+        let mut watchdog = watchdog();
+        let start = Instant::now();
+
+        watchdog.record(start, start + BLOCK / 2);
+        watchdog.record(start + BLOCK, start + BLOCK * 5 / 2);
+
+        let stats = watchdog.stats();
+        assert_eq!(stats.num_overruns, 1);
+        assert!((stats.worst_duration.as_secs_f64() - 0.015).abs() < 1e-9);
+        assert!((stats.worst_load.as_f64() - 150.0).abs() < 1e-6);
+        assert!((stats.load.as_f64() - 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn load_follows_the_recent_callbacks() {
+        let mut watchdog = watchdog();
+        let start = Instant::now();
+
+        for index in 0..RECENT_CALLBACKS as u32 * 2 {
+            let callback_start = start + BLOCK * index;
+            let duration = if index < RECENT_CALLBACKS as u32 {
+                BLOCK
+            } else {
+                BLOCK / 10
+            };
+            watchdog.record(callback_start, callback_start + duration);
+        }
+
+        let stats = watchdog.stats();
+        assert!((stats.load.as_f64() - 10.0).abs() < 1e-6);
+        assert!((stats.worst_load.as_f64() - 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn reset_forgets_the_callbacks() {
+        let mut watchdog = watchdog();
+        watchdog.set_tolerance(Percentage::from(10.0));
+        watchdog.measure(|| ());
+
+        watchdog.reset();
+
+        assert_eq!(watchdog.stats().num_callbacks, 0);
+        assert_eq!(watchdog.tolerance(), Percentage::from(10.0));
+    }
+}
@@ -0,0 +1,776 @@
+//! This module contains a reader for WAV files, which gives back the audio as a buffer with the
+//! sample rate and sample format it was stored in. Integer PCM of 8 to 32 bits and 32 bit float
+//! are supported, also in the extensible format that files with more than two channels use.
+//! Chunks other than the format and the audio are skipped.
+//!
+//! It also contains a [`WavWriter`], which writes audio block by block, so long recordings don't
+//! have to fit in memory. Files that grow past 4 GB become RF64 files when they are finalized.
+//! Its counterpart is the [`WavReader`], which reads a file block by block from any position.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::quantize::{to_bytes, SampleFormat};
+//! use rabu::units::{BitDepth, Channels, SampleRate, Samples};
+//! use rabu::wav::WavFile;
+//!
+//! let mut audio = Buffer::<f32>::allocate(Channels::from(1), Samples::from(2));
+//! audio.chan_mut(0)[1] = 0.5;
+//! let (data, _) = to_bytes(&audio, SampleFormat::Int(BitDepth::Bits16), None);
+//!
+//! // A minimal file: the format chunk and the data chunk.
+//! let mut bytes = b"RIFF".to_vec();
+//! bytes.extend((4 + 24 + 8 + data.len() as u32).to_le_bytes());
+//! bytes.extend(b"WAVEfmt ");
+//! bytes.extend(16_u32.to_le_bytes());
+//! bytes.extend([1, 0, 1, 0]); // PCM, mono
+//! bytes.extend(44100_u32.to_le_bytes());
+//! bytes.extend((44100_u32 * 2).to_le_bytes());
+//! bytes.extend([2, 0, 16, 0]); // 2 bytes per frame, 16 bits
+//! bytes.extend(b"data");
+//! bytes.extend((data.len() as u32).to_le_bytes());
+//! bytes.extend(data);
+//!
+//! let file = WavFile::parse(&bytes).unwrap();
+//! assert_eq!(file.sample_rate(), SampleRate::from(44100));
+//! assert_eq!(file.format(), SampleFormat::Int(BitDepth::Bits16));
+//! assert_eq!(file.audio().chan(0), &[0.0, 0.5]);
+//! ```
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::buffer::Buffer;
+use crate::quantize::{from_bytes, from_bytes_into, to_bytes, SampleFormat};
+use crate::sample::Sample;
+use crate::units::{BitDepth, Channels, Duration, SampleRate, Samples};
+
+/// The format tag of integer PCM.
+const FORMAT_PCM: u16 = 1;
+/// The format tag of floating point PCM.
+const FORMAT_FLOAT: u16 = 3;
+/// The format tag of the extensible format, which has the real tag in its sub format.
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+/// The length of the header the writer writes: the RIFF header, a chunk that is reserved for
+/// the RF64 sizes, the format chunk and the header of the data chunk.
+const HEADER_LENGTH: u64 = 12 + 36 + 24 + 8;
+
+/// The audio of a WAV file.
+#[derive(Clone, Debug)]
+pub struct WavFile {
+    audio: Buffer<f32>,
+    sample_rate: SampleRate,
+    format: SampleFormat,
+}
+
+impl WavFile {
+    /// Creates a file from audio, e.g. to write it later.
+    pub fn new(audio: Buffer<f32>, sample_rate: SampleRate, format: SampleFormat) -> Self {
+        Self {
+            audio,
+            sample_rate,
+            format,
+        }
+    }
+
+    /// Reads the file at the path, or gives `None` when it can't be read or isn't a supported
+    /// WAV file.
+    pub fn open(path: impl AsRef<Path>) -> Option<Self> {
+        Self::parse(&std::fs::read(path).ok()?)
+    }
+
+    /// Parses the bytes of a WAV file, or gives `None` when they aren't a supported WAV file.
+    /// RF64 files are read as well, as long as the audio is the last chunk.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let riff = bytes.get(..4)?;
+        if (riff != b"RIFF" && riff != b"RF64") || bytes.get(8..12)? != b"WAVE" {
+            return None;
+        }
+
+        let mut format = None;
+        let mut chunks = bytes.get(12..)?;
+        while chunks.len() >= 8 {
+            let kind = &chunks[..4];
+            let length = u32::from_le_bytes(chunks[4..8].try_into().ok()?) as usize;
+            // Writers often leave the length of the data chunk open while they stream, and on
+            // 32 bit targets a length that doesn't fit goes to the end of the data as well.
+            let end = length.saturating_add(8);
+            let chunk = chunks.get(8..end).unwrap_or(&chunks[8..]);
+            match kind {
+                b"fmt " => format = Some(read_format(chunk)?),
+                b"data" => {
+                    let (sample_format, num_channels, sample_rate) = format?;
+                    return Some(Self {
+                        audio: from_bytes(chunk, sample_format, num_channels),
+                        sample_rate,
+                        format: sample_format,
+                    });
+                }
+                _ => {}
+            }
+            // Chunks are padded to an even length.
+            chunks = chunks
+                .get(end.saturating_add(length % 2)..)
+                .unwrap_or_default();
+        }
+        None
+    }
+
+    /// Returns the audio.
+    pub fn audio(&self) -> &Buffer<f32> {
+        &self.audio
+    }
+
+    /// Gives back the audio.
+    pub fn into_audio(self) -> Buffer<f32> {
+        self.audio
+    }
+
+    /// Returns the sample rate.
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// Returns the format the samples are stored in.
+    pub fn format(&self) -> SampleFormat {
+        self.format
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        self.audio.num_channels()
+    }
+
+    /// Returns the length of the audio.
+    pub fn duration(&self) -> Duration {
+        Duration::from(self.audio.num_samples().to_seconds(self.sample_rate))
+    }
+}
+
+/// Reads a WAV file block by block, so it doesn't have to fit in memory.
+#[derive(Debug)]
+pub struct WavReader<R: Read + Seek> {
+    reader: R,
+    sample_rate: SampleRate,
+    num_channels: Channels,
+    format: SampleFormat,
+    /// Where the audio starts in the file, in bytes.
+    data_start: u64,
+    num_samples: Samples,
+    position: Samples,
+    bytes: Vec<u8>,
+}
+
+impl WavReader<BufReader<File>> {
+    /// Opens the file at the path, or gives `None` when it can't be read or isn't a supported
+    /// WAV file.
+    pub fn open(path: impl AsRef<Path>) -> Option<Self> {
+        Self::new(BufReader::new(File::open(path).ok()?))
+    }
+}
+
+impl<R: Read + Seek> WavReader<R> {
+    /// Reads the header from the reader, or gives `None` when it isn't a supported WAV file.
+    /// RF64 files are read as well.
+    pub fn new(mut reader: R) -> Option<Self> {
+        let mut header = [0; 12];
+        reader.read_exact(&mut header).ok()?;
+        let is_rf64 = &header[..4] == b"RF64";
+        if (&header[..4] != b"RIFF" && !is_rf64) || &header[8..] != b"WAVE" {
+            return None;
+        }
+
+        let file_length = reader.seek(SeekFrom::End(0)).ok()?;
+        let mut position = 12;
+        let mut format = None;
+        let mut rf64_data_length = None;
+        loop {
+            reader.seek(SeekFrom::Start(position)).ok()?;
+            let mut chunk_header = [0; 8];
+            reader.read_exact(&mut chunk_header).ok()?;
+            let length = u32::from_le_bytes(chunk_header[4..].try_into().ok()?) as u64;
+            position += 8;
+            match &chunk_header[..4] {
+                b"fmt " | b"ds64" => {
+                    // A length past the end of the file would only make a large allocation
+                    // before the read fails.
+                    if length > file_length.saturating_sub(position) {
+                        return None;
+                    }
+                    let mut chunk = vec![0; length as usize];
+                    reader.read_exact(&mut chunk).ok()?;
+                    if &chunk_header[..4] == b"fmt " {
+                        format = Some(read_format(&chunk)?);
+                    } else {
+                        rf64_data_length =
+                            Some(u64::from_le_bytes(chunk.get(8..16)?.try_into().ok()?));
+                    }
+                }
+                b"data" => {
+                    let (format, num_channels, sample_rate) = format?;
+                    let length = match rf64_data_length {
+                        Some(length) if is_rf64 && length > 0 => length,
+                        _ => length,
+                    };
+                    // Writers often leave the length of the data chunk open while they stream.
+                    let length = length.min(file_length - position);
+                    let frame_length = num_channels.as_u64() * format.bytes_per_sample() as u64;
+                    return Some(Self {
+                        reader,
+                        sample_rate,
+                        num_channels,
+                        format,
+                        data_start: position,
+                        num_samples: Samples::from(length / frame_length),
+                        position: Samples::from(0),
+                        bytes: Vec::new(),
+                    });
+                }
+                _ => {}
+            }
+            // Chunks are padded to an even length.
+            position += length + length % 2;
+        }
+    }
+
+    /// Returns the sample rate.
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        self.num_channels
+    }
+
+    /// Returns the format the samples are stored in.
+    pub fn format(&self) -> SampleFormat {
+        self.format
+    }
+
+    /// Returns the number of samples per channel in the file.
+    pub fn num_samples(&self) -> Samples {
+        self.num_samples
+    }
+
+    /// Returns the length of the audio.
+    pub fn duration(&self) -> Duration {
+        Duration::from(self.num_samples.to_seconds(self.sample_rate))
+    }
+
+    /// Returns the position the next block is read from.
+    pub fn position(&self) -> Samples {
+        self.position
+    }
+
+    /// Moves to the position, which is limited to the end of the file, and gives `false` when
+    /// that fails.
+    pub fn seek(&mut self, position: Samples) -> bool {
+        let position = position.min(self.num_samples);
+        let offset = self.data_start + position.as_u64() * self.frame_length();
+        if self.reader.seek(SeekFrom::Start(offset)).is_err() {
+            return false;
+        }
+        self.position = position;
+        true
+    }
+
+    /// Reads a block of at most the number of samples, which is shorter at the end of the
+    /// file, and empty after it. Gives `None` when the file can't be read.
+    pub fn read(&mut self, num_samples: Samples) -> Option<Buffer<f32>> {
+        let num_samples = num_samples.min(self.num_samples - self.position);
+        let mut buffer = Buffer::allocate(self.num_channels, num_samples);
+        self.read_into(&mut buffer)?;
+        Some(buffer)
+    }
+
+    /// Reads a block like `read`, into a buffer that is already allocated. The buffer is
+    /// shortened to the samples that were read, which is fewer than it held at the end of the
+    /// file. Returns that number of samples, or `None` when the file can't be read. This only
+    /// allocates when the block is longer than any block read before it.
+    /// This will panic if the buffer doesn't have the channels of the file.
+    #[must_use]
+    pub fn read_into(&mut self, buffer: &mut Buffer<f32>) -> Option<Samples> {
+        assert_eq!(
+            buffer.num_channels(),
+            self.num_channels,
+            "the buffer must have the channels of the file"
+        );
+        let num_samples = buffer.num_samples().min(self.num_samples - self.position);
+        self.bytes
+            .resize((num_samples.as_u64() * self.frame_length()) as usize, 0);
+        self.reader.read_exact(&mut self.bytes).ok()?;
+        self.position += num_samples;
+        from_bytes_into(&self.bytes, self.format, buffer);
+        Some(num_samples)
+    }
+
+    fn frame_length(&self) -> u64 {
+        self.num_channels.as_u64() * self.format.bytes_per_sample() as u64
+    }
+}
+
+/// Writes a WAV file block by block. The header is finalized when the writer is dropped, but
+/// [`WavWriter::finalize`] can also be called in between, so the file is valid while it is still
+/// being recorded.
+#[derive(Debug)]
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    sample_rate: SampleRate,
+    num_channels: Channels,
+    format: SampleFormat,
+    num_samples: Samples,
+}
+
+impl WavWriter<BufWriter<File>> {
+    /// Creates the file at the path, or gives `None` when it can't be created.
+    /// This will panic if there are no channels.
+    pub fn create(
+        path: impl AsRef<Path>,
+        sample_rate: SampleRate,
+        num_channels: Channels,
+        format: SampleFormat,
+    ) -> Option<Self> {
+        let file = File::create(path).ok()?;
+        Self::new(BufWriter::new(file), sample_rate, num_channels, format)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Starts a file in the writer, or gives `None` when the header can't be written.
+    /// This will panic if there are no channels.
+    pub fn new(
+        mut writer: W,
+        sample_rate: SampleRate,
+        num_channels: Channels,
+        format: SampleFormat,
+    ) -> Option<Self> {
+        assert!(num_channels.as_usize() > 0);
+        writer
+            .write_all(&header(0, sample_rate, num_channels, format))
+            .ok()?;
+        Some(Self {
+            writer,
+            sample_rate,
+            num_channels,
+            format,
+            num_samples: Samples::from(0),
+        })
+    }
+
+    /// Returns the sample rate.
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// Returns the number of channels.
+    pub fn num_channels(&self) -> Channels {
+        self.num_channels
+    }
+
+    /// Returns the format the samples are stored in.
+    pub fn format(&self) -> SampleFormat {
+        self.format
+    }
+
+    /// Returns the number of samples per channel that are written.
+    pub fn num_samples(&self) -> Samples {
+        self.num_samples
+    }
+
+    /// Returns the length of the audio that is written.
+    pub fn duration(&self) -> Duration {
+        Duration::from(self.num_samples.to_seconds(self.sample_rate))
+    }
+
+    /// Appends the block, without dither, and gives `false` when it can't be written.
+    /// This will panic if the block doesn't have the same number of channels as the writer.
+    pub fn write<T: Sample>(&mut self, block: &Buffer<T>) -> bool {
+        assert_eq!(block.num_channels(), self.num_channels);
+        let (bytes, _) = to_bytes(block, self.format, None);
+        if self.writer.write_all(&bytes).is_err() {
+            return false;
+        }
+        self.num_samples += block.num_samples();
+        true
+    }
+
+    /// Writes the sizes in the header and flushes, and gives `false` when that fails. Writing
+    /// can go on afterwards.
+    pub fn finalize(&mut self) -> bool {
+        self.write_header().is_some()
+    }
+
+    fn write_header(&mut self) -> Option<()> {
+        let data_length = self.data_length();
+        // Chunks are padded to an even length, the next block overwrites the padding.
+        if data_length % 2 == 1 {
+            self.writer.write_all(&[0]).ok()?;
+        }
+        self.writer.seek(SeekFrom::Start(0)).ok()?;
+        let header = header(
+            data_length,
+            self.sample_rate,
+            self.num_channels,
+            self.format,
+        );
+        self.writer.write_all(&header).ok()?;
+        self.writer
+            .seek(SeekFrom::Start(HEADER_LENGTH + data_length))
+            .ok()?;
+        self.writer.flush().ok()
+    }
+
+    fn data_length(&self) -> u64 {
+        let frame_length = self.num_channels.as_u64() * self.format.bytes_per_sample() as u64;
+        self.num_samples.as_u64() * frame_length
+    }
+}
+
+impl<W: Write + Seek> Drop for WavWriter<W> {
+    fn drop(&mut self) {
+        self.finalize();
+    }
+}
+
+/// Builds the header of a file with the given length of audio in bytes. When the file doesn't
+/// fit in the 32 bit sizes of a RIFF file, it becomes an RF64 file, with the sizes in the chunk
+/// that is reserved for them.
+fn header(
+    data_length: u64,
+    sample_rate: SampleRate,
+    num_channels: Channels,
+    format: SampleFormat,
+) -> Vec<u8> {
+    let riff_length = HEADER_LENGTH - 8 + data_length + data_length % 2;
+    let is_rf64 = riff_length > u32::MAX as u64;
+    let length_u32 = |length: u64| if is_rf64 { u32::MAX } else { length as u32 };
+
+    let mut bytes = Vec::with_capacity(HEADER_LENGTH as usize);
+    bytes.extend(if is_rf64 { b"RF64" } else { b"RIFF" });
+    bytes.extend(length_u32(riff_length).to_le_bytes());
+    bytes.extend(b"WAVE");
+
+    bytes.extend(if is_rf64 { b"ds64" } else { b"JUNK" });
+    bytes.extend(28_u32.to_le_bytes());
+    if is_rf64 {
+        let frame_length = num_channels.as_u64() * format.bytes_per_sample() as u64;
+        bytes.extend(riff_length.to_le_bytes());
+        bytes.extend(data_length.to_le_bytes());
+        bytes.extend((data_length / frame_length).to_le_bytes());
+        bytes.extend(0_u32.to_le_bytes());
+    } else {
+        bytes.extend([0; 28]);
+    }
+
+    let tag = match format {
+        SampleFormat::Int(_) => FORMAT_PCM,
+        SampleFormat::Float32 => FORMAT_FLOAT,
+    };
+    let bits = 8 * format.bytes_per_sample() as u16;
+    let frame_length = num_channels.as_u32() * format.bytes_per_sample() as u32;
+    bytes.extend(b"fmt ");
+    bytes.extend(16_u32.to_le_bytes());
+    bytes.extend(tag.to_le_bytes());
+    bytes.extend((num_channels.as_u32() as u16).to_le_bytes());
+    bytes.extend(sample_rate.as_u32().to_le_bytes());
+    bytes.extend((sample_rate.as_u32() * frame_length).to_le_bytes());
+    bytes.extend((frame_length as u16).to_le_bytes());
+    bytes.extend(bits.to_le_bytes());
+
+    bytes.extend(b"data");
+    bytes.extend(length_u32(data_length).to_le_bytes());
+    bytes
+}
+
+/// Reads the sample format, the number of channels and the sample rate from a format chunk.
+fn read_format(chunk: &[u8]) -> Option<(SampleFormat, Channels, SampleRate)> {
+    let u16_at = |index: usize| {
+        Some(u16::from_le_bytes(
+            chunk.get(index..index + 2)?.try_into().ok()?,
+        ))
+    };
+    let mut tag = u16_at(0)?;
+    let num_channels = u16_at(2)?;
+    let sample_rate = u32::from_le_bytes(chunk.get(4..8)?.try_into().ok()?);
+    let bits = u16_at(14)?;
+    if tag == FORMAT_EXTENSIBLE {
+        // The sub format is a GUID that starts with the format tag.
+        tag = u16_at(24)?;
+    }
+
+    let format = match (tag, bits) {
+        (FORMAT_PCM, 8) => SampleFormat::Int(BitDepth::Bits8),
+        (FORMAT_PCM, 16) => SampleFormat::Int(BitDepth::Bits16),
+        (FORMAT_PCM, 24) => SampleFormat::Int(BitDepth::Bits24),
+        (FORMAT_PCM, 32) => SampleFormat::Int(BitDepth::Bits32),
+        (FORMAT_FLOAT, 32) => SampleFormat::Float32,
+        _ => return None,
+    };
+    if num_channels == 0 || sample_rate == 0 {
+        return None;
+    }
+    Some((
+        format,
+        Channels::from(num_channels as usize),
+        SampleRate::from(sample_rate),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::quantize::to_bytes;
+    use crate::units::Samples;
+
+    fn stereo() -> Buffer<f32> {
+        let mut audio = Buffer::allocate(Channels::from(2), Samples::from(3));
+        audio.chan_mut(0).copy_from_slice(&[0.0, 0.5, -0.5]);
+        audio.chan_mut(1).copy_from_slice(&[0.25, -1.0, 0.0]);
+        audio
+    }
+
+    /// Builds a file with the given format chunk, and the audio of `stereo` in the format.
+    fn file(format_chunk: &[u8], format: SampleFormat, extra_chunk: bool) -> Vec<u8> {
+        let (data, _) = to_bytes(&stereo(), format, None);
+        let mut chunks = b"fmt ".to_vec();
+        chunks.extend((format_chunk.len() as u32).to_le_bytes());
+        chunks.extend(format_chunk);
+        if extra_chunk {
+            chunks.extend(b"LIST");
+            chunks.extend(3_u32.to_le_bytes());
+            chunks.extend([1, 2, 3, 0]);
+        }
+        chunks.extend(b"data");
+        chunks.extend((data.len() as u32).to_le_bytes());
+        chunks.extend(data);
+
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend((4 + chunks.len() as u32).to_le_bytes());
+        bytes.extend(b"WAVE");
+        bytes.extend(chunks);
+        bytes
+    }
+
+    fn format_chunk(tag: u16, bits: u16) -> Vec<u8> {
+        let block_align = 2 * bits / 8;
+        let mut chunk = tag.to_le_bytes().to_vec();
+        chunk.extend(2_u16.to_le_bytes());
+        chunk.extend(48000_u32.to_le_bytes());
+        chunk.extend((48000 * block_align as u32).to_le_bytes());
+        chunk.extend(block_align.to_le_bytes());
+        chunk.extend(bits.to_le_bytes());
+        chunk
+    }
+
+    #[test_case(SampleFormat::Int(BitDepth::Bits8), FORMAT_PCM; "8 bits")]
+    #[test_case(SampleFormat::Int(BitDepth::Bits16), FORMAT_PCM; "16 bits")]
+    #[test_case(SampleFormat::Int(BitDepth::Bits24), FORMAT_PCM; "24 bits")]
+    #[test_case(SampleFormat::Int(BitDepth::Bits32), FORMAT_PCM; "32 bits")]
+    #[test_case(SampleFormat::Float32, FORMAT_FLOAT; "float")]
+    fn reads_the_formats(format: SampleFormat, tag: u16) {
+        let bits = 8 * format.bytes_per_sample() as u16;
+
+        let file = WavFile::parse(&file(&format_chunk(tag, bits), format, true)).unwrap();
+
+        assert_eq!(file.format(), format);
+        assert_eq!(file.sample_rate(), SampleRate::from(48000));
+        assert_eq!(file.audio().data(), stereo().data());
+    }
+
+    #[test]
+    fn reads_the_extensible_format() {
+        let mut chunk = format_chunk(FORMAT_EXTENSIBLE, 24);
+        chunk.extend(22_u16.to_le_bytes());
+        chunk.extend(24_u16.to_le_bytes());
+        chunk.extend(3_u32.to_le_bytes());
+        chunk.extend(FORMAT_PCM.to_le_bytes());
+        chunk.extend([0; 14]);
+        let format = SampleFormat::Int(BitDepth::Bits24);
+
+        let file = WavFile::parse(&file(&chunk, format, false)).unwrap();
+
+        assert_eq!(file.format(), format);
+        assert_eq!(file.num_channels(), Channels::from(2));
+    }
+
+    #[test]
+    fn rejects_unsupported_files() {
+        let format = SampleFormat::Int(BitDepth::Bits16);
+        let bytes = file(&format_chunk(FORMAT_PCM, 16), format, false);
+
+        assert!(WavFile::parse(&bytes[..20]).is_none());
+        assert!(WavFile::parse(&bytes[4..]).is_none());
+        assert!(WavFile::parse(&file(&format_chunk(FORMAT_FLOAT, 64), format, false)).is_none());
+        assert!(WavFile::open("does/not/exist.wav").is_none());
+    }
+
+    #[test]
+    fn refuses_chunks_longer_than_the_file() {
+        let format = SampleFormat::Int(BitDepth::Bits16);
+        let mut bytes = file(&format_chunk(FORMAT_PCM, 16), format, false);
+        // The length of the format chunk.
+        bytes[16..20].copy_from_slice(&[0xFF; 4]);
+
+        assert!(WavReader::new(Cursor::new(bytes)).is_none());
+    }
+
+    #[test]
+    fn data_of_unknown_length_goes_to_the_end() {
+        let format = SampleFormat::Int(BitDepth::Bits16);
+        let mut bytes = file(&format_chunk(FORMAT_PCM, 16), format, false);
+        // The length of the data chunk sits right before the 12 bytes of audio.
+        let length = bytes.len() - 12 - 4;
+        bytes[length..length + 4].copy_from_slice(&[0xFF; 4]);
+
+        let file = WavFile::parse(&bytes).unwrap();
+
+        assert_eq!(file.audio().data(), stereo().data());
+    }
+
+    #[test_case(SampleFormat::Int(BitDepth::Bits8); "8 bits")]
+    #[test_case(SampleFormat::Int(BitDepth::Bits24); "24 bits")]
+    #[test_case(SampleFormat::Float32; "float")]
+    fn writes_blocks_that_read_back(format: SampleFormat) {
+        let mut cursor = Cursor::new(Vec::new());
+        let mut writer = WavWriter::new(
+            &mut cursor,
+            SampleRate::from(1000),
+            Channels::from(2),
+            format,
+        )
+        .unwrap();
+        let audio = stereo();
+        for index in audio.sample_indices() {
+            let mut block = Buffer::<f32>::allocate(Channels::from(2), Samples::from(1));
+            block.chan_mut(0)[0] = audio.chan(0)[index];
+            block.chan_mut(1)[0] = audio.chan(1)[index];
+            assert!(writer.write(&block));
+        }
+        assert_eq!(writer.num_samples(), Samples::from(3));
+        assert_eq!(writer.duration(), Duration::from_secs_f64(0.003));
+        drop(writer);
+
+        let file = WavFile::parse(cursor.get_ref()).unwrap();
+
+        assert_eq!(file.format(), format);
+        assert_eq!(file.sample_rate(), SampleRate::from(1000));
+        assert_eq!(file.audio().data(), audio.data());
+    }
+
+    #[test]
+    fn finalize_keeps_the_file_valid_while_writing() {
+        let format = SampleFormat::Int(BitDepth::Bits8);
+        let mut cursor = Cursor::new(Vec::new());
+        let mut writer = WavWriter::new(
+            &mut cursor,
+            SampleRate::from(1000),
+            Channels::from(1),
+            format,
+        )
+        .unwrap();
+        let block = Buffer::<f32>::allocate(Channels::from(1), Samples::from(3));
+
+        writer.write(&block);
+        assert!(writer.finalize());
+        writer.write(&block);
+        drop(writer);
+        let bytes = cursor.into_inner();
+
+        // The padding byte of the first finalize is overwritten, and written again at the end.
+        assert_eq!(bytes.len() as u64, HEADER_LENGTH + 6);
+        let riff_length = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        assert_eq!(riff_length as usize, bytes.len() - 8);
+        assert_eq!(
+            WavFile::parse(&bytes).unwrap().audio().num_samples(),
+            Samples::from(6)
+        );
+    }
+
+    #[test]
+    fn reads_blocks_from_any_position() {
+        let format = SampleFormat::Int(BitDepth::Bits16);
+        let bytes = file(&format_chunk(FORMAT_PCM, 16), format, true);
+        let mut reader = WavReader::new(Cursor::new(bytes)).unwrap();
+
+        assert_eq!(reader.num_samples(), Samples::from(3));
+        assert_eq!(reader.num_channels(), Channels::from(2));
+        assert_eq!(reader.sample_rate(), SampleRate::from(48000));
+
+        let first = reader.read(Samples::from(2)).unwrap();
+        assert_eq!(first.chan(0), &stereo().chan(0)[..2]);
+        let rest = reader.read(Samples::from(2)).unwrap();
+        assert_eq!(rest.chan(1), &stereo().chan(1)[2..]);
+        assert_eq!(
+            reader.read(Samples::from(2)).unwrap().num_samples(),
+            Samples::from(0)
+        );
+
+        assert!(reader.seek(Samples::from(1)));
+        assert_eq!(reader.read(Samples::from(1)).unwrap().chan(0), &[0.5]);
+    }
+
+    #[test]
+    fn reads_blocks_into_a_buffer() {
+        let format = SampleFormat::Int(BitDepth::Bits16);
+        let bytes = file(&format_chunk(FORMAT_PCM, 16), format, true);
+        let mut reader = WavReader::new(Cursor::new(bytes)).unwrap();
+        let mut block = Buffer::allocate(Channels::from(2), Samples::from(2));
+
+        assert_eq!(reader.read_into(&mut block), Some(Samples::from(2)));
+        assert_eq!(block.chan(0), &stereo().chan(0)[..2]);
+        assert_eq!(reader.read_into(&mut block), Some(Samples::from(1)));
+        assert_eq!(block.chan(1), &stereo().chan(1)[2..]);
+    }
+
+    #[test]
+    fn reads_back_large_files() {
+        let mut bytes = header(
+            8,
+            SampleRate::from(1000),
+            Channels::from(1),
+            SampleFormat::Float32,
+        );
+        bytes.extend(0.5_f32.to_le_bytes());
+        bytes.extend((-0.5_f32).to_le_bytes());
+        // Pretends the file is an RF64 file, with the sizes in the reserved chunk.
+        bytes[..4].copy_from_slice(b"RF64");
+        bytes[12..16].copy_from_slice(b"ds64");
+        bytes[28..36].copy_from_slice(&8_u64.to_le_bytes());
+        bytes[HEADER_LENGTH as usize - 4..HEADER_LENGTH as usize].copy_from_slice(&[0xFF; 4]);
+
+        let mut reader = WavReader::new(Cursor::new(bytes.clone())).unwrap();
+
+        assert_eq!(reader.read(Samples::from(4)).unwrap().chan(0), &[0.5, -0.5]);
+        assert_eq!(
+            WavFile::parse(&bytes).unwrap().audio().chan(0),
+            &[0.5, -0.5]
+        );
+    }
+
+    #[test]
+    fn large_files_become_rf64() {
+        let format = SampleFormat::Int(BitDepth::Bits16);
+        let data_length = 4 * (u32::MAX as u64 + 1);
+
+        let bytes = header(
+            data_length,
+            SampleRate::from(48000),
+            Channels::from(2),
+            format,
+        );
+        let u64_at = |index: usize| u64::from_le_bytes(bytes[index..index + 8].try_into().unwrap());
+
+        assert_eq!(bytes.len() as u64, HEADER_LENGTH);
+        assert_eq!(&bytes[..4], b"RF64");
+        assert_eq!(&bytes[4..8], &[0xFF; 4]);
+        assert_eq!(&bytes[12..16], b"ds64");
+        assert_eq!(u64_at(20), HEADER_LENGTH - 8 + data_length);
+        assert_eq!(u64_at(28), data_length);
+        assert_eq!(u64_at(36), data_length / 4);
+        assert_eq!(&bytes[bytes.len() - 4..], &[0xFF; 4]);
+    }
+}
@@ -0,0 +1,222 @@
+//! This module contains a waveshaper, which saturates audio by sending it through a transfer
+//! curve. The drive sets how hard the curve is hit, and the output gain brings the level back.
+//! Shaping creates harmonics that can go above Nyquist and alias, so the shaper can run
+//! oversampled.
+//! ```rust
+//! use rabu::waveshaper::{ShaperCurve, Waveshaper};
+//! use rabu::units::{Decibels, Latency, SampleRate};
+//!
+//! let mut shaper = Waveshaper::new(ShaperCurve::Tanh);
+//! shaper.set_drive(Decibels::from(12.0));
+//! shaper.set_output_gain(Decibels::from(-6.0));
+//! shaper.set_oversampling(4);
+//!
+//! let mut block = [0.5_f32; 256];
+//! shaper.process_block(&mut block);
+//!
+//! assert_ne!(shaper.latency(SampleRate::from(48000)), Latency::from_secs_f64(0.0));
+//! ```
+
+use crate::oversampling::Oversampler;
+use crate::processor::SampleProcessor;
+use crate::sample::Sample;
+use crate::units::{Decibels, Latency, SampleRate};
+
+/// A transfer curve, which maps input samples to output samples.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ShaperCurve {
+    /// The hyperbolic tangent: smooth saturation, like a tube or tape.
+    Tanh,
+    /// A cubic curve that is linear-ish near 0 and flattens out at 1.
+    SoftClip,
+    /// Cuts everything above 1, which is harsh and aliases the most.
+    HardClip,
+    /// A tanh that is shifted by the bias, so the positive and negative halves saturate
+    /// differently, which adds even harmonics. The output is shifted back, so silence stays
+    /// silent.
+    Asymmetric { bias: f64 },
+}
+
+impl ShaperCurve {
+    /// Returns the output of the curve for the given input.
+    pub fn apply(&self, input: f64) -> f64 {
+        match self {
+            ShaperCurve::Tanh => input.tanh(),
+            ShaperCurve::SoftClip => {
+                let x = input.clamp(-1.0, 1.0);
+                1.5 * (x - x * x * x / 3.0)
+            }
+            ShaperCurve::HardClip => input.clamp(-1.0, 1.0),
+            ShaperCurve::Asymmetric { bias } => (input + bias).tanh() - bias.tanh(),
+        }
+    }
+}
+
+/// Saturates a mono signal with a transfer curve.
+#[derive(Clone, Debug)]
+pub struct Waveshaper {
+    curve: ShaperCurve,
+    drive: Decibels,
+    output_gain: Decibels,
+    oversampler: Oversampler,
+}
+
+impl Waveshaper {
+    /// Creates a new waveshaper without drive, output gain or oversampling.
+    pub fn new(curve: ShaperCurve) -> Self {
+        Self {
+            curve,
+            drive: Decibels::from(0.0),
+            output_gain: Decibels::from(0.0),
+            oversampler: Oversampler::new(1),
+        }
+    }
+
+    /// Returns the transfer curve.
+    pub fn curve(&self) -> ShaperCurve {
+        self.curve
+    }
+
+    /// Changes the transfer curve.
+    pub fn set_curve(&mut self, curve: ShaperCurve) {
+        self.curve = curve;
+    }
+
+    /// Returns the drive.
+    pub fn drive(&self) -> Decibels {
+        self.drive
+    }
+
+    /// Changes the gain before the curve.
+    pub fn set_drive(&mut self, drive: Decibels) {
+        self.drive = drive;
+    }
+
+    /// Returns the output gain.
+    pub fn output_gain(&self) -> Decibels {
+        self.output_gain
+    }
+
+    /// Changes the gain after the curve.
+    pub fn set_output_gain(&mut self, output_gain: Decibels) {
+        self.output_gain = output_gain;
+    }
+
+    /// Returns the oversampling factor.
+    pub fn oversampling(&self) -> usize {
+        self.oversampler.factor()
+    }
+
+    /// Changes the oversampling factor, where 1 turns oversampling off. This clears the
+    /// internal state, and changes the latency.
+    /// This will panic if the factor is 0.
+    pub fn set_oversampling(&mut self, factor: usize) {
+        self.oversampler = Oversampler::new(factor);
+    }
+
+    /// Returns the delay caused by oversampling.
+    pub fn latency(&self, sample_rate: SampleRate) -> Latency {
+        self.oversampler.latency(sample_rate)
+    }
+
+    /// Clears the internal state, as if no audio was processed yet.
+    pub fn reset(&mut self) {
+        self.oversampler.reset();
+    }
+
+    /// Processes one sample.
+    pub fn process(&mut self, input: f64) -> f64 {
+        let drive = self.drive.to_gain();
+        let curve = self.curve;
+        let shaped = self
+            .oversampler
+            .process(input, |sample| curve.apply(sample * drive));
+        shaped * self.output_gain.to_gain()
+    }
+
+    /// Processes the samples in place.
+    pub fn process_block<T: Sample>(&mut self, samples: &mut [T]) {
+        for sample in samples.iter_mut() {
+            *sample = T::from_f64(self.process(sample.to_f64()));
+        }
+    }
+}
+
+impl SampleProcessor for Waveshaper {
+    fn process(&mut self, input: f64) -> f64 {
+        Waveshaper::process(self, input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::fft::{Complex, RealFft};
+    use crate::units::Samples;
+
+    #[test_case(ShaperCurve::Tanh, 30.0 => 1.0; "tanh saturates")]
+    #[test_case(ShaperCurve::SoftClip, 1.0 => 1.0; "soft clip reaches 1")]
+    #[test_case(ShaperCurve::SoftClip, 3.0 => 1.0; "soft clip stays at 1")]
+    #[test_case(ShaperCurve::HardClip, -3.0 => -1.0; "hard clip")]
+    #[test_case(ShaperCurve::HardClip, 0.5 => 0.5; "hard clip is linear below 1")]
+    #[test_case(ShaperCurve::Asymmetric { bias: 0.3 }, 0.0 => 0.0; "asymmetric keeps silence")]
+    fn curves(curve: ShaperCurve, input: f64) -> f64 {
+        (curve.apply(input) * 1e9).round() / 1e9
+    }
+
+    #[test]
+    fn asymmetric_curve_saturates_the_halves_differently() {
+        let curve = ShaperCurve::Asymmetric { bias: 0.5 };
+        assert!(curve.apply(2.0) < -curve.apply(-2.0));
+    }
+
+    #[test]
+    fn drive_and_output_gain() {
+        let mut shaper = Waveshaper::new(ShaperCurve::HardClip);
+        shaper.set_drive(Decibels::from(20.0));
+        shaper.set_output_gain(Decibels::from(-6.0));
+
+        assert!((shaper.process(0.5) - Decibels::from(-6.0).to_gain()).abs() < 1e-12);
+        assert!((shaper.process(0.01) - 0.1 * Decibels::from(-6.0).to_gain()).abs() < 1e-12);
+    }
+
+    /// Returns the part of the energy that ends up in between the harmonics of a clipped sine,
+    /// which is where aliasing lands.
+    fn aliasing(oversampling: usize) -> f64 {
+        let harmonic_spacing = 233;
+        let w = 2.0 * std::f64::consts::PI * harmonic_spacing as f64 / 4096.0;
+        let mut shaper = Waveshaper::new(ShaperCurve::HardClip);
+        shaper.set_drive(Decibels::from(12.0));
+        shaper.set_oversampling(oversampling);
+
+        let signal: Vec<f64> = (0..8192)
+            .map(|n| shaper.process((w * n as f64).sin()))
+            .skip(4096)
+            .collect();
+        let mut bins = vec![Complex::default(); 2049];
+        RealFft::new(Samples::from(4096)).forward(&signal, &mut bins);
+
+        let (aliased, total) =
+            bins.iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(aliased, total), (index, bin)| {
+                    let is_harmonic = index % harmonic_spacing == 0;
+                    let energy = bin.norm_sqr();
+                    (
+                        aliased + if is_harmonic { 0.0 } else { energy },
+                        total + energy,
+                    )
+                });
+        aliased / total
+    }
+
+    #[test]
+    fn oversampling_reduces_aliasing() {
+        let plain = aliasing(1);
+        let oversampled = aliasing(4);
+
+        assert!(oversampled < plain * 0.1, "{oversampled} vs {plain}");
+    }
+}
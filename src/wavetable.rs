@@ -0,0 +1,343 @@
+//! This module contains wavetable synthesis. A `Wavetable` holds one cycle of a waveform in
+//! several versions (mipmaps), each with half the harmonics of the one before, so the
+//! oscillator can always pick a version without harmonics above Nyquist and doesn't alias.
+//! The `WavetableOscillator` plays a list of tables and can morph between neighbouring ones.
+//! ```rust
+//! use rabu::buffer::Buffer;
+//! use rabu::units::{Channels, Frequency, SampleRate, Samples};
+//! use rabu::wavetable::{Wavetable, WavetableOscillator};
+//!
+//! let saw = Wavetable::from_fn(|phase| 2.0 * phase - 1.0);
+//! let sine = Wavetable::from_fn(|phase| (2.0 * std::f64::consts::PI * phase).sin());
+//!
+//! let mut osc = WavetableOscillator::new(
+//!     vec![saw, sine],
+//!     Frequency::from(220.0),
+//!     SampleRate::from(48000),
+//! );
+//! osc.set_morph(0.5);
+//!
+//! let mut buffer = Buffer::<f32>::allocate(Channels::from(1), Samples::from(512));
+//! osc.render(&mut buffer);
+//! ```
+
+use std::f64::consts::PI;
+
+use crate::buffer::Buffer;
+use crate::fft::{Complex, RealFft};
+use crate::sample::Sample;
+use crate::units::{Frequency, SampleRate, Samples};
+
+/// One cycle of a waveform, stored band-limited at every octave.
+#[derive(Clone, Debug)]
+pub struct Wavetable {
+    /// Level `n` contains the harmonics up to `MAX_HARMONICS >> n`. Every level has one extra
+    /// sample at the end that repeats the first, so interpolation doesn't have to wrap.
+    levels: Vec<Vec<f64>>,
+}
+
+impl Wavetable {
+    /// The number of samples in every table.
+    pub const TABLE_SIZE: usize = 2048;
+    /// The number of harmonics in the fullest table.
+    pub const MAX_HARMONICS: usize = Self::TABLE_SIZE / 2 - 1;
+
+    /// Creates a wavetable from the first channel of the buffer, which should contain exactly
+    /// one cycle of the waveform. The cycle can have any length.
+    /// This will panic if the buffer is empty.
+    pub fn from_buffer<T: Sample>(buffer: &Buffer<T>) -> Self {
+        let cycle: Vec<f64> = buffer
+            .chan(0)
+            .iter()
+            .map(|sample| sample.to_f64())
+            .collect();
+        Self::from_cycle(&cycle)
+    }
+
+    /// Creates a wavetable from a function that gives the waveform at a phase between 0 and 1.
+    pub fn from_fn(waveform: impl Fn(f64) -> f64) -> Self {
+        let cycle: Vec<f64> = (0..Self::TABLE_SIZE)
+            .map(|index| waveform(index as f64 / Self::TABLE_SIZE as f64))
+            .collect();
+        Self::from_cycle(&cycle)
+    }
+
+    /// Returns the number of mipmap levels.
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Returns the level that has as many harmonics as possible, without any of them going
+    /// above Nyquist when played at the given frequency.
+    pub fn level_for(&self, frequency: Frequency, sample_rate: SampleRate) -> usize {
+        let allowed = sample_rate.as_f64() / 2.0 / frequency.as_f64().abs();
+        (0..self.levels.len())
+            .find(|level| (Self::MAX_HARMONICS >> level) as f64 <= allowed)
+            .unwrap_or(self.levels.len() - 1)
+    }
+
+    /// Returns the value of the given level at a phase between 0 and 1, interpolating linearly.
+    pub fn value_at(&self, level: usize, phase: f64) -> f64 {
+        let table = &self.levels[level];
+        let position = phase.rem_euclid(1.0) * Self::TABLE_SIZE as f64;
+        let index = (position.floor() as usize).min(Self::TABLE_SIZE - 1);
+        let fraction = position - index as f64;
+        table[index] + (table[index + 1] - table[index]) * fraction
+    }
+
+    fn from_cycle(cycle: &[f64]) -> Self {
+        assert!(!cycle.is_empty(), "a wavetable needs at least one sample");
+        let harmonics = Self::harmonics(cycle);
+        let mut fft = RealFft::new(Samples::from(Self::TABLE_SIZE));
+        let mut bins = vec![Complex::default(); Self::TABLE_SIZE / 2 + 1];
+
+        let mut levels = Vec::new();
+        let mut max_harmonic = Self::MAX_HARMONICS;
+        loop {
+            bins.fill(Complex::default());
+            for (bin, harmonic) in bins.iter_mut().zip(&harmonics).take(max_harmonic + 1) {
+                *bin = *harmonic * Self::TABLE_SIZE as f64;
+            }
+
+            let mut table = vec![0.0; Self::TABLE_SIZE + 1];
+            fft.inverse(&bins, &mut table[..Self::TABLE_SIZE]);
+            table[Self::TABLE_SIZE] = table[0];
+            levels.push(table);
+
+            if max_harmonic == 1 {
+                break;
+            }
+            max_harmonic >>= 1;
+        }
+
+        Self { levels }
+    }
+
+    /// Returns the complex amplitudes of DC and the harmonics of the cycle that fit in the
+    /// fullest table. The Nyquist bin of the cycle is left out, since its phase is ambiguous.
+    fn harmonics(cycle: &[f64]) -> Vec<Complex> {
+        let length = cycle.len();
+        let num_harmonics = Self::MAX_HARMONICS.min(length.saturating_sub(1) / 2);
+        (0..=num_harmonics)
+            .map(|k| {
+                let sum = cycle
+                    .iter()
+                    .enumerate()
+                    .fold(Complex::default(), |sum, (n, x)| {
+                        let angle = -2.0 * PI * (k * n) as f64 / length as f64;
+                        sum + Complex::from_polar(*x, angle)
+                    });
+                sum * (1.0 / length as f64)
+            })
+            .collect()
+    }
+}
+
+/// Plays a list of wavetables, morphing between neighbouring tables.
+#[derive(Clone, Debug)]
+pub struct WavetableOscillator {
+    tables: Vec<Wavetable>,
+    frequency: Frequency,
+    sample_rate: SampleRate,
+    increment: f64,
+    level: usize,
+    phase: f64,
+    morph: f64,
+}
+
+impl WavetableOscillator {
+    /// Creates a new oscillator that plays the first table, starting at phase 0.
+    /// This will panic if no tables are given.
+    pub fn new(tables: Vec<Wavetable>, frequency: Frequency, sample_rate: SampleRate) -> Self {
+        assert!(
+            !tables.is_empty(),
+            "the oscillator needs at least one table"
+        );
+        let mut osc = Self {
+            tables,
+            frequency,
+            sample_rate,
+            increment: 0.0,
+            level: 0,
+            phase: 0.0,
+            morph: 0.0,
+        };
+        osc.update_increment();
+        osc
+    }
+
+    /// Returns the number of tables.
+    pub fn num_tables(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Returns the frequency.
+    pub fn frequency(&self) -> Frequency {
+        self.frequency
+    }
+
+    /// Changes the frequency, keeping the phase.
+    pub fn set_frequency(&mut self, frequency: Frequency) {
+        self.frequency = frequency;
+        self.update_increment();
+    }
+
+    /// Changes the sample rate, keeping the phase.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = sample_rate;
+        self.update_increment();
+    }
+
+    /// Returns the morph position.
+    pub fn morph(&self) -> f64 {
+        self.morph
+    }
+
+    /// Sets the morph position, where 0 is the first table, 1 the second, and values in between
+    /// crossfade between the neighbouring tables. The position is clamped to the tables.
+    pub fn set_morph(&mut self, morph: f64) {
+        self.morph = morph.clamp(0.0, (self.tables.len() - 1) as f64);
+    }
+
+    /// Returns the current phase in cycles, between 0 and 1.
+    pub fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    /// Jumps to the given phase in cycles.
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    /// Resets the phase to 0.
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    /// Produces the next sample.
+    pub fn next_sample(&mut self) -> f64 {
+        let first = self.morph.floor() as usize;
+        let fraction = self.morph - first as f64;
+
+        let mut value = self.tables[first].value_at(self.level, self.phase);
+        if fraction > 0.0 {
+            let next = self.tables[first + 1].value_at(self.level, self.phase);
+            value += (next - value) * fraction;
+        }
+
+        self.phase = (self.phase + self.increment).rem_euclid(1.0);
+        value
+    }
+
+    /// Fills the given slice with the next samples.
+    pub fn render_into<T: Sample>(&mut self, output: &mut [T]) {
+        for sample in output.iter_mut() {
+            *sample = T::from_f64(self.next_sample());
+        }
+    }
+
+    /// Fills every channel of the buffer with the same next samples.
+    pub fn render<T: Sample>(&mut self, buffer: &mut Buffer<T>) {
+        for index in buffer.sample_indices() {
+            let value = T::from_f64(self.next_sample());
+            for channel in buffer.iter_chans_mut() {
+                channel[index] = value;
+            }
+        }
+    }
+
+    fn update_increment(&mut self) {
+        self.increment = self.frequency.as_f64() / self.sample_rate.as_f64();
+        self.level = self.tables[0].level_for(self.frequency, self.sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::Channels;
+
+    fn sine(phase: f64) -> f64 {
+        (2.0 * PI * phase).sin()
+    }
+
+    #[test]
+    fn table_from_a_buffer_of_any_length() {
+        let mut cycle = Buffer::<f32>::allocate(Channels::from(1), Samples::from(100));
+        for (n, sample) in cycle.chan_mut(0).iter_mut().enumerate() {
+            *sample = sine(n as f64 / 100.0) as f32;
+        }
+
+        let table = Wavetable::from_buffer(&cycle);
+
+        for n in 0..64 {
+            let phase = n as f64 / 64.0;
+            assert!((table.value_at(0, phase) - sine(phase)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn every_level_halves_the_harmonics() {
+        let table = Wavetable::from_fn(|phase| sine(phase) + 0.5 * sine(3.0 * phase));
+
+        assert_eq!(table.num_levels(), 10);
+        for n in 0..32 {
+            let phase = n as f64 / 32.0;
+            // The level with three harmonics still has the third one...
+            let third = sine(phase) + 0.5 * sine(3.0 * phase);
+            assert!((table.value_at(8, phase) - third).abs() < 1e-3);
+            // ...but the last level only has the fundamental left.
+            assert!((table.value_at(9, phase) - sine(phase)).abs() < 1e-3);
+        }
+    }
+
+    #[test_case(20.0 => 0; "low note uses all harmonics")]
+    #[test_case(100.0 => 3; "mid note")]
+    #[test_case(12000.0 => 9; "high note uses the fundamental")]
+    fn level_keeps_harmonics_below_nyquist(frequency: f64) -> usize {
+        let table = Wavetable::from_fn(sine);
+        table.level_for(Frequency::from(frequency), SampleRate::from(48000))
+    }
+
+    #[test]
+    fn morphing_crossfades_between_tables() {
+        let tables = vec![Wavetable::from_fn(sine), Wavetable::from_fn(|p| -sine(p))];
+        let mut osc =
+            WavetableOscillator::new(tables, Frequency::from(440.0), SampleRate::from(48000));
+        osc.set_morph(0.5);
+
+        let mut output = vec![1.0_f64; 100];
+        osc.render_into(&mut output);
+
+        assert!(output.iter().all(|s| s.abs() < 1e-9));
+    }
+
+    #[test]
+    fn morph_is_clamped_to_the_tables() {
+        let tables = vec![Wavetable::from_fn(sine), Wavetable::from_fn(sine)];
+        let mut osc =
+            WavetableOscillator::new(tables, Frequency::from(440.0), SampleRate::from(48000));
+
+        osc.set_morph(5.0);
+        assert_eq!(osc.morph(), 1.0);
+
+        osc.set_morph(-1.0);
+        assert_eq!(osc.morph(), 0.0);
+    }
+
+    #[test]
+    fn plays_at_the_given_frequency() {
+        let mut osc = WavetableOscillator::new(
+            vec![Wavetable::from_fn(sine)],
+            Frequency::from(1000.0),
+            SampleRate::from(48000),
+        );
+
+        for n in 0..480 {
+            let expected = sine(n as f64 * 1000.0 / 48000.0);
+            assert!((osc.next_sample() - expected).abs() < 1e-5);
+        }
+    }
+}
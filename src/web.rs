@@ -0,0 +1,165 @@
+//! This module contains helpers to run processors in an `AudioWorklet` of the Web Audio API,
+//! when the crate is compiled to `wasm32-unknown-unknown` with the `web` feature. A worklet
+//! processes render quanta of 128 frames, with every channel in its own `Float32Array`.
+//! The channels can be copied in and out of the processor from slices, or JavaScript can view
+//! the channels of the processor in the memory of the module through their pointers, and have
+//! them processed in place:
+//! ```js
+//! // `channel_pointer` is a function of the module that calls the one of the processor.
+//! const pointer = exports.channel_pointer(processor, channel);
+//! const samples = new Float32Array(exports.memory.buffer, pointer, 128);
+//! ```
+//! ```rust
+//! use rabu::gate::Gate;
+//! use rabu::units::{Channels, Decibels, SampleRate, Samples};
+//! use rabu::web::{WorkletProcessor, RENDER_QUANTUM};
+//!
+//! let sample_rate = SampleRate::from(48000);
+//! let gate = Gate::new(Decibels::from(-60.0), sample_rate);
+//! let mut worklet = WorkletProcessor::new(gate, sample_rate, Channels::from(2));
+//!
+//! let input = [[0.5; RENDER_QUANTUM], [0.25; RENDER_QUANTUM]];
+//! let (mut left, mut right) = ([0.0; RENDER_QUANTUM], [0.0; RENDER_QUANTUM]);
+//! worklet.process(&[&input[0], &input[1]], &mut [&mut left, &mut right]);
+//!
+//! assert_eq!(worklet.context().position, Samples::from(128));
+//! ```
+
+use crate::buffer::Buffer;
+use crate::processor::{AudioProcessor, ProcessContext};
+use crate::units::{BufferSize, Channels, SampleRate, Samples};
+
+/// The number of frames an `AudioWorklet` processes at once.
+pub const RENDER_QUANTUM: usize = 128;
+
+/// Runs a processor in render quanta, keeping track of the position of the timeline.
+pub struct WorkletProcessor<P> {
+    processor: P,
+    buffer: Buffer<f32>,
+    context: ProcessContext,
+}
+
+impl<P: AudioProcessor> WorkletProcessor<P> {
+    /// Prepares the processor for render quanta with the given number of channels, on a
+    /// timeline that is playing from the start.
+    pub fn new(mut processor: P, sample_rate: SampleRate, num_channels: Channels) -> Self {
+        processor.prepare(sample_rate, BufferSize::from(RENDER_QUANTUM), num_channels);
+        Self {
+            processor,
+            buffer: Buffer::allocate(num_channels, Samples::from(RENDER_QUANTUM)),
+            context: ProcessContext {
+                is_playing: true,
+                ..ProcessContext::new(sample_rate)
+            },
+        }
+    }
+
+    /// Returns the processor.
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+
+    /// Returns the processor, e.g. to change its parameters.
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    /// Returns the context of the next render quantum.
+    pub fn context(&self) -> &ProcessContext {
+        &self.context
+    }
+
+    /// Returns the context of the next render quantum, e.g. to set the tempo. The position
+    /// moves on by a render quantum after every one.
+    pub fn context_mut(&mut self) -> &mut ProcessContext {
+        &mut self.context
+    }
+
+    /// Returns a pointer to the 128 samples of the channel, so JavaScript can view them as a
+    /// `Float32Array` for `process_in_place`. The pointer stays valid as long as the processor
+    /// does.
+    /// This will panic if the channel doesn't exist.
+    pub fn channel_pointer(&mut self, channel: usize) -> *mut f32 {
+        self.buffer.chan_mut(channel).as_mut_ptr()
+    }
+
+    /// Processes the render quantum that was written into the channels of the processor.
+    pub fn process_in_place(&mut self) {
+        self.processor.process(&mut self.buffer, &self.context);
+        self.context.position += Samples::from(RENDER_QUANTUM);
+    }
+
+    /// Processes a render quantum from the inputs into the outputs. Web Audio gives empty
+    /// inputs when nothing is connected, so channels that are missing or empty are silent, and
+    /// channels the processor doesn't have are skipped.
+    pub fn process(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]]) {
+        copy_from_channels(&mut self.buffer, inputs);
+        self.process_in_place();
+        copy_to_channels(&self.buffer, outputs);
+    }
+}
+
+/// Copies the channels into the buffer, as far as they fit, and makes the rest of the buffer
+/// silent.
+pub fn copy_from_channels(buffer: &mut Buffer<f32>, channels: &[&[f32]]) {
+    buffer.fill_default();
+    for (to, from) in buffer.iter_chans_mut().zip(channels) {
+        let length = to.len().min(from.len());
+        to[..length].copy_from_slice(&from[..length]);
+    }
+}
+
+/// Copies the buffer into the channels, as far as they fit.
+pub fn copy_to_channels(buffer: &Buffer<f32>, channels: &mut [&mut [f32]]) {
+    for (from, to) in buffer.iter_chans().zip(channels.iter_mut()) {
+        let length = to.len().min(from.len());
+        to[..length].copy_from_slice(&from[..length]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Halves the audio.
+    struct Half;
+
+    impl AudioProcessor for Half {
+        fn prepare(&mut self, _: SampleRate, buffer_size: BufferSize, _: Channels) {
+            assert_eq!(buffer_size.as_usize(), RENDER_QUANTUM);
+        }
+
+        fn process(&mut self, buffer: &mut Buffer<f32>, _: &ProcessContext) {
+            buffer.map_samples(|sample| sample * 0.5);
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    #[test]
+    fn missing_inputs_are_silent() {
+        let mut worklet = WorkletProcessor::new(Half, SampleRate::from(48000), Channels::from(2));
+        let input = [1.0; RENDER_QUANTUM];
+        let mut output = [[1.0; RENDER_QUANTUM]; 2];
+        let [left, right] = &mut output;
+
+        worklet.process(&[&input, &[]], &mut [left, right]);
+
+        assert!(output[0].iter().all(|&sample| sample == 0.5));
+        assert!(output[1].iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn processes_the_channels_in_place() {
+        let mut worklet = WorkletProcessor::new(Half, SampleRate::from(48000), Channels::from(1));
+
+        let pointer = worklet.channel_pointer(0);
+        // This is what a `Float32Array` view in JavaScript does.
+        unsafe { pointer.write(1.0) };
+        worklet.process_in_place();
+        worklet.process_in_place();
+
+        assert_eq!(unsafe { pointer.read() }, 0.25);
+        assert_eq!(worklet.context().position, Samples::from(256));
+    }
+}
@@ -0,0 +1,217 @@
+//! This module contains the window functions that are used for spectral analysis and filter
+//! design, together with their correction factors. Windows come in two flavours: symmetric
+//! windows are meant for filter design, periodic windows are meant for FFT analysis.
+//! ```rust
+//! use rabu::windows::Window;
+//!
+//! let mut window = [0.0_f32; 1024];
+//! Window::Hann.fill_periodic(&mut window);
+//!
+//! // Correct a windowed amplitude measurement for the energy the window removed.
+//! let coherent_gain = Window::Hann.coherent_gain(1024);
+//! assert!((coherent_gain - 0.5).abs() < 1e-9);
+//! ```
+
+use std::f64::consts::PI;
+
+use crate::buffer::Buffer;
+use crate::sample::Sample;
+
+/// A window function.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+    BlackmanHarris,
+    FlatTop,
+    /// A Kaiser window, where `beta` trades main lobe width for side lobe attenuation.
+    /// A `beta` of 0 is a rectangular window, a `beta` of about 8.6 resembles Blackman.
+    Kaiser {
+        beta: f64,
+    },
+}
+
+impl Window {
+    /// Returns the value of the symmetric window of the given length at the given index.
+    pub fn value(&self, index: usize, length: usize) -> f64 {
+        if length <= 1 {
+            return 1.0;
+        }
+        self.value_at(index as f64 / (length - 1) as f64)
+    }
+
+    /// Returns the value of the periodic window of the given length at the given index.
+    /// A periodic window is a symmetric window of `length + 1` with the last sample left out,
+    /// which makes overlapping windows add up correctly.
+    pub fn periodic_value(&self, index: usize, length: usize) -> f64 {
+        self.value_at(index as f64 / length as f64)
+    }
+
+    /// Fills the given slice with the symmetric window, which is what filter design needs.
+    pub fn fill<T: Sample>(&self, output: &mut [T]) {
+        let length = output.len();
+        for (index, value) in output.iter_mut().enumerate() {
+            *value = T::from_f64(self.value(index, length));
+        }
+    }
+
+    /// Fills the given slice with the periodic window, which is what FFT analysis needs.
+    pub fn fill_periodic<T: Sample>(&self, output: &mut [T]) {
+        let length = output.len();
+        for (index, value) in output.iter_mut().enumerate() {
+            *value = T::from_f64(self.periodic_value(index, length));
+        }
+    }
+
+    /// Multiplies every channel of the buffer with the periodic window.
+    pub fn apply<T: Sample>(&self, buffer: &mut Buffer<T>) {
+        let length = buffer.num_samples().as_usize();
+        for channel in buffer.iter_chans_mut() {
+            for (index, sample) in channel.iter_mut().enumerate() {
+                let value = sample.to_f64() * self.periodic_value(index, length);
+                *sample = T::from_f64(value);
+            }
+        }
+    }
+
+    /// Returns the coherent gain of the periodic window of the given length, which is the
+    /// factor by which the amplitude of a sinusoid is reduced by windowing.
+    /// Divide measured amplitudes by this factor to correct for it.
+    pub fn coherent_gain(&self, length: usize) -> f64 {
+        let sum: f64 = (0..length).map(|n| self.periodic_value(n, length)).sum();
+        sum / length as f64
+    }
+
+    /// Returns the equivalent noise bandwidth (ENBW) of the periodic window of the given length,
+    /// in bins. Divide measured noise powers by this factor to correct for it.
+    pub fn equivalent_noise_bandwidth(&self, length: usize) -> f64 {
+        let (sum, sum_of_squares) = (0..length)
+            .map(|n| self.periodic_value(n, length))
+            .fold((0.0, 0.0), |(sum, squares), w| (sum + w, squares + w * w));
+        length as f64 * sum_of_squares / (sum * sum)
+    }
+
+    /// Evaluates the window at a position between 0 (start) and 1 (end).
+    fn value_at(&self, position: f64) -> f64 {
+        let x = 2.0 * PI * position;
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5 * x.cos(),
+            Window::Hamming => 0.54 - 0.46 * x.cos(),
+            Window::Blackman => cosine_sum(&[0.42, 0.5, 0.08], x),
+            Window::BlackmanHarris => cosine_sum(&[0.35875, 0.48829, 0.14128, 0.01168], x),
+            Window::FlatTop => cosine_sum(
+                &[
+                    0.215_578_95,
+                    0.416_631_58,
+                    0.277_263_158,
+                    0.083_578_947,
+                    0.006_947_368,
+                ],
+                x,
+            ),
+            Window::Kaiser { beta } => {
+                let t = 2.0 * position - 1.0;
+                bessel_i0(beta * (1.0 - t * t).max(0.0).sqrt()) / bessel_i0(*beta)
+            }
+        }
+    }
+}
+
+/// Evaluates `a0 - a1 cos(x) + a2 cos(2x) - a3 cos(3x) + ...`.
+fn cosine_sum(coefficients: &[f64], x: f64) -> f64 {
+    coefficients
+        .iter()
+        .enumerate()
+        .map(|(k, a)| {
+            let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+            sign * a * (k as f64 * x).cos()
+        })
+        .sum()
+}
+
+/// The zeroth order modified Bessel function of the first kind, used by the Kaiser window.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x = x / 2.0;
+    for k in 1..50 {
+        term *= half_x / k as f64;
+        sum += term * term;
+        if term * term < sum * 1e-17 {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::units::{Channels, Samples};
+
+    fn rounded(value: f64) -> f64 {
+        (value * 100.0).round() / 100.0
+    }
+
+    #[test_case(Window::Rectangular => 1.0; "rectangular")]
+    #[test_case(Window::Hann => 0.5; "hann")]
+    #[test_case(Window::Hamming => 0.54; "hamming")]
+    #[test_case(Window::Blackman => 0.42; "blackman")]
+    #[test_case(Window::BlackmanHarris => 0.36; "blackman harris")]
+    #[test_case(Window::FlatTop => 0.22; "flat top")]
+    fn coherent_gain(window: Window) -> f64 {
+        rounded(window.coherent_gain(4096))
+    }
+
+    #[test_case(Window::Rectangular => 1.0; "rectangular")]
+    #[test_case(Window::Hann => 1.5; "hann")]
+    #[test_case(Window::Hamming => 1.36; "hamming")]
+    #[test_case(Window::BlackmanHarris => 2.0; "blackman harris")]
+    #[test_case(Window::FlatTop => 3.77; "flat top")]
+    fn equivalent_noise_bandwidth(window: Window) -> f64 {
+        rounded(window.equivalent_noise_bandwidth(4096))
+    }
+
+    #[test]
+    fn symmetric_window_is_symmetric() {
+        let mut window = [0.0_f64; 9];
+        Window::Kaiser { beta: 6.0 }.fill(&mut window);
+
+        for index in 0..9 {
+            assert!((window[index] - window[8 - index]).abs() < 1e-12);
+        }
+        assert_eq!(window[4], 1.0);
+    }
+
+    #[test]
+    fn kaiser_with_zero_beta_is_rectangular() {
+        let window = Window::Kaiser { beta: 0.0 };
+        assert!((0..16).all(|n| window.value(n, 16) == 1.0));
+    }
+
+    #[test]
+    fn periodic_hann_windows_overlap_add_to_one() {
+        let mut window = [0.0_f64; 16];
+        Window::Hann.fill_periodic(&mut window);
+
+        for index in 0..8 {
+            assert!((window[index] + window[index + 8] - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn apply_windows_every_channel() {
+        let mut buffer = Buffer::<f32>::allocate(Channels::from(2), Samples::from(4));
+        buffer.map_samples(|_| 1.0);
+
+        Window::Hann.apply(&mut buffer);
+
+        assert_eq!(buffer.chan(0), &[0.0, 0.5, 1.0, 0.5]);
+        assert_eq!(buffer.chan(1), buffer.chan(0));
+    }
+}